@@ -0,0 +1,153 @@
+// Throughput of the packet loop: the packets of a file counted with --no-decode, and decoded for
+// comparison. Set VIDEO_ANALYZER_BENCH_INPUT to measure a file of your own, such as a long
+// MPEG-TS; without it the self-test's 10 second MPEG-4 pattern is synthesized.
+//
+// The ts_500k group reads a generated MPEG-TS of TS_PACKETS video packets, once with a packet
+// allocated per read as ictx.packets() does, once into a single recycled packet as the demux
+// thread does, and analyzes it with --no-decode.
+
+extern crate ffmpeg_next as ffmpeg;
+
+use std::path::{Path, PathBuf};
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use ffmpeg::format::Pixel;
+use ffmpeg::Rational;
+use video_analyzer::{analyze, AnalyzeOptions};
+
+// The generated transport stream: one short MPEG-2 GOP of small frames, repeated with increasing
+// timestamps until it holds TS_PACKETS packets.
+const TS_PACKETS: i64 = 500_000;
+const TS_SIZE: u32 = 32;
+const TS_FPS: i32 = 25;
+const TS_GOP: u32 = 25;
+
+fn packets(c: &mut Criterion) {
+  ffmpeg::init().unwrap();
+  let (path, synthesized) = match std::env::var_os("VIDEO_ANALYZER_BENCH_INPUT") {
+    Some(path) => (PathBuf::from(path), false),
+    None => {
+      let path = std::env::temp_dir().join(format!("video_analyzer_bench_{}.mp4", std::process::id()));
+      video_analyzer::selftest::synthesize(&path).unwrap();
+      (path, true)
+    }
+  };
+
+  let mut group = c.benchmark_group("packets");
+  group.sample_size(10);
+  let no_decode = AnalyzeOptions { no_decode: true, ..AnalyzeOptions::default() };
+  group.bench_function("no_decode", |b| b.iter(|| analyze(&path, &no_decode).unwrap()));
+  let decode = AnalyzeOptions::default();
+  group.bench_function("decode", |b| b.iter(|| analyze(&path, &decode).unwrap()));
+  group.finish();
+  if synthesized {
+    let _ = std::fs::remove_file(&path);
+  }
+}
+
+// Move every packet the encoder has ready into `gop`, with whether each is a key frame.
+fn drain(encoder: &mut ffmpeg::encoder::Video, gop: &mut Vec<(Vec<u8>, bool)>) {
+  let mut packet = ffmpeg::Packet::empty();
+  while encoder.receive_packet(&mut packet).is_ok() {
+    gop.push((packet.data().unwrap_or_default().to_vec(), packet.is_key()));
+  }
+}
+
+// Encode one GOP of flat frames and keep its packets.
+fn encode_gop(encoder: &mut ffmpeg::encoder::Video) -> Result<Vec<(Vec<u8>, bool)>, ffmpeg::Error> {
+  let mut gop = Vec::new();
+  for index in 0..TS_GOP as i64 {
+    let mut frame = ffmpeg::util::frame::video::Video::new(Pixel::YUV420P, TS_SIZE, TS_SIZE);
+    for plane in 0..3 {
+      frame.data_mut(plane).fill(128);
+    }
+    frame.set_pts(Some(index));
+    encoder.send_frame(&frame)?;
+    drain(encoder, &mut gop);
+  }
+  encoder.send_eof()?;
+  drain(encoder, &mut gop);
+  Ok(gop)
+}
+
+// Write the GOP over and over into an MPEG-TS until it holds TS_PACKETS packets.
+fn write_ts(path: &Path) -> Result<(), ffmpeg::Error> {
+  let codec = ffmpeg::encoder::find(ffmpeg::codec::Id::MPEG2VIDEO).ok_or(ffmpeg::Error::EncoderNotFound)?;
+  let mut octx = ffmpeg::format::output(&path)?;
+  let mut encoder = ffmpeg::codec::context::Context::new().encoder().video()?;
+  encoder.set_width(TS_SIZE);
+  encoder.set_height(TS_SIZE);
+  encoder.set_format(Pixel::YUV420P);
+  encoder.set_time_base(Rational::new(1, TS_FPS));
+  encoder.set_frame_rate(Some(Rational::new(TS_FPS, 1)));
+  encoder.set_gop(TS_GOP);
+  encoder.set_max_b_frames(0);
+  let mut encoder = encoder.open_as(codec)?;
+
+  let mut stream = octx.add_stream(codec)?;
+  stream.set_parameters(&encoder);
+  stream.set_time_base(Rational::new(1, TS_FPS));
+  octx.write_header()?;
+  let stream_time_base = octx.stream(0).map_or(Rational::new(1, TS_FPS), |s| s.time_base());
+
+  let gop = encode_gop(&mut encoder)?;
+  for index in 0..TS_PACKETS {
+    let (data, key) = &gop[index as usize % gop.len()];
+    let mut packet = ffmpeg::Packet::copy(data);
+    packet.set_stream(0);
+    packet.set_pts(Some(index));
+    packet.set_dts(Some(index));
+    if *key {
+      packet.set_flags(ffmpeg::packet::Flags::KEY);
+    }
+    packet.rescale_ts(Rational::new(1, TS_FPS), stream_time_base);
+    packet.write(&mut octx)?;
+  }
+  octx.write_trailer()
+}
+
+// Count the packets and their bytes, with a packet allocated for every read.
+fn read_allocated(path: &Path) -> (u64, usize) {
+  let mut ictx = ffmpeg::format::input(&path).unwrap();
+  ictx.packets().fold((0, 0), |(count, bytes), (_, packet)| (count + 1, bytes + packet.size()))
+}
+
+// Count the packets and their bytes, reading every one into the same packet.
+fn read_recycled(path: &Path) -> (u64, usize) {
+  let mut ictx = ffmpeg::format::input(&path).unwrap();
+  let mut packet = ffmpeg::Packet::empty();
+  let (mut count, mut bytes) = (0, 0);
+  loop {
+    unsafe {
+      ffmpeg::ffi::av_packet_unref(ffmpeg::packet::Mut::as_mut_ptr(&mut packet));
+    }
+    match packet.read(&mut ictx) {
+      Ok(()) => {
+        count += 1;
+        bytes += packet.size();
+      }
+      Err(ffmpeg::Error::Eof) => return (count, bytes),
+      Err(_) => {}
+    }
+  }
+}
+
+fn transport_stream(c: &mut Criterion) {
+  ffmpeg::init().unwrap();
+  let path = std::env::temp_dir().join(format!("video_analyzer_bench_{}.ts", std::process::id()));
+  write_ts(&path).unwrap();
+  // Both loops must see the same packets for the comparison to mean anything.
+  assert_eq!(read_allocated(&path), read_recycled(&path));
+
+  let mut group = c.benchmark_group("ts_500k");
+  group.sample_size(10);
+  group.bench_function("allocated_per_packet", |b| b.iter(|| read_allocated(&path)));
+  group.bench_function("recycled_packet", |b| b.iter(|| read_recycled(&path)));
+  let no_decode = AnalyzeOptions { no_decode: true, ..AnalyzeOptions::default() };
+  group.bench_function("no_decode", |b| b.iter(|| analyze(&path, &no_decode).unwrap()));
+  group.finish();
+  let _ = std::fs::remove_file(&path);
+}
+
+criterion_group!(benches, packets, transport_stream);
+criterion_main!(benches);
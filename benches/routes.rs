@@ -0,0 +1,97 @@
+// Cost of finding a packet's per-stream state, the decode loop's step before any work on the
+// packet: the route table indexed by stream index that decode_packets builds with routes(),
+// against the search through each list of tracked streams that it replaced. Both sides are a
+// model of the loop, with the same stream layout and a counter as the state, so only the lookup
+// differs.
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+// A broadcast-like layout: the video first, then audio, subtitle and data streams, plus one
+// stream that isn't tracked.
+const VIDEO: usize = 0;
+const AUDIO: [usize; 8] = [1, 2, 3, 4, 5, 6, 7, 8];
+const SUBTITLES: [usize; 4] = [9, 10, 11, 12];
+const DATA: [usize; 2] = [13, 14];
+const STREAMS: usize = 16;
+const PACKETS: usize = 500_000;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Route {
+  Skip,
+  Video,
+  Audio(usize),
+  Subtitle(usize),
+  Data(usize),
+}
+
+struct State {
+  index: usize,
+  packets: u64,
+}
+
+fn states(indexes: &[usize]) -> Vec<State> {
+  indexes.iter().map(|&index| State { index, packets: 0 }).collect()
+}
+
+// The stream index of every packet: mostly video and audio, as a TS interleaves them.
+fn packet_streams() -> Vec<usize> {
+  (0..PACKETS).map(|i| match i % 16 {
+    0..=5 => VIDEO,
+    6..=12 => AUDIO[i % AUDIO.len()],
+    13 => SUBTITLES[i % SUBTITLES.len()],
+    14 => DATA[i % DATA.len()],
+    _ => STREAMS - 1,
+  }).collect()
+}
+
+// The previous loop: every list is searched for the stream in turn.
+fn linear(packets: &[usize]) -> u64 {
+  let (mut audio, mut subtitles, mut data) = (states(&AUDIO), states(&SUBTITLES), states(&DATA));
+  let mut video = 0;
+  for &index in packets {
+    if let Some(state) = audio.iter_mut().find(|s| s.index == index) {
+      state.packets += 1;
+    } else if let Some(state) = subtitles.iter_mut().find(|s| s.index == index) {
+      state.packets += 1;
+    } else if let Some(state) = data.iter_mut().find(|s| s.index == index) {
+      state.packets += 1;
+    } else if index == VIDEO {
+      video += 1;
+    }
+  }
+  video + [&audio, &subtitles, &data].iter().flat_map(|list| list.iter()).map(|s| s.packets).sum::<u64>()
+}
+
+// The route table: one index into a Vec built before the loop.
+fn routed(packets: &[usize]) -> u64 {
+  let (mut audio, mut subtitles, mut data) = (states(&AUDIO), states(&SUBTITLES), states(&DATA));
+  let mut routes = vec![Route::Skip; STREAMS];
+  audio.iter().enumerate().for_each(|(position, s)| routes[s.index] = Route::Audio(position));
+  subtitles.iter().enumerate().for_each(|(position, s)| routes[s.index] = Route::Subtitle(position));
+  data.iter().enumerate().for_each(|(position, s)| routes[s.index] = Route::Data(position));
+  routes[VIDEO] = Route::Video;
+  let mut video = 0;
+  for &index in packets {
+    match routes.get(index).copied().unwrap_or(Route::Skip) {
+      Route::Video => video += 1,
+      Route::Audio(position) => audio[position].packets += 1,
+      Route::Subtitle(position) => subtitles[position].packets += 1,
+      Route::Data(position) => data[position].packets += 1,
+      Route::Skip => {}
+    }
+  }
+  video + [&audio, &subtitles, &data].iter().flat_map(|list| list.iter()).map(|s| s.packets).sum::<u64>()
+}
+
+fn routes(c: &mut Criterion) {
+  let packets = packet_streams();
+  assert_eq!(linear(&packets), routed(&packets));
+
+  let mut group = c.benchmark_group("routes");
+  group.bench_function("linear_search", |b| b.iter(|| linear(black_box(&packets))));
+  group.bench_function("route_table", |b| b.iter(|| routed(black_box(&packets))));
+  group.finish();
+}
+
+criterion_group!(benches, routes);
+criterion_main!(benches);
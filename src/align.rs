@@ -0,0 +1,168 @@
+// `align-check <RENDITION>...` subcommand: check that the renditions of an adaptive streaming
+// ladder have their key frames at the same times, without which a player switching between them
+// stutters. Only the packets are read; key frames are the packets flagged as such.
+
+use ffmpeg::{Discard, Rational, Rescale};
+
+use crate::{format_signed_millis, format_with_commas, network, streams, AnalyzeError, BLUE_BOLD, DEFAULT_NETWORK_TIMEOUT_SECONDS, RESET};
+
+// Misaligned key frames listed per rendition.
+const MAX_EXAMPLES: usize = 5;
+
+// Tolerance when no rendition declares a frame rate: half a frame at 25 fps.
+const FALLBACK_TOLERANCE_US: i64 = 20_000;
+
+const MICROSECONDS: Rational = Rational(1, 1_000_000);
+
+// One rung of the ladder: its main video stream and the presentation times of its key frames.
+struct Rendition {
+  path: String,
+  stream_index: usize,
+  width: u32,
+  height: u32,
+  frame_rate: Option<Rational>,
+  // Sorted, in microseconds.
+  keyframes_us: Vec<i64>,
+}
+
+impl Rendition {
+  fn read(path: &str) -> Result<Self, AnalyzeError> {
+    let mut ictx = network::open(path, DEFAULT_NETWORK_TIMEOUT_SECONDS).map_err(AnalyzeError::OpenError)?;
+    let stream_index = streams::primary_index(&streams::video_tracks(&ictx)).ok_or(AnalyzeError::NoVideoStreamError)?;
+    let stream = ictx.stream(stream_index).ok_or(AnalyzeError::NoVideoStreamError)?;
+    let time_base = stream.time_base();
+    let frame_rate = [stream.avg_frame_rate(), stream.rate()].into_iter().find(|rate| rate.numerator() > 0 && rate.denominator() > 0);
+    let (width, height) = unsafe {
+      let p = stream.parameters().as_ptr();
+      ((*p).width.max(0) as u32, (*p).height.max(0) as u32)
+    };
+    // The other streams are left unread by the demuxer.
+    for index in (0..ictx.nb_streams() as usize).filter(|&index| index != stream_index) {
+      if let Some(mut stream) = ictx.stream_mut(index) {
+        unsafe { (*stream.as_mut_ptr()).discard = Discard::All.into() };
+      }
+    }
+    let mut keyframes_us = Vec::new();
+    for (stream, packet) in ictx.packets() {
+      if stream.index() != stream_index || !packet.is_key() {
+        continue;
+      }
+      if let Some(ts) = packet.pts().or(packet.dts()) {
+        keyframes_us.push(ts.rescale(time_base, MICROSECONDS));
+      }
+    }
+    keyframes_us.sort_unstable();
+    Ok(Rendition { path: path.to_string(), stream_index, width, height, frame_rate, keyframes_us })
+  }
+
+  // The key frame closest to `time`, in microseconds.
+  fn nearest(&self, time: i64) -> Option<i64> {
+    let at = self.keyframes_us.partition_point(|&ts| ts < time);
+    [at.checked_sub(1), Some(at)].into_iter().flatten()
+      .filter_map(|i| self.keyframes_us.get(i).copied())
+      .min_by_key(|ts| ts.abs_diff(time))
+  }
+}
+
+// A key frame of one rendition without a partner within the tolerance in the other.
+struct Misalignment {
+  // Key frame time and the rendition it belongs to.
+  time_us: i64,
+  rendition: usize,
+  // Closest key frame of the other rendition of the pair.
+  nearest_us: Option<i64>,
+}
+
+fn describe_rate(rate: Option<Rational>) -> String {
+  rate.map_or_else(|| "unknown fps".to_string(), |rate| format!("{:.3} fps", f64::from(rate)))
+}
+
+fn describe_time(us: i64) -> String {
+  format_signed_millis(us / 1000)
+}
+
+// Whether key frames of the two rates can keep falling on the same times: one rate has to be an
+// integer multiple of the other (60 and 30, 50 and 25), otherwise the frame times drift apart.
+fn rates_compatible(a: Rational, b: Rational) -> bool {
+  let (x, y) = (a.numerator() as i64 * b.denominator() as i64, a.denominator() as i64 * b.numerator() as i64);
+  x > 0 && y > 0 && (x % y == 0 || y % x == 0)
+}
+
+// Half the frame duration of the fastest rendition.
+fn default_tolerance_us(renditions: &[Rendition]) -> i64 {
+  renditions
+    .iter()
+    .filter_map(|r| r.frame_rate)
+    .map(|rate| 500_000 * rate.denominator() as i64 / rate.numerator() as i64)
+    .min()
+    .unwrap_or(FALLBACK_TOLERANCE_US)
+}
+
+// Key frames of `a` with no key frame of `b` within the tolerance, attributed to `rendition`.
+fn unmatched(a: &Rendition, b: &Rendition, rendition: usize, tolerance_us: i64) -> Vec<Misalignment> {
+  a.keyframes_us
+    .iter()
+    .filter_map(|&time_us| {
+      let nearest_us = b.nearest(time_us);
+      let aligned = nearest_us.is_some_and(|ts| ts.abs_diff(time_us) <= tolerance_us as u64);
+      (!aligned).then_some(Misalignment { time_us, rendition, nearest_us })
+    })
+    .collect()
+}
+
+// Check the key frames of every rendition against the first one and print the result. Returns
+// whether they are all aligned.
+pub fn run(paths: &[String], tolerance_ms: Option<f64>) -> Result<bool, AnalyzeError> {
+  if paths.len() < 2 {
+    return Err(AnalyzeError::InvalidArgument("align-check requires at least two renditions".to_string()));
+  }
+  let renditions = paths.iter().map(|path| Rendition::read(path)).collect::<Result<Vec<_>, _>>()?;
+  println!("{}Key frame alignment - {}", BLUE_BOLD, RESET);
+  for (number, rendition) in renditions.iter().enumerate() {
+    println!("Rendition {}: {} (stream #{}, {}x{}, {}): {} key frames", number, rendition.path, rendition.stream_index,
+      rendition.width, rendition.height, describe_rate(rendition.frame_rate),
+      format_with_commas(rendition.keyframes_us.len() as i64));
+  }
+
+  // Mismatched frame rates misalign by construction; listing the points would only repeat that.
+  let rates: Vec<Rational> = renditions.iter().filter_map(|r| r.frame_rate).collect();
+  if rates.iter().any(|&a| rates.iter().any(|&b| !rates_compatible(a, b))) {
+    let listed: Vec<String> = renditions.iter().enumerate()
+      .map(|(number, r)| format!("{} in rendition {}", describe_rate(r.frame_rate), number)).collect();
+    println!("Frame rates: {} - inherently unalignable, the rates aren't integer multiples of each other so their \
+      frame times drift apart", listed.join(", "));
+    println!("Renditions NOT aligned.");
+    return Ok(false);
+  }
+
+  let tolerance_us = tolerance_ms.map_or_else(|| default_tolerance_us(&renditions), |ms| (ms * 1000.0).round() as i64);
+  println!("Tolerance: {:.1} ms{}", tolerance_us as f64 / 1000.0,
+    if tolerance_ms.is_some() { "" } else { " (half a frame of the fastest rendition)" });
+
+  let reference = &renditions[0];
+  let mut aligned = true;
+  for (number, rendition) in renditions.iter().enumerate().skip(1) {
+    let mut misaligned = unmatched(reference, rendition, 0, tolerance_us);
+    misaligned.extend(unmatched(rendition, reference, number, tolerance_us));
+    misaligned.sort_by_key(|m| m.time_us);
+    if misaligned.is_empty() {
+      println!("Rendition {} against 0: aligned", number);
+      continue;
+    }
+    aligned = false;
+    println!("Rendition {} against 0: {} misaligned key frames", number, format_with_commas(misaligned.len() as i64));
+    for m in misaligned.iter().take(MAX_EXAMPLES) {
+      let other = if m.rendition == 0 { number } else { 0 };
+      let nearest = m.nearest_us.map_or_else(|| "none".to_string(), |ts| {
+        format!("{} ({:+.1} ms)", describe_time(ts), (ts - m.time_us) as f64 / 1000.0)
+      });
+      println!("  {} in rendition {}: nearest key frame of rendition {} at {}", describe_time(m.time_us), m.rendition,
+        other, nearest);
+    }
+    if misaligned.len() > MAX_EXAMPLES {
+      println!("  ... and {} more", format_with_commas((misaligned.len() - MAX_EXAMPLES) as i64));
+    }
+  }
+  println!("{}", if aligned { "Renditions aligned." } else { "Renditions NOT aligned." });
+  Ok(aligned)
+}
@@ -0,0 +1,101 @@
+// Sample and display aspect ratio of the video: the SAR the stream parameters declare, the one the
+// decoded frames carry and the DAR they give the picture. Anamorphic (non-square pixel) content is
+// called out, and so is a frame SAR contradicting the stream's, which players resolve differently.
+
+use ffmpeg::Rational;
+
+use crate::findings::{self, Finding};
+use crate::report::AspectRatioReport;
+
+// A sample aspect ratio in lowest terms; None for FFmpeg's 0/1 "unknown".
+pub fn known(sar: Rational) -> Option<(i64, i64)> {
+  (sar.numerator() > 0 && sar.denominator() > 0).then(|| reduce(sar.numerator() as i64, sar.denominator() as i64))
+}
+
+fn reduce(numerator: i64, denominator: i64) -> (i64, i64) {
+  let (mut a, mut b) = (numerator, denominator);
+  while b != 0 {
+    (a, b) = (b, a % b);
+  }
+  if a == 0 { (numerator, denominator) } else { (numerator / a, denominator / a) }
+}
+
+pub fn describe((numerator, denominator): (i64, i64)) -> String {
+  format!("{}:{}", numerator, denominator)
+}
+
+// The aspect ratios of a stream, from the declared SAR and the first decoded frame's.
+pub struct AspectRatio {
+  pub stream_sar: Option<(i64, i64)>,
+  pub frame_sar: Option<(i64, i64)>,
+  pub frames_decoded: bool,
+  // Width and height the DAR is computed from: the decoded frames', or the declared ones.
+  pub size: (u32, u32),
+}
+
+impl AspectRatio {
+  pub fn new(parameters: &ffmpeg::codec::Parameters, frame_sar: Option<Rational>, size: (u32, u32)) -> Self {
+    let stream_sar = known(Rational::from(unsafe { (*parameters.as_ptr()).sample_aspect_ratio }));
+    AspectRatio { stream_sar, frame_sar: frame_sar.and_then(known), frames_decoded: frame_sar.is_some(), size }
+  }
+
+  // The SAR the picture is displayed with: the frames', which FFmpeg-based players use, then the
+  // stream's. None when neither is known and square pixels are assumed.
+  pub fn sar(&self) -> Option<(i64, i64)> {
+    self.frame_sar.or(self.stream_sar)
+  }
+
+  pub fn dar(&self) -> Option<(i64, i64)> {
+    let (numerator, denominator) = self.sar().unwrap_or((1, 1));
+    let (width, height) = self.size;
+    (width > 0 && height > 0).then(|| reduce(width as i64 * numerator, height as i64 * denominator))
+  }
+
+  pub fn anamorphic(&self) -> bool {
+    self.sar().is_some_and(|(numerator, denominator)| numerator != denominator)
+  }
+
+  pub fn mismatch(&self) -> bool {
+    matches!((self.stream_sar, self.frame_sar), (Some(stream), Some(frame)) if stream != frame)
+  }
+}
+
+fn describe_sar(sar: Option<(i64, i64)>) -> String {
+  sar.map_or_else(|| "1:1 (assumed, unspecified)".to_string(), describe)
+}
+
+pub fn to_report(aspect: &AspectRatio) -> AspectRatioReport {
+  AspectRatioReport {
+    stream_sar: aspect.stream_sar.map(describe),
+    frame_sar: aspect.frame_sar.map(describe),
+    sar_assumed: aspect.sar().is_none(),
+    dar: aspect.dar().map(describe),
+    anamorphic: aspect.anamorphic(),
+  }
+}
+
+// Print the aspect ratio lines of the dimensions section and return a finding when the frames'
+// SAR contradicts the stream's.
+pub fn report(aspect: &AspectRatio) -> Option<Finding> {
+  out!("Sample aspect ratio (stream): {}", describe_sar(aspect.stream_sar));
+  if aspect.frames_decoded {
+    out!("Sample aspect ratio (frames): {}", describe_sar(aspect.frame_sar));
+  } else {
+    out!("Sample aspect ratio (frames): unknown (no frames decoded)");
+  }
+  if let Some(dar) = aspect.dar() {
+    out!("Display aspect ratio: {} ({}x{} with SAR {})", describe(dar), aspect.size.0, aspect.size.1,
+      describe_sar(aspect.sar()));
+  }
+  match aspect.sar().filter(|_| aspect.anamorphic()) {
+    Some(sar) => out!("Anamorphic: yes (SAR {})", describe(sar)),
+    None => out!("Anamorphic: no"),
+  }
+  if !aspect.mismatch() {
+    return None;
+  }
+  let message = format!("stream parameters declare SAR {} but the decoded frames carry SAR {}",
+    describe_sar(aspect.stream_sar), describe_sar(aspect.frame_sar));
+  out!("Aspect ratio mismatch: {}", message);
+  Some(Finding::new(&findings::SAR_MISMATCH, message))
+}
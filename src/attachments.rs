@@ -0,0 +1,81 @@
+// Attachments of the container: the fonts Matroska files carry for their ASS subtitles, and the
+// cover art MP3 and MP4 files store as an attached picture, a video stream with a single frame.
+// Each is listed with its file name and MIME type, as tagged, its codec and its size. Attached
+// pictures are never the analyzed video stream (see streams::primary_index).
+
+use ffmpeg::format::stream::Disposition;
+use ffmpeg::media::Type;
+
+use crate::{format_bytes, BLUE_BOLD, RESET};
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+  AttachedPicture,
+  Font,
+  File,
+}
+
+impl Kind {
+  pub fn name(self) -> &'static str {
+    match self {
+      Kind::AttachedPicture => "attached picture",
+      Kind::Font => "font",
+      Kind::File => "file",
+    }
+  }
+}
+
+pub struct Attachment {
+  pub index: usize,
+  pub kind: Kind,
+  pub codec: String,
+  pub filename: Option<String>,
+  pub mimetype: Option<String>,
+  pub size_bytes: u64,
+}
+
+fn is_font(codec: &str, mimetype: Option<&str>) -> bool {
+  matches!(codec, "ttf" | "otf")
+    || mimetype.is_some_and(|mime| mime.starts_with("font/") || mime.contains("truetype") || mime.contains("opentype"))
+}
+
+pub fn read(ictx: &ffmpeg::format::context::Input) -> Vec<Attachment> {
+  ictx.streams()
+    .filter_map(|stream| {
+      let parameters = stream.parameters();
+      let attached_pic = stream.disposition().contains(Disposition::ATTACHED_PIC);
+      if !attached_pic && parameters.medium() != Type::Attachment {
+        return None;
+      }
+      let codec = parameters.id().name().to_string();
+      let metadata = stream.metadata();
+      let filename = metadata.get("filename").map(str::to_string);
+      let mimetype = metadata.get("mimetype").map(str::to_string);
+      // Attachment data is the stream's extradata; an attached picture is the stream's one packet.
+      let size = unsafe {
+        if attached_pic { (*stream.as_ptr()).attached_pic.size } else { (*parameters.as_ptr()).extradata_size }
+      };
+      let kind = if attached_pic {
+        Kind::AttachedPicture
+      } else if is_font(&codec, mimetype.as_deref()) {
+        Kind::Font
+      } else {
+        Kind::File
+      };
+      Some(Attachment { index: stream.index(), kind, codec, filename, mimetype, size_bytes: size.max(0) as u64 })
+    })
+    .collect()
+}
+
+// Print the attachments; nothing for a file without any.
+pub fn report(attachments: &[Attachment]) {
+  if attachments.is_empty() {
+    return;
+  }
+  out!("{}{}Attachments - {}", RESET, BLUE_BOLD, RESET);
+  for attachment in attachments {
+    out!("#{}: {} ({}), {}, {}, {}", attachment.index, attachment.kind.name(), attachment.codec,
+      attachment.filename.as_deref().unwrap_or("no file name"), attachment.mimetype.as_deref().unwrap_or("no MIME type"),
+      format_bytes(attachment.size_bytes));
+  }
+}
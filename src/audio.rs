@@ -0,0 +1,406 @@
+// Audio streams: what every audio track declares and what decoding it measures. The decoded
+// duration comes from counting samples, the way video frames are counted. Every decoded frame is
+// also checked against the declared sample rate and channel layout, which a bad remux can leave
+// behind (48 kHz declared, 44.1 kHz decoded). The span of each track's packet timestamps is
+// compared with the span of the video frames, so a track that ends before the video fails the
+// A/V drift check; spans rather than end times, so start offsets don't count as drift. The start
+// of each track, after the packets an edit list discards and the priming samples it skips, is
+// compared with the first video frame for lipsync offsets.
+
+use std::ffi::CStr;
+
+use ffmpeg::codec::packet::side_data;
+use ffmpeg::ffi::{av_channel_layout_describe, AVChannelLayout, AVChannelOrder, AV_PKT_FLAG_DISCARD};
+use ffmpeg::format::Sample;
+use ffmpeg::media::Type;
+use ffmpeg::{Rational, Rescale};
+
+use crate::findings::{self, Finding};
+use crate::loudness;
+use crate::report::{AudioStreamReport, LoudnessReport};
+use crate::timestamp::{self, Timestamp};
+use crate::{format_optional_millis, format_signed_millis, format_with_commas, millis_to_clock_format, BLUE_BOLD, RESET};
+
+// Name of a channel layout, e.g. "stereo" or "5.1(side)". None when the channel order is
+// unspecified, where only the channel count is known.
+fn layout_name(layout: &AVChannelLayout) -> Option<String> {
+  if layout.order == AVChannelOrder::AV_CHANNEL_ORDER_UNSPEC {
+    return None;
+  }
+  let mut name = [0; 64];
+  if unsafe { av_channel_layout_describe(layout, name.as_mut_ptr(), name.len()) } < 0 {
+    return None;
+  }
+  Some(unsafe { CStr::from_ptr(name.as_ptr()) }.to_string_lossy().into_owned())
+}
+
+// Sample rate and channels, as declared by the stream parameters or carried by a decoded frame.
+#[derive(Clone, PartialEq)]
+pub struct Format {
+  pub sample_rate: u32,
+  pub channels: i32,
+  pub layout: Option<String>,
+}
+
+impl Format {
+  fn of_frame(frame: &ffmpeg::util::frame::audio::Audio) -> Self {
+    let layout = unsafe { &(*frame.as_ptr()).ch_layout };
+    Format { sample_rate: frame.rate(), channels: layout.nb_channels, layout: layout_name(layout) }
+  }
+
+  // Layouts are only compared when both sides name one.
+  fn matches(&self, other: &Format) -> bool {
+    self.sample_rate == other.sample_rate && self.channels == other.channels
+      && (self.layout.is_none() || other.layout.is_none() || self.layout == other.layout)
+  }
+
+  // "48000 Hz, 2 channels (stereo)", or "(unknown layout)".
+  pub fn describe(&self) -> String {
+    format!("{} Hz, {} channels ({})", self.sample_rate, self.channels,
+      self.layout.as_deref().unwrap_or("unknown layout"))
+  }
+}
+
+pub struct AudioStream {
+  pub index: usize,
+  pub codec: String,
+  pub sample_rate: u32,
+  pub channels: i32,
+  // Name of the declared layout; None when the stream doesn't say which channels it carries.
+  pub layout: Option<String>,
+  // Declared bit rate in bits per second, None when the stream has none.
+  pub bit_rate: Option<u64>,
+  // Name of the sample format the decoder outputs (fltp, s16, ...), None without a decoder.
+  pub sample_format: Option<&'static str>,
+  pub language: Option<String>,
+  pub time_base: Rational,
+  pub packets: u64,
+  // Lowest and highest packet presentation timestamps.
+  pub first_timestamp: Option<i64>,
+  pub last_timestamp: Option<i64>,
+  // Timestamp of the first sample presented: the first packet the edit list keeps, past the
+  // priming samples it says to skip. Packets discarded before it and the samples skipped.
+  pub presentation_start: Option<i64>,
+  pub discarded_packets: u64,
+  pub skipped_samples: u64,
+  pub samples: u64,
+  // Packets the decoder rejected; the count goes on past them.
+  pub decode_errors: u64,
+  // False when FFmpeg has no decoder for the codec; packets are still counted.
+  pub decodable: bool,
+  // EBU R128 measurement of the decoded samples, with --loudness.
+  pub loudness: Option<loudness::Meter>,
+  // Decoded frames whose sample rate or channels differ from the declared ones, and the first of them.
+  pub mismatched_frames: u64,
+  pub first_mismatch: Option<Format>,
+  // Seconds of decoded audio, each frame at its own sample rate.
+  decoded_seconds: f64,
+  // False when only the packets are counted (--no-decode).
+  decode: bool,
+  decoder: Option<ffmpeg::decoder::Audio>,
+}
+
+impl AudioStream {
+  // Decoded duration from the sample count, None without a decoder.
+  pub fn decoded_duration_ms(&self) -> Option<u64> {
+    if !self.decode || !self.decodable {
+      return None;
+    }
+    Some((self.decoded_seconds * 1000.0) as u64)
+  }
+
+  // Span of the packet timestamps.
+  pub fn timestamp_span(&self) -> Option<Timestamp> {
+    let (first, last) = (self.first_timestamp?, self.last_timestamp?);
+    Some(Timestamp::new(last - first, self.time_base))
+  }
+
+  pub fn timestamp_span_ms(&self) -> Option<u64> {
+    self.timestamp_span()?.millis()
+  }
+
+  pub fn start_time(&self) -> Option<Timestamp> {
+    Some(Timestamp::new(self.presentation_start?, self.time_base))
+  }
+
+  pub fn presentation_start_ms(&self) -> Option<i64> {
+    self.start_time()?.signed_millis()
+  }
+
+  // Audio start minus video start: positive when the audio starts later.
+  pub fn start_offset_ms(&self, video_start_ms: Option<i64>) -> Option<i64> {
+    Some(self.presentation_start_ms()? - video_start_ms?)
+  }
+
+  // Whether the start was moved by an edit list or priming samples.
+  pub fn has_priming(&self) -> bool {
+    self.discarded_packets > 0 || self.skipped_samples > 0
+  }
+
+  // Find the presentation start among the first packets.
+  fn note_start(&mut self, packet: &ffmpeg::Packet) {
+    if packet.flags().bits() & AV_PKT_FLAG_DISCARD != 0 {
+      self.discarded_packets += 1;
+      return;
+    }
+    let Some(pts) = packet.pts() else {
+      return;
+    };
+    // AV_PKT_DATA_SKIP_SAMPLES starts with the little-endian number of samples to skip.
+    self.skipped_samples = packet.side_data()
+      .find(|data| data.kind() == side_data::Type::SkipSamples)
+      .and_then(|data| data.data().get(..4).map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as u64))
+      .unwrap_or(0);
+    let skipped = if self.sample_rate > 0 {
+      (self.skipped_samples as i64).rescale(Rational::new(1, self.sample_rate as i32), self.time_base)
+    } else {
+      0
+    };
+    self.presentation_start = Some(pts + skipped);
+  }
+
+  // Audio span minus video span: negative when the audio is shorter.
+  pub fn drift_ms(&self, video_span_ms: Option<u64>) -> Option<i64> {
+    Some(self.timestamp_span_ms()? as i64 - video_span_ms? as i64)
+  }
+
+  pub fn declared_format(&self) -> Format {
+    Format { sample_rate: self.sample_rate, channels: self.channels, layout: self.layout.clone() }
+  }
+
+  fn count(&mut self, frame: &ffmpeg::util::frame::audio::Audio) {
+    self.samples += frame.samples() as u64;
+    if frame.rate() > 0 {
+      self.decoded_seconds += frame.samples() as f64 / frame.rate() as f64;
+    }
+    let format = Format::of_frame(frame);
+    if !format.matches(&self.declared_format()) {
+      self.mismatched_frames += 1;
+      self.first_mismatch.get_or_insert(format);
+    }
+    if let Some(meter) = self.loudness.as_mut() {
+      meter.push(frame);
+    }
+  }
+
+  // Count a packet of this stream and the samples it decodes to.
+  pub fn decode(&mut self, packet: &ffmpeg::Packet, frame: &mut ffmpeg::util::frame::audio::Audio) {
+    self.packets += 1;
+    if let Some(ts) = packet.pts().or(packet.dts()) {
+      self.first_timestamp = Some(self.first_timestamp.map_or(ts, |t| t.min(ts)));
+      self.last_timestamp = Some(self.last_timestamp.map_or(ts, |t| t.max(ts)));
+    }
+    if self.presentation_start.is_none() {
+      self.note_start(packet);
+    }
+    // Taken while its frames are counted, and put back.
+    let Some(mut decoder) = self.decoder.take_if(|_| self.decode) else {
+      return;
+    };
+    if decoder.send_packet(packet).is_err() {
+      self.decode_errors += 1;
+    } else {
+      while decoder.receive_frame(frame).is_ok() {
+        self.count(frame);
+      }
+    }
+    self.decoder = Some(decoder);
+  }
+
+  // Drain the samples the decoder still holds at the end of the input and release it.
+  pub fn finish(&mut self, frame: &mut ffmpeg::util::frame::audio::Audio) {
+    if let Some(mut decoder) = self.decoder.take().filter(|_| self.decode) {
+      if decoder.send_eof().is_ok() {
+        while decoder.receive_frame(frame).is_ok() {
+          self.count(frame);
+        }
+      }
+    }
+  }
+}
+
+// Open a decoder for every audio stream of the input, with a loudness meter on each decodable one
+// when `measure_loudness` is set. Without `decode`, the decoders only report the sample format and
+// the packets are counted.
+pub fn open(ictx: &ffmpeg::format::context::Input, decode: bool, measure_loudness: bool) -> Vec<AudioStream> {
+  ictx
+    .streams()
+    .filter(|stream| stream.parameters().medium() == Type::Audio)
+    .map(|stream| {
+      let parameters = stream.parameters();
+      let (sample_rate, channels, layout, bit_rate) = unsafe {
+        let p = parameters.as_ptr();
+        ((*p).sample_rate.max(0) as u32, (*p).ch_layout.nb_channels, layout_name(&(*p).ch_layout), (*p).bit_rate)
+      };
+      let decoder = ffmpeg::codec::context::Context::from_parameters(parameters.clone())
+        .and_then(|context| context.decoder().audio())
+        .ok();
+      AudioStream {
+        index: stream.index(),
+        codec: parameters.id().name().to_string(),
+        sample_rate,
+        channels,
+        layout,
+        bit_rate: (bit_rate > 0).then_some(bit_rate as u64),
+        sample_format: decoder.as_ref().map(|decoder| decoder.format()).filter(|&format| format != Sample::None)
+          .map(|format| format.name()),
+        language: stream.metadata().get("language").map(str::to_string),
+        time_base: stream.time_base(),
+        packets: 0,
+        first_timestamp: None,
+        last_timestamp: None,
+        presentation_start: None,
+        discarded_packets: 0,
+        skipped_samples: 0,
+        samples: 0,
+        decode_errors: 0,
+        decodable: decoder.is_some(),
+        loudness: if decode && measure_loudness && decoder.is_some() { loudness::Meter::new() } else { None },
+        mismatched_frames: 0,
+        first_mismatch: None,
+        decoded_seconds: 0.0,
+        decode,
+        decoder,
+      }
+    })
+    .collect()
+}
+
+// The figures of one stream, with the DTS of its last packet and the span and start of the video.
+pub fn to_report(stream: &AudioStream, highest_dts: Option<i64>, video_span_ms: Option<u64>, video_start_ms: Option<i64>)
+  -> AudioStreamReport {
+  AudioStreamReport {
+    index: stream.index,
+    codec: stream.codec.clone(),
+    sample_rate: stream.sample_rate,
+    channels: stream.channels,
+    channel_layout: stream.layout.clone(),
+    bit_rate: stream.bit_rate,
+    sample_format: stream.sample_format,
+    language: stream.language.clone(),
+    packet_count: stream.packets,
+    mismatched_frames: stream.decoded_duration_ms().map(|_| stream.mismatched_frames),
+    decoded_format: stream.first_mismatch.as_ref().map(Format::describe),
+    decoded_duration_ms: stream.decoded_duration_ms(),
+    timestamp_span_ms: stream.timestamp_span_ms(),
+    timestamp_span_raw: stream.timestamp_span(),
+    drift_ms: stream.drift_ms(video_span_ms),
+    start_ms: stream.presentation_start_ms(),
+    start_raw: stream.start_time(),
+    start_offset_ms: stream.start_offset_ms(video_start_ms),
+    edit_discarded_packets: stream.discarded_packets,
+    priming_samples: stream.skipped_samples,
+    loudness: stream.loudness.as_ref().map(|meter| {
+      let loudness = meter.result();
+      LoudnessReport {
+        integrated_lufs: loudness.integrated,
+        loudness_range_lu: loudness.range,
+        true_peak_dbtp: loudness.true_peak,
+        error: meter.error.clone(),
+      }
+    }),
+    last_packet_ms: highest_dts.and_then(|dts| Timestamp::new(dts, stream.time_base).signed_millis()),
+    last_packet_raw: highest_dts.map(|dts| Timestamp::new(dts, stream.time_base)),
+  }
+}
+
+// Print the audio section: one line per stream with what it declares and what was measured, then
+// the packet span of every stream next to the video's. Streams drifting from the video by more than
+// `drift_tolerance_ms`, or whose decoded frames differ from the declared format, are returned as
+// findings.
+pub fn report(
+  streams: &[AudioStream],
+  stream_highest_dts: &[Option<i64>],
+  video_span_ms: Option<u64>,
+  drift_tolerance_ms: u64,
+  verbose: bool,
+) -> Vec<Finding> {
+  let mut issues = Vec::new();
+  if streams.is_empty() {
+    return issues;
+  }
+  out!("{}{}Audio streams - {}", RESET, BLUE_BOLD, RESET);
+  for stream in streams {
+    let last_packet = stream_highest_dts.get(stream.index).copied().flatten().map(|dts| Timestamp::new(dts, stream.time_base));
+    let bit_rate = stream.bit_rate.map_or_else(|| "unknown bit rate".to_string(), |b| format!("{} kb/s", b / 1000));
+    out!("#{}: {}, {}, {}, {}, language {}, {} packets, decoded duration {}, last packet time {}{}",
+      stream.index, stream.codec, bit_rate, stream.declared_format().describe(),
+      stream.sample_format.unwrap_or("unknown sample format"), stream.language.as_deref().unwrap_or("unknown"),
+      format_with_commas(stream.packets as i64), format_optional_millis(stream.decoded_duration_ms()),
+      timestamp::describe_optional(last_packet, verbose),
+      if stream.decode_errors > 0 { format!(", {} decode errors", format_with_commas(stream.decode_errors as i64)) } else { String::new() });
+    if let Some(decoded) = &stream.first_mismatch {
+      out!("#{} decoded as: {} - MISMATCH in {} frames", stream.index, decoded.describe(),
+        format_with_commas(stream.mismatched_frames as i64));
+      issues.push(Finding::new(&findings::AUDIO_FORMAT_MISMATCH, format!(
+        "audio stream #{} declares {} but {} decoded frames are {}", stream.index,
+        stream.declared_format().describe(), format_with_commas(stream.mismatched_frames as i64), decoded.describe())));
+    }
+    if let Some(meter) = &stream.loudness {
+      out!("#{} loudness: {}", stream.index, loudness::describe_result(meter));
+    }
+  }
+
+  out!("{}{}Audio/video drift - {}", RESET, BLUE_BOLD, RESET);
+  out!("Video: {}", format_optional_millis(video_span_ms));
+  for stream in streams {
+    let Some(drift) = stream.drift_ms(video_span_ms) else {
+      out!("Audio #{}: {}, drift unknown", stream.index, format_optional_millis(stream.timestamp_span_ms()));
+      continue;
+    };
+    let passed = drift.unsigned_abs() <= drift_tolerance_ms;
+    out!("Audio #{}: {}, drift {} ms - {}", stream.index, format_optional_millis(stream.timestamp_span_ms()),
+      format_with_commas(drift), if passed { "PASS" } else { "FAIL" });
+    if !passed {
+      issues.push(Finding::new(&findings::AUDIO_DURATION_MISMATCH, format!(
+        "audio stream #{} spans {} but the video spans {}, {} {}, more than the {} ms tolerance", stream.index,
+        format_optional_millis(stream.timestamp_span_ms()), format_optional_millis(video_span_ms),
+        millis_to_clock_format(drift.unsigned_abs()), if drift < 0 { "shorter" } else { "longer" }, drift_tolerance_ms)));
+    }
+  }
+  if let Some(passed) = drift_passed(streams, video_span_ms, drift_tolerance_ms) {
+    out!("A/V drift check: {} (tolerance {} ms)", if passed { "PASS" } else { "FAIL" }, drift_tolerance_ms);
+  }
+  issues
+}
+
+// Whether every audio stream is within `drift_tolerance_ms` of the video; None when no stream
+// could be compared.
+pub fn drift_passed(streams: &[AudioStream], video_span_ms: Option<u64>, drift_tolerance_ms: u64) -> Option<bool> {
+  let drifts: Vec<i64> = streams.iter().filter_map(|stream| stream.drift_ms(video_span_ms)).collect();
+  (!drifts.is_empty()).then(|| drifts.iter().all(|drift| drift.unsigned_abs() <= drift_tolerance_ms))
+}
+
+// Print the start of every audio stream next to the first video frame. Streams starting more than
+// `tolerance_ms` before or after the video are returned as findings.
+pub fn report_start(streams: &[AudioStream], video_start_ms: Option<i64>, tolerance_ms: u64) -> Vec<Finding> {
+  let mut issues = Vec::new();
+  if streams.is_empty() {
+    return issues;
+  }
+  out!("{}{}Audio/video start - {}", RESET, BLUE_BOLD, RESET);
+  out!("Video: first frame at {}", video_start_ms.map_or_else(|| "unknown".to_string(), format_signed_millis));
+  for stream in streams {
+    let priming = if stream.has_priming() {
+      format!(" (edit list taken into account: {} packets discarded, {} priming samples skipped)",
+        format_with_commas(stream.discarded_packets as i64), format_with_commas(stream.skipped_samples as i64))
+    } else {
+      String::new()
+    };
+    let start = stream.presentation_start_ms().map_or_else(|| "unknown".to_string(), format_signed_millis);
+    let Some(offset) = stream.start_offset_ms(video_start_ms) else {
+      out!("Audio #{}: first sample at {}, offset unknown{}", stream.index, start, priming);
+      continue;
+    };
+    let aligned = offset.unsigned_abs() <= tolerance_ms;
+    out!("Audio #{}: first sample at {}, offset {} ms - {}{}", stream.index, start, format_with_commas(offset),
+      if aligned { "OK" } else { "MISALIGNED" }, priming);
+    if !aligned {
+      issues.push(Finding::new(&findings::AV_START_OFFSET, format!(
+        "audio stream #{} starts {} {} the video, more than the {} ms tolerance{}", stream.index,
+        millis_to_clock_format(offset.unsigned_abs()), if offset > 0 { "after" } else { "before" }, tolerance_ms,
+        if stream.has_priming() { ", edit list taken into account" } else { "" })));
+    }
+  }
+  issues
+}
@@ -0,0 +1,166 @@
+// Bitrate over time: the video packet sizes summed into one-second buckets, counted from the first
+// packet timestamp, so short spikes that an average over the whole file hides stand out.
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use ffmpeg::Rational;
+
+use crate::{format_signed_millis, format_with_commas, timestamp_to_signed_millis, BLUE_BOLD, RESET};
+
+#[derive(Default)]
+pub struct Tracker {
+  // Bytes per second, keyed by whole seconds since the first timestamp.
+  buckets: BTreeMap<i64, u64>,
+  first_ms: Option<i64>,
+  last_second: Option<i64>,
+  // Bytes of packets without a timestamp seen before the first one that has one.
+  pending: u64,
+}
+
+// Bitrate figures over the buckets, in bits per second.
+pub struct Summary {
+  pub seconds: usize,
+  pub min_bps: u64,
+  pub avg_bps: u64,
+  pub max_bps: u64,
+  // Start of the second with the highest bitrate.
+  pub peak_ms: i64,
+}
+
+impl Tracker {
+  // Add a video packet. A packet without a usable timestamp belongs to the last second seen, or to
+  // the first one when no timestamp has been seen yet.
+  pub fn push(&mut self, timestamp: Option<i64>, size: u64, time_base: Rational) {
+    let second = match timestamp.and_then(|ts| timestamp_to_signed_millis(ts, time_base)) {
+      Some(ms) => {
+        let first = *self.first_ms.get_or_insert(ms);
+        (ms - first).div_euclid(1000)
+      }
+      None => match self.last_second {
+        Some(second) => second,
+        None => {
+          self.pending += size;
+          return;
+        }
+      },
+    };
+    *self.buckets.entry(second).or_default() += size + std::mem::take(&mut self.pending);
+    self.last_second = Some(second);
+  }
+
+  // Bytes per second from the first to the last bucket, with the seconds without packets as 0.
+  pub fn series(&self) -> Vec<(i64, u64)> {
+    let (Some((&first, _)), Some((&last, _))) = (self.buckets.first_key_value(), self.buckets.last_key_value()) else {
+      return Vec::new();
+    };
+    (first..=last).map(|second| (second, self.buckets.get(&second).copied().unwrap_or(0))).collect()
+  }
+
+  // The last second usually ends before a full second has passed, so it only counts towards the
+  // maximum; min and average come from the full seconds unless it is the only one.
+  pub fn summary(&self) -> Option<Summary> {
+    let series = self.series();
+    let (&(_, last_bytes), full) = series.split_last()?;
+    let full: Vec<u64> = if full.is_empty() { vec![last_bytes] } else { full.iter().map(|&(_, bytes)| bytes).collect() };
+    let &(peak_second, peak_bytes) = series.iter().max_by_key(|&&(second, bytes)| (bytes, -second))?;
+    Some(Summary {
+      seconds: series.len(),
+      min_bps: full.iter().min().copied().unwrap_or(0) * 8,
+      avg_bps: full.iter().sum::<u64>() * 8 / full.len() as u64,
+      max_bps: peak_bytes * 8,
+      peak_ms: self.first_ms.unwrap_or(0) + peak_second * 1000,
+    })
+  }
+
+  // Write the per-second series as CSV (--bitrate-csv).
+  pub fn write_csv(&self, path: &Path) -> std::io::Result<()> {
+    let mut out = BufWriter::new(File::create(path)?);
+    writeln!(out, "second,start_ms,bytes,bits_per_second")?;
+    let first_ms = self.first_ms.unwrap_or(0);
+    for (second, bytes) in self.series() {
+      writeln!(out, "{},{},{},{}", second, first_ms + second * 1000, bytes, bytes * 8)?;
+    }
+    out.flush()
+  }
+}
+
+fn kbps(bps: u64) -> String {
+  format!("{} kb/s", format_with_commas((bps / 1000) as i64))
+}
+
+// Print the bitrate section.
+pub fn report(tracker: &Tracker) -> Option<Summary> {
+  let summary = tracker.summary()?;
+  out!("{}{}Bitrate - {}", RESET, BLUE_BOLD, RESET);
+  out!("Seconds measured: {}", format_with_commas(summary.seconds as i64));
+  out!("Video bitrate: min {}, avg {}, max {} in the second starting at {}", kbps(summary.min_bps),
+    kbps(summary.avg_bps), kbps(summary.max_bps), format_signed_millis(summary.peak_ms));
+  Some(summary)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const MILLIS: Rational = Rational(1, 1000);
+
+  // A tracker fed packets of (timestamp in milliseconds, bytes).
+  fn tracker(packets: &[(i64, u64)]) -> Tracker {
+    let mut tracker = Tracker::default();
+    for &(ms, bytes) in packets {
+      tracker.push(Some(ms), bytes, MILLIS);
+    }
+    tracker
+  }
+
+  #[test]
+  fn partial_last_second_is_left_out_of_min_and_average() {
+    // Two full seconds of 3,000 and 2,000 bytes, then 100 ms of a third.
+    let tracker = tracker(&[(0, 1000), (400, 1000), (800, 1000), (1000, 1000), (1500, 1000), (2000, 50), (2100, 50)]);
+    assert_eq!(tracker.series(), [(0, 3000), (1, 2000), (2, 100)]);
+    let summary = tracker.summary().unwrap();
+    assert_eq!(summary.seconds, 3);
+    assert_eq!((summary.min_bps, summary.avg_bps, summary.max_bps), (16_000, 20_000, 24_000));
+    assert_eq!(summary.peak_ms, 0);
+  }
+
+  #[test]
+  fn partial_last_second_still_counts_towards_the_peak() {
+    let tracker = tracker(&[(10_000, 1000), (11_000, 1000), (12_000, 4000)]);
+    let summary = tracker.summary().unwrap();
+    assert_eq!((summary.min_bps, summary.avg_bps, summary.max_bps), (8_000, 8_000, 32_000));
+    // Seconds count from the first timestamp.
+    assert_eq!(summary.peak_ms, 12_000);
+  }
+
+  #[test]
+  fn a_single_second_is_its_own_average() {
+    let summary = tracker(&[(0, 500), (300, 500)]).summary().unwrap();
+    assert_eq!((summary.seconds, summary.min_bps, summary.avg_bps, summary.max_bps), (1, 8_000, 8_000, 8_000));
+    assert!(Tracker::default().summary().is_none());
+  }
+
+  #[test]
+  fn seconds_without_packets_count_as_zero() {
+    let tracker = tracker(&[(0, 1000), (2000, 1000), (3000, 10)]);
+    assert_eq!(tracker.series(), [(0, 1000), (1, 0), (2, 1000), (3, 10)]);
+    let summary = tracker.summary().unwrap();
+    assert_eq!((summary.min_bps, summary.avg_bps), (0, 5_333));
+  }
+
+  #[test]
+  fn packets_without_timestamps() {
+    let mut tracker = Tracker::default();
+    // Before the first timestamp: added to the first second.
+    tracker.push(None, 100, MILLIS);
+    tracker.push(Some(0), 1000, MILLIS);
+    tracker.push(Some(1000), 1000, MILLIS);
+    // Afterwards: added to the last second seen.
+    tracker.push(None, 200, MILLIS);
+    tracker.push(Some(2000), 1, MILLIS);
+    assert_eq!(tracker.series(), [(0, 1100), (1, 1200), (2, 1)]);
+  }
+}
@@ -0,0 +1,210 @@
+// Key frame cadence: how far apart key frames are, what that implies for segmenting and seeking,
+// and whether it suits a given delivery target.
+
+use ffmpeg::Rational;
+use serde::Serialize;
+
+use crate::findings::{self, Finding};
+use crate::timestamp::Timestamp;
+use crate::{format_optional_millis, format_with_commas, timestamp_to_millis, DecodeStats, BLUE_BOLD, RESET};
+
+// Key frame spacing a delivery target can tolerate.
+pub struct DeliveryTarget {
+  pub name: &'static str,
+  pub description: &'static str,
+  // Longest key frame interval that still allows the target's segment duration.
+  pub max_gop_seconds: f64,
+}
+
+// Built-in delivery targets, selected with --delivery-target.
+pub static TARGETS: &[DeliveryTarget] = &[
+  DeliveryTarget { name: "archive", description: "archive / mezzanine master", max_gop_seconds: 10.0 },
+  DeliveryTarget { name: "vod", description: "HLS/DASH video on demand, 6s segments", max_gop_seconds: 6.0 },
+  DeliveryTarget { name: "dash", description: "DASH live, 4s segments", max_gop_seconds: 4.0 },
+  DeliveryTarget { name: "ll-hls", description: "low-latency HLS, 2s segments", max_gop_seconds: 2.0 },
+  DeliveryTarget { name: "broadcast", description: "MPEG-TS broadcast, fast channel change", max_gop_seconds: 1.0 },
+];
+
+// Look up a delivery target by name, ignoring case.
+pub fn target(name: &str) -> Option<&'static DeliveryTarget> {
+  TARGETS.iter().find(|t| t.name.eq_ignore_ascii_case(name))
+}
+
+// Files shorter than this are not flagged for a single key frame or all-intra coding.
+const LONG_FORM_MS: u64 = 60_000;
+
+// Key frame intervals in milliseconds, or None without a usable time base.
+pub fn intervals_ms(keyframe_timestamps: &[i64], time_base: Rational) -> Option<Vec<u64>> {
+  let mut sorted = keyframe_timestamps.to_vec();
+  sorted.sort_unstable();
+  sorted
+    .windows(2)
+    .map(|w| timestamp_to_millis(w[1] - w[0], time_base))
+    .collect()
+}
+
+// Exact key frame interval statistics, from every key frame's index and timestamp.
+#[derive(Serialize)]
+pub struct GopStats {
+  pub keyframe_count: i64,
+  pub min_frames: i64,
+  pub max_frames: i64,
+  pub avg_frames: f64,
+  pub min_seconds: Option<f64>,
+  pub max_seconds: Option<f64>,
+  pub avg_seconds: Option<f64>,
+  // Timestamp in milliseconds of the key frame that starts the longest interval.
+  pub longest_start_ms: Option<u64>,
+  pub longest_start_raw: Option<Timestamp>,
+}
+
+// None with fewer than two key frames.
+pub fn gop_stats(keyframes: &[(i64, Option<i64>)], keyframe_timestamps: &[i64], time_base: Rational) -> Option<GopStats> {
+  let frames: Vec<i64> = keyframes.windows(2).map(|w| w[1].0 - w[0].0).collect();
+  if frames.is_empty() {
+    return None;
+  }
+  let mut timestamps = keyframe_timestamps.to_vec();
+  timestamps.sort_unstable();
+  let seconds = intervals_ms(&timestamps, time_base).filter(|intervals| !intervals.is_empty());
+  let longest = seconds.as_ref().and_then(|intervals| {
+    let (position, _) = intervals.iter().enumerate().max_by_key(|(_, &ms)| ms)?;
+    Some(Timestamp::new(timestamps[position], time_base))
+  });
+  Some(GopStats {
+    keyframe_count: keyframes.len() as i64,
+    min_frames: *frames.iter().min()?,
+    max_frames: *frames.iter().max()?,
+    avg_frames: frames.iter().sum::<i64>() as f64 / frames.len() as f64,
+    min_seconds: seconds.as_ref().and_then(|s| s.iter().min()).map(|&ms| ms as f64 / 1000.0),
+    max_seconds: seconds.as_ref().and_then(|s| s.iter().max()).map(|&ms| ms as f64 / 1000.0),
+    avg_seconds: seconds.as_ref().map(|s| s.iter().sum::<u64>() as f64 / s.len() as f64 / 1000.0),
+    longest_start_ms: longest.and_then(|ts| ts.millis()),
+    longest_start_raw: longest,
+  })
+}
+
+// Print every key frame with its index and time (--keyframes).
+pub(crate) fn list(keyframes: &[(i64, Option<i64>)], time_base: Rational, verbose: bool) {
+  out!("{}{}Key frames - {}", RESET, BLUE_BOLD, RESET);
+  for &(index, timestamp) in keyframes {
+    let time = timestamp.map(|ts| crate::timestamp::Timestamp::new(ts, time_base));
+    out!("#{}: {}", format_with_commas(index), crate::timestamp::describe_optional(time, verbose));
+  }
+}
+
+// Print the key frame cadence section and return the findings it raises.
+//
+// Without a target the cadence is reported neutrally, with the segment duration and seek
+// granularity it implies and which built-in targets it fits. The single-key-frame and all-intra
+// cases are flagged either way.
+pub(crate) fn report(
+  stats: &DecodeStats,
+  time_base: Rational,
+  container: &str,
+  intra_only_codec: bool,
+  target: Option<&DeliveryTarget>,
+) -> Vec<Finding> {
+  let mut issues = Vec::new();
+  let (keyframe_count, frame_count) = (stats.keyframe_count, stats.frame_count);
+  let duration_ms = stats.measured_duration_ms(time_base);
+  out!("{}{}Key frame cadence - {}", RESET, BLUE_BOLD, RESET);
+  out!("Container: {}", container);
+  out!("Key frames: {}", keyframe_count);
+  let long_form = duration_ms.is_some_and(|ms| ms >= LONG_FORM_MS);
+
+  // Degenerate cadences are almost always encoder misconfiguration on long-form content.
+  if keyframe_count == 1 && frame_count > 1 {
+    out!("Interval: single key frame for the whole file");
+    if long_form {
+      issues.push(Finding::new(&findings::SINGLE_KEYFRAME, format!(
+        "only one key frame in {} frames ({})", frame_count, format_optional_millis(duration_ms))));
+    }
+    return issues;
+  }
+  if keyframe_count == frame_count && frame_count > 1 {
+    out!("Interval: every frame is a key frame (all-intra)");
+    if long_form && !intra_only_codec {
+      issues.push(Finding::new(&findings::ALL_INTRA, format!(
+        "all {} frames are key frames with an inter-frame codec", frame_count)));
+    }
+    return issues;
+  }
+
+  let intervals = match intervals_ms(&stats.keyframe_timestamps, time_base) {
+    Some(intervals) if !intervals.is_empty() => intervals,
+    Some(_) => {
+      out!("Interval: not measurable (fewer than 2 key frames)");
+      return issues;
+    }
+    None => {
+      out!("Interval: not measurable (no usable time base)");
+      return issues;
+    }
+  };
+  let avg = intervals.iter().sum::<u64>() as f64 / intervals.len() as f64 / 1000.0;
+  let min = *intervals.iter().min().unwrap() as f64 / 1000.0;
+  let max = *intervals.iter().max().unwrap() as f64 / 1000.0;
+  out!("Interval: avg {:.3}s (min {:.3}s, max {:.3}s)", avg, min, max);
+  if let Some(gop) = gop_stats(&stats.keyframes, &stats.keyframe_timestamps, time_base) {
+    out!("Interval in frames: avg {:.1} (min {}, max {})", gop.avg_frames, gop.min_frames, gop.max_frames);
+    out!("Longest interval starts at: {}", format_optional_millis(gop.longest_start_ms));
+  }
+  // Segments can only start on a key frame, so no segment can be shorter than the longest GOP
+  // without splitting it. A seek lands on the key frame before the target and decodes forward.
+  out!("Implied minimum segment duration: {:.3}s", max);
+  out!("Seek granularity: avg {:.3}s, worst {:.3}s", avg / 2.0, max);
+
+  match target {
+    Some(target) => {
+      out!("Delivery target: {} ({}), max key frame interval {:.1}s",
+        target.name, target.description, target.max_gop_seconds);
+      if max > target.max_gop_seconds {
+        issues.push(Finding::new(&findings::SPARSE_KEYFRAMES, format!(
+          "longest key frame interval is {:.3}s, {} allows at most {:.1}s",
+          max, target.name, target.max_gop_seconds)));
+      }
+    }
+    None => {
+      out!("{:<12} {:>10}  fits", "target", "max GOP");
+      for target in TARGETS {
+        out!("{:<12} {:>9.1}s  {}", target.name, target.max_gop_seconds,
+          if max <= target.max_gop_seconds { "yes" } else { "no" });
+      }
+    }
+  }
+  issues
+}
+
+// A KEYFRAME_TAIL finding when the last key frame is more than `max_seconds` before the last frame,
+// from the time and frames of DecodeStats::keyframe_tail; located at the last key frame.
+pub fn keyframe_tail_finding((time, frames): (Timestamp, i64), last_keyframe: Option<Timestamp>, max_seconds: f64)
+  -> Option<Finding> {
+  let tail_ms = time.signed_millis().unwrap_or(0);
+  if tail_ms as f64 <= max_seconds * 1000.0 {
+    return None;
+  }
+  let finding = Finding::new(&findings::KEYFRAME_TAIL, format!(
+    "last key frame is {:.1}s / {} frames before the end, more than the {}s maximum",
+    tail_ms as f64 / 1000.0, format_with_commas(frames), max_seconds));
+  Some(match last_keyframe {
+    Some(at) => finding.at(at),
+    None => finding,
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn keyframe_tail_beyond_the_maximum() {
+    let time_base = Rational::new(1, 1000);
+    let last_keyframe = Some(Timestamp::new(8000, time_base));
+    assert!(keyframe_tail_finding((Timestamp::new(2000, time_base), 50), last_keyframe, 2.0).is_none());
+    let finding = keyframe_tail_finding((Timestamp::new(2500, time_base), 62), last_keyframe, 2.0).unwrap();
+    assert_eq!(finding.definition.code, "KEYFRAME_TAIL");
+    assert_eq!(finding.message, "last key frame is 2.5s / 62 frames before the end, more than the 2s maximum");
+    assert_eq!(finding.timestamp.map(|at| at.value), Some(8000));
+  }
+}
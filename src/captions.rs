@@ -0,0 +1,107 @@
+// Closed captions embedded in the video: CEA-608 and CEA-708 data that broadcast-derived files carry
+// as A53 side data of the decoded frames, and caption streams the container exposes on their own
+// (e.g. a c608 track in MOV). Meant for confirming captions survived a transcode, where "0 frames
+// with captions" is the signal that they didn't.
+
+use ffmpeg::codec::Id;
+use ffmpeg::util::frame::side_data;
+
+use crate::report::CaptionReport;
+use crate::timestamp::{self, Timestamp};
+use crate::{format_with_commas, BLUE_BOLD, RESET};
+
+#[derive(Default)]
+pub struct Tracker {
+  // Frames whose A53 side data holds at least one valid caption triplet.
+  pub frames: u64,
+  pub cea608: bool,
+  pub cea708: bool,
+  pub first_timestamp: Option<i64>,
+  pub last_timestamp: Option<i64>,
+}
+
+impl Tracker {
+  pub fn push(&mut self, frame: &ffmpeg::util::frame::video::Video) {
+    let Some(data) = frame.side_data(side_data::Type::A53CC) else {
+      return;
+    };
+    // SMPTE ST 334 cc_data: 3-byte triplets of marker bits, cc_valid and cc_type, then two bytes of
+    // payload. Types 0 and 1 are the CEA-608 fields, 2 and 3 CEA-708 (DTVCC) packets.
+    let mut valid = false;
+    for triplet in data.data().chunks_exact(3) {
+      if triplet[0] & 0x04 == 0 {
+        continue;
+      }
+      valid = true;
+      match triplet[0] & 0x03 {
+        0 | 1 => self.cea608 = true,
+        _ => self.cea708 = true,
+      }
+    }
+    if !valid {
+      return;
+    }
+    self.frames += 1;
+    if let Some(ts) = frame.timestamp() {
+      self.first_timestamp = Some(self.first_timestamp.map_or(ts, |first| first.min(ts)));
+      self.last_timestamp = Some(self.last_timestamp.map_or(ts, |last| last.max(ts)));
+    }
+  }
+
+  pub fn kinds(&self) -> Vec<&'static str> {
+    [(self.cea608, "CEA-608"), (self.cea708, "CEA-708")].into_iter().filter(|(seen, _)| *seen).map(|(_, kind)| kind)
+      .collect()
+  }
+}
+
+// Streams of their own carrying closed captions, with their codec name.
+pub fn streams(ictx: &ffmpeg::format::context::Input) -> Vec<(usize, String)> {
+  ictx.streams()
+    .filter(|stream| stream.parameters().id() == Id::EIA_608)
+    .map(|stream| (stream.index(), stream.parameters().id().name().to_string()))
+    .collect()
+}
+
+pub fn to_report(tracker: &Tracker, streams: &[(usize, String)], time_base: ffmpeg::Rational) -> CaptionReport {
+  CaptionReport {
+    present: !tracker.kinds().is_empty() || !streams.is_empty(),
+    kinds: tracker.kinds(),
+    frames_with_captions: tracker.frames,
+    first_caption_ms: tracker.first_timestamp.and_then(|ts| Timestamp::new(ts, time_base).signed_millis()),
+    last_caption_ms: tracker.last_timestamp.and_then(|ts| Timestamp::new(ts, time_base).signed_millis()),
+    first_caption_raw: tracker.first_timestamp.map(|ts| Timestamp::new(ts, time_base)),
+    last_caption_raw: tracker.last_timestamp.map(|ts| Timestamp::new(ts, time_base)),
+    caption_streams: streams.iter().map(|(index, _)| *index).collect(),
+  }
+}
+
+// Print the closed captions section.
+pub fn report(
+  tracker: &Tracker,
+  streams: &[(usize, String)],
+  frame_count: i64,
+  time_base: ffmpeg::Rational,
+  verbose: bool,
+) {
+  out!("{}{}Closed captions - {}", RESET, BLUE_BOLD, RESET);
+  let kinds = tracker.kinds();
+  if kinds.is_empty() && streams.is_empty() {
+    out!("Captions: none");
+  } else if kinds.is_empty() {
+    out!("Captions: present (caption stream only)");
+  } else {
+    out!("Captions: present ({})", kinds.join(", "));
+  }
+  out!("Frames with captions: {} of {}", format_with_commas(tracker.frames as i64), format_with_commas(frame_count));
+  if tracker.frames > 0 {
+    let describe = |ts: Option<i64>| timestamp::describe_optional(ts.map(|ts| Timestamp::new(ts, time_base)), verbose);
+    out!("First caption frame: {}", describe(tracker.first_timestamp));
+    out!("Last caption frame: {}", describe(tracker.last_timestamp));
+  }
+  if streams.is_empty() {
+    out!("Caption streams: none");
+  } else {
+    let listed: Vec<String> = streams.iter().map(|(index, codec)| format!("#{} ({})", index, codec)).collect();
+    out!("Caption streams: {}", listed.join(", "));
+  }
+}
@@ -0,0 +1,104 @@
+// Chapters of the container: index, start, end and title of each, checked for order, overlaps and
+// for running past the end of the media. A file without chapters says so, so an empty list in the
+// report means none were found, not that nothing was looked at.
+
+use ffmpeg::ffi::AV_NOPTS_VALUE;
+
+use crate::findings::{self, Finding};
+use crate::metadata;
+use crate::timestamp::Timestamp;
+use crate::{format_signed_millis, BLUE_BOLD, RESET};
+
+pub struct Chapter {
+  pub index: usize,
+  // Converted from the chapter's own time base; None for a degenerate one.
+  pub start_ms: Option<i64>,
+  pub end_ms: Option<i64>,
+  pub start: Timestamp,
+  pub end: Timestamp,
+  pub title: Option<String>,
+}
+
+pub fn read(ictx: &ffmpeg::format::context::Input) -> Vec<Chapter> {
+  ictx.chapters()
+    .map(|chapter| {
+      let (start, end) = (Timestamp::new(chapter.start(), chapter.time_base()), Timestamp::new(chapter.end(), chapter.time_base()));
+      Chapter {
+        index: chapter.index(),
+        start_ms: start.signed_millis(),
+        end_ms: end.signed_millis(),
+        start,
+        end,
+        title: chapter.metadata().get("title").map(str::to_string),
+      }
+    })
+    .collect()
+}
+
+fn describe_time(ms: Option<i64>) -> String {
+  ms.map_or_else(|| "unknown".to_string(), format_signed_millis)
+}
+
+// Where the media ends on the chapters' timeline, which starts at the container start time, in ms.
+// None without a container duration.
+pub fn media_end_ms(ictx: &ffmpeg::format::context::Input) -> Option<i64> {
+  let (start_time, duration) = unsafe { ((*ictx.as_ptr()).start_time, (*ictx.as_ptr()).duration) };
+  if duration <= 0 {
+    return None;
+  }
+  let start_time = if start_time == AV_NOPTS_VALUE { 0 } else { start_time };
+  Timestamp::from_av_time_base(start_time + duration).signed_millis()
+}
+
+// Every problem with the chapter list: chapters ending before they start, starting before the
+// previous one or before it ends, and running past the end of the media.
+pub fn violations(chapters: &[Chapter], media_end_ms: Option<i64>) -> Vec<String> {
+  let mut violations = Vec::new();
+  for (i, chapter) in chapters.iter().enumerate() {
+    let (Some(start), Some(end)) = (chapter.start_ms, chapter.end_ms) else {
+      continue;
+    };
+    if end < start {
+      violations.push(format!("chapter {} ends before it starts", chapter.index));
+    }
+    let previous = i.checked_sub(1).and_then(|p| chapters[p].start_ms.zip(chapters[p].end_ms));
+    if let Some((previous_start, previous_end)) = previous {
+      if start < previous_start {
+        violations.push(format!("chapter {} starts before chapter {}", chapter.index, chapters[i - 1].index));
+      } else if start < previous_end {
+        violations.push(format!("chapter {} starts at {}, before chapter {} ends at {}", chapter.index,
+          describe_time(Some(start)), chapters[i - 1].index, describe_time(Some(previous_end))));
+      }
+    }
+  }
+  let last_end = chapters.iter().filter_map(|chapter| chapter.end_ms).max();
+  if let (Some(last_end), Some(media_end)) = (last_end, media_end_ms) {
+    if last_end > media_end {
+      violations.push(format!("the last chapter ends at {}, after the media ends at {}",
+        describe_time(Some(last_end)), describe_time(Some(media_end))));
+    }
+  }
+  violations
+}
+
+// Print the chapters section and return a finding when the list is inconsistent.
+pub fn report(chapters: &[Chapter], media_end_ms: Option<i64>) -> Option<Finding> {
+  out!("{}{}Chapters - {}", RESET, BLUE_BOLD, RESET);
+  if chapters.is_empty() {
+    out!("Chapters: no chapters");
+    return None;
+  }
+  for chapter in chapters {
+    out!("#{}: {} - {} {}", chapter.index, describe_time(chapter.start_ms), describe_time(chapter.end_ms),
+      chapter.title.as_deref().map_or_else(|| "(no title)".to_string(), metadata::escape));
+  }
+  let violations = violations(chapters, media_end_ms);
+  if violations.is_empty() {
+    out!("Chapter order: ok");
+    return None;
+  }
+  for violation in &violations {
+    out!("Chapter problem: {}", violation);
+  }
+  Some(Finding::new(&findings::CHAPTER_ERRORS, violations.join("; ")))
+}
@@ -0,0 +1,733 @@
+// The command line: input paths and named flags, parsed with clap, then checked and turned into
+// the analysis options, or one of the subcommands (`explain`, `self-test`, `compare`,
+// `align-check`) with its own arguments. A subcommand name is only recognized as the first
+// argument, so an input file named like one is given with a path, e.g. `./compare`.
+
+use std::path::PathBuf;
+use std::time::Duration;
+
+use clap::{Parser, Subcommand};
+
+use video_analyzer::{
+  cadence, extract, follow, frame_hashes, hwaccel, interleave, logging, profile, remux, sampling, scenes, strict, thumbnails, verdict,
+  AnalyzeError, AnalyzeOptions,
+  PixelQuality,
+  DEFAULT_AV_DRIFT_TOLERANCE_MS, DEFAULT_AV_OFFSET_TOLERANCE_MS, DEFAULT_DURATION_TOLERANCE_MS, DEFAULT_FREEZE_SECONDS, DEFAULT_GAP_FACTOR, DEFAULT_INTERVAL_TOLERANCE_PERCENT, DEFAULT_IO_RETRY_DELAY_MS,
+  DEFAULT_NETWORK_TIMEOUT_SECONDS,
+};
+
+use crate::exit::{self, Status};
+use crate::{isolate, jobs, output, scan};
+
+// Largest size in megabytes of the --head-tail window and the --max-interleave-mb limit, a petabyte,
+// so the size in bytes stays a file offset.
+const MAX_MB: u64 = 1 << 30;
+
+#[derive(Parser)]
+#[command(version, about = "Decode video files and report their timing, structure and damage.",
+  args_conflicts_with_subcommands = true, after_help = exit::HELP)]
+struct Args {
+  #[command(subcommand)]
+  command: Option<Command>,
+
+  #[arg(value_name = "PATH", help = "Video files or URLs (http, https, rtmp, srt, ...) to analyze, or - for stdin")]
+  paths: Vec<String>,
+
+  #[arg(long, value_name = "N", default_value_t = 1, allow_negative_numbers = true, value_parser = parse_threads,
+    help = "Decoder threads, or -1 for one per core")]
+  threads: i32,
+  #[arg(short, long, value_name = "N", default_value_t = 1, value_parser = clap::value_parser!(u64).range(1..),
+    conflicts_with = "isolate", help = "Analyze up to N files at once")]
+  jobs: u64,
+  #[arg(short, long, action = clap::ArgAction::Count,
+    help = "Show raw timestamps and their time base next to converted times, and debug messages; -vv adds FFmpeg's verbose log")]
+  verbose: u8,
+  #[arg(short, long, help = "Don't draw the progress bar, and only log errors on stderr")]
+  quiet: bool,
+  #[arg(long, value_name = "FORMAT", default_value = "bar", value_parser = ["bar", "json"],
+    help = "Progress on stderr: the bar, or JSON lines of progress events")]
+  progress: String,
+  #[arg(long, help = "Print the analysis as JSON instead of the human-readable report")]
+  json: bool,
+  #[arg(long, conflicts_with_all = ["json", "format", "tag"],
+    help = "Print the analysis as JSON, one line per file, each written as soon as the file is done")]
+  json_lines: bool,
+  #[arg(long, value_name = "FORMAT", default_value = "text", value_parser = ["text", "json", "html", "markdown"],
+    conflicts_with = "json", help = "Report format: text, json (like --json), a self-contained html page or markdown")]
+  format: String,
+  #[arg(long, value_name = "PATH", conflicts_with_all = ["isolate", "tag"],
+    help = "Write the report to PATH instead of stdout; {stem} stands for the input file name without extension")]
+  output: Option<String>,
+  #[arg(long, requires = "output", help = "Overwrite existing --output files")]
+  force: bool,
+  #[arg(long, value_name = "KEY", conflicts_with = "json",
+    help = "Print the value of one metadata tag of each file instead of analyzing it")]
+  tag: Option<String>,
+  #[arg(long, help = "Print a one-paragraph summary of the analysis")]
+  verdict: bool,
+  #[arg(long, value_name = "CHARS", default_value_t = verdict::DEFAULT_MAX_LENGTH,
+    help = "Cap on the --verdict length in characters")]
+  verdict_length: usize,
+
+  #[arg(long, value_name = "MB", value_parser = clap::value_parser!(u64).range(1..=MAX_MB),
+    conflicts_with_all = ["frames_csv", "bitrate_csv", "fps_csv"],
+    help = "Analyze only the first and last MB megabytes of each file")]
+  head_tail: Option<u64>,
+  #[arg(long, value_name = "TIME", value_parser = parse_time, conflicts_with = "head_tail",
+    help = "Start the analysis at this time, as seconds or hh:mm:ss.mmm")]
+  start: Option<u64>,
+  #[arg(long, value_name = "TIME", value_parser = parse_time, conflicts_with = "head_tail",
+    help = "End the analysis at this time, as seconds or hh:mm:ss.mmm")]
+  end: Option<u64>,
+  #[arg(long, value_name = "INDEX", help = "Video stream to analyze instead of the highest-resolution one")]
+  stream: Option<usize>,
+  #[arg(long, value_name = "N", conflicts_with = "stream",
+    help = "Analyze only variant N (from 0) of an HLS or DASH input instead of all of them")]
+  variant: Option<usize>,
+  #[arg(long, help = "Count DTS jumps of 10 seconds or more as discontinuities, as for HLS and DASH inputs")]
+  tolerate_discontinuities: bool,
+  #[arg(long, conflicts_with = "head_tail",
+    help = "Keep reading a file that is still being written, until Ctrl-C or until it stops growing")]
+  follow: bool,
+  #[arg(long, value_name = "SECONDS", default_value_t = follow::DEFAULT_STATUS_SECONDS, value_parser = parse_positive_seconds,
+    requires = "follow", help = "Time between two status lines of --follow")]
+  follow_interval: f64,
+  #[arg(long, value_name = "SECONDS", default_value_t = follow::DEFAULT_GRACE_SECONDS, value_parser = parse_positive_seconds,
+    requires = "follow", help = "How long the file may stop growing before --follow ends the analysis")]
+  follow_grace: f64,
+  #[arg(long, conflicts_with_all = ["head_tail", "no_decode"],
+    help = "Also decode every other video stream and report its frame counts")]
+  all_video_streams: bool,
+  #[arg(long, conflicts_with_all = ["head_tail", "no_decode"],
+    help = "Report stretches where the picture doesn't change")]
+  detect_freeze: bool,
+  #[arg(long, value_name = "SECONDS", default_value_t = DEFAULT_FREEZE_SECONDS, value_parser = parse_positive_seconds,
+    requires = "detect_freeze", help = "Shortest frozen stretch reported by --detect-freeze")]
+  freeze_duration: f64,
+  #[arg(long, value_name = "THRESHOLD", value_parser = parse_scene_threshold, conflicts_with_all = ["head_tail", "no_decode"],
+    help = "List the frames whose luma histogram differs from the previous frame's by more than THRESHOLD (0-1)")]
+  scenes: Option<f64>,
+  #[arg(long, value_name = "FILE", requires = "scenes", help = "Write the --scenes list as CSV")]
+  scenes_file: Option<PathBuf>,
+  #[arg(long, value_name = "FACTOR", default_value_t = DEFAULT_GAP_FACTOR, value_parser = parse_gap_factor,
+    help = "Multiple of the median frame interval beyond which a gap counts as dropped frames")]
+  gap_factor: f64,
+  #[arg(long, value_name = "PERCENT", default_value_t = DEFAULT_INTERVAL_TOLERANCE_PERCENT,
+    value_parser = clap::value_parser!(u32).range(1..),
+    help = "How far a frame interval may differ from the most common one before it counts as irregular")]
+  interval_tolerance: u32,
+  #[arg(long, value_name = "MS", default_value_t = DEFAULT_DURATION_TOLERANCE_MS,
+    help = "Largest difference between the container duration and the last frame time that passes")]
+  tolerance_ms: u64,
+  #[arg(long, value_name = "MS", default_value_t = DEFAULT_AV_DRIFT_TOLERANCE_MS,
+    help = "Largest difference between the duration of an audio stream and the video's that passes")]
+  av_drift_tolerance_ms: u64,
+  #[arg(long, value_name = "MS", default_value_t = DEFAULT_AV_OFFSET_TOLERANCE_MS,
+    help = "Largest difference between the start of an audio stream and the first video frame that passes")]
+  av_offset_tolerance_ms: u64,
+  #[arg(long, help = "Treat a file without a video stream as an error")]
+  require_video: bool,
+  #[arg(long, value_name = "QUALITY", default_value = "fast", value_parser = parse_pixel_quality,
+    help = "Fidelity of the pixel-level passes: fast or full")]
+  pixel_quality: PixelQuality,
+  #[arg(long, value_name = "DEVICE", default_value = "none", value_parser = parse_hwaccel,
+    help = "Decode on a hardware device: auto, vaapi, videotoolbox, cuda or none")]
+  hwaccel: hwaccel::Accel,
+  #[arg(long, help = "Re-decode damaged regions with error concealment")]
+  recovery_pass: bool,
+  #[arg(long, conflicts_with_all = ["head_tail", "recovery_pass", "frames_csv"],
+    help = "Count frames, key frames and timestamps from the video packets without decoding them")]
+  no_decode: bool,
+  #[arg(long,
+    conflicts_with_all = ["head_tail", "no_decode", "recovery_pass", "detect_freeze", "scenes", "frame_hashes"],
+    help = "Decode only the key frames; packet counts stay exact")]
+  keyframes_only: bool,
+  #[arg(long, value_name = "N", value_parser = clap::value_parser!(u64).range(2..),
+    conflicts_with_all = ["keyframes_only", "head_tail", "no_decode", "recovery_pass", "detect_freeze", "scenes",
+      "frame_hashes"],
+    help = "Decode every Nth video packet and every key frame; packet counts stay exact")]
+  sample_every: Option<u64>,
+  #[arg(long, conflicts_with_all = ["head_tail", "no_decode"],
+    help = "Measure the EBU R128 loudness (integrated, range, true peak) of every audio stream")]
+  loudness: bool,
+  #[arg(long, conflicts_with_all = ["head_tail", "no_decode", "sample_every", "keyframes_only"],
+    help = "Report the SMPTE timecodes embedded in the video and check their continuity")]
+  timecodes: bool,
+  #[arg(long, value_name = "TARGET", value_parser = parse_delivery_target,
+    help = "Check the key frame cadence against a delivery target")]
+  delivery_target: Option<&'static cadence::DeliveryTarget>,
+  #[arg(long, help = "List every key frame with its interval to the previous one")]
+  keyframes: bool,
+  #[arg(long, value_name = "FILE", help = "Write one CSV row per decoded frame")]
+  frames_csv: Option<PathBuf>,
+  #[arg(long, value_name = "FILE", help = "Write the video bitrate of every second as CSV")]
+  bitrate_csv: Option<PathBuf>,
+  #[arg(long, value_name = "FILE", help = "Write the number of frames of every second as CSV")]
+  fps_csv: Option<PathBuf>,
+  #[arg(long, value_name = "FILE", conflicts_with_all = ["head_tail", "no_decode"],
+    help = "Write a hash of every decoded frame's picture data")]
+  frame_hashes: Option<PathBuf>,
+  #[arg(long, value_name = "ALGORITHM", default_value = "md5", value_parser = parse_hash_algorithm,
+    requires = "frame_hashes", help = "Hash of --frame-hashes: md5 or xxh3")]
+  hash: frame_hashes::Algorithm,
+  #[arg(long, value_name = "DIR", conflicts_with_all = ["head_tail", "no_decode"],
+    help = "Write a PNG of every key frame into DIR")]
+  thumbnails: Option<PathBuf>,
+  #[arg(long, value_name = "PIXELS", requires = "thumbnails", value_parser = clap::value_parser!(u32).range(2..),
+    help = "Scale the thumbnails down to this width")]
+  thumbnail_width: Option<u32>,
+  #[arg(long, value_name = "SECONDS", requires = "thumbnails", value_parser = parse_positive_seconds,
+    help = "Skip key frames less than this many seconds after the last thumbnail")]
+  thumbnail_every: Option<f64>,
+
+  #[arg(long, value_name = "CONTAINER", value_parser = parse_remux_target,
+    help = "Check whether every stream can be stream-copied into mp4, mkv or ts")]
+  remux_target: Option<remux::RemuxTarget>,
+  #[arg(long, requires = "remux_target", help = "Exit with status 1 when a stream can't be copied into the remux target")]
+  fail_if_not_copyable: bool,
+  #[arg(long, help = "Exit with status 6 when the video packets have DTS or PTS anomalies")]
+  fail_on_timestamp_errors: bool,
+  #[arg(long, conflicts_with_all = ["head_tail", "no_decode"],
+    help = "Exit with status 7 when any decoded frame is interlaced")]
+  fail_on_interlaced: bool,
+  #[arg(long, conflicts_with = "head_tail",
+    help = "Exit with status 8 when the input has decode errors, DTS anomalies or a duration mismatch")]
+  strict: bool,
+  #[arg(long, value_name = "ID,...", value_delimiter = ',', requires = "strict", value_parser = parse_violation_id,
+    help = "Violations that don't fail --strict, e.g. dts_duplicate,duration_mismatch")]
+  strict_allow: Vec<String>,
+  #[arg(long, value_name = "N", default_value_t = 0, conflicts_with = "head_tail",
+    help = "List the N largest video packets with their timestamps")]
+  top_packets: usize,
+  #[arg(long, value_name = "FILE", conflicts_with_all = ["head_tail", "no_decode", "sample_every", "keyframes_only"],
+    help = "Write the picture type of every decoded frame, one GOP per line")]
+  gop_dump: Option<PathBuf>,
+  #[arg(long, value_name = "MB", default_value_t = interleave::DEFAULT_MAX_RUN_MB,
+    value_parser = clap::value_parser!(u64).range(..=MAX_MB), help = "Flag single-stream runs longer than this many megabytes")]
+  max_interleave_mb: u64,
+  #[arg(long, value_name = "SECONDS", default_value_t = interleave::DEFAULT_MAX_RUN_SECONDS,
+    help = "Flag single-stream runs longer than this many seconds")]
+  max_interleave_seconds: f64,
+  #[arg(long, value_name = "SECS", value_parser = parse_positive_seconds, conflicts_with = "head_tail",
+    help = "Exit with status 1 when the last key frame is more than SECS before the end")]
+  max_keyframe_tail: Option<f64>,
+  #[arg(long, value_name = "SPEC", value_parser = profile::Requirement::parse, conflicts_with = "head_tail",
+    help = "Exit with status 1 unless the streams meet a profile and level, e.g. h264:high@4.1 or aac:lc")]
+  require_profile: Option<profile::Requirement>,
+
+  #[arg(long, value_name = "N", default_value_t = 0, help = "Read errors to recover from before giving up")]
+  io_retries: u32,
+  #[arg(long, value_name = "MS", default_value_t = DEFAULT_IO_RETRY_DELAY_MS, help = "Pause before each read retry")]
+  io_retry_delay: u64,
+  #[arg(long, value_name = "SECS", default_value_t = DEFAULT_NETWORK_TIMEOUT_SECONDS,
+    help = "End the analysis of a URL that sends no data for this many seconds (0 waits forever)")]
+  network_timeout: u64,
+
+  #[arg(long, value_name = "DIR", help = "Stream-copy the regions around located errors into DIR")]
+  extract_problems: Option<PathBuf>,
+  #[arg(long, value_name = "SECONDS", default_value_t = extract::DEFAULT_CONTEXT_SECONDS,
+    value_parser = parse_context_seconds, help = "Margin kept around each extracted region")]
+  context_seconds: f64,
+
+  #[arg(long, help = "Analyze each file in a child process so a crash or hang can't take down the run")]
+  isolate: bool,
+  #[arg(long, value_name = "SECONDS", requires = "isolate", value_parser = clap::value_parser!(u64).range(1..),
+    help = "Kill an isolated analysis after this many seconds")]
+  timeout: Option<u64>,
+  #[arg(long, value_name = "MB", requires = "isolate", default_value_t = isolate::DEFAULT_MEMORY_MB,
+    help = "Memory limit of an isolated analysis")]
+  isolate_memory_mb: u64,
+
+  #[arg(long, value_name = "DIR", help = "Also analyze every matching file under DIR")]
+  recursive: Option<PathBuf>,
+  #[arg(long, value_name = "EXT,...", value_delimiter = ',', requires = "recursive",
+    help = "File extensions --recursive picks up [default: mp4,mkv,mov,ts,webm]")]
+  extensions: Vec<String>,
+}
+
+// The subcommands, which take their own arguments instead of the analysis flags.
+#[derive(Subcommand, Debug, PartialEq)]
+pub enum Command {
+  #[command(about = "Describe a finding code, or every one with --all")]
+  Explain {
+    #[arg(value_name = "FINDING_CODE", required_unless_present = "all", conflicts_with = "all")]
+    code: Option<String>,
+    #[arg(long, help = "Describe every finding code")]
+    all: bool,
+  },
+  #[command(about = "Encode a short clip and check the analysis of it; any failed check is a non-zero exit")]
+  SelfTest,
+  #[command(about = "Compare the frames of two files; any difference is a non-zero exit")]
+  Compare {
+    #[arg(value_name = "REF", help = "The reference file")]
+    reference: PathBuf,
+    #[arg(value_name = "TEST", help = "The file compared with the reference")]
+    test: PathBuf,
+  },
+  #[command(about = "Check that the key frames of ABR renditions line up; any misalignment is a non-zero exit")]
+  AlignCheck {
+    #[arg(value_name = "RENDITION", num_args = 2.., required = true,
+      help = "The renditions, compared with the first one")]
+    renditions: Vec<String>,
+    #[arg(long, value_name = "MS", value_parser = parse_tolerance_ms,
+      help = "Key frames this far apart still line up [default: half a frame of the fastest rendition]")]
+    tolerance_ms: Option<f64>,
+  },
+}
+
+fn parse_threads(value: &str) -> Result<i32, String> {
+  match value.parse::<i32>() {
+    Ok(n) if n == -1 || n >= 1 => Ok(n),
+    Ok(_) => Err("expected at least 1 thread, or -1 for one per core".to_string()),
+    Err(_) => Err("expected a thread count, or -1 for one per core".to_string()),
+  }
+}
+
+fn parse_pixel_quality(value: &str) -> Result<PixelQuality, String> {
+  match value {
+    "fast" => Ok(PixelQuality::Fast),
+    "full" => Ok(PixelQuality::Full),
+    _ => Err("expected 'fast' or 'full'".to_string()),
+  }
+}
+
+fn parse_hwaccel(value: &str) -> Result<hwaccel::Accel, String> {
+  hwaccel::Accel::parse(value).ok_or_else(|| "expected 'auto', 'vaapi', 'videotoolbox', 'cuda' or 'none'".to_string())
+}
+
+fn parse_delivery_target(value: &str) -> Result<&'static cadence::DeliveryTarget, String> {
+  cadence::target(value).ok_or_else(|| {
+    let names: Vec<_> = cadence::TARGETS.iter().map(|t| t.name).collect();
+    format!("expected one of {}", names.join(", "))
+  })
+}
+
+fn parse_remux_target(value: &str) -> Result<remux::RemuxTarget, String> {
+  remux::RemuxTarget::parse(value).ok_or_else(|| "expected 'mp4', 'mkv' or 'ts'".to_string())
+}
+
+fn parse_violation_id(value: &str) -> Result<String, String> {
+  if strict::IDS.contains(&value) {
+    Ok(value.to_string())
+  } else {
+    Err(format!("expected one of {}", strict::IDS.join(", ")))
+  }
+}
+
+fn parse_hash_algorithm(value: &str) -> Result<frame_hashes::Algorithm, String> {
+  frame_hashes::Algorithm::parse(value).ok_or_else(|| "expected 'md5' or 'xxh3'".to_string())
+}
+
+fn parse_tolerance_ms(value: &str) -> Result<f64, String> {
+  match value.parse::<f64>() {
+    Ok(ms) if ms.is_finite() && ms >= 0.0 => Ok(ms),
+    _ => Err("expected a non-negative number of milliseconds".to_string()),
+  }
+}
+
+fn parse_context_seconds(value: &str) -> Result<f64, String> {
+  match value.parse::<f64>() {
+    Ok(secs) if secs >= 0.0 => Ok(secs),
+    _ => Err("expected a non-negative number of seconds".to_string()),
+  }
+}
+
+fn parse_positive_seconds(value: &str) -> Result<f64, String> {
+  match value.parse::<f64>() {
+    Ok(secs) if secs > 0.0 => Ok(secs),
+    _ => Err("expected a positive number of seconds".to_string()),
+  }
+}
+
+// Milliseconds from seconds ("600", "12.5") or a clock time ("1:02:03.450", "02:03").
+fn parse_time(value: &str) -> Result<u64, String> {
+  let invalid = || "expected seconds or hh:mm:ss.mmm".to_string();
+  let fields: Vec<&str> = value.split(':').collect();
+  if fields.len() > 3 {
+    return Err(invalid());
+  }
+  let (seconds, counts) = fields.split_last().ok_or_else(invalid)?;
+  let seconds = seconds.parse::<f64>().ok().filter(|s| s.is_finite() && *s >= 0.0).ok_or_else(invalid)?;
+  let counts = counts.iter().map(|count| count.parse::<u32>().map_err(|_| invalid())).collect::<Result<Vec<_>, _>>()?;
+  // Every field below the highest one counts less than 60 of its unit, so "1:99:00" is rejected.
+  if (!counts.is_empty() && seconds >= 60.0) || (counts.len() == 2 && counts[1] >= 60) {
+    return Err(invalid());
+  }
+  let minutes = counts.iter().fold(0.0, |minutes, &count| minutes * 60.0 + count as f64);
+  Ok(((minutes * 60.0 + seconds) * 1000.0).round() as u64)
+}
+
+fn parse_scene_threshold(value: &str) -> Result<f64, String> {
+  match value.parse::<f64>() {
+    Ok(threshold) if threshold > 0.0 && threshold < 1.0 => Ok(threshold),
+    _ => Err("expected a histogram difference between 0 and 1, e.g. 0.4".to_string()),
+  }
+}
+
+fn parse_gap_factor(value: &str) -> Result<f64, String> {
+  match value.parse::<f64>() {
+    Ok(factor) if factor > 1.0 => Ok(factor),
+    _ => Err("expected a multiple of the median frame interval greater than 1".to_string()),
+  }
+}
+
+// What main needs from the command line: the analysis options and the flags that only concern
+// the tool itself.
+pub struct Cli {
+  pub paths: Vec<String>,
+  pub options: AnalyzeOptions,
+  // Exit with status 1 when a stream can't be copied into the remux target.
+  pub fail_if_not_copyable: bool,
+  // Exit with status 6 when the report has a TIMESTAMP_ANOMALY finding.
+  pub fail_on_timestamp_errors: bool,
+  // Exit with status 7 when any decoded frame is interlaced.
+  pub fail_on_interlaced: bool,
+  // Print the analysis as JSON instead of the human-readable report: one object, or an array of
+  // them with several input files.
+  pub json: bool,
+  // With --json-lines, one compact object per line instead of an array.
+  pub json_lines: bool,
+  // Print the text report as Markdown; see markdown.rs.
+  pub markdown: bool,
+  // Write the report of each file there instead of on stdout; see output.rs.
+  pub output: Option<String>,
+  // Print this metadata tag of each file instead of analyzing it (--tag).
+  pub tag: Option<String>,
+  // Files analyzed at once; 1 analyzes them in turn.
+  pub jobs: usize,
+  // Analyze in a child process with these limits (--isolate).
+  pub isolate: Option<isolate::Limits>,
+}
+
+// What the command line asks for: a subcommand, or the analysis of the input files.
+pub enum Invocation {
+  Command(Command),
+  Analyze(Box<Cli>),
+}
+
+// Parse the process arguments. --help, --version and malformed flags are handled by clap, which
+// prints its message and exits; the checks that need more than one flag return an error.
+pub fn parse() -> Result<Invocation, AnalyzeError> {
+  let args = match Args::try_parse() {
+    Ok(args) => args,
+    // --help and --version print on stdout and exit with 0.
+    Err(e) if !e.use_stderr() => e.exit(),
+    // Usage errors exit with status 1 instead of clap's 2, which means the input couldn't be opened.
+    Err(e) => {
+      let _ = e.print();
+      std::process::exit(Status::Failure.code());
+    }
+  };
+  if let Some(command) = args.command {
+    return Ok(Invocation::Command(command));
+  }
+  logging::set_level(logging::level(args.quiet, args.verbose));
+  let mut paths = args.paths;
+
+  if let Some(dir) = &args.recursive {
+    let mut extensions: Vec<String> = args
+      .extensions
+      .iter()
+      .map(|e| e.trim().trim_start_matches('.').to_string())
+      .filter(|e| !e.is_empty())
+      .collect();
+    if extensions.is_empty() {
+      extensions = scan::DEFAULT_EXTENSIONS.iter().map(|e| e.to_string()).collect();
+    }
+    let found = scan::find_files(dir, &extensions);
+    if found.is_empty() {
+      return Err(AnalyzeError::InvalidArgument(format!(
+        "no files with the extensions {} under {}", extensions.join(", "), dir.display())));
+    }
+    for path in found {
+      match path.to_str() {
+        Some(path) => paths.push(path.to_string()),
+        None => log::warn!("Skipping {}: not a valid UTF-8 path", path.display()),
+      }
+    }
+  }
+
+  if paths.is_empty() {
+    return Err(AnalyzeError::InvalidArgument(
+      "missing input path; pass one or more video files, or --recursive <DIR>".to_string()));
+  }
+  if paths.iter().filter(|path| *path == "-").count() > 1 {
+    return Err(AnalyzeError::InvalidArgument("stdin (-) can only be read once".to_string()));
+  }
+  if args.frames_csv.is_some() && paths.len() > 1 {
+    return Err(AnalyzeError::InvalidArgument("--frames-csv takes a single input file".to_string()));
+  }
+  if args.bitrate_csv.is_some() && paths.len() > 1 {
+    return Err(AnalyzeError::InvalidArgument("--bitrate-csv takes a single input file".to_string()));
+  }
+  if args.fps_csv.is_some() && paths.len() > 1 {
+    return Err(AnalyzeError::InvalidArgument("--fps-csv takes a single input file".to_string()));
+  }
+  if args.gop_dump.is_some() && paths.len() > 1 {
+    return Err(AnalyzeError::InvalidArgument("--gop-dump takes a single input file".to_string()));
+  }
+  if args.frame_hashes.is_some() && paths.len() > 1 {
+    return Err(AnalyzeError::InvalidArgument("--frame-hashes takes a single input file".to_string()));
+  }
+  if args.thumbnails.is_some() && paths.len() > 1 {
+    return Err(AnalyzeError::InvalidArgument("--thumbnails takes a single input file".to_string()));
+  }
+  if args.scenes_file.is_some() && paths.len() > 1 {
+    return Err(AnalyzeError::InvalidArgument("--scenes-file takes a single input file".to_string()));
+  }
+  // Events of concurrent analyses couldn't be told apart.
+  if args.progress == "json" && paths.len() > 1 {
+    return Err(AnalyzeError::InvalidArgument("--progress json takes a single input file".to_string()));
+  }
+  if args.follow && paths.len() > 1 {
+    return Err(AnalyzeError::InvalidArgument("--follow takes a single input file".to_string()));
+  }
+  // A pipe doesn't grow once it has ended.
+  if args.follow && paths.iter().any(|path| path == "-") {
+    return Err(AnalyzeError::InvalidArgument("--follow can't be used with stdin".to_string()));
+  }
+
+  if let Some(template) = &args.output {
+    output::check(template, &paths, args.force)?;
+  }
+  // The summary table of several files comes first, so the parent process would have to gather
+  // the children's reports.
+  if args.format == "markdown" && args.isolate {
+    return Err(AnalyzeError::InvalidArgument("--format markdown isn't available with --isolate".to_string()));
+  }
+  // Pages can't be concatenated like the text reports.
+  if args.format == "html" && paths.len() > 1 && args.output.is_none() {
+    return Err(AnalyzeError::InvalidArgument("--format html with several input files needs --output".to_string()));
+  }
+
+  if let (Some(start), Some(end)) = (args.start, args.end) {
+    if end <= start {
+      return Err(AnalyzeError::InvalidArgument("--end must be after --start".to_string()));
+    }
+  }
+
+  // Several files at once share the cores: one per core is split between the jobs, and an
+  // explicit count that oversubscribes them is only warned about.
+  let jobs = (args.jobs as usize).min(paths.len()).max(1);
+  let threads = match args.threads {
+    -1 if jobs > 1 => jobs::threads_per_job(jobs),
+    threads => threads,
+  };
+  if jobs > 1 && threads as usize * jobs > num_cpus::get() {
+    log::warn!("{} jobs with {} decoder threads each exceed the {} available cores", jobs, threads,
+      num_cpus::get());
+  }
+
+  Ok(Invocation::Analyze(Box::new(Cli {
+    paths,
+    options: AnalyzeOptions {
+      threads_number_input: threads,
+      head_tail_mb: args.head_tail,
+      start_ms: args.start,
+      end_ms: args.end,
+      require_video: args.require_video,
+      pixel_quality: args.pixel_quality,
+      hwaccel: args.hwaccel,
+      recovery_pass: args.recovery_pass,
+      duration_tolerance_ms: args.tolerance_ms,
+      av_drift_tolerance_ms: args.av_drift_tolerance_ms,
+      av_offset_tolerance_ms: args.av_offset_tolerance_ms,
+      max_keyframe_tail_s: args.max_keyframe_tail,
+      required_profile: args.require_profile,
+      top_packets: args.top_packets,
+      gop_dump: args.gop_dump,
+      interval_tolerance_percent: args.interval_tolerance,
+      gap_factor: args.gap_factor,
+      scenes: args.scenes.map(|threshold| scenes::Settings { threshold, csv: args.scenes_file }),
+      detect_freeze_ms: args.detect_freeze.then_some((args.freeze_duration * 1000.0) as u64),
+      no_decode: args.no_decode,
+      sampling: match args.sample_every {
+        Some(n) => Some(sampling::Mode::Every(n)),
+        None => args.keyframes_only.then_some(sampling::Mode::KeyFrames),
+      },
+      all_video_streams: args.all_video_streams,
+      measure_loudness: args.loudness,
+      timecodes: args.timecodes,
+      delivery_target: args.delivery_target,
+      verbose: args.verbose > 0,
+      stream_index: args.stream,
+      variant: args.variant,
+      tolerate_discontinuities: args.tolerate_discontinuities,
+      follow: args.follow.then_some(follow::Settings {
+        status_interval: Duration::from_secs_f64(args.follow_interval),
+        grace: Duration::from_secs_f64(args.follow_grace),
+      }),
+      remux_target: args.remux_target,
+      io_retries: args.io_retries,
+      io_retry_delay_ms: args.io_retry_delay,
+      network_timeout_seconds: args.network_timeout,
+      strict: args.strict,
+      strict_allow: args.strict_allow,
+      verdict: args.verdict,
+      verdict_length: args.verdict_length,
+      interleave_limits: interleave::Limits {
+        max_bytes: args.max_interleave_mb * 1024 * 1024,
+        max_seconds: args.max_interleave_seconds,
+      },
+      extract_problems: args.extract_problems
+        .map(|dir| extract::Settings { dir, context_seconds: args.context_seconds }),
+      list_keyframes: args.keyframes,
+      frames_csv: args.frames_csv,
+      bitrate_csv: args.bitrate_csv,
+      fps_csv: args.fps_csv,
+      frame_hashes: args.frame_hashes.map(|path| frame_hashes::Settings { path, algorithm: args.hash }),
+      thumbnails: args.thumbnails.map(|dir| thumbnails::Settings {
+        dir,
+        width: args.thumbnail_width,
+        every_seconds: args.thumbnail_every,
+      }),
+      print_report: !args.json && !args.json_lines && matches!(args.format.as_str(), "text" | "markdown"),
+      html: args.format == "html",
+      // Bars of concurrent analyses would overwrite each other; jobs::run prints a line per file.
+      show_progress: !args.quiet && jobs == 1 && args.progress == "bar",
+      progress_events: args.progress == "json",
+    },
+    fail_if_not_copyable: args.fail_if_not_copyable,
+    fail_on_timestamp_errors: args.fail_on_timestamp_errors,
+    fail_on_interlaced: args.fail_on_interlaced,
+    json: args.json || args.json_lines || args.format == "json",
+    json_lines: args.json_lines,
+    markdown: args.format == "markdown",
+    output: args.output,
+    tag: args.tag,
+    jobs,
+    isolate: args.isolate.then_some(isolate::Limits {
+      timeout: args.timeout.map(Duration::from_secs),
+      memory_mb: args.isolate_memory_mb,
+    }),
+  })))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use clap::CommandFactory;
+
+  #[test]
+  fn args_are_consistent() {
+    Args::command().debug_assert();
+  }
+
+  #[test]
+  fn time_in_seconds() {
+    assert_eq!(parse_time("0"), Ok(0));
+    assert_eq!(parse_time("600"), Ok(600_000));
+    assert_eq!(parse_time("12.5"), Ok(12_500));
+  }
+
+  #[test]
+  fn time_as_clock() {
+    assert_eq!(parse_time("02:03"), Ok(123_000));
+    assert_eq!(parse_time("1:02:03.450"), Ok(3_723_450));
+    assert_eq!(parse_time("1:00:00"), Ok(3_600_000));
+    assert_eq!(parse_time("90:00"), Ok(5_400_000));
+    assert_eq!(parse_time("100:59:59.999"), Ok(363_599_999));
+  }
+
+  #[test]
+  fn time_fields_below_the_highest_stay_under_60() {
+    assert!(parse_time("1:99:00").is_err());
+    assert!(parse_time("1:60:00").is_err());
+    assert!(parse_time("1:00:60").is_err());
+    assert!(parse_time("02:60").is_err());
+    assert!(parse_time("0:59.999").is_ok());
+  }
+
+  #[test]
+  fn malformed_times() {
+    for value in ["", "-1", "abc", "1:2:3:4", ":30", "1:", "1.5:00", "-1:00", "inf", "NaN", "1:-5"] {
+      assert!(parse_time(value).is_err(), "{value}");
+    }
+  }
+
+  #[test]
+  fn threads() {
+    assert_eq!(parse_threads("1"), Ok(1));
+    assert_eq!(parse_threads("16"), Ok(16));
+    assert_eq!(parse_threads("-1"), Ok(-1));
+    for value in ["0", "-2", "", "four", "1.5"] {
+      assert!(parse_threads(value).is_err(), "{value}");
+    }
+  }
+
+  #[test]
+  fn head_tail_has_every_output_format() {
+    for flags in [&["--json"][..], &["--json-lines"], &["--format", "html"], &["--progress", "json"], &[]] {
+      let args = ["video_analyzer", "--head-tail", "8"].iter().chain(flags).chain(&["in.mp4"]);
+      assert!(Args::try_parse_from(args).is_ok(), "{flags:?}");
+    }
+    assert!(Args::try_parse_from(["video_analyzer", "--head-tail", "8", "--frames-csv", "f.csv", "in.mp4"]).is_err());
+  }
+
+  #[test]
+  fn megabyte_sizes_stay_file_offsets() {
+    let max = MAX_MB.to_string();
+    let past = (MAX_MB + 1).to_string();
+    for flag in ["--head-tail", "--max-interleave-mb"] {
+      assert!(Args::try_parse_from(["video_analyzer", flag, max.as_str(), "in.mp4"]).is_ok(), "{flag}");
+      assert!(Args::try_parse_from(["video_analyzer", flag, past.as_str(), "in.mp4"]).is_err(), "{flag}");
+      assert!(Args::try_parse_from(["video_analyzer", flag, "18446744073709551615", "in.mp4"]).is_err(), "{flag}");
+    }
+    assert!(Args::try_parse_from(["video_analyzer", "--head-tail", "0", "in.mp4"]).is_err());
+  }
+
+  #[test]
+  fn json_lines_is_a_json_format_of_its_own() {
+    assert!(Args::try_parse_from(["video_analyzer", "--json-lines", "a.mp4", "b.mp4"]).unwrap().json_lines);
+    for other in [["--json", ""], ["--format", "json"], ["--tag", "title"]] {
+      let args = ["video_analyzer", "--json-lines", other[0], other[1], "in.mp4"].into_iter().filter(|arg| !arg.is_empty());
+      assert!(Args::try_parse_from(args).is_err(), "{other:?}");
+    }
+  }
+
+  #[test]
+  fn compare_is_a_subcommand() {
+    let args = Args::try_parse_from(["video_analyzer", "compare", "ref.mp4", "test.mp4"]).unwrap();
+    assert_eq!(args.command, Some(Command::Compare { reference: "ref.mp4".into(), test: "test.mp4".into() }));
+    assert!(args.paths.is_empty());
+    for line in [&["compare", "ref.mp4"][..], &["compare", "a.mp4", "b.mp4", "c.mp4"], &["compare", "a.mp4", "b.mp4", "--json"]] {
+      assert!(Args::try_parse_from(["video_analyzer"].iter().chain(line)).is_err(), "{line:?}");
+    }
+    assert_eq!(Args::try_parse_from(["video_analyzer", "a.mp4"]).unwrap().command, None);
+  }
+
+  #[test]
+  fn align_check_takes_a_tolerance() {
+    let args = Args::try_parse_from(["video_analyzer", "align-check", "a.mp4", "b.mp4", "--tolerance-ms", "20"]).unwrap();
+    assert_eq!(args.command, Some(Command::AlignCheck {
+      renditions: vec!["a.mp4".to_string(), "b.mp4".to_string()],
+      tolerance_ms: Some(20.0),
+    }));
+    let args = Args::try_parse_from(["video_analyzer", "align-check", "--tolerance-ms=0.5", "a.mp4", "b.mp4", "c.mp4"]).unwrap();
+    assert!(matches!(args.command, Some(Command::AlignCheck { renditions, tolerance_ms: Some(ms) }) if renditions.len() == 3 && ms == 0.5));
+    for line in [&["align-check", "a.mp4"][..], &["align-check", "a.mp4", "b.mp4", "--tolerance-ms"],
+      &["align-check", "a.mp4", "b.mp4", "--tolerance-ms", "-1"], &["align-check", "a.mp4", "b.mp4", "--tolerance-ms", "soon"]] {
+      assert!(Args::try_parse_from(["video_analyzer"].iter().chain(line)).is_err(), "{line:?}");
+    }
+  }
+
+  #[test]
+  fn explain_takes_a_code_or_all() {
+    let args = Args::try_parse_from(["video_analyzer", "explain", "TRUNCATED"]).unwrap();
+    assert_eq!(args.command, Some(Command::Explain { code: Some("TRUNCATED".to_string()), all: false }));
+    let args = Args::try_parse_from(["video_analyzer", "explain", "--all"]).unwrap();
+    assert_eq!(args.command, Some(Command::Explain { code: None, all: true }));
+    assert!(Args::try_parse_from(["video_analyzer", "explain"]).is_err());
+    assert!(Args::try_parse_from(["video_analyzer", "explain", "TRUNCATED", "--all"]).is_err());
+    assert_eq!(Args::try_parse_from(["video_analyzer", "self-test"]).unwrap().command, Some(Command::SelfTest));
+    assert!(Args::try_parse_from(["video_analyzer", "self-test", "in.mp4"]).is_err());
+  }
+
+  #[test]
+  fn flags_may_follow_paths() {
+    let args = Args::try_parse_from(["video_analyzer", "a.mp4", "--threads=4", "b.mkv", "--end", "1:00"]).unwrap();
+    assert_eq!(args.paths, ["a.mp4", "b.mkv"]);
+    assert_eq!(args.threads, 4);
+    assert_eq!(args.end, Some(60_000));
+  }
+}
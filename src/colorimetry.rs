@@ -0,0 +1,231 @@
+// Color description of the analyzed stream, for validating HDR deliveries: the range, primaries,
+// transfer, matrix and chroma siting of the decoded frames next to what the stream parameters
+// declare, and the mastering display and content light level metadata carried by the frames. A
+// file declaring BT.2020/PQ whose frames decode as BT.709 is flagged.
+
+use ffmpeg::color::{self, Primaries, Space, TransferCharacteristic};
+use ffmpeg::ffi::{AVContentLightMetadata, AVMasteringDisplayMetadata, AVRational};
+use ffmpeg::util::chroma;
+use ffmpeg::util::frame::side_data;
+
+use crate::findings::{self, Finding};
+use crate::report::{ColorReport, ContentLightLevelReport, MasteringDisplayReport};
+use crate::{BLUE_BOLD, RESET};
+
+#[derive(Clone, Copy, PartialEq)]
+pub struct Description {
+  pub range: color::Range,
+  pub primaries: Primaries,
+  pub transfer: TransferCharacteristic,
+  pub matrix: Space,
+  pub chroma_location: chroma::Location,
+}
+
+impl Description {
+  pub fn from_frame(frame: &ffmpeg::util::frame::video::Video) -> Self {
+    Description {
+      range: frame.color_range(),
+      primaries: frame.color_primaries(),
+      transfer: frame.color_transfer_characteristic(),
+      matrix: frame.color_space(),
+      chroma_location: frame.chroma_location(),
+    }
+  }
+
+  pub fn from_parameters(parameters: &ffmpeg::codec::Parameters) -> Self {
+    let p = unsafe { &*parameters.as_ptr() };
+    Description {
+      range: p.color_range.into(),
+      primaries: p.color_primaries.into(),
+      transfer: p.color_trc.into(),
+      matrix: p.color_space.into(),
+      chroma_location: p.chroma_location.into(),
+    }
+  }
+}
+
+pub fn range_name(range: color::Range) -> &'static str {
+  match range {
+    color::Range::MPEG => "limited (tv)",
+    color::Range::JPEG => "full (pc)",
+    _ => "unspecified",
+  }
+}
+
+pub fn primaries_name(primaries: Primaries) -> &'static str {
+  match primaries {
+    Primaries::Reserved0 | Primaries::Unspecified | Primaries::Reserved => "unspecified",
+    primaries => primaries.name().unwrap_or("unspecified"),
+  }
+}
+
+pub fn transfer_name(transfer: TransferCharacteristic) -> &'static str {
+  match transfer {
+    TransferCharacteristic::Reserved0 | TransferCharacteristic::Unspecified | TransferCharacteristic::Reserved => {
+      "unspecified"
+    }
+    TransferCharacteristic::SMPTE2084 => "smpte2084 (PQ)",
+    TransferCharacteristic::ARIB_STD_B67 => "arib-std-b67 (HLG)",
+    transfer => transfer.name().unwrap_or("unspecified"),
+  }
+}
+
+pub fn matrix_name(matrix: Space) -> &'static str {
+  match matrix {
+    Space::Unspecified | Space::Reserved => "unspecified",
+    matrix => matrix.name().unwrap_or("unspecified"),
+  }
+}
+
+pub fn chroma_location_name(location: chroma::Location) -> &'static str {
+  match location {
+    chroma::Location::Unspecified => "unspecified",
+    chroma::Location::Left => "left",
+    chroma::Location::Center => "center",
+    chroma::Location::TopLeft => "top left",
+    chroma::Location::Top => "top",
+    chroma::Location::BottomLeft => "bottom left",
+    chroma::Location::Bottom => "bottom",
+  }
+}
+
+fn rational(value: AVRational) -> f64 {
+  if value.den == 0 { 0.0 } else { value.num as f64 / value.den as f64 }
+}
+
+// Mastering display color volume (SMPTE ST 2086): CIE 1931 xy chromaticities of the red, green and
+// blue primaries and the white point, and the luminance range in cd/m².
+#[derive(Clone, Copy)]
+pub struct Mastering {
+  pub primaries: Option<[[f64; 2]; 3]>,
+  pub white_point: Option<[f64; 2]>,
+  pub luminance: Option<(f64, f64)>,
+}
+
+// Content light level (CTA-861.3): the brightest pixel and the brightest frame average, in cd/m².
+#[derive(Clone, Copy)]
+pub struct LightLevel {
+  pub max_cll: u32,
+  pub max_fall: u32,
+}
+
+fn read_mastering(bytes: &[u8]) -> Option<Mastering> {
+  if bytes.len() < std::mem::size_of::<AVMasteringDisplayMetadata>() {
+    return None;
+  }
+  let m = unsafe { std::ptr::read_unaligned(bytes.as_ptr() as *const AVMasteringDisplayMetadata) };
+  let xy = |point: [AVRational; 2]| [rational(point[0]), rational(point[1])];
+  Some(Mastering {
+    primaries: (m.has_primaries != 0).then(|| m.display_primaries.map(xy)),
+    white_point: (m.has_primaries != 0).then(|| xy(m.white_point)),
+    luminance: (m.has_luminance != 0).then(|| (rational(m.min_luminance), rational(m.max_luminance))),
+  })
+}
+
+fn read_light_level(bytes: &[u8]) -> Option<LightLevel> {
+  if bytes.len() < std::mem::size_of::<AVContentLightMetadata>() {
+    return None;
+  }
+  let l = unsafe { std::ptr::read_unaligned(bytes.as_ptr() as *const AVContentLightMetadata) };
+  Some(LightLevel { max_cll: l.MaxCLL, max_fall: l.MaxFALL })
+}
+
+// The color description of the first decoded frame and the first HDR metadata of each kind.
+#[derive(Default)]
+pub struct Tracker {
+  pub frame: Option<Description>,
+  pub mastering: Option<Mastering>,
+  pub light_level: Option<LightLevel>,
+}
+
+impl Tracker {
+  pub fn check(&mut self, frame: &ffmpeg::util::frame::video::Video) {
+    if self.frame.is_none() {
+      self.frame = Some(Description::from_frame(frame));
+    }
+    if self.mastering.is_none() {
+      self.mastering = frame.side_data(side_data::Type::MasteringDisplayMetadata).and_then(|d| read_mastering(d.data()));
+    }
+    if self.light_level.is_none() {
+      self.light_level = frame.side_data(side_data::Type::ContentLightLevel).and_then(|d| read_light_level(d.data()));
+    }
+  }
+}
+
+// A property of the decoded frames next to the declared one. Both must be specified to disagree.
+fn compare(label: &str, decoded: &'static str, declared: &'static str, mismatches: &mut Vec<String>) {
+  let disagree = decoded != "unspecified" && declared != "unspecified" && decoded != declared;
+  out!("{}: {} (declared {}){}", label, decoded, declared, if disagree { " - MISMATCH" } else { "" });
+  if disagree {
+    mismatches.push(format!("{} {} decoded, {} declared", label.to_lowercase(), decoded, declared));
+  }
+}
+
+fn describe_xy(xy: [f64; 2]) -> String {
+  format!("({:.4}, {:.4})", xy[0], xy[1])
+}
+
+// The decoded color description next to the declared one; None without a decoded frame.
+pub fn to_report(tracker: &Tracker, declared: &Description) -> Option<ColorReport> {
+  let decoded = tracker.frame?;
+  Some(ColorReport {
+    range: range_name(decoded.range),
+    primaries: primaries_name(decoded.primaries),
+    transfer: transfer_name(decoded.transfer),
+    matrix: matrix_name(decoded.matrix),
+    chroma_location: chroma_location_name(decoded.chroma_location),
+    declared_range: range_name(declared.range),
+    declared_primaries: primaries_name(declared.primaries),
+    declared_transfer: transfer_name(declared.transfer),
+    declared_matrix: matrix_name(declared.matrix),
+    declared_chroma_location: chroma_location_name(declared.chroma_location),
+    mastering_display: tracker.mastering.map(|mastering| MasteringDisplayReport {
+      primaries: mastering.primaries,
+      white_point: mastering.white_point,
+      min_luminance: mastering.luminance.map(|(min, _)| min),
+      max_luminance: mastering.luminance.map(|(_, max)| max),
+    }),
+    content_light_level: tracker.light_level
+      .map(|level| ContentLightLevelReport { max_cll: level.max_cll, max_fall: level.max_fall }),
+  })
+}
+
+// Print the color section and return a finding when the decoded frames contradict the declared
+// color description.
+pub fn report(tracker: &Tracker, declared: &Description) -> Option<Finding> {
+  let decoded = tracker.frame?;
+  out!("{}{}Color - {}", RESET, BLUE_BOLD, RESET);
+  let mut mismatches = Vec::new();
+  compare("Color range", range_name(decoded.range), range_name(declared.range), &mut mismatches);
+  compare("Color primaries", primaries_name(decoded.primaries), primaries_name(declared.primaries), &mut mismatches);
+  compare("Transfer characteristics", transfer_name(decoded.transfer), transfer_name(declared.transfer),
+    &mut mismatches);
+  compare("Matrix coefficients", matrix_name(decoded.matrix), matrix_name(declared.matrix), &mut mismatches);
+  compare("Chroma location", chroma_location_name(decoded.chroma_location),
+    chroma_location_name(declared.chroma_location), &mut mismatches);
+
+  match tracker.mastering {
+    Some(mastering) => {
+      match (mastering.primaries, mastering.white_point) {
+        (Some([r, g, b]), Some(white)) => out!("Mastering display primaries: R {}, G {}, B {}, white point {}",
+          describe_xy(r), describe_xy(g), describe_xy(b), describe_xy(white)),
+        _ => out!("Mastering display primaries: unspecified"),
+      }
+      match mastering.luminance {
+        Some((min, max)) => out!("Mastering display luminance: {:.4} - {:.4} cd/m²", min, max),
+        None => out!("Mastering display luminance: unspecified"),
+      }
+    }
+    None => out!("Mastering display metadata: none"),
+  }
+  match tracker.light_level {
+    Some(level) => out!("Content light level: MaxCLL {} cd/m², MaxFALL {} cd/m²", level.max_cll, level.max_fall),
+    None => out!("Content light level: none"),
+  }
+
+  if mismatches.is_empty() {
+    return None;
+  }
+  Some(Finding::new(&findings::COLOR_MISMATCH, format!("decoded frames disagree with the stream parameters: {}",
+    mismatches.join(", "))))
+}
@@ -0,0 +1,180 @@
+// `compare <REF> <TEST>` subcommand: decode the main video stream of two files in lockstep, one
+// frame of each at a time, and compare their luma planes. Meant to verify a remux (every frame
+// identical) or a transcode (PSNR). Only two frames are held at any time, whatever the length.
+
+use std::path::Path;
+
+use ffmpeg::format::Pixel;
+use ffmpeg::software::scaling;
+use ffmpeg::Rational;
+
+use crate::{format_signed_millis, format_with_commas, streams, timestamp_to_signed_millis, AnalyzeError, BLUE_BOLD, RESET};
+
+// One side of the comparison: a demuxer and the decoder of its main video stream.
+struct Source {
+  ictx: ffmpeg::format::context::Input,
+  decoder: ffmpeg::decoder::Video,
+  stream_index: usize,
+  time_base: Rational,
+  // The demuxer is exhausted and the decoder has been told so.
+  draining: bool,
+  frames: i64,
+}
+
+impl Source {
+  fn open(path: &Path) -> Result<Self, AnalyzeError> {
+    let ictx = ffmpeg::format::input(&path).map_err(AnalyzeError::OpenError)?;
+    let stream_index = streams::primary_index(&streams::video_tracks(&ictx)).ok_or(AnalyzeError::NoVideoStreamError)?;
+    let stream = ictx.stream(stream_index).ok_or(AnalyzeError::NoVideoStreamError)?;
+    let time_base = stream.time_base();
+    let decoder = ffmpeg::codec::context::Context::from_parameters(stream.parameters())?.decoder().video()?;
+    Ok(Source { ictx, decoder, stream_index, time_base, draining: false, frames: 0 })
+  }
+
+  // Decode the next frame into `frame`. Returns false at the end of the stream.
+  fn next(&mut self, frame: &mut ffmpeg::util::frame::video::Video) -> Result<bool, ffmpeg::Error> {
+    loop {
+      match self.decoder.receive_frame(frame) {
+        Ok(()) => {
+          self.frames += 1;
+          return Ok(true);
+        }
+        Err(ffmpeg::Error::Eof) => return Ok(false),
+        Err(ffmpeg::Error::Other { errno }) if errno == ffmpeg::ffi::EAGAIN => {}
+        Err(e) => return Err(e),
+      }
+      if self.draining {
+        return Ok(false);
+      }
+      let mut packet = ffmpeg::Packet::empty();
+      match packet.read(&mut self.ictx) {
+        Ok(()) if packet.stream() == self.stream_index => self.decoder.send_packet(&packet)?,
+        Ok(()) => {}
+        Err(ffmpeg::Error::Eof) => {
+          self.decoder.send_eof()?;
+          self.draining = true;
+        }
+        Err(e) => return Err(e),
+      }
+    }
+  }
+}
+
+// Converts a frame to 8-bit luma at the reference size; the test side also scales when the
+// resolutions differ. Rebuilt when the input format or size changes mid-stream.
+struct Luma {
+  scaler: Option<(scaling::Context, (Pixel, u32, u32))>,
+  frame: ffmpeg::util::frame::video::Video,
+}
+
+impl Luma {
+  fn new() -> Self {
+    Luma { scaler: None, frame: ffmpeg::util::frame::video::Video::empty() }
+  }
+
+  fn convert(&mut self, frame: &ffmpeg::util::frame::video::Video, width: u32, height: u32) -> Result<(), ffmpeg::Error> {
+    let input = (frame.format(), frame.width(), frame.height());
+    if self.scaler.as_ref().is_none_or(|(_, made_for)| *made_for != input) {
+      let context = scaling::Context::get(input.0, input.1, input.2, Pixel::GRAY8, width, height,
+        scaling::Flags::BICUBIC)?;
+      self.scaler = Some((context, input));
+    }
+    match self.scaler.as_mut() {
+      Some((scaler, _)) => scaler.run(frame, &mut self.frame),
+      None => Ok(()),
+    }
+  }
+
+  // Sum of squared differences to another converted frame, and the number of samples.
+  fn squared_error(&self, other: &Luma) -> (u64, u64) {
+    let (width, height) = (self.frame.width() as usize, self.frame.height() as usize);
+    let (a, b) = (self.frame.data(0), other.frame.data(0));
+    let (stride_a, stride_b) = (self.frame.stride(0), other.frame.stride(0));
+    let mut sum = 0u64;
+    for row in 0..height {
+      let (line_a, line_b) = (&a[row * stride_a..row * stride_a + width], &b[row * stride_b..row * stride_b + width]);
+      sum += line_a.iter().zip(line_b).map(|(&x, &y)| (x as i64 - y as i64).pow(2) as u64).sum::<u64>();
+    }
+    (sum, (width * height) as u64)
+  }
+}
+
+fn psnr(squared_error: u64, samples: u64) -> f64 {
+  if squared_error == 0 {
+    return f64::INFINITY;
+  }
+  let mse = squared_error as f64 / samples as f64;
+  10.0 * (255.0 * 255.0 / mse).log10()
+}
+
+fn describe_psnr(psnr: f64) -> String {
+  if psnr.is_infinite() { "infinite (identical)".to_string() } else { format!("{:.2} dB", psnr) }
+}
+
+fn describe_frame(index: i64, frame: &ffmpeg::util::frame::video::Video, time_base: Rational) -> String {
+  let time = frame.timestamp().and_then(|ts| timestamp_to_signed_millis(ts, time_base));
+  format!("frame {} at {}", format_with_commas(index), time.map_or_else(|| "unknown".to_string(), format_signed_millis))
+}
+
+// Compare the test file against the reference and print the result. Returns whether the two have
+// the same number of frames and identical luma in every one.
+pub fn run(reference_path: &Path, test_path: &Path) -> Result<bool, AnalyzeError> {
+  let mut reference = Source::open(reference_path)?;
+  let mut test = Source::open(test_path)?;
+  println!("{}Compare - {}", BLUE_BOLD, RESET);
+  println!("Reference: {} (stream #{}, {}x{})", reference_path.display(), reference.stream_index,
+    reference.decoder.width(), reference.decoder.height());
+  println!("Test: {} (stream #{}, {}x{})", test_path.display(), test.stream_index, test.decoder.width(),
+    test.decoder.height());
+  let (width, height) = (reference.decoder.width(), reference.decoder.height());
+  if (test.decoder.width(), test.decoder.height()) != (width, height) {
+    println!("Resolutions differ: the test frames are scaled to {}x{} before comparing", width, height);
+  }
+
+  let (mut reference_frame, mut test_frame) =
+    (ffmpeg::util::frame::video::Video::empty(), ffmpeg::util::frame::video::Video::empty());
+  let (mut reference_luma, mut test_luma) = (Luma::new(), Luma::new());
+  let mut first_difference: Option<(String, String)> = None;
+  let (mut psnr_sum, mut psnr_frames, mut min_psnr) = (0.0, 0u64, f64::INFINITY);
+  let mut compared = 0i64;
+  loop {
+    let has_reference = reference.next(&mut reference_frame).map_err(AnalyzeError::DecodeError)?;
+    let has_test = test.next(&mut test_frame).map_err(AnalyzeError::DecodeError)?;
+    if !has_reference || !has_test {
+      // Count the rest of the longer stream for the frame count comparison.
+      while has_reference && reference.next(&mut reference_frame).map_err(AnalyzeError::DecodeError)? {}
+      while has_test && test.next(&mut test_frame).map_err(AnalyzeError::DecodeError)? {}
+      break;
+    }
+    reference_luma.convert(&reference_frame, width, height)?;
+    test_luma.convert(&test_frame, width, height)?;
+    let (squared_error, samples) = reference_luma.squared_error(&test_luma);
+    let frame_psnr = psnr(squared_error, samples);
+    if squared_error > 0 && first_difference.is_none() {
+      first_difference = Some((describe_frame(compared, &reference_frame, reference.time_base),
+        describe_frame(compared, &test_frame, test.time_base)));
+    }
+    if frame_psnr.is_finite() {
+      psnr_sum += frame_psnr;
+      psnr_frames += 1;
+    }
+    min_psnr = min_psnr.min(frame_psnr);
+    compared += 1;
+  }
+
+  println!("Frames: reference {}, test {}{}", format_with_commas(reference.frames), format_with_commas(test.frames),
+    if reference.frames == test.frames { "" } else { " (MISMATCH)" });
+  println!("Frames compared: {}", format_with_commas(compared));
+  match &first_difference {
+    Some((reference_at, test_at)) => println!("First differing frame: reference {}, test {}", reference_at, test_at),
+    None => println!("First differing frame: none, the luma of every compared frame is identical"),
+  }
+  if compared > 0 {
+    let average = if psnr_frames > 0 { psnr_sum / psnr_frames as f64 } else { f64::INFINITY };
+    println!("Luma PSNR: average {}, minimum {}{}", describe_psnr(average), describe_psnr(min_psnr),
+      if psnr_frames > 0 && (psnr_frames as i64) < compared { " (average over the frames that differ)" } else { "" });
+  }
+  let identical = first_difference.is_none() && reference.frames == test.frames;
+  println!("{}", if identical { "Files match." } else { "Files DIFFER." });
+  Ok(identical)
+}
@@ -0,0 +1,143 @@
+// Internal consistency checks run after the analysis. Figures that can't all be true at once
+// point at a bug in the analyzer or at a file odd enough to deserve a closer look, so each
+// disagreement is reported as an internal finding with both values.
+
+use crate::findings::{self, Finding};
+
+// The figures the checks compare, gathered from the decode statistics and the container.
+#[derive(Default)]
+pub struct Figures {
+  pub frame_count: i64,
+  pub keyframe_count: i64,
+  // Index of the last key frame within the decoded frames.
+  pub last_keyframe_index: i64,
+  // Decoded frames per picture type: I, P, B and everything else. None when the frames were
+  // counted from packets.
+  pub frame_types: Option<[i64; 4]>,
+  pub measured_duration_ms: Option<u64>,
+  pub container_duration_ms: Option<u64>,
+  // Bytes of packets read per stream.
+  pub stream_bytes: Vec<u64>,
+  pub file_size: Option<u64>,
+}
+
+// A measured duration may exceed the declared one by this fraction before it is flagged.
+const DURATION_TOLERANCE: f64 = 0.10;
+
+fn inconsistency(message: String) -> Finding {
+  Finding::new(&findings::INCONSISTENT_FIGURES, message)
+}
+
+// Run every check and return one finding per inconsistency.
+pub fn check(figures: &Figures) -> Vec<Finding> {
+  let mut issues = Vec::new();
+
+  if let Some(frame_types) = figures.frame_types {
+    let typed_frames: i64 = frame_types.iter().sum();
+    if typed_frames != figures.frame_count {
+      issues.push(inconsistency(format!(
+        "frame type counts sum to {} but {} frames were counted", typed_frames, figures.frame_count)));
+    }
+  }
+
+  if figures.frame_count > 0 && figures.keyframe_count == 0 {
+    issues.push(inconsistency(format!(
+      "{} frames were decoded but none of them is a key frame", figures.frame_count)));
+  }
+
+  if figures.frame_count > 0 && figures.last_keyframe_index >= figures.frame_count {
+    issues.push(inconsistency(format!(
+      "last key frame index {} is not below the frame count {}", figures.last_keyframe_index, figures.frame_count)));
+  }
+
+  if let (Some(measured), Some(declared)) = (figures.measured_duration_ms, figures.container_duration_ms) {
+    if declared > 0 && measured as f64 > declared as f64 * (1.0 + DURATION_TOLERANCE) {
+      issues.push(inconsistency(format!(
+        "measured duration {}ms exceeds the container duration {}ms by more than {}%",
+        measured, declared, (DURATION_TOLERANCE * 100.0) as u32)));
+    }
+  }
+
+  if let Some(file_size) = figures.file_size {
+    let stream_bytes: u64 = figures.stream_bytes.iter().sum();
+    if stream_bytes > file_size {
+      issues.push(inconsistency(format!(
+        "packets of all streams add up to {} bytes, more than the file size of {} bytes", stream_bytes, file_size)));
+    }
+  }
+
+  issues
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn consistent() -> Figures {
+    Figures {
+      frame_count: 250,
+      keyframe_count: 10,
+      last_keyframe_index: 225,
+      frame_types: Some([10, 80, 160, 0]),
+      measured_duration_ms: Some(10_000),
+      container_duration_ms: Some(10_000),
+      stream_bytes: vec![900_000, 160_000],
+      file_size: Some(1_100_000),
+    }
+  }
+
+  fn messages(figures: &Figures) -> Vec<String> {
+    check(figures).into_iter().map(|finding| finding.message).collect()
+  }
+
+  #[test]
+  fn consistent_figures_pass() {
+    assert!(check(&consistent()).is_empty());
+    assert!(check(&Figures::default()).is_empty());
+  }
+
+  #[test]
+  fn frame_types_must_add_up() {
+    let figures = Figures { frame_types: Some([10, 80, 150, 0]), ..consistent() };
+    assert_eq!(messages(&figures), ["frame type counts sum to 240 but 250 frames were counted"]);
+    // Frames counted from packets have no picture types to compare.
+    assert!(check(&Figures { frame_types: None, frame_count: 260, ..consistent() }).is_empty());
+  }
+
+  #[test]
+  fn frames_without_key_frame() {
+    let figures = Figures { keyframe_count: 0, ..consistent() };
+    assert_eq!(messages(&figures), ["250 frames were decoded but none of them is a key frame"]);
+  }
+
+  #[test]
+  fn last_key_frame_beyond_the_frames() {
+    let figures = Figures { last_keyframe_index: 250, ..consistent() };
+    assert_eq!(messages(&figures), ["last key frame index 250 is not below the frame count 250"]);
+  }
+
+  #[test]
+  fn measured_duration_within_tolerance() {
+    assert!(check(&Figures { measured_duration_ms: Some(11_000), ..consistent() }).is_empty());
+    let figures = Figures { measured_duration_ms: Some(11_001), ..consistent() };
+    assert_eq!(messages(&figures), ["measured duration 11001ms exceeds the container duration 10000ms by more than 10%"]);
+    // A shorter measurement is the duration check's business, and a zero declared duration is no reference.
+    assert!(check(&Figures { measured_duration_ms: Some(1_000), ..consistent() }).is_empty());
+    assert!(check(&Figures { container_duration_ms: Some(0), ..consistent() }).is_empty());
+  }
+
+  #[test]
+  fn packets_larger_than_the_file() {
+    let figures = Figures { file_size: Some(1_000_000), ..consistent() };
+    assert_eq!(messages(&figures), ["packets of all streams add up to 1060000 bytes, more than the file size of 1000000 bytes"]);
+    assert!(check(&Figures { file_size: None, ..consistent() }).is_empty());
+  }
+
+  #[test]
+  fn every_inconsistency_is_reported() {
+    let figures = Figures { keyframe_count: 0, last_keyframe_index: 300, file_size: Some(1), ..consistent() };
+    let findings = check(&figures);
+    assert_eq!(findings.len(), 3);
+    assert!(findings.iter().all(|finding| finding.definition.code == findings::INCONSISTENT_FIGURES.code));
+  }
+}
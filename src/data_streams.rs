@@ -0,0 +1,186 @@
+// Data streams: timed metadata such as GoPro's GPMF telemetry, KLV in broadcast and drone TS
+// files, and SCTE-35 ad markers. Each is listed with its codec, tag and handler name, its packet
+// count and bytes, and the time range of its packets, whether FFmpeg knows the codec or not. For
+// SCTE-35 every splice_info_section is counted and listed with its command and splice time, to
+// verify that ad markers survived a transcode.
+
+use ffmpeg::codec::Id;
+use ffmpeg::media::Type;
+use ffmpeg::Rational;
+
+use crate::timestamp::Timestamp;
+use crate::{format_bytes, format_signed_millis, format_with_commas, timestamp_to_signed_millis, BLUE_BOLD, RESET};
+
+// SCTE-35 splice times count a 90 kHz clock, wrapping at 33 bits.
+const SPLICE_TIME_BASE: Rational = Rational(1, 90_000);
+const PTS_WRAP: i64 = 1 << 33;
+
+// table_id of a splice_info_section.
+const SPLICE_INFO_TABLE_ID: u8 = 0xFC;
+
+pub struct Splice {
+  // Presentation timestamp of the packet, in the stream time base.
+  pub packet_timestamp: Option<i64>,
+  // splice_command_type; None when the section couldn't be parsed.
+  pub command: Option<u8>,
+  // pts_time of the command with pts_adjustment applied, for splice_insert and time_signal.
+  pub splice_time: Option<i64>,
+}
+
+pub struct DataStream {
+  pub index: usize,
+  pub codec: String,
+  // Four-character code of the container, e.g. "gpmd", or the raw value when it isn't printable.
+  pub tag: Option<String>,
+  pub handler: Option<String>,
+  pub time_base: Rational,
+  pub packets: u64,
+  pub bytes: u64,
+  // Lowest and highest packet timestamps.
+  pub first_timestamp: Option<i64>,
+  pub last_timestamp: Option<i64>,
+  // The splice messages of an SCTE-35 stream; None for other codecs.
+  pub splices: Option<Vec<Splice>>,
+}
+
+fn describe_tag(tag: u32) -> Option<String> {
+  if tag == 0 {
+    return None;
+  }
+  let bytes = tag.to_le_bytes();
+  Some(if bytes.iter().all(|b| b.is_ascii_graphic() || *b == b' ') {
+    String::from_utf8_lossy(&bytes).into_owned()
+  } else {
+    format!("0x{:08x}", tag)
+  })
+}
+
+pub fn command_name(command: u8) -> &'static str {
+  match command {
+    0x00 => "splice_null",
+    0x04 => "splice_schedule",
+    0x05 => "splice_insert",
+    0x06 => "time_signal",
+    0x07 => "bandwidth_reservation",
+    0xFF => "private_command",
+    _ => "reserved",
+  }
+}
+
+// The 33-bit pts_time of a splice_time(), None when time_specified_flag is clear.
+fn splice_time(data: &[u8]) -> Option<i64> {
+  let bytes = data.get(..5)?;
+  if bytes[0] & 0x80 == 0 {
+    return None;
+  }
+  Some(((bytes[0] as i64 & 0x01) << 32) | (u32::from_be_bytes([bytes[1], bytes[2], bytes[3], bytes[4]]) as i64))
+}
+
+// The command type and splice time of a splice_info_section (SCTE 35, section 9.6).
+fn parse_splice(section: &[u8]) -> Option<(u8, Option<i64>)> {
+  if section.len() < 14 || section[0] != SPLICE_INFO_TABLE_ID {
+    return None;
+  }
+  // An encrypted command can't be read.
+  let encrypted = section[4] & 0x80 != 0;
+  let adjustment = ((section[4] as i64 & 0x01) << 32) | (u32::from_be_bytes([section[5], section[6], section[7], section[8]]) as i64);
+  let command = section[13];
+  let body = &section[14..];
+  let time = match command {
+    _ if encrypted => None,
+    0x06 => splice_time(body),
+    0x05 => {
+      // splice_event_id, then the cancel flag and, unless cancelled, the flags of the insert.
+      let flags = *body.get(5)?;
+      let cancelled = body.get(4)? & 0x80 != 0;
+      let program_splice = flags & 0x40 != 0;
+      let immediate = flags & 0x10 != 0;
+      (!cancelled && program_splice && !immediate).then(|| splice_time(&body[6..])).flatten()
+    }
+    _ => None,
+  };
+  Some((command, time.map(|time| (time + adjustment) % PTS_WRAP)))
+}
+
+impl DataStream {
+  // Count a packet of this stream.
+  pub fn push(&mut self, packet: &ffmpeg::Packet) {
+    self.packets += 1;
+    self.bytes += packet.size() as u64;
+    let timestamp = packet.pts().or(packet.dts());
+    if let Some(ts) = timestamp {
+      self.first_timestamp = Some(self.first_timestamp.map_or(ts, |t| t.min(ts)));
+      self.last_timestamp = Some(self.last_timestamp.map_or(ts, |t| t.max(ts)));
+    }
+    if let Some(splices) = self.splices.as_mut() {
+      let parsed = packet.data().and_then(parse_splice);
+      splices.push(Splice {
+        packet_timestamp: timestamp,
+        command: parsed.map(|(command, _)| command),
+        splice_time: parsed.and_then(|(_, time)| time),
+      });
+    }
+  }
+}
+
+// Every data stream of the input.
+pub fn open(ictx: &ffmpeg::format::context::Input) -> Vec<DataStream> {
+  ictx
+    .streams()
+    .filter(|stream| stream.parameters().medium() == Type::Data)
+    .map(|stream| {
+      let parameters = stream.parameters();
+      let id = parameters.id();
+      let tag = unsafe { (*parameters.as_ptr()).codec_tag };
+      DataStream {
+        index: stream.index(),
+        codec: if id == Id::None { "unknown".to_string() } else { id.name().to_string() },
+        tag: describe_tag(tag),
+        handler: stream.metadata().get("handler_name").map(str::to_string),
+        time_base: stream.time_base(),
+        packets: 0,
+        bytes: 0,
+        first_timestamp: None,
+        last_timestamp: None,
+        splices: (id == Id::SCTE_35).then(Vec::new),
+      }
+    })
+    .collect()
+}
+
+// Milliseconds of a splice time.
+pub fn splice_millis(time: i64) -> Option<i64> {
+  timestamp_to_signed_millis(time, SPLICE_TIME_BASE)
+}
+
+pub fn splice_timestamp(time: i64) -> Timestamp {
+  Timestamp::new(time, SPLICE_TIME_BASE)
+}
+
+// Print one line per data stream and the splice messages of SCTE-35 streams; nothing for files
+// without any.
+pub fn report(streams: &[DataStream]) {
+  if streams.is_empty() {
+    return;
+  }
+  out!("{}{}Data streams - {}", RESET, BLUE_BOLD, RESET);
+  for stream in streams {
+    let millis = |ts: Option<i64>| ts.and_then(|ts| timestamp_to_signed_millis(ts, stream.time_base));
+    out!("#{}: {}{}{}, {} packets, {}, packets from {} to {}", stream.index, stream.codec,
+      stream.tag.as_deref().map_or_else(String::new, |tag| format!(", tag {}", tag)),
+      stream.handler.as_deref().map_or_else(String::new, |h| format!(", handler '{}'", h)),
+      format_with_commas(stream.packets as i64), format_bytes(stream.bytes),
+      millis(stream.first_timestamp).map_or_else(|| "unknown".to_string(), format_signed_millis),
+      millis(stream.last_timestamp).map_or_else(|| "unknown".to_string(), format_signed_millis));
+    let Some(splices) = &stream.splices else {
+      continue;
+    };
+    out!("SCTE-35 splice messages: {}", format_with_commas(splices.len() as i64));
+    for splice in splices {
+      out!("  at {}: {}{}", millis(splice.packet_timestamp).map_or_else(|| "unknown".to_string(), format_signed_millis),
+        splice.command.map_or("unreadable section", command_name),
+        splice.splice_time.and_then(splice_millis)
+          .map_or_else(String::new, |ms| format!(", splice time {}", format_signed_millis(ms))));
+    }
+  }
+}
@@ -0,0 +1,177 @@
+// The demuxing side of decode_packets: a thread of its own reads the packets, recovers from read
+// errors and hands every packet over a bounded channel, so the decoder keeps working while a
+// read waits on a slow network mount. The channel holds at most QUEUE_PACKETS packets, which
+// caps the memory of packets read ahead of the decoder. Packets the decoder is done with come back
+// over a second channel and are read into again, so the packets are allocated once, not per read.
+
+use std::ops::Deref;
+use std::sync::mpsc::{Receiver, Sender, SyncSender};
+use std::time::Instant;
+
+use ffmpeg::Rational;
+use indicatif::ProgressBar;
+
+use crate::{follow, io_retry, network};
+
+pub const QUEUE_PACKETS: usize = 64;
+
+// How the reader walks the input.
+#[derive(Clone, Copy)]
+pub struct Config {
+  pub video_stream_index: usize,
+  pub time_base: Rational,
+  // Stop at the first packet starting at or past this byte offset.
+  pub byte_limit: Option<i64>,
+  // Read errors to recover from before giving up, and the pause before each attempt. With no
+  // retries, read errors are skipped as the packet iterator always did.
+  pub io_retries: u32,
+  pub io_retry_delay_ms: u64,
+  // Read timeout of a URL input when it is re-opened.
+  pub network_timeout_seconds: u64,
+  // Wait for a growing input at its end instead of finishing.
+  pub follow: Option<follow::Settings>,
+}
+
+// A packet received from the reader, handed back to it to read into again when dropped.
+pub struct Recycled<'a> {
+  packet: Option<ffmpeg::Packet>,
+  pool: &'a Sender<ffmpeg::Packet>,
+}
+
+impl<'a> Recycled<'a> {
+  pub fn new(packet: ffmpeg::Packet, pool: &'a Sender<ffmpeg::Packet>) -> Self {
+    Recycled { packet: Some(packet), pool }
+  }
+}
+
+impl Deref for Recycled<'_> {
+  type Target = ffmpeg::Packet;
+
+  fn deref(&self) -> &ffmpeg::Packet {
+    self.packet.as_ref().expect("packet taken before drop")
+  }
+}
+
+impl Drop for Recycled<'_> {
+  fn drop(&mut self) {
+    // The reader is gone once the input ended; the packet is freed then.
+    if let Some(packet) = self.packet.take() {
+      let _ = self.pool.send(packet);
+    }
+  }
+}
+
+// Read packets into `sender` until EOF, the byte limit or the receiver hanging up; when following,
+// until Ctrl-C or the input stops growing. Returns the read errors recovered from, or the error
+// that ended the input when the retries ran out. Packets are taken from `recycled` while it has
+// any.
+pub fn read(
+  ictx: &mut ffmpeg::format::context::Input,
+  config: Config,
+  sender: SyncSender<ffmpeg::Packet>,
+  recycled: Receiver<ffmpeg::Packet>,
+  pb: &ProgressBar,
+) -> Result<Vec<io_retry::IoRetry>, ffmpeg::Error> {
+  let mut retries = Vec::new();
+  // Last packet read, and the one to skip through after re-opening the input.
+  let mut last = io_retry::ResumePoint { position: -1, video_dts: None };
+  let mut skip_through: Option<io_retry::ResumePoint> = None;
+  // The error and the number of attempts since the last successful read. An EOF right after an
+  // error means the I/O layer gave up, not that the input ended.
+  let mut pending_error: Option<ffmpeg::Error> = None;
+  let mut consecutive_failures = 0;
+  // When the last packet was read, and whether the reader is waiting for the input to grow.
+  let mut last_packet = Instant::now();
+  let mut waiting = false;
+  // The packet to read into, kept over failed and skipped reads.
+  let mut packet = ffmpeg::Packet::empty();
+
+  loop {
+    if config.follow.is_some() && follow::interrupted() {
+      return Ok(retries);
+    }
+    // av_read_frame doesn't release what the packet still holds, so the previous payload is
+    // unreferenced first.
+    unsafe {
+      ffmpeg::ffi::av_packet_unref(ffmpeg::packet::Mut::as_mut_ptr(&mut packet));
+    }
+    let error = match packet.read(ictx) {
+      Ok(()) => None,
+      Err(ffmpeg::Error::Eof) => match (pending_error.take(), config.follow) {
+        (Some(error), _) => Some(error),
+        (None, Some(settings)) if follow::wait(ictx, settings, last_packet) => {
+          if !waiting {
+            pb.set_message("Waiting for the input to grow...");
+            waiting = true;
+          }
+          continue;
+        }
+        (None, _) => return Ok(retries),
+      },
+      Err(error) => Some(error),
+    };
+    if let Some(error) = error {
+      if config.io_retries == 0 {
+        // A timed out connection keeps failing; skipping its errors would spin on it forever.
+        if network::is_timeout(&error) {
+          return Err(error);
+        }
+        continue;
+      }
+      if retries.len() as u32 >= config.io_retries {
+        return Err(error);
+      }
+      std::thread::sleep(std::time::Duration::from_millis(config.io_retry_delay_ms));
+      // Read again in place first; if that fails as well, start over from a fresh input.
+      let reopened = consecutive_failures > 0;
+      retries.push(io_retry::IoRetry {
+        position: last.position,
+        timestamp: last.video_dts,
+        error: error.to_string(),
+        reopened,
+      });
+      consecutive_failures += 1;
+      pending_error = Some(error);
+      if reopened {
+        pb.set_message("Re-opening input after read errors...");
+        io_retry::reopen(ictx, last, config.time_base, config.network_timeout_seconds)?;
+        skip_through = Some(last);
+      }
+      continue;
+    }
+    consecutive_failures = 0;
+    pending_error = None;
+    last_packet = Instant::now();
+    if waiting {
+      pb.set_message("Processing packets...");
+      waiting = false;
+    }
+
+    let is_video = packet.stream() == config.video_stream_index;
+    if let Some(resume) = skip_through {
+      if resume.already_processed(packet.position() as i64, is_video, packet.dts()) {
+        continue;
+      }
+      skip_through = None;
+    }
+    last.position = packet.position() as i64;
+    if is_video && packet.dts().is_some() {
+      last.video_dts = packet.dts();
+    }
+
+    // Stop once the window is exhausted. Packets without a known position never end the window.
+    if let Some(limit) = config.byte_limit {
+      if packet.position() >= 0 && packet.position() as i64 >= limit {
+        return Ok(retries);
+      }
+    }
+
+    // The next read goes into a packet the decoder handed back, or a new one while all of them
+    // are in flight.
+    let next = recycled.try_recv().unwrap_or_else(|_| ffmpeg::Packet::empty());
+    // The decoding side hung up: it stopped early or failed.
+    if sender.send(std::mem::replace(&mut packet, next)).is_err() {
+      return Ok(retries);
+    }
+  }
+}
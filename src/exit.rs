@@ -0,0 +1,160 @@
+// Exit statuses, one per outcome category, so scripts can tell a missing file from a damaged one
+// without parsing the output. With several input files, the run exits with the status of the first
+// file that didn't succeed.
+
+use video_analyzer::findings::{self, FindingDefinition};
+use video_analyzer::{AnalysisReport, AnalyzeError};
+
+pub const HELP: &str = "Exit status:
+  0  Success
+  1  Invalid arguments, a crashed or killed --isolate analysis, --fail-if-not-copyable,
+     --max-keyframe-tail, --require-profile, other errors
+  2  The input could not be opened
+  3  No video stream, with --require-video
+  4  Decode errors
+  5  The container duration and the last frame time differ beyond --tolerance-ms
+  6  Timestamp anomalies, with --fail-on-timestamp-errors
+  7  Interlaced frames, with --fail-on-interlaced
+  8  Strict QC violations, with --strict
+  9  An audio stream's duration differs from the video's beyond --av-drift-tolerance-ms";
+
+// Whether the report has a finding with the definition's code.
+pub fn has_finding(report: &AnalysisReport, definition: &FindingDefinition) -> bool {
+  report.findings.iter().any(|f| f.code == definition.code)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Status {
+  Success,
+  Failure,
+  OpenFailed,
+  NoVideo,
+  DecodeErrors,
+  DurationMismatch,
+  TimestampErrors,
+  Interlaced,
+  StrictViolations,
+  AvDrift,
+}
+
+impl Status {
+  pub fn code(self) -> i32 {
+    match self {
+      Status::Success => 0,
+      Status::Failure => 1,
+      Status::OpenFailed => 2,
+      Status::NoVideo => 3,
+      Status::DecodeErrors => 4,
+      Status::DurationMismatch => 5,
+      Status::TimestampErrors => 6,
+      Status::Interlaced => 7,
+      Status::StrictViolations => 8,
+      Status::AvDrift => 9,
+    }
+  }
+
+  // The status an isolated child exited with.
+  pub fn from_code(code: i32) -> Self {
+    match code {
+      0 => Status::Success,
+      2 => Status::OpenFailed,
+      3 => Status::NoVideo,
+      4 => Status::DecodeErrors,
+      5 => Status::DurationMismatch,
+      6 => Status::TimestampErrors,
+      7 => Status::Interlaced,
+      8 => Status::StrictViolations,
+      9 => Status::AvDrift,
+      _ => Status::Failure,
+    }
+  }
+
+  // The status of an analysis that ended with an error.
+  pub fn from_error(error: &AnalyzeError) -> Self {
+    match error {
+      AnalyzeError::OpenError(_) | AnalyzeError::MoovNotAtStart => Status::OpenFailed,
+      AnalyzeError::NoVideoStreamError => Status::NoVideo,
+      AnalyzeError::DecodeError(_) => Status::DecodeErrors,
+      _ => Status::Failure,
+    }
+  }
+
+  // The status of a completed analysis, from the findings in its report. An audio-only file is
+  // only a failure with --require-video.
+  pub fn from_report(report: &AnalysisReport, require_video: bool) -> Self {
+    let has = |definition: &FindingDefinition| has_finding(report, definition);
+    if require_video && has(&findings::NO_VIDEO_STREAM) {
+      Status::NoVideo
+    } else if has(&findings::DECODE_ERRORS) || has(&findings::TRUNCATED) {
+      Status::DecodeErrors
+    } else if has(&findings::DURATION_MISMATCH) {
+      Status::DurationMismatch
+    } else if has(&findings::AUDIO_DURATION_MISMATCH) {
+      Status::AvDrift
+    } else {
+      Status::Success
+    }
+  }
+
+  // Keep the first status that isn't a success.
+  pub fn or(self, other: Status) -> Self {
+    if self == Status::Success { other } else { self }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use video_analyzer::findings::Finding;
+  use video_analyzer::report::FindingReport;
+
+  fn report_with(definitions: &[&'static FindingDefinition]) -> AnalysisReport {
+    AnalysisReport {
+      findings: definitions.iter().map(|definition| FindingReport::from(&Finding::new(definition, ""))).collect(),
+      ..AnalysisReport::default()
+    }
+  }
+
+  #[test]
+  fn audio_only_succeeds_without_require_video() {
+    let report = report_with(&[&findings::NO_VIDEO_STREAM]);
+    assert_eq!(Status::from_report(&report, false), Status::Success);
+    assert_eq!(Status::from_report(&report, false).code(), 0);
+  }
+
+  #[test]
+  fn audio_only_fails_with_require_video() {
+    let report = report_with(&[&findings::NO_VIDEO_STREAM]);
+    assert_eq!(Status::from_report(&report, true), Status::NoVideo);
+    assert_eq!(Status::from_report(&report, true).code(), 3);
+  }
+
+  #[test]
+  fn audio_only_still_reports_other_failures() {
+    let report = report_with(&[&findings::NO_VIDEO_STREAM, &findings::AUDIO_DURATION_MISMATCH]);
+    assert_eq!(Status::from_report(&report, false), Status::AvDrift);
+  }
+
+  #[test]
+  fn findings_map_to_statuses() {
+    assert_eq!(Status::from_report(&report_with(&[]), false), Status::Success);
+    assert_eq!(Status::from_report(&report_with(&[&findings::TRUNCATED]), false), Status::DecodeErrors);
+    assert_eq!(Status::from_report(&report_with(&[&findings::DURATION_MISMATCH, &findings::AUDIO_DURATION_MISMATCH]), false),
+      Status::DurationMismatch);
+  }
+
+  #[test]
+  fn codes_round_trip() {
+    for status in [Status::Success, Status::OpenFailed, Status::NoVideo, Status::DecodeErrors, Status::DurationMismatch,
+      Status::TimestampErrors, Status::Interlaced, Status::StrictViolations, Status::AvDrift] {
+      assert_eq!(Status::from_code(status.code()), status);
+    }
+    assert_eq!(Status::from_code(139), Status::Failure);
+  }
+
+  #[test]
+  fn first_failure_wins() {
+    assert_eq!(Status::Success.or(Status::NoVideo), Status::NoVideo);
+    assert_eq!(Status::DecodeErrors.or(Status::NoVideo), Status::DecodeErrors);
+  }
+}
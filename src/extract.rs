@@ -0,0 +1,151 @@
+// --extract-problems: stream-copy the region around each located error finding into a small
+// Matroska file, so a report comes with reproducers that can be sent to FFmpeg developers or a
+// vendor.
+
+use std::path::{Path, PathBuf};
+
+use ffmpeg::{Rational, Rescale};
+
+use crate::findings::{Finding, Severity};
+use crate::timestamp::Timestamp;
+use crate::{format_with_commas, BLUE_BOLD, RESET};
+
+// Caps on what a single run writes.
+const MAX_EXTRACTS: usize = 10;
+const MAX_TOTAL_BYTES: u64 = 512 * 1024 * 1024;
+
+pub const DEFAULT_CONTEXT_SECONDS: f64 = 5.0;
+
+// Where the extracts go and the margin kept around each region (--extract-problems,
+// --context-seconds).
+pub struct Settings {
+  pub dir: PathBuf,
+  pub context_seconds: f64,
+}
+
+// A file written for one finding.
+pub struct Extract {
+  pub path: PathBuf,
+  pub bytes: u64,
+  pub code: &'static str,
+}
+
+// File name from the finding code and the position, e.g. "DECODE_ERRORS_00h41m12s345.mkv".
+fn file_name(code: &str, timestamp: Timestamp) -> String {
+  let ms = timestamp.millis().unwrap_or(0);
+  format!("{}_{:02}h{:02}m{:02}s{:03}.mkv", code, ms / 3_600_000, (ms % 3_600_000) / 60_000, (ms % 60_000) / 1000, ms % 1000)
+}
+
+// Copy [start, end) of the video stream (and whatever the other streams interleave with it)
+// into `output`, starting at the key frame at or before `start`. Writing stops early once
+// `byte_budget` is used up. Returns the number of payload bytes written.
+fn copy_region(
+  path: &str,
+  output: &Path,
+  video_stream_index: usize,
+  start: i64,
+  end: i64,
+  byte_budget: u64,
+) -> Result<u64, ffmpeg::Error> {
+  let mut ictx = ffmpeg::format::input(&path)?;
+  let time_base = ictx.stream(video_stream_index).ok_or(ffmpeg::Error::StreamNotFound)?.time_base();
+  let mut octx = ffmpeg::format::output(&output)?;
+
+  // Map every audio, video and subtitle stream; data streams rarely survive a container change.
+  let mut stream_mapping = vec![None; ictx.nb_streams() as usize];
+  let mut input_time_bases = vec![Rational::new(0, 1); ictx.nb_streams() as usize];
+  let mut output_index = 0;
+  for stream in ictx.streams() {
+    let medium = stream.parameters().medium();
+    if !matches!(medium, ffmpeg::media::Type::Audio | ffmpeg::media::Type::Video | ffmpeg::media::Type::Subtitle) {
+      continue;
+    }
+    stream_mapping[stream.index()] = Some(output_index);
+    input_time_bases[stream.index()] = stream.time_base();
+    output_index += 1;
+    let mut output_stream = octx.add_stream(ffmpeg::encoder::find(ffmpeg::codec::Id::None))?;
+    output_stream.set_parameters(stream.parameters());
+    // The source container's codec tag may be invalid in Matroska.
+    unsafe {
+      (*output_stream.parameters().as_mut_ptr()).codec_tag = 0;
+    }
+  }
+  octx.write_header()?;
+
+  // ictx.seek takes AV_TIME_BASE units and lands on the key frame at or before the target.
+  let target = start.rescale(time_base, ffmpeg::rescale::TIME_BASE);
+  ictx.seek(target, ..target)?;
+
+  let mut written = 0u64;
+  for (stream, mut packet) in ictx.packets() {
+    let Some(index) = stream_mapping[stream.index()] else {
+      continue;
+    };
+    if stream.index() == video_stream_index && packet.dts().is_some_and(|dts| dts >= end) {
+      break;
+    }
+    if written + packet.size() as u64 > byte_budget {
+      break;
+    }
+    written += packet.size() as u64;
+    let output_time_base = octx.stream(index).map_or(time_base, |s| s.time_base());
+    packet.rescale_ts(input_time_bases[stream.index()], output_time_base);
+    packet.set_position(-1);
+    packet.set_stream(index);
+    packet.write_interleaved(&mut octx)?;
+  }
+  octx.write_trailer()?;
+  Ok(written)
+}
+
+// Extract the region around every error finding that has a timestamp, within the count and size
+// caps. Failures are reported per finding and don't stop the others.
+pub fn extract_problems(
+  path: &str,
+  findings: &[Finding],
+  video_stream_index: usize,
+  settings: &Settings,
+) -> Vec<Result<Extract, String>> {
+  let mut results = Vec::new();
+  if let Err(e) = std::fs::create_dir_all(&settings.dir) {
+    results.push(Err(format!("can't create {}: {}", settings.dir.display(), e)));
+    return results;
+  }
+
+  let mut total_bytes = 0u64;
+  let located = findings.iter().filter(|f| f.severity() == Severity::Error).filter_map(|f| Some((f, f.timestamp?)));
+  for (finding, timestamp) in located.take(MAX_EXTRACTS) {
+    if total_bytes >= MAX_TOTAL_BYTES {
+      results.push(Err(format!("{}: skipped, total extract size cap reached", finding.definition.code)));
+      continue;
+    }
+    let tb = timestamp.time_base;
+    let context = (settings.context_seconds * tb.denominator() as f64 / tb.numerator().max(1) as f64) as i64;
+    let output = settings.dir.join(file_name(finding.definition.code, timestamp));
+    let result = copy_region(path, &output, video_stream_index, timestamp.value - context, timestamp.value + context,
+      MAX_TOTAL_BYTES - total_bytes);
+    match result {
+      Ok(_) => {
+        let bytes = std::fs::metadata(&output).map_or(0, |m| m.len());
+        total_bytes += bytes;
+        results.push(Ok(Extract { path: output, bytes, code: finding.definition.code }));
+      }
+      Err(e) => results.push(Err(format!("{}: {}", finding.definition.code, e))),
+    }
+  }
+  results
+}
+
+// Print the problem extracts section with what extract_problems wrote or couldn't.
+pub fn report(results: &[Result<Extract, String>]) {
+  out!("{}{}Problem extracts - {}", RESET, BLUE_BOLD, RESET);
+  if results.is_empty() {
+    out!("No error findings with a position to extract.");
+  }
+  for result in results {
+    match result {
+      Ok(extract) => out!("{}: {} ({} bytes)", extract.code, extract.path.display(), format_with_commas(extract.bytes as i64)),
+      Err(message) => out!("Not extracted: {}", message),
+    }
+  }
+}
@@ -0,0 +1,184 @@
+// MP4/MOV layout: the order of the top-level boxes, read from their headers (see mp4.rs), and
+// whether the moov box, the index of the media, comes before the media data. Progressive download
+// needs it first ("faststart") to start playback before the whole file is in. Fragmented files
+// index their media in moof boxes along the way and are told apart.
+
+use std::fs::File;
+use std::io::{self, BufReader, Read, Seek};
+use std::path::Path;
+
+use crate::findings::{self, Finding};
+use crate::mp4;
+use crate::{format_with_commas, BLUE_BOLD, RESET};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Verdict {
+  Faststart,
+  MoovAfterMdat,
+  Fragmented,
+  NoMoov,
+}
+
+impl Verdict {
+  pub fn name(self) -> &'static str {
+    match self {
+      Verdict::Faststart => "yes",
+      Verdict::MoovAfterMdat => "no",
+      Verdict::Fragmented => "fragmented",
+      Verdict::NoMoov => "no moov box",
+    }
+  }
+}
+
+pub struct Layout {
+  // Top-level box types in the order they first appear, with how many of each there are.
+  pub order: Vec<(String, u64)>,
+  // Byte offsets of the moov box and of the first mdat box.
+  pub moov_offset: Option<u64>,
+  pub mdat_offset: Option<u64>,
+  pub fragments: u64,
+}
+
+impl Layout {
+  pub fn verdict(&self) -> Verdict {
+    match (self.moov_offset, self.mdat_offset) {
+      (None, _) => Verdict::NoMoov,
+      _ if self.fragments > 0 => Verdict::Fragmented,
+      (Some(moov), Some(mdat)) if mdat < moov => Verdict::MoovAfterMdat,
+      _ => Verdict::Faststart,
+    }
+  }
+}
+
+fn box_name(kind: &[u8; 4]) -> String {
+  if kind.iter().all(|b| b.is_ascii_graphic() || *b == b' ') {
+    String::from_utf8_lossy(kind).into_owned()
+  } else {
+    format!("0x{:08x}", u32::from_be_bytes(*kind))
+  }
+}
+
+// The layout of the top-level boxes of the first `file_size` bytes.
+fn layout<R: Read + Seek>(reader: &mut R, file_size: u64) -> io::Result<Layout> {
+  let mut layout = Layout { order: Vec::new(), moov_offset: None, mdat_offset: None, fragments: 0 };
+  for header in mp4::children(reader, 0, file_size)? {
+    let name = box_name(&header.kind);
+    match layout.order.iter_mut().find(|(kind, _)| *kind == name) {
+      Some((_, count)) => *count += 1,
+      None => layout.order.push((name, 1)),
+    }
+    if header.is(b"moov") {
+      layout.moov_offset.get_or_insert(header.start);
+    } else if header.is(b"mdat") {
+      layout.mdat_offset.get_or_insert(header.start);
+    } else if header.is(b"moof") {
+      layout.fragments += 1;
+    }
+  }
+  Ok(layout)
+}
+
+fn scan(path: &Path) -> io::Result<Layout> {
+  let file = File::open(path)?;
+  let file_size = file.metadata()?.len();
+  layout(&mut BufReader::new(file), file_size)
+}
+
+// The layout of a local MP4/MOV file; None for other containers, stdin and URLs.
+pub fn read(ictx: &ffmpeg::format::context::Input, path: &str) -> Option<io::Result<Layout>> {
+  let is_mp4 = ictx.format().name().split(',').any(|name| name == "mov" || name == "mp4");
+  let path = Path::new(path);
+  (is_mp4 && path.is_file()).then(|| scan(path))
+}
+
+// Print the layout; nothing for inputs that aren't local MP4/MOV files. A moov box after the media
+// data is a finding.
+pub fn report(layout: Option<&io::Result<Layout>>) -> Option<Finding> {
+  let layout = layout?;
+  out!("{}{}MP4 layout - {}", RESET, BLUE_BOLD, RESET);
+  let layout = match layout {
+    Ok(layout) => layout,
+    Err(err) => {
+      out!("Faststart: unknown, the box structure can't be read: {}", err);
+      return None;
+    }
+  };
+  let order: Vec<String> = layout.order.iter()
+    .map(|(kind, count)| if *count > 1 { format!("{} \u{d7}{}", kind, format_with_commas(*count as i64)) } else { kind.clone() })
+    .collect();
+  out!("Top-level boxes: {}", order.join(", "));
+  out!("moov offset: {}", layout.moov_offset.map_or_else(|| "none".to_string(),
+    |offset| format!("{} bytes", format_with_commas(offset as i64))));
+  let verdict = layout.verdict();
+  match verdict {
+    Verdict::Fragmented => out!("Faststart: fragmented ({} movie fragments)", format_with_commas(layout.fragments as i64)),
+    _ => out!("Faststart: {}", verdict.name()),
+  }
+  let (Verdict::MoovAfterMdat, Some(moov), Some(mdat)) = (verdict, layout.moov_offset, layout.mdat_offset) else {
+    return None;
+  };
+  Some(Finding::new(&findings::MOOV_AFTER_MDAT, format!("moov box at byte {} follows the media data at byte {}",
+    format_with_commas(moov as i64), format_with_commas(mdat as i64))))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::io::Cursor;
+
+  fn boxed(kind: &[u8; 4], payload: &[u8]) -> Vec<u8> {
+    [&((payload.len() + 8) as u32).to_be_bytes()[..], kind, payload].concat()
+  }
+
+  // A box with size 1 and a 64-bit largesize after its type, as mdat boxes over 4GB have.
+  fn large_boxed(kind: &[u8; 4], payload: &[u8]) -> Vec<u8> {
+    [&1u32.to_be_bytes()[..], kind, &((payload.len() + 16) as u64).to_be_bytes(), payload].concat()
+  }
+
+  fn layout_of(data: &[u8]) -> Layout {
+    layout(&mut Cursor::new(data), data.len() as u64).unwrap()
+  }
+
+  fn order(layout: &Layout) -> Vec<(&str, u64)> {
+    layout.order.iter().map(|(kind, count)| (kind.as_str(), *count)).collect()
+  }
+
+  #[test]
+  fn largesize_box_ahead_of_moov() {
+    let data = [boxed(b"ftyp", b"isom"), large_boxed(b"free", &[0; 8]), boxed(b"moov", &[0; 12]),
+      boxed(b"mdat", &[0; 32])].concat();
+    let layout = layout_of(&data);
+    assert_eq!(order(&layout), [("ftyp", 1), ("free", 1), ("moov", 1), ("mdat", 1)]);
+    // ftyp is 12 bytes and the free box 24, its 16-byte header included.
+    assert_eq!((layout.moov_offset, layout.mdat_offset), (Some(36), Some(56)));
+    assert_eq!(layout.verdict(), Verdict::Faststart);
+  }
+
+  #[test]
+  fn largesize_mdat_ahead_of_moov() {
+    let data = [boxed(b"ftyp", b"isom"), large_boxed(b"mdat", &[0; 40]), boxed(b"moov", &[0; 12])].concat();
+    let layout = layout_of(&data);
+    assert_eq!(order(&layout), [("ftyp", 1), ("mdat", 1), ("moov", 1)]);
+    assert_eq!((layout.mdat_offset, layout.moov_offset), (Some(12), Some(12 + 56)));
+    assert_eq!(layout.verdict(), Verdict::MoovAfterMdat);
+  }
+
+  #[test]
+  fn fragmented_and_missing_moov() {
+    let fragment = [boxed(b"moof", &[0; 8]), boxed(b"mdat", &[0; 8])].concat();
+    let data = [boxed(b"ftyp", b"iso6"), boxed(b"moov", &[]), fragment.clone(), fragment.clone()].concat();
+    let layout = layout_of(&data);
+    assert_eq!(order(&layout), [("ftyp", 1), ("moov", 1), ("moof", 2), ("mdat", 2)]);
+    assert_eq!(layout.fragments, 2);
+    assert_eq!(layout.verdict(), Verdict::Fragmented);
+    let layout = layout_of(&[boxed(b"ftyp", b"isom"), boxed(b"mdat", &[])].concat());
+    assert_eq!(layout.verdict(), Verdict::NoMoov);
+  }
+
+  #[test]
+  fn box_names() {
+    assert_eq!(box_name(b"moov"), "moov");
+    assert_eq!(box_name(b"ab  "), "ab  ");
+    assert_eq!(box_name(&[0xa9, b'n', b'a', b'm']), "0xa96e616d");
+  }
+}
@@ -0,0 +1,679 @@
+// Catalogue of the findings the analyzer can raise. Every finding is created from one of the
+// definitions below, so a code can't be reported without its documentation.
+
+use crate::timestamp::Timestamp;
+
+// Severity of a finding, ordered from most to least serious.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+  Error,
+  Warning,
+  Info,
+  // An inconsistency between the analyzer's own figures rather than a property of the file.
+  Internal,
+}
+
+impl Severity {
+  pub fn label(&self) -> &'static str {
+    match self {
+      Severity::Error => "ERROR",
+      Severity::Warning => "WARNING",
+      Severity::Info => "INFO",
+      Severity::Internal => "INTERNAL",
+    }
+  }
+}
+
+// Static definition of a finding code and the text printed by `explain`.
+pub struct FindingDefinition {
+  pub code: &'static str,
+  pub severity: Severity,
+  // What was detected.
+  pub detected: &'static str,
+  // How the analyzer measures it.
+  pub measured: &'static str,
+  // Typical causes.
+  pub causes: &'static str,
+  // Remediation hints.
+  pub remediation: &'static str,
+}
+
+impl FindingDefinition {
+  // Stable reference to this code's documentation, suitable for deep links from UIs.
+  pub fn doc_ref(&self) -> String {
+    format!("video_analyzer:findings/{}", self.code)
+  }
+
+  // Paragraph printed by `explain <CODE>`.
+  pub fn explanation(&self) -> String {
+    format!(
+      "{} ({})\n  Detected: {}\n  Measured: {}\n  Typical causes: {}\n  Remediation: {}\n  Reference: {}",
+      self.code,
+      self.severity.label(),
+      self.detected,
+      self.measured,
+      self.causes,
+      self.remediation,
+      self.doc_ref()
+    )
+  }
+}
+
+pub static NO_VIDEO_STREAM: FindingDefinition = FindingDefinition {
+  code: "NO_VIDEO_STREAM",
+  severity: Severity::Info,
+  detected: "The input has streams but none of them is a video stream.",
+  measured: "No stream of type video other than attached pictures (cover art) is found.",
+  causes: "Audio-only deliveries (MP3, AAC, audio-only MP4), possibly with cover art, or a remux that dropped \
+    the video track.",
+  remediation: "Nothing to do for audio-only content. Otherwise re-export with the video track included, \
+    and pass --require-video in pipelines where video is mandatory.",
+};
+
+pub static UNKNOWN_FRAME_RATE: FindingDefinition = FindingDefinition {
+  code: "UNKNOWN_FRAME_RATE",
+  severity: Severity::Warning,
+  detected: "The video stream declares no usable frame rate (0/0 or x/0).",
+  measured: "Both the stream's average frame rate and its real base frame rate are checked; rate-derived \
+    figures fall back to values measured from frame timestamps.",
+  causes: "Motion-JPEG over HTTP, raw V4L2 captures saved to Matroska, some screen recorders.",
+  remediation: "Remux with an explicit frame rate (e.g. ffmpeg -r on input) if downstream tools need one.",
+};
+
+pub static NO_CONTAINER_DURATION: FindingDefinition = FindingDefinition {
+  code: "NO_CONTAINER_DURATION",
+  severity: Severity::Info,
+  detected: "The container does not declare an overall duration.",
+  measured: "The format context duration is unset (AV_NOPTS_VALUE).",
+  causes: "Live captures, streamed formats such as MPEG-TS or MJPEG, files that were never finalized.",
+  remediation: "Use the measured duration instead, or remux into a container that records one.",
+};
+
+pub static TAIL_DECODE_ERRORS: FindingDefinition = FindingDefinition {
+  code: "TAIL_DECODE_ERRORS",
+  severity: Severity::Warning,
+  detected: "Packets in the tail window were rejected by the decoder.",
+  measured: "Decoder errors are counted while decoding the last N megabytes in --head-tail mode.",
+  causes: "The first packets after the seek reference frames outside the window (harmless), or the tail \
+    of the file is damaged.",
+  remediation: "A handful of errors right after the seek is expected. Many errors, or errors at the very \
+    end, warrant a full analysis of the file.",
+};
+
+pub static TRUNCATED: FindingDefinition = FindingDefinition {
+  code: "TRUNCATED",
+  severity: Severity::Error,
+  detected: "The end of the file could not be decoded, which usually means the file is truncated.",
+  measured: "In --head-tail mode, the tail window produced no decodable frames.",
+  causes: "Interrupted uploads or copies, recordings that were stopped without finalizing the file.",
+  remediation: "Request a re-upload or re-copy of the source; compare the file size against the origin.",
+};
+
+pub static DECODE_ERRORS: FindingDefinition = FindingDefinition {
+  code: "DECODE_ERRORS",
+  severity: Severity::Error,
+  detected: "Packets of the video stream were rejected by the decoder, or their frames failed to decode.",
+  measured: "Errors returned by the strict decoder are counted per kind and decoding goes on with the next \
+    packet; with --recovery-pass the damaged regions are re-decoded with error concealment to count \
+    recoverable frames.",
+  causes: "Bit errors from storage or transmission, broken muxing, encoder bugs, partial uploads.",
+  remediation: "Run with --recovery-pass to see how many frames a lenient decoder salvages; if few frames \
+    are recoverable, request a re-upload of the source.",
+};
+
+pub static CROP_MISMATCH: FindingDefinition = FindingDefinition {
+  code: "CROP_MISMATCH",
+  severity: Severity::Warning,
+  detected: "Container-level cropping (MP4 clean aperture) and codec-level cropping disagree.",
+  measured: "The 'clap' box of the track's sample entry is compared with the size of the decoded frames, \
+    which already have the bitstream's cropping (e.g. H.264 frame_cropping) applied.",
+  causes: "Muxers that write a clean aperture for the coded size, or editors that set a clap box for a \
+    different crop than the encoder used.",
+  remediation: "Decide which size is intended and remux so both agree; players differ in which one they honor.",
+};
+
+pub static DECLARED_SIZE_MISMATCH: FindingDefinition = FindingDefinition {
+  code: "DECLARED_SIZE_MISMATCH",
+  severity: Severity::Warning,
+  detected: "The codec parameters declare a different resolution than the first decoded frame has.",
+  measured: "The width and height of the stream's codec parameters are compared with those of the first \
+    decoded frame.",
+  causes: "A stream cut after a resolution change, a container header written from a different encode \
+    than the bitstream, or parameter sets that disagree with the sample description.",
+  remediation: "Tools configured from the container header scale or crop the wrong size; remux so the \
+    header matches the bitstream.",
+};
+
+pub static RESOLUTION_CHANGE: FindingDefinition = FindingDefinition {
+  code: "RESOLUTION_CHANGE",
+  severity: Severity::Warning,
+  detected: "The resolution of the decoded frames changes mid-stream.",
+  measured: "Every decoded frame's width and height are compared with the previous frame's; each change \
+    is listed with its frame number and timestamp.",
+  causes: "Live captures whose source switches, adaptive streams recorded across variants, or files \
+    concatenated from encodes of different sizes.",
+  remediation: "Scale the input to a single resolution before transcoders or players that configure \
+    themselves from the first frame.",
+};
+
+pub static SPARSE_KEYFRAMES: FindingDefinition = FindingDefinition {
+  code: "SPARSE_KEYFRAMES",
+  severity: Severity::Warning,
+  detected: "Key frames are further apart than the selected delivery target allows.",
+  measured: "The longest interval between decoded key frames is compared with the maximum of the \
+    --delivery-target preset (e.g. 2s for ll-hls, 10s for archive).",
+  causes: "Encoder GOP length or keyint set for a different target, scene-cut detection disabled key \
+    frame insertion, or a file made for archive being delivered for streaming.",
+  remediation: "Re-encode with a fixed GOP (e.g. -g / keyint at fps x segment duration) and scene-cut \
+    key frames allowed only on top of it.",
+};
+
+pub static SINGLE_KEYFRAME: FindingDefinition = FindingDefinition {
+  code: "SINGLE_KEYFRAME",
+  severity: Severity::Warning,
+  detected: "The whole file contains exactly one key frame.",
+  measured: "Decoded key frames are counted over a file of at least one minute.",
+  causes: "Infinite GOP settings (keyint=infinite, -g 0 on some encoders), screen capture tools.",
+  remediation: "Re-encode with a finite GOP; the file can't be segmented and seeking decodes from the start.",
+};
+
+pub static ALL_INTRA: FindingDefinition = FindingDefinition {
+  code: "ALL_INTRA",
+  severity: Severity::Warning,
+  detected: "Every frame is a key frame although the codec supports inter-frame prediction.",
+  measured: "Decoded key frames equal the frame count over a file of at least one minute; intra-only \
+    codecs such as ProRes or DNxHD are not flagged.",
+  causes: "-g 1 or intra-refresh settings left over from editing workflows, all-I presets.",
+  remediation: "Re-encode with a normal GOP for distribution; all-intra files are several times larger.",
+};
+
+pub static PROFILE_REQUIREMENT: FindingDefinition = FindingDefinition {
+  code: "PROFILE_REQUIREMENT",
+  severity: Severity::Error,
+  detected: "A stream doesn't meet the profile and level given with --require-profile.",
+  measured: "The profile and level in the codec parameters are compared with the target, and the \
+    resolution and frame rate of the video with the limits of the level it declares: frame size and \
+    macroblock rate for H.264, luma picture size and sample rate for HEVC, AV1 and VP9.",
+  causes: "Encoder presets above the target device's capabilities, or encoders that declare a level \
+    lower than the picture size and frame rate need.",
+  remediation: "Re-encode with the target profile and level (e.g. -profile:v high -level 4.1), lowering \
+    the resolution or frame rate when they don't fit the level.",
+};
+
+pub static KEYFRAME_TAIL: FindingDefinition = FindingDefinition {
+  code: "KEYFRAME_TAIL",
+  severity: Severity::Warning,
+  detected: "The last key frame is further from the end of the video than --max-keyframe-tail allows.",
+  measured: "The time from the last key frame to the last frame, with the number of frames in between, \
+    is compared with --max-keyframe-tail.",
+  causes: "A long or open GOP at the end of the encode, or an edit that cut the file mid-GOP without \
+    re-encoding the end.",
+  remediation: "Re-encode with a fixed GOP, or force a key frame near the end (e.g. -force_key_frames); \
+    players seeking into the tail decode from the last key frame.",
+};
+
+pub static REORDER_DEPTH_MISMATCH: FindingDefinition = FindingDefinition {
+  code: "REORDER_DEPTH_MISMATCH",
+  severity: Severity::Warning,
+  detected: "Frames are reordered by more frames than the stream declares.",
+  measured: "For every decoded frame in decode order, the frames decoded before it and presented after it \
+    are counted; the largest count is compared with the video delay of the codec parameters.",
+  causes: "Encoders or muxers that write a wrong max_num_reorder_frames or sps_max_num_reorder_pics, \
+    streams spliced from encodes with different B-frame settings.",
+  remediation: "Players and hardware decoders size their reorder buffer by the declaration and may show \
+    frames out of order; re-encode or rewrite the VUI with the correct value.",
+};
+
+pub static TIMECODE_DISCONTINUITY: FindingDefinition = FindingDefinition {
+  code: "TIMECODE_DISCONTINUITY",
+  severity: Severity::Warning,
+  detected: "The embedded timecodes of the video break their sequence.",
+  measured: "With --timecodes, every frame's SMPTE timecode (S12M side data from pic_timing SEI or GOP \
+    headers) is compared with the previous one advanced by the frames in between, counting the frame \
+    numbers drop-frame timecodes skip at 29.97 and 59.94 fps.",
+  causes: "Edits and splices that keep the source timecodes, captures restarted mid-recording, encoders that \
+    regenerate timecodes per segment, or drop-frame flags that don't match the frame rate.",
+  remediation: "Restripe the timecode from a single start value when re-encoding, or edit at the listed \
+    frames with their timecodes in mind.",
+};
+
+pub static BEST_STREAM_MISMATCH: FindingDefinition = FindingDefinition {
+  code: "BEST_STREAM_MISMATCH",
+  severity: Severity::Info,
+  detected: "The demuxer's best video stream is not the highest-resolution video stream.",
+  measured: "libavformat's best-stream choice is compared with the video stream (other than attached \
+    pictures) with the most pixels; the analysis uses the latter unless --stream is given.",
+  causes: "Low-resolution preview tracks muxed by action cameras and drones (GoPro, DJI), or thumbnail \
+    tracks placed before the main track.",
+  remediation: "Tools that pick the first or best stream may process the preview; select the main track \
+    explicitly, or remux without the preview track.",
+};
+
+pub static INCONSISTENT_FIGURES: FindingDefinition = FindingDefinition {
+  code: "INCONSISTENT_FIGURES",
+  severity: Severity::Internal,
+  detected: "Two figures of the report contradict each other.",
+  measured: "After the analysis, related figures are cross-checked: frame type counts against the frame \
+    count, key frames against frames, measured against container duration (10% tolerance) and bytes \
+    read per stream against the file size.",
+  causes: "A bug in the analyzer, or a file malformed in a way that defeats one of the measurements.",
+  remediation: "Treat the figures involved with suspicion and report the file and the message to the \
+    maintainers.",
+};
+
+pub static IO_RETRY: FindingDefinition = FindingDefinition {
+  code: "IO_RETRY",
+  severity: Severity::Warning,
+  detected: "Reading the input failed and was retried.",
+  measured: "With --io-retries, each error returned by av_read_frame is retried after --io-retry-delay, \
+    first in place and then by re-opening the input and seeking back; packets processed before the \
+    error are not counted again.",
+  causes: "Flaky network storage (CIFS/NFS returning EIO), dropped HTTP connections, failing disks.",
+  remediation: "The figures are complete if the analysis finished. Repeated retries at the same position \
+    point at a damaged region of the file or its storage; copy the file locally and compare checksums.",
+};
+
+pub static ANALYSIS_CRASHED: FindingDefinition = FindingDefinition {
+  code: "ANALYSIS_CRASHED",
+  severity: Severity::Error,
+  detected: "The isolated analysis process crashed, was killed or timed out.",
+  measured: "With --isolate the analysis runs in a child process under address space and CPU limits; a \
+    child ending on a signal or running past --timeout is reported instead of failing the caller.",
+  causes: "Decoder crashes on malformed or hostile input, files that make a demuxer loop, memory \
+    exhaustion on huge or corrupt dimensions.",
+  remediation: "Treat the file as untrusted and broken. Re-run without --isolate under a debugger to \
+    investigate, or raise --timeout and --isolate-memory-mb for legitimately huge files.",
+};
+
+pub static POOR_INTERLEAVING: FindingDefinition = FindingDefinition {
+  code: "POOR_INTERLEAVING",
+  severity: Severity::Warning,
+  detected: "A stream is stored in contiguous runs larger than progressive playback tolerates.",
+  measured: "Packets are sorted by byte position and grouped into same-stream runs, measured in bytes \
+    and media time against --max-interleave-mb (default 2) and --max-interleave-seconds (default 5). \
+    Without packet positions, the timestamp lag between streams in read order is used instead.",
+  causes: "Tracks concatenated instead of interleaved (e.g. all audio after all video), muxers with \
+    large interleave settings, edits that appended a track.",
+  remediation: "Remux with proper interleaving, e.g. ffmpeg -c copy -movflags +faststart for MP4.",
+};
+
+pub static MOOV_AFTER_MDAT: FindingDefinition = FindingDefinition {
+  code: "MOOV_AFTER_MDAT",
+  severity: Severity::Warning,
+  detected: "The moov box of an MP4/MOV file comes after its media data, so the file isn't faststart.",
+  measured: "The top-level box headers of the file are read in order, 64-bit sizes included, and the offset \
+    of the moov box is compared with the first mdat box. Fragmented files are reported as such and not flagged.",
+  causes: "Muxers and editors that write the index when the recording ends (cameras, screen recorders, \
+    ffmpeg without -movflags +faststart).",
+  remediation: "Progressive playback over HTTP has to fetch the end of the file before it can start. Move the \
+    moov box forward without re-encoding, e.g. ffmpeg -i in.mp4 -c copy -movflags +faststart out.mp4.",
+};
+
+pub static INIT_SEGMENT_ONLY: FindingDefinition = FindingDefinition {
+  code: "INIT_SEGMENT_ONLY",
+  severity: Severity::Warning,
+  detected: "The file is the init segment of a fragmented MP4 (fMP4, CMAF) without any media segment.",
+  measured: "The moov box declares fragmented tracks (mvex) and the track fragment runs of the moof boxes, \
+    if any, hold no sample, which is why no frame is decoded.",
+  causes: "The init segment of an HLS or DASH rendition (init.mp4) analyzed on its own, or a recording \
+    stopped before its first fragment was written.",
+  remediation: "Concatenate the init segment with its media segments (e.g. cat init.mp4 seg1.m4s > full.mp4) \
+    and analyze the result, or analyze the playlist or manifest.",
+};
+
+pub static TS_CONTINUITY_ERRORS: FindingDefinition = FindingDefinition {
+  code: "TS_CONTINUITY_ERRORS",
+  severity: Severity::Warning,
+  detected: "Transport stream packets of a PID are missing or out of order.",
+  measured: "The 4-bit continuity counter of every TS packet with a payload is compared with the previous \
+    packet of its PID, in a scan of the packet headers of a local file. Repeated packets and packets \
+    flagged with discontinuity_indicator are allowed.",
+  causes: "Packet loss on the network or satellite link of a capture, drops in a recorder that couldn't \
+    keep up, files cut or spliced without regard for TS packets.",
+  remediation: "Capture again from a cleaner source, or check the receiving network for loss; the decoded \
+    picture around the errors is usually damaged.",
+};
+
+pub static TS_PCR_DISCONTINUITY: FindingDefinition = FindingDefinition {
+  code: "TS_PCR_DISCONTINUITY",
+  severity: Severity::Warning,
+  detected: "The program clock reference of a transport stream goes backwards or jumps ahead without \
+    being announced.",
+  measured: "Each PCR is compared with the previous one of its PID in a scan of the packet headers of a \
+    local file; a PCR behind it or more than a second ahead is a discontinuity. Jumps on packets with \
+    discontinuity_indicator are listed but not raised.",
+  causes: "Captures spliced from several sources, encoders restarted mid-stream, concatenated files \
+    (cat a.ts b.ts).",
+  remediation: "Remux so the timestamps run on (e.g. ffmpeg -fflags +genpts -c copy), or set \
+    discontinuity_indicator where the streams were spliced.",
+};
+
+// Every definition, in the order `explain --all` lists them.
+pub static RANGE_VIOLATION: FindingDefinition = FindingDefinition {
+  code: "RANGE_VIOLATION",
+  severity: Severity::Warning,
+  detected: "Decoded luma samples fall outside the stream's declared color range.",
+  measured: "Luma samples of every decoded frame (every 4th row and column with --pixel-quality fast) \
+    are compared with the declared range, scaled to the bit depth: 16-235 for limited (tv) range at 8 \
+    bits. Frames with more than 1% of samples outside are counted. Unspecified range is treated as \
+    limited, as players do.",
+  causes: "Full-range sources (screen captures, phone cameras, JPEG stills) encoded without converting \
+    or without setting the range flag, or a range flag rewritten by a remux.",
+  remediation: "Either flag the stream as full range (e.g. -color_range pc) or convert the samples to \
+    limited range; otherwise playback shows crushed blacks or washed-out highlights.",
+};
+
+pub static AUDIO_DURATION_MISMATCH: FindingDefinition = FindingDefinition {
+  code: "AUDIO_DURATION_MISMATCH",
+  severity: Severity::Warning,
+  detected: "An audio stream is shorter or longer than the video by more than --av-drift-tolerance-ms \
+    (a second by default).",
+  measured: "The span of the audio packet timestamps, last minus first, is compared with the span of \
+    the decoded video frame timestamps, so different start offsets don't count as drift. Every audio \
+    stream is compared with the video.",
+  causes: "Recordings where audio capture stopped early, edits that trimmed one track only, \
+    concatenation of clips with mismatched track lengths.",
+  remediation: "Pad or trim the audio to the video length when remuxing (e.g. ffmpeg -af apad \
+    -shortest), or re-export from the editor.",
+};
+
+pub static AV_START_OFFSET: FindingDefinition = FindingDefinition {
+  code: "AV_START_OFFSET",
+  severity: Severity::Warning,
+  detected: "An audio stream starts before or after the video by more than --av-offset-tolerance-ms.",
+  measured: "The presentation start of every audio stream, each timestamp in its own stream's time base, \
+    is compared with the first video frame. The audio start is the first packet an edit list keeps, \
+    past the priming samples it says to skip, so AAC encoder delay isn't an offset.",
+  causes: "Encoders or capture devices that start one track late, edits that trimmed one track only, \
+    muxers that drop the edit list or write it for one track.",
+  remediation: "Viewers notice lipsync errors from about 45 ms of audio lead or 125 ms of lag. Shift the \
+    audio when remuxing (e.g. ffmpeg -itsoffset) or re-export from the editor.",
+};
+
+pub static AUDIO_FORMAT_MISMATCH: FindingDefinition = FindingDefinition {
+  code: "AUDIO_FORMAT_MISMATCH",
+  severity: Severity::Warning,
+  detected: "Decoded audio frames have a different sample rate or channel layout than the stream declares.",
+  measured: "Every decoded frame's sample rate, channel count and layout are compared with the stream \
+    parameters. Layouts are only compared when both sides name one; the decoded duration counts each \
+    frame at its own rate.",
+  causes: "Remuxes that copied the parameters of another track or an earlier version of the file, \
+    concatenation of clips recorded at different rates, or HE-AAC signalled at its core rate.",
+  remediation: "Players that trust the declared rate resample or play at the wrong speed. Remux with the \
+    parameters of the bitstream (ffmpeg -c copy rewrites them), or resample to the declared rate.",
+};
+
+pub static TIMESTAMP_ANOMALY: FindingDefinition = FindingDefinition {
+  code: "TIMESTAMP_ANOMALY",
+  severity: Severity::Warning,
+  detected: "Video packets have missing, backwards or duplicate DTS values, or a PTS before their DTS.",
+  measured: "Every video packet's DTS is compared with the previous packet's in file order, and its PTS \
+    with its own DTS. The first few positions of each kind are listed in the timestamp validation section.",
+  causes: "Hardware encoders and capture devices that reset or jitter their clock, streams spliced \
+    without rebasing the timestamps, muxers that leave the DTS unset.",
+  remediation: "Remux with regenerated timestamps (e.g. ffmpeg -fflags +genpts) and check the listed \
+    positions in a player; without a fix, players may stutter, drop frames or lose A/V sync there.",
+};
+
+pub static START_TIME_MISMATCH: FindingDefinition = FindingDefinition {
+  code: "START_TIME_MISMATCH",
+  severity: Severity::Warning,
+  detected: "The start time the container declares for the video doesn't match its first frame.",
+  measured: "The start time of the video stream (or of the container, when the stream has none) is \
+    compared with the timestamp of the first frame, within --tolerance-ms.",
+  causes: "Captures cut mid-stream whose first frames were undecodable, edit lists or muxers that \
+    write a start time without checking the media, timestamps rewritten after muxing.",
+  remediation: "Remux (e.g. ffmpeg -c copy) so the start time is derived from the media; check that the \
+    first seconds aren't missing from the capture.",
+};
+
+pub static DURATION_MISMATCH: FindingDefinition = FindingDefinition {
+  code: "DURATION_MISMATCH",
+  severity: Severity::Error,
+  detected: "The container's declared duration disagrees with the time of the last frame.",
+  measured: "The difference between the container duration and the last video packet's DTS, counted \
+    from the stream's start time, is compared with --tolerance-ms (500 ms by default). For a fragmented \
+    MP4 the duration summed from the track fragment runs of the video stream stands in for the container's.",
+  causes: "Truncated files, muxers that wrote the duration before the recording ended, edits that cut \
+    the streams without rewriting the header, a stream much shorter than the others.",
+  remediation: "Remux to rewrite the duration from the actual content (e.g. ffmpeg -c copy); if the \
+    content itself is short, request the complete source.",
+};
+
+pub static VARIABLE_FRAME_RATE: FindingDefinition = FindingDefinition {
+  code: "VARIABLE_FRAME_RATE",
+  severity: Severity::Info,
+  detected: "The frame intervals vary, so the declared frame rate doesn't describe the stream.",
+  measured: "The decoded frame timestamps are sorted into presentation order and their intervals \
+    histogrammed; fewer than 95% of them fall in the most common cluster, and no short repeating \
+    cadence (pulldown) is constant either. The measured average is the frame count over the decoded duration.",
+  causes: "Screen recordings and phone cameras that only emit a frame when the picture changes or the \
+    sensor allows, streams assembled from sources with different rates.",
+  remediation: "Use the measured average rather than the declared rate for timing. Where a constant \
+    rate is required (editing, broadcast), convert with e.g. ffmpeg -fps_mode cfr -r <rate>.",
+};
+
+pub static DROPPED_FRAMES: FindingDefinition = FindingDefinition {
+  code: "DROPPED_FRAMES",
+  severity: Severity::Warning,
+  detected: "Some intervals between frames are long enough to hold missing frames.",
+  measured: "The decoded frame timestamps are sorted into presentation order; an interval longer than \
+    --gap-factor (1.75 by default) times the median interval counts as round(interval / median) - 1 \
+    dropped frames. The longest gaps are listed with their position.",
+  causes: "Capture devices and encoders that couldn't keep up, network loss in live ingest, cuts that \
+    removed frames without rebasing the timestamps. Variable rate content has legitimate long intervals.",
+  remediation: "Check the listed positions for stutter. For capture pipelines, look at the CPU and \
+    disk load at those times; for variable rate sources, raise --gap-factor.",
+};
+
+pub static DUPLICATE_FRAMES: FindingDefinition = FindingDefinition {
+  code: "DUPLICATE_FRAMES",
+  severity: Severity::Warning,
+  detected: "Several decoded frames carry the same presentation timestamp.",
+  measured: "Sorted decoded frame timestamps that are equal to the previous one are counted.",
+  causes: "Muxers that repeat a frame to fill a gap without a new timestamp, broken timestamp \
+    generation in capture software, streams concatenated without rebasing.",
+  remediation: "Remux with regenerated timestamps (e.g. ffmpeg -fflags +genpts); players may show \
+    the repeated frames as a freeze or drop them.",
+};
+
+pub static FROZEN_VIDEO: FindingDefinition = FindingDefinition {
+  code: "FROZEN_VIDEO",
+  severity: Severity::Warning,
+  detected: "The decoded picture stays identical for longer than the --freeze-duration threshold.",
+  measured: "With --detect-freeze, every 8th row and luma sample of each decoded frame is hashed and \
+    compared with the previous frame's hash. Runs of identical frames lasting at least \
+    --freeze-duration (2 seconds by default) are listed with their start and length.",
+  causes: "Capture cards that lost their input signal and repeat the last frame, encoders that stalled \
+    and padded with repeats, or legitimately still content such as slides and title cards.",
+  remediation: "Check the listed positions against the source. For capture failures, inspect the \
+    input signal and card at those times and re-record the affected segments.",
+};
+
+pub static FIELD_ORDER_MISMATCH: FindingDefinition = FindingDefinition {
+  code: "FIELD_ORDER_MISMATCH",
+  severity: Severity::Warning,
+  detected: "The decoded frames' interlacing disagrees with the field order of the stream parameters.",
+  measured: "Every decoded frame's interlaced and top-field-first flags are counted; the resulting scan \
+    type (progressive, TFF, BFF or mixed) is compared with the field order the codec parameters declare.",
+  causes: "Encoders and transcoders that don't carry the field order into the container, progressive \
+    masters encoded as interlaced, edits that mixed interlaced and progressive sources.",
+  remediation: "Check the content visually for combing. Fix the flag when remuxing (e.g. ffmpeg \
+    -field_order or a setfield filter), or deinterlace with the detected order, not the declared one.",
+};
+
+pub static COLOR_MISMATCH: FindingDefinition = FindingDefinition {
+  code: "COLOR_MISMATCH",
+  severity: Severity::Warning,
+  detected: "The decoded frames' color description disagrees with the one of the stream parameters.",
+  measured: "The color range, primaries, transfer characteristics, matrix coefficients and chroma location \
+    of the first decoded frame are compared with the codec parameters. Values left unspecified on \
+    either side aren't compared.",
+  causes: "Containers tagged as HDR (BT.2020/PQ or HLG) around an SDR encode, transcoders that rewrote the \
+    container tags but not the bitstream's VUI, or the other way around.",
+  remediation: "Confirm which description matches the content, then fix the other one when remuxing (e.g. \
+    ffmpeg -color_primaries/-color_trc/-colorspace, or a bitstream filter for the VUI).",
+};
+
+pub static ROTATION_CONFLICT: FindingDefinition = FindingDefinition {
+  code: "ROTATION_CONFLICT",
+  severity: Severity::Warning,
+  detected: "The legacy rotate tag and the display matrix of the video stream disagree on the rotation.",
+  measured: "The rotation of the stream's display matrix side data is compared with its \"rotate\" \
+    metadata tag, both as clockwise degrees.",
+  causes: "Tools that rewrote one of them without the other, e.g. an editor updating the tag after a \
+    rotation while the muxer kept the original matrix.",
+  remediation: "Check which orientation is right on a player, then remux with a single, correct rotation \
+    (e.g. ffmpeg -display_rotation, and drop the tag with -metadata:s:v rotate=).",
+};
+
+pub static SAR_MISMATCH: FindingDefinition = FindingDefinition {
+  code: "SAR_MISMATCH",
+  severity: Severity::Warning,
+  detected: "The sample aspect ratio of the decoded frames disagrees with the one of the stream parameters.",
+  measured: "The SAR of the first decoded frame, which comes from the bitstream (e.g. the H.264 VUI), is \
+    compared in lowest terms with the SAR of the stream parameters, which containers such as MP4 (pasp) \
+    and Matroska set. Unspecified (0/1) values aren't compared.",
+  causes: "Remuxing that rewrote the container aspect ratio without the bitstream's, or encoders writing a \
+    default SAR into the VUI of anamorphic content.",
+  remediation: "Decide which aspect ratio is intended and make both agree when remuxing (e.g. ffmpeg \
+    -aspect, or the h264_metadata/hevc_metadata bitstream filters for the VUI).",
+};
+
+pub static CHAPTER_ERRORS: FindingDefinition = FindingDefinition {
+  code: "CHAPTER_ERRORS",
+  severity: Severity::Warning,
+  detected: "The container's chapter list is out of order, has overlapping chapters or runs past the end of \
+    the media.",
+  measured: "Chapter start and end times are converted from each chapter's time base to milliseconds. Every \
+    chapter must end after it starts and start no earlier than the previous one ends, and the last end \
+    must not be later than the container start time plus its duration.",
+  causes: "Chapter lists carried over from a longer edit, authoring tools that write the end of a chapter \
+    as the start of the next plus a margin, or chapters imported out of order.",
+  remediation: "Correct the chapter list (e.g. with an FFMETADATA file and ffmpeg -map_chapters, or \
+    mkvpropedit/MP4Box) so chapters are sequential and within the media.",
+};
+
+pub static ALL: &[&FindingDefinition] = &[
+  &NO_VIDEO_STREAM,
+  &UNKNOWN_FRAME_RATE,
+  &NO_CONTAINER_DURATION,
+  &TAIL_DECODE_ERRORS,
+  &TRUNCATED,
+  &DECODE_ERRORS,
+  &CROP_MISMATCH,
+  &DECLARED_SIZE_MISMATCH,
+  &RESOLUTION_CHANGE,
+  &SPARSE_KEYFRAMES,
+  &SINGLE_KEYFRAME,
+  &ALL_INTRA,
+  &KEYFRAME_TAIL,
+  &PROFILE_REQUIREMENT,
+  &REORDER_DEPTH_MISMATCH,
+  &TIMECODE_DISCONTINUITY,
+  &BEST_STREAM_MISMATCH,
+  &INCONSISTENT_FIGURES,
+  &IO_RETRY,
+  &ANALYSIS_CRASHED,
+  &POOR_INTERLEAVING,
+  &MOOV_AFTER_MDAT,
+  &INIT_SEGMENT_ONLY,
+  &TS_CONTINUITY_ERRORS,
+  &TS_PCR_DISCONTINUITY,
+  &RANGE_VIOLATION,
+  &AUDIO_DURATION_MISMATCH,
+  &AV_START_OFFSET,
+  &AUDIO_FORMAT_MISMATCH,
+  &TIMESTAMP_ANOMALY,
+  &START_TIME_MISMATCH,
+  &DURATION_MISMATCH,
+  &VARIABLE_FRAME_RATE,
+  &DROPPED_FRAMES,
+  &DUPLICATE_FRAMES,
+  &FROZEN_VIDEO,
+  &FIELD_ORDER_MISMATCH,
+  &COLOR_MISMATCH,
+  &ROTATION_CONFLICT,
+  &SAR_MISMATCH,
+  &CHAPTER_ERRORS,
+];
+
+// Look up a definition by its code, ignoring case.
+pub fn definition(code: &str) -> Option<&'static FindingDefinition> {
+  ALL.iter().copied().find(|d| d.code.eq_ignore_ascii_case(code))
+}
+
+// A finding raised while analyzing a file.
+pub struct Finding {
+  pub definition: &'static FindingDefinition,
+  pub message: String,
+  // Where in the video stream the problem is, for findings tied to a position.
+  pub timestamp: Option<Timestamp>,
+}
+
+impl Finding {
+  pub fn new(definition: &'static FindingDefinition, message: impl Into<String>) -> Self {
+    Finding { definition, message: message.into(), timestamp: None }
+  }
+
+  pub fn at(mut self, timestamp: Timestamp) -> Self {
+    self.timestamp = Some(timestamp);
+    self
+  }
+
+  pub fn severity(&self) -> Severity {
+    self.definition.severity
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn codes_are_unique() {
+    for (i, definition) in ALL.iter().enumerate() {
+      assert!(ALL[i + 1..].iter().all(|other| !other.code.eq_ignore_ascii_case(definition.code)),
+        "{} is listed twice", definition.code);
+    }
+  }
+
+  #[test]
+  fn every_definition_is_listed() {
+    let declared = include_str!("findings.rs").lines()
+      .filter(|line| line.starts_with("pub static ") && line.ends_with(": FindingDefinition = FindingDefinition {"))
+      .count();
+    assert_eq!(ALL.len(), declared);
+  }
+
+  #[test]
+  fn every_definition_is_documented() {
+    for definition in ALL {
+      assert!(!definition.code.is_empty());
+      assert!(definition.code.bytes().all(|b| b.is_ascii_uppercase() || b.is_ascii_digit() || b == b'_'),
+        "{}", definition.code);
+      for (field, text) in [("detected", definition.detected), ("measured", definition.measured),
+        ("causes", definition.causes), ("remediation", definition.remediation)] {
+        assert!(!text.trim().is_empty(), "{} has no {} text", definition.code, field);
+        assert!(!text.contains("  "), "{} {} text has a run of spaces", definition.code, field);
+      }
+      let explanation = definition.explanation();
+      assert!(explanation.starts_with(definition.code));
+      assert!(explanation.ends_with(&definition.doc_ref()));
+    }
+  }
+
+  #[test]
+  fn lookup_ignores_case() {
+    assert_eq!(definition("truncated").map(|d| d.code), Some("TRUNCATED"));
+    assert_eq!(definition("DECODE_ERRORS").map(|d| d.code), Some("DECODE_ERRORS"));
+    assert!(definition("NOT_A_CODE").is_none());
+    assert_eq!(TRUNCATED.doc_ref(), "video_analyzer:findings/TRUNCATED");
+  }
+
+  #[test]
+  fn severities_are_ordered() {
+    assert!(Severity::Error < Severity::Warning && Severity::Warning < Severity::Info && Severity::Info < Severity::Internal);
+    assert_eq!(Finding::new(&DECODE_ERRORS, "").severity().label(), "ERROR");
+  }
+}
@@ -0,0 +1,106 @@
+// --follow: analyze a file while it is being written, as a live recording is. At the end of the
+// input the reader waits for more data instead of finishing, with the decoder kept alive, until
+// Ctrl-C or until the file has stopped growing for a grace period; the full report follows
+// either way. A one-line status goes to stderr at a fixed interval meanwhile. This suits formats
+// read front to back, like MPEG-TS, Matroska and fragmented MP4; a plain MP4 only ever has the
+// sample index it was opened with.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use ffmpeg::Rational;
+use indicatif::ProgressBar;
+
+use crate::{format_optional_millis, format_with_commas, timestamp_to_millis};
+
+pub const DEFAULT_STATUS_SECONDS: f64 = 10.0;
+pub const DEFAULT_GRACE_SECONDS: f64 = 30.0;
+
+// Pause between two reads at the end of the input.
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+#[derive(Clone, Copy)]
+pub struct Settings {
+  // Time between two status lines.
+  pub status_interval: Duration,
+  // How long the input may stop growing before the analysis ends.
+  pub grace: Duration,
+}
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+// Turn Ctrl-C into a request to stop following, so the report is still printed.
+#[cfg(unix)]
+pub fn catch_interrupt() {
+  extern "C" fn on_interrupt(_signal: libc::c_int) {
+    INTERRUPTED.store(true, Ordering::SeqCst);
+  }
+  unsafe {
+    libc::signal(libc::SIGINT, on_interrupt as extern "C" fn(libc::c_int) as libc::sighandler_t);
+  }
+}
+
+#[cfg(windows)]
+pub fn catch_interrupt() {
+  use windows_sys::Win32::Foundation::BOOL;
+  use windows_sys::Win32::System::Console::{SetConsoleCtrlHandler, CTRL_C_EVENT};
+  unsafe extern "system" fn on_interrupt(kind: u32) -> BOOL {
+    if kind != CTRL_C_EVENT {
+      return 0;
+    }
+    INTERRUPTED.store(true, Ordering::SeqCst);
+    1
+  }
+  unsafe {
+    SetConsoleCtrlHandler(Some(on_interrupt), 1);
+  }
+}
+
+#[cfg(not(any(unix, windows)))]
+pub fn catch_interrupt() {}
+
+// Whether Ctrl-C was pressed since catch_interrupt.
+pub fn interrupted() -> bool {
+  INTERRUPTED.load(Ordering::SeqCst)
+}
+
+// At the end of the input: wait for it to grow and clear the end-of-file state so the next read
+// tries again. Returns false when following should end, on Ctrl-C or after the grace period
+// without new packets since `last_packet`.
+pub fn wait(ictx: &mut ffmpeg::format::context::Input, settings: Settings, last_packet: Instant) -> bool {
+  if interrupted() || last_packet.elapsed() >= settings.grace {
+    return false;
+  }
+  std::thread::sleep(POLL_INTERVAL);
+  unsafe {
+    let pb = (*ictx.as_mut_ptr()).pb;
+    if !pb.is_null() {
+      (*pb).eof_reached = 0;
+    }
+  }
+  true
+}
+
+// Prints the status line every status_interval.
+pub struct Status {
+  interval: Duration,
+  last: Instant,
+}
+
+impl Status {
+  pub fn new(settings: Settings) -> Self {
+    Status { interval: settings.status_interval, last: Instant::now() }
+  }
+
+  // Print the status when it is due.
+  pub fn tick(&mut self, pb: &ProgressBar, frames: i64, last_frame: Option<i64>, fps: Option<f64>, time_base: Rational) {
+    if self.last.elapsed() < self.interval {
+      return;
+    }
+    self.last = Instant::now();
+    let line = format!("Following: {} frames, last frame at {}, {}", format_with_commas(frames),
+      format_optional_millis(last_frame.and_then(|ts| timestamp_to_millis(ts, time_base))),
+      fps.map_or_else(|| "fps unknown".to_string(), |fps| format!("{:.3} fps measured", fps)));
+    pb.suspend(|| eprintln!("{}", line));
+  }
+}
@@ -0,0 +1,251 @@
+// Robust frame rate estimation. Dividing the frame count by the duration is skewed by a few
+// timestamp glitches at the start or end of a file, so the rate is taken from the dominant
+// cluster of a histogram of frame-to-frame timestamp deltas instead.
+
+use ffmpeg::Rational;
+use std::collections::BTreeMap;
+
+use crate::findings::{self, Finding};
+use crate::{describe_rate, format_with_commas};
+
+// Share of deltas the dominant cluster must cover for the stream to count as constant rate.
+const CONSTANT_RATE_SHARE: f64 = 0.95;
+
+// Deltas within this fraction of each other belong to the same cluster, which absorbs the 1/1001
+// rounding of NTSC rates. One tick is always tolerated for coarse time bases (e.g. 33/34 ms).
+const RELATIVE_TOLERANCE: f64 = 0.005;
+
+// Longest repeating cadence looked for when no single delta dominates (3:2 pulldown repeats every 2
+// frames, 2:3:3:2 every 4).
+const MAX_CADENCE: usize = 5;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RateMode {
+  Constant,
+  // Constant over a repeating group of frames, e.g. 3:2 pulldown timestamps.
+  Cadence(usize),
+  Variable,
+}
+
+pub struct RateEstimate {
+  // Rate of the dominant delta cluster.
+  pub modal_fps: f64,
+  // Fraction of deltas (or cadence groups) in the dominant cluster.
+  pub dominant_share: f64,
+  pub mode: RateMode,
+  // Mean of the dominant cluster of single frame intervals, also for a cadence.
+  pub modal_interval_ms: f64,
+  // Standard deviation of the frame intervals.
+  pub interval_stddev_ms: f64,
+  // Frame intervals further from the modal interval than the tolerance.
+  pub irregular_intervals: usize,
+}
+
+// The value whose neighbourhood holds the most samples, with the mean of those samples and the
+// fraction of all samples they make up.
+fn dominant_cluster(samples: &[i64]) -> Option<(f64, f64)> {
+  if samples.is_empty() {
+    return None;
+  }
+  let mut histogram: BTreeMap<i64, usize> = BTreeMap::new();
+  for &sample in samples {
+    *histogram.entry(sample).or_default() += 1;
+  }
+
+  let mut best: Option<(usize, f64)> = None;
+  for &center in histogram.keys() {
+    let tolerance = ((center as f64 * RELATIVE_TOLERANCE) as i64).max(1);
+    let (mut count, mut sum) = (0usize, 0i64);
+    for (&value, &n) in histogram.range(center - tolerance..=center + tolerance) {
+      count += n;
+      sum += value * n as i64;
+    }
+    if best.is_none_or(|(best_count, _)| count > best_count) {
+      best = Some((count, sum as f64 / count as f64));
+    }
+  }
+  best.map(|(count, mean)| (mean, count as f64 / samples.len() as f64))
+}
+
+// Estimate the frame rate from decoded frame timestamps (any order; they are sorted, so B-frame
+// reordering doesn't show as irregular intervals). `tolerance` is the fraction of the modal interval
+// an interval may differ by before it counts as irregular. Returns None with fewer than two
+// distinct timestamps or a degenerate time base.
+pub fn estimate(timestamps: &[i64], time_base: Rational, tolerance: f64) -> Option<RateEstimate> {
+  if time_base.numerator() <= 0 || time_base.denominator() <= 0 {
+    return None;
+  }
+  let seconds_per_tick = time_base.numerator() as f64 / time_base.denominator() as f64;
+  let mut sorted = timestamps.to_vec();
+  sorted.sort_unstable();
+  // Duplicate timestamps are glitches themselves, not zero-length frame intervals.
+  let deltas: Vec<i64> = sorted.windows(2).map(|w| w[1] - w[0]).filter(|&d| d > 0).collect();
+  if deltas.is_empty() {
+    return None;
+  }
+  let (mean_delta, share) = dominant_cluster(&deltas)?;
+  let mean = deltas.iter().sum::<i64>() as f64 / deltas.len() as f64;
+  let variance = deltas.iter().map(|&d| (d as f64 - mean).powi(2)).sum::<f64>() / deltas.len() as f64;
+  let mut estimate = RateEstimate {
+    modal_fps: 1.0 / (mean_delta * seconds_per_tick),
+    dominant_share: share,
+    mode: if share >= CONSTANT_RATE_SHARE { RateMode::Constant } else { RateMode::Variable },
+    modal_interval_ms: mean_delta * seconds_per_tick * 1000.0,
+    interval_stddev_ms: variance.sqrt() * seconds_per_tick * 1000.0,
+    irregular_intervals: deltas.iter().filter(|&&d| (d as f64 - mean_delta).abs() > mean_delta * tolerance).count(),
+  };
+
+  // No single interval dominates: look for a short repeating group of intervals whose total is
+  // constant, which is how pulldown shows up in timestamps.
+  if estimate.mode == RateMode::Variable {
+    for period in 2..=MAX_CADENCE.min(deltas.len() / 2) {
+      let groups: Vec<i64> = deltas.chunks_exact(period).map(|c| c.iter().sum()).collect();
+      if let Some((mean_group, group_share)) = dominant_cluster(&groups) {
+        if group_share >= CONSTANT_RATE_SHARE {
+          estimate.modal_fps = period as f64 / (mean_group * seconds_per_tick);
+          estimate.dominant_share = group_share;
+          estimate.mode = RateMode::Cadence(period);
+          break;
+        }
+      }
+    }
+  }
+  Some(estimate)
+}
+
+// Describe the rate mode for the report.
+pub fn describe_mode(mode: RateMode) -> String {
+  match mode {
+    RateMode::Constant => "constant".to_string(),
+    RateMode::Cadence(period) => format!("constant over a repeating {}-frame cadence", period),
+    RateMode::Variable => "variable".to_string(),
+  }
+}
+
+// Print the modal frame rate lines and return a finding for a variable frame rate. Without an
+// estimate, `sampled` tells a sampled decode from timestamps that don't give one.
+pub fn report(
+  estimate: Option<&RateEstimate>,
+  sampled: bool,
+  tolerance_percent: u32,
+  declared: Rational,
+  measured_fps: Option<f64>,
+) -> Option<Finding> {
+  let Some(rate) = estimate else {
+    out!("Modal frame rate: {}", if sampled { "not measured from a sample" } else { "unknown" });
+    return None;
+  };
+  out!("Modal frame rate: {:.3} fps ({:.1}% of frame intervals)", rate.modal_fps, rate.dominant_share * 100.0);
+  out!("Frame rate mode: {}", describe_mode(rate.mode));
+  out!("Most common frame interval: {:.3} ms (standard deviation {:.3} ms)", rate.modal_interval_ms,
+    rate.interval_stddev_ms);
+  out!("Irregular frame intervals: {} (more than {}% from the most common)",
+    format_with_commas(rate.irregular_intervals as i64), tolerance_percent);
+  (rate.mode == RateMode::Variable).then(|| Finding::new(&findings::VARIABLE_FRAME_RATE, format!(
+    "only {:.1}% of frame intervals are {:.3} ms apart; declared {}, measured average {}",
+    rate.dominant_share * 100.0, rate.modal_interval_ms, describe_rate(declared),
+    measured_fps.map_or_else(|| "unknown".to_string(), |fps| format!("{:.3} fps", fps)))))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  // Timestamps of frames `interval` ticks apart.
+  fn regular(frames: i64, interval: i64) -> Vec<i64> {
+    (0..frames).map(|i| i * interval).collect()
+  }
+
+  #[test]
+  fn constant_rate() {
+    let estimate = estimate(&regular(250, 512), Rational::new(1, 12800), 0.05).unwrap();
+    assert!((estimate.modal_fps - 25.0).abs() < 1e-9);
+    assert_eq!(estimate.mode, RateMode::Constant);
+    assert_eq!(estimate.dominant_share, 1.0);
+    assert!((estimate.modal_interval_ms - 40.0).abs() < 1e-9);
+    assert_eq!(estimate.interval_stddev_ms, 0.0);
+    assert_eq!(estimate.irregular_intervals, 0);
+  }
+
+  #[test]
+  fn ntsc_rate_on_a_millisecond_time_base() {
+    // 29.97 fps in 1/1000 alternates between 33 and 34 ms intervals.
+    let timestamps: Vec<i64> = (0..300).map(|i| (i as f64 * 1001.0 / 30.0).round() as i64).collect();
+    let estimate = estimate(&timestamps, Rational::new(1, 1000), 0.05).unwrap();
+    assert_eq!(estimate.mode, RateMode::Constant);
+    assert!((estimate.modal_fps - 29.97).abs() < 0.05, "{}", estimate.modal_fps);
+  }
+
+  #[test]
+  fn glitches_dont_move_the_modal_rate() {
+    let mut timestamps = regular(100, 1000);
+    // A stall of a few frames at the end.
+    timestamps.extend([100_000 + 5000, 100_000 + 9000]);
+    let estimate = estimate(&timestamps, Rational::new(1, 30000), 0.05).unwrap();
+    assert!((estimate.modal_fps - 30.0).abs() < 1e-9);
+    assert_eq!(estimate.mode, RateMode::Constant);
+    assert_eq!(estimate.irregular_intervals, 2);
+  }
+
+  #[test]
+  fn reordered_and_duplicate_timestamps() {
+    // Decode order of B-frames, and a repeated timestamp.
+    let timestamps = [0, 3000, 1000, 2000, 6000, 4000, 5000, 5000, 7000];
+    let estimate = estimate(&timestamps, Rational::new(1, 24000), 0.05).unwrap();
+    assert!((estimate.modal_fps - 24.0).abs() < 1e-9);
+    assert_eq!(estimate.irregular_intervals, 0);
+  }
+
+  #[test]
+  fn pulldown_cadence() {
+    // 3:2 pulldown of 23.976 fps film in a 1/60000 time base: intervals of 3 and 2 fields.
+    let mut timestamps = vec![0];
+    for i in 0..100 {
+      timestamps.push(timestamps.last().unwrap() + if i % 2 == 0 { 3003 } else { 2002 });
+    }
+    let estimate = estimate(&timestamps, Rational::new(1, 60000), 0.05).unwrap();
+    assert_eq!(estimate.mode, RateMode::Cadence(2));
+    assert!((estimate.modal_fps - 24000.0 / 1001.0).abs() < 1e-9);
+  }
+
+  #[test]
+  fn variable_rate() {
+    let mut timestamps = vec![0];
+    for i in 0..200 {
+      timestamps.push(timestamps.last().unwrap() + [10, 17, 40, 23, 31, 55, 12][i % 7] * (1 + i as i64 % 3));
+    }
+    let estimate = estimate(&timestamps, Rational::new(1, 1000), 0.05).unwrap();
+    assert_eq!(estimate.mode, RateMode::Variable);
+    assert!(estimate.dominant_share < CONSTANT_RATE_SHARE);
+    assert!(estimate.irregular_intervals > 0);
+  }
+
+  #[test]
+  fn not_measurable() {
+    assert!(estimate(&[], Rational::new(1, 1000), 0.05).is_none());
+    assert!(estimate(&[42], Rational::new(1, 1000), 0.05).is_none());
+    assert!(estimate(&[7, 7, 7], Rational::new(1, 1000), 0.05).is_none());
+    assert!(estimate(&regular(10, 1), Rational::new(0, 0), 0.05).is_none());
+    assert!(estimate(&regular(10, 1), Rational::new(1, 0), 0.05).is_none());
+  }
+
+  #[test]
+  fn mode_descriptions() {
+    assert_eq!(describe_mode(RateMode::Constant), "constant");
+    assert_eq!(describe_mode(RateMode::Cadence(4)), "constant over a repeating 4-frame cadence");
+    assert_eq!(describe_mode(RateMode::Variable), "variable");
+  }
+
+  #[test]
+  fn only_a_variable_rate_is_a_finding() {
+    let constant = estimate(&regular(250, 512), Rational::new(1, 12800), 0.05).unwrap();
+    assert!(report(Some(&constant), false, 5, Rational::new(25, 1), Some(25.0)).is_none());
+    let jittered: Vec<i64> = (0..200).map(|i: i64| i * 40 + (i * i * 7919) % 31).collect();
+    let variable = estimate(&jittered, Rational::new(1, 1000), 0.05).unwrap();
+    assert_eq!(variable.mode, RateMode::Variable);
+    let finding = report(Some(&variable), false, 5, Rational::new(25, 1), None).unwrap();
+    assert_eq!(finding.definition.code, "VARIABLE_FRAME_RATE");
+    assert!(finding.message.ends_with("measured average unknown"), "{}", finding.message);
+    assert!(report(None, true, 5, Rational::new(25, 1), None).is_none());
+  }
+}
@@ -0,0 +1,109 @@
+// Frame rate stability: the decoded frames counted per second of their presentation timestamp,
+// to tell a stream that delivers its 30 frames every second from one that averages 30 while
+// swinging between 28 and 32. The first and last seconds are usually partial, so they are left
+// out of the figures.
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use ffmpeg::Rational;
+
+use crate::{format_signed_millis, format_with_commas, timestamp_to_signed_millis, BLUE_BOLD, RESET};
+
+// Seconds listed as the worst, furthest from the mean first.
+const WORST_SECONDS: usize = 5;
+
+pub struct Series {
+  // Frames per whole second of presentation time, from the first second with a frame to the last,
+  // the seconds without any as 0.
+  seconds: Vec<(i64, u64)>,
+}
+
+pub struct Summary {
+  pub full_seconds: usize,
+  // Fewest and most frames of a full second, with the start of the first such second in ms.
+  pub min: (u64, i64),
+  pub max: (u64, i64),
+  pub mean: f64,
+  pub stddev: f64,
+  // Start in ms and frames of the full seconds furthest from the mean.
+  pub worst: Vec<(i64, u64)>,
+}
+
+impl Series {
+  // Bucket the presentation timestamps of the frames, in any order.
+  pub fn new(timestamps: &[i64], time_base: Rational) -> Self {
+    let seconds: Vec<i64> = timestamps.iter()
+      .filter_map(|&ts| timestamp_to_signed_millis(ts, time_base))
+      .map(|ms| ms.div_euclid(1000))
+      .collect();
+    let (Some(&first), Some(&last)) = (seconds.iter().min(), seconds.iter().max()) else {
+      return Series { seconds: Vec::new() };
+    };
+    let mut counts = vec![0u64; (last - first + 1) as usize];
+    for second in seconds {
+      counts[(second - first) as usize] += 1;
+    }
+    Series { seconds: counts.into_iter().enumerate().map(|(i, frames)| (first + i as i64, frames)).collect() }
+  }
+
+  fn full(&self) -> &[(i64, u64)] {
+    if self.seconds.len() < 3 { &[] } else { &self.seconds[1..self.seconds.len() - 1] }
+  }
+
+  // None without a full second, i.e. for less than three seconds of frames.
+  pub fn summary(&self) -> Option<Summary> {
+    let full = self.full();
+    let &(min_second, min_frames) = full.iter().min_by_key(|&&(second, frames)| (frames, second))?;
+    let &(max_second, max_frames) = full.iter().max_by_key(|&&(second, frames)| (frames, -second))?;
+    let mean = full.iter().map(|&(_, frames)| frames as f64).sum::<f64>() / full.len() as f64;
+    let variance = full.iter().map(|&(_, frames)| (frames as f64 - mean).powi(2)).sum::<f64>() / full.len() as f64;
+    let deviation = |frames: u64| (frames as f64 - mean).abs();
+    let mut worst: Vec<(i64, u64)> = full.iter().copied().filter(|&(_, frames)| deviation(frames) > 0.0).collect();
+    worst.sort_by(|a, b| deviation(b.1).total_cmp(&deviation(a.1)).then(a.0.cmp(&b.0)));
+    worst.truncate(WORST_SECONDS);
+    Some(Summary {
+      full_seconds: full.len(),
+      min: (min_frames, min_second * 1000),
+      max: (max_frames, max_second * 1000),
+      mean,
+      stddev: variance.sqrt(),
+      worst: worst.into_iter().map(|(second, frames)| (second * 1000, frames)).collect(),
+    })
+  }
+
+  // Write the frames of every second as CSV (--fps-csv), the partial first and last seconds
+  // marked.
+  pub fn write_csv(&self, path: &Path) -> std::io::Result<()> {
+    let mut out = BufWriter::new(File::create(path)?);
+    writeln!(out, "second,start_ms,frames,partial")?;
+    let last = self.seconds.len().saturating_sub(1);
+    for (i, &(second, frames)) in self.seconds.iter().enumerate() {
+      writeln!(out, "{},{},{},{}", second, second * 1000, frames, (i == 0 || i == last) as u8)?;
+    }
+    out.flush()
+  }
+}
+
+// Print the section; nothing without frames.
+pub fn report(series: &Series) -> Option<Summary> {
+  if series.seconds.is_empty() {
+    return None;
+  }
+  out!("{}{}Frame rate per second - {}", RESET, BLUE_BOLD, RESET);
+  let Some(summary) = series.summary() else {
+    out!("Full seconds measured: 0, the frames span less than three seconds of presentation time");
+    return None;
+  };
+  out!("Full seconds measured: {} (the partial first and last seconds left out)",
+    format_with_commas(summary.full_seconds as i64));
+  out!("Frames per second: min {} in the second starting at {}, max {} in the second starting at {}, mean {:.2}, \
+    standard deviation {:.2}", summary.min.0, format_signed_millis(summary.min.1), summary.max.0,
+    format_signed_millis(summary.max.1), summary.mean, summary.stddev);
+  let worst: Vec<String> = summary.worst.iter()
+    .map(|&(start_ms, frames)| format!("{} ({} frames)", format_signed_millis(start_ms), frames))
+    .collect();
+  out!("Worst seconds: {}", if worst.is_empty() { "none, every full second has as many frames".to_string() } else { worst.join(", ") });
+  Some(summary)
+}
@@ -0,0 +1,274 @@
+// Fragmented MP4 (fMP4, CMAF): the moov box only describes the tracks, with an mvex box marking
+// them as fragmented, and the samples are indexed by the moof boxes that follow. The duration such
+// a container declares can't be trusted, so the timing of every track is read from its track
+// fragments: the base decode time of each fragment (tfdt) and the sample durations and composition
+// offsets of its runs (trun), with the defaults of tfhd and trex. An init segment on its own has the
+// tracks but not a single sample.
+
+use std::fs::File;
+use std::io::{self, BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+
+use ffmpeg::Rational;
+
+use crate::findings::{self, Finding};
+use crate::mp4::{self, BoxHeader};
+use crate::timestamp::Timestamp;
+use crate::{format_optional_millis, format_signed_millis, format_with_commas, BLUE_BOLD, RESET};
+
+// tfhd flags.
+const BASE_DATA_OFFSET: u32 = 0x01;
+const SAMPLE_DESCRIPTION_INDEX: u32 = 0x02;
+const DEFAULT_SAMPLE_DURATION: u32 = 0x08;
+
+// trun flags, and the optional fields of each sample in the order they are stored.
+const DATA_OFFSET: u32 = 0x01;
+const FIRST_SAMPLE_FLAGS: u32 = 0x04;
+const SAMPLE_DURATION: u32 = 0x100;
+const SAMPLE_SIZE: u32 = 0x200;
+const SAMPLE_FLAGS: u32 = 0x400;
+const SAMPLE_COMPOSITION_OFFSET: u32 = 0x800;
+const SAMPLE_FIELDS: [u32; 4] = [SAMPLE_DURATION, SAMPLE_SIZE, SAMPLE_FLAGS, SAMPLE_COMPOSITION_OFFSET];
+
+pub struct Track {
+  // Stream index, which follows the order of the trak boxes.
+  pub index: usize,
+  pub track_id: u32,
+  pub timescale: u32,
+  // Track fragments (traf boxes) and samples of the track over every moof box.
+  pub fragments: u64,
+  pub samples: u64,
+  // Decode time of the first sample and past the last one, in the track's timescale.
+  pub first_decode_time: Option<u64>,
+  pub decode_end: Option<u64>,
+  // Lowest decode time plus composition offset of any sample, before edit lists.
+  pub earliest_presentation: Option<i64>,
+}
+
+impl Track {
+  fn time_base(&self) -> Option<Rational> {
+    (self.timescale > 0).then(|| Rational::new(1, self.timescale.min(i32::MAX as u32) as i32))
+  }
+
+  // Sum of the sample durations, from the first decode time.
+  pub fn duration_raw(&self) -> Option<Timestamp> {
+    let span = self.decode_end?.checked_sub(self.first_decode_time?)?;
+    Some(Timestamp::new(span as i64, self.time_base()?))
+  }
+
+  pub fn duration_ms(&self) -> Option<u64> {
+    self.duration_raw()?.millis()
+  }
+
+  pub fn earliest_presentation_raw(&self) -> Option<Timestamp> {
+    Some(Timestamp::new(self.earliest_presentation?, self.time_base()?))
+  }
+
+  pub fn earliest_presentation_ms(&self) -> Option<i64> {
+    self.earliest_presentation_raw()?.signed_millis()
+  }
+}
+
+pub struct Fragmented {
+  // moof boxes.
+  pub fragments: u64,
+  pub tracks: Vec<Track>,
+}
+
+impl Fragmented {
+  // An init segment without media segments: no track has a sample.
+  pub fn init_only(&self) -> bool {
+    self.tracks.iter().all(|track| track.samples == 0)
+  }
+
+  pub fn track(&self, index: usize) -> Option<&Track> {
+    self.tracks.iter().find(|track| track.index == index)
+  }
+}
+
+// Version and flags of a full box.
+fn version_flags<R: Read>(reader: &mut R) -> io::Result<(u32, u32)> {
+  let word = mp4::read_u32(reader)?;
+  Ok((word >> 24, word & 0x00ff_ffff))
+}
+
+// Track ID (tkhd) and timescale (mdhd) of a trak box; 0 for either when its box is missing.
+fn track_header<R: Read + Seek>(reader: &mut R, trak: &BoxHeader) -> io::Result<(u32, u32)> {
+  let mut track_id = 0;
+  if let Some(tkhd) = mp4::child(reader, trak, b"tkhd")? {
+    reader.seek(SeekFrom::Start(tkhd.payload_start()))?;
+    // Creation and modification times come first, 64-bit in version 1.
+    let (version, _) = version_flags(reader)?;
+    reader.seek(SeekFrom::Current(if version == 1 { 16 } else { 8 }))?;
+    track_id = mp4::read_u32(reader)?;
+  }
+  let mut timescale = 0;
+  if let Some(mdia) = mp4::child(reader, trak, b"mdia")? {
+    if let Some(mdhd) = mp4::child(reader, &mdia, b"mdhd")? {
+      reader.seek(SeekFrom::Start(mdhd.payload_start()))?;
+      let (version, _) = version_flags(reader)?;
+      reader.seek(SeekFrom::Current(if version == 1 { 16 } else { 8 }))?;
+      timescale = mp4::read_u32(reader)?;
+    }
+  }
+  Ok((track_id, timescale))
+}
+
+// Default sample duration of each track ID, from the trex boxes of mvex.
+fn trex_durations<R: Read + Seek>(reader: &mut R, mvex: &BoxHeader) -> io::Result<Vec<(u32, u32)>> {
+  let mut durations = Vec::new();
+  for trex in mp4::children(reader, mvex.payload_start(), mvex.end())?.into_iter().filter(|b| b.is(b"trex")) {
+    reader.seek(SeekFrom::Start(trex.payload_start()))?;
+    version_flags(reader)?;
+    let track_id = mp4::read_u32(reader)?;
+    // default_sample_description_index
+    mp4::read_u32(reader)?;
+    durations.push((track_id, mp4::read_u32(reader)?));
+  }
+  Ok(durations)
+}
+
+fn present(track: &mut Track, time: i64) {
+  track.earliest_presentation = Some(track.earliest_presentation.map_or(time, |earliest| earliest.min(time)));
+}
+
+// Add the samples of one track fragment to its track.
+fn read_traf<R: Read + Seek>(reader: &mut R, traf: &BoxHeader, tracks: &mut [Track], defaults: &[(u32, u32)])
+  -> io::Result<()> {
+  let Some(tfhd) = mp4::child(reader, traf, b"tfhd")? else {
+    return Ok(());
+  };
+  reader.seek(SeekFrom::Start(tfhd.payload_start()))?;
+  let (_, flags) = version_flags(reader)?;
+  let track_id = mp4::read_u32(reader)?;
+  if flags & BASE_DATA_OFFSET != 0 {
+    mp4::read_u64(reader)?;
+  }
+  if flags & SAMPLE_DESCRIPTION_INDEX != 0 {
+    mp4::read_u32(reader)?;
+  }
+  let default_duration = if flags & DEFAULT_SAMPLE_DURATION != 0 {
+    mp4::read_u32(reader)?
+  } else {
+    defaults.iter().find(|(id, _)| *id == track_id).map_or(0, |&(_, duration)| duration)
+  } as u64;
+  let Some(track) = tracks.iter_mut().find(|track| track.track_id == track_id) else {
+    return Ok(());
+  };
+  track.fragments += 1;
+
+  // Without a tfdt box, a fragment follows the previous one of its track.
+  let mut decode_time = match mp4::child(reader, traf, b"tfdt")? {
+    Some(tfdt) => {
+      reader.seek(SeekFrom::Start(tfdt.payload_start()))?;
+      let (version, _) = version_flags(reader)?;
+      if version == 1 { mp4::read_u64(reader)? } else { mp4::read_u32(reader)? as u64 }
+    }
+    None => track.decode_end.unwrap_or(0),
+  };
+  track.first_decode_time = Some(track.first_decode_time.map_or(decode_time, |first| first.min(decode_time)));
+
+  for trun in mp4::children(reader, traf.payload_start(), traf.end())?.into_iter().filter(|b| b.is(b"trun")) {
+    reader.seek(SeekFrom::Start(trun.payload_start()))?;
+    let (_, flags) = version_flags(reader)?;
+    let count = mp4::read_u32(reader)? as u64;
+    if flags & DATA_OFFSET != 0 {
+      mp4::read_u32(reader)?;
+    }
+    if flags & FIRST_SAMPLE_FLAGS != 0 {
+      mp4::read_u32(reader)?;
+    }
+    let entry_size = SAMPLE_FIELDS.iter().filter(|&&field| flags & field != 0).count() as u64 * 4;
+    if entry_size == 0 {
+      // Every sample has the default duration and no composition offset.
+      if count > 0 {
+        present(track, decode_time as i64);
+      }
+      decode_time += count * default_duration;
+      track.samples += count;
+      continue;
+    }
+    // A count running past the end of the box is corrupt; take the samples the box holds.
+    let count = count.min(trun.end().saturating_sub(reader.stream_position()?) / entry_size);
+    for _ in 0..count {
+      let duration = if flags & SAMPLE_DURATION != 0 { mp4::read_u32(reader)? as u64 } else { default_duration };
+      if flags & SAMPLE_SIZE != 0 {
+        mp4::read_u32(reader)?;
+      }
+      if flags & SAMPLE_FLAGS != 0 {
+        mp4::read_u32(reader)?;
+      }
+      // Signed in version 1, and written as such by most version 0 muxers too.
+      let offset = if flags & SAMPLE_COMPOSITION_OFFSET != 0 { mp4::read_u32(reader)? as i32 as i64 } else { 0 };
+      present(track, decode_time as i64 + offset);
+      decode_time += duration;
+    }
+    track.samples += count;
+  }
+  track.decode_end = Some(track.decode_end.map_or(decode_time, |end| end.max(decode_time)));
+  Ok(())
+}
+
+// The track fragments of a local MP4/MOV file; None when it isn't fragmented, i.e. has neither an
+// mvex nor a moof box.
+pub fn read(path: &Path) -> io::Result<Option<Fragmented>> {
+  let file = File::open(path)?;
+  let file_size = file.metadata()?.len();
+  let mut reader = BufReader::new(file);
+
+  let top = mp4::children(&mut reader, 0, file_size)?;
+  let Some(moov) = top.iter().find(|b| b.is(b"moov")) else {
+    return Ok(None);
+  };
+  let mvex = mp4::child(&mut reader, moov, b"mvex")?;
+  let moofs: Vec<&BoxHeader> = top.iter().filter(|b| b.is(b"moof")).collect();
+  if mvex.is_none() && moofs.is_empty() {
+    return Ok(None);
+  }
+  let defaults = match &mvex {
+    Some(mvex) => trex_durations(&mut reader, mvex)?,
+    None => Vec::new(),
+  };
+
+  let mut tracks = Vec::new();
+  let traks = mp4::children(&mut reader, moov.payload_start(), moov.end())?.into_iter().filter(|b| b.is(b"trak"));
+  for (index, trak) in traks.enumerate() {
+    let (track_id, timescale) = track_header(&mut reader, &trak)?;
+    tracks.push(Track {
+      index,
+      track_id,
+      timescale,
+      fragments: 0,
+      samples: 0,
+      first_decode_time: None,
+      decode_end: None,
+      earliest_presentation: None,
+    });
+  }
+  for moof in &moofs {
+    for traf in mp4::children(&mut reader, moof.payload_start(), moof.end())?.into_iter().filter(|b| b.is(b"traf")) {
+      read_traf(&mut reader, &traf, &mut tracks, &defaults)?;
+    }
+  }
+  Ok(Some(Fragmented { fragments: moofs.len() as u64, tracks }))
+}
+
+// Print the fragments and the timing of every track. An init segment without any sample is a
+// finding, since it otherwise shows up as a video of zero frames.
+pub fn report(fragmented: &Fragmented) -> Option<Finding> {
+  out!("{}{}Fragmented MP4 - {}", RESET, BLUE_BOLD, RESET);
+  out!("Movie fragments: {}", format_with_commas(fragmented.fragments as i64));
+  for track in &fragmented.tracks {
+    out!("#{} (track ID {}): {} fragments, {} samples, earliest presentation {}, duration {}", track.index,
+      track.track_id, format_with_commas(track.fragments as i64), format_with_commas(track.samples as i64),
+      track.earliest_presentation_ms().map_or_else(|| "unknown".to_string(), format_signed_millis),
+      format_optional_millis(track.duration_ms()));
+  }
+  if !fragmented.init_only() {
+    out!("Durations: from the track fragment runs, not the container");
+    return None;
+  }
+  out!("Media samples: none, the file is an init segment only");
+  Some(Finding::new(&findings::INIT_SEGMENT_ONLY, format!("{} tracks declared, no movie fragment carries a sample",
+    fragmented.tracks.len())))
+}
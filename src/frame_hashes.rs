@@ -0,0 +1,163 @@
+// --frame-hashes: a hash of every decoded frame's picture data, like ffmpeg's framemd5 muxer, to
+// verify archived files and to compare decodes across thread counts. Only the visible bytes of
+// each row are hashed, never the stride padding, so the hash depends on the content alone.
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+
+use md5::{Digest, Md5};
+use xxhash_rust::xxh3::Xxh3;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Algorithm {
+  Md5,
+  Xxh3,
+}
+
+impl Algorithm {
+  pub fn parse(name: &str) -> Option<Self> {
+    match name.to_ascii_lowercase().as_str() {
+      "md5" => Some(Algorithm::Md5),
+      "xxh3" | "xxhash" => Some(Algorithm::Xxh3),
+      _ => None,
+    }
+  }
+
+  fn name(self) -> &'static str {
+    match self {
+      Algorithm::Md5 => "md5",
+      Algorithm::Xxh3 => "xxh3",
+    }
+  }
+}
+
+enum Hasher {
+  Md5(Md5),
+  Xxh3(Box<Xxh3>),
+}
+
+impl Hasher {
+  fn new(algorithm: Algorithm) -> Self {
+    match algorithm {
+      Algorithm::Md5 => Hasher::Md5(Md5::new()),
+      Algorithm::Xxh3 => Hasher::Xxh3(Box::new(Xxh3::new())),
+    }
+  }
+
+  fn update(&mut self, bytes: &[u8]) {
+    match self {
+      Hasher::Md5(hasher) => hasher.update(bytes),
+      Hasher::Xxh3(hasher) => hasher.update(bytes),
+    }
+  }
+
+  fn finish(self) -> String {
+    match self {
+      Hasher::Md5(hasher) => hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect(),
+      Hasher::Xxh3(hasher) => format!("{:016x}", hasher.digest()),
+    }
+  }
+}
+
+// Hash of the visible bytes of every plane, from the row length at alignment 1.
+fn hash_frame(frame: &ffmpeg::util::frame::video::Video, algorithm: Algorithm) -> String {
+  let mut hasher = Hasher::new(algorithm);
+  for plane in 0..frame.planes() {
+    let row_bytes = unsafe { ffmpeg::ffi::av_image_get_linesize(frame.format().into(), frame.width() as i32, plane as i32) };
+    let (Ok(row_bytes), height) = (usize::try_from(row_bytes), frame.plane_height(plane) as usize) else {
+      continue;
+    };
+    hash_plane(&mut hasher, frame.data(plane), frame.stride(plane), row_bytes, height);
+  }
+  hasher.finish()
+}
+
+// Hash the first `row_bytes` of each of the `height` rows of a plane, leaving out the padding up to
+// the stride.
+fn hash_plane(hasher: &mut Hasher, data: &[u8], stride: usize, row_bytes: usize, height: usize) {
+  for row in 0..height {
+    hasher.update(&data[row * stride..row * stride + row_bytes]);
+  }
+}
+
+// The file to write and the hash to write into it (--frame-hashes, --hash).
+pub struct Settings {
+  pub path: PathBuf,
+  pub algorithm: Algorithm,
+}
+
+pub struct Writer {
+  out: BufWriter<File>,
+  algorithm: Algorithm,
+  // The first write error; lines after it are dropped and the error is returned by finish().
+  error: Option<std::io::Error>,
+}
+
+impl Writer {
+  // Create the file and write the header row.
+  pub fn create(settings: &Settings) -> std::io::Result<Self> {
+    let mut out = BufWriter::new(File::create(&settings.path)?);
+    writeln!(out, "frame,pts,{}", settings.algorithm.name())?;
+    Ok(Writer { out, algorithm: settings.algorithm, error: None })
+  }
+
+  // Write the line of one decoded frame. `index` counts decoded frames from 0.
+  pub fn write(&mut self, index: i64, frame: &ffmpeg::util::frame::video::Video) {
+    if self.error.is_some() {
+      return;
+    }
+    let pts = frame.pts().map_or_else(String::new, |pts| pts.to_string());
+    if let Err(e) = writeln!(self.out, "{},{},{}", index, pts, hash_frame(frame, self.algorithm)) {
+      self.error = Some(e);
+    }
+  }
+
+  // Flush the file, or return the first write error.
+  pub fn finish(mut self) -> std::io::Result<()> {
+    if let Some(error) = self.error.take() {
+      return Err(error);
+    }
+    self.out.flush()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn digest(algorithm: Algorithm, bytes: &[u8]) -> String {
+    let mut hasher = Hasher::new(algorithm);
+    hasher.update(bytes);
+    hasher.finish()
+  }
+
+  #[test]
+  fn algorithm_names() {
+    assert_eq!(Algorithm::parse("md5"), Some(Algorithm::Md5));
+    assert_eq!(Algorithm::parse("MD5"), Some(Algorithm::Md5));
+    assert_eq!(Algorithm::parse("xxh3"), Some(Algorithm::Xxh3));
+    assert_eq!(Algorithm::parse("xxhash"), Some(Algorithm::Xxh3));
+    assert_eq!(Algorithm::parse("sha1"), None);
+    assert_eq!(Algorithm::Md5.name(), "md5");
+  }
+
+  #[test]
+  fn digests_match_the_reference_values() {
+    // The same hex digests as `ffmpeg -f framemd5` and `xxhsum -H3`.
+    assert_eq!(digest(Algorithm::Md5, b""), "d41d8cd98f00b204e9800998ecf8427e");
+    assert_eq!(digest(Algorithm::Md5, b"abc"), "900150983cd24fb0d6963f7d28e17f72");
+    assert_eq!(digest(Algorithm::Xxh3, b""), "2d06800538d394c2");
+  }
+
+  #[test]
+  fn stride_padding_is_not_hashed() {
+    let visible = [1u8, 2, 3, 4, 5, 6];
+    let padded = [1u8, 2, 3, 0xaa, 0xbb, 4, 5, 6, 0xcc, 0xdd];
+    for algorithm in [Algorithm::Md5, Algorithm::Xxh3] {
+      let mut strided = Hasher::new(algorithm);
+      hash_plane(&mut strided, &padded, 5, 3, 2);
+      assert_eq!(strided.finish(), digest(algorithm, &visible));
+    }
+  }
+}
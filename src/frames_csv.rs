@@ -0,0 +1,69 @@
+// --frames-csv: one row per decoded frame, written while decoding so long files don't buffer
+// millions of rows and an aborted run still leaves the frames up to the failure on disk.
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use ffmpeg::Rational;
+
+use crate::timestamp_to_signed_millis;
+
+pub struct Writer {
+  out: BufWriter<File>,
+  rows: u64,
+  // The first write error; rows after it are dropped and the error is returned by finish().
+  error: Option<std::io::Error>,
+}
+
+fn picture_type(kind: ffmpeg::picture::Type) -> &'static str {
+  match kind {
+    ffmpeg::picture::Type::I => "I",
+    ffmpeg::picture::Type::P => "P",
+    ffmpeg::picture::Type::B => "B",
+    ffmpeg::picture::Type::S => "S",
+    ffmpeg::picture::Type::SI => "SI",
+    ffmpeg::picture::Type::SP => "SP",
+    ffmpeg::picture::Type::BI => "BI",
+    ffmpeg::picture::Type::None => "",
+  }
+}
+
+// Empty cell for a missing value.
+fn cell<T: ToString>(value: Option<T>) -> String {
+  value.map_or_else(String::new, |v| v.to_string())
+}
+
+impl Writer {
+  // Create the file and write the header row.
+  pub fn create(path: &Path) -> std::io::Result<Self> {
+    let mut out = BufWriter::new(File::create(path)?);
+    writeln!(out, "frame,pts,dts,timestamp_ms,picture_type,packet_size,key_frame")?;
+    Ok(Writer { out, rows: 0, error: None })
+  }
+
+  // Write the row of one decoded frame. `index` counts decoded frames from 0.
+  pub fn write(&mut self, index: i64, frame: &ffmpeg::util::frame::video::Video, time_base: Rational) {
+    if self.error.is_some() {
+      return;
+    }
+    let packet = frame.packet();
+    let dts = (packet.dts != ffmpeg::ffi::AV_NOPTS_VALUE).then_some(packet.dts);
+    let timestamp_ms = frame.timestamp().and_then(|ts| timestamp_to_signed_millis(ts, time_base));
+    let result = writeln!(self.out, "{},{},{},{},{},{},{}", index, cell(frame.pts()), cell(dts), cell(timestamp_ms),
+      picture_type(frame.kind()), packet.size, frame.is_key());
+    match result {
+      Ok(()) => self.rows += 1,
+      Err(e) => self.error = Some(e),
+    }
+  }
+
+  // Flush the file. Returns the number of rows written, or the first write error.
+  pub fn finish(mut self) -> std::io::Result<u64> {
+    if let Some(error) = self.error.take() {
+      return Err(error);
+    }
+    self.out.flush()?;
+    Ok(self.rows)
+  }
+}
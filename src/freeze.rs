@@ -0,0 +1,144 @@
+// --detect-freeze: stretches where the picture stops changing, as a failing capture card produces
+// while the audio goes on. Each decoded frame's luma plane is hashed in place, every
+// SAMPLE_STEP-th row and sample, and compared with the previous frame's hash; no frame is copied.
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::Hasher;
+
+use ffmpeg::Rational;
+
+use crate::findings::{self, Finding};
+use crate::pixels::PixelSemantics;
+use crate::report::FreezeSegmentReport;
+use crate::timestamp::Timestamp;
+use crate::{format_signed_millis, format_with_commas, millis_to_clock_format, timestamp_to_signed_millis, BLUE_BOLD, RESET};
+
+// Rows and samples skipped between hashed luma samples. Coarse enough that a 4K frame costs about
+// as much as a thumbnail, fine enough that motion and fades still change the hash.
+const SAMPLE_STEP: usize = 8;
+
+// Segments listed in the report.
+const MAX_LISTED_SEGMENTS: usize = 20;
+
+pub struct Segment {
+  // Timestamp of the first frame of the frozen picture.
+  pub start: i64,
+  // Time until the picture changed, or until the last frame at the end of the stream.
+  pub duration: i64,
+  // Frames identical to the first one.
+  pub repeated_frames: u64,
+}
+
+pub struct Tracker {
+  // Shortest freeze reported, in time base units.
+  min_duration: i64,
+  semantics: Option<PixelSemantics>,
+  // False when the pixel format can't be hashed.
+  pub scanned: bool,
+  previous_hash: Option<u64>,
+  // Start, last timestamp and repeated frames of the picture currently unchanged.
+  run: Option<(i64, i64, u64)>,
+  pub segments: Vec<Segment>,
+}
+
+impl Tracker {
+  pub fn new(min_duration_ms: u64, time_base: Rational) -> Self {
+    let min_duration = if time_base.numerator() > 0 {
+      (min_duration_ms as i128 * time_base.denominator() as i128 / (time_base.numerator() as i128 * 1000)) as i64
+    } else {
+      i64::MAX
+    };
+    Tracker { min_duration, semantics: None, scanned: false, previous_hash: None, run: None, segments: Vec::new() }
+  }
+
+  fn hash(&mut self, frame: &ffmpeg::util::frame::video::Video) -> Option<u64> {
+    let semantics = *self.semantics.get_or_insert_with(|| PixelSemantics::from_frame(frame));
+    let bytes = semantics.luma_sample_bytes()?;
+    let data = frame.data(0);
+    let stride = frame.stride(0);
+    let (width, height) = (frame.plane_width(0) as usize, frame.plane_height(0) as usize);
+    let mut hasher = DefaultHasher::new();
+    for row in (0..height).step_by(SAMPLE_STEP) {
+      let line = &data[row * stride..row * stride + width * bytes];
+      for sample in line.chunks_exact(bytes).step_by(SAMPLE_STEP) {
+        hasher.write(sample);
+      }
+    }
+    Some(hasher.finish())
+  }
+
+  // Compare a decoded frame with the previous one. Frames without a timestamp extend the current
+  // picture without moving its end.
+  pub fn check(&mut self, frame: &ffmpeg::util::frame::video::Video) {
+    let Some(hash) = self.hash(frame) else {
+      return;
+    };
+    self.scanned = true;
+    let timestamp = frame.timestamp();
+    if self.previous_hash == Some(hash) {
+      if let Some((_, last, repeated)) = self.run.as_mut() {
+        *last = timestamp.unwrap_or(*last).max(*last);
+        *repeated += 1;
+      }
+    } else {
+      self.close(timestamp);
+      self.run = timestamp.map(|ts| (ts, ts, 0));
+    }
+    self.previous_hash = Some(hash);
+  }
+
+  // End the current picture at `end`, or at its last frame, and keep it if it lasted long enough.
+  fn close(&mut self, end: Option<i64>) {
+    if let Some((start, last, repeated_frames)) = self.run.take() {
+      let duration = end.unwrap_or(last).max(last) - start;
+      if repeated_frames > 0 && duration >= self.min_duration {
+        self.segments.push(Segment { start, duration, repeated_frames });
+      }
+    }
+  }
+
+  // Close the picture still unchanged at the end of the stream.
+  pub fn finish(&mut self) {
+    self.close(None);
+  }
+}
+
+fn millis(ticks: i64, time_base: Rational) -> i64 {
+  timestamp_to_signed_millis(ticks, time_base).unwrap_or(0)
+}
+
+pub fn to_report(tracker: &Tracker, time_base: Rational) -> Vec<FreezeSegmentReport> {
+  tracker.segments.iter().map(|segment| FreezeSegmentReport {
+    start_ms: timestamp_to_signed_millis(segment.start, time_base),
+    duration_ms: timestamp_to_signed_millis(segment.duration, time_base),
+    start_raw: Timestamp::new(segment.start, time_base),
+    duration_raw: Timestamp::new(segment.duration, time_base),
+    repeated_frames: segment.repeated_frames,
+  }).collect()
+}
+
+// Print the frozen segments and return a finding when there are any.
+pub fn report(tracker: &Tracker, time_base: Rational, min_duration_ms: u64) -> Option<Finding> {
+  out!("{}{}Frozen video - {}", RESET, BLUE_BOLD, RESET);
+  if !tracker.scanned {
+    out!("Not checked: the pixel format has no luma plane that can be compared");
+    return None;
+  }
+  out!("Frozen segments of {} or longer: {}", millis_to_clock_format(min_duration_ms),
+    format_with_commas(tracker.segments.len() as i64));
+  for segment in tracker.segments.iter().take(MAX_LISTED_SEGMENTS) {
+    out!("{}: frozen for {} ({} repeated frames)", format_signed_millis(millis(segment.start, time_base)),
+      millis_to_clock_format(millis(segment.duration, time_base).unsigned_abs()),
+      format_with_commas(segment.repeated_frames as i64));
+  }
+  if tracker.segments.len() > MAX_LISTED_SEGMENTS {
+    out!("... {} more", format_with_commas((tracker.segments.len() - MAX_LISTED_SEGMENTS) as i64));
+  }
+  let longest = tracker.segments.iter().max_by_key(|segment| segment.duration)?;
+  let total: i64 = tracker.segments.iter().map(|segment| segment.duration).sum();
+  Some(Finding::new(&findings::FROZEN_VIDEO, format!(
+    "{} frozen segments totalling {}, the longest {}", format_with_commas(tracker.segments.len() as i64),
+    millis_to_clock_format(millis(total, time_base).unsigned_abs()),
+    millis_to_clock_format(millis(longest.duration, time_base).unsigned_abs())))
+    .at(Timestamp::new(longest.start, time_base)))
+}
@@ -0,0 +1,176 @@
+// Dropped and duplicated frames, from the decoded frame timestamps in presentation order. An
+// interval much longer than the median one is taken for missing frames; a timestamp seen twice for
+// a repeated frame. The factor is configurable, since variable rate content has long intervals
+// that aren't drops.
+
+use ffmpeg::Rational;
+
+use crate::findings::{self, Finding};
+use crate::report::{FrameGapReport, GapReport};
+use crate::timestamp::Timestamp;
+use crate::{format_signed_millis, format_with_commas, millis_to_clock_format, timestamp_to_signed_millis, BLUE_BOLD, RESET};
+
+// Gaps listed in the report, longest first.
+const MAX_LISTED_GAPS: usize = 5;
+
+pub struct Gap {
+  // Timestamp of the frame before the gap.
+  pub start: i64,
+  // Length of the interval in time base units.
+  pub length: i64,
+  // Frames estimated missing in it.
+  pub missing: u64,
+}
+
+pub struct Summary {
+  pub median_interval: i64,
+  pub dropped_frames: u64,
+  pub duplicate_frames: u64,
+  // The longest gaps, longest first.
+  pub worst_gaps: Vec<Gap>,
+  pub gap_count: usize,
+}
+
+// Find the gaps longer than `factor` times the median interval and the repeated timestamps. None
+// with fewer than two distinct timestamps.
+pub fn detect(timestamps: &[i64], factor: f64) -> Option<Summary> {
+  let mut sorted = timestamps.to_vec();
+  sorted.sort_unstable();
+  let duplicate_frames = sorted.windows(2).filter(|w| w[0] == w[1]).count() as u64;
+  sorted.dedup();
+  let mut intervals: Vec<i64> = sorted.windows(2).map(|w| w[1] - w[0]).collect();
+  if intervals.is_empty() {
+    return None;
+  }
+  intervals.sort_unstable();
+  let median_interval = intervals[intervals.len() / 2];
+
+  let mut gaps: Vec<Gap> = sorted
+    .windows(2)
+    .filter(|w| (w[1] - w[0]) as f64 > median_interval as f64 * factor)
+    .map(|w| {
+      let length = w[1] - w[0];
+      let missing = ((length as f64 / median_interval as f64).round() as u64).saturating_sub(1).max(1);
+      Gap { start: w[0], length, missing }
+    })
+    .collect();
+  let dropped_frames = gaps.iter().map(|gap| gap.missing).sum();
+  let gap_count = gaps.len();
+  gaps.sort_by_key(|gap| std::cmp::Reverse(gap.length));
+  gaps.truncate(MAX_LISTED_GAPS);
+  Some(Summary { median_interval, dropped_frames, duplicate_frames, worst_gaps: gaps, gap_count })
+}
+
+// The median interval with its fractional milliseconds, e.g. 33.367 for 29.97 fps.
+pub fn median_interval_ms(summary: &Summary, time_base: Rational) -> f64 {
+  summary.median_interval as f64 * time_base.numerator() as f64 / time_base.denominator() as f64 * 1000.0
+}
+
+fn millis(ticks: i64, time_base: Rational) -> i64 {
+  timestamp_to_signed_millis(ticks, time_base).unwrap_or(0)
+}
+
+pub fn to_report(summary: &Summary, time_base: Rational) -> FrameGapReport {
+  FrameGapReport {
+    median_interval_ms: median_interval_ms(summary, time_base),
+    dropped_frames: summary.dropped_frames,
+    duplicate_frames: summary.duplicate_frames,
+    gap_count: summary.gap_count,
+    worst_gaps: summary.worst_gaps.iter().map(|gap| GapReport {
+      start_ms: timestamp_to_signed_millis(gap.start, time_base),
+      length_ms: timestamp_to_signed_millis(gap.length, time_base),
+      start_raw: Timestamp::new(gap.start, time_base),
+      length_raw: Timestamp::new(gap.length, time_base),
+      missing_frames: gap.missing,
+    }).collect(),
+  }
+}
+
+// Print the section and return the findings for drops and duplicates.
+pub fn report(summary: &Summary, time_base: Rational, factor: f64) -> Vec<Finding> {
+  let mut issues = Vec::new();
+  out!("{}{}Dropped and duplicated frames - {}", RESET, BLUE_BOLD, RESET);
+  out!("Median frame interval: {:.3} ms (gaps longer than {}x count as drops)",
+    median_interval_ms(summary, time_base), factor);
+  out!("Estimated dropped frames: {} in {} gaps", format_with_commas(summary.dropped_frames as i64),
+    format_with_commas(summary.gap_count as i64));
+  out!("Duplicated frames: {}", format_with_commas(summary.duplicate_frames as i64));
+  for gap in &summary.worst_gaps {
+    out!("Gap at {}: {} ({} frames missing)", format_signed_millis(millis(gap.start, time_base)),
+      millis_to_clock_format(millis(gap.length, time_base).unsigned_abs()), format_with_commas(gap.missing as i64));
+  }
+
+  if let Some(worst) = summary.worst_gaps.first() {
+    issues.push(Finding::new(&findings::DROPPED_FRAMES, format!(
+      "about {} frames missing in {} gaps, the longest {}",
+      format_with_commas(summary.dropped_frames as i64), format_with_commas(summary.gap_count as i64),
+      millis_to_clock_format(millis(worst.length, time_base).unsigned_abs())))
+      .at(Timestamp::new(worst.start, time_base)));
+  }
+  if summary.duplicate_frames > 0 {
+    issues.push(Finding::new(&findings::DUPLICATE_FRAMES, format!(
+      "{} frames repeat the timestamp of another frame", format_with_commas(summary.duplicate_frames as i64))));
+  }
+  issues
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn frames(timestamps: impl IntoIterator<Item = i64>) -> Vec<i64> {
+    timestamps.into_iter().collect()
+  }
+
+  #[test]
+  fn regular_frames_have_no_gaps() {
+    let summary = detect(&frames((0..100).map(|i| i * 1001)), 1.5).unwrap();
+    assert_eq!(summary.median_interval, 1001);
+    assert_eq!((summary.dropped_frames, summary.duplicate_frames, summary.gap_count), (0, 0, 0));
+    assert!(summary.worst_gaps.is_empty());
+    assert!((median_interval_ms(&summary, Rational::new(1, 30000)) - 33.367).abs() < 0.001);
+  }
+
+  #[test]
+  fn gaps_are_estimated_drops() {
+    // Frames 10-12 and 50 are missing.
+    let timestamps = frames((0..100).filter(|i| !(10..13).contains(i) && *i != 50).map(|i| i * 40));
+    let summary = detect(&timestamps, 1.5).unwrap();
+    assert_eq!(summary.median_interval, 40);
+    assert_eq!((summary.dropped_frames, summary.gap_count), (4, 2));
+    let worst: Vec<(i64, i64, u64)> = summary.worst_gaps.iter().map(|gap| (gap.start, gap.length, gap.missing)).collect();
+    assert_eq!(worst, [(360, 160, 3), (1960, 80, 1)]);
+  }
+
+  #[test]
+  fn the_factor_decides_what_is_a_gap() {
+    let timestamps = frames((0..20).filter(|&i| i != 5).map(|i| i * 40));
+    assert_eq!(detect(&timestamps, 1.5).unwrap().gap_count, 1);
+    assert_eq!(detect(&timestamps, 2.0).unwrap().gap_count, 0);
+  }
+
+  #[test]
+  fn only_the_longest_gaps_are_listed() {
+    let timestamps = frames((0..200).filter(|i| i % 20 != 10).map(|i| i * 40));
+    let summary = detect(&timestamps, 1.5).unwrap();
+    assert_eq!(summary.gap_count, 10);
+    assert_eq!(summary.dropped_frames, 10);
+    assert_eq!(summary.worst_gaps.len(), MAX_LISTED_GAPS);
+  }
+
+  #[test]
+  fn repeated_timestamps_are_duplicates() {
+    let summary = detect(&[0, 40, 40, 80, 120, 120, 120, 160], 1.5).unwrap();
+    assert_eq!(summary.duplicate_frames, 3);
+    assert_eq!(summary.dropped_frames, 0);
+    // Presentation order doesn't matter.
+    assert_eq!(detect(&[120, 0, 80, 40, 40], 1.5).unwrap().duplicate_frames, 1);
+  }
+
+  #[test]
+  fn too_few_timestamps() {
+    assert!(detect(&[], 1.5).is_none());
+    assert!(detect(&[500], 1.5).is_none());
+    assert!(detect(&[500, 500], 1.5).is_none());
+  }
+}
@@ -0,0 +1,300 @@
+// GOP structure: the picture type of every decoded frame, put back in decode order by the DTS of
+// the packet each frame came from, and split into GOPs at the I-frames. The GOP most of them share
+// is the encoder's pattern. Frames before the first I-frame (a cut into an open GOP) and the final
+// GOP, usually truncated by the end of the input, take no part in finding it.
+// A GOP is open when frames decoded after its I-frame are presented before it: leading pictures
+// that may reference the previous GOP. Only the timestamps tell, so HEVC's RASL pictures (which do
+// reference it) and RADL pictures (which don't) are both counted.
+// The reorder depth is how many frames a decoder has to hold back to present in order: for each
+// frame, the frames decoded before it and presented after it. Players size their buffers by it.
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use ffmpeg::Rational;
+use serde::Serialize;
+
+use crate::findings::{self, Finding};
+use crate::timestamp::Timestamp;
+use crate::{format_with_commas, timestamp_to_signed_millis, BLUE_BOLD, RESET};
+
+// Patterns longer than this are printed in their compact form.
+const MAX_PLAIN_CHARS: usize = 32;
+
+// Open GOPs listed.
+const MAX_EXAMPLES: usize = 5;
+
+// Frames decoded before each frame compared with it for the reorder depth; H.264 and HEVC allow
+// at most 16 frames of reordering.
+const REORDER_WINDOW: usize = 32;
+
+#[derive(Clone, Copy)]
+struct Frame {
+  // DTS of the packet the frame came from.
+  dts: Option<i64>,
+  pts: Option<i64>,
+  letter: u8,
+}
+
+#[derive(Default)]
+pub struct Tracker {
+  // Every decoded frame, in output order.
+  frames: Vec<Frame>,
+}
+
+impl Tracker {
+  pub fn push(&mut self, dts: Option<i64>, pts: Option<i64>, kind: ffmpeg::picture::Type, key: bool) {
+    let letter = match kind {
+      ffmpeg::picture::Type::I => b'I',
+      ffmpeg::picture::Type::P => b'P',
+      ffmpeg::picture::Type::B => b'B',
+      // Some intra-only decoders leave the picture type unset on their key frames.
+      ffmpeg::picture::Type::None if key => b'I',
+      _ => b'?',
+    };
+    self.frames.push(Frame { dts, pts, letter });
+  }
+
+  // Whether the frames could be put in decode order: every one of them came with a packet DTS.
+  fn in_decode_order(&self) -> bool {
+    self.frames.iter().all(|frame| frame.dts.is_some())
+  }
+
+  // The frames in decode order when known, output order otherwise.
+  fn ordered(&self) -> Vec<Frame> {
+    let mut frames = self.frames.clone();
+    if self.in_decode_order() {
+      frames.sort_by_key(|frame| frame.dts);
+    }
+    frames
+  }
+}
+
+#[derive(Serialize)]
+pub struct GopStructure {
+  // "decode", or "output" when some frames came without a packet DTS.
+  pub order: &'static str,
+  // Frames before the first I-frame.
+  pub leading_frames: usize,
+  pub gop_count: usize,
+  // The most common GOP among those before the final one, and how many GOPs have it.
+  pub pattern: Option<String>,
+  pub pattern_gops: usize,
+  // Every GOP but the final one has the pattern.
+  pub fixed: bool,
+  // GOPs per length in frames, the final one included.
+  pub lengths: BTreeMap<usize, usize>,
+  pub final_gop_frames: Option<usize>,
+  // The final GOP is the start of the pattern, cut short by the end of the input.
+  pub final_gop_truncated: bool,
+  // GOPs with leading pictures, and the presentation times of the first ones' I-frames.
+  pub open_gops: usize,
+  pub open_gop_examples_ms: Vec<i64>,
+  pub open_gop_examples_raw: Vec<Timestamp>,
+  pub closed_gops: bool,
+  // Largest number of frames decoded before a frame and presented after it; None when the frames
+  // aren't in decode order.
+  pub max_reorder_depth: Option<usize>,
+  // Video delay of the codec parameters, the reordering the stream declares.
+  pub declared_reorder_delay: i32,
+  // Longest run of consecutive B-frames, in decode order.
+  pub longest_b_run: usize,
+  #[serde(skip)]
+  open_gop_examples: Vec<i64>,
+  #[serde(skip)]
+  types: String,
+}
+
+impl GopStructure {
+  // The leading frames and every GOP, one per line.
+  pub fn write_dump(&self, path: &Path) -> std::io::Result<()> {
+    let mut out = BufWriter::new(File::create(path)?);
+    let (leading, gops) = split(&self.types);
+    for gop in leading.into_iter().chain(gops) {
+      writeln!(out, "{}", gop)?;
+    }
+    out.flush()
+  }
+}
+
+// The frames before the first I-frame and the GOPs, each starting with one.
+fn split(types: &str) -> (Option<&str>, Vec<&str>) {
+  let starts: Vec<usize> = types.match_indices('I').map(|(at, _)| at).collect();
+  let leading = match starts.first() {
+    Some(&0) => None,
+    Some(&first) => Some(&types[..first]),
+    None if types.is_empty() => None,
+    None => Some(types),
+  };
+  let gops = starts
+    .iter()
+    .zip(starts.iter().skip(1).copied().chain([types.len()]))
+    .map(|(&start, end)| &types[start..end])
+    .collect();
+  (leading, gops)
+}
+
+// Whether frames of the GOP after its I-frame are presented before it.
+fn is_open(gop: &[Frame]) -> bool {
+  let Some(start) = gop.first().and_then(|frame| frame.pts) else {
+    return false;
+  };
+  gop.iter().skip(1).any(|frame| frame.pts.is_some_and(|pts| pts < start))
+}
+
+fn reorder_depth(frames: &[Frame]) -> usize {
+  (0..frames.len())
+    .filter_map(|i| {
+      let pts = frames[i].pts?;
+      let earlier = &frames[i.saturating_sub(REORDER_WINDOW)..i];
+      Some(earlier.iter().filter(|frame| frame.pts.is_some_and(|earlier| earlier > pts)).count())
+    })
+    .max()
+    .unwrap_or(0)
+}
+
+pub fn analyze(tracker: &Tracker, time_base: Rational, declared_reorder_delay: i32) -> Option<GopStructure> {
+  if tracker.frames.is_empty() {
+    return None;
+  }
+  let frames = tracker.ordered();
+  let types: String = frames.iter().map(|frame| frame.letter as char).collect();
+  let (leading, gops) = split(&types);
+  // The letters are ASCII, so each GOP's byte range is also its range of frames.
+  let mut offset = leading.map_or(0, str::len);
+  let mut open_gop_examples = Vec::new();
+  let mut open_gops = 0;
+  for gop in &gops {
+    let gop_frames = &frames[offset..offset + gop.len()];
+    offset += gop.len();
+    if is_open(gop_frames) {
+      open_gops += 1;
+      if open_gop_examples.len() < MAX_EXAMPLES {
+        open_gop_examples.extend(gop_frames[0].pts);
+      }
+    }
+  }
+  let mut lengths = BTreeMap::new();
+  for gop in &gops {
+    *lengths.entry(gop.len()).or_insert(0) += 1;
+  }
+  // A single GOP is its own pattern; otherwise the final one is left out.
+  let complete = if gops.len() > 1 { &gops[..gops.len() - 1] } else { &gops[..] };
+  let mut counts: Vec<(&str, usize)> = Vec::new();
+  for gop in complete {
+    match counts.iter_mut().find(|(pattern, _)| pattern == gop) {
+      Some((_, count)) => *count += 1,
+      None => counts.push((gop, 1)),
+    }
+  }
+  // The first one seen wins ties.
+  let best = counts.iter().rev().max_by_key(|(_, count)| *count).copied();
+  let final_gop = (gops.len() > 1).then(|| gops[gops.len() - 1]);
+  let final_gop_truncated = match (best, final_gop) {
+    (Some((pattern, _)), Some(last)) => last.len() < pattern.len() && pattern.starts_with(last),
+    _ => false,
+  };
+  let pattern_gops = best.map_or(0, |(pattern, count)| count + usize::from(final_gop == Some(pattern)));
+  Some(GopStructure {
+    order: if tracker.in_decode_order() { "decode" } else { "output" },
+    leading_frames: leading.map_or(0, str::len),
+    gop_count: gops.len(),
+    pattern: best.map(|(pattern, _)| pattern.to_string()),
+    pattern_gops,
+    fixed: best.is_some_and(|(_, count)| count == complete.len()),
+    lengths,
+    final_gop_frames: final_gop.map(str::len),
+    final_gop_truncated,
+    open_gops,
+    open_gop_examples_ms: open_gop_examples.iter().filter_map(|&ts| timestamp_to_signed_millis(ts, time_base)).collect(),
+    open_gop_examples_raw: open_gop_examples.iter().map(|&ts| Timestamp::new(ts, time_base)).collect(),
+    closed_gops: open_gops == 0 && !gops.is_empty(),
+    open_gop_examples,
+    max_reorder_depth: tracker.in_decode_order().then(|| reorder_depth(&frames)),
+    declared_reorder_delay,
+    longest_b_run: types.split(|letter| letter != 'B').map(str::len).max().unwrap_or(0),
+    types,
+  })
+}
+
+// A long GOP written as its I-frame and the repeated run after it, e.g. I(PBB)x83P, when the rest
+// of the GOP repeats; otherwise its first letters.
+fn compact(gop: &str) -> String {
+  if gop.len() <= MAX_PLAIN_CHARS {
+    return gop.to_string();
+  }
+  let (head, rest) = gop.split_at(1);
+  for period in 1..=rest.len() / 2 {
+    let unit = &rest[..period];
+    let repeats = rest.len() / period;
+    let tail = &rest[repeats * period..];
+    if rest.as_bytes().chunks(period).all(|chunk| unit.as_bytes().starts_with(chunk)) {
+      return format!("{}({})x{}{}", head, unit, repeats, tail);
+    }
+  }
+  format!("{}... ({} frames)", &gop[..MAX_PLAIN_CHARS], format_with_commas(gop.len() as i64))
+}
+
+fn describe_lengths(lengths: &BTreeMap<usize, usize>) -> String {
+  let parts: Vec<String> = lengths
+    .iter()
+    .rev()
+    .map(|(length, count)| format!("{} frames x {}", format_with_commas(*length as i64), format_with_commas(*count as i64)))
+    .collect();
+  parts.join(", ")
+}
+
+// Print the GOP structure section.
+pub fn report(structure: &GopStructure, time_base: Rational, verbose: bool) {
+  out!("{}{}GOP structure ({} order) - {}", RESET, BLUE_BOLD, structure.order, RESET);
+  if structure.leading_frames > 0 {
+    out!("Frames before the first I-frame: {}", format_with_commas(structure.leading_frames as i64));
+  }
+  out!("GOPs: {}", format_with_commas(structure.gop_count as i64));
+  match &structure.pattern {
+    Some(pattern) if structure.fixed => out!("GOP pattern: {} (every GOP)", compact(pattern)),
+    Some(pattern) if structure.pattern_gops > 1 => out!("GOP pattern: {} (most common, {} of {} GOPs)", compact(pattern),
+      format_with_commas(structure.pattern_gops as i64), format_with_commas(structure.gop_count as i64)),
+    Some(_) => out!("GOP pattern: none, the GOPs all differ"),
+    None => out!("GOP pattern: none, no I-frame decoded"),
+  }
+  if !structure.lengths.is_empty() {
+    out!("GOP lengths: {}", describe_lengths(&structure.lengths));
+  }
+  if let Some(frames) = structure.final_gop_frames {
+    out!("Final GOP: {} frames{}", format_with_commas(frames as i64),
+      if structure.final_gop_truncated { ", the start of the pattern cut short by the end" } else { "" });
+  }
+  if structure.gop_count == 0 {
+    return;
+  }
+  let examples: Vec<String> = structure.open_gop_examples.iter().map(|&ts| Timestamp::new(ts, time_base).describe(verbose)).collect();
+  let more = if structure.open_gops > examples.len() { ", ..." } else { "" };
+  out!("Open GOPs (leading pictures presented before the I-frame, by timestamp): {}{}",
+    format_with_commas(structure.open_gops as i64),
+    if examples.is_empty() { String::new() } else { format!(" (at {}{})", examples.join(", "), more) });
+  out!("Closed GOPs: {}", if structure.closed_gops { "yes" } else { "no" });
+}
+
+// Print the measured reorder depth next to the declared one and flag a stream that reorders more
+// than it declares, which underflows the buffers of players sized by the declaration.
+pub fn report_reordering(structure: &GopStructure) -> Option<Finding> {
+  let declared_delay = structure.declared_reorder_delay;
+  out!("{}{}Reordering - {}", RESET, BLUE_BOLD, RESET);
+  out!("Longest B-frame run: {} frames", format_with_commas(structure.longest_b_run as i64));
+  out!("Declared reorder delay: {} frames", declared_delay);
+  let Some(depth) = structure.max_reorder_depth else {
+    out!("Max reorder depth: unknown, frames without a packet DTS");
+    return None;
+  };
+  let agreement = match (depth as i64).cmp(&i64::from(declared_delay)) {
+    std::cmp::Ordering::Equal => "",
+    std::cmp::Ordering::Greater => " (more than declared)",
+    std::cmp::Ordering::Less => " (less than declared)",
+  };
+  out!("Max reorder depth: {} frames{}", format_with_commas(depth as i64), agreement);
+  (depth as i64 > i64::from(declared_delay)).then(|| Finding::new(&findings::REORDER_DEPTH_MISMATCH,
+    format!("frames are reordered by up to {} frames, the stream declares {}", depth, declared_delay)))
+}
@@ -0,0 +1,177 @@
+// --format html: the analysis as one self-contained page for sharing QC results: the summary
+// table, the findings, and bar charts of the bitrate over time, the video packet sizes and the key
+// frame intervals, drawn as inline SVG from the series behind the text report. The page fetches
+// nothing. The per-second bitrate is averaged into at most MAX_BARS bars and the distributions
+// are binned, so hours of content keep the page small. The header names the tool version, the
+// file, and the xxh3-128 hash of a local file so a report can be traced back to its input.
+
+use std::fmt::Write;
+use std::fs::File;
+use std::io::Read;
+
+use ffmpeg::Rational;
+use xxhash_rust::xxh3::Xxh3;
+
+use crate::report::AnalysisReport;
+use crate::{bitrate, cadence, format_bytes, format_with_commas, millis_to_clock_format, network, packet_sizes, pipe};
+
+// Bars of the bitrate chart, and bins of the key frame interval chart.
+const MAX_BARS: usize = 400;
+const MAX_BINS: usize = 40;
+
+// Narrowest bin of the key frame interval chart.
+const INTERVAL_BIN_MS: u64 = 100;
+
+const CHART_WIDTH: f64 = 800.0;
+const CHART_HEIGHT: f64 = 200.0;
+
+const STYLE: &str = "body{font-family:system-ui,sans-serif;margin:2em;color:#222}\
+h1{font-size:1.4em}h2{font-size:1.1em;margin-top:2em}\
+table{border-collapse:collapse}td,th{border:1px solid #ccc;padding:4px 8px;text-align:left}\
+.meta{color:#666;font-size:.9em}.error{color:#b00}.warning{color:#b60}.internal{color:#b00}\
+svg{background:#f8f8f8;border:1px solid #ddd}rect{fill:#3b6fb6}text{font-size:11px;fill:#444}";
+
+fn escape(text: &str) -> String {
+  text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+// The xxh3-128 hash of a local file; URLs and stdin can't be read again.
+fn hash_file(path: &str) -> Option<String> {
+  if network::is_url(path) || pipe::is_stdin(path) {
+    return None;
+  }
+  let mut file = File::open(path).ok()?;
+  let mut hasher = Xxh3::new();
+  let mut buffer = vec![0; 1 << 20];
+  loop {
+    match file.read(&mut buffer).ok()? {
+      0 => return Some(format!("{:032x}", hasher.digest128())),
+      read => hasher.update(&buffer[..read]),
+    }
+  }
+}
+
+// A bar chart, each bar with its label and value in a tooltip.
+fn bar_chart(bars: &[(String, f64)], describe: impl Fn(f64) -> String) -> String {
+  let Some(max) = bars.iter().map(|(_, value)| *value).reduce(f64::max).filter(|max| *max > 0.0) else {
+    return "<p>No data.</p>".to_string();
+  };
+  let width = CHART_WIDTH / bars.len() as f64;
+  let mut svg = format!("<svg width=\"{}\" height=\"{}\" viewBox=\"0 0 {} {}\">", CHART_WIDTH, CHART_HEIGHT + 20.0,
+    CHART_WIDTH, CHART_HEIGHT + 20.0);
+  for (i, (label, value)) in bars.iter().enumerate() {
+    let height = value / max * (CHART_HEIGHT - 15.0);
+    let _ = write!(svg, "<rect x=\"{:.1}\" y=\"{:.1}\" width=\"{:.1}\" height=\"{:.1}\"><title>{}: {}</title></rect>",
+      i as f64 * width, CHART_HEIGHT - height, (width - 1.0).max(0.5), height, escape(label), escape(&describe(*value)));
+  }
+  let (first, last) = (&bars[0].0, &bars[bars.len() - 1].0);
+  let _ = write!(svg, "<text x=\"2\" y=\"11\">max {}</text><text x=\"2\" y=\"{}\">{}</text>\
+    <text x=\"{}\" y=\"{}\" text-anchor=\"end\">{}</text></svg>", escape(&describe(max)), CHART_HEIGHT + 15.0,
+    escape(first), CHART_WIDTH - 2.0, CHART_HEIGHT + 15.0, escape(last));
+  svg
+}
+
+// The per-second bitrate averaged over runs of seconds, at most MAX_BARS of them.
+fn bitrate_bars(tracker: &bitrate::Tracker) -> Vec<(String, f64)> {
+  let series = tracker.series();
+  let seconds = series.len().div_ceil(MAX_BARS).max(1);
+  series.chunks(seconds).map(|chunk| {
+    let bits = chunk.iter().map(|&(_, bytes)| bytes * 8).sum::<u64>();
+    (millis_to_clock_format(chunk[0].0.max(0) as u64 * 1000), bits as f64 / chunk.len() as f64)
+  }).collect()
+}
+
+// Packet counts per power-of-two size class, the empty classes in between included.
+fn size_bars(tracker: &packet_sizes::Tracker) -> Vec<(String, f64)> {
+  let (Some((&first, _)), Some((&last, _))) = (tracker.size_classes.first_key_value(), tracker.size_classes.last_key_value()) else {
+    return Vec::new();
+  };
+  (first..=last).map(|class| {
+    let label = format!("{} to {}", format_bytes(1u64 << class), format_bytes(1u64 << (class + 1)));
+    (label, tracker.size_classes.get(&class).copied().unwrap_or(0) as f64)
+  }).collect()
+}
+
+// Key frame intervals binned by INTERVAL_BIN_MS, or wider bins when they would be more than
+// MAX_BINS.
+fn interval_bars(keyframe_timestamps: &[i64], time_base: Rational) -> Vec<(String, f64)> {
+  let Some(intervals) = cadence::intervals_ms(keyframe_timestamps, time_base).filter(|intervals| !intervals.is_empty()) else {
+    return Vec::new();
+  };
+  let (min, max) = (*intervals.iter().min().unwrap_or(&0), *intervals.iter().max().unwrap_or(&0));
+  let bins = ((max - min) / INTERVAL_BIN_MS) as usize + 1;
+  let bin_ms = INTERVAL_BIN_MS * bins.div_ceil(MAX_BINS) as u64;
+  let start = min / bin_ms * bin_ms;
+  let mut counts = vec![0u64; ((max - start) / bin_ms) as usize + 1];
+  for interval in &intervals {
+    counts[((interval - start) / bin_ms) as usize] += 1;
+  }
+  counts.iter().enumerate().map(|(i, &count)| {
+    let from = start + i as u64 * bin_ms;
+    (format!("{:.1}s to {:.1}s", from as f64 / 1000.0, (from + bin_ms) as f64 / 1000.0), count as f64)
+  }).collect()
+}
+
+fn row(html: &mut String, name: &str, value: String) {
+  let _ = write!(html, "<tr><th>{}</th><td>{}</td></tr>", name, escape(&value));
+}
+
+// Render the page of an analysis.
+pub fn render(report: &AnalysisReport, bitrate: &bitrate::Tracker, packet_sizes: &packet_sizes::Tracker,
+  keyframe_timestamps: &[i64], time_base: Rational) -> String {
+  let unknown = || "unknown".to_string();
+  let millis = |ms: Option<u64>| ms.map_or_else(unknown, millis_to_clock_format);
+  let mut html = String::new();
+  let _ = write!(html, "<!DOCTYPE html><html><head><meta charset=\"utf-8\"><title>{} - video_analyzer</title>\
+    <style>{}</style></head><body>", escape(&report.path), STYLE);
+  let _ = write!(html, "<h1>{}</h1><p class=\"meta\">video_analyzer {}<br>xxh3-128: {}</p>", escape(&report.path),
+    env!("CARGO_PKG_VERSION"), hash_file(&report.path).unwrap_or_else(|| "not computed for URLs and stdin".to_string()));
+
+  html.push_str("<h2>Summary</h2><table>");
+  row(&mut html, "Codec", report.codec.clone().unwrap_or_else(unknown));
+  row(&mut html, "Container duration", millis(report.container_duration_ms));
+  row(&mut html, "Decoded duration", millis(report.decoded_duration_ms));
+  row(&mut html, "Duration check", match report.duration_check_passed {
+    Some(true) => "PASS".to_string(),
+    Some(false) => "FAIL".to_string(),
+    None => "not checked".to_string(),
+  });
+  row(&mut html, "A/V drift check", match report.av_drift_check_passed {
+    Some(true) => "PASS".to_string(),
+    Some(false) => "FAIL".to_string(),
+    None => "not checked".to_string(),
+  });
+  row(&mut html, "Faststart", report.mp4_layout.as_ref()
+    .map_or_else(|| "not a local MP4/MOV file".to_string(), |layout| layout.faststart.to_string()));
+  row(&mut html, "Frames", report.frame_count.map_or_else(unknown, format_with_commas));
+  row(&mut html, "Key frames", report.keyframe_count.map_or_else(unknown, format_with_commas));
+  row(&mut html, "Frame rate", report.measured_fps.map_or_else(unknown, |fps| format!("{:.3} fps measured", fps)));
+  row(&mut html, "Video bitrate", report.bitrate.as_ref().map_or_else(unknown, |bitrate| {
+    format!("avg {} kb/s, max {} kb/s", format_with_commas((bitrate.avg_bps / 1000) as i64),
+      format_with_commas((bitrate.max_bps / 1000) as i64))
+  }));
+  row(&mut html, "Decode errors", report.decode_errors.map_or_else(unknown, |errors| format_with_commas(errors as i64)));
+  html.push_str("</table>");
+
+  html.push_str("<h2>Findings</h2>");
+  if report.findings.is_empty() {
+    html.push_str("<p>None.</p>");
+  } else {
+    html.push_str("<table><tr><th>Severity</th><th>Code</th><th>Time</th><th>Message</th></tr>");
+    for finding in &report.findings {
+      let _ = write!(html, "<tr><td class=\"{}\">{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+        finding.severity.to_lowercase(), finding.severity, finding.code, finding.timestamp_ms.map_or_else(String::new, |ms| millis_to_clock_format(ms.max(0) as u64)),
+        escape(&finding.message));
+    }
+    html.push_str("</table>");
+  }
+
+  let kbps = |bps: f64| format!("{} kb/s", format_with_commas((bps / 1000.0) as i64));
+  let count = |count: f64| format!("{} packets", format_with_commas(count as i64));
+  let _ = write!(html, "<h2>Bitrate over time</h2>{}", bar_chart(&bitrate_bars(bitrate), kbps));
+  let _ = write!(html, "<h2>Video packet sizes</h2>{}", bar_chart(&size_bars(packet_sizes), count));
+  let _ = write!(html, "<h2>Key frame intervals</h2>{}", bar_chart(&interval_bars(keyframe_timestamps, time_base),
+    |count| format!("{} intervals", format_with_commas(count as i64))));
+  html.push_str("</body></html>\n");
+  html
+}
@@ -0,0 +1,159 @@
+// --hwaccel: decode the analyzed stream on a hardware device. Frames stay in device memory and are
+// only copied to system memory when a pixel-level check needs their samples; counting frames, key
+// frames and timestamps reads the frame properties alone, which are the same on either path.
+
+use std::ffi::{CStr, CString};
+use std::ptr;
+
+use ffmpeg::ffi::{
+  av_frame_copy_props, av_frame_unref, av_hwdevice_ctx_create, av_hwdevice_find_type_by_name, av_hwdevice_get_type_name,
+  av_hwframe_transfer_data, avcodec_default_get_format, avcodec_get_hw_config,
+  AVCodecContext, AVHWDeviceType, AVPixelFormat, AV_CODEC_HW_CONFIG_METHOD_HW_DEVICE_CTX,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Accel {
+  // The first device the codec supports that can be opened.
+  Auto,
+  Vaapi,
+  VideoToolbox,
+  Cuda,
+  None,
+}
+
+impl Accel {
+  pub fn parse(name: &str) -> Option<Self> {
+    match name.to_ascii_lowercase().as_str() {
+      "auto" => Some(Accel::Auto),
+      "vaapi" => Some(Accel::Vaapi),
+      "videotoolbox" => Some(Accel::VideoToolbox),
+      "cuda" => Some(Accel::Cuda),
+      "none" => Some(Accel::None),
+      _ => None,
+    }
+  }
+
+  fn device_name(self) -> Option<&'static str> {
+    match self {
+      Accel::Vaapi => Some("vaapi"),
+      Accel::VideoToolbox => Some("videotoolbox"),
+      Accel::Cuda => Some("cuda"),
+      Accel::Auto | Accel::None => None,
+    }
+  }
+}
+
+// Pick the hardware pixel format stored in `opaque` by `setup`, or let FFmpeg choose a software
+// format when the decoder doesn't offer it (e.g. an unsupported profile).
+unsafe extern "C" fn get_format(context: *mut AVCodecContext, formats: *const AVPixelFormat) -> AVPixelFormat {
+  let wanted = (*context).opaque as isize;
+  let mut format = formats;
+  while *format != AVPixelFormat::AV_PIX_FMT_NONE {
+    if *format as isize == wanted {
+      return *format;
+    }
+    format = format.add(1);
+  }
+  avcodec_default_get_format(context, formats)
+}
+
+// The hardware pixel format of the codec for a device type, when it decodes through a device
+// context.
+fn hw_format(codec: ffmpeg::Codec, device_type: AVHWDeviceType) -> Option<AVPixelFormat> {
+  (0..).map_while(|index| unsafe { avcodec_get_hw_config(codec.as_ptr(), index).as_ref() }).find_map(|config| {
+    let usable = config.methods & AV_CODEC_HW_CONFIG_METHOD_HW_DEVICE_CTX != 0 && config.device_type == device_type;
+    usable.then_some(config.pix_fmt)
+  })
+}
+
+fn device_type_name(device_type: AVHWDeviceType) -> String {
+  unsafe {
+    let name = av_hwdevice_get_type_name(device_type);
+    if name.is_null() { "unknown".to_string() } else { CStr::from_ptr(name).to_string_lossy().into_owned() }
+  }
+}
+
+// Open a device of this type and attach it to the decoder context.
+fn attach(
+  context: &mut ffmpeg::codec::context::Context,
+  codec: ffmpeg::Codec,
+  device_type: AVHWDeviceType,
+) -> Result<String, String> {
+  let name = device_type_name(device_type);
+  let format = hw_format(codec, device_type).ok_or_else(|| format!("{} can't decode {}", name, codec.name()))?;
+  let mut device = ptr::null_mut();
+  let result = unsafe { av_hwdevice_ctx_create(&mut device, device_type, ptr::null(), ptr::null_mut(), 0) };
+  if result < 0 {
+    return Err(format!("no {} device could be opened ({})", name, ffmpeg::Error::from(result)));
+  }
+  unsafe {
+    let raw = context.as_mut_ptr();
+    // The context owns the device reference from here and releases it when it is freed.
+    (*raw).hw_device_ctx = device;
+    (*raw).opaque = format as isize as *mut std::ffi::c_void;
+    (*raw).get_format = Some(get_format);
+  }
+  Ok(name)
+}
+
+// Set up hardware decoding on a decoder context before it is opened. Returns the device name, or
+// why decoding stays in software; None when no accelerator was requested.
+pub fn setup(context: &mut ffmpeg::codec::context::Context, accel: Accel) -> Option<Result<String, String>> {
+  if accel == Accel::None {
+    return None;
+  }
+  let Some(codec) = ffmpeg::decoder::find(context.id()) else {
+    return Some(Err(format!("no decoder for {:?}", context.id())));
+  };
+  if let Some(name) = accel.device_name() {
+    let name = CString::new(name).unwrap_or_default();
+    let device_type = unsafe { av_hwdevice_find_type_by_name(name.as_ptr()) };
+    if device_type == AVHWDeviceType::AV_HWDEVICE_TYPE_NONE {
+      return Some(Err(format!("this FFmpeg build has no {} support", name.to_string_lossy())));
+    }
+    return Some(attach(context, codec, device_type));
+  }
+
+  // Auto: every device type the codec can decode with, in FFmpeg's order.
+  let mut reasons = Vec::new();
+  let device_types = (0..).map_while(|index| unsafe { avcodec_get_hw_config(codec.as_ptr(), index).as_ref() })
+    .filter(|config| config.methods & AV_CODEC_HW_CONFIG_METHOD_HW_DEVICE_CTX != 0)
+    .map(|config| config.device_type)
+    .collect::<Vec<_>>();
+  for device_type in device_types {
+    match attach(context, codec, device_type) {
+      Ok(name) => return Some(Ok(name)),
+      Err(reason) => reasons.push(reason),
+    }
+  }
+  if reasons.is_empty() {
+    reasons.push(format!("this FFmpeg build has no hardware decoder for {}", codec.name()));
+  }
+  Some(Err(reasons.join("; ")))
+}
+
+// Whether the frame holds a reference to device memory rather than samples.
+pub fn is_hardware(frame: &ffmpeg::util::frame::video::Video) -> bool {
+  frame.format().descriptor()
+    .is_some_and(|d| unsafe { (*d.as_ptr()).flags } & ffmpeg::ffi::AV_PIX_FMT_FLAG_HWACCEL != 0)
+}
+
+// Copy a hardware frame's samples and properties into a software frame, replacing its previous
+// contents.
+pub fn transfer(
+  frame: &ffmpeg::util::frame::video::Video,
+  software: &mut ffmpeg::util::frame::video::Video,
+) -> Result<(), ffmpeg::Error> {
+  unsafe {
+    av_frame_unref(software.as_mut_ptr());
+    let result = av_hwframe_transfer_data(software.as_mut_ptr(), frame.as_ptr(), 0);
+    if result < 0 {
+      return Err(ffmpeg::Error::from(result));
+    }
+    let result = av_frame_copy_props(software.as_mut_ptr(), frame.as_ptr());
+    if result < 0 {
+      return Err(ffmpeg::Error::from(result));
+    }
+  }
+  Ok(())
+}
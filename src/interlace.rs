@@ -0,0 +1,116 @@
+// Interlacing of the decoded frames: how many are interlaced and in which field order, against the
+// field order the stream parameters declare. Badly flagged mezzanine files often declare one and
+// carry the other, which a deinterlacer trusting the flag gets wrong.
+
+use ffmpeg::ffi::AVFieldOrder;
+
+use crate::findings::{self, Finding};
+use crate::report::InterlaceReport;
+use crate::{format_with_commas, BLUE_BOLD, RESET};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FieldOrder {
+  Progressive,
+  TopFirst,
+  BottomFirst,
+  // Interlaced frames of both orders, or progressive and interlaced frames together.
+  Mixed,
+}
+
+impl FieldOrder {
+  pub fn name(self) -> &'static str {
+    match self {
+      FieldOrder::Progressive => "progressive",
+      FieldOrder::TopFirst => "TFF",
+      FieldOrder::BottomFirst => "BFF",
+      FieldOrder::Mixed => "mixed",
+    }
+  }
+}
+
+// The field order of the stream parameters, by the field displayed first. None when unknown.
+pub fn declared(parameters: &ffmpeg::codec::Parameters) -> Option<FieldOrder> {
+  match unsafe { (*parameters.as_ptr()).field_order } {
+    AVFieldOrder::AV_FIELD_PROGRESSIVE => Some(FieldOrder::Progressive),
+    // TB and BT are coded in one order and displayed in the other.
+    AVFieldOrder::AV_FIELD_TT | AVFieldOrder::AV_FIELD_BT => Some(FieldOrder::TopFirst),
+    AVFieldOrder::AV_FIELD_BB | AVFieldOrder::AV_FIELD_TB => Some(FieldOrder::BottomFirst),
+    _ => None,
+  }
+}
+
+#[derive(Default)]
+pub struct Tracker {
+  pub progressive: u64,
+  pub top_first: u64,
+  pub bottom_first: u64,
+}
+
+impl Tracker {
+  pub fn push(&mut self, frame: &ffmpeg::util::frame::video::Video) {
+    if !frame.is_interlaced() {
+      self.progressive += 1;
+    } else if frame.is_top_first() {
+      self.top_first += 1;
+    } else {
+      self.bottom_first += 1;
+    }
+  }
+
+  pub fn interlaced(&self) -> u64 {
+    self.top_first + self.bottom_first
+  }
+
+  // Field order of the interlaced frames alone. None without any.
+  pub fn field_order(&self) -> Option<FieldOrder> {
+    match (self.top_first, self.bottom_first) {
+      (0, 0) => None,
+      (_, 0) => Some(FieldOrder::TopFirst),
+      (0, _) => Some(FieldOrder::BottomFirst),
+      _ => Some(FieldOrder::Mixed),
+    }
+  }
+
+  // Scan type of the stream: progressive, one field order, or mixed. None without any frame.
+  pub fn detected(&self) -> Option<FieldOrder> {
+    match (self.progressive, self.field_order()) {
+      (0, None) => None,
+      (_, None) => Some(FieldOrder::Progressive),
+      (0, order) => order,
+      _ => Some(FieldOrder::Mixed),
+    }
+  }
+}
+
+// None until a frame was decoded.
+pub fn to_report(tracker: &Tracker, declared: Option<FieldOrder>) -> Option<InterlaceReport> {
+  tracker.detected().map(|detected| InterlaceReport {
+    interlaced_frames: tracker.interlaced(),
+    progressive_frames: tracker.progressive,
+    detected_scan_type: detected.name(),
+    detected_field_order: tracker.field_order().map(|order| order.name()),
+    declared_field_order: declared.map(|order| order.name()),
+  })
+}
+
+// Print the interlacing section and return a finding when the decoded frames disagree with the
+// declared field order.
+pub fn report(tracker: &Tracker, declared: Option<FieldOrder>) -> Option<Finding> {
+  let detected = tracker.detected()?;
+  out!("{}{}Interlacing - {}", RESET, BLUE_BOLD, RESET);
+  out!("Interlaced frames: {}", format_with_commas(tracker.interlaced() as i64));
+  out!("Progressive frames: {}", format_with_commas(tracker.progressive as i64));
+  out!("Detected scan type: {}", detected.name());
+  out!("Detected field order: {}", tracker.field_order().map_or("none (no interlaced frames)", FieldOrder::name));
+  out!("Declared field order: {}", declared.map_or("unknown", FieldOrder::name));
+  let declared = declared?;
+  if declared == detected {
+    return None;
+  }
+  let message = format!("stream parameters declare {} but the decoded frames are {} ({} interlaced: {} TFF, {} BFF; \
+    {} progressive)", declared.name(), detected.name(), format_with_commas(tracker.interlaced() as i64),
+    format_with_commas(tracker.top_first as i64), format_with_commas(tracker.bottom_first as i64),
+    format_with_commas(tracker.progressive as i64));
+  out!("Field order mismatch: {}", message);
+  Some(Finding::new(&findings::FIELD_ORDER_MISMATCH, message))
+}
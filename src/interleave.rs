@@ -0,0 +1,249 @@
+// Mux interleaving: how large the contiguous single-stream runs of the file are. Progressive
+// playback over HTTP has to download a whole run of one stream before it gets the next piece of
+// the others, so big runs mean big buffers or extra range requests.
+
+use ffmpeg::Rational;
+
+use crate::findings::{self, Finding};
+use crate::{format_with_commas, BLUE_BOLD, RESET};
+
+// What is kept of every packet read, in read order.
+struct PacketRecord {
+  position: i64,
+  size: u64,
+  stream: usize,
+  dts: Option<i64>,
+  duration: i64,
+}
+
+#[derive(Default)]
+pub struct Tracker {
+  records: Vec<PacketRecord>,
+}
+
+impl Tracker {
+  pub fn push(&mut self, packet: &ffmpeg::Packet) {
+    self.records.push(PacketRecord {
+      position: packet.position() as i64,
+      size: packet.size() as u64,
+      stream: packet.stream(),
+      dts: packet.dts(),
+      duration: packet.duration(),
+    });
+  }
+}
+
+// Runs exceeding either limit are flagged.
+#[derive(Clone, Copy)]
+pub struct Limits {
+  pub max_bytes: u64,
+  pub max_seconds: f64,
+}
+
+pub const DEFAULT_MAX_RUN_MB: u64 = 2;
+pub const DEFAULT_MAX_RUN_SECONDS: f64 = 5.0;
+
+// Contiguous runs of one stream.
+#[derive(Default)]
+struct StreamRuns {
+  count: u64,
+  total_bytes: u64,
+  max_bytes: u64,
+  total_seconds: f64,
+  max_seconds: f64,
+  // Byte offset where the largest run (by bytes or time) starts.
+  worst_position: i64,
+}
+
+fn seconds(ticks: i64, time_base: Rational) -> f64 {
+  if time_base.denominator() <= 0 {
+    return 0.0;
+  }
+  ticks as f64 * time_base.numerator() as f64 / time_base.denominator() as f64
+}
+
+// Group packets by physical position into same-stream runs. Demuxers such as MP4 return packets
+// in timestamp order rather than file order, so the records are sorted by byte position first.
+fn positional_runs(records: &[PacketRecord], time_bases: &[Rational]) -> Vec<StreamRuns> {
+  let mut sorted: Vec<&PacketRecord> = records.iter().filter(|r| r.position >= 0).collect();
+  sorted.sort_by_key(|r| r.position);
+
+  let mut runs: Vec<StreamRuns> = (0..time_bases.len()).map(|_| StreamRuns::default()).collect();
+  let mut start = 0;
+  while start < sorted.len() {
+    let stream = sorted[start].stream;
+    let mut end = start;
+    while end < sorted.len() && sorted[end].stream == stream {
+      end += 1;
+    }
+    let run = &sorted[start..end];
+    let bytes: u64 = run.iter().map(|r| r.size).sum();
+    let dts: Vec<i64> = run.iter().filter_map(|r| r.dts).collect();
+    let time_base = time_bases.get(stream).copied().unwrap_or(Rational::new(0, 1));
+    let span = match (dts.iter().min(), dts.iter().max()) {
+      (Some(first), Some(last)) => seconds(last - first + run[end - start - 1].duration.max(0), time_base),
+      _ => 0.0,
+    };
+
+    if let Some(stats) = runs.get_mut(stream) {
+      stats.count += 1;
+      stats.total_bytes += bytes;
+      stats.total_seconds += span;
+      if bytes > stats.max_bytes || span > stats.max_seconds {
+        stats.worst_position = run[0].position;
+      }
+      stats.max_bytes = stats.max_bytes.max(bytes);
+      stats.max_seconds = stats.max_seconds.max(span);
+    }
+    start = end;
+  }
+  runs
+}
+
+// Without byte positions, the largest lag in read order between a stream's timestamp and the
+// newest timestamp of any other stream, in seconds per stream.
+fn dts_skew(records: &[PacketRecord], time_bases: &[Rational]) -> Vec<f64> {
+  let mut latest: Vec<Option<f64>> = vec![None; time_bases.len()];
+  let mut skew = vec![0.0f64; time_bases.len()];
+  for record in records {
+    let (Some(dts), Some(&time_base)) = (record.dts, time_bases.get(record.stream)) else {
+      continue;
+    };
+    let t = seconds(dts, time_base);
+    let newest_other = latest
+      .iter()
+      .enumerate()
+      .filter(|(i, _)| *i != record.stream)
+      .filter_map(|(_, t)| *t)
+      .fold(f64::NEG_INFINITY, f64::max);
+    if newest_other.is_finite() {
+      skew[record.stream] = skew[record.stream].max(newest_other - t);
+    }
+    latest[record.stream] = Some(latest[record.stream].map_or(t, |l: f64| l.max(t)));
+  }
+  skew
+}
+
+// Print the interleaving section and flag runs over the limits. Single-stream files have nothing
+// to interleave and print nothing.
+pub fn report(tracker: &Tracker, ictx: &ffmpeg::format::context::Input, limits: Limits) -> Vec<Finding> {
+  let mut issues = Vec::new();
+  let time_bases: Vec<Rational> = ictx.streams().map(|s| s.time_base()).collect();
+  if time_bases.len() < 2 || tracker.records.is_empty() {
+    return issues;
+  }
+  out!("{}{}Interleaving - {}", RESET, BLUE_BOLD, RESET);
+
+  let positioned = tracker.records.iter().filter(|r| r.position >= 0).count();
+  if positioned * 2 < tracker.records.len() {
+    out!("Note: the demuxer doesn't report packet positions; showing timestamp skew between streams instead.");
+    for (stream, skew) in dts_skew(&tracker.records, &time_bases).into_iter().enumerate() {
+      out!("#{}: max lag behind other streams {:.3}s", stream, skew);
+      if skew > limits.max_seconds {
+        issues.push(Finding::new(&findings::POOR_INTERLEAVING, format!(
+          "stream #{} lags other streams by up to {:.3}s (limit {:.1}s)", stream, skew, limits.max_seconds)));
+      }
+    }
+    return issues;
+  }
+
+  for (stream, runs) in positional_runs(&tracker.records, &time_bases).into_iter().enumerate() {
+    if runs.count == 0 {
+      continue;
+    }
+    out!("#{}: {} runs, avg {} bytes / {:.3}s, max {} bytes / {:.3}s", stream, format_with_commas(runs.count as i64),
+      format_with_commas((runs.total_bytes / runs.count) as i64), runs.total_seconds / runs.count as f64,
+      format_with_commas(runs.max_bytes as i64), runs.max_seconds);
+    if runs.max_bytes > limits.max_bytes || runs.max_seconds > limits.max_seconds {
+      issues.push(Finding::new(&findings::POOR_INTERLEAVING, format!(
+        "stream #{} has a contiguous run of {} bytes / {:.3}s at byte {} (limits {} bytes / {:.1}s)",
+        stream, format_with_commas(runs.max_bytes as i64), runs.max_seconds, runs.worst_position,
+        format_with_commas(limits.max_bytes as i64), limits.max_seconds)));
+    }
+  }
+  issues
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn record(position: i64, size: u64, stream: usize, dts: Option<i64>, duration: i64) -> PacketRecord {
+    PacketRecord { position, size, stream, dts, duration }
+  }
+
+  fn assert_seconds(actual: f64, expected: f64) {
+    assert!((actual - expected).abs() < 1e-9, "{actual} != {expected}");
+  }
+
+  #[test]
+  fn runs_follow_the_byte_positions() {
+    let millis = [Rational::new(1, 1000); 2];
+    // Video in packets of 40 ms, audio of 20 ms, listed in timestamp order as MP4 demuxers return
+    // them rather than in file order.
+    let records = [
+      record(0, 100, 0, Some(0), 40),
+      record(300, 50, 1, Some(0), 20),
+      record(350, 50, 1, Some(20), 20),
+      record(100, 100, 0, Some(40), 40),
+      record(1400, 50, 1, Some(40), 20),
+      record(200, 100, 0, Some(80), 40),
+      record(400, 1000, 0, Some(120), 40),
+      // Without a position, so not part of any run.
+      record(-1, 5000, 0, Some(160), 40),
+    ];
+    let runs = positional_runs(&records, &millis);
+    let video = &runs[0];
+    assert_eq!((video.count, video.total_bytes, video.max_bytes, video.worst_position), (2, 1300, 1000, 400));
+    assert_seconds(video.total_seconds, 0.16);
+    assert_seconds(video.max_seconds, 0.12);
+    let audio = &runs[1];
+    assert_eq!((audio.count, audio.total_bytes, audio.max_bytes, audio.worst_position), (2, 150, 100, 300));
+    assert_seconds(audio.total_seconds, 0.06);
+    assert_seconds(audio.max_seconds, 0.04);
+  }
+
+  #[test]
+  fn runs_of_unknown_streams_only_split_the_others() {
+    let millis = [Rational::new(1, 1000); 2];
+    let records = [
+      record(0, 100, 0, Some(0), 40),
+      record(100, 10, 5, Some(0), 0),
+      record(110, 100, 0, Some(40), 40),
+      // A timestamp-less run has no span.
+      record(210, 70, 1, None, 20),
+    ];
+    let runs = positional_runs(&records, &millis);
+    assert_eq!(runs.len(), 2);
+    assert_eq!((runs[0].count, runs[0].max_bytes), (2, 100));
+    assert_seconds(runs[0].max_seconds, 0.04);
+    assert_eq!((runs[1].count, runs[1].max_bytes), (1, 70));
+    assert_seconds(runs[1].max_seconds, 0.0);
+  }
+
+  #[test]
+  fn skew_behind_the_other_streams() {
+    // Video at 90 kHz, audio at 48 kHz: the audio packet of 0.5s comes after the video of 2s.
+    let time_bases = [Rational::new(1, 90_000), Rational::new(1, 48_000)];
+    let records = [
+      record(-1, 0, 0, Some(0), 0),
+      record(-1, 0, 1, Some(0), 0),
+      record(-1, 0, 0, Some(90_000), 0),
+      record(-1, 0, 0, Some(180_000), 0),
+      record(-1, 0, 1, Some(24_000), 0),
+      record(-1, 0, 1, None, 0),
+      record(-1, 0, 1, Some(120_000), 0),
+      record(-1, 0, 3, Some(0), 0),
+    ];
+    let skew = dts_skew(&records, &time_bases);
+    assert_seconds(skew[0], 0.0);
+    assert_seconds(skew[1], 1.5);
+  }
+
+  #[test]
+  fn seconds_of_an_unknown_time_base() {
+    assert_seconds(seconds(90_000, Rational::new(1, 90_000)), 1.0);
+    assert_seconds(seconds(90_000, Rational::new(0, 1)), 0.0);
+    assert_seconds(seconds(90_000, Rational::new(1, 0)), 0.0);
+  }
+}
@@ -0,0 +1,76 @@
+// Recovery from transient read errors (EIO from CIFS/NFS mounts, dropped HTTP connections) while
+// walking the packets of a long input, so one hiccup doesn't end a multi-hour analysis.
+
+use ffmpeg::{Rational, Rescale};
+use std::ffi::CStr;
+
+use crate::findings::{self, Finding};
+use crate::network;
+use crate::timestamp::Timestamp;
+
+// A read error that was retried, reported as a finding.
+pub struct IoRetry {
+  // Byte position and timestamp (video stream time base) of the last packet processed before
+  // the error.
+  pub position: i64,
+  pub timestamp: Option<i64>,
+  pub error: String,
+  // Whether the input had to be re-opened rather than read again in place.
+  pub reopened: bool,
+}
+
+// An IO_RETRY finding for every read error that was retried.
+pub fn findings(retries: &[IoRetry], time_base: Rational, verbose: bool) -> Vec<Finding> {
+  retries.iter().map(|retry| {
+    let at = retry.timestamp.map_or_else(|| "before the first video packet".to_string(),
+      |ts| Timestamp::new(ts, time_base).describe(verbose));
+    Finding::new(&findings::IO_RETRY, format!("read error '{}' after byte {} ({}), {}", retry.error, retry.position, at,
+      if retry.reopened { "re-opened the input" } else { "read again" }))
+  }).collect()
+}
+
+// The last packet decode_packets processed, so a re-opened input can resume right after it
+// without counting anything twice.
+#[derive(Clone, Copy)]
+pub struct ResumePoint {
+  pub position: i64,
+  pub video_dts: Option<i64>,
+}
+
+impl ResumePoint {
+  // Whether a packet read after re-opening was already processed before the error. Packets
+  // without a byte position fall back to comparing video timestamps.
+  pub fn already_processed(&self, position: i64, is_video: bool, dts: Option<i64>) -> bool {
+    if position >= 0 && self.position >= 0 {
+      return position <= self.position;
+    }
+    match (is_video, dts, self.video_dts) {
+      (true, Some(dts), Some(last)) => dts <= last,
+      _ => false,
+    }
+  }
+}
+
+// Re-open the input from its URL and seek to the key frame at or before the resume point. The
+// stream layout of the new context is the same as the old one's.
+pub fn reopen(
+  ictx: &mut ffmpeg::format::context::Input,
+  resume: ResumePoint,
+  time_base: Rational,
+  network_timeout_seconds: u64,
+) -> Result<(), ffmpeg::Error> {
+  let url = unsafe {
+    let url = (*ictx.as_ptr()).url;
+    if url.is_null() {
+      return Err(ffmpeg::Error::InvalidData);
+    }
+    CStr::from_ptr(url).to_string_lossy().into_owned()
+  };
+  *ictx = network::open(&url, network_timeout_seconds)?;
+  if let Some(dts) = resume.video_dts {
+    // ictx.seek takes AV_TIME_BASE units; packets up to the resume point are skipped afterwards.
+    let target = dts.rescale(time_base, ffmpeg::rescale::TIME_BASE);
+    ictx.seek(target, ..target)?;
+  }
+  Ok(())
+}
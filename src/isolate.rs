@@ -0,0 +1,286 @@
+// --isolate: run the analysis of an untrusted file in a child process, so a decoder crash, a hang
+// or runaway memory use becomes a finding instead of taking the calling process down.
+
+use std::io::{Read, Write};
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+use video_analyzer::findings::{self, Finding};
+use video_analyzer::{print_findings, report, AnalyzeError};
+
+use crate::exit::Status;
+
+// Limits applied to the child process.
+#[derive(Clone, Copy)]
+pub struct Limits {
+  // Wall-clock time after which the child is killed.
+  pub timeout: Option<Duration>,
+  // Address space limit (RLIMIT_AS) on Unix.
+  pub memory_mb: u64,
+}
+
+pub const DEFAULT_MEMORY_MB: u64 = 4096;
+
+// Flags that only concern the parent, with whether they take a value.
+const PARENT_FLAGS: &[(&str, bool)] = &[
+  ("--isolate", false),
+  ("--timeout", true),
+  ("--isolate-memory-mb", true),
+  ("--recursive", true),
+  ("--extensions", true),
+];
+
+// What an isolated analysis produced.
+pub struct Outcome {
+  // The child's exit status; a crash or a timeout is a failure.
+  pub status: Status,
+  // With --json, the child's report, or one carrying the crash finding when it didn't finish.
+  pub json: Option<String>,
+}
+
+// The command line for the child: the parent's arguments (without the program name) for `path`
+// alone, without the isolation flags and the other input files. A file found by --recursive isn't
+// among the parent's arguments, so it is added.
+fn child_args(parent_args: impl Iterator<Item = String>, path: &str, paths: &[String]) -> Vec<String> {
+  let mut args = Vec::new();
+  let mut has_path = false;
+  let mut iter = parent_args;
+  while let Some(arg) = iter.next() {
+    // Flags taking a value may be written `--flag value` or `--flag=value`.
+    let name = arg.split('=').next().unwrap_or(&arg);
+    match PARENT_FLAGS.iter().find(|(flag, _)| *flag == name) {
+      Some((_, true)) if !arg.contains('=') => {
+        iter.next();
+      }
+      Some((_, true)) => {}
+      Some((_, false)) => {}
+      None if arg != path && paths.contains(&arg) => {}
+      None => {
+        has_path |= arg == path;
+        args.push(arg);
+      }
+    }
+  }
+  if !has_path {
+    args.push(path.to_string());
+  }
+  args
+}
+
+// Cap the child's address space and, with a timeout, its CPU time. CPU time is summed over all
+// threads, so it gets one timeout's worth per core.
+#[cfg(unix)]
+fn apply_limits(command: &mut Command, limits: Limits) {
+  use std::os::unix::process::CommandExt;
+
+  let memory = limits.memory_mb.saturating_mul(1024 * 1024) as libc::rlim_t;
+  let cpu = limits.timeout.map(|t| (t.as_secs().max(1) * num_cpus::get() as u64) as libc::rlim_t);
+  unsafe {
+    command.pre_exec(move || {
+      let limit = libc::rlimit { rlim_cur: memory, rlim_max: memory };
+      if libc::setrlimit(libc::RLIMIT_AS, &limit) != 0 {
+        return Err(std::io::Error::last_os_error());
+      }
+      if let Some(cpu) = cpu {
+        let limit = libc::rlimit { rlim_cur: cpu, rlim_max: cpu };
+        if libc::setrlimit(libc::RLIMIT_CPU, &limit) != 0 {
+          return Err(std::io::Error::last_os_error());
+        }
+      }
+      Ok(())
+    });
+  }
+}
+
+#[cfg(not(unix))]
+fn apply_limits(_command: &mut Command, _limits: Limits) {}
+
+// Describe how a child that didn't exit normally ended.
+#[cfg(unix)]
+fn describe_signal(status: &std::process::ExitStatus) -> Option<String> {
+  use std::os::unix::process::ExitStatusExt;
+
+  let signal = status.signal()?;
+  let name = match signal {
+    libc::SIGSEGV => " (SIGSEGV)",
+    libc::SIGABRT => " (SIGABRT)",
+    libc::SIGBUS => " (SIGBUS)",
+    libc::SIGFPE => " (SIGFPE)",
+    libc::SIGILL => " (SIGILL)",
+    libc::SIGKILL => " (SIGKILL)",
+    libc::SIGXCPU => " (SIGXCPU, CPU limit reached)",
+    _ => "",
+  };
+  Some(format!("killed by signal {}{}", signal, name))
+}
+
+#[cfg(not(unix))]
+fn describe_signal(_status: &std::process::ExitStatus) -> Option<String> {
+  None
+}
+
+// Re-run the current binary on the same arguments for `path` in a child process, forward its
+// report and turn a crash or a timeout into a finding. With `json_lines`, the JSON that stands in
+// for the child's is on one line, as the child prints its own.
+pub fn run(limits: Limits, path: &str, paths: &[String], json: bool, json_lines: bool) -> Result<Outcome, AnalyzeError> {
+  let exe = std::env::current_exe().map_err(|e| AnalyzeError::InvalidArgument(format!("--isolate: {}", e)))?;
+  let mut command = Command::new(exe);
+  command.args(child_args(std::env::args().skip(1), path, paths));
+  supervise(command, limits, path, json, json_lines)
+}
+
+// Run `command` as the analysis of `path` under `limits` and wait for it.
+pub fn supervise(mut command: Command, limits: Limits, path: &str, json: bool, json_lines: bool)
+  -> Result<Outcome, AnalyzeError> {
+  command.stdout(Stdio::piped()).stderr(Stdio::inherit());
+  apply_limits(&mut command, limits);
+  let mut child = command.spawn().map_err(|e| AnalyzeError::InvalidArgument(format!("--isolate: {}", e)))?;
+
+  // Forward the report as it is produced; whatever was printed before a crash stays visible. The
+  // JSON is collected instead, so the caller can place it in the output.
+  let mut stdout = child.stdout.take().expect("child stdout is piped");
+  let forward = std::thread::spawn(move || -> std::io::Result<Vec<u8>> {
+    let mut collected = Vec::new();
+    if json {
+      stdout.read_to_end(&mut collected)?;
+    } else {
+      std::io::copy(&mut stdout, &mut std::io::stdout())?;
+    }
+    Ok(collected)
+  });
+
+  let started = Instant::now();
+  let mut timed_out = false;
+  let status = loop {
+    if let Some(status) = child.try_wait()? {
+      break status;
+    }
+    if limits.timeout.is_some_and(|timeout| started.elapsed() >= timeout) {
+      timed_out = true;
+      let _ = child.kill();
+      break child.wait()?;
+    }
+    std::thread::sleep(Duration::from_millis(50));
+  };
+  let collected = forward.join().ok().and_then(|result| result.ok()).unwrap_or_default();
+  let _ = std::io::stdout().flush();
+  let output = (json && !collected.is_empty()).then(|| String::from_utf8_lossy(&collected).into_owned());
+
+  if status.success() {
+    return Ok(Outcome { status: Status::Success, json: output });
+  }
+  let message = if timed_out {
+    format!("analysis exceeded the {}s timeout and was killed", limits.timeout.map_or(0, |t| t.as_secs()))
+  } else if let Some(signal) = describe_signal(&status) {
+    format!("analysis process {}", signal)
+  } else {
+    // A normal error exit of the child; its own message has already been printed on stderr.
+    let error = report::FileError {
+      path: path.to_string(),
+      error: format!("analysis exited with status {}", status.code().unwrap_or(1)),
+    };
+    let error = if json_lines { report::to_json_line(&error) } else { report::to_json(&error) };
+    let json = json.then(|| output.unwrap_or(error));
+    return Ok(Outcome { status: Status::from_code(status.code().unwrap_or(1)), json });
+  };
+  let finding = Finding::new(&findings::ANALYSIS_CRASHED, message);
+  // The child prints its JSON only once the analysis is complete, so a crash leaves stdout empty.
+  if json {
+    let report = report::AnalysisReport {
+      path: path.to_string(),
+      findings: vec![report::FindingReport::from(&finding)],
+      execution_time_ms: started.elapsed().as_millis() as u64,
+      ..report::AnalysisReport::default()
+    };
+    let json = if json_lines { report::to_json_line(&report) } else { report::to_json(&report) };
+    return Ok(Outcome { status: Status::Failure, json: Some(json) });
+  }
+  print_findings(&mut [finding]);
+  Ok(Outcome { status: Status::Failure, json: None })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn args(line: &str) -> impl Iterator<Item = String> + '_ {
+    line.split(' ').map(str::to_string)
+  }
+
+  #[test]
+  fn child_gets_its_file_alone() {
+    let paths = ["a.mp4".to_string(), "b.mkv".to_string(), "c.ts".to_string()];
+    assert_eq!(child_args(args("--isolate a.mp4 b.mkv --json c.ts"), "b.mkv", &paths), ["b.mkv", "--json"]);
+  }
+
+  #[test]
+  fn parent_flags_are_dropped_with_their_values() {
+    let paths = ["in.mp4".to_string()];
+    let line = "--isolate --timeout 30 --isolate-memory-mb=2048 --threads 4 in.mp4 --recursive dir --extensions=mp4";
+    assert_eq!(child_args(args(line), "in.mp4", &paths), ["--threads", "4", "in.mp4"]);
+  }
+
+  #[test]
+  fn inline_values_dont_take_the_next_argument() {
+    let paths = ["in.mp4".to_string()];
+    assert_eq!(child_args(args("--timeout=30 in.mp4 --quiet"), "in.mp4", &paths), ["in.mp4", "--quiet"]);
+  }
+
+  #[test]
+  fn recursive_files_are_given_to_the_child() {
+    let paths = ["dir/a.mp4".to_string(), "dir/b.mkv".to_string()];
+    let line = "--isolate --recursive dir --json";
+    assert_eq!(child_args(args(line), "dir/b.mkv", &paths), ["--json", "dir/b.mkv"]);
+    assert_eq!(child_args(args("--recursive=dir --extensions mp4 --quiet"), "dir/a.mp4", &paths), ["--quiet", "dir/a.mp4"]);
+  }
+
+  // A stand-in for the analysis: a shell script instead of the child video_analyzer.
+  fn shell(script: &str) -> Command {
+    let mut command = Command::new("sh");
+    command.args(["-c", script]);
+    command
+  }
+
+  fn limits(timeout_secs: u64) -> Limits {
+    Limits { timeout: Some(Duration::from_secs(timeout_secs)), memory_mb: DEFAULT_MEMORY_MB }
+  }
+
+  fn crash_finding(outcome: &Outcome) -> String {
+    let report: serde_json::Value = serde_json::from_str(outcome.json.as_deref().unwrap()).unwrap();
+    assert_eq!(report["path"], "in.mp4");
+    assert_eq!(report["findings"][0]["code"], findings::ANALYSIS_CRASHED.code);
+    report["findings"][0]["message"].as_str().unwrap().to_string()
+  }
+
+  #[cfg(unix)]
+  #[test]
+  fn aborted_child_is_a_crash_finding() {
+    let outcome = supervise(shell("kill -ABRT $$"), limits(30), "in.mp4", true, true).unwrap();
+    assert_eq!(outcome.status, Status::Failure);
+    assert!(!outcome.json.as_deref().unwrap().trim_end().contains('\n'));
+    assert_eq!(crash_finding(&outcome), "analysis process killed by signal 6 (SIGABRT)");
+  }
+
+  #[test]
+  fn hung_child_is_killed_at_the_timeout() {
+    let started = Instant::now();
+    // exec, so the kill reaches sleep itself and nothing else holds the child's stdout open.
+    let outcome = supervise(shell("exec sleep 30"), limits(1), "in.mp4", true, false).unwrap();
+    assert!(started.elapsed() < Duration::from_secs(10));
+    assert_eq!(outcome.status, Status::Failure);
+    assert_eq!(crash_finding(&outcome), "analysis exceeded the 1s timeout and was killed");
+  }
+
+  #[test]
+  fn error_exit_keeps_the_childs_status() {
+    let outcome = supervise(shell("exit 4"), limits(30), "in.mp4", true, false).unwrap();
+    assert_eq!(outcome.status, Status::from_code(4));
+    let error: serde_json::Value = serde_json::from_str(outcome.json.as_deref().unwrap()).unwrap();
+    assert_eq!(error["error"], "analysis exited with status 4");
+
+    // The child's own JSON is kept when it printed one.
+    let outcome = supervise(shell("echo '{\"path\":\"in.mp4\"}'; exit 2"), limits(30), "in.mp4", true, false).unwrap();
+    assert_eq!(outcome.status, Status::OpenFailed);
+    assert_eq!(outcome.json.as_deref().map(str::trim_end), Some("{\"path\":\"in.mp4\"}"));
+  }
+}
@@ -0,0 +1,55 @@
+// --jobs: analyze several files at once on worker threads, each with its own input and decoder.
+// Reports are captured per file and handed back in input order, so the output reads the same as
+// a sequential run; completions are announced on stderr as they happen, in whatever order.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
+
+use video_analyzer::{analyze_captured, AnalysisReport, AnalyzeError, AnalyzeOptions};
+
+// Decoder threads per file for --threads -1 with `jobs` files at once: the cores split evenly.
+pub fn threads_per_job(jobs: usize) -> i32 {
+  (num_cpus::get() / jobs.max(1)).max(1) as i32
+}
+
+// Analyze every path with up to `jobs` analyses at a time. `each` gets the path, the result and
+// the captured report of every file, in input order.
+pub fn run<F>(paths: &[String], options: &AnalyzeOptions, jobs: usize, mut each: F)
+where
+  F: FnMut(&str, Result<AnalysisReport, AnalyzeError>, String),
+{
+  let next = AtomicUsize::new(0);
+  let (sender, receiver) = mpsc::channel();
+  thread::scope(|scope| {
+    for _ in 0..jobs.min(paths.len()) {
+      let sender = sender.clone();
+      let next = &next;
+      scope.spawn(move || loop {
+        let index = next.fetch_add(1, Ordering::Relaxed);
+        let Some(path) = paths.get(index) else {
+          break;
+        };
+        let (result, text) = analyze_captured(Path::new(path), options);
+        if sender.send((index, result, text)).is_err() {
+          break;
+        }
+      });
+    }
+    drop(sender);
+
+    // Results arrive in completion order; hold them until every earlier file is done.
+    let mut pending = BTreeMap::new();
+    let mut printed = 0;
+    for (done, (index, result, text)) in receiver.iter().enumerate() {
+      log::info!("Finished {} of {}: {}", done + 1, paths.len(), paths[index]);
+      pending.insert(index, (result, text));
+      while let Some((result, text)) = pending.remove(&printed) {
+        each(&paths[printed], result, text);
+        printed += 1;
+      }
+    }
+  });
+}
@@ -0,0 +1,2861 @@
+// Video analysis library behind the video_analyzer command line tool. `analyze` runs the whole
+// analysis of one file and returns its figures; the human-readable report is printed along the
+// way when AnalyzeOptions::print_report is set.
+
+extern crate ffmpeg_next as ffmpeg;
+extern crate num_cpus;
+
+use std::cell::{Cell, RefCell};
+use std::collections::BTreeMap;
+
+thread_local! {
+  // Set while an analysis runs without AnalyzeOptions::print_report.
+  static QUIET: Cell<bool> = const { Cell::new(false) };
+  // Set while analyze_captured runs: the report goes here instead of stdout.
+  static CAPTURE: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+// println! for the human-readable report, silent unless the report is wanted.
+macro_rules! out {
+  ($($arg:tt)*) => {
+    if !crate::QUIET.with(|quiet| quiet.get()) {
+      let captured = crate::CAPTURE.with(|capture| match capture.borrow_mut().as_mut() {
+        Some(text) => {
+          use std::fmt::Write;
+          let _ = writeln!(text, $($arg)*);
+          true
+        }
+        None => false,
+      });
+      if !captured {
+        println!($($arg)*);
+      }
+    }
+  };
+}
+
+pub mod align;
+mod aspect;
+mod attachments;
+mod audio;
+mod bitrate;
+pub mod cadence;
+mod captions;
+mod chapters;
+mod colorimetry;
+pub mod compare;
+mod consistency;
+mod data_streams;
+mod demux;
+pub mod extract;
+pub mod findings;
+pub mod follow;
+pub mod frame_hashes;
+mod frames_csv;
+mod faststart;
+mod fps;
+mod fps_stability;
+mod fragments;
+mod freeze;
+mod gaps;
+mod gop_structure;
+mod html;
+pub mod hwaccel;
+pub mod interleave;
+mod interlace;
+mod io_retry;
+pub mod logging;
+mod loudness;
+mod memory;
+pub mod metadata;
+mod monotonic;
+mod mp4;
+mod network;
+mod packet_sizes;
+mod pipe;
+mod pixel_format;
+mod pixels;
+pub mod profile;
+mod progress;
+mod random_access;
+mod recovery;
+mod rotation;
+pub mod sampling;
+pub mod remux;
+pub mod report;
+mod resolution;
+pub mod scenes;
+pub mod selftest;
+mod stream_table;
+mod streams;
+pub mod strict;
+mod subtitles;
+pub mod thumbnails;
+mod variants;
+mod timecode;
+pub mod timestamp;
+mod tmcd;
+mod transport;
+pub mod verdict;
+mod video_streams;
+
+use std::io::IsTerminal;
+use std::path::Path;
+use std::time::Instant;
+use ffmpeg::media::Type;
+use ffmpeg::codec::threading;
+use ffmpeg::{Rational, Rescale};
+use indicatif::ProgressBar;
+use thiserror::Error;
+use findings::Finding;
+use timestamp::Timestamp;
+
+pub use report::AnalysisReport;
+
+// Errors that end an analysis.
+#[derive(Error, Debug)]
+pub enum AnalyzeError {
+    #[error("No video stream found")]
+    NoVideoStreamError,
+
+    #[error("The input contains no streams")]
+    NoStreamsError,
+
+    #[error("Invalid argument: {0}")]
+    InvalidArgument(String),
+
+    #[error("Could not open the input: {0}")]
+    OpenError(ffmpeg::Error),
+
+    #[error("Decoding failed: {0}")]
+    DecodeError(ffmpeg::Error),
+
+    #[error("Network timeout: no data from the input for {0} seconds")]
+    NetworkTimeout(u64),
+
+    #[error("MP4/MOV on stdin needs its moov atom before the media data (faststart); remux it with \
+      `ffmpeg -i in.mp4 -c copy -movflags +faststart out.mp4` or pass the file path instead")]
+    MoovNotAtStart,
+
+    #[error(transparent)]
+    FfmpegError(#[from] ffmpeg::Error),
+
+    #[error(transparent)]
+    IoError(#[from] std::io::Error),
+}
+
+// Blue color and bold for the headers
+pub const BLUE_BOLD: &str = "\x1B[1;34m";
+// Reset color and style back to default
+pub const RESET: &str = "\x1B[0m";
+
+// How much fidelity the pixel-level passes (black, freeze, scenes, crop) decode with.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PixelQuality {
+  // Full resolution, every plane.
+  Full,
+  // Reduced resolution via the decoder's lowres option where the codec supports it.
+  Fast,
+}
+
+// Shortest frozen picture reported with --detect-freeze when --freeze-duration isn't given.
+pub const DEFAULT_FREEZE_SECONDS: f64 = 2.0;
+
+// Duration check tolerance when --tolerance-ms isn't given.
+pub const DEFAULT_DURATION_TOLERANCE_MS: u64 = 500;
+
+// A/V drift check tolerance when --av-drift-tolerance-ms isn't given.
+pub const DEFAULT_AV_DRIFT_TOLERANCE_MS: u64 = 1000;
+
+// Largest audio start offset from the video when --av-offset-tolerance-ms isn't given.
+pub const DEFAULT_AV_OFFSET_TOLERANCE_MS: u64 = 100;
+
+// Multiple of the median frame interval a gap must exceed to count as dropped frames when
+// --gap-factor isn't given.
+pub const DEFAULT_GAP_FACTOR: f64 = 1.75;
+
+// Percentage a frame interval may differ from the most common one when --interval-tolerance isn't
+// given.
+pub const DEFAULT_INTERVAL_TOLERANCE_PERCENT: u32 = 10;
+
+// Pause before retrying a failed read when --io-retry-delay isn't given.
+pub const DEFAULT_IO_RETRY_DELAY_MS: u64 = 1000;
+
+// Seconds a URL input may go without data when --network-timeout isn't given.
+pub const DEFAULT_NETWORK_TIMEOUT_SECONDS: u64 = 30;
+
+// Lowres factor used by fast pixel passes: each dimension is divided by 2^FAST_LOWRES.
+const FAST_LOWRES: u8 = 2;
+
+// Print the findings section, most severe first. Nothing is printed when there are no findings.
+pub fn print_findings(findings: &mut [Finding]) {
+  if findings.is_empty() {
+    return;
+  }
+  findings.sort_by_key(|f| f.severity());
+  out!("{}{}Findings - {}", RESET, BLUE_BOLD, RESET);
+  for finding in findings.iter() {
+    out!("[{}] {}: {}", finding.severity().label(), finding.definition.code, finding.message);
+  }
+}
+
+// What to analyze and how. The defaults match the command line tool without flags, except that
+// nothing is printed.
+pub struct AnalyzeOptions {
+  // Decoder threads; -1 uses every available core.
+  pub threads_number_input: i32,
+  // When set, only the first and last N megabytes of the input are analyzed.
+  pub head_tail_mb: Option<u64>,
+  // Analyze only the part of the video stream between these times, in milliseconds from its start.
+  // The input is seeked to the key frame before start_ms; the frames up to it are decoded but not
+  // counted.
+  pub start_ms: Option<u64>,
+  pub end_ms: Option<u64>,
+  // Treat a missing video stream as an error instead of producing an audio-only report.
+  pub require_video: bool,
+  pub pixel_quality: PixelQuality,
+  // Decode on a hardware device; frames are copied to system memory only for the pixel checks.
+  pub hwaccel: hwaccel::Accel,
+  // Re-decode damaged regions with error concealment after the strict pass.
+  pub recovery_pass: bool,
+  // Decode every video stream in the same pass and report the frame figures of each; the detailed
+  // checks stay with the analyzed stream.
+  pub all_video_streams: bool,
+  // Largest difference between the container duration and the last frame time that passes the
+  // duration check.
+  pub duration_tolerance_ms: u64,
+  // Largest difference between the span of an audio stream and the video's that passes the A/V
+  // drift check.
+  pub av_drift_tolerance_ms: u64,
+  // Largest difference between the start of an audio stream and the first video frame before an
+  // AV_START_OFFSET finding.
+  pub av_offset_tolerance_ms: u64,
+  // Largest time from the last key frame to the end of the video before a KEYFRAME_TAIL finding.
+  pub max_keyframe_tail_s: Option<f64>,
+  // Profile and level the streams of the target's codec must not exceed; a PROFILE_REQUIREMENT
+  // finding otherwise.
+  pub required_profile: Option<profile::Requirement>,
+  // Percentage of the most common frame interval an interval may differ by before it counts as
+  // irregular.
+  pub interval_tolerance_percent: u32,
+  // Multiple of the median frame interval beyond which a gap counts as dropped frames.
+  pub gap_factor: f64,
+  // Report stretches where the decoded picture doesn't change for at least this many milliseconds.
+  pub detect_freeze_ms: Option<u64>,
+  // Record every frame whose luma histogram differs from the previous frame's by more than the
+  // threshold as a scene change.
+  pub scenes: Option<scenes::Settings>,
+  // Derive the frame figures from the video packets without decoding them. Picture types, pixel
+  // checks, audio decoding and the recovery pass need decoded frames and are skipped.
+  pub no_decode: bool,
+  // Decode only the key frames, or every Nth video packet; packet figures stay exact and frame
+  // totals are estimated from the sample.
+  pub sampling: Option<sampling::Mode>,
+  // Measure the EBU R128 loudness of every audio stream while decoding it.
+  pub measure_loudness: bool,
+  // Read the SMPTE timecodes of the decoded frames and check their continuity.
+  pub timecodes: bool,
+  // Delivery target the key frame cadence is checked against.
+  pub delivery_target: Option<&'static cadence::DeliveryTarget>,
+  // Show raw timestamps and their time base next to converted times.
+  pub verbose: bool,
+  // Video stream to analyze instead of the highest-resolution one.
+  pub stream_index: Option<usize>,
+  // Variant of an HLS or DASH input to analyze alone, numbered from 0 in stream order. Without it,
+  // every variant is decoded and compared.
+  pub variant: Option<usize>,
+  // Count DTS jumps of 10 seconds or more as discontinuities rather than timestamp anomalies. Always
+  // on for HLS and DASH inputs.
+  pub tolerate_discontinuities: bool,
+  // Keep reading a file that is still being written; see follow.rs.
+  pub follow: Option<follow::Settings>,
+  // Container to evaluate stream-copy compatibility against.
+  pub remux_target: Option<remux::RemuxTarget>,
+  // Read errors to recover from, and the pause before each attempt.
+  pub io_retries: u32,
+  pub io_retry_delay_ms: u64,
+  // Seconds a URL input may go without data before the analysis ends with a network timeout; 0
+  // waits forever.
+  pub network_timeout_seconds: u64,
+  // Contiguous single-stream runs beyond these limits are flagged.
+  pub interleave_limits: interleave::Limits,
+  // Stream-copy the regions around located errors into a directory.
+  pub extract_problems: Option<extract::Settings>,
+  // Print every key frame and include the list in the report.
+  pub list_keyframes: bool,
+  // File to write one CSV row per decoded frame to. Not written with head_tail_mb.
+  pub frames_csv: Option<std::path::PathBuf>,
+  // Write a hash of every decoded frame's picture data to a file.
+  pub frame_hashes: Option<frame_hashes::Settings>,
+  // Write a PNG of every decoded key frame into a directory, named by its timestamp.
+  pub thumbnails: Option<thumbnails::Settings>,
+  // File to write the video bitrate of every second to. Not written with head_tail_mb.
+  pub bitrate_csv: Option<std::path::PathBuf>,
+  // File to write the frames of every presentation second to. Not written with head_tail_mb.
+  pub fps_csv: Option<std::path::PathBuf>,
+  // List this many of the largest video packets; without it, only the largest one is printed.
+  pub top_packets: usize,
+  // File to write the picture type of every decoded frame to, one GOP per line.
+  pub gop_dump: Option<std::path::PathBuf>,
+  // Check for the anomalies of strict QC (strict::IDS) and list the ones found; those in
+  // strict_allow don't fail the run.
+  pub strict: bool,
+  pub strict_allow: Vec<String>,
+  // Add a one-paragraph summary, capped at verdict_length characters.
+  pub verdict: bool,
+  pub verdict_length: usize,
+  // Print the human-readable report on stdout while analyzing.
+  pub print_report: bool,
+  // Render the report as an HTML page too, into AnalysisReport::html.
+  pub html: bool,
+  // Draw progress on stderr while analyzing, when stdout is a terminal.
+  pub show_progress: bool,
+  // Write progress events as JSON lines on stderr instead, whatever stdout is.
+  pub progress_events: bool,
+}
+
+impl Default for AnalyzeOptions {
+  fn default() -> Self {
+    AnalyzeOptions {
+      threads_number_input: 1,
+      head_tail_mb: None,
+      start_ms: None,
+      end_ms: None,
+      require_video: false,
+      pixel_quality: PixelQuality::Fast,
+      hwaccel: hwaccel::Accel::None,
+      recovery_pass: false,
+      duration_tolerance_ms: DEFAULT_DURATION_TOLERANCE_MS,
+      av_drift_tolerance_ms: DEFAULT_AV_DRIFT_TOLERANCE_MS,
+      av_offset_tolerance_ms: DEFAULT_AV_OFFSET_TOLERANCE_MS,
+      max_keyframe_tail_s: None,
+      required_profile: None,
+      interval_tolerance_percent: DEFAULT_INTERVAL_TOLERANCE_PERCENT,
+      gap_factor: DEFAULT_GAP_FACTOR,
+      detect_freeze_ms: None,
+      scenes: None,
+      no_decode: false,
+      sampling: None,
+      all_video_streams: false,
+      measure_loudness: false,
+      timecodes: false,
+      delivery_target: None,
+      verbose: false,
+      stream_index: None,
+      variant: None,
+      tolerate_discontinuities: false,
+      follow: None,
+      remux_target: None,
+      io_retries: 0,
+      io_retry_delay_ms: DEFAULT_IO_RETRY_DELAY_MS,
+      network_timeout_seconds: DEFAULT_NETWORK_TIMEOUT_SECONDS,
+      interleave_limits: interleave::Limits {
+        max_bytes: interleave::DEFAULT_MAX_RUN_MB * 1024 * 1024,
+        max_seconds: interleave::DEFAULT_MAX_RUN_SECONDS,
+      },
+      extract_problems: None,
+      list_keyframes: false,
+      frames_csv: None,
+      frame_hashes: None,
+      thumbnails: None,
+      bitrate_csv: None,
+      fps_csv: None,
+      top_packets: 0,
+      gop_dump: None,
+      strict: false,
+      strict_allow: Vec::new(),
+      verdict: false,
+      verdict_length: verdict::DEFAULT_MAX_LENGTH,
+      print_report: false,
+      html: false,
+      show_progress: false,
+      progress_events: false,
+    }
+  }
+}
+
+pub fn millis_to_clock_format(ms: u64) -> String {
+  let hours = ms / 3_600_000;
+  let mins = (ms % 3_600_000) / 60_000;
+  let secs = (ms % 60_000) / 1000;
+  let millis = ms % 1000;
+
+  if hours > 0 {
+      format!("{:02}h {:02}m {:02}s .{:03}ms", hours, mins, secs, millis)
+  } else {
+      format!("{:02}m {:02}s .{:03}ms", mins, secs, millis)
+  }
+}
+
+pub fn format_with_commas(num: i64) -> String {
+  let num_str = num.to_string();
+  let chars: Vec<_> = num_str.chars().rev().enumerate().flat_map(|(i, c)| {
+      if i != 0 && i % 3 == 0 {
+          vec![',', c]
+      } else {
+          vec![c]
+      }
+  }).collect();
+  chars.into_iter().rev().collect()
+}
+
+// Format a byte count with binary units, e.g. "1.84 GiB".
+pub fn format_bytes(bytes: u64) -> String {
+  const UNITS: [&str; 5] = ["bytes", "KiB", "MiB", "GiB", "TiB"];
+  let mut value = bytes as f64;
+  let mut unit = 0;
+  while value >= 1024.0 && unit < UNITS.len() - 1 {
+    value /= 1024.0;
+    unit += 1;
+  }
+  if unit == 0 { format!("{} bytes", bytes) } else { format!("{:.2} {}", value, UNITS[unit]) }
+}
+
+// Print the peak memory of the process with its exact byte count, and return it for the report.
+fn report_peak_memory() -> Option<u64> {
+  let peak = memory::peak_rss_bytes();
+  match peak {
+    Some(bytes) => out!("Peak memory: {} ({} bytes)", format_bytes(bytes), format_with_commas(bytes as i64)),
+    None => out!("Peak memory: unknown on this platform"),
+  }
+  peak
+}
+
+// Convert a timestamp expressed in `time_base` units into milliseconds. Returns None when the
+// time base is degenerate (0/0 or x/0), which some MJPEG and raw captures declare, and for
+// negative timestamps, which timestamp_to_signed_millis keeps.
+fn timestamp_to_millis(ts: i64, time_base: Rational) -> Option<u64> {
+  if ts < 0 || time_base.numerator() <= 0 || time_base.denominator() <= 0 {
+    return None;
+  }
+  Some((ts as f64 * time_base.numerator() as f64 / time_base.denominator() as f64 * 1000.0) as u64)
+}
+
+// Convert a timestamp into signed milliseconds, keeping the sign of negative timestamps (e.g. the
+// first DTS of files that express B-frame delay with negative timestamps).
+fn timestamp_to_signed_millis(ts: i64, time_base: Rational) -> Option<i64> {
+  if time_base.numerator() <= 0 || time_base.denominator() <= 0 {
+    return None;
+  }
+  Some((ts as f64 * time_base.numerator() as f64 / time_base.denominator() as f64 * 1000.0).round() as i64)
+}
+
+// Format signed milliseconds, e.g. "-00m 00s .080ms".
+fn format_signed_millis(ms: i64) -> String {
+  let clock = millis_to_clock_format(ms.unsigned_abs());
+  if ms < 0 { format!("-{}", clock) } else { clock }
+}
+
+// Format an optional millisecond value, marking missing values as unknown.
+fn format_optional_millis(ms: Option<u64>) -> String {
+  ms.map_or_else(|| "unknown".to_string(), millis_to_clock_format)
+}
+
+// Frames per second for a declared rate, or None when the rate is 0/0 or otherwise unusable.
+fn rate_to_fps(rate: Rational) -> Option<f64> {
+  if rate.numerator() <= 0 || rate.denominator() <= 0 {
+    return None;
+  }
+  Some(rate.numerator() as f64 / rate.denominator() as f64)
+}
+
+// Describe a declared frame rate, e.g. "30000/1001 (29.970 fps)" or "unknown (0/0)".
+fn describe_rate(rate: Rational) -> String {
+  match rate_to_fps(rate) {
+    Some(fps) => format!("{}/{} ({:.3} fps)", rate.numerator(), rate.denominator(), fps),
+    None => format!("unknown ({}/{})", rate.numerator(), rate.denominator()),
+  }
+}
+
+// Set the decoder's lowres factor for the requested pixel quality. Must be called before the
+// decoder is opened. Returns the factor actually applied, which is capped by the codec's
+// max_lowres (0 for most modern codecs such as H.264 and HEVC).
+fn apply_pixel_quality(context: &mut ffmpeg::codec::context::Context, quality: PixelQuality) -> u8 {
+  let requested = match quality {
+    PixelQuality::Full => 0,
+    PixelQuality::Fast => FAST_LOWRES,
+  };
+  let max_lowres = ffmpeg::decoder::find(context.id())
+    .map_or(0, |codec| unsafe { (*codec.as_ptr()).max_lowres });
+  let lowres = requested.min(max_lowres);
+  unsafe {
+    (*context.as_mut_ptr()).lowres = lowres as i32;
+  }
+  lowres
+}
+
+// Describe the pixel quality level for the report, since detector thresholds can shift slightly
+// between levels.
+fn describe_pixel_quality(quality: PixelQuality, lowres: u8) -> String {
+  match quality {
+    PixelQuality::Full => "full".to_string(),
+    PixelQuality::Fast if lowres > 0 => format!("fast (lowres {}, 1/{} scale)", lowres, 1 << lowres),
+    PixelQuality::Fast => "fast (lowres not supported by codec, full resolution)".to_string(),
+  }
+}
+
+// Print the raw container duration, hiding the AV_NOPTS_VALUE sentinel used for "unknown".
+fn describe_raw_duration(duration: i64) -> String {
+  if duration <= 0 { "unknown".to_string() } else { duration.to_string() }
+}
+
+// Container-level duration, or None when the container doesn't declare one.
+// ictx.duration() is expressed in AV_TIME_BASE units and is AV_NOPTS_VALUE when unknown.
+fn container_duration(ictx: &ffmpeg::format::context::Input) -> Option<Timestamp> {
+  if ictx.duration() <= 0 {
+    return None;
+  }
+  Some(Timestamp::from_av_time_base(ictx.duration()))
+}
+
+// Size of the input in bytes as reported by the I/O layer, or None when it is unknown (pipes,
+// HTTP without Content-Length).
+fn input_size(ictx: &ffmpeg::format::context::Input) -> Option<u64> {
+  let size = unsafe {
+    let pb_ctx = (*ictx.as_ptr()).pb;
+    if pb_ctx.is_null() { -1 } else { ffmpeg::ffi::avio_size(pb_ctx) }
+  };
+  if size > 0 { Some(size as u64) } else { None }
+}
+
+// Counters gathered while decoding a run of packets from the video stream.
+#[derive(Default)]
+struct DecodeStats {
+  // Index and timestamp of the last key frame seen.
+  last_frame: i64,
+  last_keyframe_timestamp: Option<i64>,
+  frame_count: i64,
+  // Timestamps (stream time base) of every key frame, used for cadence reporting.
+  keyframe_timestamps: Vec<i64>,
+  // Index in output order and timestamp of every key frame, for exact intervals in frames.
+  keyframes: Vec<(i64, Option<i64>)>,
+  // Key frames decoded, including those without a timestamp.
+  keyframe_count: i64,
+  // Decoded frames per picture type: I, P, B and everything else.
+  frame_types: [i64; 4],
+  // Picture type of every decoded frame, for the GOP structure, kept with record_timeline.
+  gop_structure: gop_structure::Tracker,
+  // Packets, bytes and packet DTS range read from every stream.
+  streams: stream_table::Counts,
+  highest_dts: Option<i64>,
+  // Lowest packet timestamps, negative when the muxer expresses the decoder delay that way.
+  lowest_dts: Option<i64>,
+  lowest_pts: Option<i64>,
+  // Dimensions of the first decoded frame, which already reflect codec-level cropping.
+  first_frame_size: Option<(u32, u32)>,
+  // Sample aspect ratio of the first decoded frame, 0/1 when unknown.
+  first_frame_sar: Option<Rational>,
+  // Timestamp of the first frame out of the decoder, counted or not, and the number of frames
+  // before DecodeConfig::start_timestamp that were decoded and not counted.
+  first_decoded_timestamp: Option<i64>,
+  frames_before_start: i64,
+  // Video packets left out with DecodeConfig::skip_to_keyframe.
+  packets_before_keyframe: u64,
+  // Best-effort timestamp of the first video packet read.
+  first_packet_timestamp: Option<i64>,
+  // Video packets and counted frames presented before zero: the frames an edit list or a trim with
+  // -ss leaves before the presentation start.
+  negative_pts_packets: u64,
+  negative_pts_frames: u64,
+  // Lowest and highest best-effort frame timestamps, used for the measured duration.
+  first_frame_timestamp: Option<i64>,
+  last_frame_timestamp: Option<i64>,
+  // Packets the decoder rejected and frames it failed to return; decoding goes on past them. Each
+  // error is counted under its message, and the first and last ones with a timestamp are kept.
+  decode_errors: u64,
+  decode_error_kinds: BTreeMap<String, u64>,
+  first_error_timestamp: Option<i64>,
+  last_error_timestamp: Option<i64>,
+  // Timestamps recorded when DecodeConfig::record_timeline is set: every decoded frame, every
+  // packet flagged as a key frame, and every packet the decoder rejected.
+  frame_timestamps: Vec<i64>,
+  keyframe_packet_timestamps: Vec<i64>,
+  error_timestamps: Vec<i64>,
+  // Read errors recovered from with DecodeConfig::io_retries.
+  io_retries: Vec<io_retry::IoRetry>,
+  // Position, size and timestamp of every packet of every stream, kept with record_timeline.
+  interleave: interleave::Tracker,
+  // Interlaced and progressive decoded frames.
+  interlace: interlace::Tracker,
+  // Decoded frames carrying closed captions.
+  captions: captions::Tracker,
+  // Pixel format of every decoded frame against the first one's.
+  pixel_format: pixel_format::Tracker,
+  // Decoded frame sizes, every change against the previous frame's.
+  resolution: resolution::Tracker,
+  // Color description and HDR metadata of the decoded frames.
+  color: colorimetry::Tracker,
+  // Luma samples against the declared color range, kept with DecodeConfig::range_check_step.
+  range_check: pixels::RangeCheck,
+  // Embedded timecodes, kept with DecodeConfig::timecodes.
+  timecodes: Option<timecode::Tracker>,
+  // Unchanged pictures, kept with DecodeConfig::freeze_min_ms.
+  freeze: Option<freeze::Tracker>,
+  // Scene changes, kept with DecodeConfig::scene_threshold.
+  scenes: Option<scenes::Tracker>,
+  // Exact video packet counts, kept with DecodeConfig::sampling.
+  sampling: Option<sampling::Counts>,
+  // Properties, packet and sample counts of every audio stream, with DecodeConfig::decode_audio (or
+  // the packet counts only, with count_packets).
+  audio: Vec<audio::AudioStream>,
+  // Video packet bytes per second, kept with record_timeline.
+  bitrate: bitrate::Tracker,
+  // Video packet size statistics and the largest packets, kept with record_timeline.
+  packet_sizes: packet_sizes::Tracker,
+  // IDR, CRA/BLA and recovery point key frames of H.264 and HEVC, kept with record_timeline.
+  random_access: random_access::Tracker,
+  // Frame counts of the other video streams, with DecodeConfig::other_video.
+  other_video: Vec<video_streams::VideoStream>,
+  // Packet and cue counts of every subtitle stream, with DecodeConfig::count_subtitles.
+  subtitles: Vec<subtitles::SubtitleStream>,
+  // Packets and time range of every data stream, and the SCTE-35 splice messages, with
+  // DecodeConfig::count_data_streams.
+  data_streams: Vec<data_streams::DataStream>,
+  // DTS and PTS anomalies of the video packets, kept with record_timeline.
+  monotonic: monotonic::Tracker,
+  // Continuous runs of the video packets' DTS, with DecodeConfig::split_segments.
+  segments: Option<transport::Segments>,
+  // JSON progress events, written with DecodeConfig::progress_events.
+  progress_events: Option<progress::Events>,
+}
+
+// Controls how decode_packets walks the input.
+#[derive(Clone, Copy, Default)]
+struct DecodeConfig {
+  // Stop at the first packet starting at or past this byte offset.
+  byte_limit: Option<i64>,
+  // Decode the frames before this timestamp (stream time base) without counting them, after a seek
+  // landed on the key frame before it.
+  start_timestamp: Option<i64>,
+  // Stop at the first video packet whose timestamp reaches this value (stream time base).
+  end_timestamp: Option<i64>,
+  // Keep the per-frame, per-keyframe and per-error timestamps in DecodeStats.
+  record_timeline: bool,
+  // Read errors to recover from before giving up, and the pause before each attempt. With no
+  // retries, read errors are skipped as the packet iterator always did.
+  io_retries: u32,
+  io_retry_delay_ms: u64,
+  // Read timeout of a URL input, also set when it is re-opened.
+  network_timeout_seconds: u64,
+  // Scan every decoded frame's luma plane against the declared color range, every Nth row and
+  // column.
+  range_check_step: Option<usize>,
+  // Largest video packets to keep in DecodeStats::packet_sizes.
+  top_packets: usize,
+  // Decode every audio stream as well and count its samples.
+  decode_audio: bool,
+  // With decode_audio, also measure the loudness of the decoded samples.
+  measure_loudness: bool,
+  // Advance the progress bar by what it counts, and show the frame count next to it.
+  progress: Option<progress::Unit>,
+  // Count every video packet as a frame, with the packet's key flag and timestamp, instead of
+  // decoding it. Picture types and decode errors stay unknown.
+  count_packets: bool,
+  // Send only these video packets to the decoder and count every packet.
+  sampling: Option<sampling::Mode>,
+  // Decode every other video stream as well, with this threading configuration.
+  other_video: Option<threading::Config>,
+  // Count large DTS jumps of the video packets as discontinuities.
+  tolerate_discontinuities: bool,
+  // Wait for a growing input at its end, and print a status line meanwhile.
+  follow: Option<follow::Settings>,
+  // Write progress as JSON lines on stderr; see progress::Events.
+  progress_events: bool,
+  // Count the packets and text cues of every subtitle stream.
+  count_subtitles: bool,
+  // Count the packets of every data stream and read the SCTE-35 splice messages.
+  count_data_streams: bool,
+  // Split the video packets into continuous segments at DTS discontinuities.
+  split_segments: bool,
+  // Read the timecode of every decoded frame.
+  timecodes: bool,
+  // Compare every decoded frame with the previous one and keep the unchanged stretches at least
+  // this many milliseconds long.
+  freeze_min_ms: Option<u64>,
+  // Score every decoded frame against the previous one and keep those above this threshold as
+  // scene changes.
+  scene_threshold: Option<f64>,
+  // Right after a seek, don't send the video packets before the first key packet to the decoder:
+  // they reference frames from before the seek point, so their errors say nothing about the file.
+  skip_to_keyframe: bool,
+}
+
+// Files written frame by frame as the frames come out of the decoder.
+#[derive(Default)]
+struct FrameOutputs<'a> {
+  // A row for every decoded frame.
+  frames_csv: Option<&'a mut frames_csv::Writer>,
+  // An image of every decoded key frame.
+  thumbnails: Option<&'a mut thumbnails::Writer>,
+  // A hash of every decoded frame.
+  frame_hashes: Option<&'a mut frame_hashes::Writer>,
+}
+
+// Whether a frame or packet comes before the start of the analyzed range. Those without a
+// timestamp are counted.
+fn before_start(start: Option<i64>, timestamp: Option<i64>) -> bool {
+  matches!((start, timestamp), (Some(start), Some(ts)) if ts < start)
+}
+
+// Where the packets of a stream go: its position in the list of tracked streams of its kind.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Route {
+  Skip,
+  Video,
+  Audio(usize),
+  Subtitle(usize),
+  Data(usize),
+  OtherVideo(usize),
+}
+
+// The route of every stream, indexed by stream index, so a packet finds its state without a search
+// through every list. A stream in several lists goes to the first one checked, as it always has.
+fn routes(stats: &DecodeStats, video_stream_index: usize, stream_count: usize) -> Vec<Route> {
+  let mut routes = vec![Route::Skip; stream_count.max(video_stream_index + 1)];
+  let mut set = |index: usize, route: Route| {
+    if let Some(slot) = routes.get_mut(index).filter(|slot| **slot == Route::Skip) {
+      *slot = route;
+    }
+  };
+  stats.audio.iter().enumerate().for_each(|(position, s)| set(s.index, Route::Audio(position)));
+  stats.subtitles.iter().enumerate().for_each(|(position, s)| set(s.index, Route::Subtitle(position)));
+  stats.data_streams.iter().enumerate().for_each(|(position, s)| set(s.index, Route::Data(position)));
+  stats.other_video.iter().enumerate().for_each(|(position, s)| set(s.index, Route::OtherVideo(position)));
+  set(video_stream_index, Route::Video);
+  routes
+}
+
+// Read and decode packets of the video stream until EOF or until one of the limits in `config`
+// is reached. Unless errors are tolerated, the first decode error is returned. Every decoded
+// frame is passed to `outputs` as it comes out of the decoder.
+fn decode_packets(
+  ictx: &mut ffmpeg::format::context::Input,
+  decoder: &mut ffmpeg::decoder::Video,
+  video_stream_index: usize,
+  frame: &mut ffmpeg::util::frame::video::Video,
+  config: DecodeConfig,
+  mut outputs: FrameOutputs,
+  pb: &ProgressBar,
+) -> Result<DecodeStats, ffmpeg::Error> {
+  let mut stats = DecodeStats::default();
+  let time_base = ictx.stream(video_stream_index).map_or(Rational::new(0, 1), |s| s.time_base());
+  stats.freeze = config.freeze_min_ms.map(|ms| freeze::Tracker::new(ms, time_base));
+  stats.scenes = config.scene_threshold.map(scenes::Tracker::new);
+  if config.timecodes {
+    let rate = ictx.stream(video_stream_index).and_then(|s| {
+      [s.avg_frame_rate(), s.rate()].into_iter().find(|rate| rate.numerator() > 0 && rate.denominator() > 0)
+    });
+    stats.timecodes = Some(timecode::Tracker::new(rate));
+  }
+  stats.sampling = config.sampling.map(|_| sampling::Counts::default());
+  stats.progress_events = config.progress_events.then(progress::Events::start);
+  let needs_pixels = config.range_check_step.is_some() || stats.freeze.is_some() || stats.scenes.is_some()
+    || outputs.thumbnails.is_some() || outputs.frame_hashes.is_some();
+  let mut software_frame = ffmpeg::util::frame::video::Video::empty();
+  // Time of the first video packet, where a progress bar over the duration starts.
+  let mut progress_origin: Option<i64> = None;
+
+  let mut audio_frame = ffmpeg::util::frame::audio::Audio::empty();
+  // Counting packets instead of decoding still lists the declared audio properties.
+  if config.decode_audio || config.count_packets {
+    stats.audio = audio::open(ictx, config.decode_audio, config.measure_loudness);
+  }
+  if let Some(threading_config) = config.other_video {
+    stats.other_video = video_streams::open(ictx, video_stream_index, threading_config);
+  }
+  stats.packet_sizes = packet_sizes::Tracker::new(config.top_packets);
+  if let Some(stream) = ictx.stream(video_stream_index) {
+    stats.random_access = random_access::Tracker::new(&stream.parameters());
+  }
+  if config.tolerate_discontinuities {
+    stats.monotonic = monotonic::Tracker::tolerating_discontinuities(time_base);
+  }
+  if config.count_subtitles {
+    stats.subtitles = subtitles::open(ictx);
+  }
+  if config.count_data_streams {
+    stats.data_streams = data_streams::open(ictx);
+  }
+  if config.split_segments {
+    stats.segments = Some(transport::Segments::new(time_base));
+  }
+  let mut awaiting_keyframe = config.skip_to_keyframe;
+
+  // Pass every frame the decoder has ready to the counters, checks and outputs.
+  let mut receive = |decoder: &mut ffmpeg::decoder::Video, frame: &mut ffmpeg::util::frame::video::Video,
+    stats: &mut DecodeStats, packet_timestamp: Option<i64>| -> Result<(), ffmpeg::Error> {
+    loop {
+      match decoder.receive_frame(frame) {
+        Ok(()) => {}
+        Err(ffmpeg::Error::Eof) => break,
+        Err(ffmpeg::Error::Other { errno }) if errno == ffmpeg::ffi::EAGAIN => break,
+        // With frame threading, damage in a packet shows up when its frame is due.
+        Err(e) => {
+          stats.note_decode_error(&e, packet_timestamp, config.record_timeline);
+          break;
+        }
+      }
+      if stats.first_decoded_timestamp.is_none() {
+        stats.first_decoded_timestamp = frame.timestamp();
+      }
+      if before_start(config.start_timestamp, frame.timestamp()) {
+        stats.frames_before_start += 1;
+        continue;
+      }
+      // Hardware frames are copied to system memory only for the checks that read samples.
+      let pixels: &ffmpeg::util::frame::video::Video = if needs_pixels && hwaccel::is_hardware(frame) {
+        hwaccel::transfer(frame, &mut software_frame)?;
+        &software_frame
+      } else {
+        frame
+      };
+      stats.check_frame(decoder, frame, pixels, &config);
+      if let Some(csv) = outputs.frames_csv.as_deref_mut() {
+        csv.write(stats.frame_count, frame, time_base);
+      }
+      if let Some(hashes) = outputs.frame_hashes.as_deref_mut() {
+        hashes.write(stats.frame_count, pixels);
+      }
+      if let Some(thumbnails) = outputs.thumbnails.as_deref_mut().filter(|_| frame.is_key()) {
+        thumbnails.write(stats.frame_count, pixels, time_base);
+      }
+      // Increment the frame count.
+      stats.frame_count += 1;
+      if config.progress.is_some() {
+        progress::frames(pb, stats.frame_count);
+      }
+    }
+    Ok(())
+  };
+
+  let routes = routes(&stats, video_stream_index, ictx.nb_streams() as usize);
+
+  // Packets are read on a thread of their own and decoded on this one as they arrive, then handed
+  // back to be read into again.
+  let (sender, receiver) = std::sync::mpsc::sync_channel(demux::QUEUE_PACKETS);
+  let (pool, recycled) = std::sync::mpsc::channel();
+  let demux_config = demux::Config {
+    video_stream_index,
+    time_base,
+    byte_limit: config.byte_limit,
+    io_retries: config.io_retries,
+    io_retry_delay_ms: config.io_retry_delay_ms,
+    network_timeout_seconds: config.network_timeout_seconds,
+    follow: config.follow,
+  };
+  let (decoded, read) = std::thread::scope(|scope| {
+    let reader = scope.spawn(|| demux::read(ictx, demux_config, sender, recycled, pb));
+    let mut follow_status = config.follow.map(follow::Status::new);
+    let decoded: Result<(), ffmpeg::Error> = 'decode: {
+      // Iterate over each packet in the video stream.
+      for packet in receiver.iter() {
+        let packet = demux::Recycled::new(packet, &pool);
+        pb.tick();
+        if let Some(status) = follow_status.as_mut() {
+          status.tick(pb, stats.frame_count, stats.last_frame_timestamp, stats.measured_fps(time_base), time_base);
+        }
+        let stream_index = packet.stream();
+        match config.progress {
+          Some(progress::Unit::Millis) if stream_index == video_stream_index => {
+            if let Some(ms) = packet.dts().and_then(|dts| timestamp_to_signed_millis(dts, time_base)) {
+              let origin = *progress_origin.get_or_insert(ms);
+              pb.set_position((ms - origin).max(0) as u64);
+            }
+          }
+          Some(progress::Unit::Bytes) if packet.position() >= 0 => pb.set_position(packet.position() as u64),
+          _ => {}
+        }
+        if let Some(events) = stats.progress_events.as_mut().filter(|_| stream_index == video_stream_index) {
+          events.progress(pb, config.progress, stats.frame_count,
+            stats.highest_dts.and_then(|dts| timestamp_to_signed_millis(dts, time_base)));
+        }
+
+        stats.streams.push(&packet);
+        if config.record_timeline {
+          stats.interleave.push(&packet);
+        }
+        // Streams that show up after the header, as in MPEG-TS, aren't tracked.
+        match routes.get(stream_index).copied().unwrap_or(Route::Skip) {
+          Route::Video => {}
+          Route::Audio(position) => {
+            stats.audio[position].decode(&packet, &mut audio_frame);
+            continue;
+          }
+          Route::Subtitle(position) => {
+            stats.subtitles[position].push(&packet);
+            continue;
+          }
+          Route::Data(position) => {
+            stats.data_streams[position].push(&packet);
+            continue;
+          }
+          Route::OtherVideo(position) => {
+            stats.other_video[position].decode(&packet);
+            continue;
+          }
+          // Skip packets that aren't from a tracked stream.
+          Route::Skip => continue,
+        }
+
+        let packet_timestamp = packet.pts().or(packet.dts());
+        if let (Some(end), Some(ts)) = (config.end_timestamp, packet_timestamp) {
+          if ts >= end {
+            break;
+          }
+        }
+        if config.record_timeline && packet.is_key() {
+          if let Some(ts) = packet_timestamp {
+            stats.keyframe_packet_timestamps.push(ts);
+          }
+          if let Some(data) = packet.data() {
+            stats.random_access.push(data, packet_timestamp);
+          }
+        }
+        if config.record_timeline {
+          if !before_start(config.start_timestamp, packet_timestamp) {
+            stats.bitrate.push(packet.dts().or(packet.pts()), packet.size() as u64, time_base);
+            stats.packet_sizes.push(packet_timestamp, packet.size() as u64, packet.is_key());
+          }
+          stats.monotonic.push(packet.dts(), packet.pts());
+        }
+
+        if stats.first_packet_timestamp.is_none() {
+          stats.first_packet_timestamp = packet_timestamp;
+        }
+        if let Some(dts) = packet.dts() {
+          stats.lowest_dts = Some(stats.lowest_dts.map_or(dts, |l| l.min(dts)));
+        }
+        if let Some(pts) = packet.pts() {
+          stats.lowest_pts = Some(stats.lowest_pts.map_or(pts, |l| l.min(pts)));
+          stats.negative_pts_packets += (pts < 0) as u64;
+        }
+
+        // Compare and update the highest_dts.
+        stats.highest_dts = match stats.highest_dts {
+          Some(val) if packet.dts().is_some() => {
+              if packet.dts().unwrap() > val {
+                  packet.dts()
+              } else {
+                  stats.highest_dts
+              }
+          }
+          None => packet.dts(),
+          _ => stats.highest_dts,
+        };
+        if let Some(segments) = stats.segments.as_mut() {
+          segments.push(&packet);
+        }
+
+        if config.count_packets {
+          if stats.first_decoded_timestamp.is_none() {
+            stats.first_decoded_timestamp = packet_timestamp;
+          }
+          if before_start(config.start_timestamp, packet_timestamp) {
+            stats.frames_before_start += 1;
+            continue;
+          }
+          stats.note_frame(packet.is_key(), packet_timestamp, config.record_timeline);
+          stats.frame_count += 1;
+          if config.progress.is_some() {
+            progress::frames(pb, stats.frame_count);
+          }
+          continue;
+        }
+
+        if let (Some(mode), Some(counts)) = (config.sampling, stats.sampling.as_mut()) {
+          if !counts.push(mode, packet.is_key(), packet_timestamp) {
+            continue;
+          }
+        }
+        if awaiting_keyframe {
+          if !packet.is_key() {
+            stats.packets_before_keyframe += 1;
+            continue;
+          }
+          awaiting_keyframe = false;
+        }
+
+        // Send the packet to the decoder.
+        if let Err(e) = decoder.send_packet(&*packet) {
+          if let Some(counts) = stats.sampling.as_mut().filter(|_| !packet.is_key()) {
+            counts.reference_errors += 1;
+            continue;
+          }
+          stats.note_decode_error(&e, packet_timestamp, config.record_timeline);
+          continue;
+        }
+
+        // Fetch decoded frames from the decoder. Loop until no more frames are available.
+        if let Err(e) = receive(decoder, frame, &mut stats, packet_timestamp) {
+          break 'decode Err(e);
+        }
+      }
+
+      // The reader closed the channel or the end was reached: drain the frames the decoder still
+      // holds, which with frame threading are the last several of the range.
+      if config.count_packets {
+        break 'decode Ok(());
+      }
+      match decoder.send_eof() {
+        Ok(()) => receive(decoder, frame, &mut stats, None),
+        Err(e) => Err(e),
+      }
+    };
+    // Dropping the receiver makes the reader stop at its next packet if decoding ended early.
+    drop(receiver);
+    (decoded, reader.join().unwrap_or_else(|panic| std::panic::resume_unwind(panic)))
+  });
+  decoded?;
+  stats.io_retries = read?;
+  for stream in &mut stats.audio {
+    stream.finish(&mut audio_frame);
+  }
+  for stream in &mut stats.other_video {
+    stream.finish();
+  }
+  if let Some(freeze) = stats.freeze.as_mut() {
+    freeze.finish();
+  }
+  // The decoded key frames are indexed within the sample; their packets index them among every
+  // frame.
+  if let Some(counts) = &stats.sampling {
+    stats.keyframes = counts.keyframes.clone();
+    stats.last_frame = counts.keyframes.last().map_or(0, |&(index, _)| index);
+    stats.last_keyframe_timestamp = counts.keyframes.iter().rev().find_map(|&(_, ts)| ts);
+  }
+
+  Ok(stats)
+}
+
+impl DecodeStats {
+  // Count an error of the decoder at the packet with this timestamp, if it has one.
+  fn note_decode_error(&mut self, error: &ffmpeg::Error, timestamp: Option<i64>, record_timeline: bool) {
+    self.decode_errors += 1;
+    *self.decode_error_kinds.entry(error.to_string()).or_insert(0) += 1;
+    if let Some(ts) = timestamp {
+      self.first_error_timestamp = Some(self.first_error_timestamp.map_or(ts, |t| t.min(ts)));
+      self.last_error_timestamp = Some(self.last_error_timestamp.map_or(ts, |t| t.max(ts)));
+      if record_timeline {
+        self.error_timestamps.push(ts);
+      }
+    }
+  }
+
+  // Record the key flag and timestamp of the frame about to be counted as frame_count.
+  fn note_frame(&mut self, key: bool, timestamp: Option<i64>, record_timeline: bool) {
+    // If the frame is a key frame, update the last_frame counter.
+    if key {
+      self.last_frame = self.frame_count;
+      self.last_keyframe_timestamp = timestamp.or(self.last_keyframe_timestamp);
+      self.keyframe_count += 1;
+      self.keyframes.push((self.frame_count, timestamp));
+      if let Some(ts) = timestamp {
+        self.keyframe_timestamps.push(ts);
+      }
+    }
+    if let Some(ts) = timestamp {
+      if record_timeline {
+        self.frame_timestamps.push(ts);
+      }
+      self.negative_pts_frames += (ts < 0) as u64;
+      self.first_frame_timestamp = Some(self.first_frame_timestamp.map_or(ts, |t| t.min(ts)));
+      self.last_frame_timestamp = Some(self.last_frame_timestamp.map_or(ts, |t| t.max(ts)));
+    }
+  }
+
+  // Pass a decoded frame, about to be counted as frame_count, to every per-frame check: `frame` for
+  // what the decoder says about it, `pixels` (in system memory) for the checks that read samples.
+  fn check_frame(
+    &mut self,
+    decoder: &ffmpeg::decoder::Video,
+    frame: &ffmpeg::util::frame::video::Video,
+    pixels: &ffmpeg::util::frame::video::Video,
+    config: &DecodeConfig,
+  ) {
+    if self.first_frame_size.is_none() {
+      self.first_frame_size = Some((frame.width(), frame.height()));
+      self.first_frame_sar = Some(frame.aspect_ratio());
+    }
+    self.note_frame(frame.is_key(), frame.timestamp(), config.record_timeline);
+    let frame_type = match frame.kind() {
+      ffmpeg::picture::Type::I => 0,
+      ffmpeg::picture::Type::P => 1,
+      ffmpeg::picture::Type::B => 2,
+      // Some intra-only decoders leave the picture type unset on their key frames.
+      ffmpeg::picture::Type::None if frame.is_key() => 0,
+      _ => 3,
+    };
+    self.frame_types[frame_type] += 1;
+    if config.record_timeline {
+      let dts = Some(frame.packet().dts).filter(|&dts| dts != ffmpeg::ffi::AV_NOPTS_VALUE);
+      self.gop_structure.push(dts, frame.pts(), frame.kind(), frame.is_key());
+    }
+    self.interlace.push(frame);
+    self.pixel_format.push(pixel_format::of_frame(decoder, frame), self.frame_count, frame.timestamp());
+    self.resolution.push((frame.width(), frame.height()), self.frame_count, frame.timestamp());
+    self.color.check(frame);
+    self.captions.push(frame);
+    if let Some(timecodes) = self.timecodes.as_mut() {
+      timecodes.push(frame, self.frame_count);
+    }
+    if let Some(step) = config.range_check_step {
+      self.range_check.check(pixels, step);
+    }
+    if let Some(freeze) = self.freeze.as_mut() {
+      freeze.check(pixels);
+    }
+    if let Some(scenes) = self.scenes.as_mut() {
+      scenes.check(pixels);
+    }
+  }
+
+  // Time and frames from the last key frame to the last frame of `frame_count`: what a player
+  // seeking near the end has to decode at worst. The last frame is the later of the last decoded
+  // frame and the highest packet DTS, as a sample may not decode the frames at the end.
+  fn keyframe_tail(&self, time_base: Rational, frame_count: i64) -> Option<(Timestamp, i64)> {
+    let (keyframe, last) = (self.last_keyframe_timestamp?, self.last_frame_timestamp.max(self.highest_dts)?);
+    Some((Timestamp::new((last - keyframe).max(0), time_base), (frame_count - 1 - self.last_frame).max(0)))
+  }
+
+  // Duration spanned by the decoded frames' timestamps, if any were decoded. Across timestamp
+  // discontinuities, the continuous segments of the packets are added up instead.
+  fn measured_duration(&self, time_base: Rational) -> Option<Timestamp> {
+    if let Some(span) = self.segments.as_ref().and_then(|segments| segments.continuous_span()) {
+      return Some(Timestamp::new(span, time_base));
+    }
+    let (first, last) = (self.first_frame_timestamp?, self.last_frame_timestamp?);
+    Some(Timestamp::new(last - first, time_base))
+  }
+
+  // Measured duration in milliseconds, if the timestamps carry any usable timing.
+  fn measured_duration_ms(&self, time_base: Rational) -> Option<u64> {
+    self.measured_duration(time_base)?.millis()
+  }
+
+  // Frame rate derived from the frame count and measured duration. None when the timestamps
+  // don't span any time (single frame, missing timestamps or degenerate time base).
+  fn measured_fps(&self, time_base: Rational) -> Option<f64> {
+    let duration_ms = self.measured_duration_ms(time_base)?;
+    if duration_ms == 0 || self.frame_count < 2 {
+      return None;
+    }
+    // N frames span N - 1 frame intervals between the first and last timestamp.
+    Some((self.frame_count - 1) as f64 * 1000.0 / duration_ms as f64)
+  }
+}
+
+// Print the decode error count with the times of the first and last error and the count of each
+// kind of error. Any error is a finding, located at the first one so --extract-problems has a
+// region to cut.
+fn report_decode_errors(stats: &DecodeStats, time_base: Rational, verbose: bool) -> Option<Finding> {
+  let span = match (stats.first_error_timestamp, stats.last_error_timestamp) {
+    (Some(first), Some(last)) => format!(" (first at {}, last at {})", Timestamp::new(first, time_base).describe(verbose),
+      Timestamp::new(last, time_base).describe(verbose)),
+    _ => String::new(),
+  };
+  out!("Decode errors: {}{}", format_with_commas(stats.decode_errors as i64), span);
+  for (kind, count) in &stats.decode_error_kinds {
+    out!("  {}: {}", kind, format_with_commas(*count as i64));
+  }
+  if stats.decode_errors == 0 {
+    return None;
+  }
+  let finding = Finding::new(&findings::DECODE_ERRORS, format!("{} decode errors in the video stream", stats.decode_errors));
+  Some(match stats.first_error_timestamp {
+    Some(first) => finding.at(Timestamp::new(first, time_base)),
+    None => finding,
+  })
+}
+
+// Describe the picture type histogram, e.g. "I 12 (4.8%), P 238 (95.2%), B 0 (0.0%), other 0 (0.0%)".
+fn describe_frame_types(frame_types: &[i64; 4]) -> String {
+  let total: i64 = frame_types.iter().sum();
+  ["I", "P", "B", "other"]
+    .iter()
+    .zip(frame_types)
+    .map(|(name, &count)| {
+      let share = if total > 0 { count as f64 * 100.0 / total as f64 } else { 0.0 };
+      format!("{} {} ({:.1}%)", name, format_with_commas(count), share)
+    })
+    .collect::<Vec<_>>()
+    .join(", ")
+}
+
+// Describe the spacing between key frames, e.g. "avg 2.000s (min 2.000s, max 2.000s)".
+fn keyframe_cadence(keyframe_timestamps: &[i64], time_base: Rational) -> String {
+  if keyframe_timestamps.len() < 2 {
+    return "not measurable (fewer than 2 key frames)".to_string();
+  }
+  let intervals: Option<Vec<u64>> = keyframe_timestamps
+    .windows(2)
+    .map(|w| timestamp_to_millis((w[1] - w[0]).max(0), time_base))
+    .collect();
+  let Some(intervals) = intervals else {
+    return "not measurable (no usable time base)".to_string();
+  };
+  let avg = intervals.iter().sum::<u64>() as f64 / intervals.len() as f64 / 1000.0;
+  let min = *intervals.iter().min().unwrap() as f64 / 1000.0;
+  let max = *intervals.iter().max().unwrap() as f64 / 1000.0;
+  format!("avg {:.3}s (min {:.3}s, max {:.3}s)", avg, min, max)
+}
+
+// What the analysis measured for each variant of an HLS or DASH input: the analyzed stream from the
+// main statistics, the others from their decoders.
+fn variant_figures(
+  variant_list: &[variants::Variant],
+  stats: &DecodeStats,
+  video_stream_index: usize,
+  time_base: Rational,
+) -> Vec<variants::Figures> {
+  variant_list
+    .iter()
+    .map(|variant| {
+      let bytes = stats.streams.bytes.get(variant.stream_index).copied().unwrap_or(0);
+      if variant.stream_index == video_stream_index {
+        return variants::Figures {
+          frames: Some(stats.frame_count),
+          duration: stats.measured_duration(time_base),
+          keyframe_intervals_ms: cadence::intervals_ms(&stats.keyframe_timestamps, time_base).unwrap_or_default(),
+          bytes,
+        };
+      }
+      match stats.other_video.iter().find(|stream| stream.index == variant.stream_index && stream.decodable) {
+        Some(stream) => variants::Figures {
+          frames: Some(stream.frame_count),
+          duration: stream.measured_duration(),
+          keyframe_intervals_ms: cadence::intervals_ms(&stream.keyframe_timestamps, stream.time_base).unwrap_or_default(),
+          bytes,
+        },
+        None => variants::Figures { frames: None, duration: None, keyframe_intervals_ms: Vec::new(), bytes },
+      }
+    })
+    .collect()
+}
+
+// Size of a head/tail window in bytes. A window too large to be a file offset is larger than any
+// file anyway.
+fn window_bytes(window_mb: u64) -> i64 {
+  i64::try_from(window_mb.saturating_mul(1024 * 1024)).unwrap_or(i64::MAX)
+}
+
+// Timestamp (AV_TIME_BASE) at the same share of the container duration as `offset` is of the file,
+// to seek demuxers that can't seek by byte. None when the container declares no duration.
+fn relative_seek_target(duration: i64, offset: i64, file_size: i64) -> Option<i64> {
+  if duration == ffmpeg::ffi::AV_NOPTS_VALUE || duration <= 0 || file_size <= 0 {
+    return None;
+  }
+  Some((duration as i128 * offset as i128 / file_size as i128) as i64)
+}
+
+// The figures of one --head-tail window.
+fn window_report(stats: &DecodeStats, time_base: Rational) -> report::WindowReport {
+  // The presentation time of the latest frame: the highest DTS trails it by the reordering delay.
+  let last_frame = stats.last_frame_timestamp.map(|pts| Timestamp::new(pts, time_base));
+  report::WindowReport {
+    frame_count: stats.frame_count,
+    keyframe_count: stats.keyframe_timestamps.len() as i64,
+    decode_errors: stats.decode_errors,
+    packets_before_keyframe: stats.packets_before_keyframe,
+    last_frame_ms: last_frame.and_then(|t| t.signed_millis()),
+    last_frame_raw: last_frame,
+  }
+}
+
+// Analyze only the first and last `window_mb` megabytes of the input and print a report for both
+// windows. Everything between them is skipped and reported as unanalyzed. A tail the demuxer
+// can't seek to is reported as not analyzed rather than failing the file.
+fn analyze_head_tail(
+  ictx: &mut ffmpeg::format::context::Input,
+  video: &mut Video,
+  window_mb: u64,
+  options: &AnalyzeOptions,
+  pb: &ProgressBar,
+) -> Result<(report::HeadTailReport, Vec<Finding>), ffmpeg::Error> {
+  let (video_stream_index, time_base, verbose) = (video.index, video.time_base, options.verbose);
+  let decoder = &mut video.decoder;
+  let window_bytes = window_bytes(window_mb);
+  let events = options.progress_events.then(progress::Events::start);
+  let config = DecodeConfig { progress_events: options.progress_events, ..DecodeConfig::default() };
+
+  // The total size comes from the I/O layer, so it also works for HTTP inputs that report a
+  // Content-Length. Seeking on those makes the http protocol issue a new byte-range request,
+  // which keeps the transfer to roughly twice the window size.
+  let file_size = input_size(ictx).map_or(-1, |size| size as i64);
+
+  let mut frame = ffmpeg::util::frame::video::Video::empty();
+
+  if file_size <= 0 || file_size <= window_bytes.saturating_mul(2) {
+    // Nothing to skip (or size unknown): the windows would cover the whole file anyway.
+    pb.set_message("Processing packets (file smaller than both windows)...");
+    let stats = decode_packets(ictx, decoder, video_stream_index, &mut frame, config, FrameOutputs::default(), pb)?;
+    pb.finish_with_message("Processing complete.");
+    out!("{}Head/tail analysis - {}", BLUE_BOLD, RESET);
+    if file_size <= 0 {
+      out!("File size unknown; the whole input was analyzed.");
+    } else {
+      out!("File size {} bytes is within 2 x {} MB; the whole input was analyzed.",
+        format_with_commas(file_size), window_mb);
+    }
+    out!("Frames count: {}", format_with_commas(stats.frame_count));
+    out!("Key frame cadence: {}", keyframe_cadence(&stats.keyframe_timestamps, time_base));
+    if let Some(events) = &events {
+      events.done(stats.frame_count, stats.highest_dts.and_then(|dts| timestamp_to_signed_millis(dts, time_base)));
+    }
+    let head_tail = report::HeadTailReport {
+      window_bytes,
+      file_size: (file_size > 0).then_some(file_size),
+      whole_file: true,
+      head: window_report(&stats, time_base),
+      tail: None,
+      tail_not_analyzed: None,
+      unanalyzed: None,
+      truncated: None,
+    };
+    return Ok((head_tail, Vec::new()));
+  }
+
+  // Head window: decode normally from the start until the byte limit.
+  pb.set_message("Processing head window...");
+  let head = decode_packets(ictx, decoder, video_stream_index, &mut frame,
+    DecodeConfig { byte_limit: Some(window_bytes), ..config }, FrameOutputs::default(), pb)?;
+
+  // Tail window: seek to (size - window) and let the demuxer resynchronize. Demuxers that can't
+  // seek by byte (e.g. MP4) fall back to a timestamp seek at the same relative position, which
+  // needs a declared duration.
+  let tail_start = file_size - window_bytes;
+  let seek_result = unsafe {
+    ffmpeg::ffi::avformat_seek_file(ictx.as_mut_ptr(), -1, i64::MIN, tail_start, i64::MAX, ffmpeg::ffi::AVSEEK_FLAG_BYTE)
+  };
+  let seeked = if seek_result >= 0 {
+    Ok(())
+  } else {
+    match relative_seek_target(ictx.duration(), tail_start, file_size) {
+      Some(target) => ictx.seek(target, ..target)
+        .map_err(|e| format!("the demuxer can't seek by byte and the seek by time failed: {}", e)),
+      None => Err("the demuxer can't seek by byte and the container declares no duration to seek by".to_string()),
+    }
+  };
+  let tail = match seeked {
+    Ok(()) => {
+      // Drop any reference frames from the head window before decoding the tail.
+      decoder.flush();
+      // Decoding right after a seek usually starts mid-GOP: the packets up to the first key frame
+      // are left out, and errors past it are counted.
+      pb.set_message("Processing tail window...");
+      Ok(decode_packets(ictx, decoder, video_stream_index, &mut frame,
+        DecodeConfig { skip_to_keyframe: true, ..config }, FrameOutputs::default(), pb)?)
+    }
+    Err(reason) => Err(reason),
+  };
+  pb.finish_with_message("Processing complete.");
+  if let Some(events) = &events {
+    let last = tail.as_ref().unwrap_or(&head);
+    events.done(head.frame_count + tail.as_ref().map_or(0, |tail| tail.frame_count),
+      last.highest_dts.and_then(|dts| timestamp_to_signed_millis(dts, time_base)));
+  }
+
+  let declared_duration = container_duration(ictx);
+
+  out!("{}Head window (first {} MB) - {}", BLUE_BOLD, window_mb, RESET);
+  out!("ictx.duration: {}", describe_raw_duration(ictx.duration()));
+  out!("Declared Duration: {}", timestamp::describe_optional(declared_duration, verbose));
+  out!("Time base numerator: {}", time_base.numerator());
+  out!("Time base denominator: {}", time_base.denominator());
+  out!("Frames count: {}", format_with_commas(head.frame_count));
+  out!("Key frames: {}", format_with_commas(head.keyframe_timestamps.len() as i64));
+  out!("Key frame cadence: {}", keyframe_cadence(&head.keyframe_timestamps, time_base));
+
+  let unanalyzed_end = if tail.is_ok() { tail_start } else { file_size };
+  out!("{}{}Unanalyzed - {}", RESET, BLUE_BOLD, RESET);
+  out!("Bytes {} to {} ({} bytes) were skipped and NOT analyzed.",
+    format_with_commas(window_bytes), format_with_commas(unanalyzed_end),
+    format_with_commas(unanalyzed_end - window_bytes));
+
+  out!("{}{}Tail window (last {} MB) - {}", RESET, BLUE_BOLD, window_mb, RESET);
+  let declared_duration_ms = declared_duration.and_then(|d| d.millis());
+  let mut findings = Vec::new();
+  if declared_duration_ms.is_none() {
+    findings.push(Finding::new(&findings::NO_CONTAINER_DURATION, "container declares no duration"));
+  }
+  let mut head_tail = report::HeadTailReport {
+    window_bytes,
+    file_size: Some(file_size),
+    whole_file: false,
+    head: window_report(&head, time_base),
+    tail: None,
+    tail_not_analyzed: None,
+    unanalyzed: Some(report::ByteRangeReport { start: window_bytes, end: unanalyzed_end }),
+    truncated: None,
+  };
+  let tail = match tail {
+    Ok(tail) => tail,
+    Err(reason) => {
+      out!("Tail window: not analyzed, {}", reason);
+      print_findings(&mut findings);
+      head_tail.tail_not_analyzed = Some(reason);
+      return Ok((head_tail, findings));
+    }
+  };
+  let tail_report = window_report(&tail, time_base);
+  out!("Frames count: {}", format_with_commas(tail.frame_count));
+  out!("Packets before the first key frame: {} (not decoded)", format_with_commas(tail.packets_before_keyframe as i64));
+  out!("Decode errors: {}", format_with_commas(tail.decode_errors as i64));
+  out!("Last Frame Time: {}", timestamp::describe_optional(tail_report.last_frame_raw, verbose));
+  if let (Some(declared_duration_ms), Some(tail_last_frame_ms)) = (declared_duration_ms, tail_report.last_frame_ms) {
+    let diff_ms = declared_duration_ms as i64 - tail_last_frame_ms;
+    out!("Last frame vs declared duration: {}{}",
+      if diff_ms < 0 { "+" } else { "-" }, millis_to_clock_format(diff_ms.unsigned_abs()));
+  }
+  // The tail is considered decodable when it produced frames; a truncated file usually decodes
+  // nothing past the cut or ends far short of its declared duration.
+  let decodable = tail.frame_count > 0;
+  out!("Tail decodable: {}", if decodable { "yes" } else { "no (possible truncation)" });
+
+  if tail.decode_errors > 0 {
+    findings.push(Finding::new(&findings::TAIL_DECODE_ERRORS,
+      format!("{} packets in the tail window failed to decode", tail.decode_errors)));
+  }
+  if !decodable {
+    findings.push(Finding::new(&findings::TRUNCATED, "no frames could be decoded from the tail window"));
+  }
+  print_findings(&mut findings);
+
+  head_tail.tail = Some(tail_report);
+  head_tail.truncated = Some(!decodable);
+  Ok((head_tail, findings))
+}
+
+// How a file accounts for the decoder's reordering delay.
+fn delay_mechanism(
+  ictx: &ffmpeg::format::context::Input,
+  path: &str,
+  video_stream_index: usize,
+  stats: &DecodeStats,
+) -> String {
+  if stats.lowest_dts.is_some_and(|dts| dts < 0) {
+    return "negative timestamps".to_string();
+  }
+  let is_mp4 = ictx.format().name().split(',').any(|name| name == "mov" || name == "mp4");
+  if is_mp4 && std::path::Path::new(path).is_file() {
+    let offset = mp4::edit_list_offsets(std::path::Path::new(path))
+      .ok()
+      .and_then(|offsets| offsets.get(video_stream_index).copied().flatten());
+    if let Some(offset) = offset.filter(|&offset| offset > 0) {
+      return format!("edit list (media starts at {})", offset);
+    }
+  }
+  "neither".to_string()
+}
+
+// Print the lowest packet timestamps, the decoder-reported frame delay and how the container
+// expresses that delay.
+fn report_timestamps(
+  ictx: &ffmpeg::format::context::Input,
+  path: &str,
+  decoder: &ffmpeg::decoder::Video,
+  video_stream_index: usize,
+  time_base: Rational,
+  stats: &DecodeStats,
+  verbose: bool,
+) {
+  // has_b_frames is the number of frames the decoder holds back for reordering; it is only known
+  // for sure once the bitstream has been parsed.
+  let delay_frames = unsafe { (*decoder.as_ptr()).has_b_frames };
+
+  out!("{}{}Timestamps - {}", RESET, BLUE_BOLD, RESET);
+  out!("Lowest DTS: {}", timestamp::describe_optional(stats.lowest_dts.map(|ts| Timestamp::new(ts, time_base)), verbose));
+  out!("Lowest PTS: {}", timestamp::describe_optional(stats.lowest_pts.map(|ts| Timestamp::new(ts, time_base)), verbose));
+  if let (Some(dts), Some(pts)) = (stats.lowest_dts, stats.lowest_pts) {
+    out!("PTS - DTS offset: {}", Timestamp::new(pts - dts, time_base).describe(verbose));
+  }
+  out!("Negative PTS: {} packets, {} frames", format_with_commas(stats.negative_pts_packets as i64),
+    format_with_commas(stats.negative_pts_frames as i64));
+  out!("Decoder delay: {} frames", delay_frames);
+  out!("Delay expressed by: {}", delay_mechanism(ictx, path, video_stream_index, stats));
+}
+
+// The declared and measured start of the video timestamps.
+struct StartOffset {
+  container: Option<Timestamp>,
+  stream: Option<Timestamp>,
+  first_packet: Option<Timestamp>,
+  first_frame: Option<Timestamp>,
+}
+
+impl StartOffset {
+  // A START_TIME_MISMATCH finding when the start the video stream (or else the container)
+  // declares is more than `tolerance_ms` from the first frame; located at the first frame.
+  fn mismatch(&self, tolerance_ms: u64, verbose: bool) -> Option<Finding> {
+    let (declared_by, declared) = self.stream.map(|ts| ("video stream", ts)).or(self.container.map(|ts| ("container", ts)))?;
+    let first = self.first_frame?;
+    let apart_ms = (declared.signed_millis()? - first.signed_millis()?).unsigned_abs();
+    (apart_ms > tolerance_ms).then(|| Finding::new(&findings::START_TIME_MISMATCH, format!(
+      "{} declares a start time of {} but the first frame is at {}, {} apart", declared_by,
+      declared.describe(verbose), first.describe(verbose), millis_to_clock_format(apart_ms))).at(first))
+  }
+}
+
+// Print the start time the container and the video stream declare next to the timestamps of the
+// first video packet and frame. The first frame's is the start offset of the video.
+fn report_start_offset(
+  ictx: &ffmpeg::format::context::Input,
+  start_time: i64,
+  first_frame: Option<Timestamp>,
+  stats: &DecodeStats,
+  time_base: Rational,
+  verbose: bool,
+) -> StartOffset {
+  let container_start = unsafe { (*ictx.as_ptr()).start_time };
+  let offset = StartOffset {
+    container: (container_start != ffmpeg::ffi::AV_NOPTS_VALUE).then(|| Timestamp::from_av_time_base(container_start)),
+    stream: (start_time != ffmpeg::ffi::AV_NOPTS_VALUE).then(|| Timestamp::new(start_time, time_base)),
+    first_packet: stats.first_packet_timestamp.map(|ts| Timestamp::new(ts, time_base)),
+    first_frame,
+  };
+  out!("Container start time: {}", timestamp::describe_optional(offset.container, verbose));
+  out!("Video stream start time: {}", timestamp::describe_optional(offset.stream, verbose));
+  out!("First video packet: {}", timestamp::describe_optional(offset.first_packet, verbose));
+  out!("Start offset (first frame): {}", timestamp::describe_optional(offset.first_frame, verbose));
+  offset
+}
+
+// Whether the codec only has intra frames (ProRes, DNxHD, MJPEG...), for which every frame being a
+// key frame is expected.
+fn is_intra_only(id: ffmpeg::codec::Id) -> bool {
+  unsafe {
+    let descriptor = ffmpeg::ffi::avcodec_descriptor_get(id.into());
+    !descriptor.is_null() && (*descriptor).props & ffmpeg::ffi::AV_CODEC_PROP_INTRA_ONLY != 0
+  }
+}
+
+// Print coded versus display dimensions, codec-level cropping and, for MP4/MOV, the clean
+// aperture ('clap') of the analyzed track. Disagreements are returned as findings.
+fn report_dimensions(
+  ictx: &ffmpeg::format::context::Input,
+  path: &str,
+  decoder: &ffmpeg::decoder::Video,
+  parameters: &ffmpeg::codec::Parameters,
+  video_stream_index: usize,
+  stats: &DecodeStats,
+) -> Vec<Finding> {
+  let mut findings = Vec::new();
+  // The decoder only knows the coded (macroblock-aligned, uncropped) size after parsing the
+  // bitstream, so this runs after the decode pass.
+  let (coded_width, coded_height) = unsafe {
+    let ctx = decoder.as_ptr();
+    ((*ctx).coded_width, (*ctx).coded_height)
+  };
+  let (declared_width, declared_height) = unsafe {
+    let par = parameters.as_ptr();
+    ((*par).width, (*par).height)
+  };
+
+  out!("{}{}Dimensions - {}", RESET, BLUE_BOLD, RESET);
+  out!("Coded size: {}x{}", coded_width, coded_height);
+  out!("Declared size: {}x{}", declared_width, declared_height);
+  let display = stats.first_frame_size;
+  match display {
+    Some((width, height)) => {
+      out!("Display size (decoded frames): {}x{}", width, height);
+      if coded_width > 0 && coded_height > 0 {
+        out!("Codec-level cropping: {} columns, {} rows",
+          (coded_width - width as i32).max(0), (coded_height - height as i32).max(0));
+      }
+    }
+    None => out!("Display size (decoded frames): unknown (no frames decoded)"),
+  }
+
+  // libavformat doesn't export the clap box, so read it from the file for local MP4/MOV inputs.
+  let is_mp4 = ictx.format().name().split(',').any(|name| name == "mov" || name == "mp4");
+  if !is_mp4 || !std::path::Path::new(path).is_file() {
+    return findings;
+  }
+  let aperture = mp4::clean_apertures(std::path::Path::new(path))
+    .ok()
+    .and_then(|mut tracks| if video_stream_index < tracks.len() { tracks.swap_remove(video_stream_index) } else { None });
+  match aperture {
+    Some(clap) => {
+      out!("Clean aperture: {}/{} x {}/{} (offset {}/{}, {}/{})",
+        clap.width.0, clap.width.1, clap.height.0, clap.height.1,
+        clap.horizontal_offset.0, clap.horizontal_offset.1,
+        clap.vertical_offset.0, clap.vertical_offset.1);
+      if let (Some(clap_size), Some(display)) = (clap.size(), display) {
+        if clap_size != display {
+          findings.push(Finding::new(&findings::CROP_MISMATCH, format!(
+            "container clean aperture is {}x{} but the codec crops to {}x{}",
+            clap_size.0, clap_size.1, display.0, display.1)));
+        }
+      }
+    }
+    None => out!("Clean aperture: none"),
+  }
+  findings
+}
+
+// What is read from the boxes of a local MP4/MOV file, without libav.
+struct Mp4Boxes {
+  layout: Option<std::io::Result<faststart::Layout>>,
+  fragmented: Option<fragments::Fragmented>,
+}
+
+// What the container declares, read before any packet: the stream-copy verdict, the tags, chapters
+// and attachments, and the boxes of a local MP4/MOV file.
+struct Container {
+  copyable: Option<bool>,
+  tags: metadata::Tags,
+  chapters: Vec<chapters::Chapter>,
+  attachments: Vec<attachments::Attachment>,
+  boxes: Mp4Boxes,
+}
+
+impl Container {
+  // The remux verdict only needs the stream parameters, so it is printed before any decoding.
+  fn read(ictx: &ffmpeg::format::context::Input, path: &str, remux_target: Option<remux::RemuxTarget>) -> Self {
+    let copyable = remux_target.map(|target| remux::report(ictx, target));
+    let layout = faststart::read(ictx, path);
+    Container {
+      copyable,
+      tags: metadata::read(ictx),
+      chapters: chapters::read(ictx),
+      attachments: attachments::read(ictx),
+      boxes: Mp4Boxes {
+        fragmented: layout.as_ref().and_then(|layout| layout.as_ref().ok())
+          .and_then(|_| fragments::read(std::path::Path::new(path)).ok().flatten()),
+        layout,
+      },
+    }
+  }
+
+  // A report holding what the container declares, for the analysis to fill in the rest.
+  fn to_report(&self, ictx: &ffmpeg::format::context::Input, path: &str) -> AnalysisReport {
+    AnalysisReport {
+      path: path.to_string(),
+      container_duration_ms: container_duration(ictx).and_then(|d| d.millis()),
+      container_duration_raw: container_duration(ictx),
+      chapters: Some(self.chapters.iter().map(report::ChapterReport::from).collect()),
+      metadata: Some(report::MetadataReport::from(&self.tags)),
+      copyable: self.copyable,
+      attachments: self.attachments.iter().map(report::AttachmentReport::from).collect(),
+      mp4_layout: self.boxes.layout.as_ref().and_then(|layout| layout.as_ref().ok()).map(report::Mp4LayoutReport::from),
+      fragmented: self.boxes.fragmented.as_ref().map(report::FragmentedReport::from),
+      ..AnalysisReport::default()
+    }
+  }
+}
+
+// Report a file that has streams but no video stream (e.g. MP3 or audio-only MP4): basic
+// metadata, the list of streams and the duration measured from the best audio stream's packets.
+fn report_without_video(
+  ictx: &mut ffmpeg::format::context::Input,
+  container: &Container,
+  analysis: &mut AnalysisReport,
+  verbose: bool,
+  pb: &ProgressBar,
+) -> Vec<Finding> {
+  let audio = ictx.streams().best(Type::Audio).map(|s| (s.index(), s.time_base()));
+
+  // Walk the packets for the stream table and the last timestamp of the best audio stream; no
+  // decoding needed.
+  let mut counts = stream_table::Counts::default();
+  pb.set_message("Processing packets...");
+  for (_, packet) in ictx.packets() {
+    pb.tick();
+    counts.push(&packet);
+  }
+  pb.finish_with_message("Processing complete.");
+
+  out!("{}Basic file metadata - {}", BLUE_BOLD, RESET);
+  out!("ictx.duration: {}", describe_raw_duration(ictx.duration()));
+  out!("Media Duration: {}", timestamp::describe_optional(container_duration(ictx), verbose));
+  let cover_art = container.attachments.iter().any(|a| a.kind == attachments::Kind::AttachedPicture);
+  let no_video = if cover_art { "no video stream present besides cover art" } else { "no video stream present" };
+  out!("Note: {}; frame-based figures are not available.", no_video);
+
+  (analysis.streams, analysis.container_bit_rate) = stream_table::report(ictx, &counts, true);
+  attachments::report(&container.attachments);
+  let layout_finding = faststart::report(container.boxes.layout.as_ref());
+  let fragment_finding = container.boxes.fragmented.as_ref().and_then(fragments::report);
+
+  if let Some((audio_index, time_base)) = audio {
+    out!("{}{}Calculated from the audio packets - {}", RESET, BLUE_BOLD, RESET);
+    out!("Audio stream index: {}", audio_index);
+    let last_packet = counts.highest_dts.get(audio_index).copied().flatten().map(|dts| Timestamp::new(dts, time_base));
+    out!("Last Packet Time: {}", timestamp::describe_optional(last_packet, verbose));
+  }
+
+  metadata::report(&container.tags);
+  let chapter_finding = chapters::report(&container.chapters, chapters::media_end_ms(ictx));
+
+  let mut findings = vec![Finding::new(&findings::NO_VIDEO_STREAM, no_video)];
+  if container_duration(ictx).is_none() && container.boxes.fragmented.is_none() {
+    findings.push(Finding::new(&findings::NO_CONTAINER_DURATION, "container declares no duration"));
+  }
+  findings.extend(layout_finding);
+  findings.extend(fragment_finding);
+  findings.extend(chapter_finding);
+  print_findings(&mut findings);
+  findings
+}
+
+// Print how long the analysis took and the peak memory, and return both for the report.
+fn report_resources(start: Instant) -> (u64, Option<u64>) {
+  let code_execution_time_ms = start.elapsed().as_millis() as u64;
+  out!("Code execution time: {}", millis_to_clock_format(code_execution_time_ms));
+  (code_execution_time_ms, report_peak_memory())
+}
+
+// Decoder threads for AnalyzeOptions::threads_number_input: -1 uses every available core, and an
+// invalid count falls back to 1.
+fn thread_count(threads_number_input: i32) -> usize {
+  if threads_number_input == -1 {
+    let threads_number = num_cpus::get();
+    log::info!("Setting threading to the number of available cores: {}.", threads_number);
+    threads_number
+  } else if threads_number_input < 1 {
+    log::warn!("invalid thread count provided. Defaulting to 1 thread.");
+    1
+  } else {
+    threads_number_input as usize
+  }
+}
+
+// The video stream to analyze: the one given with --stream or --variant, otherwise the highest
+// resolution one, which isn't always the demuxer's best stream when a preview track is present.
+struct Selection {
+  tracks: Vec<streams::VideoTrack>,
+  variants: Vec<variants::Variant>,
+  // The stream asked for, if any.
+  requested: Option<usize>,
+  // The stream picked; None without a video stream.
+  index: Option<usize>,
+  // A BEST_STREAM_MISMATCH finding when the pick isn't the demuxer's best stream.
+  finding: Option<Finding>,
+}
+
+impl Selection {
+  fn choose(ictx: &mut ffmpeg::format::context::Input, options: &AnalyzeOptions) -> Result<Self, AnalyzeError> {
+    let tracks = streams::video_tracks(ictx);
+    let best_index = ictx.streams().best(Type::Video).map(|stream| stream.index());
+    // --variant analyzes the stream of that rendition and leaves the others unread.
+    let variant_list = variants::list(ictx);
+    let requested = match options.variant {
+      Some(number) => {
+        let variant = variant_list.get(number)
+          .ok_or_else(|| AnalyzeError::InvalidArgument(variants::invalid_selection(&variant_list, number)))?;
+        variants::select(ictx, variant);
+        Some(variant.stream_index)
+      }
+      None => options.stream_index,
+    };
+    let index = match requested {
+      Some(index) if tracks.iter().any(|t| t.index == index) => Some(index),
+      Some(index) => return Err(AnalyzeError::InvalidArgument(streams::invalid_selection(ictx, &tracks, index))),
+      None => streams::primary_index(&tracks),
+    };
+    let mut finding = None;
+    if let (None, Some(best), Some(selected)) = (requested, best_index, index) {
+      if best != selected {
+        let best_is_cover_art = tracks.iter().any(|t| t.index == best && t.attached_pic);
+        finding = Some(Finding::new(&findings::BEST_STREAM_MISMATCH, if best_is_cover_art {
+          format!("demuxer's best video stream #{} is an attached picture, analyzing #{}", best, selected)
+        } else {
+          format!("demuxer's best video stream is #{}, analyzing the higher-resolution #{}", best, selected)
+        }));
+      }
+    }
+    Ok(Selection { tracks, variants: variant_list, requested, index, finding })
+  }
+}
+
+// The analyzed video stream and its opened decoder.
+struct Video {
+  index: usize,
+  parameters: ffmpeg::codec::Parameters,
+  rotation: rotation::Metadata,
+  profile: profile::Declared,
+  decoder: ffmpeg::decoder::Video,
+  // Lowres factor applied for the pixel quality, and the hardware device decoding, if any.
+  lowres: u8,
+  hardware_device: Option<String>,
+  threading: threading::Config,
+  // The software format the stream opens with; a hardware decoder switches to its device format
+  // at the first frame.
+  opened_format: ffmpeg::format::Pixel,
+  time_base: Rational,
+  start_time: i64,
+  // The average frame rate, or the real base rate when it isn't set.
+  declared_rate: Rational,
+}
+
+impl Video {
+  // Create a decoding context for the stream and open it with `threads` frame threads.
+  fn open(input: &ffmpeg::format::stream::Stream, options: &AnalyzeOptions, threads: usize) -> Result<Self, AnalyzeError> {
+    let parameters = input.parameters();
+    let mut context_decoder = ffmpeg::codec::context::Context::from_parameters(parameters.clone())?;
+    let lowres = apply_pixel_quality(&mut context_decoder, options.pixel_quality);
+    // A requested accelerator that can't be used leaves the decoder in software.
+    let accel = if options.no_decode { hwaccel::Accel::None } else { options.hwaccel };
+    let hardware_device = match hwaccel::setup(&mut context_decoder, accel) {
+      Some(Ok(device)) => Some(device),
+      Some(Err(reason)) => {
+        log::warn!("hardware decoding unavailable, decoding in software: {}", reason);
+        None
+      }
+      None => None,
+    };
+    // Create a threading config and set the number of threads. FFmpeg starts the threads when the
+    // decoder is opened, so it has to be set on the context before.
+    let threading_config = threading::Config {
+      kind: threading::Type::Frame,
+      count: threads
+    };
+    context_decoder.set_threading(threading_config);
+    let decoder = context_decoder.decoder().video()?;
+    Ok(Video {
+      index: input.index(),
+      rotation: rotation::Metadata::of_stream(input),
+      profile: profile::Declared::of_stream(input),
+      opened_format: decoder.format(),
+      decoder,
+      parameters,
+      lowres,
+      hardware_device,
+      threading: threading_config,
+      time_base: input.time_base(),
+      start_time: input.start_time(),
+      declared_rate: match input.avg_frame_rate() {
+        rate if rate_to_fps(rate).is_some() => rate,
+        _ => input.rate(),
+      },
+    })
+  }
+
+  // Width and height the stream parameters declare.
+  fn declared_size(&self) -> (u32, u32) {
+    unsafe {
+      let par = self.parameters.as_ptr();
+      ((*par).width.max(0) as u32, (*par).height.max(0) as u32)
+    }
+  }
+}
+
+// The part of the video stream that is analyzed, and how long it should last. --start and --end
+// count from the start of the stream, like the duration check.
+struct Range {
+  // Start time of the stream, 0 when it has none.
+  stream_start: i64,
+  // --start and --end in the stream time base.
+  start_timestamp: Option<i64>,
+  end_timestamp: Option<i64>,
+  start_ms: u64,
+  // From the track fragments of a fragmented file, whatever duration the container declares, and
+  // otherwise the container's. A file still being written declares the duration of what had been
+  // written when it was opened, if any, so that isn't what the last frame is checked against.
+  media_duration_ms: Option<u64>,
+  // Length of the analyzed range; without --end it runs to the end of the media.
+  window_ms: Option<u64>,
+}
+
+impl Range {
+  fn new(ictx: &ffmpeg::format::context::Input, video: &Video, options: &AnalyzeOptions, boxes: &Mp4Boxes) -> Self {
+    let stream_start = if video.start_time == ffmpeg::ffi::AV_NOPTS_VALUE { 0 } else { video.start_time };
+    let range_timestamp = |ms: u64| stream_start + (ms as i64).rescale(Rational::new(1, 1000), video.time_base);
+    let start_ms = options.start_ms.unwrap_or(0);
+    let fragment_duration_ms = boxes.fragmented.as_ref()
+      .map(|fragmented| fragmented.track(video.index).and_then(|track| track.duration_ms()));
+    let media_duration_ms = match fragment_duration_ms {
+      Some(duration_ms) => duration_ms,
+      None => container_duration(ictx).and_then(|d| d.millis()),
+    }.filter(|_| options.follow.is_none());
+    let window_ms = match options.end_ms {
+      Some(end) => Some(media_duration_ms.map_or(end, |duration| duration.min(end))),
+      None => media_duration_ms,
+    }.map(|end| end.saturating_sub(start_ms));
+    Range {
+      stream_start,
+      start_timestamp: options.start_ms.filter(|&ms| ms > 0).map(range_timestamp),
+      end_timestamp: options.end_ms.map(range_timestamp),
+      start_ms,
+      media_duration_ms,
+      window_ms,
+    }
+  }
+}
+
+// What the decode pass over a range checks, from the options. The recovery pass needs the strict
+// pass to remember where it failed. The frame timestamps of the timeline also feed the modal frame
+// rate.
+fn decode_config(
+  ictx: &ffmpeg::format::context::Input,
+  video: &Video,
+  options: &AnalyzeOptions,
+  range: &Range,
+  variant_count: usize,
+  progress_unit: progress::Unit,
+) -> DecodeConfig {
+  DecodeConfig {
+    start_timestamp: range.start_timestamp,
+    end_timestamp: range.end_timestamp,
+    record_timeline: true,
+    io_retries: options.io_retries,
+    io_retry_delay_ms: options.io_retry_delay_ms,
+    network_timeout_seconds: options.network_timeout_seconds,
+    // Frames decoded without the references skipped before them aren't worth a range check.
+    range_check_step: (!options.no_decode && !matches!(options.sampling, Some(sampling::Mode::Every(_))))
+      .then_some(match options.pixel_quality {
+        PixelQuality::Full => 1,
+        PixelQuality::Fast => pixels::FAST_SAMPLE_STEP,
+      }),
+    top_packets: options.top_packets,
+    decode_audio: !options.no_decode,
+    measure_loudness: options.measure_loudness,
+    count_packets: options.no_decode,
+    sampling: options.sampling.filter(|_| !options.no_decode),
+    progress: Some(progress_unit),
+    // Every variant of an HLS or DASH input is decoded along, unless --variant picked one.
+    other_video: ((options.all_video_streams || (variant_count > 1 && options.variant.is_none()))
+      && !options.no_decode).then_some(video.threading),
+    tolerate_discontinuities: options.tolerate_discontinuities || variants::is_adaptive(ictx),
+    follow: options.follow,
+    progress_events: options.progress_events,
+    count_subtitles: true,
+    count_data_streams: true,
+    split_segments: transport::is_transport_stream(ictx),
+    timecodes: options.timecodes && !options.no_decode,
+    freeze_min_ms: options.detect_freeze_ms.filter(|_| !options.no_decode),
+    scene_threshold: options.scenes.as_ref().map(|scenes| scenes.threshold).filter(|_| !options.no_decode),
+    ..DecodeConfig::default()
+  }
+}
+
+// Decode the analyzed range of the video stream, writing the per-frame files along.
+fn decode_range(
+  ictx: &mut ffmpeg::format::context::Input,
+  video: &mut Video,
+  options: &AnalyzeOptions,
+  range: &Range,
+  variant_count: usize,
+  pb: &ProgressBar,
+) -> Result<DecodeStats, AnalyzeError> {
+  pb.set_message("Processing packets...");
+  let following = options.follow.is_some();
+  let progress_unit = progress::start(pb, range.window_ms, input_size(ictx).filter(|_| !following));
+  if following {
+    follow::catch_interrupt();
+  }
+  let config = decode_config(ictx, video, options, range, variant_count, progress_unit);
+  // Created before decoding so a bad path fails early; dropping the writer on a decode error
+  // flushes the rows written so far.
+  let mut frames_csv = match &options.frames_csv {
+    Some(csv_path) => Some(frames_csv::Writer::create(csv_path)?),
+    None => None,
+  };
+  let mut thumbnails = match &options.thumbnails {
+    Some(settings) => Some(thumbnails::Writer::create(settings)?),
+    None => None,
+  };
+  let mut frame_hashes = match &options.frame_hashes {
+    Some(settings) => Some(frame_hashes::Writer::create(settings)?),
+    None => None,
+  };
+  let outputs = FrameOutputs {
+    frames_csv: frames_csv.as_mut(),
+    thumbnails: thumbnails.as_mut(),
+    frame_hashes: frame_hashes.as_mut(),
+  };
+  // Create an empty video frame outside the loop to reuse it.
+  let mut frame = ffmpeg::util::frame::video::Video::empty();
+  let stats = decode_packets(ictx, &mut video.decoder, video.index, &mut frame, config, outputs, pb)
+    .map_err(AnalyzeError::DecodeError)?;
+  if let Some(csv) = frames_csv {
+    csv.finish()?;
+  }
+  if let Some(hashes) = frame_hashes {
+    hashes.finish()?;
+  }
+  if let Some(thumbnails) = thumbnails {
+    let written = thumbnails.finish()?;
+    out!("Thumbnails written: {}", format_with_commas(written as i64));
+  }
+  pb.finish_with_message("Processing complete.");
+  if let Some(events) = &stats.progress_events {
+    events.done(stats.frame_count, stats.highest_dts.and_then(|dts| timestamp_to_signed_millis(dts, video.time_base)));
+  }
+  Ok(stats)
+}
+
+// The time of the last frame against the duration the media, the requested range or the track
+// fragments declare.
+struct DurationCheck {
+  // Timestamp of the first frame (or packet) and the highest DTS, which is the last frame time.
+  first_frame: Option<Timestamp>,
+  last_frame: Option<Timestamp>,
+  // The last frame time from the start of the stream or range.
+  last_frame_ms: Option<i64>,
+  // What declares the expected duration, for the report.
+  expected_label: &'static str,
+  difference_ms: Option<u64>,
+  passed: Option<bool>,
+  // Timestamp discontinuities of the video stream, across which the check isn't made.
+  discontinuities: usize,
+}
+
+impl DurationCheck {
+  fn new(stats: &DecodeStats, video: &Video, range: &Range, options: &AnalyzeOptions, boxes: &Mp4Boxes) -> Self {
+    // Get the media duration based on last frame.
+    let last_frame = stats.highest_dts.map(|h_dts| Timestamp::new(h_dts, video.time_base));
+    // The container duration counts from the start of the stream, so the last frame is measured from
+    // there too; streams often start at a non-zero time in MPEG-TS. A stream without a start time is
+    // measured from its first frame instead. With --start or --end, the last frame is measured from
+    // the start of the range and compared with the length of the range.
+    let first_frame = stats.first_frame_timestamp.or(stats.first_packet_timestamp)
+      .map(|ts| Timestamp::new(ts, video.time_base));
+    let origin = match first_frame {
+      Some(first) if video.start_time == ffmpeg::ffi::AV_NOPTS_VALUE && options.start_ms.is_none() => first.value,
+      _ => range.stream_start,
+    };
+    let start_ms = Timestamp::new(origin, video.time_base).signed_millis().unwrap_or(0);
+    let last_frame_ms = last_frame.and_then(|t| t.signed_millis()).map(|ms| ms - start_ms - range.start_ms as i64);
+    // The last frame and a container duration estimated from the first and last timestamps both
+    // span the jumps of a stream with discontinuities.
+    let discontinuities = stats.segments.as_ref().map_or(0, |segments| segments.discontinuities());
+    let difference_ms = range.window_ms.zip(last_frame_ms).filter(|_| discontinuities == 0)
+      .map(|(window, last)| (window as i64 - last).unsigned_abs());
+    let expected_label = match () {
+      _ if options.start_ms.is_some() || options.end_ms.is_some() => "requested range",
+      _ if boxes.fragmented.is_some() => "track fragment",
+      _ => "container",
+    };
+    DurationCheck {
+      first_frame,
+      last_frame,
+      last_frame_ms,
+      expected_label,
+      difference_ms,
+      passed: difference_ms.map(|difference| difference <= options.duration_tolerance_ms),
+      discontinuities,
+    }
+  }
+
+  // A DURATION_MISMATCH finding when the check failed.
+  fn finding(&self, window_ms: Option<u64>, tolerance_ms: u64) -> Option<Finding> {
+    (self.passed == Some(false)).then(|| Finding::new(&findings::DURATION_MISMATCH, format!(
+      "{} duration {} and last frame time {} differ by {}, more than the {} ms tolerance", self.expected_label,
+      format_optional_millis(window_ms), format_signed_millis(self.last_frame_ms.unwrap_or(0)),
+      millis_to_clock_format(self.difference_ms.unwrap_or(0)), tolerance_ms)))
+  }
+}
+
+// A video stream decoded over its range: what every report section after the decode pass reads.
+// Each section prints its part of the human-readable report, fills in its fields of the
+// AnalysisReport and adds its findings.
+struct Pass<'a> {
+  ictx: &'a ffmpeg::format::context::Input,
+  path: &'a str,
+  stdin: bool,
+  options: &'a AnalyzeOptions,
+  container: &'a Container,
+  selection: &'a Selection,
+  video: &'a Video,
+  range: &'a Range,
+  stats: &'a DecodeStats,
+  duration: &'a DurationCheck,
+}
+
+impl Pass<'_> {
+  fn following(&self) -> bool {
+    self.options.follow.is_some()
+  }
+
+  fn ranged(&self) -> bool {
+    self.options.start_ms.is_some() || self.options.end_ms.is_some()
+  }
+
+  // Video frames of the whole range; with sampling, the frames are counted among the packets.
+  fn video_frames(&self) -> i64 {
+    self.stats.sampling.as_ref().map_or(self.stats.frame_count, |counts| counts.video_packets)
+  }
+
+  // A sample only spans part of the frames: its frame count, duration and frame rate scaled up to
+  // every packet.
+  fn estimated(&self) -> Option<(Option<i64>, Option<u64>, Option<f64>)> {
+    let time_base = self.video.time_base;
+    self.stats.sampling.as_ref().map(|counts| {
+      let frames = counts.estimated_frames(self.stats.frame_count);
+      let duration_ms = self.stats.measured_duration_ms(time_base).and_then(|span| counts.estimated_duration_ms(span));
+      let fps = frames.zip(duration_ms).filter(|&(frames, ms)| frames > 1 && ms > 0)
+        .map(|(frames, ms)| (frames - 1) as f64 * 1000.0 / ms as f64);
+      (frames, duration_ms, fps)
+    })
+  }
+
+  // The basic file metadata and the figures calculated from the frames: key frames, frame count,
+  // picture types, decode errors, the duration check and the measured frame rate.
+  fn report_frames(&self, analysis: &mut AnalysisReport, findings: &mut Vec<Finding>) {
+    let (options, stats, decoder) = (self.options, self.stats, &self.video.decoder);
+    let (time_base, declared_rate) = (self.video.time_base, self.video.declared_rate);
+    let duration = self.duration;
+    out!("{}Basic file metadata - {}", BLUE_BOLD, RESET);
+    out!("ictx.duration: {}", describe_raw_duration(self.ictx.duration()));
+    out!("Media Duration: {}{}", format_optional_millis(self.range.media_duration_ms),
+      if self.container.boxes.fragmented.is_some() { " (from the track fragment runs)" } else { "" });
+    if self.ranged() {
+      out!("Analyzed range: {} to {}", millis_to_clock_format(self.range.start_ms),
+        options.end_ms.map_or_else(|| "the end".to_string(), millis_to_clock_format));
+      let first_decoded = stats.first_decoded_timestamp.map(|ts| Timestamp::new(ts, time_base));
+      out!("First decoded frame: {} ({} frames before the start decoded and not counted)",
+        timestamp::describe_optional(first_decoded, options.verbose), format_with_commas(stats.frames_before_start));
+    }
+    let start_offset = report_start_offset(self.ictx, self.video.start_time, duration.first_frame, stats, time_base,
+      options.verbose);
+    out!("Time base numerator: {}", time_base.numerator());
+    out!("Time base denominator: {}", time_base.denominator());
+    out!("Declared frame rate: {}", describe_rate(declared_rate));
+    if options.no_decode {
+      out!("Analysis mode: packets only; frame figures are derived from the video packets, not decode-verified");
+      out!("{}{}Calculated from the packets - {}", RESET, BLUE_BOLD, RESET);
+    } else {
+      match options.sampling {
+        Some(mode) => out!("Analysis mode: sampled decode of {}; packet figures are exact, frame figures sampled",
+          mode.describe()),
+        None => out!("Analysis mode: full decode"),
+      }
+      out!("Decode path: {}", self.video.hardware_device.as_deref()
+        .map_or_else(|| "software".to_string(), |device| format!("hardware ({})", device)));
+      out!("Pixel quality: {}", describe_pixel_quality(options.pixel_quality, self.video.lowres));
+      if let Some(counts) = &stats.sampling {
+        out!("{}{}Calculated from the packets - {}", RESET, BLUE_BOLD, RESET);
+        out!("Video packets: {}", format_with_commas(counts.video_packets));
+        out!("Key frame packets: {}", format_with_commas(counts.key_packets));
+        out!("{}{}Calculated from the sampled frames - {}", RESET, BLUE_BOLD, RESET);
+        out!("Packets decoded: {} of {}", format_with_commas(counts.sampled_packets),
+          format_with_commas(counts.video_packets));
+        if counts.reference_errors > 0 {
+          out!("Packets rejected for skipped references: {}", format_with_commas(counts.reference_errors as i64));
+        }
+      } else if options.all_video_streams {
+        out!("{}{}Calculated from the frames of stream #{} ({}, {}x{}) - {}", RESET, BLUE_BOLD, self.video.index,
+          decoder.id().name(), decoder.width(), decoder.height(), RESET);
+      } else {
+        out!("{}{}Calculated from the frames - {}", RESET, BLUE_BOLD, RESET);
+      }
+    }
+    out!("Last key frame id: {}", format_with_commas(stats.last_frame));
+    let last_keyframe = stats.last_keyframe_timestamp.map(|ts| Timestamp::new(ts, time_base));
+    out!("Last key frame at: {}", timestamp::describe_optional(last_keyframe, options.verbose));
+    // With sampling, the frames after the last key frame are counted among the packets.
+    let keyframe_tail = stats.keyframe_tail(time_base, self.video_frames());
+    out!("Last key frame distance from end: {}", keyframe_tail.map_or_else(|| "unknown".to_string(), |(time, frames)| {
+      format!("{:.1}s / {} frames", time.signed_millis().unwrap_or(0) as f64 / 1000.0, format_with_commas(frames))
+    }));
+    out!("Frames count: {}", format_with_commas(stats.frame_count));
+    if options.no_decode {
+      out!("Picture types: not available without decoding");
+    } else {
+      out!("Picture types: {}", describe_frame_types(&stats.frame_types));
+      findings.extend(report_decode_errors(stats, time_base, options.verbose));
+    }
+    out!("Last Frame Time: {}", timestamp::describe_optional(duration.last_frame, options.verbose));
+    match (duration.difference_ms, duration.passed) {
+      (Some(difference), Some(passed)) => {
+        out!("Duration difference: {} ({} {} vs last frame {})", millis_to_clock_format(difference),
+          duration.expected_label, format_optional_millis(self.range.window_ms),
+          format_signed_millis(duration.last_frame_ms.unwrap_or(0)));
+        out!("Duration check: {} (tolerance {} ms)", if passed { "PASS" } else { "FAIL" }, options.duration_tolerance_ms);
+      }
+      _ if duration.discontinuities > 0 => out!("Duration check: skipped, {} timestamp discontinuities in the video \
+        stream; the measured duration adds up its continuous segments", duration.discontinuities),
+      _ if self.following() && self.range.window_ms.is_none() =>
+        out!("Duration check: skipped, the container duration isn't final with --follow"),
+      _ if self.stdin && self.range.window_ms.is_none() => out!("Duration check: skipped, stdin carries no container duration"),
+      _ if self.container.boxes.fragmented.as_ref().is_some_and(|fragmented| fragmented.init_only()) =>
+        out!("Duration check: skipped, the file is an init segment without samples"),
+      _ => out!("Duration check: not possible without both a {} duration and a last frame time", duration.expected_label),
+    }
+    out!("Measured Duration: {}", timestamp::describe_optional(stats.measured_duration(time_base), options.verbose));
+    let estimated = self.estimated();
+    match estimated {
+      Some((frames, duration_ms, fps)) => {
+        out!("Estimated frames count: {} (estimated)", frames.map_or_else(|| "unknown".to_string(), format_with_commas));
+        out!("Estimated Duration: {} (estimated)", format_optional_millis(duration_ms));
+        out!("Measured frame rate: {} (estimated)",
+          fps.map_or_else(|| "unknown".to_string(), |fps| format!("{:.3} fps", fps)));
+      }
+      None => out!("Measured frame rate: {}", stats.measured_fps(time_base)
+        .map_or_else(|| "unknown".to_string(), |fps| format!("{:.3} fps", fps))),
+    }
+
+    if rate_to_fps(declared_rate).is_none() {
+      findings.push(Finding::new(&findings::UNKNOWN_FRAME_RATE,
+        format!("declared frame rate is {}", describe_rate(declared_rate))));
+    }
+    findings.extend(duration.finding(self.range.window_ms, options.duration_tolerance_ms));
+    if let (Some(max), Some(tail)) = (options.max_keyframe_tail_s, keyframe_tail) {
+      findings.extend(cadence::keyframe_tail_finding(tail, last_keyframe, max));
+    }
+    // After --start, the first frame is the range's rather than the file's.
+    if options.start_ms.is_none() {
+      findings.extend(start_offset.mismatch(options.duration_tolerance_ms, options.verbose));
+    }
+    if self.range.media_duration_ms.is_none() && !self.following() && self.container.boxes.fragmented.is_none() {
+      findings.push(Finding::new(&findings::NO_CONTAINER_DURATION, "container declares no duration"));
+    }
+
+    analysis.time_base = Some(report::TimeBase { numerator: time_base.numerator(), denominator: time_base.denominator() });
+    analysis.decoded_duration_ms = stats.measured_duration_ms(time_base);
+    analysis.decoded_duration_raw = stats.measured_duration(time_base);
+    analysis.duration_difference_ms = duration.difference_ms;
+    analysis.duration_check_passed = duration.passed;
+    analysis.container_start_time_ms = start_offset.container.and_then(|ts| ts.signed_millis());
+    analysis.stream_start_time_ms = start_offset.stream.and_then(|ts| ts.signed_millis());
+    analysis.first_packet_ms = start_offset.first_packet.and_then(|ts| ts.signed_millis());
+    analysis.start_offset_ms = start_offset.first_frame.and_then(|ts| ts.signed_millis());
+    analysis.container_start_time_raw = start_offset.container;
+    analysis.stream_start_time_raw = start_offset.stream;
+    analysis.first_packet_raw = start_offset.first_packet;
+    analysis.start_offset_raw = start_offset.first_frame;
+    analysis.sampling = options.sampling.zip(stats.sampling.as_ref()).map(|(mode, counts)| report::SamplingReport {
+      mode: mode.describe(),
+      video_packets: counts.video_packets,
+      key_packets: counts.key_packets,
+      sampled_packets: counts.sampled_packets,
+      estimated_frame_count: estimated.and_then(|(frames, _, _)| frames),
+      estimated_duration_ms: estimated.and_then(|(_, duration_ms, _)| duration_ms),
+    });
+    analysis.analyzed_range = self.ranged().then(|| report::RangeReport {
+      start_ms: self.range.start_ms,
+      end_ms: options.end_ms,
+      first_decoded_frame_ms: stats.first_decoded_timestamp.and_then(|ts| timestamp_to_signed_millis(ts, time_base)),
+      first_decoded_frame_raw: stats.first_decoded_timestamp.map(|ts| Timestamp::new(ts, time_base)),
+      frames_before_start: stats.frames_before_start,
+    });
+    analysis.frame_source = Some(if options.no_decode { "packets" } else { "decode" });
+    analysis.decode_path = (!options.no_decode)
+      .then(|| self.video.hardware_device.clone().unwrap_or_else(|| "software".to_string()));
+    analysis.frame_count = Some(stats.frame_count);
+    analysis.keyframe_count = Some(stats.keyframe_count);
+    analysis.picture_types = (!options.no_decode).then(|| report::PictureTypes {
+      i: stats.frame_types[0],
+      p: stats.frame_types[1],
+      b: stats.frame_types[2],
+      other: stats.frame_types[3],
+    });
+    analysis.last_keyframe_index = Some(stats.last_frame);
+    analysis.last_keyframe_ms = last_keyframe.and_then(|ts| ts.signed_millis());
+    analysis.keyframe_tail_ms = keyframe_tail.and_then(|(time, _)| time.signed_millis());
+    analysis.last_keyframe_raw = last_keyframe;
+    analysis.keyframe_tail_raw = keyframe_tail.map(|(time, _)| time);
+    analysis.keyframe_tail_frames = keyframe_tail.map(|(_, frames)| frames);
+    analysis.decode_errors = (!options.no_decode).then_some(stats.decode_errors);
+    analysis.decode_error_kinds = (!options.no_decode).then(|| stats.decode_error_kinds.clone());
+    analysis.first_decode_error_ms = stats.first_error_timestamp.and_then(|ts| Timestamp::new(ts, time_base).signed_millis());
+    analysis.last_decode_error_ms = stats.last_error_timestamp.and_then(|ts| Timestamp::new(ts, time_base).signed_millis());
+    analysis.first_decode_error_raw = stats.first_error_timestamp.map(|ts| Timestamp::new(ts, time_base));
+    analysis.last_decode_error_raw = stats.last_error_timestamp.map(|ts| Timestamp::new(ts, time_base));
+    analysis.highest_dts = stats.highest_dts;
+    analysis.lowest_dts = stats.lowest_dts;
+    analysis.lowest_pts = stats.lowest_pts;
+    analysis.negative_pts_packets = stats.negative_pts_packets;
+    analysis.negative_pts_frames = stats.negative_pts_frames;
+    analysis.declared_fps = rate_to_fps(declared_rate);
+    analysis.measured_fps = stats.measured_fps(time_base);
+  }
+
+  // The modal frame rate of the frame intervals, the other video streams and the variants.
+  fn report_frame_rate(&self, analysis: &mut AnalysisReport, findings: &mut Vec<Finding>) {
+    let (options, stats, time_base) = (self.options, self.stats, self.video.time_base);
+    // Intervals between sampled frames say nothing about the frame rate or dropped frames.
+    let rate_estimate = fps::estimate(&stats.frame_timestamps, time_base,
+      options.interval_tolerance_percent as f64 / 100.0).filter(|_| stats.sampling.is_none());
+    findings.extend(fps::report(rate_estimate.as_ref(), stats.sampling.is_some(), options.interval_tolerance_percent,
+      self.video.declared_rate, stats.measured_fps(time_base)));
+    if options.all_video_streams {
+      video_streams::report(&stats.other_video, &stats.streams.highest_dts, options.verbose);
+    }
+    let variant_list = &self.selection.variants;
+    let variant_figures = variant_figures(variant_list, stats, self.video.index, time_base);
+    if !variant_list.is_empty() {
+      variants::report(variant_list, &variant_figures, self.video.index);
+    }
+
+    analysis.modal_fps = rate_estimate.as_ref().map(|rate| rate.modal_fps);
+    analysis.frame_rate_mode = rate_estimate.as_ref().map(|rate| fps::describe_mode(rate.mode));
+    analysis.modal_frame_interval_ms = rate_estimate.as_ref().map(|rate| rate.modal_interval_ms);
+    analysis.frame_interval_stddev_ms = rate_estimate.as_ref().map(|rate| rate.interval_stddev_ms);
+    analysis.irregular_frame_intervals = rate_estimate.as_ref().map(|rate| rate.irregular_intervals as u64);
+    analysis.variants = (!variant_list.is_empty())
+      .then(|| variant_list.iter().zip(&variant_figures).map(|(variant, figures)| variants::to_report(variant, figures)).collect());
+    analysis.video_streams = stats.other_video.iter()
+      .map(|stream| video_streams::to_report(stream, stats.streams.highest_dts.get(stream.index).copied().flatten()))
+      .collect();
+  }
+
+  // The stream table, the attachments and the MP4 box layout.
+  fn report_layout(&self, analysis: &mut AnalysisReport, findings: &mut Vec<Finding>) {
+    // A range or a file still being written only has some of each stream's packets.
+    (analysis.streams, analysis.container_bit_rate) = stream_table::report(self.ictx, &self.stats.streams,
+      !self.following() && !self.ranged());
+    attachments::report(&self.container.attachments);
+    findings.extend(faststart::report(self.container.boxes.layout.as_ref()));
+    findings.extend(self.container.boxes.fragmented.as_ref().and_then(fragments::report));
+  }
+
+  // How long the analysis took, how fast it went, the peak memory and the decoder threads.
+  fn report_performance(&self, elapsed: std::time::Duration, analysis: &mut AnalysisReport) {
+    let code_execution_time_ms = elapsed.as_millis() as u64;
+    out!("Code execution time: {}", millis_to_clock_format(code_execution_time_ms));
+    // Frames (or packets, when they weren't all decoded) per wall-clock second, and media time per
+    // wall-clock time. Runs too short to time leave both unknown.
+    let counted_packets = self.options.no_decode || self.stats.sampling.is_some();
+    let throughput_media_ms = match self.estimated() {
+      Some((_, duration_ms, _)) => duration_ms,
+      None => self.stats.measured_duration_ms(self.video.time_base),
+    };
+    let elapsed_seconds = elapsed.as_secs_f64();
+    let per_second = (elapsed_seconds > 0.0).then(|| self.video_frames() as f64 / elapsed_seconds);
+    let realtime_factor = throughput_media_ms.filter(|_| elapsed_seconds > 0.0)
+      .map(|ms| ms as f64 / 1000.0 / elapsed_seconds);
+    let unit = if counted_packets { "packets/s" } else { "fps" };
+    match per_second {
+      Some(per_second) => out!("Throughput: {} {}, {}", format_with_commas(per_second.round() as i64), unit,
+        realtime_factor.map_or_else(|| "realtime factor unknown".to_string(),
+          |factor| format!("{:.1}\u{d7} realtime", factor))),
+      None => out!("Throughput: unknown, the run was too short to time"),
+    }
+    analysis.execution_time_ms = code_execution_time_ms;
+    analysis.peak_memory_bytes = report_peak_memory();
+    // What the opened decoder runs with, which FFmpeg may lower for codecs without frame threading.
+    let decoder_threads = self.video.decoder.threading().count;
+    out!("Decoder threads: {}", decoder_threads);
+    analysis.decoder_threads = Some(decoder_threads);
+    analysis.throughput = per_second.map(|per_second| report::ThroughputReport {
+      unit: if counted_packets { "packets" } else { "frames" },
+      per_second,
+      realtime_factor,
+    });
+  }
+
+  // The timestamps, the preview tracks and what the decoded pictures carry: sample range, field
+  // order, pixel format, captions and color.
+  fn report_picture(&self, analysis: &mut AnalysisReport, findings: &mut Vec<Finding>) {
+    let (options, stats, video, time_base) = (self.options, self.stats, self.video, self.video.time_base);
+    report_timestamps(self.ictx, self.path, &video.decoder, video.index, time_base, stats, options.verbose);
+    streams::report_previews(&self.selection.tracks, video.index);
+    let range_finding = pixels::report(&stats.range_check, time_base);
+    let declared_field_order = interlace::declared(&video.parameters);
+    findings.extend(interlace::report(&stats.interlace, declared_field_order));
+    if !options.no_decode {
+      pixel_format::report(&stats.pixel_format, video.opened_format, time_base, options.verbose);
+    }
+    let caption_streams = captions::streams(self.ictx);
+    if !options.no_decode {
+      captions::report(&stats.captions, &caption_streams, stats.frame_count, time_base, options.verbose);
+    }
+    let declared_color = colorimetry::Description::from_parameters(&video.parameters);
+    findings.extend(colorimetry::report(&stats.color, &declared_color));
+    findings.extend(range_finding);
+
+    analysis.interlacing = interlace::to_report(&stats.interlace, declared_field_order);
+    analysis.captions = (!options.no_decode).then(|| captions::to_report(&stats.captions, &caption_streams, time_base));
+    analysis.pixel_format = (!options.no_decode)
+      .then(|| pixel_format::to_report(&stats.pixel_format, video.opened_format, time_base));
+    analysis.color = colorimetry::to_report(&stats.color, &declared_color);
+  }
+
+  // Bit rate over time, frame rate stability, packet sizes and the GOP structure, with the files
+  // they are written to.
+  fn report_timing(&self, analysis: &mut AnalysisReport, findings: &mut Vec<Finding>) -> Result<(), AnalyzeError> {
+    let (options, stats, time_base) = (self.options, self.stats, self.video.time_base);
+    let bitrate = bitrate::report(&stats.bitrate);
+    // A sample leaves out the frames between those it decodes.
+    let fps_series = stats.sampling.is_none().then(|| fps_stability::Series::new(&stats.frame_timestamps, time_base));
+    let fps_stability = fps_series.as_ref().and_then(fps_stability::report);
+    packet_sizes::report(&stats.packet_sizes, options.top_packets, time_base, options.verbose);
+    // Sampling leaves out the frames between those it decodes.
+    let declared_reorder_delay = unsafe { (*self.video.parameters.as_ptr()).video_delay };
+    let gop_structure = if options.no_decode || stats.sampling.is_some() {
+      None
+    } else {
+      gop_structure::analyze(&stats.gop_structure, time_base, declared_reorder_delay)
+    };
+    if let Some(structure) = &gop_structure {
+      gop_structure::report(structure, time_base, options.verbose);
+      findings.extend(gop_structure::report_reordering(structure));
+      if let Some(dump_path) = &options.gop_dump {
+        structure.write_dump(dump_path)?;
+      }
+    }
+    if let Some(csv_path) = &options.bitrate_csv {
+      stats.bitrate.write_csv(csv_path)?;
+    }
+    if let (Some(csv_path), Some(series)) = (&options.fps_csv, &fps_series) {
+      series.write_csv(csv_path)?;
+    }
+
+    analysis.packet_sizes = packet_sizes::to_report(&stats.packet_sizes, time_base);
+    analysis.bitrate = bitrate.as_ref().map(report::BitrateReport::from);
+    analysis.fps_stability = fps_stability.as_ref().map(report::FpsStabilityReport::from);
+    analysis.gop_structure = gop_structure;
+    Ok(())
+  }
+
+  // The other streams against the video: audio, codec profiles, subtitles, data streams, the
+  // transport stream, timecode tracks, tags and chapters.
+  fn report_streams(&self, analysis: &mut AnalysisReport, findings: &mut Vec<Finding>) {
+    let (options, stats, video, time_base) = (self.options, self.stats, self.video, self.video.time_base);
+    let video_span_ms = stats.measured_duration_ms(time_base);
+    findings.extend(audio::report(&stats.audio, &stats.streams.highest_dts, video_span_ms,
+      options.av_drift_tolerance_ms, options.verbose));
+    // After --start, both start where the seek landed.
+    let video_start_ms = self.duration.first_frame.and_then(|ts| ts.signed_millis()).filter(|_| options.start_ms.is_none());
+    findings.extend(audio::report_start(&stats.audio, video_start_ms, options.av_offset_tolerance_ms));
+    let audio_profiles: Vec<profile::Declared> = self.ictx.streams()
+      .filter(|stream| stream.parameters().medium() == Type::Audio)
+      .map(|stream| profile::Declared::of_stream(&stream)).collect();
+    let (width, height) = video.declared_size();
+    let profile_violations = profile::report(&profile::Video {
+      declared: &video.profile,
+      width,
+      height,
+      // A sample's frame count says nothing about the frame rate.
+      fps: stats.measured_fps(time_base).filter(|_| stats.sampling.is_none()).or(rate_to_fps(video.declared_rate)),
+    }, &audio_profiles, options.required_profile.as_ref());
+    if let Some(requirement) = &options.required_profile {
+      findings.extend(profile::finding(requirement, &profile_violations));
+    }
+    subtitles::report(&stats.subtitles);
+    data_streams::report(&stats.data_streams);
+    let transport = transport::read(self.ictx, self.path, self.following());
+    if let Some(transport) = &transport {
+      findings.extend(transport::report(transport, stats.segments.as_ref(), time_base));
+    }
+    let timecode_tracks = tmcd::tracks(self.ictx);
+    tmcd::report(&timecode_tracks, self.video_frames());
+    metadata::report(&self.container.tags);
+    findings.extend(chapters::report(&self.container.chapters, chapters::media_end_ms(self.ictx)));
+
+    analysis.av_drift_check_passed = audio::drift_passed(&stats.audio, video_span_ms, options.av_drift_tolerance_ms);
+    analysis.audio_streams = stats.audio.iter().map(|stream| audio::to_report(stream,
+      stats.streams.highest_dts.get(stream.index).copied().flatten(), video_span_ms, video_start_ms))
+      .collect();
+    analysis.subtitle_streams = stats.subtitles.iter().map(subtitles::to_report).collect();
+    analysis.data_streams = stats.data_streams.iter().map(report::DataStreamReport::from).collect();
+    analysis.profiles = std::iter::once(&video.profile).chain(&audio_profiles).map(report::ProfileReport::from).collect();
+    analysis.profile_check_passed = options.required_profile.as_ref().map(|_| profile_violations.is_empty());
+    analysis.transport = transport.as_ref().map(|transport| transport::to_report(transport, stats.segments.as_ref(), time_base));
+    analysis.timecode_tracks = timecode_tracks.iter().map(|track| tmcd::to_report(track, self.video_frames())).collect();
+  }
+
+  // The size of the picture: coded and display dimensions, resolution changes, rotation and aspect
+  // ratio.
+  fn report_geometry(&self, analysis: &mut AnalysisReport, findings: &mut Vec<Finding>) {
+    let (options, stats, video) = (self.options, self.stats, self.video);
+    findings.extend(report_dimensions(self.ictx, self.path, &video.decoder, &video.parameters, video.index, stats));
+    // Rotated from the decoded frames, or from the declared size without any.
+    let unrotated_size = stats.first_frame_size.or_else(|| Some((video.decoder.width(), video.decoder.height())))
+      .filter(|&(width, height)| width > 0 && height > 0);
+    if !options.no_decode {
+      findings.extend(resolution::report(&stats.resolution, video.declared_size(), video.time_base, options.verbose));
+    }
+    findings.extend(rotation::report(&video.rotation, unrotated_size));
+    let display_size = unrotated_size.map(|size| rotation::display_size(&video.rotation.effective(), size));
+    let aspect = aspect::AspectRatio::new(&video.parameters, stats.first_frame_sar, unrotated_size.unwrap_or((0, 0)));
+    findings.extend(aspect::report(&aspect));
+
+    analysis.resolution = (!options.no_decode).then(|| resolution::to_report(&stats.resolution, video.time_base));
+    analysis.aspect_ratio = Some(aspect::to_report(&aspect));
+    analysis.rotation = Some(rotation::to_report(&video.rotation, display_size));
+  }
+
+  // The sequence of packets and frames: interleaving, timestamp order, scene changes, timecodes,
+  // frozen pictures, dropped frames and the key frames.
+  fn report_sequence(&self, analysis: &mut AnalysisReport, findings: &mut Vec<Finding>) -> Result<(), AnalyzeError> {
+    let (options, stats, time_base) = (self.options, self.stats, self.video.time_base);
+    findings.extend(interleave::report(&stats.interleave, self.ictx, options.interleave_limits));
+    findings.extend(monotonic::report(&stats.monotonic, time_base));
+    if let Some(scenes) = &stats.scenes {
+      scenes::report(scenes, time_base);
+      if let Some(scenes_path) = options.scenes.as_ref().and_then(|settings| settings.csv.as_ref()) {
+        scenes.write_csv(scenes_path, time_base)?;
+      }
+    }
+    if let Some(timecodes) = &stats.timecodes {
+      findings.extend(timecode::report(timecodes, stats.frame_count, time_base, options.verbose));
+    }
+    if let (Some(freeze), Some(min_ms)) = (&stats.freeze, options.detect_freeze_ms) {
+      findings.extend(freeze::report(freeze, time_base, min_ms));
+    }
+    let frame_gaps = gaps::detect(&stats.frame_timestamps, options.gap_factor).filter(|_| stats.sampling.is_none());
+    if let Some(summary) = &frame_gaps {
+      findings.extend(gaps::report(summary, time_base, options.gap_factor));
+    }
+    findings.extend(cadence::report(stats, time_base, self.ictx.format().name(),
+      is_intra_only(self.video.decoder.id()), options.delivery_target));
+    if options.list_keyframes {
+      cadence::list(&stats.keyframes, time_base, options.verbose);
+    }
+    random_access::report(&stats.random_access, time_base);
+
+    analysis.timecodes = stats.timecodes.as_ref().map(|timecodes| timecode::to_report(timecodes, time_base));
+    analysis.frame_gaps = frame_gaps.map(|summary| gaps::to_report(&summary, time_base));
+    analysis.freeze_segments = stats.freeze.as_ref().map(|freeze| freeze::to_report(freeze, time_base));
+    analysis.scene_changes = stats.scenes.as_ref().map(|scenes| scenes::to_report(scenes, time_base));
+    analysis.gop = cadence::gop_stats(&stats.keyframes, &stats.keyframe_timestamps, time_base);
+    analysis.random_access = random_access::to_report(&stats.random_access, time_base);
+    analysis.keyframes = options.list_keyframes.then(|| stats.keyframes.iter().map(|&(index, timestamp)| report::KeyframeReport {
+      index,
+      timestamp_ms: timestamp.and_then(|ts| Timestamp::new(ts, time_base).signed_millis()),
+      timestamp_raw: timestamp.map(|ts| Timestamp::new(ts, time_base)),
+    }).collect());
+    Ok(())
+  }
+
+  // Whether the counts agree with each other, and the read errors recovered from.
+  fn check_consistency(&self, findings: &mut Vec<Finding>) {
+    let (stats, time_base) = (self.stats, self.video.time_base);
+    // With sampling, the frame figures to check against the key frames are the packets'.
+    findings.extend(consistency::check(&consistency::Figures {
+      frame_count: self.video_frames(),
+      keyframe_count: stats.keyframe_count,
+      last_keyframe_index: stats.last_frame,
+      frame_types: (!self.options.no_decode && stats.sampling.is_none()).then_some(stats.frame_types),
+      measured_duration_ms: stats.measured_duration_ms(time_base),
+      container_duration_ms: container_duration(self.ictx).and_then(|d| d.millis()),
+      stream_bytes: stats.streams.bytes.clone(),
+      file_size: input_size(self.ictx),
+    }));
+    findings.extend(io_retry::findings(&stats.io_retries, time_base, self.options.verbose));
+  }
+
+  // What the one-paragraph verdict summarizes besides the findings.
+  fn summary(&self) -> verdict::Summary {
+    let (stats, time_base) = (self.stats, self.video.time_base);
+    verdict::Summary {
+      duration_ms: container_duration(self.ictx).and_then(|d| d.millis()).or(stats.measured_duration_ms(time_base)),
+      height: self.video.declared_size().1,
+      fps: rate_to_fps(self.video.declared_rate).or(stats.measured_fps(time_base)),
+      codec: self.video.decoder.id().name().to_string(),
+      bit_rate: self.ictx.bit_rate(),
+      audio: verdict::describe_audio(self.ictx),
+      keyframe_interval_s: cadence::intervals_ms(&stats.keyframe_timestamps, time_base)
+        .filter(|intervals| !intervals.is_empty())
+        .map(|intervals| intervals.iter().sum::<u64>() as f64 / intervals.len() as f64 / 1000.0),
+    }
+  }
+}
+
+// Analyze one file like `analyze`, but return the human-readable report as text instead of
+// printing it, so analyses running on several threads can be printed in order.
+pub fn analyze_captured(path: &Path, options: &AnalyzeOptions) -> (Result<AnalysisReport, AnalyzeError>, String) {
+  CAPTURE.with(|capture| *capture.borrow_mut() = Some(String::new()));
+  let result = analyze(path, options);
+  let text = CAPTURE.with(|capture| capture.borrow_mut().take()).unwrap_or_default();
+  (result, text)
+}
+
+// Analyze one file or URL. The report holds the figures of the analysis; with
+// AnalyzeOptions::print_report, the human-readable report is printed on stdout as well.
+pub fn analyze(path: &Path, options: &AnalyzeOptions) -> Result<AnalysisReport, AnalyzeError> {
+  analyze_input(path, options).map_err(|error| network::classify(error, options.network_timeout_seconds))
+}
+
+fn analyze_input(path: &Path, options: &AnalyzeOptions) -> Result<AnalysisReport, AnalyzeError> {
+  ffmpeg::init()?;
+  QUIET.with(|quiet| quiet.set(!options.print_report));
+
+  // Capture the current time to measure duration later.
+  let start = Instant::now();
+
+  let path = path.to_str()
+    .ok_or_else(|| AnalyzeError::InvalidArgument(format!("{} is not a valid UTF-8 path", path.display())))?
+    .to_string();
+  let threads_number = thread_count(options.threads_number_input);
+
+  // Open the input video file, URL or stdin. A pipe is read forward only.
+  let stdin = pipe::is_stdin(&path);
+  let mut ictx = if stdin {
+    pipe::check_options(options)?;
+    pipe::open()?
+  } else {
+    network::open(&path, options.network_timeout_seconds).map_err(AnalyzeError::OpenError)?
+  };
+
+  // A container without any stream at all is the only hard failure.
+  if ictx.nb_streams() == 0 {
+    return Err(AnalyzeError::NoStreamsError);
+  }
+  let container = Container::read(&ictx, &path, options.remux_target);
+
+  // indicatif draws on stderr, so the progress never ends up in the --json output. Nothing is
+  // drawn when stdout isn't a terminal, since the report is then going to a file or a pipe.
+  let pb = progress::spinner(options.show_progress && std::io::stdout().is_terminal());
+
+  let mut selection = Selection::choose(&mut ictx, options)?;
+  // Without a video stream, report what the file does contain unless the caller insisted on video.
+  let mut video = match selection.index.and_then(|index| ictx.stream(index)) {
+    Some(input) => Video::open(&input, options, threads_number)?,
+    None if options.require_video => return Err(AnalyzeError::NoVideoStreamError),
+    None => {
+      let mut analysis = container.to_report(&ictx, &path);
+      let findings = report_without_video(&mut ictx, &container, &mut analysis, options.verbose, &pb);
+      (analysis.execution_time_ms, analysis.peak_memory_bytes) = report_resources(start);
+      analysis.findings = findings.iter().map(report::FindingReport::from).collect();
+      // The page has the summary and findings only, its charts being of the video.
+      if options.html {
+        analysis.html = Some(html::render(&analysis, &bitrate::Tracker::default(), &packet_sizes::Tracker::default(), &[],
+          Rational::new(1, 1)));
+      }
+      return Ok(analysis);
+    }
+  };
+  // State which stream is analyzed, since the choice is implicit without --stream.
+  if let Some(track) = selection.tracks.iter().find(|t| t.index == video.index) {
+    out!("Analyzed stream: {}", streams::describe_selection(track, selection.requested.is_some(), selection.tracks.len()));
+  }
+  let mut analysis = AnalysisReport {
+    video_stream_index: Some(video.index),
+    codec: Some(video.decoder.id().name().to_string()),
+    ..container.to_report(&ictx, &path)
+  };
+
+  if let Some(window_mb) = options.head_tail_mb {
+    let (head_tail, findings) = analyze_head_tail(&mut ictx, &mut video, window_mb, options, &pb)
+      .map_err(AnalyzeError::DecodeError)?;
+    metadata::report(&container.tags);
+    (analysis.execution_time_ms, analysis.peak_memory_bytes) = report_resources(start);
+    out!("Decoder threads: {}", video.decoder.threading().count);
+    // The frame figures of the report are for the whole file; the windows have their own.
+    analysis.head_tail = Some(head_tail);
+    analysis.findings = findings.iter().map(report::FindingReport::from).collect();
+    analysis.decoder_threads = Some(video.decoder.threading().count);
+    if options.html {
+      analysis.html = Some(html::render(&analysis, &bitrate::Tracker::default(), &packet_sizes::Tracker::default(), &[],
+        video.time_base));
+    }
+    return Ok(analysis);
+  }
+
+  let range = Range::new(&ictx, &video, options, &container.boxes);
+  // Seeking lands on the key frame at or before the start; ictx.seek takes AV_TIME_BASE units.
+  if let Some(ts) = range.start_timestamp {
+    let target = ts.rescale(video.time_base, ffmpeg::rescale::TIME_BASE);
+    ictx.seek(target, ..target)?;
+  }
+  let stats = decode_range(&mut ictx, &mut video, options, &range, selection.variants.len(), &pb)?;
+  // Calculate the time taken to process the video.
+  let elapsed = start.elapsed();
+
+  let duration = DurationCheck::new(&stats, &video, &range, options, &container.boxes);
+  let mut findings: Vec<Finding> = selection.finding.take().into_iter().collect();
+  let pass = Pass {
+    ictx: &ictx,
+    path: &path,
+    stdin,
+    options,
+    container: &container,
+    selection: &selection,
+    video: &video,
+    range: &range,
+    stats: &stats,
+    duration: &duration,
+  };
+  pass.report_frames(&mut analysis, &mut findings);
+  pass.report_frame_rate(&mut analysis, &mut findings);
+  pass.report_layout(&mut analysis, &mut findings);
+  pass.report_performance(elapsed, &mut analysis);
+  pass.report_picture(&mut analysis, &mut findings);
+  pass.report_timing(&mut analysis, &mut findings)?;
+  pass.report_streams(&mut analysis, &mut findings);
+  pass.report_geometry(&mut analysis, &mut findings);
+  pass.report_sequence(&mut analysis, &mut findings)?;
+  pass.check_consistency(&mut findings);
+  let summary = options.verdict.then(|| pass.summary());
+
+  // Retry the damaged regions with a lenient decoder to measure how salvageable the file is.
+  let time_base = video.time_base;
+  if options.recovery_pass && stats.decode_errors > 0 {
+    let regions = recovery::run(&mut ictx, video.parameters.clone(), video.threading, video.index, time_base, &stats, &pb)?;
+    pb.finish_and_clear();
+    recovery::report(&regions, stats.frame_count, time_base, options.verbose);
+  }
+  print_findings(&mut findings);
+  if let Some(settings) = &options.extract_problems {
+    extract::report(&extract::extract_problems(&path, &findings, video.index, settings));
+  }
+  let violations = options.strict.then(|| strict::collect(&strict::Checks {
+    decode_errors: stats.decode_errors,
+    first_decode_error: stats.first_error_timestamp,
+    timestamps: &stats.monotonic,
+    duration_mismatch_ms: duration.difference_ms.filter(|_| duration.passed == Some(false)),
+  }, time_base, &options.strict_allow));
+  if let Some(violations) = &violations {
+    strict::report(violations, options.verbose);
+  }
+  let verdict = summary.map(|summary| verdict::render(&summary, &findings, options.verdict_length));
+  if let Some(verdict) = &verdict {
+    out!("{}{}Verdict - {}", RESET, BLUE_BOLD, RESET);
+    out!("{}", verdict);
+  }
+  analysis.findings = findings.iter().map(report::FindingReport::from).collect();
+  analysis.strict_violations = violations.map(|violations| violations.iter().map(report::ViolationReport::from).collect());
+  analysis.verdict = verdict;
+  if options.html {
+    analysis.html = Some(html::render(&analysis, &stats.bitrate, &stats.packet_sizes, &stats.keyframe_timestamps, time_base));
+  }
+  Ok(analysis)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn degenerate_time_bases_have_no_millis() {
+    assert_eq!(timestamp_to_millis(90_000, Rational::new(1, 90_000)), Some(1000));
+    assert_eq!(timestamp_to_millis(90_000, Rational::new(0, 0)), None);
+    assert_eq!(timestamp_to_millis(90_000, Rational::new(1, 0)), None);
+    assert_eq!(timestamp_to_millis(90_000, Rational::new(0, 1)), None);
+  }
+
+  #[test]
+  fn zero_frame_rates_are_unknown() {
+    assert_eq!(rate_to_fps(Rational::new(30000, 1001)), Some(30000.0 / 1001.0));
+    assert_eq!(rate_to_fps(Rational::new(0, 0)), None);
+    assert_eq!(rate_to_fps(Rational::new(0, 1)), None);
+    assert_eq!(describe_rate(Rational::new(30000, 1001)), "30000/1001 (29.970 fps)");
+    assert_eq!(describe_rate(Rational::new(0, 0)), "unknown (0/0)");
+  }
+
+  #[test]
+  fn missing_container_duration() {
+    assert_eq!(describe_raw_duration(ffmpeg::ffi::AV_NOPTS_VALUE), "unknown");
+    assert_eq!(describe_raw_duration(0), "unknown");
+    assert_eq!(describe_raw_duration(10_000_000), "10000000");
+    assert_eq!(format_optional_millis(None), "unknown");
+  }
+
+  #[test]
+  fn measured_fps_needs_a_time_span() {
+    let time_base = Rational::new(1, 25);
+    let mut stats = DecodeStats { frame_count: 250, first_frame_timestamp: Some(0), last_frame_timestamp: Some(249),
+      ..DecodeStats::default() };
+    assert_eq!(stats.measured_duration_ms(time_base), Some(9960));
+    assert!((stats.measured_fps(time_base).unwrap() - 25.0).abs() < 1e-9);
+    assert_eq!(stats.measured_fps(Rational::new(0, 0)), None);
+    stats.frame_count = 1;
+    assert_eq!(stats.measured_fps(time_base), None);
+    stats = DecodeStats { frame_count: 10, first_frame_timestamp: Some(5), last_frame_timestamp: Some(5),
+      ..DecodeStats::default() };
+    assert_eq!(stats.measured_fps(time_base), None);
+    assert_eq!(DecodeStats::default().measured_fps(time_base), None);
+  }
+
+  #[test]
+  fn head_tail_windows() {
+    assert_eq!(window_bytes(2), 2 * 1024 * 1024);
+    assert_eq!(window_bytes(u64::MAX), i64::MAX);
+    assert_eq!(window_bytes(u64::MAX / 1024).saturating_mul(2), i64::MAX);
+    assert_eq!(relative_seek_target(10_000_000, 750, 1000), Some(7_500_000));
+    assert_eq!(relative_seek_target(i64::MAX, i64::MAX - 1, i64::MAX), Some(i64::MAX - 1));
+    assert_eq!(relative_seek_target(ffmpeg::ffi::AV_NOPTS_VALUE, 750, 1000), None);
+    assert_eq!(relative_seek_target(0, 750, 1000), None);
+  }
+
+  #[test]
+  fn key_frame_cadence() {
+    let time_base = Rational::new(1, 1000);
+    assert_eq!(keyframe_cadence(&[0, 2000, 4000, 5000], time_base), "avg 1.667s (min 1.000s, max 2.000s)");
+    assert_eq!(keyframe_cadence(&[0], time_base), "not measurable (fewer than 2 key frames)");
+    assert_eq!(keyframe_cadence(&[0, 2000], Rational::new(0, 0)), "not measurable (no usable time base)");
+  }
+
+  #[test]
+  fn declared_start_against_the_first_frame() {
+    let time_base = Rational::new(1, 90000);
+    let offset = StartOffset {
+      container: Some(Timestamp::from_av_time_base(1_400_000)),
+      stream: None,
+      first_packet: None,
+      first_frame: Some(Timestamp::new(180_000, time_base)),
+    };
+    assert!(offset.mismatch(1000, false).is_none());
+    let finding = offset.mismatch(100, false).unwrap();
+    assert_eq!(finding.definition.code, "START_TIME_MISMATCH");
+    assert!(finding.message.starts_with("container declares"), "{}", finding.message);
+    let later = StartOffset { stream: Some(Timestamp::new(0, time_base)), ..offset };
+    assert!(later.mismatch(100, false).unwrap().message.starts_with("video stream declares"));
+    assert!(StartOffset { first_frame: None, ..later }.mismatch(0, false).is_none());
+  }
+
+  #[test]
+  fn frames_before_zero_are_counted() {
+    let mut stats = DecodeStats::default();
+    for (key, ts) in [(true, Some(-2002)), (false, Some(-1001)), (false, None), (false, Some(0)), (true, Some(1001))] {
+      stats.note_frame(key, ts, false);
+      stats.frame_count += 1;
+    }
+    assert_eq!(stats.negative_pts_frames, 2);
+    assert_eq!((stats.first_frame_timestamp, stats.last_frame_timestamp), (Some(-2002), Some(1001)));
+    assert_eq!(stats.measured_duration_ms(Rational::new(1, 30000)), Some(100));
+    assert_eq!((stats.keyframe_count, stats.last_frame), (2, 4));
+  }
+
+  #[test]
+  fn signed_millis() {
+    assert_eq!(timestamp_to_millis(-1, Rational::new(1, 1000)), None);
+    assert_eq!(timestamp_to_signed_millis(-3003, Rational::new(1, 30000)), Some(-100));
+    assert_eq!(format_signed_millis(-80), "-00m 00s .080ms");
+    assert_eq!(format_signed_millis(0), "00m 00s .000ms");
+  }
+
+  #[test]
+  fn clock_format() {
+    assert_eq!(millis_to_clock_format(0), "00m 00s .000ms");
+    assert_eq!(millis_to_clock_format(61_005), "01m 01s .005ms");
+    assert_eq!(millis_to_clock_format(3_723_450), "01h 02m 03s .450ms");
+    assert_eq!(millis_to_clock_format(100 * 3_600_000), "100h 00m 00s .000ms");
+  }
+
+  #[test]
+  fn thousands_separators() {
+    assert_eq!(format_with_commas(0), "0");
+    assert_eq!(format_with_commas(999), "999");
+    assert_eq!(format_with_commas(1000), "1,000");
+    assert_eq!(format_with_commas(1_234_567), "1,234,567");
+    assert_eq!(format_with_commas(-1_234), "-1,234");
+  }
+
+  #[test]
+  fn byte_units() {
+    assert_eq!(format_bytes(512), "512 bytes");
+    assert_eq!(format_bytes(1536), "1.50 KiB");
+    assert_eq!(format_bytes(1_975_684_956), "1.84 GiB");
+  }
+
+  #[test]
+  fn default_options_print_nothing() {
+    let options = AnalyzeOptions::default();
+    assert!(!options.print_report && !options.show_progress && !options.progress_events && !options.html);
+    assert_eq!(options.threads_number_input, 1);
+  }
+
+  #[test]
+  fn packets_are_routed_by_stream_index() {
+    let data_stream = |index| data_streams::DataStream {
+      index,
+      codec: "bin_data".to_string(),
+      tag: None,
+      handler: None,
+      time_base: Rational::new(1, 1000),
+      packets: 0,
+      bytes: 0,
+      first_timestamp: None,
+      last_timestamp: None,
+      splices: None,
+    };
+    let stats = DecodeStats { data_streams: vec![data_stream(3), data_stream(1)], ..DecodeStats::default() };
+    assert_eq!(routes(&stats, 0, 5), [Route::Video, Route::Data(1), Route::Skip, Route::Data(0), Route::Skip]);
+    // A stream index past the header's count still has a slot.
+    assert_eq!(routes(&DecodeStats::default(), 2, 0), [Route::Skip, Route::Skip, Route::Video]);
+  }
+}
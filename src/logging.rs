@@ -0,0 +1,119 @@
+// Diagnostics on stderr through the log crate: the tool's own warnings and, through FFmpeg's log
+// callback, libav's messages, under one verbosity. -q keeps the errors only, -v adds the debug
+// messages and -vv FFmpeg's verbose output, such as the per-stream details of the demuxers and
+// decoders. stdout only carries the report.
+
+use std::cell::RefCell;
+use std::ffi::{c_char, c_int, c_void, CStr};
+
+use ffmpeg::ffi;
+use ffmpeg::util::log as av_log;
+use log::{Level, LevelFilter, Log, Metadata, Record};
+
+// Target of the messages logged by FFmpeg.
+const FFMPEG_TARGET: &str = "ffmpeg";
+
+// Bytes of a formatted FFmpeg message; longer ones are truncated.
+const LINE_SIZE: usize = 1024;
+
+// bindgen turns a va_list parameter into a pointer on the targets where va_list is an array.
+#[cfg(all(target_arch = "x86_64", not(windows)))]
+type VaList = *mut ffi::__va_list_tag;
+#[cfg(not(all(target_arch = "x86_64", not(windows))))]
+type VaList = ffi::va_list;
+
+struct StderrLogger;
+
+impl Log for StderrLogger {
+  fn enabled(&self, metadata: &Metadata) -> bool {
+    metadata.level() <= log::max_level()
+  }
+
+  fn log(&self, record: &Record) {
+    if !self.enabled(record.metadata()) {
+      return;
+    }
+    // FFmpeg's lines start with their context, e.g. "[h264 @ 0x...]".
+    match record.level() {
+      _ if record.target() == FFMPEG_TARGET => eprintln!("{}", record.args()),
+      Level::Error => eprintln!("Error: {}", record.args()),
+      Level::Warn => eprintln!("Warning: {}", record.args()),
+      _ => eprintln!("{}", record.args()),
+    }
+  }
+
+  fn flush(&self) {}
+}
+
+static LOGGER: StderrLogger = StderrLogger;
+
+// The level of -q, no flag, -v and -vv.
+pub fn level(quiet: bool, verbose: u8) -> LevelFilter {
+  match (quiet, verbose) {
+    (true, _) => LevelFilter::Error,
+    (false, 0) => LevelFilter::Info,
+    (false, 1) => LevelFilter::Debug,
+    _ => LevelFilter::Trace,
+  }
+}
+
+// Install the logger and FFmpeg's log callback, at the level of no flag until set_level.
+pub fn init() {
+  if log::set_logger(&LOGGER).is_ok() {
+    unsafe {
+      ffi::av_log_set_callback(Some(ffmpeg_callback));
+    }
+  }
+  set_level(LevelFilter::Info);
+}
+
+pub fn set_level(level: LevelFilter) {
+  log::set_max_level(level);
+  // The callback filters the messages itself, but parts of FFmpeg check the level before
+  // building one.
+  av_log::set_level(match level {
+    LevelFilter::Off => av_log::Level::Quiet,
+    LevelFilter::Error => av_log::Level::Error,
+    LevelFilter::Warn => av_log::Level::Warning,
+    LevelFilter::Info | LevelFilter::Debug => av_log::Level::Info,
+    LevelFilter::Trace => av_log::Level::Verbose,
+  });
+}
+
+// FFmpeg's levels: verbose and more detailed messages only show with -vv.
+fn level_of(av_level: c_int) -> Level {
+  if av_level <= ffi::AV_LOG_ERROR {
+    Level::Error
+  } else if av_level <= ffi::AV_LOG_WARNING {
+    Level::Warn
+  } else if av_level <= ffi::AV_LOG_INFO {
+    Level::Info
+  } else {
+    Level::Trace
+  }
+}
+
+thread_local! {
+  // A message not ended by a newline yet, and whether the next piece starts a line, which
+  // av_log_format_line2 then prefixes with the context. Frame threads log from their own threads,
+  // so each one keeps its own.
+  static PENDING: RefCell<(String, c_int)> = const { RefCell::new((String::new(), 1)) };
+}
+
+unsafe extern "C" fn ffmpeg_callback(avcl: *mut c_void, av_level: c_int, fmt: *const c_char, vl: VaList) {
+  let level = level_of(av_level);
+  if level > log::max_level() {
+    return;
+  }
+  let mut line = [0 as c_char; LINE_SIZE];
+  PENDING.with(|pending| {
+    let mut pending = pending.borrow_mut();
+    let (text, print_prefix) = &mut *pending;
+    ffi::av_log_format_line2(avcl, av_level, fmt, vl, line.as_mut_ptr(), LINE_SIZE as c_int, print_prefix);
+    text.push_str(&CStr::from_ptr(line.as_ptr()).to_string_lossy());
+    if text.ends_with('\n') {
+      log::log!(target: FFMPEG_TARGET, level, "{}", text.trim_end());
+      text.clear();
+    }
+  });
+}
@@ -0,0 +1,150 @@
+// EBU R128 loudness of an audio stream, measured on the samples the audio decoder already produces
+// during the analysis: integrated loudness, loudness range and true peak. Decoded frames arrive as
+// s16, s32, fltp and so on, so they are converted to interleaved f32 first, and every channel is
+// weighted by its position (the LFE left out, surrounds counted louder) per ITU-R BS.1770.
+
+use ebur128::{Channel, EbuR128, Mode};
+use ffmpeg::format::sample::{Sample, Type as SampleType};
+use ffmpeg::software::resampling;
+use ffmpeg::ChannelLayout;
+
+// The results of a measurement. Each is None while too little audio was measured (silence, or
+// less than the 3 seconds of a loudness range window).
+pub struct Loudness {
+  // LUFS.
+  pub integrated: Option<f64>,
+  // LU.
+  pub range: Option<f64>,
+  // dBTP, the highest of the channels.
+  pub true_peak: Option<f64>,
+}
+
+// The BS.1770 position of each channel of a layout, in FFmpeg's channel order. None for a layout
+// without a known channel mask, which keeps the meter's default (L, R, C, LFE, Ls, Rs).
+fn channel_map(layout: ChannelLayout) -> Option<Vec<Channel>> {
+  if layout.is_empty() {
+    return None;
+  }
+  let sides = layout.contains(ChannelLayout::SIDE_LEFT);
+  let map = [
+    (ChannelLayout::FRONT_LEFT, Channel::Left),
+    (ChannelLayout::FRONT_RIGHT, Channel::Right),
+    (ChannelLayout::FRONT_CENTER, Channel::Center),
+    (ChannelLayout::LOW_FREQUENCY, Channel::Unused),
+    // Back channels are the surrounds of 5.1, and the rear pair of 7.1 next to side surrounds.
+    (ChannelLayout::BACK_LEFT, if sides { Channel::Mp135 } else { Channel::LeftSurround }),
+    (ChannelLayout::BACK_RIGHT, if sides { Channel::Mm135 } else { Channel::RightSurround }),
+    (ChannelLayout::FRONT_LEFT_OF_CENTER, Channel::Left),
+    (ChannelLayout::FRONT_RIGHT_OF_CENTER, Channel::Right),
+    (ChannelLayout::BACK_CENTER, Channel::Mp180),
+    (ChannelLayout::SIDE_LEFT, Channel::LeftSurround),
+    (ChannelLayout::SIDE_RIGHT, Channel::RightSurround),
+    (ChannelLayout::LOW_FREQUENCY_2, Channel::Unused),
+  ];
+  // Channels are stored in the order of their bits; height and other channels count as frontal.
+  Some((0..64)
+    .map(|bit| 1u64 << bit)
+    .filter(|&bit| layout.bits() & bit != 0)
+    .map(|bit| map.iter().find(|(flag, _)| flag.bits() == bit).map_or(Channel::Center, |(_, channel)| *channel))
+    .collect())
+}
+
+pub struct Meter {
+  meter: EbuR128,
+  // Converts the decoded frames to interleaved f32, rebuilt when their format changes.
+  resampler: Option<resampling::Context>,
+  converted: ffmpeg::util::frame::audio::Audio,
+  samples: Vec<f32>,
+  // Sample frames added so far, per channel.
+  measured: u64,
+  // Set when a frame couldn't be measured; the measurement stops there.
+  pub error: Option<String>,
+}
+
+impl Meter {
+  pub fn new() -> Option<Self> {
+    // Parameters are set from the first frame; 2 channels at 48 kHz is only a placeholder.
+    let meter = EbuR128::new(2, 48000, Mode::I | Mode::LRA | Mode::TRUE_PEAK).ok()?;
+    Some(Meter {
+      meter,
+      resampler: None,
+      converted: ffmpeg::util::frame::audio::Audio::empty(),
+      samples: Vec::new(),
+      measured: 0,
+      error: None,
+    })
+  }
+
+  // Add the samples of a decoded frame.
+  pub fn push(&mut self, frame: &ffmpeg::util::frame::audio::Audio) {
+    if self.error.is_some() || frame.samples() == 0 {
+      return;
+    }
+    if let Err(error) = self.add(frame) {
+      self.error = Some(error);
+    }
+  }
+
+  fn add(&mut self, frame: &ffmpeg::util::frame::audio::Audio) -> Result<(), String> {
+    let packed = Sample::F32(SampleType::Packed);
+    let channels = frame.channels() as u32;
+    let stale = self.resampler.as_ref().is_none_or(|resampler| {
+      let input = resampler.input();
+      input.format != frame.format() || input.channel_layout != frame.channel_layout() || input.rate != frame.rate()
+    });
+    if stale {
+      let resampler = resampling::Context::get(frame.format(), frame.channel_layout(), frame.rate(), packed,
+        frame.channel_layout(), frame.rate()).map_err(|e| format!("can't convert the samples: {}", e))?;
+      self.resampler = Some(resampler);
+      // A change of channel count or rate mid-stream starts the measurement over.
+      if self.meter.channels() != channels || self.meter.rate() != frame.rate() {
+        self.meter.change_parameters(channels, frame.rate()).map_err(|e| format!("{:?}", e))?;
+      }
+      match channel_map(frame.channel_layout()) {
+        Some(map) if map.len() == channels as usize => {
+          self.meter.set_channel_map(&map).map_err(|e| format!("{:?}", e))?
+        }
+        _ => {}
+      }
+    }
+    let resampler = self.resampler.as_mut().expect("resampler set above");
+    resampler.run(frame, &mut self.converted).map_err(|e| format!("can't convert the samples: {}", e))?;
+    let length = self.converted.samples() * channels as usize;
+    self.samples.clear();
+    self.samples.extend(self.converted.data(0).chunks_exact(4).take(length)
+      .map(|bytes| f32::from_ne_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])));
+    self.meter.add_frames_f32(&self.samples).map_err(|e| format!("{:?}", e))?;
+    self.measured += self.converted.samples() as u64;
+    Ok(())
+  }
+
+  pub fn result(&self) -> Loudness {
+    let finite = |value: f64| value.is_finite().then_some(value);
+    let peak = (0..self.meter.channels())
+      .filter_map(|channel| self.meter.true_peak(channel).ok())
+      .fold(0.0f64, f64::max);
+    Loudness {
+      integrated: self.meter.loudness_global().ok().and_then(finite),
+      range: self.meter.loudness_range().ok().and_then(finite).filter(|_| self.measured_seconds() >= 3.0),
+      true_peak: finite(20.0 * peak.log10()),
+    }
+  }
+
+  fn measured_seconds(&self) -> f64 {
+    self.measured as f64 / self.meter.rate() as f64
+  }
+}
+
+fn describe(value: Option<f64>, unit: &str) -> String {
+  value.map_or_else(|| "unmeasured".to_string(), |value| format!("{:.1} {}", value, unit))
+}
+
+// One line of the audio section, e.g. "integrated -23.0 LUFS, range 7.4 LU, true peak -1.2 dBTP".
+pub fn describe_result(meter: &Meter) -> String {
+  if let Some(error) = &meter.error {
+    return format!("not measured ({})", error);
+  }
+  let loudness = meter.result();
+  format!("integrated {}, range {}, true peak {}", describe(loudness.integrated, "LUFS"),
+    describe(loudness.range, "LU"), describe(loudness.true_peak, "dBTP"))
+}
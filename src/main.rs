@@ -1,194 +1,462 @@
 extern crate ffmpeg_next as ffmpeg;
-extern crate num_cpus;
 
+mod cli;
+mod exit;
+mod isolate;
+mod jobs;
+mod markdown;
+mod output;
+mod scan;
+
+use std::io::Write;
+use std::path::Path;
 use std::time::Instant;
-use ffmpeg::format::input;
-use ffmpeg::media::Type;
-use ffmpeg::codec::threading;
-use indicatif::{ProgressBar, ProgressStyle};
-use thiserror::Error;
-
-// Define an enumeration for custom error types.
-#[derive(Error, Debug)]
-enum MyError {
-    #[error("No video stream found")]
-    NoVideoStreamError,
-    
-    #[error(transparent)]
-    FfmpegError(#[from] ffmpeg::Error),
-}
-
-// Implement the `From` trait to allow converting a `MyError` into an `ffmpeg::Error`.
-impl From<MyError> for ffmpeg::Error {
-    fn from(err: MyError) -> Self {
-        match err {
-            MyError::NoVideoStreamError => ffmpeg::Error::Unknown,
-            MyError::FfmpegError(e) => e,
-        }
+
+use serde::Serialize;
+
+use exit::Status;
+use video_analyzer::{
+  align, analyze, analyze_captured, compare, findings, format_with_commas, logging, metadata, millis_to_clock_format, report, selftest, AnalysisReport,
+  AnalyzeError,
+  BLUE_BOLD, RESET,
+};
+
+// Handle `explain <FINDING_CODE>` and `explain --all`.
+fn explain(code: Option<&str>, all: bool) -> Result<(), AnalyzeError> {
+  if all {
+    for definition in findings::ALL {
+      println!("{}\n", definition.explanation());
     }
+    return Ok(());
+  }
+  let code = code.unwrap_or_default();
+  let definition = findings::definition(code)
+    .ok_or_else(|| AnalyzeError::InvalidArgument(format!("unknown finding code '{}'", code)))?;
+  println!("{}", definition.explanation());
+  Ok(())
 }
 
-fn millis_to_clock_format(ms: u64) -> String {
-  let hours = ms / 3_600_000;
-  let mins = (ms % 3_600_000) / 60_000;
-  let secs = (ms % 60_000) / 1000;
-  let millis = ms % 1000;
+// With --json, reports are printed as they complete: a single object for one input file, an
+// array of them for several. With --json-lines, each is a line of its own instead, so the output
+// of an interrupted run is still valid up to its last line. Every report is flushed as it is
+// written.
+struct JsonOutput<W: Write> {
+  out: W,
+  array: bool,
+  lines: bool,
+  count: usize,
+}
 
-  if hours > 0 {
-      format!("{:02}h {:02}m {:02}s .{:03}ms", hours, mins, secs, millis)
-  } else {
-      format!("{:02}m {:02}s .{:03}ms", mins, secs, millis)
+impl<W: Write> JsonOutput<W> {
+  fn start(mut out: W, array: bool, lines: bool) -> Self {
+    let array = array && !lines;
+    if array {
+      let _ = writeln!(out, "[");
+    }
+    JsonOutput { out, array, lines, count: 0 }
+  }
+
+  fn push<T: Serialize>(&mut self, value: &T) {
+    let json = if self.lines { report::to_json_line(value) } else { report::to_json(value) };
+    self.push_text(&json);
+  }
+
+  // A report serialized already, as an isolated child prints it.
+  fn push_text(&mut self, json: &str) {
+    if self.lines {
+      let _ = writeln!(self.out, "{}", json.trim_end());
+    } else {
+      let _ = write!(self.out, "{}{}", if self.count > 0 { ",\n" } else { "" }, json.trim_end());
+    }
+    let _ = self.out.flush();
+    self.count += 1;
+  }
+
+  fn finish(mut self) {
+    if self.count > 0 && !self.lines {
+      let _ = writeln!(self.out);
+    }
+    if self.array {
+      let _ = writeln!(self.out, "]");
+    }
+    let _ = self.out.flush();
   }
 }
 
-fn format_with_commas(num: i64) -> String {
-  let num_str = num.to_string();
-  let chars: Vec<_> = num_str.chars().rev().enumerate().map(|(i, c)| {
-      if i != 0 && i % 3 == 0 {
-          vec![',', c]
-      } else {
-          vec![c]
-      }
-  }).flatten().collect();
-  chars.into_iter().rev().collect()
+// The figures of one file, printed as soon as it is analyzed when there are several.
+struct FileRow<'a> {
+  path: &'a str,
+  frames: Option<i64>,
+  duration_ms: Option<u64>,
+  failed: bool,
 }
 
-// Entry point of the program.
-fn main() -> Result<(), ffmpeg::Error> {
-  // Initialize the ffmpeg library.
-  ffmpeg::init().unwrap();
+impl<'a> FileRow<'a> {
+  fn new(path: &'a str, report: Option<&AnalysisReport>) -> Self {
+    FileRow {
+      path,
+      frames: report.and_then(|r| r.frame_count),
+      duration_ms: report.and_then(|r| r.decoded_duration_ms.or(r.container_duration_ms)),
+      failed: false,
+    }
+  }
 
-  // Capture the current time to measure duration later.
-  let start = Instant::now();
+  fn failed(path: &'a str) -> Self {
+    FileRow { path, frames: None, duration_ms: None, failed: true }
+  }
+
+  // e.g. "Result: a.mp4: 250 frames, 00m 10s .000ms, ok"; figures an isolated child kept to itself
+  // are left out.
+  fn describe(&self) -> String {
+    let figures = [
+      self.frames.map(|frames| format!("{} frames", format_with_commas(frames))),
+      self.duration_ms.map(millis_to_clock_format),
+      Some(if self.failed { "FAILED" } else { "ok" }.to_string()),
+    ];
+    format!("Result: {}: {}", self.path, figures.into_iter().flatten().collect::<Vec<_>>().join(", "))
+  }
+}
+
+// Running totals over the analyzed files, for the summary printed after several files. Only the
+// sums are kept, so a batch of any size runs in the same memory.
+#[derive(Default)]
+struct Totals {
+  files: usize,
+  failed: usize,
+  frames: i64,
+  duration_ms: u64,
+}
+
+impl Totals {
+  fn add(&mut self, row: &FileRow) {
+    self.files += 1;
+    self.failed += row.failed as usize;
+    self.frames += row.frames.unwrap_or(0);
+    self.duration_ms += row.duration_ms.unwrap_or(0);
+  }
+}
+
+// The summary of a multi-file run, as text with its section header.
+fn summary(totals: &Totals, isolated: bool, elapsed_ms: u64) -> String {
+  let mut text = format!("{}{}Summary - {}\n", RESET, BLUE_BOLD, RESET);
+  text += &format!("Files analyzed: {} ({} failed)\n", format_with_commas(totals.files as i64),
+    format_with_commas(totals.failed as i64));
+  // Isolated children print their own reports, so their figures never reach the parent.
+  if !isolated {
+    text += &format!("Total frames: {}\n", format_with_commas(totals.frames));
+    text += &format!("Total duration: {}\n", millis_to_clock_format(totals.duration_ms));
+  }
+  text + &format!("Total execution time: {}\n", millis_to_clock_format(elapsed_ms))
+}
+
+// What the run has gathered from the files analyzed so far.
+struct Run {
+  totals: Totals,
+  not_copyable: bool,
+  status: Status,
+  json: Option<JsonOutput<std::io::Stdout>>,
+}
 
-  // Extract the file path from the command-line arguments. Return an error if no path is provided.
-  let path = std::env::args()
-    .nth(1)
-    .ok_or(MyError::NoVideoStreamError)?;
-
-  // Extract thread count from command-line arguments. If not provided, it defaults to 1.
-  let threads_number_input: i32 = std::env::args()
-    .nth(2)
-    .map_or(1, |s| s.parse::<i32>().unwrap_or(1));
-  let mut threads_number: usize = 1;
-  // Check and warn if invalid thread count is provided.
-  if threads_number_input == -1 {
-    threads_number = num_cpus::get();
-    println!("Setting threading to the number of available cores: {}.", threads_number);
-  } else { 
-    if threads_number_input < 1 {
-      println!("invalid thread count provided. Defaulting to 1 thread.");
+impl Run {
+  // Add one file to the totals and, after its text report, print its line.
+  fn account(&mut self, cli: &cli::Cli, row: FileRow) {
+    self.totals.add(&row);
+    if cli.paths.len() > 1 && !cli.json && !cli.markdown && cli.output.is_none() {
+      println!("{}", row.describe());
     }
   }
-  
-  // Open the input video file.
-  let mut ictx = input(&path)?;
-  
-  // Fetch the best video stream from the file. If there's none, return a StreamNotFound error.
-  let input = ictx
-    .streams()
-    .best(Type::Video)
-    .ok_or(ffmpeg::Error::StreamNotFound)?;
-  
-  // Get the index of the video stream.
-  let video_stream_index = input.index();
-
-  // Create a decoding context for the video stream.
-  let context_decoder = ffmpeg::codec::context::Context::from_parameters(input.parameters())?;
-  let mut decoder = context_decoder.decoder().video()?;
-  // Create a threading config and set the number of threads.
-  let threading_config = threading::Config { 
-    kind: threading::Type::Frame, 
-    count: threads_number 
-  };
-  decoder.set_threading(threading_config);
-
-  let time_base = input.time_base();
-
-  // Initialize counters to track the last key frame and total frame count.
-  let mut last_frame = 0;
-  let mut frame_count = 0;
-  
-  // Initialize highest_pts as an Option<i64> with None to indicate it hasn't been set yet.
-  let mut highest_dts: Option<i64> = None;
-
-  // Create an empty video frame outside the loop to reuse it.
-  let mut frame = ffmpeg::util::frame::video::Video::empty();
-
-  let pb = ProgressBar::new_spinner();
-  let style = ProgressStyle::default_spinner()
-    .tick_chars("/|\\- ")
-    .template("{spinner:.green} {msg}");
-  pb.set_style(style.unwrap());
-  pb.set_message("Processing packets...");
-  
-  // Iterate over each packet in the video stream.
-  for (stream, packet) in ictx.packets() {
-    pb.tick();
-    // Skip packets that aren't from the video stream.
-    if stream.index() != video_stream_index {
-      continue;
+
+  // Account for the analysis of one file and print its JSON.
+  fn record(&mut self, cli: &cli::Cli, path: &str, result: Result<AnalysisReport, AnalyzeError>) {
+    match result {
+      Ok(report) => {
+        self.account(cli, FileRow::new(path, Some(&report)));
+        // Strict QC takes precedence over the status of the findings, which the violations overlap.
+        if report.strict_violations.iter().flatten().any(|v| !v.allowed) {
+          self.status = self.status.or(Status::StrictViolations);
+        }
+        self.not_copyable |= report.copyable == Some(false);
+        self.status = self.status.or(Status::from_report(&report, cli.options.require_video));
+        if cli.fail_on_timestamp_errors && exit::has_finding(&report, &findings::TIMESTAMP_ANOMALY) {
+          self.status = self.status.or(Status::TimestampErrors);
+        }
+        if exit::has_finding(&report, &findings::KEYFRAME_TAIL) || exit::has_finding(&report, &findings::PROFILE_REQUIREMENT) {
+          self.status = self.status.or(Status::Failure);
+        }
+        if cli.fail_on_interlaced && report.interlacing.as_ref().is_some_and(|i| i.interlaced_frames > 0) {
+          self.status = self.status.or(Status::Interlaced);
+        }
+        if let Some(output) = self.json.as_mut() {
+          output.push(&report);
+        }
+        if let (Some(html), None) = (&report.html, &cli.output) {
+          print!("{}", html);
+        }
+      }
+      Err(e) => {
+        eprintln!("{}: {}", path, e);
+        self.account(cli, FileRow::failed(path));
+        self.status = self.status.or(Status::from_error(&e));
+        if let Some(output) = self.json.as_mut() {
+          output.push(&report::FileError { path: path.to_string(), error: e.to_string() });
+        }
+      }
+    }
+  }
+
+  // Account for a file analyzed by an isolated child. A crash, a timeout or a child that can't be
+  // started fails this file, not the whole batch.
+  fn isolated(&mut self, cli: &cli::Cli, path: &str, outcome: Result<isolate::Outcome, AnalyzeError>) {
+    let outcome = match outcome {
+      Ok(outcome) => outcome,
+      Err(e) => return self.record(cli, path, Err(e)),
+    };
+    if let (Some(output), Some(text)) = (self.json.as_mut(), outcome.json) {
+      output.push_text(&text);
     }
+    self.account(cli, if outcome.status == Status::Success { FileRow::new(path, None) } else { FileRow::failed(path) });
+    self.status = self.status.or(outcome.status);
+  }
 
-    // Compare and update the highest_dts.
-    highest_dts = match highest_dts {
-      Some(val) if packet.dts().is_some() => {
-          if packet.dts().unwrap() > val {
-              packet.dts()
-          } else {
-              highest_dts
-          }
+  // Print or write the captured report of one file, then account for it.
+  fn captured(&mut self, cli: &cli::Cli, path: &str, result: Result<AnalysisReport, AnalyzeError>, text: String) {
+    match &cli.output {
+      Some(template) => self.write_output(cli, template, path, &result, &text),
+      None if cli.markdown => {
+        print!("{}", markdown::section(path, &text, result.as_ref().err().map(|e| e.to_string())));
+        let _ = std::io::stdout().flush();
       }
-      None => packet.dts(),
-      _ => highest_dts,
+      None => {
+        print_file_header(cli, path);
+        print!("{}", text);
+      }
+    }
+    self.record(cli, path, result);
+  }
+
+  // With --output: write the report of one file and print a line about it instead. A failed
+  // analysis writes nothing; record reports the error.
+  fn write_output(&mut self, cli: &cli::Cli, template: &str, path: &str, result: &Result<AnalysisReport, AnalyzeError>,
+    text: &str) {
+    let Ok(report) = result else {
+      return;
     };
-    
-    // Send the packet to the decoder.
-    decoder.send_packet(&packet)?;
-    
-    // Fetch decoded frames from the decoder. Loop until no more frames are available.
-    while decoder.receive_frame(&mut frame).is_ok() {
-      // If the frame is a key frame, update the last_frame counter.
-      if frame.is_key() {
-          last_frame = frame_count;
+    let target = output::resolve(template, path);
+    let contents = match &report.html {
+      Some(html) => html.clone(),
+      None if cli.json_lines => report::to_json_line(report),
+      None if cli.json => report::to_json(report),
+      None if cli.markdown => markdown::section(path, text, None),
+      None => output::plain(text),
+    };
+    match output::write(&target, &contents) {
+      Ok(()) => println!("{}: {} frames, {}, report written to {}", path,
+        report.frame_count.map_or_else(|| "unknown".to_string(), format_with_commas),
+        report.decoded_duration_ms.or(report.container_duration_ms).map_or_else(|| "unknown duration".to_string(),
+          millis_to_clock_format),
+        target.display()),
+      Err(e) => {
+        eprintln!("{}: can't write {}: {}", path, target.display(), e);
+        self.status = self.status.or(Status::Failure);
       }
-      // Increment the frame count.
-      frame_count += 1;
     }
   }
-  pb.finish_with_message("Processing complete.");
-
-  // Calculate the time taken to process the video.
-  let code_execution_time = start.elapsed();
-  let code_execution_time_ms = code_execution_time.as_millis() as u64;
-  
-  // Get the media duration. You might need to change `numerator()` and `denominator()`
-  // to the correct methods or fields if they are named differently.
-  let media_duration_ms = (ictx.duration() as f64 * time_base.denominator() as f64 / time_base.numerator() as f64 * 1000.0) as u64;
-
-  // Get the media duration based on last frame.
-  let mut last_frame_ms = 0;
-  if let Some(h_dts) = highest_dts {
-    last_frame_ms = (h_dts as f64 * time_base.numerator() as f64 / time_base.denominator() as f64 * 1000.0) as u64;
-  }
-
-  // Blue color and bold for the headers
-  const BLUE_BOLD: &str = "\x1B[1;34m";
-  // Reset color and style back to default
-  const RESET: &str = "\x1B[0m";
-  
-  println!("{}Basic file metadata - {}", BLUE_BOLD, RESET);
-  println!("ictx.duration: {}", ictx.duration());
-  println!("Media Duration: {}", millis_to_clock_format(media_duration_ms));
-  println!("Time base numerator: {}", time_base.numerator());
-  println!("Time base denominator: {}", time_base.denominator());
-
-  println!("{}{}Calculated from the frames - {}", RESET, BLUE_BOLD, RESET);
-  println!("Last key frame id: {}", format_with_commas(last_frame));
-  println!("Frames count: {}", format_with_commas(frame_count));
-  println!("Last Frame Time: {}", millis_to_clock_format(last_frame_ms));
-  println!("Code execution time: {}", millis_to_clock_format(code_execution_time_ms));
-
-  // Return an Ok result.
-  Ok(())
+}
+
+// Print the header that separates the reports of several files.
+fn print_file_header(cli: &cli::Cli, path: &str) {
+  if cli.paths.len() > 1 && !cli.json {
+    println!("{}{}File: {} - {}", RESET, BLUE_BOLD, path, RESET);
+  }
+}
+
+// Handle --tag: print the raw value of the tag for each file, prefixed with the path when there
+// are several. A file without the tag is reported on stderr and fails the run.
+fn print_tag(paths: &[String], key: &str) -> Status {
+  let mut status = Status::Success;
+  for path in paths {
+    match metadata::lookup(Path::new(path), key) {
+      Ok(Some(value)) if paths.len() > 1 => println!("{}: {}", path, value),
+      Ok(Some(value)) => println!("{}", value),
+      Ok(None) => {
+        eprintln!("{}: no '{}' tag", path, key);
+        status = status.or(Status::Failure);
+      }
+      Err(e) => {
+        eprintln!("{}: {}", path, e);
+        status = status.or(Status::from_error(&e));
+      }
+    }
+  }
+  status
+}
+
+// Run a subcommand and return the status to exit with.
+fn run_command(command: cli::Command) -> Result<Status, AnalyzeError> {
+  let passed = match command {
+    // Only prints documentation and never opens a file.
+    cli::Command::Explain { code, all } => return explain(code.as_deref(), all).map(|()| Status::Success),
+    // Encodes and analyzes its own file.
+    cli::Command::SelfTest => selftest::run()?,
+    // Frame-by-frame comparison of two files.
+    cli::Command::Compare { reference, test } => compare::run(&reference, &test)?,
+    // Key frame alignment of the renditions of a ladder.
+    cli::Command::AlignCheck { renditions, tolerance_ms } => align::run(&renditions, tolerance_ms)?,
+  };
+  Ok(if passed { Status::Success } else { Status::Failure })
+}
+
+// Run the subcommand or analysis the arguments ask for and return the status to exit with.
+fn run() -> Result<Status, AnalyzeError> {
+  // Initialize the ffmpeg library, and route its log through the tool's.
+  ffmpeg::init()?;
+  logging::init();
+
+  let cli = match cli::parse()? {
+    cli::Invocation::Command(command) => return run_command(command),
+    cli::Invocation::Analyze(cli) => *cli,
+  };
+  if let Some(key) = &cli.tag {
+    return Ok(print_tag(&cli.paths, key));
+  }
+  let start = Instant::now();
+  let mut run = Run {
+    totals: Totals::default(),
+    not_copyable: false,
+    status: Status::Success,
+    json: (cli.json && cli.output.is_none()).then(|| JsonOutput::start(std::io::stdout(), cli.paths.len() > 1, cli.json_lines)),
+  };
+
+  if cli.jobs > 1 {
+    jobs::run(&cli.paths, &cli.options, cli.jobs, |path, result, text| run.captured(&cli, path, result, text));
+  } else {
+    // Files are analyzed in turn; a failure is reported and the remaining files still run.
+    for path in &cli.paths {
+      if cli.output.is_some() || cli.markdown {
+        let (result, text) = analyze_captured(Path::new(path), &cli.options);
+        run.captured(&cli, path, result, text);
+        continue;
+      }
+      print_file_header(&cli, path);
+      // The child re-parses the same arguments for this file without --isolate and does the
+      // actual analysis.
+      if let Some(limits) = cli.isolate {
+        run.isolated(&cli, path, isolate::run(limits, path, &cli.paths, cli.json, cli.json_lines));
+        continue;
+      }
+      run.record(&cli, path, analyze(Path::new(path), &cli.options));
+    }
+  }
+
+  let elapsed_ms = start.elapsed().as_millis() as u64;
+  match run.json {
+    Some(output) => output.finish(),
+    None if cli.paths.len() < 2 => {}
+    None if cli.markdown && cli.output.is_none() => print!("{}", markdown::render(&summary(&run.totals, cli.isolate.is_some(), elapsed_ms), 2)),
+    None => print!("{}", summary(&run.totals, cli.isolate.is_some(), elapsed_ms)),
+  }
+  if cli.fail_if_not_copyable && run.not_copyable {
+    run.status = run.status.or(Status::Failure);
+  }
+  Ok(run.status)
+}
+
+// Entry point of the program.
+fn main() {
+  let status = run().unwrap_or_else(|e| {
+    eprintln!("{}", e);
+    Status::from_error(&e)
+  });
+  if status != Status::Success {
+    std::process::exit(status.code());
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn error(path: &str) -> report::FileError {
+    report::FileError { path: path.to_string(), error: "Failed to open input".to_string() }
+  }
+
+  #[test]
+  fn json_lines_parse_up_to_an_interruption() {
+    let mut out = Vec::new();
+    {
+      let mut output = JsonOutput::start(&mut out, true, true);
+      output.push(&error("a.mp4"));
+      output.push_text(&report::to_json_line(&error("b.mp4")));
+      // Interrupted before finish: what was written is complete lines.
+    }
+    let text = String::from_utf8(out).unwrap();
+    let paths: Vec<String> = text.lines()
+      .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap()["path"].as_str().unwrap().to_string())
+      .collect();
+    assert_eq!(paths, ["a.mp4", "b.mp4"]);
+    assert!(text.ends_with('\n'));
+  }
+
+  #[test]
+  fn json_array_of_several_files() {
+    let mut out = Vec::new();
+    let mut output = JsonOutput::start(&mut out, true, false);
+    output.push(&error("a.mp4"));
+    output.push(&error("b.mp4"));
+    output.finish();
+    let json: serde_json::Value = serde_json::from_slice(&out).unwrap();
+    assert_eq!(json.as_array().map(Vec::len), Some(2));
+
+    let mut out = Vec::new();
+    let mut output = JsonOutput::start(&mut out, false, false);
+    output.push(&error("a.mp4"));
+    output.finish();
+    assert_eq!(serde_json::from_slice::<serde_json::Value>(&out).unwrap()["path"], "a.mp4");
+  }
+
+  #[test]
+  fn totals_are_summed_as_files_finish() {
+    let mut totals = Totals::default();
+    let row = FileRow { path: "a.mp4", frames: Some(250), duration_ms: Some(10_000), failed: false };
+    assert_eq!(row.describe(), "Result: a.mp4: 250 frames, 00m 10s .000ms, ok");
+    totals.add(&row);
+    totals.add(&FileRow::failed("b.mp4"));
+    assert_eq!(FileRow::failed("b.mp4").describe(), "Result: b.mp4: FAILED");
+    assert_eq!((totals.files, totals.failed, totals.frames, totals.duration_ms), (2, 1, 250, 10_000));
+    let text = summary(&totals, false, 1500);
+    assert!(text.contains("Files analyzed: 2 (1 failed)\nTotal frames: 250\nTotal duration: 00m 10s .000ms\n"), "{text}");
+    assert!(!summary(&totals, true, 1500).contains("Total frames"));
+  }
+
+  // Children that abort, hang and exit with an error each fail their own file; the files after
+  // them are still analyzed and accounted for.
+  #[cfg(unix)]
+  #[test]
+  fn isolated_failures_dont_stop_the_batch() {
+    let limits = isolate::Limits { timeout: Some(std::time::Duration::from_secs(1)), memory_mb: isolate::DEFAULT_MEMORY_MB };
+    let scripts = ["kill -ABRT $$", "exec sleep 30", "exit 2", "exit 0"];
+    let cli = cli::Cli {
+      paths: scripts.iter().map(|script| script.to_string()).collect(),
+      options: video_analyzer::AnalyzeOptions::default(),
+      fail_if_not_copyable: false,
+      fail_on_timestamp_errors: false,
+      fail_on_interlaced: false,
+      json: false,
+      json_lines: false,
+      markdown: false,
+      output: None,
+      tag: None,
+      jobs: 1,
+      isolate: Some(limits),
+    };
+    let mut run = Run { totals: Totals::default(), not_copyable: false, status: Status::Success, json: None };
+    for path in &cli.paths {
+      let mut command = std::process::Command::new("sh");
+      command.args(["-c", path]);
+      run.isolated(&cli, path, isolate::supervise(command, limits, path, cli.json, cli.json_lines));
+    }
+    run.isolated(&cli, "missing.mp4", Err(AnalyzeError::InvalidArgument("--isolate: no such file".to_string())));
+    assert_eq!((run.totals.files, run.totals.failed), (5, 4));
+    assert_eq!(run.status, Status::Failure);
+  }
 }
@@ -0,0 +1,90 @@
+// --format markdown: the text report rewritten for pasting into tickets. Each section header
+// becomes a heading, its "Name: value" lines a table and runs of list lines (indented, or key
+// frames numbered with #) a code block, so the figures keep the formatting of the text report.
+// Several files get one section each, printed as each file is done, and a summary of the totals.
+
+use std::fmt::Write;
+
+use video_analyzer::BLUE_BOLD;
+
+use crate::output;
+
+enum Block {
+  Rows(Vec<(String, String)>),
+  List(Vec<String>),
+  Text(String),
+}
+
+fn cell(text: &str) -> String {
+  text.replace('|', "\\|")
+}
+
+// Group the lines of one section.
+fn blocks(lines: &[String]) -> Vec<Block> {
+  let mut blocks: Vec<Block> = Vec::new();
+  for line in lines.iter().map(|line| line.trim_end()).filter(|line| !line.is_empty()) {
+    if line.starts_with(char::is_whitespace) || line.starts_with('#') {
+      match blocks.last_mut() {
+        Some(Block::List(list)) => list.push(line.to_string()),
+        _ => blocks.push(Block::List(vec![line.to_string()])),
+      }
+      continue;
+    }
+    let Some((name, value)) = line.split_once(": ") else {
+      blocks.push(Block::Text(line.to_string()));
+      continue;
+    };
+    let row = (name.to_string(), value.to_string());
+    match blocks.last_mut() {
+      Some(Block::Rows(rows)) => rows.push(row),
+      _ => blocks.push(Block::Rows(vec![row])),
+    }
+  }
+  blocks
+}
+
+fn push_blocks(markdown: &mut String, lines: &[String]) {
+  for block in blocks(lines) {
+    match block {
+      Block::Rows(rows) => {
+        markdown.push_str("| Field | Value |\n| --- | --- |\n");
+        for (name, value) in rows {
+          let _ = writeln!(markdown, "| {} | {} |", cell(&name), cell(&value));
+        }
+      }
+      Block::List(list) => {
+        let _ = writeln!(markdown, "```\n{}\n```", list.join("\n"));
+      }
+      Block::Text(line) => markdown.push_str(&line),
+    }
+    markdown.push('\n');
+  }
+}
+
+// The Markdown of a captured text report, its sections as headings of `level`.
+pub fn render(text: &str, level: usize) -> String {
+  let mut markdown = String::new();
+  let mut lines = Vec::new();
+  for line in text.lines() {
+    let plain = output::plain(line);
+    // Section headers are the colored lines, "Name - ".
+    if !line.contains(BLUE_BOLD) {
+      lines.push(plain);
+      continue;
+    }
+    push_blocks(&mut markdown, &lines);
+    lines.clear();
+    let _ = writeln!(markdown, "{} {}\n", "#".repeat(level), plain.trim().trim_end_matches('-').trim_end());
+  }
+  push_blocks(&mut markdown, &lines);
+  markdown
+}
+
+// The section of one file: its heading, the report and, when the analysis failed, the error.
+pub fn section(path: &str, text: &str, error: Option<String>) -> String {
+  let mut markdown = format!("## {}\n\n{}", path, render(text, 3));
+  if let Some(error) = error {
+    let _ = writeln!(markdown, "**Analysis failed:** {}\n", error);
+  }
+  markdown
+}
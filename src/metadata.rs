@@ -0,0 +1,87 @@
+// Metadata tags of the container and of each stream: creation_time, encoder, title, language and
+// whatever custom tags the muxer wrote. Values may hold newlines, escape sequences or bytes that
+// aren't UTF-8, so the printed report escapes control characters; the JSON keeps the value.
+
+use std::ffi::CStr;
+use std::path::Path;
+
+use ffmpeg::ffi::{av_dict_get, AV_DICT_IGNORE_SUFFIX};
+
+use crate::{AnalyzeError, BLUE_BOLD, RESET};
+
+// Key/value pairs in the order the dictionary holds them.
+pub type Entries = Vec<(String, String)>;
+
+pub struct Tags {
+  pub format: Entries,
+  // Stream index and its tags, for every stream.
+  pub streams: Vec<(usize, Entries)>,
+}
+
+// Every entry of a dictionary. Bytes that aren't UTF-8 become U+FFFD, the only change made to a value.
+fn entries(dictionary: ffmpeg::DictionaryRef) -> Entries {
+  let mut entries = Vec::new();
+  let mut entry = std::ptr::null();
+  loop {
+    entry = unsafe { av_dict_get(dictionary.as_ptr(), c"".as_ptr(), entry, AV_DICT_IGNORE_SUFFIX) };
+    if entry.is_null() {
+      return entries;
+    }
+    let (key, value) = unsafe { (CStr::from_ptr((*entry).key), CStr::from_ptr((*entry).value)) };
+    entries.push((key.to_string_lossy().into_owned(), value.to_string_lossy().into_owned()));
+  }
+}
+
+pub fn read(ictx: &ffmpeg::format::context::Input) -> Tags {
+  Tags {
+    format: entries(ictx.metadata()),
+    streams: ictx.streams().map(|stream| (stream.index(), entries(stream.metadata()))).collect(),
+  }
+}
+
+fn value<'a>(entries: &'a Entries, key: &str) -> Option<&'a str> {
+  entries.iter().find(|(k, _)| k.eq_ignore_ascii_case(key)).map(|(_, v)| v.as_str())
+}
+
+impl Tags {
+  // Value of a tag, looked up case-insensitively as FFmpeg does: the container's, then the first
+  // stream's that has it.
+  pub fn find(&self, key: &str) -> Option<&str> {
+    value(&self.format, key).or_else(|| self.streams.iter().find_map(|(_, entries)| value(entries, key)))
+  }
+}
+
+// Make a value safe to print on a terminal: control characters, including newlines and escape
+// sequences, are written as Rust escapes (\n, \u{1b}).
+pub fn escape(value: &str) -> String {
+  value.chars()
+    .map(|c| if c.is_control() { c.escape_debug().to_string() } else { c.to_string() })
+    .collect()
+}
+
+// Print the metadata section, grouped by scope. Streams without tags are left out.
+pub fn report(tags: &Tags) {
+  out!("{}{}Metadata - {}", RESET, BLUE_BOLD, RESET);
+  let print = |entries: &Entries| {
+    for (key, value) in entries {
+      out!("  {}: {}", escape(key), escape(value));
+    }
+  };
+  if tags.format.is_empty() {
+    out!("Container: no tags");
+  } else {
+    out!("Container:");
+    print(&tags.format);
+  }
+  for (index, entries) in tags.streams.iter().filter(|(_, entries)| !entries.is_empty()) {
+    out!("Stream #{}:", index);
+    print(entries);
+  }
+}
+
+// --tag: the value of one tag, without analyzing the file.
+pub fn lookup(path: &Path, key: &str) -> Result<Option<String>, AnalyzeError> {
+  ffmpeg::init()?;
+  let ictx = ffmpeg::format::input(&path).map_err(AnalyzeError::OpenError)?;
+  Ok(read(&ictx).find(key).map(str::to_string))
+}
@@ -0,0 +1,130 @@
+// Timestamp validation of the video packets in decode order. Packets must carry a DTS that
+// increases from one packet to the next and a PTS that doesn't come before it; the highest DTS
+// alone hides backwards jumps, so each kind of anomaly is counted with its first positions.
+// Segmented inputs (HLS, DASH, concatenated transport streams) restart their timestamps at every
+// discontinuity; tolerating those, jumps beyond DISCONTINUITY_SECONDS are counted on their own.
+
+use ffmpeg::Rational;
+
+use crate::findings::{self, Finding};
+use crate::timestamp::Timestamp;
+use crate::{format_signed_millis, format_with_commas, timestamp_to_signed_millis, BLUE_BOLD, RESET};
+
+// Positions kept per kind of anomaly.
+const MAX_EXAMPLES: usize = 5;
+
+// A DTS jump at least this large, either way, is a discontinuity when they are tolerated. FFmpeg
+// uses the same threshold (-dts_delta_threshold) for formats with timestamp discontinuities.
+pub const DISCONTINUITY_SECONDS: i64 = 10;
+
+#[derive(Default)]
+pub struct Anomaly {
+  pub count: u64,
+  // Timestamps (stream time base) of the first occurrences; None when the packet had none at all.
+  pub examples: Vec<Option<i64>>,
+}
+
+impl Anomaly {
+  fn record(&mut self, timestamp: Option<i64>) {
+    self.count += 1;
+    if self.examples.len() < MAX_EXAMPLES {
+      self.examples.push(timestamp);
+    }
+  }
+
+  pub fn first(&self) -> Option<i64> {
+    self.examples.iter().copied().flatten().next()
+  }
+}
+
+#[derive(Default)]
+pub struct Tracker {
+  previous_dts: Option<i64>,
+  // Jumps counted as discontinuities instead of anomalies, in the stream time base. None when
+  // discontinuities aren't tolerated.
+  discontinuity_threshold: Option<i64>,
+  pub missing_dts: Anomaly,
+  pub backwards_dts: Anomaly,
+  pub duplicate_dts: Anomaly,
+  pub pts_before_dts: Anomaly,
+  pub discontinuities: Anomaly,
+}
+
+impl Tracker {
+  // A tracker that counts DTS jumps of DISCONTINUITY_SECONDS or more as discontinuities.
+  pub fn tolerating_discontinuities(time_base: Rational) -> Self {
+    let threshold = (time_base.numerator() > 0)
+      .then(|| DISCONTINUITY_SECONDS * time_base.denominator() as i64 / time_base.numerator() as i64);
+    Tracker { discontinuity_threshold: threshold, ..Tracker::default() }
+  }
+
+  // Check a video packet against the previous one.
+  pub fn push(&mut self, dts: Option<i64>, pts: Option<i64>) {
+    let Some(dts) = dts else {
+      self.missing_dts.record(pts);
+      return;
+    };
+    let threshold = self.discontinuity_threshold;
+    match self.previous_dts {
+      Some(previous) if threshold.is_some_and(|t| dts.abs_diff(previous) >= t as u64) => {
+        self.discontinuities.record(Some(dts))
+      }
+      Some(previous) if dts < previous => self.backwards_dts.record(Some(dts)),
+      Some(previous) if dts == previous => self.duplicate_dts.record(Some(dts)),
+      _ => {}
+    }
+    if pts.is_some_and(|pts| pts < dts) {
+      self.pts_before_dts.record(Some(dts));
+    }
+    self.previous_dts = Some(dts);
+  }
+
+  // Each anomaly with its report label and the noun of its finding message.
+  fn anomalies(&self) -> [(&'static str, &'static str, &Anomaly); 4] {
+    [
+      ("Packets without DTS", "packets without a DTS", &self.missing_dts),
+      ("DTS lower than the previous packet's", "backwards DTS jumps", &self.backwards_dts),
+      ("DTS equal to the previous packet's", "duplicate DTS values", &self.duplicate_dts),
+      ("PTS before DTS", "packets presented before they are decoded", &self.pts_before_dts),
+    ]
+  }
+}
+
+fn describe_examples(anomaly: &Anomaly, time_base: Rational) -> String {
+  let times: Vec<String> = anomaly
+    .examples
+    .iter()
+    .map(|ts| {
+      ts.and_then(|ts| timestamp_to_signed_millis(ts, time_base))
+        .map_or_else(|| "no timestamp".to_string(), format_signed_millis)
+    })
+    .collect();
+  let more = if anomaly.count as usize > times.len() { ", ..." } else { "" };
+  format!(" (at {}{})", times.join(", "), more)
+}
+
+// Print the counts and return a finding per kind of anomaly present, located at its first
+// occurrence with a timestamp.
+pub fn report(tracker: &Tracker, time_base: Rational) -> Vec<Finding> {
+  let mut issues = Vec::new();
+  out!("{}{}Timestamp validation - {}", RESET, BLUE_BOLD, RESET);
+  for (label, noun, anomaly) in tracker.anomalies() {
+    if anomaly.count == 0 {
+      out!("{}: 0", label);
+      continue;
+    }
+    out!("{}: {}{}", label, format_with_commas(anomaly.count as i64), describe_examples(anomaly, time_base));
+    let finding = Finding::new(&findings::TIMESTAMP_ANOMALY, format!("{} {} in the video stream",
+      format_with_commas(anomaly.count as i64), noun));
+    issues.push(match anomaly.first() {
+      Some(first) => finding.at(Timestamp::new(first, time_base)),
+      None => finding,
+    });
+  }
+  if tracker.discontinuity_threshold.is_some() {
+    let anomaly = &tracker.discontinuities;
+    out!("Discontinuities (tolerated, DTS jumps of {}s or more): {}{}", DISCONTINUITY_SECONDS,
+      format_with_commas(anomaly.count as i64), if anomaly.count > 0 { describe_examples(anomaly, time_base) } else { String::new() });
+  }
+  issues
+}
@@ -0,0 +1,352 @@
+// Minimal ISO BMFF (MP4/MOV) box reader, independent of libav, for the few boxes libavformat
+// doesn't expose.
+
+use std::fs::File;
+use std::io::{self, BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+
+// Header of a single box.
+pub struct BoxHeader {
+  pub kind: [u8; 4],
+  // Absolute offset of the first byte of the box.
+  pub start: u64,
+  // 8 for regular boxes, 16 with a 64-bit largesize.
+  pub header_len: u64,
+  // Total size including the header.
+  pub size: u64,
+}
+
+impl BoxHeader {
+  pub fn is(&self, kind: &[u8; 4]) -> bool {
+    &self.kind == kind
+  }
+
+  pub fn payload_start(&self) -> u64 {
+    self.start + self.header_len
+  }
+
+  pub fn end(&self) -> u64 {
+    self.start + self.size
+  }
+}
+
+// Read the box header at `offset`. Returns None when fewer than 8 bytes remain before `limit`.
+pub fn read_box_header<R: Read + Seek>(reader: &mut R, offset: u64, limit: u64) -> io::Result<Option<BoxHeader>> {
+  if offset + 8 > limit {
+    return Ok(None);
+  }
+  reader.seek(SeekFrom::Start(offset))?;
+  let mut header = [0u8; 8];
+  reader.read_exact(&mut header)?;
+  let size32 = u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as u64;
+  let kind = [header[4], header[5], header[6], header[7]];
+
+  let (size, header_len) = match size32 {
+    // A 64-bit largesize follows the type; needed for mdat boxes of files over 4GB.
+    1 => {
+      let mut large = [0u8; 8];
+      reader.read_exact(&mut large)?;
+      (u64::from_be_bytes(large), 16)
+    }
+    // Size 0 means the box extends to the end of its parent (or of the file).
+    0 => (limit - offset, 8),
+    size => (size, 8),
+  };
+  if size < header_len {
+    return Err(io::Error::new(io::ErrorKind::InvalidData, "box size smaller than its header"));
+  }
+  Ok(Some(BoxHeader { kind, start: offset, header_len, size }))
+}
+
+// Headers of every direct child box in [start, end).
+pub fn children<R: Read + Seek>(reader: &mut R, start: u64, end: u64) -> io::Result<Vec<BoxHeader>> {
+  let mut boxes = Vec::new();
+  let mut offset = start;
+  while let Some(header) = read_box_header(reader, offset, end)? {
+    offset = header.end().min(end);
+    boxes.push(header);
+  }
+  Ok(boxes)
+}
+
+// Find the first child of the given kind.
+pub fn child<R: Read + Seek>(reader: &mut R, parent: &BoxHeader, kind: &[u8; 4]) -> io::Result<Option<BoxHeader>> {
+  Ok(children(reader, parent.payload_start(), parent.end())?.into_iter().find(|b| b.is(kind)))
+}
+
+// Clean aperture ('clap') of a visual sample entry, as rational values.
+pub struct CleanAperture {
+  pub width: (u32, u32),
+  pub height: (u32, u32),
+  pub horizontal_offset: (i32, u32),
+  pub vertical_offset: (i32, u32),
+}
+
+impl CleanAperture {
+  // Width and height in whole pixels, or None for a zero denominator.
+  pub fn size(&self) -> Option<(u32, u32)> {
+    if self.width.1 == 0 || self.height.1 == 0 {
+      return None;
+    }
+    Some((self.width.0 / self.width.1, self.height.0 / self.height.1))
+  }
+}
+
+pub fn read_u32<R: Read>(reader: &mut R) -> io::Result<u32> {
+  let mut buf = [0u8; 4];
+  reader.read_exact(&mut buf)?;
+  Ok(u32::from_be_bytes(buf))
+}
+
+// Size of a VisualSampleEntry's fixed fields between its header and its child boxes.
+const VISUAL_SAMPLE_ENTRY_FIELDS: u64 = 78;
+
+// Read the clean aperture of each track's first sample entry, in track order (which matches
+// libavformat's stream indices for MP4/MOV). Non-video tracks and tracks without a 'clap' box
+// yield None.
+pub fn clean_apertures(path: &Path) -> io::Result<Vec<Option<CleanAperture>>> {
+  let file = File::open(path)?;
+  let file_size = file.metadata()?.len();
+  let mut reader = BufReader::new(file);
+
+  let moov = match children(&mut reader, 0, file_size)?.into_iter().find(|b| b.is(b"moov")) {
+    Some(moov) => moov,
+    None => return Ok(Vec::new()),
+  };
+
+  let mut apertures = Vec::new();
+  for trak in children(&mut reader, moov.payload_start(), moov.end())?.into_iter().filter(|b| b.is(b"trak")) {
+    apertures.push(track_clean_aperture(&mut reader, &trak)?);
+  }
+  Ok(apertures)
+}
+
+fn track_clean_aperture<R: Read + Seek>(reader: &mut R, trak: &BoxHeader) -> io::Result<Option<CleanAperture>> {
+  // trak > mdia > minf > stbl > stsd
+  let mut current = match child(reader, trak, b"mdia")? {
+    Some(b) => b,
+    None => return Ok(None),
+  };
+  for kind in [b"minf", b"stbl", b"stsd"] {
+    current = match child(reader, &current, kind)? {
+      Some(b) => b,
+      None => return Ok(None),
+    };
+  }
+
+  // stsd: version/flags (4 bytes) and entry count (4 bytes) precede the sample entries.
+  let entry = match read_box_header(reader, current.payload_start() + 8, current.end())? {
+    Some(entry) => entry,
+    None => return Ok(None),
+  };
+  let boxes_start = entry.payload_start() + VISUAL_SAMPLE_ENTRY_FIELDS;
+  if boxes_start >= entry.end() {
+    return Ok(None);
+  }
+  // Audio sample entries are shorter and don't parse as boxes at this offset; treat any read
+  // error inside the entry as "no clean aperture".
+  let clap = match children(reader, boxes_start, entry.end()) {
+    Ok(boxes) => boxes.into_iter().find(|b| b.is(b"clap")),
+    Err(_) => None,
+  };
+  let clap = match clap {
+    Some(clap) if clap.size >= clap.header_len + 32 => clap,
+    _ => return Ok(None),
+  };
+
+  reader.seek(SeekFrom::Start(clap.payload_start()))?;
+  let width = (read_u32(reader)?, read_u32(reader)?);
+  let height = (read_u32(reader)?, read_u32(reader)?);
+  let horizontal_offset = (read_u32(reader)? as i32, read_u32(reader)?);
+  let vertical_offset = (read_u32(reader)? as i32, read_u32(reader)?);
+  Ok(Some(CleanAperture { width, height, horizontal_offset, vertical_offset }))
+}
+
+pub fn read_u64<R: Read>(reader: &mut R) -> io::Result<u64> {
+  let mut buf = [0u8; 8];
+  reader.read_exact(&mut buf)?;
+  Ok(u64::from_be_bytes(buf))
+}
+
+// For each track, in track order, the media time (in the track's media time base) at which the
+// first non-empty edit of its edit list starts. A positive value is how MP4 muxers usually hide
+// the B-frame delay; None when the track has no edit list or only empty edits.
+pub fn edit_list_offsets(path: &Path) -> io::Result<Vec<Option<i64>>> {
+  let file = File::open(path)?;
+  let file_size = file.metadata()?.len();
+  let mut reader = BufReader::new(file);
+
+  let moov = match children(&mut reader, 0, file_size)?.into_iter().find(|b| b.is(b"moov")) {
+    Some(moov) => moov,
+    None => return Ok(Vec::new()),
+  };
+
+  let mut offsets = Vec::new();
+  for trak in children(&mut reader, moov.payload_start(), moov.end())?.into_iter().filter(|b| b.is(b"trak")) {
+    offsets.push(track_edit_offset(&mut reader, &trak)?);
+  }
+  Ok(offsets)
+}
+
+fn track_edit_offset<R: Read + Seek>(reader: &mut R, trak: &BoxHeader) -> io::Result<Option<i64>> {
+  // trak > edts > elst
+  let Some(edts) = child(reader, trak, b"edts")? else {
+    return Ok(None);
+  };
+  let Some(elst) = child(reader, &edts, b"elst")? else {
+    return Ok(None);
+  };
+
+  reader.seek(SeekFrom::Start(elst.payload_start()))?;
+  let version = read_u32(reader)? >> 24;
+  let entries = read_u32(reader)?;
+  for _ in 0..entries {
+    // Version 1 entries use 64-bit duration and media time; both versions end with a 32-bit rate.
+    let media_time = if version == 1 {
+      read_u64(reader)?;
+      read_u64(reader)? as i64
+    } else {
+      read_u32(reader)?;
+      read_u32(reader)? as i32 as i64
+    };
+    read_u32(reader)?;
+    // A media time of -1 marks an empty edit, which delays the track instead of trimming it.
+    if media_time >= 0 {
+      return Ok(Some(media_time));
+    }
+  }
+  Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::io::Cursor;
+
+  // A box of `kind` around `payload`.
+  pub(crate) fn boxed(kind: &[u8; 4], payload: &[u8]) -> Vec<u8> {
+    let mut data = ((payload.len() + 8) as u32).to_be_bytes().to_vec();
+    data.extend_from_slice(kind);
+    data.extend_from_slice(payload);
+    data
+  }
+
+  fn words(values: &[u32]) -> Vec<u8> {
+    values.iter().flat_map(|value| value.to_be_bytes()).collect()
+  }
+
+  // A trak box holding a single visual sample entry with the given child boxes.
+  fn video_trak(entry_boxes: &[u8]) -> Vec<u8> {
+    let mut entry = vec![0u8; VISUAL_SAMPLE_ENTRY_FIELDS as usize];
+    entry.extend_from_slice(entry_boxes);
+    let mut stsd = words(&[0, 1]);
+    stsd.extend(boxed(b"avc1", &entry));
+    let stbl = boxed(b"stbl", &boxed(b"stsd", &stsd));
+    boxed(b"trak", &boxed(b"mdia", &boxed(b"minf", &stbl)))
+  }
+
+  fn header(data: &[u8]) -> BoxHeader {
+    read_box_header(&mut Cursor::new(data), 0, data.len() as u64).unwrap().unwrap()
+  }
+
+  #[test]
+  fn box_headers() {
+    let data = boxed(b"free", &[0; 4]);
+    let free = header(&data);
+    assert!(free.is(b"free"));
+    assert_eq!((free.start, free.header_len, free.size, free.payload_start(), free.end()), (0, 8, 12, 8, 12));
+
+    let mut large = 1u32.to_be_bytes().to_vec();
+    large.extend_from_slice(b"mdat");
+    large.extend(20u64.to_be_bytes());
+    large.extend([0; 4]);
+    let mdat = header(&large);
+    assert_eq!((mdat.header_len, mdat.size, mdat.payload_start()), (16, 20, 16));
+
+    // Size 0 runs to the end of the parent.
+    let mut open = 0u32.to_be_bytes().to_vec();
+    open.extend_from_slice(b"mdat");
+    open.extend([0; 24]);
+    assert_eq!(header(&open).size, 32);
+  }
+
+  #[test]
+  fn truncated_and_corrupt_headers() {
+    let data = boxed(b"free", &[]);
+    assert!(read_box_header(&mut Cursor::new(&data), 4, data.len() as u64).unwrap().is_none());
+    let mut corrupt = 4u32.to_be_bytes().to_vec();
+    corrupt.extend_from_slice(b"free");
+    assert!(read_box_header(&mut Cursor::new(&corrupt), 0, 8).is_err());
+  }
+
+  #[test]
+  fn child_boxes() {
+    let mut data = boxed(b"ftyp", b"isom");
+    data.extend(boxed(b"moov", &[boxed(b"mvhd", &[0; 8]), boxed(b"trak", &[]), boxed(b"trak", &[])].concat()));
+    data.extend(boxed(b"mdat", &[0; 16]));
+    let mut reader = Cursor::new(&data);
+    let top = children(&mut reader, 0, data.len() as u64).unwrap();
+    let kinds: Vec<&[u8; 4]> = top.iter().map(|b| &b.kind).collect();
+    assert_eq!(kinds, [b"ftyp", b"moov", b"mdat"]);
+    assert_eq!(top[2].start, 12 + 8 + 16 + 8 + 8);
+    let moov = &top[1];
+    assert_eq!(children(&mut reader, moov.payload_start(), moov.end()).unwrap().len(), 3);
+    assert_eq!(child(&mut reader, moov, b"trak").unwrap().unwrap().start, moov.payload_start() + 16);
+    assert!(child(&mut reader, moov, b"udta").unwrap().is_none());
+  }
+
+  #[test]
+  fn clean_aperture() {
+    let clap = boxed(b"clap", &words(&[1916, 1, 1076, 1, u32::MAX - 1, 2, 2, 1]));
+    let mut data = video_trak(&[boxed(b"pasp", &words(&[1, 1])), clap].concat());
+    let aperture = track_clean_aperture(&mut Cursor::new(&data), &header(&data)).unwrap().unwrap();
+    assert_eq!(aperture.size(), Some((1916, 1076)));
+    assert_eq!(aperture.horizontal_offset, (-2, 2));
+    assert_eq!(aperture.vertical_offset, (2, 1));
+
+    data = video_trak(&boxed(b"pasp", &words(&[1, 1])));
+    assert!(track_clean_aperture(&mut Cursor::new(&data), &header(&data)).unwrap().is_none());
+    data = boxed(b"trak", &boxed(b"tkhd", &[0; 84]));
+    assert!(track_clean_aperture(&mut Cursor::new(&data), &header(&data)).unwrap().is_none());
+  }
+
+  #[test]
+  fn clean_aperture_with_zero_denominator() {
+    let aperture = CleanAperture { width: (1920, 0), height: (1080, 1), horizontal_offset: (0, 1), vertical_offset: (0, 1) };
+    assert_eq!(aperture.size(), None);
+  }
+
+  // A trak box with an edit list of (duration, media time) entries.
+  fn edited_trak(version: u32, entries: &[(u64, i64)]) -> Vec<u8> {
+    let mut elst = words(&[version << 24, entries.len() as u32]);
+    for &(duration, media_time) in entries {
+      if version == 1 {
+        elst.extend(duration.to_be_bytes());
+        elst.extend(media_time.to_be_bytes());
+      } else {
+        elst.extend(words(&[duration as u32, media_time as i32 as u32]));
+      }
+      elst.extend(words(&[0x0001_0000]));
+    }
+    boxed(b"trak", &boxed(b"edts", &boxed(b"elst", &elst)))
+  }
+
+  fn edit_offset(data: &[u8]) -> Option<i64> {
+    track_edit_offset(&mut Cursor::new(data), &header(data)).unwrap()
+  }
+
+  #[test]
+  fn edit_list_media_start() {
+    assert_eq!(edit_offset(&edited_trak(0, &[(10_000, 1024)])), Some(1024));
+    assert_eq!(edit_offset(&edited_trak(1, &[(10_000, 3003)])), Some(3003));
+    assert_eq!(edit_offset(&edited_trak(0, &[(10_000, 0)])), Some(0));
+  }
+
+  #[test]
+  fn empty_edits_are_skipped() {
+    assert_eq!(edit_offset(&edited_trak(0, &[(500, -1), (10_000, 2002)])), Some(2002));
+    assert_eq!(edit_offset(&edited_trak(1, &[(500, -1)])), None);
+    assert_eq!(edit_offset(&edited_trak(0, &[])), None);
+    assert_eq!(edit_offset(&boxed(b"trak", &boxed(b"tkhd", &[0; 84]))), None);
+  }
+}
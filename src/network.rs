@@ -0,0 +1,57 @@
+// Network inputs: http(s), rtmp, srt and the other protocols FFmpeg opens from a URL. They are
+// opened with a read timeout, and http(s) with reconnects, so a stalled connection ends the
+// analysis with a network timeout instead of waiting on the socket forever.
+
+use ffmpeg::util::error::ETIMEDOUT;
+
+use crate::AnalyzeError;
+
+// Whether the input is a URL ("scheme://...") rather than a file path. Windows drive letters
+// ("C:\...") don't qualify, since a scheme is followed by "//".
+pub fn is_url(path: &str) -> bool {
+  path.split_once("://").is_some_and(|(scheme, _)| {
+    scheme.len() > 1 && scheme.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
+  })
+}
+
+// Options of the protocol layer for a URL: rw_timeout applies to every protocol, the reconnect
+// options to http(s) only. A timeout of 0 waits forever.
+fn options(url: &str, timeout_seconds: u64) -> ffmpeg::Dictionary<'static> {
+  let mut options = ffmpeg::Dictionary::new();
+  if timeout_seconds > 0 {
+    options.set("rw_timeout", &(timeout_seconds * 1_000_000).to_string());
+  }
+  if url.starts_with("http://") || url.starts_with("https://") {
+    options.set("reconnect", "1");
+    options.set("reconnect_streamed", "1");
+    options.set("reconnect_on_network_error", "1");
+    if timeout_seconds > 0 {
+      options.set("reconnect_delay_max", &timeout_seconds.to_string());
+    }
+  }
+  options
+}
+
+// Open a file, or a URL with the timeout and reconnect options.
+pub fn open(path: &str, timeout_seconds: u64) -> Result<ffmpeg::format::context::Input, ffmpeg::Error> {
+  if is_url(path) {
+    ffmpeg::format::input_with_dictionary(&path, options(path, timeout_seconds))
+  } else {
+    ffmpeg::format::input(&path)
+  }
+}
+
+// Whether an error is the protocol layer giving up on a stalled connection (AVERROR(ETIMEDOUT)).
+pub fn is_timeout(error: &ffmpeg::Error) -> bool {
+  matches!(error, ffmpeg::Error::Other { errno } if *errno == ETIMEDOUT)
+}
+
+// Report a timed out read as a network timeout, whichever step of the analysis it ended.
+pub fn classify(error: AnalyzeError, timeout_seconds: u64) -> AnalyzeError {
+  match &error {
+    AnalyzeError::OpenError(e) | AnalyzeError::DecodeError(e) | AnalyzeError::FfmpegError(e) if is_timeout(e) => {
+      AnalyzeError::NetworkTimeout(timeout_seconds)
+    }
+    _ => error,
+  }
+}
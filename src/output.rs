@@ -0,0 +1,160 @@
+// --output: the report of each input in a file instead of on stdout, which then only gets a line
+// per file. With several inputs the path is a template: {stem} stands for the input's file name
+// without its extension. Reports are written to a temporary file next to the target and renamed
+// over it, so a failed write never leaves half a report behind.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use video_analyzer::AnalyzeError;
+
+const STEM: &str = "{stem}";
+
+// The file name of an input without its extension; URLs lose their query first.
+fn stem(input: &str) -> String {
+  if input == "-" {
+    return "stdin".to_string();
+  }
+  let path = input.split(['?', '#']).next().unwrap_or(input);
+  Path::new(path).file_stem().map_or_else(|| "report".to_string(), |stem| stem.to_string_lossy().into_owned())
+}
+
+// Where the report of `input` goes.
+pub fn resolve(template: &str, input: &str) -> PathBuf {
+  PathBuf::from(template.replace(STEM, &stem(input)))
+}
+
+// Check the targets before anything is analyzed: one per input, and none that exists already
+// unless `force` is set.
+pub fn check(template: &str, paths: &[String], force: bool) -> Result<(), AnalyzeError> {
+  if paths.len() > 1 && !template.contains(STEM) {
+    return Err(AnalyzeError::InvalidArgument(format!("--output needs {} in the path with several input files", STEM)));
+  }
+  let mut targets: HashMap<PathBuf, &str> = HashMap::new();
+  for path in paths {
+    let target = resolve(template, path);
+    if let Some(other) = targets.insert(target.clone(), path) {
+      return Err(AnalyzeError::InvalidArgument(format!("{} and {} would both write {}", other, path,
+        target.display())));
+    }
+    if target.exists() && !force {
+      return Err(AnalyzeError::InvalidArgument(format!("{} already exists; pass --force to overwrite it",
+        target.display())));
+    }
+  }
+  Ok(())
+}
+
+// The text report without its color escape sequences, which a file has no use for.
+pub fn plain(text: &str) -> String {
+  let mut plain = String::with_capacity(text.len());
+  let mut chars = text.chars();
+  while let Some(c) = chars.next() {
+    if c == '\x1B' {
+      chars.by_ref().find(|&c| c == 'm');
+    } else {
+      plain.push(c);
+    }
+  }
+  plain
+}
+
+// Write `contents` to `target` through a temporary file in the same directory, creating the
+// directories on the way.
+pub fn write(target: &Path, contents: &str) -> std::io::Result<()> {
+  let directory = target.parent().filter(|parent| !parent.as_os_str().is_empty()).unwrap_or(Path::new("."));
+  fs::create_dir_all(directory)?;
+  let name = target.file_name().map_or_else(|| "report".into(), |name| name.to_string_lossy().into_owned());
+  let temporary = directory.join(format!(".{}.{}.tmp", name, std::process::id()));
+  let written = fs::File::create(&temporary).and_then(|mut file| {
+    file.write_all(contents.as_bytes())?;
+    if !contents.ends_with('\n') {
+      file.write_all(b"\n")?;
+    }
+    file.sync_all()
+  });
+  match written.and_then(|()| fs::rename(&temporary, target)) {
+    Ok(()) => Ok(()),
+    Err(e) => {
+      let _ = fs::remove_file(&temporary);
+      Err(e)
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  // An empty directory of its own under the system temporary directory.
+  fn scratch(name: &str) -> PathBuf {
+    let directory = std::env::temp_dir().join(format!("video_analyzer-output-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&directory);
+    fs::create_dir_all(&directory).unwrap();
+    directory
+  }
+
+  #[test]
+  fn stems() {
+    assert_eq!(stem("/media/clips/intro.final.mp4"), "intro.final");
+    assert_eq!(stem("movie.mkv"), "movie");
+    assert_eq!(stem("https://cdn.example.com/live/master.m3u8?token=abc#t=10"), "master");
+    assert_eq!(stem("-"), "stdin");
+    assert_eq!(stem("/"), "report");
+  }
+
+  #[test]
+  fn templates() {
+    assert_eq!(resolve("reports/{stem}.json", "/in/a.mp4"), PathBuf::from("reports/a.json"));
+    assert_eq!(resolve("{stem}-{stem}.txt", "b.ts"), PathBuf::from("b-b.txt"));
+    assert_eq!(resolve("report.txt", "c.mov"), PathBuf::from("report.txt"));
+  }
+
+  #[test]
+  fn several_inputs_need_the_stem() {
+    let paths = ["a.mp4".to_string(), "b.mp4".to_string()];
+    let error = check("/nonexistent/report.txt", &paths, false).unwrap_err();
+    assert!(error.to_string().contains("--output needs {stem}"), "{error}");
+    assert!(check("/nonexistent/{stem}.txt", &paths, false).is_ok());
+    assert!(check("/nonexistent/report.txt", &paths[..1], false).is_ok());
+  }
+
+  #[test]
+  fn inputs_must_not_share_a_target() {
+    let paths = ["one/clip.mp4".to_string(), "two/clip.mkv".to_string()];
+    let error = check("/nonexistent/{stem}.txt", &paths, true).unwrap_err();
+    assert!(error.to_string().contains("one/clip.mp4 and two/clip.mkv would both write"), "{error}");
+  }
+
+  #[test]
+  fn existing_targets_need_force() {
+    let directory = scratch("existing");
+    let template = directory.join("{stem}.txt").to_string_lossy().into_owned();
+    fs::write(directory.join("a.txt"), "old").unwrap();
+    let paths = ["a.mp4".to_string()];
+    assert!(check(&template, &paths, false).unwrap_err().to_string().contains("already exists"));
+    assert!(check(&template, &paths, true).is_ok());
+    fs::remove_dir_all(directory).unwrap();
+  }
+
+  #[test]
+  fn colors_are_stripped() {
+    assert_eq!(plain("\x1B[0m\x1B[1;34mSummary - \x1B[0m\nFrames: 10\n"), "Summary - \nFrames: 10\n");
+    assert_eq!(plain("no escapes"), "no escapes");
+    assert_eq!(plain("cut \x1B[1;3"), "cut ");
+  }
+
+  #[test]
+  fn writes_replace_the_target() {
+    let directory = scratch("write");
+    let target = directory.join("nested/report.txt");
+    write(&target, "first").unwrap();
+    write(&target, "second\n").unwrap();
+    assert_eq!(fs::read_to_string(&target).unwrap(), "second\n");
+    // Only the report is left, no temporary file.
+    assert_eq!(fs::read_dir(directory.join("nested")).unwrap().count(), 1);
+    fs::remove_dir_all(directory).unwrap();
+  }
+}
@@ -0,0 +1,125 @@
+// Video packet sizes, split between key frame packets and the others, so a pathological encode (a
+// single 40 MB I-frame, a run of oversized P-frames) stands out from the averages. --top-packets
+// lists the largest ones with their timestamps to jump to in a player.
+
+use std::collections::BTreeMap;
+
+use ffmpeg::Rational;
+
+use crate::report::{PacketReport, PacketSizeReport, PacketSizeStats};
+use crate::timestamp::Timestamp;
+use crate::{format_bytes, format_with_commas, BLUE_BOLD, RESET};
+
+#[derive(Default, Clone, Copy)]
+pub struct Sizes {
+  pub count: u64,
+  pub total_bytes: u64,
+  pub min: Option<u64>,
+  pub max: Option<u64>,
+}
+
+impl Sizes {
+  fn push(&mut self, size: u64) {
+    self.count += 1;
+    self.total_bytes += size;
+    self.min = Some(self.min.map_or(size, |min| min.min(size)));
+    self.max = Some(self.max.map_or(size, |max| max.max(size)));
+  }
+
+  pub fn average(&self) -> Option<u64> {
+    (self.count > 0).then(|| self.total_bytes / self.count)
+  }
+}
+
+#[derive(Clone, Copy)]
+pub struct Packet {
+  // Position among the video packets, in decode order.
+  pub index: u64,
+  pub timestamp: Option<i64>,
+  pub size: u64,
+  pub key: bool,
+}
+
+#[derive(Default)]
+pub struct Tracker {
+  pub all: Sizes,
+  pub key: Sizes,
+  pub other: Sizes,
+  // Packets per power of two: the count of sizes from 2^n up to 2^(n+1) under n.
+  pub size_classes: BTreeMap<u32, u64>,
+  // The largest packets, largest first; the first packet of a size wins ties.
+  pub largest: Vec<Packet>,
+  // Packets kept in `largest`, at least 1.
+  keep: usize,
+}
+
+impl Tracker {
+  pub fn new(top: usize) -> Self {
+    Tracker { keep: top.max(1), ..Tracker::default() }
+  }
+
+  pub fn push(&mut self, timestamp: Option<i64>, size: u64, key: bool) {
+    let packet = Packet { index: self.all.count, timestamp, size, key };
+    self.all.push(size);
+    if key { self.key.push(size) } else { self.other.push(size) }
+    *self.size_classes.entry(size.max(1).ilog2()).or_default() += 1;
+    if self.largest.len() == self.keep && self.largest.last().is_some_and(|smallest| smallest.size >= size) {
+      return;
+    }
+    let at = self.largest.partition_point(|p| p.size >= size);
+    self.largest.insert(at, packet);
+    self.largest.truncate(self.keep);
+  }
+}
+
+fn describe_sizes(sizes: &Sizes) -> String {
+  match (sizes.average(), sizes.min, sizes.max) {
+    (Some(average), Some(min), Some(max)) => format!("{} packets, {} (average {}, min {}, max {})",
+      format_with_commas(sizes.count as i64), format_bytes(sizes.total_bytes), format_bytes(average),
+      format_bytes(min), format_bytes(max)),
+    _ => "none".to_string(),
+  }
+}
+
+fn describe_packet(packet: &Packet, time_base: Rational, verbose: bool) -> String {
+  let at = packet.timestamp.map_or_else(|| "no timestamp".to_string(), |ts| Timestamp::new(ts, time_base).describe(verbose));
+  format!("#{} at {}: {} ({} bytes){}", format_with_commas(packet.index as i64), at, format_bytes(packet.size),
+    format_with_commas(packet.size as i64), if packet.key { ", key frame" } else { "" })
+}
+
+// None without any video packet.
+pub fn to_report(tracker: &Tracker, time_base: Rational) -> Option<PacketSizeReport> {
+  (tracker.all.count > 0).then(|| PacketSizeReport {
+    all: PacketSizeStats::from(&tracker.all),
+    key: PacketSizeStats::from(&tracker.key),
+    other: PacketSizeStats::from(&tracker.other),
+    largest: tracker.largest.iter().map(|packet| PacketReport {
+      index: packet.index,
+      timestamp_ms: packet.timestamp.and_then(|ts| Timestamp::new(ts, time_base).signed_millis()),
+      timestamp_raw: packet.timestamp.map(|ts| Timestamp::new(ts, time_base)),
+      size: packet.size,
+      key: packet.key,
+    }).collect(),
+  })
+}
+
+// Print the packet size section. With top > 0, the largest packets are listed one per line.
+pub fn report(tracker: &Tracker, top: usize, time_base: Rational, verbose: bool) {
+  if tracker.all.count == 0 {
+    return;
+  }
+  out!("{}{}Packet sizes - {}", RESET, BLUE_BOLD, RESET);
+  out!("Video packets: {}", describe_sizes(&tracker.all));
+  out!("Key frame packets: {}", describe_sizes(&tracker.key));
+  out!("Other packets: {}", describe_sizes(&tracker.other));
+  if top == 0 {
+    if let Some(largest) = tracker.largest.first() {
+      out!("Largest packet: {}", describe_packet(largest, time_base, verbose));
+    }
+    return;
+  }
+  out!("Largest packets:");
+  for packet in tracker.largest.iter().take(top) {
+    out!("  {}", describe_packet(packet, time_base, verbose));
+  }
+}
@@ -0,0 +1,158 @@
+// Input read from stdin ("-"), e.g. `cat file.ts | video_analyzer -`. FFmpeg reads it through a
+// read callback of our own rather than the pipe: protocol, so the bytes going by can be checked for
+// an MP4/MOV whose moov atom comes after the media data: without seeking, such a file can't be
+// demuxed, and FFmpeg only says "moov atom not found" or reads through it without packets.
+
+use std::ffi::{c_int, c_void};
+use std::io::{IsTerminal, Read};
+
+use ffmpeg::ffi::{
+  av_malloc, avformat_alloc_context, avformat_close_input, avformat_find_stream_info, avformat_open_input,
+  avio_alloc_context, AVERROR, AVERROR_EOF, AVFMT_FLAG_CUSTOM_IO,
+};
+use ffmpeg::util::error::{EIO, ENOMEM};
+
+use crate::{AnalyzeError, AnalyzeOptions};
+
+pub const PATH: &str = "-";
+
+// Size of the buffer FFmpeg reads stdin into.
+const BUFFER_SIZE: usize = 64 * 1024;
+
+// Top-level ISO BMFF boxes a file may start with.
+const LEADING_BOXES: [&[u8; 4]; 8] = [b"ftyp", b"styp", b"moov", b"mdat", b"free", b"skip", b"wide", b"pnot"];
+
+pub fn is_stdin(path: &str) -> bool {
+  path == PATH
+}
+
+// Reject the options that seek in the input, which a pipe can't do.
+pub fn check_options(options: &AnalyzeOptions) -> Result<(), AnalyzeError> {
+  let seeking = [
+    (options.head_tail_mb.is_some(), "--head-tail"),
+    (options.start_ms.is_some(), "--start"),
+    (options.recovery_pass, "--recovery-pass"),
+    (options.extract_problems.is_some(), "--extract-problems"),
+    (options.io_retries > 0, "--io-retries"),
+  ];
+  match seeking.iter().find(|(set, _)| *set) {
+    Some((_, flag)) => Err(AnalyzeError::InvalidArgument(format!("{} needs to seek in the input, which stdin can't", flag))),
+    None => Ok(()),
+  }
+}
+
+// Reads stdin for FFmpeg and follows the top-level ISO BMFF boxes going by, until the first moov or
+// mdat box.
+struct Reader {
+  stdin: std::io::Stdin,
+  // Bytes read so far.
+  position: u64,
+  // Offset of the next top-level box and the bytes of its header read so far. None once the input
+  // turned out not to be ISO BMFF, or the first moov or mdat box was found.
+  next_box: Option<u64>,
+  header: Vec<u8>,
+  // Which of moov and mdat came first.
+  first: Option<[u8; 4]>,
+}
+
+impl Reader {
+  fn scan(&mut self, data: &[u8]) {
+    // A 64-bit size follows the type when the 32-bit size is 1.
+    let length = |header: &[u8]| if header.len() >= 4 && header[..4] == [0, 0, 0, 1] { 16 } else { 8 };
+    let end = self.position + data.len() as u64;
+    while let Some(next) = self.next_box.filter(|&next| next < end) {
+      // A header split between two reads continues at the start of this one.
+      let mut from = (next + self.header.len() as u64 - self.position) as usize;
+      while self.header.len() < length(&self.header) && from < data.len() {
+        self.header.push(data[from]);
+        from += 1;
+      }
+      let length = length(&self.header);
+      if self.header.len() < length {
+        break;
+      }
+      let kind: [u8; 4] = self.header[4..8].try_into().expect("8-byte header");
+      let size = match u32::from_be_bytes(self.header[..4].try_into().expect("8-byte header")) {
+        1 => u64::from_be_bytes(self.header[8..16].try_into().expect("16-byte header")),
+        size => size as u64,
+      };
+      if next == 0 && !LEADING_BOXES.contains(&&kind) {
+        self.next_box = None;
+      } else if &kind == b"moov" || &kind == b"mdat" {
+        self.first = Some(kind);
+        self.next_box = None;
+      } else if size < length as u64 {
+        // A box running to the end of the input (size 0) or a broken one: nothing to follow.
+        self.next_box = None;
+      } else {
+        self.next_box = Some(next + size);
+      }
+      self.header.clear();
+    }
+    self.position = end;
+  }
+
+  fn moov_after_mdat(&self) -> bool {
+    self.first == Some(*b"mdat")
+  }
+}
+
+unsafe extern "C" fn read_packet(opaque: *mut c_void, buffer: *mut u8, size: c_int) -> c_int {
+  let reader = &mut *(opaque as *mut Reader);
+  let buffer = std::slice::from_raw_parts_mut(buffer, size.max(0) as usize);
+  loop {
+    match reader.stdin.read(buffer) {
+      Ok(0) => return AVERROR_EOF,
+      Ok(read) => {
+        reader.scan(&buffer[..read]);
+        return read as c_int;
+      }
+      Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+      Err(_) => return AVERROR(EIO),
+    }
+  }
+}
+
+// Open stdin as the input. stdin can only be read once, so the reader and its I/O context live for
+// the rest of the process; closing the input leaves a custom I/O context alone.
+pub fn open() -> Result<ffmpeg::format::context::Input, AnalyzeError> {
+  if std::io::stdin().is_terminal() {
+    return Err(AnalyzeError::InvalidArgument("- reads the input from stdin, which is a terminal; pipe the input in".to_string()));
+  }
+  let reader = Box::leak(Box::new(Reader {
+    stdin: std::io::stdin(),
+    position: 0,
+    next_box: Some(0),
+    header: Vec::with_capacity(16),
+    first: None,
+  }));
+  unsafe {
+    let buffer = av_malloc(BUFFER_SIZE) as *mut u8;
+    let pb = avio_alloc_context(buffer, BUFFER_SIZE as c_int, 0, reader as *mut Reader as *mut c_void,
+      Some(read_packet), None, None);
+    let mut ctx = avformat_alloc_context();
+    if buffer.is_null() || pb.is_null() || ctx.is_null() {
+      return Err(AnalyzeError::OpenError(ffmpeg::Error::Other { errno: ENOMEM }));
+    }
+    (*ctx).pb = pb;
+    (*ctx).flags |= AVFMT_FLAG_CUSTOM_IO;
+    // avformat_open_input frees the context when it fails.
+    let mut result = avformat_open_input(&mut ctx, c"pipe:".as_ptr(), std::ptr::null(), std::ptr::null_mut());
+    if result >= 0 {
+      result = avformat_find_stream_info(ctx, std::ptr::null_mut());
+      if result < 0 {
+        avformat_close_input(&mut ctx);
+      }
+    }
+    if reader.moov_after_mdat() {
+      if result >= 0 {
+        avformat_close_input(&mut ctx);
+      }
+      return Err(AnalyzeError::MoovNotAtStart);
+    }
+    if result < 0 {
+      return Err(AnalyzeError::OpenError(ffmpeg::Error::from(result)));
+    }
+    Ok(ffmpeg::format::context::Input::wrap(ctx))
+  }
+}
@@ -0,0 +1,130 @@
+// Pixel format of the video: its name, bits per component and chroma subsampling, as the opened
+// decoder declares it and as the frames decode. Some streams renegotiate the format after an SPS
+// change, so every decoded frame's format is compared with the first one's.
+
+use ffmpeg::ffi::{AV_PIX_FMT_FLAG_HWACCEL, AV_PIX_FMT_FLAG_RGB};
+use ffmpeg::format::Pixel;
+use ffmpeg::Rational;
+
+use crate::report::{PixelFormatChangeReport, PixelFormatReport};
+use crate::timestamp::{self, Timestamp};
+use crate::{format_with_commas, hwaccel, BLUE_BOLD, RESET};
+
+// Name of the format, or "unknown" for the formats FFmpeg has no descriptor for (e.g. none).
+pub fn name(format: Pixel) -> &'static str {
+  format.descriptor().map_or("unknown", |d| d.name())
+}
+
+// Bits per component of the first (luma or red) component. None for hardware and unknown formats.
+pub fn bit_depth(format: Pixel) -> Option<u32> {
+  let descriptor = format.descriptor()?;
+  let d = unsafe { &*descriptor.as_ptr() };
+  (d.flags & AV_PIX_FMT_FLAG_HWACCEL == 0).then(|| d.comp[0].depth.max(0) as u32)
+}
+
+// J:a:b notation of the chroma subsampling; 4:0:0 for formats without chroma. None for hardware
+// and unknown formats.
+pub fn subsampling(format: Pixel) -> Option<&'static str> {
+  let descriptor = format.descriptor()?;
+  let d = unsafe { &*descriptor.as_ptr() };
+  if d.flags & AV_PIX_FMT_FLAG_HWACCEL != 0 {
+    return None;
+  }
+  // Gray formats, with or without alpha.
+  if d.flags & AV_PIX_FMT_FLAG_RGB == 0 && d.nb_components < 3 {
+    return Some("4:0:0");
+  }
+  match (d.log2_chroma_w, d.log2_chroma_h) {
+    (0, 0) => Some("4:4:4"),
+    (0, 1) => Some("4:4:0"),
+    (1, 0) => Some("4:2:2"),
+    (1, 1) => Some("4:2:0"),
+    (2, 0) => Some("4:1:1"),
+    (2, 2) => Some("4:1:0"),
+    _ => None,
+  }
+}
+
+// Format of the decoded samples. Hardware frames stand for the software format the decoder
+// negotiated for the device.
+pub fn of_frame(decoder: &ffmpeg::decoder::Video, frame: &ffmpeg::util::frame::video::Video) -> Pixel {
+  if hwaccel::is_hardware(frame) {
+    Pixel::from(unsafe { (*decoder.as_ptr()).sw_pix_fmt })
+  } else {
+    frame.format()
+  }
+}
+
+// A decoded frame whose format differs from the previous frame's.
+pub struct Change {
+  pub frame: i64,
+  pub timestamp: Option<i64>,
+  pub from: Pixel,
+  pub to: Pixel,
+}
+
+#[derive(Default)]
+pub struct Tracker {
+  pub first: Option<Pixel>,
+  last: Option<Pixel>,
+  pub changes: Vec<Change>,
+}
+
+impl Tracker {
+  pub fn push(&mut self, format: Pixel, frame: i64, timestamp: Option<i64>) {
+    match self.last {
+      None => self.first = Some(format),
+      Some(last) if last != format => self.changes.push(Change { frame, timestamp, from: last, to: format }),
+      Some(_) => {}
+    }
+    self.last = Some(format);
+  }
+}
+
+fn describe(format: Pixel) -> String {
+  match (bit_depth(format), subsampling(format)) {
+    (Some(depth), Some(subsampling)) => format!("{} ({}-bit {})", name(format), depth, subsampling),
+    _ => name(format).to_string(),
+  }
+}
+
+pub fn to_report(tracker: &Tracker, opened: Pixel, time_base: Rational) -> PixelFormatReport {
+  PixelFormatReport {
+    declared: name(opened),
+    decoded: tracker.first.map(name),
+    bits_per_component: tracker.first.and_then(bit_depth),
+    chroma_subsampling: tracker.first.and_then(subsampling),
+    changes: tracker.changes.iter().map(|change| PixelFormatChangeReport {
+      frame: change.frame,
+      timestamp: change.timestamp,
+      timestamp_ms: change.timestamp.and_then(|ts| Timestamp::new(ts, time_base).signed_millis()),
+      timestamp_raw: change.timestamp.map(|ts| Timestamp::new(ts, time_base)),
+      from: name(change.from),
+      to: name(change.to),
+    }).collect(),
+  }
+}
+
+// Print the pixel format section: the format the opened decoder declared, the one of the first
+// decoded frame and every change after it.
+pub fn report(tracker: &Tracker, opened: Pixel, time_base: Rational, verbose: bool) {
+  out!("{}{}Pixel format - {}", RESET, BLUE_BOLD, RESET);
+  out!("Declared pixel format (decoder): {}", describe(opened));
+  let Some(first) = tracker.first else {
+    out!("Decoded pixel format: unknown (no frames decoded)");
+    return;
+  };
+  out!("Decoded pixel format: {}", name(first));
+  out!("Bits per component: {}", bit_depth(first).map_or_else(|| "unknown".to_string(), |d| d.to_string()));
+  out!("Chroma subsampling: {}", subsampling(first).unwrap_or("unknown"));
+  if tracker.changes.is_empty() {
+    out!("Pixel format changes: none");
+    return;
+  }
+  out!("Pixel format changes: {}", format_with_commas(tracker.changes.len() as i64));
+  for change in &tracker.changes {
+    out!("  Frame {} ({}): {} to {}", format_with_commas(change.frame),
+      timestamp::describe_optional(change.timestamp.map(|ts| Timestamp::new(ts, time_base)), verbose),
+      describe(change.from), describe(change.to));
+  }
+}
@@ -0,0 +1,177 @@
+// What decoded sample values mean: the declared color range, the bit depth and the transfer
+// characteristic. Worked out once from the first decoded frame, so every pixel-level check reads
+// its thresholds from the same place instead of assuming 8-bit limited range.
+
+use ffmpeg::color;
+use ffmpeg::format::Pixel;
+
+use crate::findings::{self, Finding};
+use crate::timestamp::Timestamp;
+
+// Share of a frame's luma samples that may sit outside the declared range before the frame
+// counts as a violation. Ringing around sharp edges overshoots limited range a little.
+const VIOLATION_SHARE: f64 = 0.01;
+
+// Rows and columns skipped between scanned luma samples with --pixel-quality fast.
+pub const FAST_SAMPLE_STEP: usize = 4;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Range {
+  // 16-235 luma at 8 bits, "tv".
+  Limited,
+  // 0-255 luma at 8 bits, "pc".
+  Full,
+  // Not flagged; players assume limited, so the checks do too.
+  Unspecified,
+}
+
+#[derive(Clone, Copy)]
+pub struct PixelSemantics {
+  pub range: Range,
+  pub bit_depth: u32,
+  pub transfer: color::TransferCharacteristic,
+  pub format: Pixel,
+}
+
+impl PixelSemantics {
+  pub fn from_frame(frame: &ffmpeg::util::frame::video::Video) -> Self {
+    let range = match frame.color_range() {
+      color::Range::MPEG => Range::Limited,
+      color::Range::JPEG => Range::Full,
+      _ => Range::Unspecified,
+    };
+    let bit_depth = frame.format().descriptor()
+      .map_or(8, |d| unsafe { (*d.as_ptr()).comp[0].depth.max(1) as u32 });
+    PixelSemantics { range, bit_depth, transfer: frame.color_transfer_characteristic(), format: frame.format() }
+  }
+
+  // Scale an 8-bit sample value to this bit depth.
+  pub fn scale(&self, value: u32) -> u32 {
+    value << self.bit_depth.saturating_sub(8)
+  }
+
+  // Lowest and highest luma value of the declared range.
+  pub fn luma_limits(&self) -> (u32, u32) {
+    match self.range {
+      Range::Full => (0, (1 << self.bit_depth) - 1),
+      Range::Limited | Range::Unspecified => (self.scale(16), self.scale(235)),
+    }
+  }
+
+  // Bytes per luma sample when the luma plane can be scanned directly: planar YUV in native
+  // little-endian order, one byte up to 8 bits and two above. None for RGB, packed, palette,
+  // bitstream and hardware formats.
+  pub fn luma_sample_bytes(&self) -> Option<usize> {
+    let descriptor = self.format.descriptor()?;
+    let d = unsafe { &*descriptor.as_ptr() };
+    let excluded = ffmpeg::ffi::AV_PIX_FMT_FLAG_BE | ffmpeg::ffi::AV_PIX_FMT_FLAG_PAL
+      | ffmpeg::ffi::AV_PIX_FMT_FLAG_BITSTREAM | ffmpeg::ffi::AV_PIX_FMT_FLAG_HWACCEL | ffmpeg::ffi::AV_PIX_FMT_FLAG_RGB;
+    if d.flags & excluded != 0 || d.flags & ffmpeg::ffi::AV_PIX_FMT_FLAG_PLANAR == 0 || d.comp[0].plane != 0 {
+      return None;
+    }
+    match (d.comp[0].step, self.bit_depth) {
+      (1, depth) if depth <= 8 => Some(1),
+      (2, depth) if depth > 8 && depth <= 16 => Some(2),
+      _ => None,
+    }
+  }
+}
+
+fn describe_range(range: Range) -> &'static str {
+  match range {
+    Range::Limited => "limited (tv)",
+    Range::Full => "full (pc)",
+    Range::Unspecified => "unspecified (treated as limited)",
+  }
+}
+
+// Frames whose luma samples leave the declared range.
+#[derive(Default)]
+pub struct RangeCheck {
+  pub semantics: Option<PixelSemantics>,
+  // False when the pixel format can't be scanned.
+  pub scanned: bool,
+  pub frames_checked: u64,
+  pub violating_frames: u64,
+  pub min_seen: Option<u32>,
+  pub max_seen: Option<u32>,
+  // Timestamp of the first violating frame, stream time base.
+  pub first_violation: Option<i64>,
+}
+
+impl RangeCheck {
+  // Scan the luma plane of one frame, every `step`th row and column.
+  pub fn check(&mut self, frame: &ffmpeg::util::frame::video::Video, step: usize) {
+    let semantics = *self.semantics.get_or_insert_with(|| PixelSemantics::from_frame(frame));
+    let Some(bytes) = semantics.luma_sample_bytes() else {
+      return;
+    };
+    self.scanned = true;
+    let (low, high) = semantics.luma_limits();
+    let data = frame.data(0);
+    let stride = frame.stride(0);
+    let (width, height) = (frame.plane_width(0) as usize, frame.plane_height(0) as usize);
+
+    let (mut total, mut outside) = (0u64, 0u64);
+    let (mut min, mut max) = (u32::MAX, 0u32);
+    for row in (0..height).step_by(step.max(1)) {
+      let line = &data[row * stride..];
+      for column in (0..width).step_by(step.max(1)) {
+        let value = match bytes {
+          1 => line[column] as u32,
+          _ => u16::from_le_bytes([line[column * 2], line[column * 2 + 1]]) as u32,
+        };
+        min = min.min(value);
+        max = max.max(value);
+        total += 1;
+        if value < low || value > high {
+          outside += 1;
+        }
+      }
+    }
+    if total == 0 {
+      return;
+    }
+    self.frames_checked += 1;
+    self.min_seen = Some(self.min_seen.map_or(min, |m| m.min(min)));
+    self.max_seen = Some(self.max_seen.map_or(max, |m| m.max(max)));
+    if outside as f64 > total as f64 * VIOLATION_SHARE {
+      self.violating_frames += 1;
+      if self.first_violation.is_none() {
+        self.first_violation = frame.timestamp();
+      }
+    }
+  }
+}
+
+// Print the pixel semantics section and return a finding when frames have luma outside the declared
+// range, located at the first of them.
+pub fn report(check: &RangeCheck, time_base: ffmpeg::Rational) -> Option<Finding> {
+  let semantics = check.semantics?;
+  out!("{}{}Pixel semantics - {}", crate::RESET, crate::BLUE_BOLD, crate::RESET);
+  let (low, high) = semantics.luma_limits();
+  out!("Color range: {}", describe_range(semantics.range));
+  out!("Bit depth: {}", semantics.bit_depth);
+  out!("Transfer: {}", semantics.transfer.name().unwrap_or("unspecified"));
+  out!("Luma range: {} - {}", low, high);
+  if !check.scanned {
+    out!("Sample values: not checked for pixel format {:?}", semantics.format);
+    return None;
+  }
+  if let (Some(min), Some(max)) = (check.min_seen, check.max_seen) {
+    out!("Luma seen: {} - {}", min, max);
+  }
+  out!("Frames outside the declared range: {} of {}", crate::format_with_commas(check.violating_frames as i64),
+    crate::format_with_commas(check.frames_checked as i64));
+  if check.violating_frames == 0 {
+    return None;
+  }
+  let finding = Finding::new(&findings::RANGE_VIOLATION, format!(
+    "{} of {} frames have luma outside the declared range {}-{} (seen {}-{})",
+    crate::format_with_commas(check.violating_frames as i64), crate::format_with_commas(check.frames_checked as i64),
+    low, high, check.min_seen.unwrap_or(0), check.max_seen.unwrap_or(0)));
+  Some(match check.first_violation {
+    Some(first) => finding.at(Timestamp::new(first, time_base)),
+    None => finding,
+  })
+}
@@ -0,0 +1,475 @@
+// Codec profile and level: the names for H.264, HEVC, AV1, VP9 and AAC, and with
+// --require-profile a check of the analyzed streams against a target such as h264:high@4.1. A
+// stream fails with a higher profile or level than the target, or when its resolution and frame
+// rate exceed the limits of the level it declares, which encoders sometimes get wrong. Other codecs
+// are listed with their numeric values.
+
+use ffmpeg::codec::Id;
+
+use crate::findings::{self, Finding};
+use crate::{format_with_commas, BLUE_BOLD, RESET};
+
+// FF_PROFILE_UNKNOWN and FF_LEVEL_UNKNOWN.
+const UNKNOWN: i32 = -99;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Family {
+  H264,
+  Hevc,
+  Av1,
+  Vp9,
+  Aac,
+}
+
+impl Family {
+  fn of(id: Id) -> Option<Self> {
+    match id {
+      Id::H264 => Some(Family::H264),
+      Id::HEVC => Some(Family::Hevc),
+      Id::AV1 => Some(Family::Av1),
+      Id::VP9 => Some(Family::Vp9),
+      Id::AAC => Some(Family::Aac),
+      _ => None,
+    }
+  }
+
+  fn parse(name: &str) -> Option<Self> {
+    match name {
+      "h264" | "avc" => Some(Family::H264),
+      "hevc" | "h265" => Some(Family::Hevc),
+      "av1" => Some(Family::Av1),
+      "vp9" => Some(Family::Vp9),
+      "aac" => Some(Family::Aac),
+      _ => None,
+    }
+  }
+
+  pub fn name(self) -> &'static str {
+    match self {
+      Family::H264 => "h264",
+      Family::Hevc => "hevc",
+      Family::Av1 => "av1",
+      Family::Vp9 => "vp9",
+      Family::Aac => "aac",
+    }
+  }
+}
+
+// Profile names with the specification names, the names --require-profile takes and their rank:
+// a profile is within a target of the same family when its rank isn't higher. Profiles without a
+// rank only match themselves.
+const PROFILES: &[(Family, i32, &str, &str, Option<u8>)] = &[
+  (Family::H264, 578, "Constrained Baseline", "constrained-baseline", Some(0)),
+  (Family::H264, 66, "Baseline", "baseline", Some(1)),
+  (Family::H264, 77, "Main", "main", Some(2)),
+  (Family::H264, 88, "Extended", "extended", None),
+  (Family::H264, 100, "High", "high", Some(3)),
+  (Family::H264, 110, "High 10", "high10", Some(4)),
+  (Family::H264, 2158, "High 10 Intra", "high10-intra", Some(4)),
+  (Family::H264, 122, "High 4:2:2", "high422", Some(5)),
+  (Family::H264, 2170, "High 4:2:2 Intra", "high422-intra", Some(5)),
+  (Family::H264, 244, "High 4:4:4 Predictive", "high444", Some(6)),
+  (Family::H264, 2292, "High 4:4:4 Intra", "high444-intra", Some(6)),
+  (Family::H264, 44, "CAVLC 4:4:4 Intra", "cavlc444", Some(6)),
+  (Family::H264, 118, "Multiview High", "multiview-high", None),
+  (Family::H264, 128, "Stereo High", "stereo-high", None),
+  (Family::Hevc, 3, "Main Still Picture", "main-still", Some(0)),
+  (Family::Hevc, 1, "Main", "main", Some(1)),
+  (Family::Hevc, 2, "Main 10", "main10", Some(2)),
+  (Family::Hevc, 4, "Format Range Extensions", "rext", Some(3)),
+  (Family::Hevc, 5, "High Throughput", "high-throughput", None),
+  (Family::Hevc, 9, "Screen Content Coding", "scc", None),
+  (Family::Av1, 0, "Main", "main", Some(0)),
+  (Family::Av1, 1, "High", "high", Some(1)),
+  (Family::Av1, 2, "Professional", "professional", Some(2)),
+  (Family::Vp9, 0, "Profile 0", "0", Some(0)),
+  (Family::Vp9, 1, "Profile 1", "1", Some(1)),
+  (Family::Vp9, 2, "Profile 2", "2", Some(2)),
+  (Family::Vp9, 3, "Profile 3", "3", Some(3)),
+  (Family::Aac, 1, "LC", "lc", Some(0)),
+  (Family::Aac, 4, "HE-AAC", "he", Some(1)),
+  (Family::Aac, 28, "HE-AACv2", "hev2", Some(2)),
+  (Family::Aac, 0, "Main", "main", None),
+  (Family::Aac, 2, "SSR", "ssr", None),
+  (Family::Aac, 3, "LTP", "ltp", None),
+  (Family::Aac, 22, "LD", "ld", None),
+  (Family::Aac, 38, "ELD", "eld", None),
+];
+
+// Levels are compared as major * 100 + minor * 10; H.264's level 1b sits between 1 and 1.1.
+const LEVEL_1B: u32 = 105;
+
+fn level_key(major: u32, minor: u32) -> u32 {
+  major * 100 + minor * 10
+}
+
+fn describe_level(key: u32) -> String {
+  match key {
+    LEVEL_1B => "1b".to_string(),
+    _ if key.is_multiple_of(100) => format!("{}", key / 100),
+    _ => format!("{}.{}", key / 100, key % 100 / 10),
+  }
+}
+
+fn parse_level(text: &str) -> Option<u32> {
+  if text == "1b" {
+    return Some(LEVEL_1B);
+  }
+  let (major, minor) = text.split_once('.').unwrap_or((text, "0"));
+  let (major, minor) = (major.parse::<u32>().ok()?, minor.parse::<u32>().ok()?);
+  (minor < 10).then(|| level_key(major, minor))
+}
+
+// The level of the codec's own numbering.
+fn level_of(family: Family, level: i32) -> Option<u32> {
+  if level < 0 {
+    return None;
+  }
+  let level = level as u32;
+  match family {
+    Family::H264 if level == 9 => Some(LEVEL_1B),
+    // level_idc, 10 times the level.
+    Family::H264 | Family::Vp9 => Some(level_key(level / 10, level % 10)),
+    // general_level_idc, 30 times the level.
+    Family::Hevc => Some(level_key(level / 30, level % 30 / 3)),
+    // seq_level_idx 31 leaves the level unconstrained.
+    Family::Av1 if level >= 31 => None,
+    Family::Av1 => Some(level_key(2 + (level >> 2), level & 3)),
+    Family::Aac => None,
+  }
+}
+
+// Largest picture and sample rate of each level: macroblocks and macroblocks per second for H.264
+// (Table A-1), luma samples and luma samples per second for the others (HEVC Table A.8, AV1 Annex
+// A.3, the VP9 level definitions).
+const LIMITS: &[(Family, u32, u64, u64)] = &[
+  (Family::H264, 100, 99, 1_485),
+  (Family::H264, LEVEL_1B, 99, 1_485),
+  (Family::H264, 110, 396, 3_000),
+  (Family::H264, 120, 396, 6_000),
+  (Family::H264, 130, 396, 11_880),
+  (Family::H264, 200, 396, 11_880),
+  (Family::H264, 210, 792, 19_800),
+  (Family::H264, 220, 1_620, 20_250),
+  (Family::H264, 300, 1_620, 40_500),
+  (Family::H264, 310, 3_600, 108_000),
+  (Family::H264, 320, 5_120, 216_000),
+  (Family::H264, 400, 8_192, 245_760),
+  (Family::H264, 410, 8_192, 245_760),
+  (Family::H264, 420, 8_704, 522_240),
+  (Family::H264, 500, 22_080, 589_824),
+  (Family::H264, 510, 36_864, 983_040),
+  (Family::H264, 520, 36_864, 2_073_600),
+  (Family::H264, 600, 139_264, 4_177_920),
+  (Family::H264, 610, 139_264, 8_355_840),
+  (Family::H264, 620, 139_264, 16_711_680),
+  (Family::Hevc, 100, 36_864, 552_960),
+  (Family::Hevc, 200, 122_880, 3_686_400),
+  (Family::Hevc, 210, 245_760, 7_372_800),
+  (Family::Hevc, 300, 552_960, 16_588_800),
+  (Family::Hevc, 310, 983_040, 33_177_600),
+  (Family::Hevc, 400, 2_228_224, 66_846_720),
+  (Family::Hevc, 410, 2_228_224, 133_693_440),
+  (Family::Hevc, 500, 8_912_896, 267_386_880),
+  (Family::Hevc, 510, 8_912_896, 534_773_760),
+  (Family::Hevc, 520, 8_912_896, 1_069_547_520),
+  (Family::Hevc, 600, 35_651_584, 1_069_547_520),
+  (Family::Hevc, 610, 35_651_584, 2_139_095_040),
+  (Family::Hevc, 620, 35_651_584, 4_278_190_080),
+  (Family::Av1, 200, 147_456, 4_423_680),
+  (Family::Av1, 210, 278_784, 8_363_520),
+  (Family::Av1, 300, 665_856, 19_975_680),
+  (Family::Av1, 310, 1_065_024, 31_950_720),
+  (Family::Av1, 400, 2_359_296, 70_778_880),
+  (Family::Av1, 410, 2_359_296, 141_557_760),
+  (Family::Av1, 500, 8_912_896, 267_386_880),
+  (Family::Av1, 510, 8_912_896, 534_773_760),
+  (Family::Av1, 520, 8_912_896, 1_069_547_520),
+  (Family::Av1, 530, 8_912_896, 1_069_547_520),
+  (Family::Av1, 600, 35_651_584, 1_069_547_520),
+  (Family::Av1, 610, 35_651_584, 2_139_095_040),
+  (Family::Av1, 620, 35_651_584, 4_278_190_080),
+  (Family::Av1, 630, 35_651_584, 4_278_190_080),
+  (Family::Vp9, 100, 36_864, 829_440),
+  (Family::Vp9, 110, 73_728, 2_764_800),
+  (Family::Vp9, 200, 122_880, 4_608_000),
+  (Family::Vp9, 210, 245_760, 9_216_000),
+  (Family::Vp9, 300, 552_960, 20_736_000),
+  (Family::Vp9, 310, 983_040, 36_864_000),
+  (Family::Vp9, 400, 2_228_224, 83_558_400),
+  (Family::Vp9, 410, 2_228_224, 160_432_128),
+  (Family::Vp9, 500, 8_912_896, 311_951_360),
+  (Family::Vp9, 510, 8_912_896, 588_251_136),
+  (Family::Vp9, 520, 8_912_896, 1_176_502_272),
+  (Family::Vp9, 600, 35_651_584, 1_176_502_272),
+  (Family::Vp9, 610, 35_651_584, 2_353_004_544),
+  (Family::Vp9, 620, 35_651_584, 4_706_009_088),
+];
+
+// The profile and level a stream declares.
+pub struct Declared {
+  pub index: usize,
+  pub codec: String,
+  pub family: Option<Family>,
+  // Raw values of the codec parameters; -99 when unknown.
+  pub profile: i32,
+  pub level: i32,
+}
+
+impl Declared {
+  pub fn of_stream(stream: &ffmpeg::format::stream::Stream) -> Self {
+    let parameters = stream.parameters();
+    let (profile, level) = unsafe { ((*parameters.as_ptr()).profile, (*parameters.as_ptr()).level) };
+    Declared {
+      index: stream.index(),
+      codec: parameters.id().name().to_string(),
+      family: Family::of(parameters.id()),
+      profile,
+      level,
+    }
+  }
+
+  fn profile_entry(&self) -> Option<&'static (Family, i32, &'static str, &'static str, Option<u8>)> {
+    let family = self.family?;
+    PROFILES.iter().find(|(f, value, ..)| *f == family && *value == self.profile)
+  }
+
+  pub fn profile_name(&self) -> Option<&'static str> {
+    self.profile_entry().map(|(_, _, name, ..)| *name)
+  }
+
+  fn level_key(&self) -> Option<u32> {
+    level_of(self.family?, self.level)
+  }
+
+  pub fn level_name(&self) -> Option<String> {
+    self.level_key().map(describe_level)
+  }
+
+  // "High@4.1", "LC", or the raw values for codecs without names.
+  pub fn describe(&self) -> String {
+    let raw = |value: i32| if value == UNKNOWN { "unknown".to_string() } else { value.to_string() };
+    match (self.family, self.profile_name()) {
+      (Some(_), Some(profile)) => match self.level_name() {
+        Some(level) => format!("{}@{}", profile, level),
+        None => profile.to_string(),
+      },
+      (Some(_), None) => format!("profile {}, level {} (unknown profile)", raw(self.profile), raw(self.level)),
+      (None, _) => format!("profile {}, level {} (no names known for {})", raw(self.profile), raw(self.level), self.codec),
+    }
+  }
+}
+
+// A --require-profile target.
+#[derive(Clone, Copy, Debug)]
+pub struct Requirement {
+  pub family: Family,
+  profile: &'static str,
+  rank: Option<u8>,
+  level: Option<u32>,
+}
+
+impl Requirement {
+  // "h264:high@4.1", "hevc:main10@5.1" or "aac:lc".
+  pub fn parse(spec: &str) -> Result<Self, String> {
+    let spec = spec.to_ascii_lowercase();
+    let (codec, rest) = spec.split_once(':').ok_or("expected CODEC:PROFILE[@LEVEL], e.g. h264:high@4.1")?;
+    let family = Family::parse(codec).ok_or_else(|| format!("unknown codec '{}', expected h264, hevc, av1, vp9 or aac", codec))?;
+    let (profile, level) = match rest.split_once('@') {
+      Some((profile, level)) => (profile, Some(parse_level(level).ok_or_else(|| format!("invalid level '{}'", level))?)),
+      None => (rest, None),
+    };
+    let entry = PROFILES.iter().find(|(f, _, _, key, _)| *f == family && *key == profile).ok_or_else(|| {
+      let names: Vec<_> = PROFILES.iter().filter(|(f, ..)| *f == family).map(|(_, _, _, key, _)| *key).collect();
+      format!("unknown {} profile '{}', expected one of {}", family.name(), profile, names.join(", "))
+    })?;
+    if level.is_some() && family == Family::Aac {
+      return Err("aac has no levels".to_string());
+    }
+    Ok(Requirement { family, profile: entry.2, rank: entry.4, level })
+  }
+
+  pub fn describe(&self) -> String {
+    match self.level {
+      Some(level) => format!("{} {}@{}", self.family.name(), self.profile, describe_level(level)),
+      None => format!("{} {}", self.family.name(), self.profile),
+    }
+  }
+
+  // Why the stream doesn't meet the requirement; empty when it does.
+  fn violations(&self, stream: &Declared) -> Vec<String> {
+    let mut violations = Vec::new();
+    if stream.family != Some(self.family) {
+      violations.push(format!("stream #{} is {}, not {}", stream.index, stream.codec, self.family.name()));
+      return violations;
+    }
+    match stream.profile_entry() {
+      None => violations.push(format!("stream #{} has an unknown profile ({})", stream.index, stream.profile)),
+      Some(&(_, _, name, _, rank)) => {
+        let within = name == self.profile || matches!((rank, self.rank), (Some(rank), Some(target)) if rank <= target);
+        if !within {
+          violations.push(format!("stream #{} is {}, above {}", stream.index, name, self.profile));
+        }
+      }
+    }
+    if let Some(target) = self.level {
+      match stream.level_key() {
+        None => violations.push(format!("stream #{} declares no level", stream.index)),
+        Some(level) if level > target => violations.push(format!("stream #{} is level {}, above {}", stream.index,
+          describe_level(level), describe_level(target))),
+        Some(_) => {}
+      }
+    }
+    violations
+  }
+}
+
+// Why a picture of `width` x `height` at `fps` doesn't fit the limits of `level`; None when it fits
+// or the limits aren't known.
+fn exceeds_level(family: Family, level: u32, width: u32, height: u32, fps: f64) -> Option<String> {
+  let &(_, _, max_picture, max_rate) = LIMITS.iter().find(|(f, l, ..)| *f == family && *l == level)?;
+  let (picture, unit) = match family {
+    Family::H264 => (width.div_ceil(16) as u64 * height.div_ceil(16) as u64, "macroblocks"),
+    _ => (width as u64 * height as u64, "luma samples"),
+  };
+  let rate = (picture as f64 * fps) as u64;
+  if picture > max_picture {
+    Some(format!("{}x{} is {} {}, level {} allows {}", width, height, format_with_commas(picture as i64), unit,
+      describe_level(level), format_with_commas(max_picture as i64)))
+  } else if rate > max_rate {
+    Some(format!("{}x{} at {:.3} fps is {} {} per second, level {} allows {}", width, height, fps,
+      format_with_commas(rate as i64), unit, describe_level(level), format_with_commas(max_rate as i64)))
+  } else {
+    None
+  }
+}
+
+// A PROFILE_REQUIREMENT finding for the violations report returned; None when there are none.
+pub fn finding(requirement: &Requirement, violations: &[String]) -> Option<Finding> {
+  (!violations.is_empty()).then(|| Finding::new(&findings::PROFILE_REQUIREMENT,
+    format!("not {}: {}", requirement.describe(), violations.join("; "))))
+}
+
+// The analyzed video stream, with what its level limits are checked against.
+pub struct Video<'a> {
+  pub declared: &'a Declared,
+  pub width: u32,
+  pub height: u32,
+  pub fps: Option<f64>,
+}
+
+// Print the profile and level of every stream that has one, the level limits of the analyzed video
+// stream and, with a requirement, whether the streams of its codec meet it. Returns the violations.
+pub fn report(video: &Video, audio: &[Declared], requirement: Option<&Requirement>) -> Vec<String> {
+  out!("{}{}Profile and level - {}", RESET, BLUE_BOLD, RESET);
+  for declared in std::iter::once(video.declared).chain(audio) {
+    out!("#{} {}: {}", declared.index, declared.codec, declared.describe());
+  }
+  let limits = video.declared.family.zip(video.declared.level_key()).zip(video.fps)
+    .map(|((family, level), fps)| exceeds_level(family, level, video.width, video.height, fps));
+  match &limits {
+    Some(Some(reason)) => out!("Level limits: EXCEEDED, {}", reason),
+    Some(None) => out!("Level limits: within the declared level"),
+    None => {}
+  }
+
+  let Some(requirement) = requirement else {
+    return Vec::new();
+  };
+  // A video requirement applies to the analyzed stream, an audio one to every audio stream.
+  let streams: Vec<&Declared> = if requirement.family == Family::Aac { audio.iter().collect() } else { vec![video.declared] };
+  let mut violations: Vec<String> = streams.iter().flat_map(|stream| requirement.violations(stream)).collect();
+  if streams.is_empty() {
+    violations.push("no audio stream to check".to_string());
+  }
+  if requirement.family != Family::Aac {
+    if let Some(Some(reason)) = limits {
+      violations.push(format!("stream #{} exceeds its declared level: {}", video.declared.index, reason));
+    }
+  }
+  out!("Required profile: {} - {}", requirement.describe(), if violations.is_empty() { "PASS" } else { "FAIL" });
+  for violation in &violations {
+    out!("  {}", violation);
+  }
+  violations
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn declared(family: Family, profile: i32, level: i32) -> Declared {
+    Declared { index: 0, codec: family.name().to_string(), family: Some(family), profile, level }
+  }
+
+  #[test]
+  fn parse_valid_specs() {
+    let requirement = Requirement::parse("h264:high@4.1").unwrap();
+    assert_eq!((requirement.family, requirement.profile, requirement.rank, requirement.level),
+      (Family::H264, "High", Some(3), Some(410)));
+    assert_eq!(requirement.describe(), "h264 High@4.1");
+    let requirement = Requirement::parse("HEVC:Main10@5").unwrap();
+    assert_eq!((requirement.family, requirement.profile, requirement.level), (Family::Hevc, "Main 10", Some(500)));
+    assert_eq!(Requirement::parse("avc:baseline@1b").unwrap().level, Some(LEVEL_1B));
+    assert_eq!(Requirement::parse("h265:main").unwrap().level, None);
+    assert_eq!(Requirement::parse("aac:lc").unwrap().describe(), "aac LC");
+    assert_eq!(Requirement::parse("vp9:2@4.1").unwrap().profile, "Profile 2");
+    // Profiles without a rank only match themselves.
+    assert_eq!(Requirement::parse("h264:extended").unwrap().rank, None);
+  }
+
+  #[test]
+  fn parse_invalid_specs() {
+    assert_eq!(Requirement::parse("high@4.1").unwrap_err(), "expected CODEC:PROFILE[@LEVEL], e.g. h264:high@4.1");
+    assert_eq!(Requirement::parse("mpeg2:main").unwrap_err(), "unknown codec 'mpeg2', expected h264, hevc, av1, vp9 or aac");
+    assert_eq!(Requirement::parse("av1:ultra").unwrap_err(), "unknown av1 profile 'ultra', expected one of main, high, professional");
+    assert_eq!(Requirement::parse("h264:high@4.10").unwrap_err(), "invalid level '4.10'");
+    assert_eq!(Requirement::parse("h264:high@").unwrap_err(), "invalid level ''");
+    assert_eq!(Requirement::parse("h264:high@four").unwrap_err(), "invalid level 'four'");
+    assert_eq!(Requirement::parse("aac:lc@2").unwrap_err(), "aac has no levels");
+  }
+
+  #[test]
+  fn codec_levels() {
+    assert_eq!(level_of(Family::H264, 41), Some(410));
+    assert_eq!(level_of(Family::H264, 9), Some(LEVEL_1B));
+    assert_eq!(level_of(Family::Hevc, 123), Some(410));
+    assert_eq!(level_of(Family::Hevc, 150), Some(500));
+    assert_eq!(level_of(Family::Av1, 9), Some(410));
+    assert_eq!(level_of(Family::Av1, 31), None);
+    assert_eq!(level_of(Family::Vp9, 31), Some(310));
+    assert_eq!(level_of(Family::H264, UNKNOWN), None);
+    assert_eq!(level_of(Family::Aac, 2), None);
+    assert_eq!([100, LEVEL_1B, 410].map(describe_level), ["1", "1b", "4.1"]);
+    assert_eq!(declared(Family::H264, 100, 41).describe(), "High@4.1");
+    assert_eq!(declared(Family::Aac, 1, UNKNOWN).describe(), "LC");
+    assert_eq!(declared(Family::H264, 7, 41).describe(), "profile 7, level 41 (unknown profile)");
+  }
+
+  #[test]
+  fn violations_against_the_requirement() {
+    let requirement = Requirement::parse("h264:high@4.1").unwrap();
+    assert!(requirement.violations(&declared(Family::H264, 100, 41)).is_empty());
+    assert!(requirement.violations(&declared(Family::H264, 77, 31)).is_empty());
+    assert_eq!(requirement.violations(&declared(Family::H264, 110, 42)),
+      ["stream #0 is High 10, above High", "stream #0 is level 4.2, above 4.1"]);
+    assert_eq!(requirement.violations(&declared(Family::H264, 88, 30)), ["stream #0 is Extended, above High"]);
+    assert_eq!(requirement.violations(&declared(Family::H264, 100, UNKNOWN)), ["stream #0 declares no level"]);
+    assert_eq!(requirement.violations(&declared(Family::Hevc, 1, 123)), ["stream #0 is hevc, not h264"]);
+  }
+
+  #[test]
+  fn level_limits() {
+    // 1080p is 8,160 macroblocks, within level 4.1's 8,192; at 60 fps it exceeds 245,760 per second.
+    assert_eq!(exceeds_level(Family::H264, 410, 1920, 1080, 30.0), None);
+    assert_eq!(exceeds_level(Family::H264, 410, 1920, 1080, 60.0).unwrap(),
+      "1920x1080 at 60.000 fps is 489,600 macroblocks per second, level 4.1 allows 245,760");
+    assert_eq!(exceeds_level(Family::H264, 410, 3840, 2160, 30.0).unwrap(),
+      "3840x2160 is 32,400 macroblocks, level 4.1 allows 8,192");
+    assert_eq!(exceeds_level(Family::Hevc, 400, 1920, 1080, 30.0), None);
+    assert_eq!(exceeds_level(Family::Hevc, 400, 1920, 1080, 60.0).unwrap(),
+      "1920x1080 at 60.000 fps is 124,416,000 luma samples per second, level 4 allows 66,846,720");
+    // No limits are known for this level.
+    assert_eq!(exceeds_level(Family::H264, 990, 7680, 4320, 120.0), None);
+  }
+}
@@ -0,0 +1,198 @@
+// Progress display on stderr. The decode pass shows a bar with the percentage, elapsed time and
+// ETA, measured in media time when the container declares a duration and in bytes read when only
+// the input size is known. Without either (live streams, HTTP without Content-Length), the spinner
+// stays with the elapsed time and the frame count; for the shorter passes, it stays as it is.
+//
+// With --progress json nothing is drawn; the decode pass writes progress events to stderr instead,
+// one JSON object per line, at most EVENTS_PER_SECOND of them, and a done event at the end.
+
+use std::time::{Duration, Instant};
+
+use indicatif::{ProgressBar, ProgressStyle};
+use serde::Serialize;
+
+use crate::format_with_commas;
+
+// What the position of the bar counts.
+#[derive(Clone, Copy)]
+pub enum Unit {
+  // Milliseconds of video since the first video packet.
+  Millis,
+  // Input bytes read.
+  Bytes,
+  // Nothing: the length of the input is unknown and the spinner stays.
+  Elapsed,
+}
+
+// Frames decoded between two updates of the frame count next to the bar.
+const FRAMES_PER_UPDATE: i64 = 100;
+
+// The spinner shown until the decode pass starts, or a hidden bar when nothing should be drawn.
+pub fn spinner(show: bool) -> ProgressBar {
+  if !show {
+    return ProgressBar::hidden();
+  }
+  let pb = ProgressBar::new_spinner();
+  let style = ProgressStyle::default_spinner()
+    .tick_chars("/|\\- ")
+    .template("{spinner:.green} {msg}");
+  pb.set_style(style.unwrap());
+  pb
+}
+
+// Turn the spinner into a bar over the container duration, or over the input size when the
+// duration is unknown. Returns what the bar counts.
+pub fn start(pb: &ProgressBar, duration_ms: Option<u64>, size: Option<u64>) -> Unit {
+  let (length, unit) = match (duration_ms, size) {
+    (Some(ms), _) if ms > 0 => (ms, Unit::Millis),
+    (_, Some(bytes)) if bytes > 0 => (bytes, Unit::Bytes),
+    _ => {
+      // Ticking on its own, the spinner and the elapsed time keep moving while a read waits.
+      let style = ProgressStyle::default_spinner()
+        .tick_chars("/|\\- ")
+        .template("{spinner:.green} [{elapsed_precise}] {msg}");
+      pb.set_style(style.unwrap());
+      pb.enable_steady_tick(std::time::Duration::from_millis(250));
+      return Unit::Elapsed;
+    }
+  };
+  let style = ProgressStyle::default_bar()
+    .tick_chars("/|\\- ")
+    .template("{spinner:.green} [{elapsed_precise}] [{wide_bar:.cyan/blue}] {percent}% ETA {eta} {msg}");
+  pb.set_length(length);
+  pb.set_style(style.unwrap().progress_chars("=> "));
+  unit
+}
+
+// Show the frame count next to the bar every FRAMES_PER_UPDATE frames.
+pub fn frames(pb: &ProgressBar, frame_count: i64) {
+  if frame_count % FRAMES_PER_UPDATE == 0 {
+    pb.set_message(format!("{} frames", format_with_commas(frame_count)));
+  }
+}
+
+// Cap on the progress events written per second, however fast packets come.
+const EVENTS_PER_SECOND: u32 = 4;
+
+// Lets an event through once per interval.
+struct Throttle {
+  interval: Duration,
+  last: Option<Instant>,
+}
+
+impl Throttle {
+  fn new(per_second: u32) -> Self {
+    Throttle { interval: Duration::from_secs(1) / per_second.max(1), last: None }
+  }
+
+  // Whether an event is due at `now`; the first one always is.
+  fn due(&mut self, now: Instant) -> bool {
+    if self.last.is_some_and(|last| now.duration_since(last) < self.interval) {
+      return false;
+    }
+    self.last = Some(now);
+    true
+  }
+}
+
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum Event {
+  Progress {
+    frames: i64,
+    last_dts_ms: Option<i64>,
+    // Only when the bar runs over the container duration.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    percent: Option<f64>,
+  },
+  Done {
+    frames: i64,
+    last_dts_ms: Option<i64>,
+    elapsed_ms: u64,
+  },
+}
+
+// The JSON progress events of one decode pass.
+pub struct Events {
+  throttle: Throttle,
+  started: Instant,
+}
+
+impl Events {
+  pub fn start() -> Self {
+    Events { throttle: Throttle::new(EVENTS_PER_SECOND), started: Instant::now() }
+  }
+
+  // Write a progress event if one is due. The percentage is the bar's position over its length,
+  // when it counts milliseconds of the container duration.
+  pub fn progress(&mut self, pb: &ProgressBar, unit: Option<Unit>, frames: i64, last_dts_ms: Option<i64>) {
+    if !self.throttle.due(Instant::now()) {
+      return;
+    }
+    let percent = match (unit, pb.length()) {
+      (Some(Unit::Millis), Some(length)) if length > 0 => {
+        Some((pb.position() as f64 * 1000.0 / length as f64).round().min(1000.0) / 10.0)
+      }
+      _ => None,
+    };
+    write(&Event::Progress { frames, last_dts_ms, percent });
+  }
+
+  // Write the final event, past the throttle.
+  pub fn done(&self, frames: i64, last_dts_ms: Option<i64>) {
+    write(&Event::Done { frames, last_dts_ms, elapsed_ms: self.started.elapsed().as_millis() as u64 });
+  }
+}
+
+fn write(event: &Event) {
+  if let Ok(line) = serde_json::to_string(event) {
+    eprintln!("{}", line);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn one_event_per_interval() {
+    let mut throttle = Throttle::new(4);
+    let start = Instant::now();
+    assert!(throttle.due(start));
+    for ms in [0, 1, 100, 249] {
+      assert!(!throttle.due(start + Duration::from_millis(ms)), "{}ms", ms);
+    }
+    assert!(throttle.due(start + Duration::from_millis(250)));
+    assert!(!throttle.due(start + Duration::from_millis(499)));
+    assert!(throttle.due(start + Duration::from_millis(2000)));
+  }
+
+  #[test]
+  fn the_rate_caps_a_flood_of_packets() {
+    let mut throttle = Throttle::new(EVENTS_PER_SECOND);
+    let start = Instant::now();
+    // A million packets over ten seconds.
+    let events = (0..1_000_000u32).filter(|&i| throttle.due(start + Duration::from_micros(10) * i)).count();
+    assert_eq!(events, 10 * EVENTS_PER_SECOND as usize);
+  }
+
+  #[test]
+  fn a_zero_rate_is_one_per_second() {
+    let mut throttle = Throttle::new(0);
+    let start = Instant::now();
+    assert!(throttle.due(start));
+    assert!(!throttle.due(start + Duration::from_millis(999)));
+    assert!(throttle.due(start + Duration::from_secs(1)));
+  }
+
+  #[test]
+  fn event_lines() {
+    let progress = Event::Progress { frames: 12345, last_dts_ms: Some(678900), percent: Some(42.1) };
+    assert_eq!(serde_json::to_string(&progress).unwrap(),
+      r#"{"type":"progress","frames":12345,"last_dts_ms":678900,"percent":42.1}"#);
+    let unknown = Event::Progress { frames: 1, last_dts_ms: None, percent: None };
+    assert_eq!(serde_json::to_string(&unknown).unwrap(), r#"{"type":"progress","frames":1,"last_dts_ms":null}"#);
+    let done = Event::Done { frames: 250, last_dts_ms: Some(9960), elapsed_ms: 812 };
+    assert_eq!(serde_json::to_string(&done).unwrap(), r#"{"type":"done","frames":250,"last_dts_ms":9960,"elapsed_ms":812}"#);
+  }
+}
@@ -0,0 +1,337 @@
+// Random access points of H.264 and HEVC streams. FFmpeg flags IDR frames, CRA and BLA pictures
+// and recovery point SEIs alike as key frames, but only an IDR starts a GOP that needs nothing
+// before it, which some packagers require at every segment boundary. The NAL unit types of every
+// key frame packet tell them apart. Other codecs aren't classified.
+
+use ffmpeg::codec::Id;
+use ffmpeg::Rational;
+use serde::Serialize;
+
+use crate::cadence;
+use crate::remux::{self, Packaging};
+use crate::report::RandomAccessReport;
+use crate::{format_with_commas, BLUE_BOLD, RESET};
+
+// SEI payload type of a recovery point, in both codecs.
+const RECOVERY_POINT_SEI: u32 = 6;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Kind {
+  Idr,
+  // HEVC clean random access and broken link access pictures.
+  CraBla,
+  // A recovery point SEI on a non-IDR frame, as x264 writes with --open-gop or intra refresh.
+  RecoveryPoint,
+  // None of these, e.g. an I-frame the container flags as a sync sample.
+  Other,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Codec {
+  H264,
+  Hevc,
+}
+
+#[derive(Clone, Copy)]
+struct Parser {
+  codec: Codec,
+  // Bytes of the NAL unit length prefix; None for Annex B start codes.
+  length_size: Option<usize>,
+}
+
+impl Parser {
+  fn new(codec: Id, extradata: &[u8]) -> Option<Self> {
+    let codec = match codec {
+      Id::H264 => Codec::H264,
+      Id::HEVC => Codec::Hevc,
+      _ => return None,
+    };
+    // lengthSizeMinusOne is in the fifth byte of avcC and the 22nd of hvcC.
+    let length_size = match (remux::packaging(extradata), codec) {
+      (Packaging::AnnexB, _) => None,
+      (Packaging::LengthPrefixed, Codec::H264) => Some(usize::from(*extradata.get(4)? & 3) + 1),
+      (Packaging::LengthPrefixed, Codec::Hevc) => Some(usize::from(*extradata.get(21)? & 3) + 1),
+    };
+    Some(Parser { codec, length_size })
+  }
+
+  // The NAL units of a packet, without their start code or length prefix.
+  fn units<'a>(&self, data: &'a [u8]) -> Vec<&'a [u8]> {
+    let mut units = Vec::new();
+    match self.length_size {
+      Some(size) => {
+        let mut rest = data;
+        while rest.len() > size {
+          let length = rest[..size].iter().fold(0usize, |length, &byte| (length << 8) | usize::from(byte));
+          let Some(unit) = rest.get(size..size + length) else {
+            break;
+          };
+          units.push(unit);
+          rest = &rest[size + length..];
+        }
+      }
+      None => {
+        let starts: Vec<usize> = data.windows(3).enumerate().filter(|(_, w)| *w == [0, 0, 1]).map(|(at, _)| at + 3).collect();
+        for (i, &start) in starts.iter().enumerate() {
+          let end = starts.get(i + 1).map_or(data.len(), |&next| next - 3);
+          units.push(&data[start..end]);
+        }
+      }
+    }
+    units
+  }
+
+  fn classify(&self, data: &[u8]) -> Kind {
+    let mut recovery_point = false;
+    for unit in self.units(data) {
+      let Some(&header) = unit.first() else {
+        continue;
+      };
+      match self.codec {
+        Codec::H264 => match header & 0x1f {
+          5 => return Kind::Idr,
+          6 => recovery_point |= has_recovery_point(&unit[1..]),
+          _ => {}
+        },
+        Codec::Hevc => match (header >> 1) & 0x3f {
+          19 | 20 => return Kind::Idr,
+          16..=18 | 21 => return Kind::CraBla,
+          39 => recovery_point |= has_recovery_point(unit.get(2..).unwrap_or(&[])),
+          _ => {}
+        },
+      }
+    }
+    if recovery_point { Kind::RecoveryPoint } else { Kind::Other }
+  }
+}
+
+// Whether the messages of an SEI payload include a recovery point. Emulation prevention bytes are
+// left in, which at worst misplaces the messages following one that contains them.
+fn has_recovery_point(mut payload: &[u8]) -> bool {
+  // Types and sizes are coded as a run of 0xFF bytes, each adding 255, and a final byte.
+  fn read_value(bytes: &mut &[u8]) -> Option<u32> {
+    let mut value = 0;
+    loop {
+      let (&byte, rest) = bytes.split_first()?;
+      *bytes = rest;
+      value += u32::from(byte);
+      if byte != 0xff {
+        return Some(value);
+      }
+    }
+  }
+  // 0x80 is the RBSP trailing bits after the last message.
+  while payload.first().is_some_and(|&byte| byte != 0x80) {
+    let (Some(kind), Some(size)) = (read_value(&mut payload), read_value(&mut payload)) else {
+      return false;
+    };
+    if kind == RECOVERY_POINT_SEI {
+      return true;
+    }
+    payload = payload.get(size as usize..).unwrap_or(&[]);
+  }
+  false
+}
+
+#[derive(Default)]
+pub struct Tracker {
+  // None for codecs other than H.264 and HEVC.
+  parser: Option<Parser>,
+  pub idr: u64,
+  pub cra_bla: u64,
+  pub recovery_point: u64,
+  pub other: u64,
+  // Timestamps (stream time base) of the IDR frames.
+  pub idr_timestamps: Vec<i64>,
+}
+
+impl Tracker {
+  pub fn new(parameters: &ffmpeg::codec::Parameters) -> Self {
+    let extradata = unsafe {
+      let par = parameters.as_ptr();
+      if (*par).extradata.is_null() || (*par).extradata_size <= 0 {
+        &[][..]
+      } else {
+        std::slice::from_raw_parts((*par).extradata, (*par).extradata_size as usize)
+      }
+    };
+    Tracker { parser: Parser::new(parameters.id(), extradata), ..Tracker::default() }
+  }
+
+  pub fn is_active(&self) -> bool {
+    self.parser.is_some()
+  }
+
+  // Classify a key frame packet.
+  pub fn push(&mut self, data: &[u8], timestamp: Option<i64>) {
+    let Some(parser) = self.parser else {
+      return;
+    };
+    match parser.classify(data) {
+      Kind::Idr => {
+        self.idr += 1;
+        self.idr_timestamps.extend(timestamp);
+      }
+      Kind::CraBla => self.cra_bla += 1,
+      Kind::RecoveryPoint => self.recovery_point += 1,
+      Kind::Other => self.other += 1,
+    }
+  }
+}
+
+// Interval statistics over the IDR frames only, in seconds.
+#[derive(Serialize)]
+pub struct IdrIntervals {
+  pub min_seconds: f64,
+  pub max_seconds: f64,
+  pub avg_seconds: f64,
+}
+
+pub fn idr_intervals(tracker: &Tracker, time_base: Rational) -> Option<IdrIntervals> {
+  let intervals = cadence::intervals_ms(&tracker.idr_timestamps, time_base).filter(|intervals| !intervals.is_empty())?;
+  Some(IdrIntervals {
+    min_seconds: *intervals.iter().min()? as f64 / 1000.0,
+    max_seconds: *intervals.iter().max()? as f64 / 1000.0,
+    avg_seconds: intervals.iter().sum::<u64>() as f64 / intervals.len() as f64 / 1000.0,
+  })
+}
+
+// None for codecs other than H.264 and HEVC.
+pub fn to_report(tracker: &Tracker, time_base: Rational) -> Option<RandomAccessReport> {
+  tracker.is_active().then(|| RandomAccessReport {
+    idr: tracker.idr,
+    cra_bla: tracker.cra_bla,
+    recovery_point: tracker.recovery_point,
+    other: tracker.other,
+    idr_intervals: idr_intervals(tracker, time_base),
+  })
+}
+
+// Print the random access point section, for H.264 and HEVC only.
+pub fn report(tracker: &Tracker, time_base: Rational) {
+  if !tracker.is_active() {
+    return;
+  }
+  out!("{}{}Random access points - {}", RESET, BLUE_BOLD, RESET);
+  out!("Key frames: {} IDR, {} CRA/BLA, {} recovery point SEI, {} other", format_with_commas(tracker.idr as i64),
+    format_with_commas(tracker.cra_bla as i64), format_with_commas(tracker.recovery_point as i64),
+    format_with_commas(tracker.other as i64));
+  match idr_intervals(tracker, time_base) {
+    Some(intervals) => out!("IDR interval: avg {:.3}s (min {:.3}s, max {:.3}s)", intervals.avg_seconds,
+      intervals.min_seconds, intervals.max_seconds),
+    None => out!("IDR interval: not measurable (fewer than 2 IDR frames)"),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const ANNEX_B_H264: Parser = Parser { codec: Codec::H264, length_size: None };
+  const ANNEX_B_HEVC: Parser = Parser { codec: Codec::Hevc, length_size: None };
+  const AVCC: Parser = Parser { codec: Codec::H264, length_size: Some(4) };
+  const HVCC: Parser = Parser { codec: Codec::Hevc, length_size: Some(4) };
+
+  // A length-prefixed packet of the units, with 4 byte lengths.
+  fn prefixed(units: &[&[u8]]) -> Vec<u8> {
+    units.iter().flat_map(|unit| (unit.len() as u32).to_be_bytes().into_iter().chain(unit.iter().copied())).collect()
+  }
+
+  #[test]
+  fn length_size_comes_from_the_configuration_record() {
+    let mut avcc = vec![1, 0x64, 0, 0x1f, 0xfd, 0xe1];
+    assert_eq!(Parser::new(Id::H264, &avcc).unwrap().length_size, Some(2));
+    avcc[4] = 0xff;
+    assert_eq!(Parser::new(Id::H264, &avcc).unwrap().length_size, Some(4));
+    let mut hvcc = vec![0; 23];
+    hvcc[0] = 1;
+    hvcc[21] = 0x0f;
+    assert_eq!(Parser::new(Id::HEVC, &hvcc).unwrap().length_size, Some(4));
+    assert_eq!(Parser::new(Id::H264, &[0, 0, 0, 1, 0x67]).unwrap().length_size, None);
+    assert_eq!(Parser::new(Id::H264, &[]).unwrap().length_size, None);
+    // A record too short to hold lengthSizeMinusOne, and a codec that isn't classified.
+    assert!(Parser::new(Id::H264, &[1, 0x64]).is_none());
+    assert!(Parser::new(Id::MPEG2VIDEO, &[]).is_none());
+  }
+
+  #[test]
+  fn annex_b_units() {
+    let data = [0, 0, 1, 0x67, 0x64, 0, 0, 1, 0x68, 0xee, 0, 0, 1, 0x65, 0x88];
+    assert_eq!(ANNEX_B_H264.units(&data), vec![&[0x67, 0x64][..], &[0x68, 0xee], &[0x65, 0x88]]);
+    // A 4 byte start code leaves its leading zero on the previous unit, as trailing_zero_8bits.
+    let data = [0, 0, 0, 1, 0x09, 0xf0, 0, 0, 0, 1, 0x65, 0x88];
+    assert_eq!(ANNEX_B_H264.units(&data), vec![&[0x09, 0xf0, 0][..], &[0x65, 0x88]]);
+    assert!(ANNEX_B_H264.units(&[0x65, 0x88]).is_empty());
+  }
+
+  #[test]
+  fn length_prefixed_units() {
+    let data = prefixed(&[&[0x09, 0xf0], &[0x65, 0x88, 0x84]]);
+    assert_eq!(AVCC.units(&data), vec![&[0x09, 0xf0][..], &[0x65, 0x88, 0x84]]);
+    let two_bytes = Parser { codec: Codec::H264, length_size: Some(2) };
+    assert_eq!(two_bytes.units(&[0, 1, 0x65, 0, 2, 0x41, 0x9a]), vec![&[0x65][..], &[0x41, 0x9a]]);
+  }
+
+  #[test]
+  fn truncated_length_prefixes_stop_the_units() {
+    // A length past the end of the packet.
+    let mut data = prefixed(&[&[0x09, 0xf0]]);
+    data.extend([0, 0, 0, 9, 0x65, 0x88]);
+    assert_eq!(AVCC.units(&data), vec![&[0x09, 0xf0][..]]);
+    // Fewer bytes than a prefix.
+    assert!(AVCC.units(&[0, 0]).is_empty());
+    assert!(AVCC.units(&[]).is_empty());
+    assert_eq!(AVCC.classify(&[0, 0, 0, 9, 0x65, 0x88]), Kind::Other);
+  }
+
+  #[test]
+  fn h264_idr() {
+    assert_eq!(ANNEX_B_H264.classify(&[0, 0, 0, 1, 0x09, 0xf0, 0, 0, 1, 0x65, 0x88]), Kind::Idr);
+    assert_eq!(AVCC.classify(&prefixed(&[&[0x09, 0xf0], &[0x65, 0x88]])), Kind::Idr);
+    // A non-IDR slice flagged as a key frame by the container.
+    assert_eq!(ANNEX_B_H264.classify(&[0, 0, 1, 0x41, 0x9a]), Kind::Other);
+    assert_eq!(AVCC.classify(&prefixed(&[&[0x41, 0x9a]])), Kind::Other);
+  }
+
+  #[test]
+  fn hevc_cra_and_bla() {
+    // CRA_NUT (21), BLA_W_LP (16) and BLA_N_LP (18), then IDR_W_RADL (19).
+    for header in [0x2a, 0x20, 0x24] {
+      assert_eq!(ANNEX_B_HEVC.classify(&[0, 0, 0, 1, header, 0x01, 0xaf]), Kind::CraBla, "{header:#x}");
+      assert_eq!(HVCC.classify(&prefixed(&[&[header, 0x01, 0xaf]])), Kind::CraBla, "{header:#x}");
+    }
+    assert_eq!(ANNEX_B_HEVC.classify(&[0, 0, 1, 0x26, 0x01, 0xaf]), Kind::Idr);
+    assert_eq!(HVCC.classify(&prefixed(&[&[0x26, 0x01, 0xaf]])), Kind::Idr);
+    // TRAIL_R.
+    assert_eq!(HVCC.classify(&prefixed(&[&[0x02, 0x01, 0xd0]])), Kind::Other);
+  }
+
+  #[test]
+  fn recovery_point_sei() {
+    // A recovery point SEI (type 6) ahead of a non-IDR slice.
+    let sei = [0x06, 0x06, 0x01, 0xc4, 0x80];
+    assert_eq!(ANNEX_B_H264.classify(&[&[0, 0, 1][..], &sei, &[0, 0, 1, 0x41, 0x9a]].concat()), Kind::RecoveryPoint);
+    assert_eq!(AVCC.classify(&prefixed(&[&sei, &[0x41, 0x9a]])), Kind::RecoveryPoint);
+    // The same SEI in an HEVC prefix SEI NAL unit (39).
+    let hevc_sei = [0x4e, 0x01, 0x06, 0x01, 0xc4, 0x80];
+    assert_eq!(HVCC.classify(&prefixed(&[&hevc_sei, &[0x02, 0x01, 0xd0]])), Kind::RecoveryPoint);
+    // An IDR wins over the SEI.
+    assert_eq!(AVCC.classify(&prefixed(&[&sei, &[0x65, 0x88]])), Kind::Idr);
+  }
+
+  #[test]
+  fn sei_messages() {
+    assert!(has_recovery_point(&[0x06, 0x01, 0xc4, 0x80]));
+    // After a user data message (type 5) of 2 bytes.
+    assert!(has_recovery_point(&[0x05, 0x02, 0xaa, 0xbb, 0x06, 0x01, 0xc4, 0x80]));
+    // A buffering period only, and a type of 255 + 6 coded with a 0xFF byte.
+    assert!(!has_recovery_point(&[0x00, 0x01, 0xaa, 0x80]));
+    assert!(!has_recovery_point(&[0xff, 0x06, 0x01, 0xaa, 0x80]));
+    // Nothing after the trailing bits is read.
+    assert!(!has_recovery_point(&[0x80, 0x06, 0x01, 0xc4]));
+    // Truncated: a type without a size, and a run of 0xFF with no final byte.
+    assert!(!has_recovery_point(&[0x05]));
+    assert!(!has_recovery_point(&[0xff, 0xff]));
+    assert!(!has_recovery_point(&[]));
+  }
+}
@@ -0,0 +1,145 @@
+// Second, error-resilient decode of the regions the strict pass failed on. The strict pass
+// records where key frames and errors were, so this pass seeks straight to the damaged regions
+// instead of re-decoding the whole file.
+
+use ffmpeg::codec::threading;
+use ffmpeg::decoder::{Check, Conceal};
+use ffmpeg::{Rational, Rescale};
+use indicatif::ProgressBar;
+
+use crate::timestamp::Timestamp;
+use crate::{decode_packets, format_with_commas, DecodeConfig, DecodeStats, FrameOutputs, BLUE_BOLD, RESET};
+
+// A run of the stream between two key frames that contained decode errors.
+pub struct DamagedRegion {
+  // Key frame at or before the first error (stream time base).
+  pub start: i64,
+  // Next key frame after the last error, or None when the damage runs to the end of the stream.
+  pub end: Option<i64>,
+  pub errors: usize,
+  // Frames decoded inside the region by the strict and the lenient decoder.
+  pub strict_frames: usize,
+  pub lenient_frames: usize,
+}
+
+impl DamagedRegion {
+  // Frames the lenient decoder produced that the strict one did not.
+  pub fn recovered_frames(&self) -> usize {
+    self.lenient_frames.saturating_sub(self.strict_frames)
+  }
+}
+
+// Count the values of a sorted slice that fall into [start, end).
+fn count_in_range(sorted: &[i64], start: i64, end: Option<i64>) -> usize {
+  let lo = sorted.partition_point(|&t| t < start);
+  let hi = end.map_or(sorted.len(), |e| sorted.partition_point(|&t| t < e));
+  hi.saturating_sub(lo)
+}
+
+// Group the strict pass's error timestamps into regions bounded by the key frames around them.
+// Errors within the same GOP share a region.
+pub fn damaged_regions(strict: &DecodeStats) -> Vec<DamagedRegion> {
+  let mut keyframes = strict.keyframe_packet_timestamps.clone();
+  keyframes.sort_unstable();
+  keyframes.dedup();
+  let mut errors = strict.error_timestamps.clone();
+  errors.sort_unstable();
+
+  let mut regions: Vec<DamagedRegion> = Vec::new();
+  for error in errors {
+    // Number of key frames at or before the error.
+    let idx = keyframes.partition_point(|&k| k <= error);
+    let start = if idx > 0 {
+      keyframes[idx - 1]
+    } else {
+      strict.first_frame_timestamp.map_or(error, |first| first.min(error))
+    };
+    match regions.last_mut() {
+      Some(region) if region.start == start => region.errors += 1,
+      _ => regions.push(DamagedRegion {
+        start,
+        end: keyframes.get(idx).copied(),
+        errors: 1,
+        strict_frames: 0,
+        lenient_frames: 0,
+      }),
+    }
+  }
+
+  let mut frames = strict.frame_timestamps.clone();
+  frames.sort_unstable();
+  for region in &mut regions {
+    region.strict_frames = count_in_range(&frames, region.start, region.end);
+  }
+  regions
+}
+
+// Open a decoder that conceals errors and outputs whatever it can instead of rejecting damage:
+// relaxed error recognition, error concealment, corrupt frames output and flag2 showall.
+fn open_lenient_decoder(
+  parameters: ffmpeg::codec::Parameters,
+  threading_config: threading::Config,
+) -> Result<ffmpeg::decoder::Video, ffmpeg::Error> {
+  let mut decoder = ffmpeg::codec::context::Context::from_parameters(parameters)?.decoder();
+  decoder.conceal(Conceal::GUESS_MVS | Conceal::DEBLOCK);
+  decoder.check(Check::empty());
+  decoder.set_flags(ffmpeg::codec::Flags::OUTPUT_CORRUPT);
+  unsafe {
+    (*decoder.as_mut_ptr()).flags2 |= ffmpeg::ffi::AV_CODEC_FLAG2_SHOW_ALL;
+  }
+  decoder.set_threading(threading_config);
+  decoder.video()
+}
+
+// Re-decode every damaged region found by the strict pass with the lenient decoder and fill in
+// how many frames each one yields.
+pub fn run(
+  ictx: &mut ffmpeg::format::context::Input,
+  parameters: ffmpeg::codec::Parameters,
+  threading_config: threading::Config,
+  video_stream_index: usize,
+  time_base: Rational,
+  strict: &DecodeStats,
+  pb: &ProgressBar,
+) -> Result<Vec<DamagedRegion>, ffmpeg::Error> {
+  let mut regions = damaged_regions(strict);
+  let mut decoder = open_lenient_decoder(parameters, threading_config)?;
+  let mut frame = ffmpeg::util::frame::video::Video::empty();
+
+  for region in &mut regions {
+    pb.set_message(format!("Recovery pass: region at {}...", region.start));
+    // Seek to the key frame that opens the region; ictx.seek takes AV_TIME_BASE units.
+    let target = region.start.rescale(time_base, ffmpeg::rescale::TIME_BASE);
+    ictx.seek(target, ..target)?;
+    decoder.flush();
+
+    let config = DecodeConfig {
+      end_timestamp: region.end,
+      record_timeline: true,
+      ..DecodeConfig::default()
+    };
+    let lenient = decode_packets(ictx, &mut decoder, video_stream_index, &mut frame, config, FrameOutputs::default(), pb)?;
+    let mut frames = lenient.frame_timestamps;
+    frames.sort_unstable();
+    region.lenient_frames = count_in_range(&frames, region.start, region.end);
+  }
+
+  Ok(regions)
+}
+
+// Print the recovery pass section: the frames the lenient decoder got back over those of the strict
+// pass, for every damaged region.
+pub fn report(regions: &[DamagedRegion], strict_frames: i64, time_base: Rational, verbose: bool) {
+  let recoverable: usize = regions.iter().map(|r| r.recovered_frames()).sum();
+  out!("{}{}Recovery pass - {}", RESET, BLUE_BOLD, RESET);
+  out!("Damaged regions: {}", format_with_commas(regions.len() as i64));
+  out!("Frames decoded (strict): {}", format_with_commas(strict_frames));
+  out!("Recoverable frames: {}", format_with_commas(recoverable as i64));
+  for region in regions {
+    let start = Timestamp::new(region.start, time_base).describe(verbose);
+    let end = region.end.map_or_else(|| "end".to_string(), |end| Timestamp::new(end, time_base).describe(verbose));
+    out!("Region {} - {}: {} errors, {} frames strict, {} frames lenient (+{})",
+      start, end, region.errors, region.strict_frames,
+      region.lenient_frames, region.recovered_frames());
+  }
+}
@@ -0,0 +1,417 @@
+// Remux planning: the codec parameters that decide whether a stream can be copied into another
+// container, checked against a per-container compatibility table.
+
+use ffmpeg::codec::Id;
+use ffmpeg::media::Type;
+
+use crate::{BLUE_BOLD, RESET};
+
+// Containers --remux-target can evaluate.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RemuxTarget {
+  Mp4,
+  Mkv,
+  Ts,
+}
+
+impl RemuxTarget {
+  pub fn parse(name: &str) -> Option<Self> {
+    match name {
+      "mp4" => Some(RemuxTarget::Mp4),
+      "mkv" => Some(RemuxTarget::Mkv),
+      "ts" => Some(RemuxTarget::Ts),
+      _ => None,
+    }
+  }
+
+  pub fn name(&self) -> &'static str {
+    match self {
+      RemuxTarget::Mp4 => "mp4",
+      RemuxTarget::Mkv => "mkv",
+      RemuxTarget::Ts => "ts",
+    }
+  }
+}
+
+// Whether a container can carry a codec with stream copy.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Support {
+  Yes,
+  // Only with -strict experimental in FFmpeg 6.0, and poorly supported by players.
+  Experimental,
+  No,
+}
+
+pub struct CodecRule {
+  pub codec: Id,
+  // Codec tag the rule is limited to, for streams FFmpeg has no codec ID for (e.g. tmcd).
+  pub tag: Option<&'static [u8; 4]>,
+  pub mp4: Support,
+  pub mkv: Support,
+  pub ts: Support,
+}
+
+impl CodecRule {
+  fn support(&self, target: RemuxTarget) -> Support {
+    match target {
+      RemuxTarget::Mp4 => self.mp4,
+      RemuxTarget::Mkv => self.mkv,
+      RemuxTarget::Ts => self.ts,
+    }
+  }
+
+  fn applies(&self, stream: &StreamParameters) -> bool {
+    self.codec == stream.codec && self.tag.is_none_or(|tag| u32::from_le_bytes(*tag) == stream.codec_tag)
+  }
+}
+
+const fn rule(codec: Id, mp4: Support, mkv: Support, ts: Support) -> CodecRule {
+  CodecRule { codec, tag: None, mp4, mkv, ts }
+}
+
+const fn tagged_rule(codec: Id, tag: &'static [u8; 4], mp4: Support, mkv: Support, ts: Support) -> CodecRule {
+  CodecRule { codec, tag: Some(tag), mp4, mkv, ts }
+}
+
+use Support::{Experimental, No, Yes};
+
+// Stream-copy support of FFmpeg 6.0's mp4, matroska and mpegts muxers. Codecs missing from the
+// table are treated as not copyable.
+pub static RULES: &[CodecRule] = &[
+  // Video
+  rule(Id::H264, Yes, Yes, Yes),
+  rule(Id::HEVC, Yes, Yes, Yes),
+  rule(Id::AV1, Yes, Yes, No),
+  rule(Id::VP9, Yes, Yes, No),
+  rule(Id::VP8, No, Yes, No),
+  rule(Id::MPEG4, Yes, Yes, Yes),
+  rule(Id::MPEG2VIDEO, Yes, Yes, Yes),
+  rule(Id::MJPEG, Yes, Yes, No),
+  rule(Id::PRORES, No, Yes, No),
+  rule(Id::DNXHD, No, Yes, No),
+  // Audio
+  rule(Id::AAC, Yes, Yes, Yes),
+  rule(Id::MP3, Yes, Yes, Yes),
+  rule(Id::AC3, Yes, Yes, Yes),
+  rule(Id::EAC3, Yes, Yes, Yes),
+  rule(Id::DTS, Yes, Yes, Yes),
+  rule(Id::OPUS, Yes, Yes, Yes),
+  rule(Id::FLAC, Experimental, Yes, No),
+  rule(Id::VORBIS, Experimental, Yes, No),
+  rule(Id::ALAC, Yes, Yes, No),
+  rule(Id::TRUEHD, Experimental, Yes, Yes),
+  rule(Id::PCM_S16LE, No, Yes, No),
+  rule(Id::PCM_S24LE, No, Yes, No),
+  // Subtitles
+  rule(Id::MOV_TEXT, Yes, No, No),
+  rule(Id::WEBVTT, Yes, Yes, No),
+  rule(Id::SUBRIP, No, Yes, No),
+  rule(Id::ASS, No, Yes, No),
+  rule(Id::SSA, No, Yes, No),
+  rule(Id::HDMV_PGS_SUBTITLE, No, Yes, No),
+  rule(Id::DVD_SUBTITLE, Yes, Yes, No),
+  rule(Id::DVB_SUBTITLE, No, Yes, Yes),
+  rule(Id::DVB_TELETEXT, No, No, Yes),
+  // Attachments: Matroska fonts, which only Matroska carries, and cover art.
+  rule(Id::TTF, No, Yes, No),
+  rule(Id::OTF, No, Yes, No),
+  rule(Id::PNG, Yes, Yes, No),
+  // Data: GoPro GPMF and other binary tracks of MOV/MP4, KLV and ID3 metadata of transport streams,
+  // and QuickTime timecode tracks, which the mp4 muxer writes again from the timecode tag.
+  rule(Id::BIN_DATA, Yes, No, No),
+  rule(Id::SMPTE_KLV, No, No, Yes),
+  rule(Id::TIMED_ID3, No, No, Yes),
+  tagged_rule(Id::None, b"tmcd", Yes, No, No),
+];
+
+// How an H.264/HEVC bitstream is packaged.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Packaging {
+  // Length-prefixed NAL units with avcC/hvcC extradata (MP4, Matroska).
+  LengthPrefixed,
+  // Start-code delimited NAL units with in-band parameter sets (MPEG-TS, raw streams).
+  AnnexB,
+}
+
+// The properties of a stream that constrain remuxing.
+pub struct StreamParameters {
+  pub index: usize,
+  pub medium: Type,
+  pub codec: Id,
+  pub codec_tag: u32,
+  pub profile: i32,
+  pub level: i32,
+  // Chroma subsampling of video streams, e.g. "4:2:0".
+  pub chroma: Option<String>,
+  pub packaging: Option<Packaging>,
+  // MPEG-4 audio object type of AAC streams (2 = LC, 5 = HE-AAC, 29 = HE-AACv2).
+  pub audio_object_type: Option<i32>,
+}
+
+fn chroma_format(format: ffmpeg::format::Pixel) -> Option<String> {
+  let descriptor = format.descriptor()?;
+  if descriptor.nb_components() < 3 {
+    return Some("4:0:0".to_string());
+  }
+  let chroma = match (descriptor.log2_chroma_w(), descriptor.log2_chroma_h()) {
+    (0, 0) => "4:4:4",
+    (1, 0) => "4:2:2",
+    (1, 1) => "4:2:0",
+    (2, 0) => "4:1:1",
+    _ => return Some(descriptor.name().to_string()),
+  };
+  Some(chroma.to_string())
+}
+
+// The pixel format with this raw AVPixelFormat value, which is what the format field of the codec
+// parameters holds for video streams. The value is looked up among the formats libavutil
+// describes instead of cast, since a value the bindings don't declare isn't a valid enum.
+fn pixel_format(format: i32) -> Option<ffmpeg::format::Pixel> {
+  let mut descriptor = std::ptr::null();
+  loop {
+    descriptor = unsafe { ffmpeg::ffi::av_pix_fmt_desc_next(descriptor) };
+    if descriptor.is_null() {
+      return None;
+    }
+    let id = unsafe { ffmpeg::ffi::av_pix_fmt_desc_get_id(descriptor) };
+    if id as i32 == format {
+      return Some(ffmpeg::format::Pixel::from(id));
+    }
+  }
+}
+
+// avcC and hvcC records start with configurationVersion 1; Annex-B extradata starts with a start
+// code, and streams with in-band parameter sets have none at all.
+pub fn packaging(extradata: &[u8]) -> Packaging {
+  if extradata.first() == Some(&1) {
+    Packaging::LengthPrefixed
+  } else {
+    Packaging::AnnexB
+  }
+}
+
+// Collect the remux-relevant parameters of every stream.
+pub fn stream_parameters(ictx: &ffmpeg::format::context::Input) -> Vec<StreamParameters> {
+  ictx
+    .streams()
+    .map(|stream| {
+      let parameters = stream.parameters();
+      let medium = parameters.medium();
+      let codec = parameters.id();
+      let (codec_tag, profile, level, format, extradata) = unsafe {
+        let par = parameters.as_ptr();
+        let extradata = if (*par).extradata.is_null() || (*par).extradata_size <= 0 {
+          Vec::new()
+        } else {
+          std::slice::from_raw_parts((*par).extradata, (*par).extradata_size as usize).to_vec()
+        };
+        ((*par).codec_tag, (*par).profile, (*par).level, (*par).format, extradata)
+      };
+      let chroma = if medium == Type::Video && format >= 0 {
+        pixel_format(format).and_then(chroma_format)
+      } else {
+        None
+      };
+      StreamParameters {
+        index: stream.index(),
+        medium,
+        codec,
+        codec_tag,
+        profile,
+        level,
+        chroma,
+        packaging: matches!(codec, Id::H264 | Id::HEVC).then(|| packaging(&extradata)),
+        // FFmpeg's AAC profiles are the audio object type minus one.
+        audio_object_type: (codec == Id::AAC && profile >= 0).then_some(profile + 1),
+      }
+    })
+    .collect()
+}
+
+// Render a codec tag as its four characters, e.g. "avc1", or as hex when it isn't printable.
+fn format_tag(tag: u32) -> String {
+  if tag == 0 {
+    return "none".to_string();
+  }
+  let bytes = tag.to_le_bytes();
+  if bytes.iter().all(|b| b.is_ascii_graphic() || *b == b' ') {
+    bytes.iter().map(|&b| b as char).collect()
+  } else {
+    format!("0x{:08x}", tag)
+  }
+}
+
+// Whether the stream can be copied into `target`, with the reason. Copyable streams may still
+// carry a note about what the muxer has to do.
+pub fn evaluate(stream: &StreamParameters, target: RemuxTarget) -> (bool, String) {
+  let support = RULES.iter().find(|r| r.applies(stream)).map_or(No, |r| r.support(target));
+  match support {
+    No => return (false, format!("{} is not supported in {}", stream.codec.name(), target.name())),
+    Experimental => {
+      return (false, format!("{} in {} is experimental (-strict experimental)", stream.codec.name(), target.name()))
+    }
+    Yes => {}
+  }
+  let note = match (stream.packaging, target) {
+    (Some(Packaging::LengthPrefixed), RemuxTarget::Ts) => "needs the mp4toannexb bitstream filter, which FFmpeg inserts",
+    (Some(Packaging::AnnexB), RemuxTarget::Mp4 | RemuxTarget::Mkv) => "Annex-B is converted to length-prefixed by the muxer",
+    _ if stream.codec == Id::HEVC && target == RemuxTarget::Mp4 && format_tag(stream.codec_tag) == "hev1" => {
+      "tagged hev1; use -tag:v hvc1 for Apple players"
+    }
+    _ if stream.codec == Id::None => "written from the video stream's timecode tag; don't -map the stream itself",
+    _ => "",
+  };
+  (true, note.to_string())
+}
+
+// Print the remux parameters and verdict of every stream. Returns whether all streams are
+// copyable into `target`.
+pub fn report(ictx: &ffmpeg::format::context::Input, target: RemuxTarget) -> bool {
+  out!("{}{}Remux to {} - {}", RESET, BLUE_BOLD, target.name(), RESET);
+  let mut all_copyable = true;
+  for stream in stream_parameters(ictx) {
+    let mut details = vec![format!("tag {}", format_tag(stream.codec_tag))];
+    if stream.profile >= 0 {
+      details.push(format!("profile {}", stream.profile));
+    }
+    if stream.level > 0 {
+      details.push(format!("level {}", stream.level));
+    }
+    if let Some(chroma) = &stream.chroma {
+      details.push(chroma.clone());
+    }
+    match stream.packaging {
+      Some(Packaging::LengthPrefixed) => details.push("length-prefixed (AVCC)".to_string()),
+      Some(Packaging::AnnexB) => details.push("Annex-B".to_string()),
+      None => {}
+    }
+    if let Some(aot) = stream.audio_object_type {
+      details.push(format!("audio object type {}", aot));
+    }
+
+    let (copyable, reason) = evaluate(&stream, target);
+    all_copyable &= copyable;
+    out!("#{}: {:?} {} ({}): stream-copyable: {}{}", stream.index, stream.medium, stream.codec.name(),
+      details.join(", "), if copyable { "yes" } else { "no" },
+      if reason.is_empty() { String::new() } else { format!(" ({})", reason) });
+  }
+  out!("Verdict: {}", if all_copyable { "all streams are stream-copyable" } else { "NOT stream-copyable" });
+  all_copyable
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn stream(medium: Type, codec: Id, tag: &[u8; 4]) -> StreamParameters {
+    StreamParameters {
+      index: 0,
+      medium,
+      codec,
+      codec_tag: u32::from_le_bytes(*tag),
+      profile: -99,
+      level: 0,
+      chroma: None,
+      packaging: None,
+      audio_object_type: None,
+    }
+  }
+
+  fn copyable(stream: &StreamParameters, target: RemuxTarget) -> bool {
+    evaluate(stream, target).0
+  }
+
+  #[test]
+  fn one_rule_per_codec() {
+    for (i, rule) in RULES.iter().enumerate() {
+      assert!(RULES[i + 1..].iter().all(|other| other.codec != rule.codec || other.tag != rule.tag),
+        "{:?} has two rules", rule.codec);
+    }
+  }
+
+  #[test]
+  fn table_verdicts() {
+    let h264 = stream(Type::Video, Id::H264, b"avc1");
+    assert!(copyable(&h264, RemuxTarget::Mp4) && copyable(&h264, RemuxTarget::Mkv) && copyable(&h264, RemuxTarget::Ts));
+    let vp8 = stream(Type::Video, Id::VP8, b"\0\0\0\0");
+    assert!(!copyable(&vp8, RemuxTarget::Mp4) && copyable(&vp8, RemuxTarget::Mkv) && !copyable(&vp8, RemuxTarget::Ts));
+    let subrip = stream(Type::Subtitle, Id::SUBRIP, b"\0\0\0\0");
+    assert!(!copyable(&subrip, RemuxTarget::Mp4));
+    assert!(copyable(&stream(Type::Subtitle, Id::MOV_TEXT, b"tx3g"), RemuxTarget::Mp4));
+  }
+
+  #[test]
+  fn experimental_is_not_copyable() {
+    let (copyable, reason) = evaluate(&stream(Type::Audio, Id::FLAC, b"fLaC"), RemuxTarget::Mp4);
+    assert!(!copyable);
+    assert!(reason.contains("experimental"), "{reason}");
+  }
+
+  #[test]
+  fn unknown_codecs_are_not_copyable() {
+    for target in [RemuxTarget::Mp4, RemuxTarget::Mkv, RemuxTarget::Ts] {
+      assert!(!copyable(&stream(Type::Video, Id::CINEPAK, b"cvid"), target));
+    }
+  }
+
+  #[test]
+  fn attachments() {
+    for font in [Id::TTF, Id::OTF] {
+      let font = stream(Type::Attachment, font, b"\0\0\0\0");
+      assert!(copyable(&font, RemuxTarget::Mkv));
+      assert!(!copyable(&font, RemuxTarget::Mp4) && !copyable(&font, RemuxTarget::Ts));
+    }
+    let cover = stream(Type::Video, Id::PNG, b"png ");
+    assert!(copyable(&cover, RemuxTarget::Mp4) && copyable(&cover, RemuxTarget::Mkv));
+  }
+
+  #[test]
+  fn data_streams() {
+    let gpmf = stream(Type::Data, Id::BIN_DATA, b"gpmd");
+    assert!(copyable(&gpmf, RemuxTarget::Mp4) && !copyable(&gpmf, RemuxTarget::Mkv));
+    assert!(copyable(&stream(Type::Data, Id::SMPTE_KLV, b"KLVA"), RemuxTarget::Ts));
+    assert!(copyable(&stream(Type::Data, Id::TIMED_ID3, b"ID3 "), RemuxTarget::Ts));
+
+    let tmcd = stream(Type::Data, Id::None, b"tmcd");
+    let (copyable_to_mp4, note) = evaluate(&tmcd, RemuxTarget::Mp4);
+    assert!(copyable_to_mp4);
+    assert!(note.contains("timecode tag"), "{note}");
+    assert!(!copyable(&tmcd, RemuxTarget::Mkv));
+    // The rule is limited to the tmcd tag; other streams without a codec ID aren't copyable.
+    assert!(!copyable(&stream(Type::Data, Id::None, b"rtmd"), RemuxTarget::Mp4));
+  }
+
+  #[test]
+  fn packaging_notes() {
+    let mut h264 = stream(Type::Video, Id::H264, b"avc1");
+    h264.packaging = Some(Packaging::LengthPrefixed);
+    assert_eq!(evaluate(&h264, RemuxTarget::Ts), (true, "needs the mp4toannexb bitstream filter, which FFmpeg inserts".to_string()));
+    assert_eq!(evaluate(&h264, RemuxTarget::Mkv), (true, String::new()));
+    h264.packaging = Some(Packaging::AnnexB);
+    assert!(evaluate(&h264, RemuxTarget::Mp4).1.starts_with("Annex-B"));
+    let hev1 = stream(Type::Video, Id::HEVC, b"hev1");
+    assert_eq!(evaluate(&hev1, RemuxTarget::Mp4).1, "tagged hev1; use -tag:v hvc1 for Apple players");
+  }
+
+  #[test]
+  fn extradata_packaging() {
+    assert_eq!(packaging(&[1, 0x64, 0, 0x1f]), Packaging::LengthPrefixed);
+    assert_eq!(packaging(&[0, 0, 0, 1, 0x67]), Packaging::AnnexB);
+    assert_eq!(packaging(&[]), Packaging::AnnexB);
+  }
+
+  #[test]
+  fn codec_tags() {
+    assert_eq!(format_tag(u32::from_le_bytes(*b"avc1")), "avc1");
+    assert_eq!(format_tag(0), "none");
+    assert_eq!(format_tag(0x0000_001b), "0x0000001b");
+  }
+
+  #[test]
+  fn target_names() {
+    for target in [RemuxTarget::Mp4, RemuxTarget::Mkv, RemuxTarget::Ts] {
+      assert_eq!(RemuxTarget::parse(target.name()), Some(target));
+    }
+    assert_eq!(RemuxTarget::parse("avi"), None);
+  }
+}
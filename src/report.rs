@@ -0,0 +1,977 @@
+// --json: the analysis as a single JSON object on stdout. The fields mirror the human-readable
+// report; values the analysis couldn't determine are null.
+
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+
+use crate::attachments::{self, Attachment};
+use crate::bitrate;
+use crate::cadence::GopStats;
+use crate::data_streams::{self, DataStream};
+use crate::faststart::{self, Layout};
+use crate::chapters::Chapter;
+use crate::findings::Finding;
+use crate::fps_stability;
+use crate::fragments::Fragmented;
+use crate::gop_structure::GopStructure;
+use crate::metadata;
+use crate::packet_sizes;
+use crate::profile;
+use crate::random_access::IdrIntervals;
+use crate::strict;
+use crate::timestamp::Timestamp;
+use crate::timecode::TimecodeReport;
+use crate::tmcd::TimecodeTrackReport;
+use crate::transport::TransportReport;
+
+#[derive(Serialize)]
+pub struct TimeBase {
+  pub numerator: i32,
+  pub denominator: i32,
+}
+
+#[derive(Serialize)]
+pub struct FindingReport {
+  pub code: &'static str,
+  pub severity: &'static str,
+  pub message: String,
+  // Position in the analyzed stream, when the finding has one.
+  pub timestamp_ms: Option<i64>,
+  pub timestamp_raw: Option<Timestamp>,
+  // The code's documentation, as `explain` prints it.
+  pub doc_ref: String,
+}
+
+impl From<&Finding> for FindingReport {
+  fn from(finding: &Finding) -> Self {
+    FindingReport {
+      code: finding.definition.code,
+      severity: finding.severity().label(),
+      message: finding.message.clone(),
+      timestamp_ms: finding.timestamp.and_then(|ts| ts.signed_millis()),
+      timestamp_raw: finding.timestamp,
+      doc_ref: finding.definition.doc_ref(),
+    }
+  }
+}
+
+#[derive(Serialize)]
+pub struct ViolationReport {
+  pub id: &'static str,
+  pub count: u64,
+  pub message: String,
+  pub first_ms: Option<i64>,
+  pub first_raw: Option<Timestamp>,
+  // Listed with --strict-allow, so it doesn't fail the run.
+  pub allowed: bool,
+}
+
+impl From<&strict::Violation> for ViolationReport {
+  fn from(violation: &strict::Violation) -> Self {
+    ViolationReport {
+      id: violation.id,
+      count: violation.count,
+      message: violation.description.clone(),
+      first_ms: violation.first.and_then(|ts| ts.signed_millis()),
+      first_raw: violation.first,
+      allowed: violation.allowed,
+    }
+  }
+}
+
+// Decoded frames per picture type.
+#[derive(Serialize)]
+pub struct PictureTypes {
+  pub i: i64,
+  pub p: i64,
+  pub b: i64,
+  pub other: i64,
+}
+
+#[derive(Serialize)]
+pub struct KeyframeReport {
+  // Index among the decoded frames.
+  pub index: i64,
+  pub timestamp_ms: Option<i64>,
+  pub timestamp_raw: Option<Timestamp>,
+}
+
+// Video packet sizes in bytes.
+#[derive(Serialize)]
+pub struct PacketSizeStats {
+  pub count: u64,
+  pub total_bytes: u64,
+  pub min: Option<u64>,
+  pub max: Option<u64>,
+  pub average: Option<u64>,
+}
+
+impl From<&packet_sizes::Sizes> for PacketSizeStats {
+  fn from(sizes: &packet_sizes::Sizes) -> Self {
+    PacketSizeStats {
+      count: sizes.count,
+      total_bytes: sizes.total_bytes,
+      min: sizes.min,
+      max: sizes.max,
+      average: sizes.average(),
+    }
+  }
+}
+
+#[derive(Serialize)]
+pub struct PacketReport {
+  // Position among the video packets, in decode order.
+  pub index: u64,
+  pub timestamp_ms: Option<i64>,
+  pub timestamp_raw: Option<Timestamp>,
+  pub size: u64,
+  pub key: bool,
+}
+
+#[derive(Serialize)]
+pub struct PacketSizeReport {
+  pub all: PacketSizeStats,
+  pub key: PacketSizeStats,
+  pub other: PacketSizeStats,
+  // Largest first: the largest packet, or --top-packets of them.
+  pub largest: Vec<PacketReport>,
+}
+
+// Key frames of H.264 and HEVC streams by the kind of random access point they are.
+#[derive(Serialize)]
+pub struct RandomAccessReport {
+  pub idr: u64,
+  pub cra_bla: u64,
+  pub recovery_point: u64,
+  pub other: u64,
+  pub idr_intervals: Option<IdrIntervals>,
+}
+
+// Video bitrate over one-second buckets, in bits per second.
+#[derive(Serialize)]
+pub struct BitrateReport {
+  pub min_bps: u64,
+  pub avg_bps: u64,
+  pub max_bps: u64,
+  // Start of the second with the highest bitrate.
+  pub peak_ms: i64,
+}
+
+impl From<&bitrate::Summary> for BitrateReport {
+  fn from(summary: &bitrate::Summary) -> Self {
+    BitrateReport { min_bps: summary.min_bps, avg_bps: summary.avg_bps, max_bps: summary.max_bps, peak_ms: summary.peak_ms }
+  }
+}
+
+// Frames per second of presentation time, over the full seconds.
+#[derive(Serialize)]
+pub struct FpsStabilityReport {
+  pub full_seconds: usize,
+  // Fewest and most frames of a second, with the start of that second.
+  pub min_fps: u64,
+  pub min_second_ms: i64,
+  pub max_fps: u64,
+  pub max_second_ms: i64,
+  pub mean_fps: f64,
+  pub stddev_fps: f64,
+  // The seconds furthest from the mean, furthest first.
+  pub worst_seconds: Vec<SecondReport>,
+}
+
+impl From<&fps_stability::Summary> for FpsStabilityReport {
+  fn from(summary: &fps_stability::Summary) -> Self {
+    FpsStabilityReport {
+      full_seconds: summary.full_seconds,
+      min_fps: summary.min.0,
+      min_second_ms: summary.min.1,
+      max_fps: summary.max.0,
+      max_second_ms: summary.max.1,
+      mean_fps: summary.mean,
+      stddev_fps: summary.stddev,
+      worst_seconds: summary.worst.iter().map(|&(start_ms, frames)| SecondReport { start_ms, frames }).collect(),
+    }
+  }
+}
+
+#[derive(Serialize)]
+pub struct SecondReport {
+  pub start_ms: i64,
+  pub frames: u64,
+}
+
+// Frames estimated dropped from long gaps between frame timestamps, and repeated timestamps.
+#[derive(Serialize)]
+pub struct FrameGapReport {
+  pub median_interval_ms: f64,
+  pub dropped_frames: u64,
+  pub duplicate_frames: u64,
+  pub gap_count: usize,
+  // The longest gaps, longest first.
+  pub worst_gaps: Vec<GapReport>,
+}
+
+#[derive(Serialize)]
+pub struct GapReport {
+  // Timestamp of the frame before the gap.
+  pub start_ms: Option<i64>,
+  pub length_ms: Option<i64>,
+  pub start_raw: Timestamp,
+  pub length_raw: Timestamp,
+  pub missing_frames: u64,
+}
+
+// Exact packet counts and the totals estimated from the sample, with --keyframes-only or
+// --sample-every. frame_count and the other frame figures of the report are the sample's.
+#[derive(Serialize)]
+pub struct SamplingReport {
+  pub mode: String,
+  pub video_packets: i64,
+  pub key_packets: i64,
+  pub sampled_packets: i64,
+  pub estimated_frame_count: Option<i64>,
+  pub estimated_duration_ms: Option<u64>,
+}
+
+// Interlacing of the decoded frames: "progressive", "TFF", "BFF" or "mixed".
+#[derive(Serialize)]
+pub struct InterlaceReport {
+  pub interlaced_frames: u64,
+  pub progressive_frames: u64,
+  pub detected_scan_type: &'static str,
+  // Order of the interlaced frames; null without any.
+  pub detected_field_order: Option<&'static str>,
+  // Field order of the stream parameters; null when unknown.
+  pub declared_field_order: Option<&'static str>,
+}
+
+// Closed captions: "CEA-608" and "CEA-708" kinds found in the A53 side data of the frames, and the
+// indexes of caption streams of their own.
+#[derive(Serialize)]
+pub struct CaptionReport {
+  pub present: bool,
+  pub kinds: Vec<&'static str>,
+  pub frames_with_captions: u64,
+  pub first_caption_ms: Option<i64>,
+  pub last_caption_ms: Option<i64>,
+  pub first_caption_raw: Option<Timestamp>,
+  pub last_caption_raw: Option<Timestamp>,
+  pub caption_streams: Vec<usize>,
+}
+
+// Aspect ratios as "num:den" strings. The SARs are null when unspecified, in which case square
+// pixels are assumed for the DAR and sar_assumed is true.
+#[derive(Serialize)]
+pub struct AspectRatioReport {
+  pub stream_sar: Option<String>,
+  pub frame_sar: Option<String>,
+  pub sar_assumed: bool,
+  pub dar: Option<String>,
+  pub anamorphic: bool,
+}
+
+// Clockwise rotation and flips a player applies, 0 degrees without rotation metadata, and the
+// frame size once they're applied. The display matrix and rotate tag values are null when absent.
+#[derive(Serialize)]
+pub struct RotationReport {
+  pub degrees: f64,
+  pub horizontal_flip: bool,
+  pub vertical_flip: bool,
+  pub display_matrix_degrees: Option<f64>,
+  pub rotate_tag_degrees: Option<f64>,
+  pub display_width: Option<u32>,
+  pub display_height: Option<u32>,
+}
+
+// Pixel format of the opened decoder and of the first decoded frame, and every decoded frame whose
+// format differs from the previous one. Depths and subsampling are null for hardware formats.
+#[derive(Serialize)]
+pub struct PixelFormatReport {
+  pub declared: &'static str,
+  pub decoded: Option<&'static str>,
+  pub bits_per_component: Option<u32>,
+  pub chroma_subsampling: Option<&'static str>,
+  pub changes: Vec<PixelFormatChangeReport>,
+}
+
+#[derive(Serialize)]
+pub struct PixelFormatChangeReport {
+  pub frame: i64,
+  // The raw value, as before timestamp_raw.
+  pub timestamp: Option<i64>,
+  pub timestamp_ms: Option<i64>,
+  pub timestamp_raw: Option<Timestamp>,
+  pub from: &'static str,
+  pub to: &'static str,
+}
+
+#[derive(Serialize)]
+pub struct Size {
+  pub width: u32,
+  pub height: u32,
+}
+
+// Size of the first decoded frame and every change after it.
+#[derive(Serialize)]
+pub struct ResolutionReport {
+  pub first: Option<Size>,
+  pub changes: Vec<ResolutionChangeReport>,
+}
+
+#[derive(Serialize)]
+pub struct ResolutionChangeReport {
+  pub frame: i64,
+  pub timestamp_ms: Option<i64>,
+  pub timestamp_raw: Option<Timestamp>,
+  pub from: Size,
+  pub to: Size,
+}
+
+// Color description of the first decoded frame, with "unspecified" for values it leaves unset, and
+// the declared values of the stream parameters next to each.
+#[derive(Serialize)]
+pub struct ColorReport {
+  pub range: &'static str,
+  pub primaries: &'static str,
+  pub transfer: &'static str,
+  pub matrix: &'static str,
+  pub chroma_location: &'static str,
+  pub declared_range: &'static str,
+  pub declared_primaries: &'static str,
+  pub declared_transfer: &'static str,
+  pub declared_matrix: &'static str,
+  pub declared_chroma_location: &'static str,
+  // HDR metadata of the frames; null without any.
+  pub mastering_display: Option<MasteringDisplayReport>,
+  pub content_light_level: Option<ContentLightLevelReport>,
+}
+
+// CIE 1931 xy chromaticities ([x, y], red, green and blue) and luminance in cd/m²; each null when
+// the metadata leaves it out.
+#[derive(Serialize)]
+pub struct MasteringDisplayReport {
+  pub primaries: Option<[[f64; 2]; 3]>,
+  pub white_point: Option<[f64; 2]>,
+  pub min_luminance: Option<f64>,
+  pub max_luminance: Option<f64>,
+}
+
+#[derive(Serialize)]
+pub struct ContentLightLevelReport {
+  pub max_cll: u32,
+  pub max_fall: u32,
+}
+
+// Units counted per wall-clock second ("frames", or "packets" with --no-decode and sampling), and
+// the media time covered per wall-clock second.
+#[derive(Serialize)]
+pub struct ThroughputReport {
+  pub unit: &'static str,
+  pub per_second: f64,
+  pub realtime_factor: Option<f64>,
+}
+
+// The part of the stream analyzed with --start and --end, in milliseconds from its start.
+#[derive(Serialize)]
+pub struct RangeReport {
+  pub start_ms: u64,
+  pub end_ms: Option<u64>,
+  // The key frame decoding started from, at or before start_ms, and the frames decoded before
+  // start_ms that aren't counted.
+  pub first_decoded_frame_ms: Option<i64>,
+  pub first_decoded_frame_raw: Option<Timestamp>,
+  pub frames_before_start: i64,
+}
+
+// With --head-tail: the windows analyzed at both ends of the file, the bytes skipped between
+// them and whether the tail still decodes. A file within both windows is analyzed whole, as the
+// head.
+#[derive(Serialize)]
+pub struct HeadTailReport {
+  pub window_bytes: i64,
+  // Null when the input doesn't report its size.
+  pub file_size: Option<i64>,
+  pub whole_file: bool,
+  pub head: WindowReport,
+  // Null when the tail wasn't analyzed, with the reason in tail_not_analyzed.
+  pub tail: Option<WindowReport>,
+  pub tail_not_analyzed: Option<String>,
+  // Null when the whole file was analyzed.
+  pub unanalyzed: Option<ByteRangeReport>,
+  // No frame could be decoded from the tail; null when the tail wasn't analyzed.
+  pub truncated: Option<bool>,
+}
+
+// One --head-tail window.
+#[derive(Serialize)]
+pub struct WindowReport {
+  pub frame_count: i64,
+  pub keyframe_count: i64,
+  pub decode_errors: u64,
+  // Packets before the first key frame after the seek to the tail, which aren't decoded.
+  pub packets_before_keyframe: u64,
+  pub last_frame_ms: Option<i64>,
+  pub last_frame_raw: Option<Timestamp>,
+}
+
+// Byte offsets of the input, the end excluded.
+#[derive(Serialize)]
+pub struct ByteRangeReport {
+  pub start: i64,
+  pub end: i64,
+}
+
+// A stretch of unchanged pictures, with --detect-freeze.
+#[derive(Serialize)]
+pub struct FreezeSegmentReport {
+  pub start_ms: Option<i64>,
+  pub duration_ms: Option<i64>,
+  pub start_raw: Timestamp,
+  pub duration_raw: Timestamp,
+  pub repeated_frames: u64,
+}
+
+// A frame starting a new scene, with --scenes.
+#[derive(Serialize)]
+pub struct SceneChangeReport {
+  pub timestamp_ms: Option<i64>,
+  pub timestamp_raw: Option<Timestamp>,
+  pub score: f64,
+}
+
+// A video stream besides the analyzed one, with --all-video-streams. The counts are null when the
+// codec can't be decoded.
+#[derive(Serialize)]
+pub struct VideoStreamReport {
+  pub index: usize,
+  pub codec: String,
+  pub width: u32,
+  pub height: u32,
+  pub frame_count: Option<i64>,
+  pub keyframe_count: Option<i64>,
+  pub last_keyframe_index: Option<i64>,
+  pub decode_errors: Option<u64>,
+  pub decoded_duration_ms: Option<u64>,
+  pub last_packet_ms: Option<i64>,
+  pub decoded_duration_raw: Option<Timestamp>,
+  pub last_packet_raw: Option<Timestamp>,
+}
+
+#[derive(Serialize)]
+pub struct VariantReport {
+  pub number: usize,
+  pub stream_index: usize,
+  pub codec: String,
+  pub width: u32,
+  pub height: u32,
+  // Declared in the playlist or manifest, in bits per second.
+  pub bandwidth: Option<u64>,
+  // Null when the variant wasn't decoded.
+  pub frame_count: Option<i64>,
+  pub duration_ms: Option<u64>,
+  pub duration_raw: Option<Timestamp>,
+  pub average_keyframe_interval_ms: Option<u64>,
+  pub longest_keyframe_interval_ms: Option<u64>,
+  pub measured_bitrate: Option<u64>,
+}
+
+#[derive(Serialize)]
+pub struct AudioStreamReport {
+  pub index: usize,
+  pub codec: String,
+  pub sample_rate: u32,
+  pub channels: i32,
+  // Null when the stream doesn't declare which channels it carries.
+  pub channel_layout: Option<String>,
+  pub bit_rate: Option<u64>,
+  pub sample_format: Option<&'static str>,
+  pub language: Option<String>,
+  pub packet_count: u64,
+  // Decoded frames whose sample rate or channels differ from the declared ones; null without decoding.
+  pub mismatched_frames: Option<u64>,
+  // The first of them, e.g. "44100 Hz, 2 channels (stereo)".
+  pub decoded_format: Option<String>,
+  // From the number of decoded samples; null when the codec can't be decoded.
+  pub decoded_duration_ms: Option<u64>,
+  // Span of the packet timestamps, and how much longer (or, negative, shorter) it is than the
+  // video's span.
+  pub timestamp_span_ms: Option<u64>,
+  pub timestamp_span_raw: Option<Timestamp>,
+  pub drift_ms: Option<i64>,
+  // Presentation start after the edit list, and how much later (or, negative, earlier) it is than
+  // the first video frame; the offset is null with --start.
+  pub start_ms: Option<i64>,
+  pub start_raw: Option<Timestamp>,
+  pub start_offset_ms: Option<i64>,
+  // Packets the edit list discarded before the start, and priming samples it skipped.
+  pub edit_discarded_packets: u64,
+  pub priming_samples: u64,
+  // With --loudness.
+  pub loudness: Option<LoudnessReport>,
+  pub last_packet_ms: Option<i64>,
+  pub last_packet_raw: Option<Timestamp>,
+}
+
+// EBU R128 measurement of an audio stream. Values are null when too little audio was measured;
+// error says why a stream couldn't be measured to the end.
+#[derive(Serialize)]
+pub struct LoudnessReport {
+  pub integrated_lufs: Option<f64>,
+  pub loudness_range_lu: Option<f64>,
+  pub true_peak_dbtp: Option<f64>,
+  pub error: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct SubtitleStreamReport {
+  pub index: usize,
+  pub codec: String,
+  pub language: Option<String>,
+  pub packet_count: u64,
+  // Packets with visible text, for text formats; null for bitmap formats.
+  pub cue_count: Option<u64>,
+  pub last_packet_ms: Option<i64>,
+  pub last_packet_raw: Option<Timestamp>,
+}
+
+// A splice_info_section of an SCTE-35 stream.
+#[derive(Serialize)]
+pub struct SpliceReport {
+  pub packet_ms: Option<i64>,
+  pub packet_raw: Option<Timestamp>,
+  // e.g. "splice_insert" or "time_signal"; null when the section couldn't be parsed.
+  pub command: Option<&'static str>,
+  // Splice time of splice_insert and time_signal commands, on the 90 kHz clock of the packets.
+  pub splice_ms: Option<i64>,
+  pub splice_raw: Option<Timestamp>,
+}
+
+#[derive(Serialize)]
+pub struct DataStreamReport {
+  pub index: usize,
+  pub codec: String,
+  pub codec_tag: Option<String>,
+  pub handler: Option<String>,
+  pub packet_count: u64,
+  pub bytes: u64,
+  pub first_packet_ms: Option<i64>,
+  pub last_packet_ms: Option<i64>,
+  pub first_packet_raw: Option<Timestamp>,
+  pub last_packet_raw: Option<Timestamp>,
+  // Null for streams other than SCTE-35.
+  pub splices: Option<Vec<SpliceReport>>,
+}
+
+impl From<&DataStream> for DataStreamReport {
+  fn from(stream: &DataStream) -> Self {
+    let raw = |ts: Option<i64>| ts.map(|ts| Timestamp::new(ts, stream.time_base));
+    let millis = |ts: Option<i64>| raw(ts).and_then(|ts| ts.signed_millis());
+    DataStreamReport {
+      index: stream.index,
+      codec: stream.codec.clone(),
+      codec_tag: stream.tag.clone(),
+      handler: stream.handler.clone(),
+      packet_count: stream.packets,
+      bytes: stream.bytes,
+      first_packet_ms: millis(stream.first_timestamp),
+      last_packet_ms: millis(stream.last_timestamp),
+      first_packet_raw: raw(stream.first_timestamp),
+      last_packet_raw: raw(stream.last_timestamp),
+      splices: stream.splices.as_ref().map(|splices| splices.iter().map(|splice| SpliceReport {
+        packet_ms: millis(splice.packet_timestamp),
+        packet_raw: raw(splice.packet_timestamp),
+        command: splice.command.map(data_streams::command_name),
+        splice_ms: splice.splice_time.and_then(data_streams::splice_millis),
+        splice_raw: splice.splice_time.map(data_streams::splice_timestamp),
+      }).collect()),
+    }
+  }
+}
+
+// The profile and level of a stream, with their names for the codecs that have them.
+#[derive(Serialize)]
+pub struct ProfileReport {
+  pub index: usize,
+  pub codec: String,
+  // Raw values of the codec parameters, -99 when unknown.
+  pub profile: i32,
+  pub level: i32,
+  pub profile_name: Option<&'static str>,
+  pub level_name: Option<String>,
+}
+
+impl From<&profile::Declared> for ProfileReport {
+  fn from(declared: &profile::Declared) -> Self {
+    ProfileReport {
+      index: declared.index,
+      codec: declared.codec.clone(),
+      profile: declared.profile,
+      level: declared.level,
+      profile_name: declared.profile_name(),
+      level_name: declared.level_name(),
+    }
+  }
+}
+
+// A row of the stream table: every stream of the container, with the packets read from it.
+#[derive(Serialize)]
+pub struct StreamSummaryReport {
+  pub index: usize,
+  // "video", "audio", "subtitle", "data", "attachment" or "unknown".
+  pub kind: &'static str,
+  pub codec: String,
+  pub declared_duration_ms: Option<u64>,
+  pub declared_duration_raw: Option<Timestamp>,
+  pub declared_bit_rate: Option<u64>,
+  // Bytes over the packet span, and its difference from the declared rate in percent; the
+  // difference is null when nothing is declared.
+  pub measured_bit_rate: Option<u64>,
+  pub bit_rate_difference_percent: Option<f64>,
+  pub packet_count: u64,
+  pub bytes: u64,
+  // Span of the packet DTS, and whether the declared duration is far from it.
+  pub observed_span_ms: Option<u64>,
+  pub observed_span_raw: Option<Timestamp>,
+  pub duration_mismatch: bool,
+}
+
+// An attachment stream or attached picture.
+#[derive(Serialize)]
+pub struct AttachmentReport {
+  pub index: usize,
+  // "attached_picture" (cover art), "font" or "file".
+  pub kind: &'static str,
+  pub codec: String,
+  pub filename: Option<String>,
+  pub mimetype: Option<String>,
+  pub size_bytes: u64,
+}
+
+impl From<&Attachment> for AttachmentReport {
+  fn from(attachment: &Attachment) -> Self {
+    AttachmentReport {
+      index: attachment.index,
+      kind: match attachment.kind {
+        attachments::Kind::AttachedPicture => "attached_picture",
+        attachments::Kind::Font => "font",
+        attachments::Kind::File => "file",
+      },
+      codec: attachment.codec.clone(),
+      filename: attachment.filename.clone(),
+      mimetype: attachment.mimetype.clone(),
+      size_bytes: attachment.size_bytes,
+    }
+  }
+}
+
+// The bit rate of the whole file and the bytes that aren't packet payload.
+#[derive(Serialize)]
+pub struct ContainerBitRateReport {
+  pub declared_bit_rate: Option<u64>,
+  // File size over the container duration.
+  pub measured_bit_rate: Option<u64>,
+  pub file_bytes: u64,
+  pub payload_bytes: u64,
+  pub overhead_bytes: u64,
+  pub overhead_percent: f64,
+}
+
+// The top-level box structure of a local MP4/MOV file.
+#[derive(Serialize)]
+pub struct Mp4LayoutReport {
+  // Box types in the order they first appear.
+  pub box_order: Vec<String>,
+  pub moov_offset: Option<u64>,
+  pub mdat_offset: Option<u64>,
+  pub fragment_count: u64,
+  // "yes", "no", "fragmented" or "no_moov".
+  pub faststart: &'static str,
+}
+
+impl From<&Layout> for Mp4LayoutReport {
+  fn from(layout: &Layout) -> Self {
+    Mp4LayoutReport {
+      box_order: layout.order.iter().map(|(kind, _)| kind.clone()).collect(),
+      moov_offset: layout.moov_offset,
+      mdat_offset: layout.mdat_offset,
+      fragment_count: layout.fragments,
+      faststart: match layout.verdict() {
+        faststart::Verdict::Faststart => "yes",
+        faststart::Verdict::MoovAfterMdat => "no",
+        faststart::Verdict::Fragmented => "fragmented",
+        faststart::Verdict::NoMoov => "no_moov",
+      },
+    }
+  }
+}
+
+// A track of a fragmented MP4, timed from its track fragment runs.
+#[derive(Serialize)]
+pub struct FragmentTrackReport {
+  pub index: usize,
+  pub track_id: u32,
+  pub fragment_count: u64,
+  pub sample_count: u64,
+  // Before edit lists.
+  pub earliest_presentation_ms: Option<i64>,
+  pub duration_ms: Option<u64>,
+  pub earliest_presentation_raw: Option<Timestamp>,
+  pub duration_raw: Option<Timestamp>,
+}
+
+#[derive(Serialize)]
+pub struct FragmentedReport {
+  // moof boxes.
+  pub fragment_count: u64,
+  // The moov box without a single sample in the fragments.
+  pub init_segment_only: bool,
+  pub tracks: Vec<FragmentTrackReport>,
+}
+
+impl From<&Fragmented> for FragmentedReport {
+  fn from(fragmented: &Fragmented) -> Self {
+    FragmentedReport {
+      fragment_count: fragmented.fragments,
+      init_segment_only: fragmented.init_only(),
+      tracks: fragmented.tracks.iter().map(|track| FragmentTrackReport {
+        index: track.index,
+        track_id: track.track_id,
+        fragment_count: track.fragments,
+        sample_count: track.samples,
+        earliest_presentation_ms: track.earliest_presentation_ms(),
+        duration_ms: track.duration_ms(),
+        earliest_presentation_raw: track.earliest_presentation_raw(),
+        duration_raw: track.duration_raw(),
+      }).collect(),
+    }
+  }
+}
+
+// A chapter of the container, with times in ms on the timeline of the streams.
+#[derive(Serialize)]
+pub struct ChapterReport {
+  pub index: usize,
+  pub start_ms: Option<i64>,
+  pub end_ms: Option<i64>,
+  pub start_raw: Timestamp,
+  pub end_raw: Timestamp,
+  pub title: Option<String>,
+}
+
+impl From<&Chapter> for ChapterReport {
+  fn from(chapter: &Chapter) -> Self {
+    ChapterReport {
+      index: chapter.index,
+      start_ms: chapter.start_ms,
+      end_ms: chapter.end_ms,
+      start_raw: chapter.start,
+      end_raw: chapter.end,
+      title: chapter.title.clone(),
+    }
+  }
+}
+
+// Metadata tags by scope: the container's, and each stream's by stream index. Values are kept as
+// read, with no escaping beyond JSON's.
+#[derive(Serialize)]
+pub struct MetadataReport {
+  pub format: BTreeMap<String, String>,
+  pub streams: BTreeMap<usize, BTreeMap<String, String>>,
+}
+
+impl From<&metadata::Tags> for MetadataReport {
+  fn from(tags: &metadata::Tags) -> Self {
+    let map = |entries: &metadata::Entries| entries.iter().cloned().collect();
+    MetadataReport {
+      format: map(&tags.format),
+      streams: tags.streams.iter().map(|(index, entries)| (*index, map(entries))).collect(),
+    }
+  }
+}
+
+#[derive(Serialize, Default)]
+pub struct AnalysisReport {
+  pub path: String,
+  // Index of the analyzed video stream, null for files without video.
+  pub video_stream_index: Option<usize>,
+  pub codec: Option<String>,
+  pub time_base: Option<TimeBase>,
+  pub container_duration_ms: Option<u64>,
+  pub container_duration_raw: Option<Timestamp>,
+  // Span of the decoded frame timestamps.
+  pub decoded_duration_ms: Option<u64>,
+  pub decoded_duration_raw: Option<Timestamp>,
+  // Difference between the container duration (the length of the range with --start or --end)
+  // and the last frame time, and whether it is within the tolerance.
+  pub duration_difference_ms: Option<u64>,
+  pub duration_check_passed: Option<bool>,
+  // Whether every audio stream's packet span is within the tolerance of the video's; null without
+  // audio.
+  pub av_drift_check_passed: Option<bool>,
+  // Declared start times, and the timestamps of the first video packet and frame; the first frame's
+  // is the start offset of the video.
+  pub container_start_time_ms: Option<i64>,
+  pub stream_start_time_ms: Option<i64>,
+  pub first_packet_ms: Option<i64>,
+  pub start_offset_ms: Option<i64>,
+  pub container_start_time_raw: Option<Timestamp>,
+  pub stream_start_time_raw: Option<Timestamp>,
+  pub first_packet_raw: Option<Timestamp>,
+  pub start_offset_raw: Option<Timestamp>,
+  // Null when no frame was decoded.
+  pub interlacing: Option<InterlaceReport>,
+  pub aspect_ratio: Option<AspectRatioReport>,
+  pub rotation: Option<RotationReport>,
+  pub pixel_format: Option<PixelFormatReport>,
+  pub resolution: Option<ResolutionReport>,
+  // Null with --no-decode.
+  pub captions: Option<CaptionReport>,
+  pub timecode_tracks: Vec<TimecodeTrackReport>,
+  pub timecodes: Option<TimecodeReport>,
+  pub color: Option<ColorReport>,
+  // With --keyframes-only or --sample-every, the packet counts and estimated totals.
+  pub sampling: Option<SamplingReport>,
+  // With --start or --end, the analyzed range.
+  pub analyzed_range: Option<RangeReport>,
+  // With --head-tail, the two windows; the frame figures of the whole file are then null.
+  pub head_tail: Option<HeadTailReport>,
+  // Where the frame figures come from: "decode" for decoded frames, "packets" for video packets
+  // counted without decoding (--no-decode). Null when there are no frame figures.
+  pub frame_source: Option<&'static str>,
+  // "software" or the hardware device the frames were decoded on.
+  pub decode_path: Option<String>,
+  pub frame_count: Option<i64>,
+  pub keyframe_count: Option<i64>,
+  pub picture_types: Option<PictureTypes>,
+  pub last_keyframe_index: Option<i64>,
+  // Time of the last key frame, and the time and frames from it to the last frame.
+  pub last_keyframe_ms: Option<i64>,
+  pub keyframe_tail_ms: Option<i64>,
+  pub last_keyframe_raw: Option<Timestamp>,
+  pub keyframe_tail_raw: Option<Timestamp>,
+  pub keyframe_tail_frames: Option<i64>,
+  pub gop: Option<GopStats>,
+  pub gop_structure: Option<GopStructure>,
+  pub random_access: Option<RandomAccessReport>,
+  pub packet_sizes: Option<PacketSizeReport>,
+  pub bitrate: Option<BitrateReport>,
+  pub fps_stability: Option<FpsStabilityReport>,
+  pub frame_gaps: Option<FrameGapReport>,
+  // With --detect-freeze.
+  pub freeze_segments: Option<Vec<FreezeSegmentReport>>,
+  // With --scenes.
+  pub scene_changes: Option<Vec<SceneChangeReport>>,
+  // With --keyframes.
+  pub keyframes: Option<Vec<KeyframeReport>>,
+  pub decode_errors: Option<u64>,
+  // Number of decode errors per error message, and the times of the first and last one.
+  pub decode_error_kinds: Option<BTreeMap<String, u64>>,
+  pub first_decode_error_ms: Option<i64>,
+  pub last_decode_error_ms: Option<i64>,
+  pub first_decode_error_raw: Option<Timestamp>,
+  pub last_decode_error_raw: Option<Timestamp>,
+  pub highest_dts: Option<i64>,
+  pub lowest_dts: Option<i64>,
+  pub lowest_pts: Option<i64>,
+  // Video packets and counted frames with a PTS before zero.
+  pub negative_pts_packets: u64,
+  pub negative_pts_frames: u64,
+  pub declared_fps: Option<f64>,
+  pub measured_fps: Option<f64>,
+  pub modal_fps: Option<f64>,
+  pub frame_rate_mode: Option<String>,
+  pub modal_frame_interval_ms: Option<f64>,
+  pub frame_interval_stddev_ms: Option<f64>,
+  pub irregular_frame_intervals: Option<u64>,
+  // Empty for a file without chapters; null when the chapters weren't read (--head-tail).
+  pub chapters: Option<Vec<ChapterReport>>,
+  pub metadata: Option<MetadataReport>,
+  // With --remux-target, whether every stream can be stream-copied into the target.
+  pub copyable: Option<bool>,
+  // With --all-video-streams, and for the variants of an HLS or DASH input.
+  pub video_streams: Vec<VideoStreamReport>,
+  // The variants of an HLS or DASH input; null for any other input.
+  pub variants: Option<Vec<VariantReport>>,
+  pub audio_streams: Vec<AudioStreamReport>,
+  pub subtitle_streams: Vec<SubtitleStreamReport>,
+  pub data_streams: Vec<DataStreamReport>,
+  // Every stream of the container; empty with --head-tail.
+  pub streams: Vec<StreamSummaryReport>,
+  // The analyzed video stream and every audio stream, and with --require-profile whether they meet
+  // it.
+  pub profiles: Vec<ProfileReport>,
+  pub profile_check_passed: Option<bool>,
+  // Null when the input size is unknown or not every packet was read.
+  pub container_bit_rate: Option<ContainerBitRateReport>,
+  pub attachments: Vec<AttachmentReport>,
+  // Null for inputs that aren't local MP4/MOV files.
+  pub mp4_layout: Option<Mp4LayoutReport>,
+  // Null unless the input is a fragmented MP4 (fMP4, CMAF).
+  pub fragmented: Option<FragmentedReport>,
+  // Null unless the input is an MPEG transport stream.
+  pub transport: Option<TransportReport>,
+  pub findings: Vec<FindingReport>,
+  // With --strict.
+  pub strict_violations: Option<Vec<ViolationReport>>,
+  // With --verdict.
+  pub verdict: Option<String>,
+  pub execution_time_ms: u64,
+  // Peak resident memory of the process at the end of the analysis, when the platform reports it.
+  pub peak_memory_bytes: Option<u64>,
+  // Threads the opened video decoder runs with.
+  pub decoder_threads: Option<usize>,
+  // Decode speed; null when the run was too short to time.
+  pub throughput: Option<ThroughputReport>,
+  // With AnalyzeOptions::html, the report as an HTML page; never part of the JSON.
+  #[serde(skip)]
+  pub html: Option<String>,
+}
+
+// Entry for an input file whose analysis failed.
+#[derive(Serialize)]
+pub struct FileError {
+  pub path: String,
+  pub error: String,
+}
+
+// Pretty-printed JSON of a report or file error.
+pub fn to_json<T: Serialize>(value: &T) -> String {
+  serde_json::to_string_pretty(value).unwrap_or_else(|e| {
+    eprintln!("Failed to serialize the report: {}", e);
+    "null".to_string()
+  })
+}
+
+// Compact JSON on a single line, for --json-lines.
+pub fn to_json_line<T: Serialize>(value: &T) -> String {
+  serde_json::to_string(value).unwrap_or_else(|e| {
+    eprintln!("Failed to serialize the report: {}", e);
+    "null".to_string()
+  })
+}
+
+// Print the report as pretty-printed JSON.
+pub fn print(report: &AnalysisReport) {
+  println!("{}", to_json(report));
+}
+
+#[cfg(test)]
+mod tests {
+  use ffmpeg::Rational;
+
+  use super::*;
+  use crate::findings;
+
+  #[test]
+  fn finding_times_carry_their_raw_timestamp() {
+    let finding = Finding::new(&findings::DECODE_ERRORS, "3 decode errors in the video stream")
+      .at(Timestamp::new(153600, Rational::new(1, 15360)));
+    let json: serde_json::Value = serde_json::from_str(&to_json(&FindingReport::from(&finding))).unwrap();
+    assert_eq!(json["timestamp_ms"], 10000);
+    assert_eq!(json["timestamp_raw"], serde_json::json!({"value": 153600, "time_base": "1/15360"}));
+
+    let json: serde_json::Value = serde_json::from_str(&to_json(&FindingReport::from(&Finding::new(&findings::DECODE_ERRORS, ""))))
+      .unwrap();
+    assert!(json["timestamp_ms"].is_null() && json["timestamp_raw"].is_null());
+  }
+}
@@ -0,0 +1,132 @@
+// Resolution of the decoded frames. Live captures change it when the source switches, which
+// transcoders configured from the first frame don't expect, so every decoded frame's size is
+// compared with the previous one's.
+
+use ffmpeg::Rational;
+
+use crate::findings::{self, Finding};
+use crate::report::{ResolutionChangeReport, ResolutionReport, Size};
+use crate::timestamp::{self, Timestamp};
+use crate::{format_with_commas, BLUE_BOLD, RESET};
+
+// A decoded frame whose size differs from the previous frame's.
+pub struct Change {
+  pub frame: i64,
+  pub timestamp: Option<i64>,
+  pub from: (u32, u32),
+  pub to: (u32, u32),
+}
+
+#[derive(Default)]
+pub struct Tracker {
+  pub first: Option<(u32, u32)>,
+  last: Option<(u32, u32)>,
+  pub changes: Vec<Change>,
+}
+
+impl Tracker {
+  pub fn push(&mut self, size: (u32, u32), frame: i64, timestamp: Option<i64>) {
+    match self.last {
+      None => self.first = Some(size),
+      Some(last) if last != size => self.changes.push(Change { frame, timestamp, from: last, to: size }),
+      Some(_) => {}
+    }
+    self.last = Some(size);
+  }
+}
+
+fn describe((width, height): (u32, u32)) -> String {
+  format!("{}x{}", width, height)
+}
+
+pub fn to_report(tracker: &Tracker, time_base: Rational) -> ResolutionReport {
+  ResolutionReport {
+    first: tracker.first.map(|(width, height)| Size { width, height }),
+    changes: tracker.changes.iter().map(|change| ResolutionChangeReport {
+      frame: change.frame,
+      timestamp_ms: change.timestamp.and_then(|ts| Timestamp::new(ts, time_base).signed_millis()),
+      timestamp_raw: change.timestamp.map(|ts| Timestamp::new(ts, time_base)),
+      from: Size { width: change.from.0, height: change.from.1 },
+      to: Size { width: change.to.0, height: change.to.1 },
+    }).collect(),
+  }
+}
+
+// Print the resolution section: the size the codec parameters declare, the first decoded frame's
+// and every change after it. Returns a finding for a declared size the frames don't have and one
+// for the changes.
+pub fn report(tracker: &Tracker, declared: (u32, u32), time_base: Rational, verbose: bool) -> Vec<Finding> {
+  let mut issues = Vec::new();
+  out!("{}{}Resolution - {}", RESET, BLUE_BOLD, RESET);
+  out!("Declared resolution (codec parameters): {}", describe(declared));
+  let Some(first) = tracker.first else {
+    out!("Decoded resolution: unknown (no frames decoded)");
+    return issues;
+  };
+  out!("Decoded resolution: {}", describe(first));
+  if declared.0 > 0 && declared.1 > 0 && declared != first {
+    issues.push(Finding::new(&findings::DECLARED_SIZE_MISMATCH, format!(
+      "codec parameters declare {} but the first frame decodes to {}", describe(declared), describe(first))));
+  }
+  if tracker.changes.is_empty() {
+    out!("Resolution changes: none");
+    return issues;
+  }
+  out!("Resolution changes: {}", format_with_commas(tracker.changes.len() as i64));
+  for change in &tracker.changes {
+    out!("  Frame {} ({}): {} to {}", format_with_commas(change.frame),
+      timestamp::describe_optional(change.timestamp.map(|ts| Timestamp::new(ts, time_base)), verbose),
+      describe(change.from), describe(change.to));
+  }
+  let change = &tracker.changes[0];
+  let finding = Finding::new(&findings::RESOLUTION_CHANGE, format!("{} resolution changes, the first from {} to {}",
+    format_with_commas(tracker.changes.len() as i64), describe(change.from), describe(change.to)));
+  issues.push(match change.timestamp {
+    Some(ts) => finding.at(Timestamp::new(ts, time_base)),
+    None => finding,
+  });
+  issues
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn track(sizes: &[(u32, u32)]) -> Tracker {
+    let mut tracker = Tracker::default();
+    for (frame, &size) in sizes.iter().enumerate() {
+      tracker.push(size, frame as i64, Some(frame as i64 * 3600));
+    }
+    tracker
+  }
+
+  fn quiet_report(tracker: &Tracker, declared: (u32, u32)) -> Vec<&'static str> {
+    crate::QUIET.with(|quiet| quiet.set(true));
+    report(tracker, declared, Rational::new(1, 90000), false).iter().map(|finding| finding.definition.code).collect()
+  }
+
+  #[test]
+  fn constant_size() {
+    let tracker = track(&[(1920, 1080); 5]);
+    assert_eq!(tracker.first, Some((1920, 1080)));
+    assert!(tracker.changes.is_empty());
+    assert!(quiet_report(&tracker, (1920, 1080)).is_empty());
+  }
+
+  #[test]
+  fn every_switch_is_a_change() {
+    let tracker = track(&[(1280, 720), (1280, 720), (1920, 1080), (1920, 1080), (1280, 720)]);
+    let changes: Vec<_> = tracker.changes.iter().map(|c| (c.frame, c.timestamp, c.from, c.to)).collect();
+    assert_eq!(changes, [(2, Some(7200), (1280, 720), (1920, 1080)), (4, Some(14400), (1920, 1080), (1280, 720))]);
+    assert_eq!(quiet_report(&tracker, (1280, 720)), [findings::RESOLUTION_CHANGE.code]);
+  }
+
+  #[test]
+  fn declared_size_the_frames_dont_have() {
+    let tracker = track(&[(1920, 1080)]);
+    assert_eq!(quiet_report(&tracker, (1920, 1088)), [findings::DECLARED_SIZE_MISMATCH.code]);
+    // An undeclared size is no mismatch, and without frames there is nothing to compare.
+    assert!(quiet_report(&tracker, (0, 0)).is_empty());
+    assert!(quiet_report(&Tracker::default(), (1920, 1080)).is_empty());
+  }
+}
@@ -0,0 +1,146 @@
+// Display orientation of the video stream: the rotation and flip a player applies to the decoded
+// frames, from the display matrix side data and the legacy "rotate" tag. Phone recordings are stored
+// landscape and rotated on display, so a player ignoring the matrix shows them sideways.
+
+use ffmpeg::codec::packet::side_data;
+
+use crate::findings::{self, Finding};
+use crate::report::RotationReport;
+
+// Clockwise rotation in degrees, from 0 to 360, applied after the flips.
+#[derive(Clone, Copy, Default, PartialEq)]
+pub struct Orientation {
+  pub degrees: f64,
+  pub horizontal_flip: bool,
+  pub vertical_flip: bool,
+}
+
+impl Orientation {
+  // Whether the displayed picture is transposed: rotated by a quarter or three quarters of a turn.
+  pub fn swaps_dimensions(&self) -> bool {
+    let quarter = (normalize(self.degrees) / 90.0).round() as i64;
+    quarter % 2 == 1
+  }
+
+  pub fn describe(&self) -> String {
+    let flips = match (self.horizontal_flip, self.vertical_flip) {
+      (false, false) => "",
+      (true, false) => ", flipped horizontally",
+      (false, true) => ", flipped vertically",
+      (true, true) => ", flipped horizontally and vertically",
+    };
+    format!("{}° clockwise{}", format_degrees(self.degrees), flips)
+  }
+}
+
+fn normalize(degrees: f64) -> f64 {
+  let degrees = degrees.rem_euclid(360.0);
+  // Matrices written with rounding errors land a hair below 360.
+  if degrees > 359.5 { 0.0 } else { degrees }
+}
+
+fn format_degrees(degrees: f64) -> String {
+  if degrees.fract() == 0.0 { format!("{}", degrees) } else { format!("{:.2}", degrees) }
+}
+
+// Read the orientation from the nine 32-bit entries of an FFmpeg display matrix, as FFmpeg's own
+// autorotation does: a negative determinant is a horizontal flip, and the rotation is the one left
+// once it's undone. A horizontal flip turned by half a turn is the same picture as a vertical flip.
+fn from_display_matrix(bytes: &[u8]) -> Option<Orientation> {
+  if bytes.len() < 9 * 4 {
+    return None;
+  }
+  let entry = |i: usize| i32::from_ne_bytes(bytes[i * 4..i * 4 + 4].try_into().unwrap()) as f64;
+  let mut matrix = [entry(0), entry(1), entry(3), entry(4)];
+  let horizontal_flip = matrix[0] * matrix[3] - matrix[1] * matrix[2] < 0.0;
+  if horizontal_flip {
+    matrix[0] = -matrix[0];
+    matrix[2] = -matrix[2];
+  }
+  let scale_x = matrix[0].hypot(matrix[2]);
+  let scale_y = matrix[1].hypot(matrix[3]);
+  if scale_x == 0.0 || scale_y == 0.0 {
+    return None;
+  }
+  let clockwise = (matrix[1] / scale_y).atan2(matrix[0] / scale_x).to_degrees();
+  let degrees = normalize((clockwise * 100.0).round() / 100.0);
+  if horizontal_flip && degrees == 180.0 {
+    return Some(Orientation { degrees: 0.0, horizontal_flip: false, vertical_flip: true });
+  }
+  Some(Orientation { degrees, horizontal_flip, vertical_flip: false })
+}
+
+// Rotation metadata of a stream. Both fields are None when the stream carries none.
+pub struct Metadata {
+  pub display_matrix: Option<Orientation>,
+  // Clockwise degrees of the legacy "rotate" tag.
+  pub rotate_tag: Option<f64>,
+}
+
+impl Metadata {
+  pub fn of_stream(stream: &ffmpeg::format::stream::Stream) -> Self {
+    let display_matrix = stream.side_data()
+      .find(|data| data.kind() == side_data::Type::DisplayMatrix)
+      .and_then(|data| from_display_matrix(data.data()));
+    let rotate_tag = stream.metadata().get("rotate")
+      .and_then(|value| value.trim().parse::<f64>().ok())
+      .map(normalize);
+    Metadata { display_matrix, rotate_tag }
+  }
+
+  // The orientation players apply: the display matrix, which FFmpeg and most players prefer, then
+  // the tag, then none.
+  pub fn effective(&self) -> Orientation {
+    self.display_matrix
+      .or_else(|| self.rotate_tag.map(|degrees| Orientation { degrees, ..Orientation::default() }))
+      .unwrap_or_default()
+  }
+
+  // Whether the tag and the matrix both exist and disagree on the rotation.
+  pub fn conflicts(&self) -> bool {
+    matches!((self.display_matrix, self.rotate_tag), (Some(matrix), Some(tag)) if matrix.degrees != tag)
+  }
+}
+
+// Size of the picture as displayed, with the sides swapped for quarter turns.
+pub fn display_size(orientation: &Orientation, (width, height): (u32, u32)) -> (u32, u32) {
+  if orientation.swaps_dimensions() { (height, width) } else { (width, height) }
+}
+
+// The orientation the player applies and where it comes from, with the picture size as displayed.
+pub fn to_report(metadata: &Metadata, display_size: Option<(u32, u32)>) -> RotationReport {
+  let orientation = metadata.effective();
+  RotationReport {
+    degrees: orientation.degrees,
+    horizontal_flip: orientation.horizontal_flip,
+    vertical_flip: orientation.vertical_flip,
+    display_matrix_degrees: metadata.display_matrix.map(|matrix| matrix.degrees),
+    rotate_tag_degrees: metadata.rotate_tag,
+    display_width: display_size.map(|(width, _)| width),
+    display_height: display_size.map(|(_, height)| height),
+  }
+}
+
+// Print the orientation lines of the dimensions section and return a finding when the rotate tag
+// and the display matrix disagree.
+pub fn report(metadata: &Metadata, size: Option<(u32, u32)>) -> Option<Finding> {
+  let orientation = metadata.effective();
+  let source = match (metadata.display_matrix, metadata.rotate_tag) {
+    (Some(_), _) => "display matrix",
+    (None, Some(_)) => "rotate tag",
+    (None, None) => "no rotation metadata",
+  };
+  out!("Rotation: {} ({})", orientation.describe(), source);
+  if let Some(size) = size {
+    let (width, height) = display_size(&orientation, size);
+    out!("Effective display size: {}x{}", width, height);
+  }
+  if !metadata.conflicts() {
+    return None;
+  }
+  let (matrix, tag) = (metadata.display_matrix?, metadata.rotate_tag?);
+  let message = format!("display matrix rotates {}° clockwise but the rotate tag says {}°",
+    format_degrees(matrix.degrees), format_degrees(tag));
+  out!("Rotation conflict: {}", message);
+  Some(Finding::new(&findings::ROTATION_CONFLICT, message))
+}
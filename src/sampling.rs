@@ -0,0 +1,77 @@
+// --keyframes-only and --sample-every: send only some of the video packets to the decoder for a
+// quick check of a long file. Every packet is still read, so packet-level figures stay exact;
+// frame-level figures come from the decoded sample and totals are extrapolated from it.
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Mode {
+  // Decode the key frames only.
+  KeyFrames,
+  // Decode every Nth video packet, and every key frame so the sample has references to build on.
+  Every(u64),
+}
+
+impl Mode {
+  // Whether the video packet with this index (from 0) is sent to the decoder.
+  pub fn decodes(self, index: i64, key: bool) -> bool {
+    match self {
+      Mode::KeyFrames => key,
+      Mode::Every(n) => key || index % n as i64 == 0,
+    }
+  }
+
+  pub fn describe(self) -> String {
+    match self {
+      Mode::KeyFrames => "key frames only".to_string(),
+      Mode::Every(n) => format!("every {} video packets and every key frame", n),
+    }
+  }
+}
+
+// Exact packet counts next to the sample.
+#[derive(Default)]
+pub struct Counts {
+  pub video_packets: i64,
+  pub key_packets: i64,
+  // Packets sent to the decoder.
+  pub sampled_packets: i64,
+  // Packets rejected by the decoder that aren't key frames. With --sample-every, these usually
+  // reference a frame that was skipped, so they aren't counted as decode errors.
+  pub reference_errors: u64,
+  // Index among the video packets (decode order) and timestamp of every key packet. Stand in for
+  // the decoded key frames' indexes, which only count the sample.
+  pub keyframes: Vec<(i64, Option<i64>)>,
+  // Indexes of the first and last packet sent, the stretch the sampled frames span.
+  first_sampled: Option<i64>,
+  last_sampled: Option<i64>,
+}
+
+impl Counts {
+  // Count a video packet. Returns whether it is sent to the decoder.
+  pub fn push(&mut self, mode: Mode, key: bool, timestamp: Option<i64>) -> bool {
+    let index = self.video_packets;
+    self.video_packets += 1;
+    if key {
+      self.key_packets += 1;
+      self.keyframes.push((index, timestamp));
+    }
+    if !mode.decodes(index, key) {
+      return false;
+    }
+    self.sampled_packets += 1;
+    self.first_sampled.get_or_insert(index);
+    self.last_sampled = Some(index);
+    true
+  }
+
+  // Frame count extrapolated from the frames decoded per packet sent.
+  pub fn estimated_frames(&self, decoded_frames: i64) -> Option<i64> {
+    (self.sampled_packets > 0)
+      .then(|| (decoded_frames as f64 * self.video_packets as f64 / self.sampled_packets as f64).round() as i64)
+  }
+
+  // Duration extrapolated from the span of the sampled frames to every packet.
+  pub fn estimated_duration_ms(&self, sampled_span_ms: u64) -> Option<u64> {
+    let sampled = self.last_sampled? - self.first_sampled?;
+    (sampled > 0).then(|| (sampled_span_ms as f64 * (self.video_packets - 1) as f64 / sampled as f64).round() as u64)
+  }
+}
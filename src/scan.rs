@@ -0,0 +1,112 @@
+// --recursive: collect the video files under a directory tree, in sorted order so runs over the
+// same tree produce diffable output.
+
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// Extensions analyzed when --extensions isn't given.
+pub const DEFAULT_EXTENSIONS: &[&str] = &["mp4", "mkv", "mov", "ts", "webm"];
+
+fn has_extension(path: &Path, extensions: &[String]) -> bool {
+  path
+    .extension()
+    .and_then(|e| e.to_str())
+    .is_some_and(|e| extensions.iter().any(|wanted| wanted.eq_ignore_ascii_case(e)))
+}
+
+// Walk `dir`, following symlinks but entering every real directory once, so symlink loops end.
+// Entries that can't be read are skipped with a warning on stderr.
+fn walk(dir: &Path, extensions: &[String], visited: &mut HashSet<PathBuf>, files: &mut Vec<PathBuf>) {
+  match fs::canonicalize(dir) {
+    Ok(real) => {
+      if !visited.insert(real) {
+        return;
+      }
+    }
+    Err(e) => {
+      log::warn!("Skipping {}: {}", dir.display(), e);
+      return;
+    }
+  }
+  let entries = match fs::read_dir(dir) {
+    Ok(entries) => entries,
+    Err(e) => {
+      log::warn!("Skipping {}: {}", dir.display(), e);
+      return;
+    }
+  };
+  for entry in entries {
+    let path = match entry {
+      Ok(entry) => entry.path(),
+      Err(e) => {
+        log::warn!("Skipping an entry of {}: {}", dir.display(), e);
+        continue;
+      }
+    };
+    // fs::metadata follows symlinks, so linked directories and files are treated as their targets.
+    match fs::metadata(&path) {
+      Ok(metadata) if metadata.is_dir() => walk(&path, extensions, visited, files),
+      Ok(_) if has_extension(&path, extensions) => match fs::File::open(&path) {
+        Ok(_) => files.push(path),
+        Err(e) => log::warn!("Skipping {}: {}", path.display(), e),
+      },
+      Ok(_) => {}
+      Err(e) => log::warn!("Skipping {}: {}", path.display(), e),
+    }
+  }
+}
+
+// Every readable file under `dir` with one of `extensions`, sorted by path.
+pub fn find_files(dir: &Path, extensions: &[String]) -> Vec<PathBuf> {
+  let mut files = Vec::new();
+  walk(dir, extensions, &mut HashSet::new(), &mut files);
+  files.sort();
+  files
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn extensions(names: &[&str]) -> Vec<String> {
+    names.iter().map(|name| name.to_string()).collect()
+  }
+
+  #[cfg(unix)]
+  #[test]
+  fn files_are_found_once_in_sorted_order() {
+    use std::os::unix::fs::symlink;
+
+    let root = std::env::temp_dir().join(format!("video_analyzer_scan_{}", std::process::id()));
+    let _ = fs::remove_dir_all(&root);
+    fs::create_dir_all(root.join("sub")).unwrap();
+    // A directory named like a video file is still walked.
+    fs::create_dir_all(root.join("video.mp4")).unwrap();
+    for file in ["sub/c.ts", "b.MP4", "a.mkv", "notes.txt", "video.mp4/d.webm"] {
+      fs::write(root.join(file), b"").unwrap();
+    }
+    // A link back up the tree, which must not be followed forever, and a link to a file.
+    symlink("..", root.join("sub/loop")).unwrap();
+    symlink("../a.mkv", root.join("sub/link.mov")).unwrap();
+    symlink("missing.mp4", root.join("dangling.mp4")).unwrap();
+
+    let defaults = find_files(&root, &extensions(DEFAULT_EXTENSIONS));
+    let only_ts = find_files(&root, &extensions(&["ts"]));
+    let _ = fs::remove_dir_all(&root);
+
+    let expected: Vec<PathBuf> = ["a.mkv", "b.MP4", "sub/c.ts", "sub/link.mov", "video.mp4/d.webm"]
+      .iter().map(|file| root.join(file)).collect();
+    assert_eq!(defaults, expected);
+    assert_eq!(only_ts, [root.join("sub/c.ts")]);
+  }
+
+  #[test]
+  fn extensions_ignore_case() {
+    let wanted = extensions(&["mp4", "ts"]);
+    assert!(has_extension(Path::new("a/b.MP4"), &wanted));
+    assert!(has_extension(Path::new("c.ts"), &wanted));
+    assert!(!has_extension(Path::new("c.ts.part"), &wanted));
+    assert!(!has_extension(Path::new("mp4"), &wanted));
+  }
+}
@@ -0,0 +1,130 @@
+// --scenes: scene cuts from the pixel data. Each decoded frame gets a 256-bin histogram of its
+// sampled luma plane, and the score of a frame is the normalized sum of absolute differences to
+// the previous frame's histogram: 0 for the same distribution, 1 for disjoint ones. Key frames are
+// not treated specially; encoders place them on a schedule as much as on cuts.
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use ffmpeg::Rational;
+
+use crate::pixels::PixelSemantics;
+use crate::report::SceneChangeReport;
+use crate::timestamp::Timestamp;
+use crate::{format_signed_millis, format_with_commas, timestamp_to_signed_millis, BLUE_BOLD, RESET};
+
+// Rows and samples skipped between luma samples counted into the histogram.
+const SAMPLE_STEP: usize = 4;
+
+// Cuts listed in the report; the file gets all of them.
+const MAX_LISTED_CUTS: usize = 50;
+
+// The threshold a frame's score has to pass to count as a cut (--scenes), and the CSV file to write
+// the cuts to (--scenes-file).
+pub struct Settings {
+  pub threshold: f64,
+  pub csv: Option<PathBuf>,
+}
+
+pub struct Cut {
+  // Timestamp of the first frame of the new scene, stream time base.
+  pub timestamp: Option<i64>,
+  pub score: f64,
+}
+
+pub struct Tracker {
+  threshold: f64,
+  semantics: Option<PixelSemantics>,
+  // False when the pixel format can't be scanned.
+  pub scanned: bool,
+  previous: Option<[u32; 256]>,
+  pub cuts: Vec<Cut>,
+}
+
+impl Tracker {
+  pub fn new(threshold: f64) -> Self {
+    Tracker { threshold, semantics: None, scanned: false, previous: None, cuts: Vec::new() }
+  }
+
+  // Luma histogram of a frame, with samples above 8 bits scaled down to 8.
+  fn histogram(&mut self, frame: &ffmpeg::util::frame::video::Video) -> Option<[u32; 256]> {
+    let semantics = *self.semantics.get_or_insert_with(|| PixelSemantics::from_frame(frame));
+    let bytes = semantics.luma_sample_bytes()?;
+    let shift = semantics.bit_depth.saturating_sub(8);
+    let data = frame.data(0);
+    let stride = frame.stride(0);
+    let (width, height) = (frame.plane_width(0) as usize, frame.plane_height(0) as usize);
+    let mut histogram = [0u32; 256];
+    for row in (0..height).step_by(SAMPLE_STEP) {
+      let line = &data[row * stride..];
+      for column in (0..width).step_by(SAMPLE_STEP) {
+        let value = match bytes {
+          1 => line[column] as u32,
+          _ => u16::from_le_bytes([line[column * 2], line[column * 2 + 1]]) as u32 >> shift,
+        };
+        histogram[value.min(255) as usize] += 1;
+      }
+    }
+    Some(histogram)
+  }
+
+  // Score a decoded frame against the previous one and keep it when it starts a new scene.
+  pub fn check(&mut self, frame: &ffmpeg::util::frame::video::Video) {
+    let Some(histogram) = self.histogram(frame) else {
+      return;
+    };
+    self.scanned = true;
+    if let Some(previous) = self.previous.replace(histogram) {
+      let total: u64 = histogram.iter().map(|&n| n as u64).sum();
+      if total == 0 {
+        return;
+      }
+      let difference: u64 = histogram.iter().zip(&previous).map(|(&a, &b)| a.abs_diff(b) as u64).sum();
+      let score = difference as f64 / (2 * total) as f64;
+      if score > self.threshold {
+        self.cuts.push(Cut { timestamp: frame.timestamp(), score });
+      }
+    }
+  }
+
+  // Write one row per cut as CSV (--scenes-file).
+  pub fn write_csv(&self, path: &Path, time_base: Rational) -> std::io::Result<()> {
+    let mut out = BufWriter::new(File::create(path)?);
+    writeln!(out, "timestamp_ms,time,score")?;
+    for cut in &self.cuts {
+      let ms = cut.timestamp.and_then(|ts| timestamp_to_signed_millis(ts, time_base));
+      writeln!(out, "{},{},{:.4}", ms.map_or_else(String::new, |ms| ms.to_string()),
+        ms.map_or_else(String::new, format_signed_millis), cut.score)?;
+    }
+    out.flush()
+  }
+}
+
+pub fn to_report(tracker: &Tracker, time_base: Rational) -> Vec<SceneChangeReport> {
+  tracker.cuts.iter().map(|cut| SceneChangeReport {
+    timestamp_ms: cut.timestamp.and_then(|ts| timestamp_to_signed_millis(ts, time_base)),
+    timestamp_raw: cut.timestamp.map(|ts| Timestamp::new(ts, time_base)),
+    score: cut.score,
+  }).collect()
+}
+
+// Print the scene change section.
+pub fn report(tracker: &Tracker, time_base: Rational) {
+  out!("{}{}Scene changes - {}", RESET, BLUE_BOLD, RESET);
+  if !tracker.scanned {
+    out!("Not checked: the pixel format has no luma plane that can be scanned");
+    return;
+  }
+  out!("Scene changes: {} (luma histogram difference above {})", format_with_commas(tracker.cuts.len() as i64),
+    tracker.threshold);
+  for cut in tracker.cuts.iter().take(MAX_LISTED_CUTS) {
+    match cut.timestamp.and_then(|ts| timestamp_to_signed_millis(ts, time_base)) {
+      Some(ms) => out!("{} ({} ms), score {:.3}", format_signed_millis(ms), ms, cut.score),
+      None => out!("no timestamp, score {:.3}", cut.score),
+    }
+  }
+  if tracker.cuts.len() > MAX_LISTED_CUTS {
+    out!("... {} more", format_with_commas((tracker.cuts.len() - MAX_LISTED_CUTS) as i64));
+  }
+}
@@ -0,0 +1,214 @@
+// `self-test` subcommand: encode a short file with known properties, analyze it and compare the
+// measurements against the ground truth. Useful as a smoke test of the local FFmpeg build.
+
+use ffmpeg::format::Pixel;
+use ffmpeg::Rational;
+use std::path::Path;
+
+use crate::{analyze, report, AnalyzeError, AnalyzeOptions, BLUE_BOLD, RESET};
+
+// Ground truth of the synthesized asset: 10 seconds at 25 fps with a key frame every second.
+const WIDTH: u32 = 320;
+const HEIGHT: u32 = 240;
+const FPS: i32 = 25;
+const GOP: u32 = 25;
+pub const FRAMES: i64 = 250;
+
+// An MPEG-2 transport stream whose resolution switches mid-stream, as a live capture does when its
+// source changes, for the tests. Each size lasts SEGMENT_FRAMES frames.
+pub const SIZES: [(u32, u32); 2] = [(1280, 720), (1920, 1080)];
+pub const SEGMENT_FRAMES: i64 = 10;
+
+// Timing checks allow one millisecond of rounding.
+const TOLERANCE_MS: u64 = 1;
+
+// Fill a frame with a diagonal gradient that moves a few pixels per frame, so the encoder has
+// real motion to predict but no scene cuts.
+fn test_pattern(index: i64, width: u32, height: u32) -> ffmpeg::util::frame::video::Video {
+  let mut frame = ffmpeg::util::frame::video::Video::new(Pixel::YUV420P, width, height);
+  let stride = frame.stride(0);
+  let luma = frame.data_mut(0);
+  for y in 0..height as usize {
+    for x in 0..width as usize {
+      luma[y * stride + x] = ((x + y + index as usize * 4) & 0xff) as u8;
+    }
+  }
+  for plane in 1..3 {
+    frame.data_mut(plane).fill(128);
+  }
+  frame.set_pts(Some(index));
+  frame
+}
+
+// Move every packet the encoder has ready into the muxer.
+fn write_encoded(
+  encoder: &mut ffmpeg::encoder::Video,
+  octx: &mut ffmpeg::format::context::Output,
+  stream_index: usize,
+  stream_time_base: Rational,
+) -> Result<(), ffmpeg::Error> {
+  let mut packet = ffmpeg::Packet::empty();
+  while encoder.receive_packet(&mut packet).is_ok() {
+    packet.set_stream(stream_index);
+    packet.rescale_ts(Rational::new(1, FPS), stream_time_base);
+    packet.write_interleaved(octx)?;
+  }
+  Ok(())
+}
+
+// Encode the test asset with the built-in MPEG-4 Part 2 encoder into an MP4 file. Both are part
+// of every FFmpeg build, unlike libx264.
+pub fn synthesize(path: &Path) -> Result<(), ffmpeg::Error> {
+  let codec = ffmpeg::encoder::find(ffmpeg::codec::Id::MPEG4).ok_or(ffmpeg::Error::EncoderNotFound)?;
+  let mut octx = ffmpeg::format::output(&path)?;
+  let global_header = octx.format().flags().contains(ffmpeg::format::Flags::GLOBAL_HEADER);
+
+  let mut encoder = ffmpeg::codec::context::Context::new().encoder().video()?;
+  encoder.set_width(WIDTH);
+  encoder.set_height(HEIGHT);
+  encoder.set_format(Pixel::YUV420P);
+  encoder.set_time_base(Rational::new(1, FPS));
+  encoder.set_frame_rate(Some(Rational::new(FPS, 1)));
+  encoder.set_gop(GOP);
+  encoder.set_max_b_frames(0);
+  if global_header {
+    encoder.set_flags(ffmpeg::codec::Flags::GLOBAL_HEADER);
+  }
+  // Without scene-cut detection the only key frames are the ones the GOP size places.
+  let mut options = ffmpeg::Dictionary::new();
+  options.set("sc_threshold", "1000000000");
+  let mut encoder = encoder.open_as_with(codec, options)?;
+
+  let mut stream = octx.add_stream(codec)?;
+  stream.set_parameters(&encoder);
+  stream.set_time_base(Rational::new(1, FPS));
+  let stream_index = stream.index();
+  octx.write_header()?;
+  // The muxer may pick its own time base while writing the header.
+  let stream_time_base = octx.stream(stream_index).map_or(Rational::new(1, FPS), |s| s.time_base());
+
+  for index in 0..FRAMES {
+    encoder.send_frame(&test_pattern(index, WIDTH, HEIGHT))?;
+    write_encoded(&mut encoder, &mut octx, stream_index, stream_time_base)?;
+  }
+  encoder.send_eof()?;
+  write_encoded(&mut encoder, &mut octx, stream_index, stream_time_base)?;
+  octx.write_trailer()
+}
+
+// Encode SIZES one after the other into a single MPEG-2 stream of a transport stream, each size
+// with an encoder of its own. The sequence header of the second encode carries the new size.
+pub fn synthesize_resolution_change(path: &Path) -> Result<(), ffmpeg::Error> {
+  let codec = ffmpeg::encoder::find(ffmpeg::codec::Id::MPEG2VIDEO).ok_or(ffmpeg::Error::EncoderNotFound)?;
+  let mut octx = ffmpeg::format::output(&path)?;
+  let mut stream_index = None;
+  let mut stream_time_base = Rational::new(1, FPS);
+  let mut index = 0;
+  for (width, height) in SIZES {
+    let mut encoder = ffmpeg::codec::context::Context::new().encoder().video()?;
+    encoder.set_width(width);
+    encoder.set_height(height);
+    encoder.set_format(Pixel::YUV420P);
+    encoder.set_time_base(Rational::new(1, FPS));
+    encoder.set_frame_rate(Some(Rational::new(FPS, 1)));
+    encoder.set_gop(GOP);
+    encoder.set_max_b_frames(0);
+    let mut encoder = encoder.open_as(codec)?;
+    // The stream is declared with the first size, as a capture started before the switch would be.
+    let stream = match stream_index {
+      Some(stream) => stream,
+      None => {
+        let mut stream = octx.add_stream(codec)?;
+        stream.set_parameters(&encoder);
+        stream.set_time_base(Rational::new(1, FPS));
+        let stream = stream.index();
+        octx.write_header()?;
+        stream_time_base = octx.stream(stream).map_or(stream_time_base, |s| s.time_base());
+        stream_index = Some(stream);
+        stream
+      }
+    };
+    for _ in 0..SEGMENT_FRAMES {
+      encoder.send_frame(&test_pattern(index, width, height))?;
+      write_encoded(&mut encoder, &mut octx, stream, stream_time_base)?;
+      index += 1;
+    }
+    encoder.send_eof()?;
+    write_encoded(&mut encoder, &mut octx, stream, stream_time_base)?;
+  }
+  octx.write_trailer()
+}
+
+// Print one assertion and return whether it passed.
+fn check(name: &str, expected: String, actual: String, passed: bool) -> bool {
+  println!("{} {}: expected {}, measured {}", if passed { "PASS" } else { "FAIL" }, name, expected, actual);
+  passed
+}
+
+fn within(actual: Option<u64>, expected: u64) -> bool {
+  actual.is_some_and(|ms| ms.abs_diff(expected) <= TOLERANCE_MS)
+}
+
+fn describe(value: Option<i64>) -> String {
+  value.map_or_else(|| "unknown".to_string(), |value| value.to_string())
+}
+
+fn describe_ms(ms: Option<u64>) -> String {
+  ms.map_or_else(|| "unknown".to_string(), |ms| format!("{}ms", ms))
+}
+
+// Analyze the synthesized file as a caller of the library does, read the figures back from its
+// JSON report and print PASS/FAIL per assertion. Returns whether every assertion passed.
+fn verify(path: &Path) -> Result<bool, AnalyzeError> {
+  let report = analyze(path, &AnalyzeOptions::default())?;
+  // A report that doesn't parse back fails every check below.
+  let json: serde_json::Value = serde_json::from_str(&report::to_json(&report)).unwrap_or_default();
+  let number = |key: &str| json[key].as_f64();
+  let int = |key: &str| json[key].as_i64();
+  let ms = |key: &str| json[key].as_u64();
+
+  let frame_ms = 1000 / FPS as u64;
+  let mut passed = true;
+  let frames = int("frame_count");
+  passed &= check("frame count", FRAMES.to_string(), describe(frames), frames == Some(FRAMES));
+  // Timestamps span one frame less than the file: the last frame starts at (N - 1) / fps.
+  let expected_span_ms = (FRAMES as u64 - 1) * frame_ms;
+  let measured_span_ms = ms("decoded_duration_ms");
+  passed &= check("measured duration", describe_ms(Some(expected_span_ms)), describe_ms(measured_span_ms),
+    within(measured_span_ms, expected_span_ms));
+  let expected_duration_ms = FRAMES as u64 * frame_ms;
+  let declared_duration_ms = ms("container_duration_ms");
+  passed &= check("container duration", describe_ms(Some(expected_duration_ms)), describe_ms(declared_duration_ms),
+    within(declared_duration_ms, expected_duration_ms));
+
+  let expected_keyframes = FRAMES / GOP as i64;
+  let keyframes = int("keyframe_count");
+  passed &= check("key frames", expected_keyframes.to_string(), describe(keyframes), keyframes == Some(expected_keyframes));
+  let gop_ms = GOP as u64 * frame_ms;
+  let interval_ms = |key: &str| json["gop"][key].as_f64().map(|seconds| (seconds * 1000.0).round() as u64);
+  let (min, max) = (interval_ms("min_seconds"), interval_ms("max_seconds"));
+  passed &= check("key frame interval", format!("{}ms", gop_ms),
+    format!("min {}, max {}", describe_ms(min), describe_ms(max)),
+    within(min, gop_ms) && within(max, gop_ms));
+
+  for (name, key) in [("measured frame rate", "measured_fps"), ("declared frame rate", "declared_fps")] {
+    let fps = number(key);
+    passed &= check(name, format!("{} fps", FPS), fps.map_or_else(|| "unknown".to_string(), |fps| format!("{:.3} fps", fps)),
+      fps.is_some_and(|fps| (fps - FPS as f64).abs() < 0.01));
+  }
+  Ok(passed)
+}
+
+// Run the self-test in the system temp directory. Returns whether every assertion passed; errors
+// while encoding or opening the asset are returned as errors.
+pub fn run() -> Result<bool, AnalyzeError> {
+  let path = std::env::temp_dir().join(format!("video_analyzer_self_test_{}.mp4", std::process::id()));
+  println!("{}Self-test - {}", BLUE_BOLD, RESET);
+  println!("Synthesizing {} frames at {} fps, key frame every {} frames: {}", FRAMES, FPS, GOP, path.display());
+
+  let result = synthesize(&path).map_err(AnalyzeError::from).and_then(|_| verify(&path));
+  let _ = std::fs::remove_file(&path);
+  let passed = result?;
+  println!("{}", if passed { "Self-test passed." } else { "Self-test FAILED." });
+  Ok(passed)
+}
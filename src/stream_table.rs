@@ -0,0 +1,166 @@
+// Every stream of the container in one table, the analyzed one or not: its type, codec, declared
+// duration and bit rate next to the packets and bytes read from it. A declared duration far from
+// the span of the stream's packet timestamps is marked, since one of the two misstates the length
+// of the stream. The bit rates measured from the bytes and spans follow, next to the declared ones,
+// and the muxing overhead: the bytes of the file that aren't packet payload.
+
+use ffmpeg::format::context::Input;
+use ffmpeg::media::Type;
+use ffmpeg::Packet;
+
+use crate::report::{ContainerBitRateReport, StreamSummaryReport};
+use crate::timestamp::Timestamp;
+use crate::{container_duration, format_with_commas, input_size, millis_to_clock_format, BLUE_BOLD, RESET};
+
+// A declared duration is marked when it differs from the packet span by more than this share of
+// the longer of the two, and by more than SPAN_TOLERANCE_MS.
+const SPAN_TOLERANCE: f64 = 0.1;
+const SPAN_TOLERANCE_MS: u64 = 1000;
+
+const MARKER: &str = "*";
+
+// Packets read, indexed by stream.
+#[derive(Default)]
+pub struct Counts {
+  pub bytes: Vec<u64>,
+  pub packets: Vec<u64>,
+  // Lowest and highest packet DTS.
+  pub lowest_dts: Vec<Option<i64>>,
+  pub highest_dts: Vec<Option<i64>>,
+}
+
+impl Counts {
+  pub fn push(&mut self, packet: &Packet) {
+    let index = packet.stream();
+    if index >= self.bytes.len() {
+      self.bytes.resize(index + 1, 0);
+      self.packets.resize(index + 1, 0);
+      self.lowest_dts.resize(index + 1, None);
+      self.highest_dts.resize(index + 1, None);
+    }
+    self.bytes[index] += packet.size() as u64;
+    self.packets[index] += 1;
+    if let Some(dts) = packet.dts() {
+      let lowest = &mut self.lowest_dts[index];
+      *lowest = Some(lowest.map_or(dts, |l| l.min(dts)));
+      let highest = &mut self.highest_dts[index];
+      *highest = Some(highest.map_or(dts, |h| h.max(dts)));
+    }
+  }
+}
+
+pub fn type_name(medium: Type) -> &'static str {
+  match medium {
+    Type::Video => "video",
+    Type::Audio => "audio",
+    Type::Subtitle => "subtitle",
+    Type::Data => "data",
+    Type::Attachment => "attachment",
+    Type::Unknown => "unknown",
+  }
+}
+
+// Bits per second of `bytes` over `span_ms`; None for an empty span.
+fn bit_rate(bytes: u64, span_ms: u64) -> Option<u64> {
+  (span_ms > 0).then(|| (bytes as f64 * 8000.0 / span_ms as f64) as u64)
+}
+
+// How far the measured rate is above (or, negative, below) the declared one, in percent; None when
+// either is unknown.
+fn difference_percent(measured: Option<u64>, declared: Option<u64>) -> Option<f64> {
+  let (measured, declared) = (measured?, declared.filter(|&declared| declared > 0)?);
+  Some((measured as f64 - declared as f64) * 100.0 / declared as f64)
+}
+
+fn describe_rates(measured: Option<u64>, declared: Option<u64>) -> String {
+  let kbps = |bps: u64| format!("{} kb/s", format_with_commas((bps / 1000) as i64));
+  format!("measured {}, declared {}{}", measured.map_or_else(|| "unknown".to_string(), kbps),
+    declared.map_or_else(|| "not declared".to_string(), kbps),
+    difference_percent(measured, declared).map_or_else(String::new, |percent| format!(" ({:+.1}%)", percent)))
+}
+
+// Whether a declared duration is wildly off the observed packet span.
+fn span_mismatch(declared_ms: u64, observed_ms: u64) -> bool {
+  let difference = declared_ms.abs_diff(observed_ms);
+  difference > SPAN_TOLERANCE_MS && difference as f64 > declared_ms.max(observed_ms) as f64 * SPAN_TOLERANCE
+}
+
+// Print the table and the bit rates, and return them for the report. `complete` is off when the
+// packets read aren't the whole stream, e.g. for a range or a file still being written, which
+// leaves out the duration marks and the file figures.
+pub fn report(ictx: &Input, counts: &Counts, complete: bool) -> (Vec<StreamSummaryReport>, Option<ContainerBitRateReport>) {
+  let rows: Vec<StreamSummaryReport> = ictx.streams().map(|stream| {
+    let index = stream.index();
+    let parameters = stream.parameters();
+    let declared_rate = unsafe { (*parameters.as_ptr()).bit_rate };
+    let time_base = stream.time_base();
+    let declared_duration = Some(stream.duration()).filter(|&d| d > 0).map(|d| Timestamp::new(d, time_base));
+    let declared_duration_ms = declared_duration.and_then(|d| d.millis());
+    let span = counts.lowest_dts.get(index).copied().flatten().zip(counts.highest_dts.get(index).copied().flatten());
+    let observed_span = span.map(|(lowest, highest)| Timestamp::new(highest - lowest, time_base));
+    let observed_span_ms = observed_span.and_then(|span| span.millis());
+    let bytes = counts.bytes.get(index).copied().unwrap_or(0);
+    let declared_bit_rate = (declared_rate > 0).then_some(declared_rate as u64);
+    let measured_bit_rate = observed_span_ms.and_then(|span| bit_rate(bytes, span));
+    StreamSummaryReport {
+      index,
+      kind: type_name(parameters.medium()),
+      codec: parameters.id().name().to_string(),
+      declared_duration_ms,
+      declared_duration_raw: declared_duration,
+      declared_bit_rate,
+      measured_bit_rate,
+      bit_rate_difference_percent: difference_percent(measured_bit_rate, declared_bit_rate),
+      packet_count: counts.packets.get(index).copied().unwrap_or(0),
+      bytes,
+      observed_span_ms,
+      observed_span_raw: observed_span,
+      duration_mismatch: complete && declared_duration_ms.zip(observed_span_ms)
+        .is_some_and(|(declared, observed)| span_mismatch(declared, observed)),
+    }
+  }).collect();
+
+  out!("{}{}Streams - {}", RESET, BLUE_BOLD, RESET);
+  out!("  {:<3} {:<10} {:<12} {:<22} {:<14} {:>12} {:>16}", "#", "Type", "Codec", "Declared duration",
+    "Declared rate", "Packets", "Bytes");
+  for row in &rows {
+    let duration = row.declared_duration_ms.map_or_else(|| "unknown".to_string(), millis_to_clock_format);
+    let marker = if row.duration_mismatch { MARKER } else { "" };
+    out!("  {:<3} {:<10} {:<12} {:<22} {:<14} {:>12} {:>16}", row.index, row.kind, row.codec,
+      format!("{}{}", duration, marker),
+      row.declared_bit_rate.map_or_else(|| "not declared".to_string(), |b| format!("{} kb/s", format_with_commas((b / 1000) as i64))),
+      format_with_commas(row.packet_count as i64), format_with_commas(row.bytes as i64));
+  }
+  for row in rows.iter().filter(|row| row.duration_mismatch) {
+    out!("{} Stream #{} declares {} but its packets span {}", MARKER, row.index,
+      row.declared_duration_ms.map_or_else(String::new, millis_to_clock_format),
+      row.observed_span_ms.map_or_else(String::new, millis_to_clock_format));
+  }
+
+  out!("{}{}Bit rates - {}", RESET, BLUE_BOLD, RESET);
+  for row in &rows {
+    out!("#{} {}: {}", row.index, row.kind, describe_rates(row.measured_bit_rate, row.declared_bit_rate));
+  }
+  let container = input_size(ictx).filter(|_| complete).map(|file_bytes| {
+    let payload_bytes: u64 = rows.iter().map(|row| row.bytes).sum();
+    let overhead_bytes = file_bytes.saturating_sub(payload_bytes);
+    let declared = (ictx.bit_rate() > 0).then_some(ictx.bit_rate() as u64);
+    ContainerBitRateReport {
+      declared_bit_rate: declared,
+      measured_bit_rate: container_duration(ictx).and_then(|d| d.millis()).and_then(|ms| bit_rate(file_bytes, ms)),
+      file_bytes,
+      payload_bytes,
+      overhead_bytes,
+      overhead_percent: overhead_bytes as f64 * 100.0 / file_bytes as f64,
+    }
+  });
+  match &container {
+    Some(container) => {
+      out!("Container: {}", describe_rates(container.measured_bit_rate, container.declared_bit_rate));
+      out!("Muxing overhead: {} bytes ({:.2}% of the {} byte file)", format_with_commas(container.overhead_bytes as i64),
+        container.overhead_percent, format_with_commas(container.file_bytes as i64));
+    }
+    None => out!("Muxing overhead: unknown{}", if complete { ", the input size isn't known" } else { ", not every packet was read" }),
+  }
+  (rows, container)
+}
@@ -0,0 +1,124 @@
+// Video stream selection. Action cameras and drones often mux a low-resolution preview track next
+// to the main one, and the demuxer's "best" stream isn't always the main track, so the analyzed
+// stream is picked by resolution and the rest are reported as previews.
+
+use ffmpeg::format::stream::Disposition;
+use ffmpeg::media::Type;
+
+// Properties of a video stream relevant to telling main tracks and previews apart.
+pub struct VideoTrack {
+  pub index: usize,
+  pub codec: String,
+  pub width: i32,
+  pub height: i32,
+  // Declared bit rate in bits/s, 0 when unknown.
+  pub bit_rate: i64,
+  pub handler: Option<String>,
+  // Cover art or thumbnail stored as a single attached picture.
+  pub attached_pic: bool,
+}
+
+impl VideoTrack {
+  fn area(&self) -> i64 {
+    self.width as i64 * self.height as i64
+  }
+
+  // Whether this track looks like a preview or thumbnail of `primary`: much lower resolution or
+  // bit rate, or a handler name saying so.
+  pub fn is_preview_of(&self, primary: &VideoTrack) -> bool {
+    if self.attached_pic {
+      return true;
+    }
+    let hinted = self.handler.as_deref().is_some_and(|handler| {
+      let handler = handler.to_ascii_lowercase();
+      handler.contains("preview") || handler.contains("thumb")
+    });
+    let low_resolution = self.area() * PREVIEW_RATIO <= primary.area();
+    let low_bit_rate = self.bit_rate > 0 && primary.bit_rate > 0 && self.bit_rate * PREVIEW_RATIO <= primary.bit_rate;
+    hinted || low_resolution || low_bit_rate
+  }
+}
+
+// A track with a quarter of the primary's pixels or bit rate, or less, counts as a preview.
+const PREVIEW_RATIO: i64 = 4;
+
+// Every video stream of the input, in stream order.
+pub fn video_tracks(ictx: &ffmpeg::format::context::Input) -> Vec<VideoTrack> {
+  ictx
+    .streams()
+    .filter(|stream| stream.parameters().medium() == Type::Video)
+    .map(|stream| {
+      let parameters = stream.parameters();
+      let (width, height, bit_rate) = unsafe {
+        let par = parameters.as_ptr();
+        ((*par).width, (*par).height, (*par).bit_rate)
+      };
+      VideoTrack {
+        index: stream.index(),
+        codec: parameters.id().name().to_string(),
+        width,
+        height,
+        bit_rate,
+        handler: stream.metadata().get("handler_name").map(str::to_string),
+        attached_pic: stream.disposition().contains(Disposition::ATTACHED_PIC),
+      }
+    })
+    .collect()
+}
+
+// The stream the main analysis should target: the highest-resolution video stream that isn't an
+// attached picture, with the bit rate breaking ties. None when the only video is cover art, so an
+// audio file with a picture is reported as audio-only.
+pub fn primary_index(tracks: &[VideoTrack]) -> Option<usize> {
+  tracks.iter()
+    .filter(|t| !t.attached_pic)
+    .max_by_key(|t| (t.area(), t.bit_rate))
+    .map(|t| t.index)
+}
+
+// Print the tracks other than `primary` that look like previews or thumbnails.
+pub fn report_previews(tracks: &[VideoTrack], primary: usize) {
+  let Some(main) = tracks.iter().find(|t| t.index == primary) else {
+    return;
+  };
+  let previews: Vec<_> = tracks.iter().filter(|t| t.index != primary && t.is_preview_of(main)).collect();
+  if previews.is_empty() {
+    return;
+  }
+  out!("{}{}Preview and thumbnail tracks - {}", crate::RESET, crate::BLUE_BOLD, crate::RESET);
+  for track in previews {
+    let bit_rate = if track.bit_rate > 0 { format!("{} kb/s", track.bit_rate / 1000) } else { "unknown bit rate".to_string() };
+    out!("#{}: {} {}x{}, {}{}{}", track.index, track.codec, track.width, track.height, bit_rate,
+      track.handler.as_deref().map_or_else(String::new, |h| format!(", handler '{}'", h)),
+      if track.attached_pic { ", attached picture" } else { "" });
+  }
+}
+
+// Describe the analyzed stream and how it was picked, e.g. "#0: h264 1920x1080, the
+// highest-resolution video stream".
+pub fn describe_selection(track: &VideoTrack, explicit: bool, video_streams: usize) -> String {
+  let reason = if explicit {
+    "selected with --stream"
+  } else if video_streams > 1 {
+    "the highest-resolution of the video streams"
+  } else {
+    "the only video stream"
+  };
+  format!("#{}: {} {}x{}, {}", track.index, track.codec, track.width, track.height, reason)
+}
+
+// Why the stream given with --stream can't be analyzed: it doesn't exist, or it isn't video.
+pub fn invalid_selection(ictx: &ffmpeg::format::context::Input, tracks: &[VideoTrack], index: usize) -> String {
+  let video: Vec<String> = tracks.iter().map(|t| format!("#{}", t.index)).collect();
+  let available = if video.is_empty() { "the input has no video stream".to_string() } else {
+    format!("video streams: {}", video.join(", "))
+  };
+  match ictx.stream(index) {
+    Some(stream) => {
+      let parameters = stream.parameters();
+      format!("--stream {} is a {:?} stream ({}), not a video stream; {}", index, parameters.medium(),
+        parameters.id().name(), available)
+    }
+    None => format!("--stream {} doesn't exist, the input has {} streams; {}", index, ictx.nb_streams(), available),
+  }
+}
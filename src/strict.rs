@@ -0,0 +1,79 @@
+// --strict: automated QC that fails the run on any anomaly. The checks behind it all run to the
+// end of the input, so one run lists every violation. Each kind has a stable identifier for CI to
+// allow with --strict-allow.
+
+use ffmpeg::Rational;
+
+use crate::monotonic::{Anomaly, Tracker};
+use crate::timestamp::Timestamp;
+use crate::{format_with_commas, millis_to_clock_format, BLUE_BOLD, RESET};
+
+// Every violation identifier, in report order.
+pub const IDS: [&str; 6] = ["decode_error", "dts_missing", "dts_backwards", "dts_duplicate", "pts_before_dts", "duration_mismatch"];
+
+pub struct Violation {
+  pub id: &'static str,
+  pub count: u64,
+  // Where it first occurred, when that has a timestamp.
+  pub first: Option<Timestamp>,
+  pub description: String,
+  // Listed with --strict-allow: reported, but it doesn't fail the run.
+  pub allowed: bool,
+}
+
+// What the analysis found that strict QC checks.
+pub struct Checks<'a> {
+  pub decode_errors: u64,
+  pub first_decode_error: Option<i64>,
+  pub timestamps: &'a Tracker,
+  // Difference between the container duration and the last frame time, when it's beyond the
+  // tolerance.
+  pub duration_mismatch_ms: Option<u64>,
+}
+
+fn anomaly(id: &'static str, anomaly: &Anomaly, noun: &str, time_base: Rational) -> (&'static str, u64, Option<Timestamp>, String) {
+  let description = format!("{} {}", format_with_commas(anomaly.count as i64), noun);
+  (id, anomaly.count, anomaly.first().map(|ts| Timestamp::new(ts, time_base)), description)
+}
+
+// The violations present, in the order of IDS.
+pub fn collect(checks: &Checks, time_base: Rational, allow: &[String]) -> Vec<Violation> {
+  let tracker = checks.timestamps;
+  let candidates = [
+    ("decode_error", checks.decode_errors, checks.first_decode_error.map(|ts| Timestamp::new(ts, time_base)),
+      format!("{} errors of the video decoder", format_with_commas(checks.decode_errors as i64))),
+    anomaly("dts_missing", &tracker.missing_dts, "video packets without a DTS", time_base),
+    anomaly("dts_backwards", &tracker.backwards_dts, "video packets with a DTS lower than the previous one", time_base),
+    anomaly("dts_duplicate", &tracker.duplicate_dts, "video packets with the DTS of the previous one", time_base),
+    anomaly("pts_before_dts", &tracker.pts_before_dts, "video packets presented before they are decoded", time_base),
+    ("duration_mismatch", checks.duration_mismatch_ms.map_or(0, |_| 1), None, checks.duration_mismatch_ms
+      .map_or_else(String::new, |ms| format!("container duration and last frame time {} apart", millis_to_clock_format(ms)))),
+  ];
+  candidates
+    .into_iter()
+    .filter(|(_, count, _, _)| *count > 0)
+    .map(|(id, count, first, description)| Violation {
+      id,
+      count,
+      first,
+      description,
+      allowed: allow.iter().any(|allowed| allowed == id),
+    })
+    .collect()
+}
+
+// Print one line per violation and the outcome.
+pub fn report(violations: &[Violation], verbose: bool) {
+  out!("{}{}Strict QC - {}", RESET, BLUE_BOLD, RESET);
+  for v in violations {
+    let first = v.first.map_or_else(String::new, |first| format!(", first at {}", first.describe(verbose)));
+    out!("{}: {}{}{}", v.id, v.description, first,
+      if v.allowed { " (allowed)" } else { "" });
+  }
+  let failing = violations.iter().filter(|v| !v.allowed).count();
+  if failing > 0 {
+    out!("Strict QC: FAILED, {} kinds of violation", failing);
+  } else {
+    out!("Strict QC: passed");
+  }
+}
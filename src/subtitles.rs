@@ -0,0 +1,128 @@
+// Subtitle streams: what each one declares and how many packets it carries, to verify the tracks
+// survived a remux. Text formats are also parsed for the number of cues with visible text; bitmap
+// formats (PGS, DVB) only get packet counts.
+
+use ffmpeg::codec::Id;
+use ffmpeg::media::Type;
+use ffmpeg::Rational;
+
+use crate::report::SubtitleStreamReport;
+use crate::timestamp::Timestamp;
+use crate::{format_signed_millis, format_with_commas, timestamp_to_signed_millis, BLUE_BOLD, RESET};
+
+pub struct SubtitleStream {
+  pub index: usize,
+  pub codec: String,
+  pub language: Option<String>,
+  pub time_base: Rational,
+  pub packets: u64,
+  // Packets whose text isn't empty once markup is removed. None for bitmap formats.
+  pub cues: Option<u64>,
+  // Highest presentation timestamp of the stream's packets.
+  pub last_timestamp: Option<i64>,
+  id: Id,
+}
+
+// Codecs whose packets hold the cue text.
+fn is_text(id: Id) -> bool {
+  matches!(id, Id::SUBRIP | Id::SRT | Id::ASS | Id::SSA | Id::WEBVTT | Id::MOV_TEXT | Id::TEXT)
+}
+
+// The displayed text of a packet, still with inline markup.
+fn cue_text(id: Id, data: &[u8]) -> &[u8] {
+  match id {
+    // Matroska stores ASS events as "ReadOrder,Layer,Style,Name,MarginL,MarginR,MarginV,Effect,Text".
+    Id::ASS | Id::SSA => data.splitn(9, |&b| b == b',').nth(8).unwrap_or(&[]),
+    // 3GPP timed text starts with the big-endian length of the text.
+    Id::MOV_TEXT if data.len() >= 2 => {
+      let length = u16::from_be_bytes([data[0], data[1]]) as usize;
+      &data[2..(2 + length).min(data.len())]
+    }
+    _ => data,
+  }
+}
+
+// Whether any character is left outside {\ass overrides}, <html tags>, whitespace and the ASS
+// escapes \N, \n and \h (line breaks and hard space).
+fn has_visible_text(text: &[u8]) -> bool {
+  let mut bytes = text.iter();
+  while let Some(&byte) = bytes.next() {
+    let closing = match byte {
+      b'{' => b'}',
+      b'<' => b'>',
+      b'\\' => {
+        bytes.next();
+        continue;
+      }
+      _ if byte.is_ascii_whitespace() => continue,
+      _ => return true,
+    };
+    if !bytes.by_ref().any(|&b| b == closing) {
+      return false;
+    }
+  }
+  false
+}
+
+impl SubtitleStream {
+  // Count a packet of this stream.
+  pub fn push(&mut self, packet: &ffmpeg::Packet) {
+    self.packets += 1;
+    if let Some(ts) = packet.pts().or(packet.dts()) {
+      self.last_timestamp = Some(self.last_timestamp.map_or(ts, |t| t.max(ts)));
+    }
+    if let (Some(cues), Some(data)) = (self.cues.as_mut(), packet.data()) {
+      if has_visible_text(cue_text(self.id, data)) {
+        *cues += 1;
+      }
+    }
+  }
+}
+
+// Every subtitle stream of the input.
+pub fn open(ictx: &ffmpeg::format::context::Input) -> Vec<SubtitleStream> {
+  ictx
+    .streams()
+    .filter(|stream| stream.parameters().medium() == Type::Subtitle)
+    .map(|stream| {
+      let id = stream.parameters().id();
+      SubtitleStream {
+        index: stream.index(),
+        codec: id.name().to_string(),
+        language: stream.metadata().get("language").map(str::to_string),
+        time_base: stream.time_base(),
+        packets: 0,
+        cues: is_text(id).then_some(0),
+        last_timestamp: None,
+        id,
+      }
+    })
+    .collect()
+}
+
+pub fn to_report(stream: &SubtitleStream) -> SubtitleStreamReport {
+  SubtitleStreamReport {
+    index: stream.index,
+    codec: stream.codec.clone(),
+    language: stream.language.clone(),
+    packet_count: stream.packets,
+    cue_count: stream.cues,
+    last_packet_ms: stream.last_timestamp.and_then(|ts| Timestamp::new(ts, stream.time_base).signed_millis()),
+    last_packet_raw: stream.last_timestamp.map(|ts| Timestamp::new(ts, stream.time_base)),
+  }
+}
+
+// Print one line per subtitle stream; nothing for files without any.
+pub fn report(streams: &[SubtitleStream]) {
+  if streams.is_empty() {
+    return;
+  }
+  out!("{}{}Subtitle streams - {}", RESET, BLUE_BOLD, RESET);
+  for stream in streams {
+    let last = stream.last_timestamp.and_then(|ts| timestamp_to_signed_millis(ts, stream.time_base));
+    out!("#{}: {}, language {}, {} packets{}, last packet at {}", stream.index, stream.codec,
+      stream.language.as_deref().unwrap_or("unknown"), format_with_commas(stream.packets as i64),
+      stream.cues.map_or_else(String::new, |cues| format!(", {} non-empty cues", format_with_commas(cues as i64))),
+      last.map_or_else(|| "unknown".to_string(), format_signed_millis));
+  }
+}
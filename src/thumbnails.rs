@@ -0,0 +1,125 @@
+// --thumbnails: a PNG of every decoded key frame, written during the decode pass instead of a
+// separate ffmpeg run. The frame is scaled into a frame of the writer's own, so the decoded frame
+// and the statistics taken from it are untouched.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use ffmpeg::format::Pixel;
+use ffmpeg::software::scaling;
+use ffmpeg::Rational;
+
+use crate::{timestamp_to_signed_millis, AnalyzeError};
+
+// Where the thumbnails go and which key frames get one (--thumbnails, --thumbnail-width,
+// --thumbnail-every).
+pub struct Settings {
+  pub dir: PathBuf,
+  // Scale the thumbnails down to this width.
+  pub width: Option<u32>,
+  // Skip key frames less than this many seconds after the last thumbnail.
+  pub every_seconds: Option<f64>,
+}
+
+pub struct Writer {
+  dir: PathBuf,
+  // Scale down to this width, keeping the aspect ratio; never up.
+  width: Option<u32>,
+  // Skip key frames less than this many milliseconds after the last thumbnail.
+  every_ms: Option<i64>,
+  last_ms: Option<i64>,
+  // Converter to RGB, with the input format and size it was made for.
+  scaler: Option<(scaling::Context, (Pixel, u32, u32))>,
+  rgb: ffmpeg::util::frame::video::Video,
+  written: u64,
+  // The first error; thumbnails after it are skipped and the error is returned by finish().
+  error: Option<AnalyzeError>,
+}
+
+// Output size for a frame: the requested width with the height scaled to match, both even.
+fn output_size(width: u32, height: u32, requested: Option<u32>) -> (u32, u32) {
+  match requested {
+    Some(requested) if requested < width => {
+      let scaled = (height as u64 * requested as u64 / width as u64) as u32;
+      ((requested & !1).max(2), (scaled & !1).max(2))
+    }
+    _ => (width, height),
+  }
+}
+
+impl Writer {
+  // Create the output directory.
+  pub fn create(settings: &Settings) -> std::io::Result<Self> {
+    fs::create_dir_all(&settings.dir)?;
+    Ok(Writer {
+      dir: settings.dir.clone(),
+      width: settings.width,
+      every_ms: settings.every_seconds.map(|secs| (secs * 1000.0) as i64),
+      last_ms: None,
+      scaler: None,
+      rgb: ffmpeg::util::frame::video::Video::empty(),
+      written: 0,
+      error: None,
+    })
+  }
+
+  // Write a thumbnail of a decoded key frame, unless one was written less than every_ms before.
+  // `index` counts decoded frames from 0 and names frames without a timestamp.
+  pub fn write(&mut self, index: i64, frame: &ffmpeg::util::frame::video::Video, time_base: Rational) {
+    if self.error.is_some() {
+      return;
+    }
+    let ms = frame.timestamp().and_then(|ts| timestamp_to_signed_millis(ts, time_base));
+    if let (Some(ms), Some(last), Some(every)) = (ms, self.last_ms, self.every_ms) {
+      if ms - last < every {
+        return;
+      }
+    }
+    let name = match ms {
+      Some(ms) => format!("{:09}ms.png", ms),
+      None => format!("frame{:09}.png", index),
+    };
+    match self.encode(frame, &self.dir.join(name)) {
+      Ok(()) => {
+        self.written += 1;
+        self.last_ms = ms.or(self.last_ms);
+      }
+      Err(e) => self.error = Some(e),
+    }
+  }
+
+  fn encode(&mut self, frame: &ffmpeg::util::frame::video::Video, path: &Path) -> Result<(), AnalyzeError> {
+    let input = (frame.format(), frame.width(), frame.height());
+    let (width, height) = output_size(frame.width(), frame.height(), self.width);
+    if self.scaler.as_ref().is_none_or(|(_, made_for)| *made_for != input) {
+      let context = scaling::Context::get(input.0, input.1, input.2, Pixel::RGB24, width, height,
+        scaling::Flags::BILINEAR)?;
+      self.scaler = Some((context, input));
+    }
+    if let Some((scaler, _)) = self.scaler.as_mut() {
+      scaler.run(frame, &mut self.rgb)?;
+    }
+
+    let codec = ffmpeg::encoder::find(ffmpeg::codec::Id::PNG).ok_or(ffmpeg::Error::EncoderNotFound)?;
+    let mut encoder = ffmpeg::codec::context::Context::new().encoder().video()?;
+    encoder.set_width(width);
+    encoder.set_height(height);
+    encoder.set_format(Pixel::RGB24);
+    encoder.set_time_base(Rational::new(1, 1));
+    let mut encoder = encoder.open_as(codec)?;
+    encoder.send_frame(&self.rgb)?;
+    encoder.send_eof()?;
+    let mut packet = ffmpeg::Packet::empty();
+    encoder.receive_packet(&mut packet)?;
+    fs::write(path, packet.data().unwrap_or_default())?;
+    Ok(())
+  }
+
+  // Returns the number of thumbnails written, or the first error.
+  pub fn finish(self) -> Result<u64, AnalyzeError> {
+    match self.error {
+      Some(error) => Err(error),
+      None => Ok(self.written),
+    }
+  }
+}
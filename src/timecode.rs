@@ -0,0 +1,342 @@
+// --timecodes: SMPTE 12M timecodes embedded in the video, as broadcast H.264 and HEVC streams carry
+// them in pic_timing SEI messages and MPEG-2 streams in GOP headers. FFmpeg's decoders parse those
+// and attach the timecode to the decoded frame as S12M side data, which is read here. Timecodes
+// are checked for continuity frame by frame at the nominal rate of the stream, counting the
+// frame numbers 29.97 and 59.94 fps drop-frame timecodes skip.
+
+use ffmpeg::util::frame::side_data;
+use ffmpeg::Rational;
+use serde::Serialize;
+
+use crate::findings::{self, Finding};
+use crate::timestamp::{self, Timestamp};
+use crate::{format_with_commas, timestamp_to_signed_millis, BLUE_BOLD, RESET};
+
+// Discontinuities listed.
+const MAX_EXAMPLES: usize = 5;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Timecode {
+  pub hours: u32,
+  pub minutes: u32,
+  pub seconds: u32,
+  pub frames: u32,
+  pub drop_frame: bool,
+}
+
+impl Timecode {
+  // Unpack the SMPTE 12M-1 binary form FFmpeg stores in the side data. Above 30 fps the frame
+  // pair number is doubled and the field bit tells the two frames apart, as in
+  // av_timecode_make_smpte_tc_string2.
+  fn from_smpte(tc: u32, nominal_fps: Option<u32>) -> Self {
+    let bcd = |bits: u32| (bits >> 4) * 10 + (bits & 0xf);
+    let mut frames = bcd((tc >> 24) & 0x3f);
+    match nominal_fps {
+      Some(50) => frames = frames * 2 + ((tc >> 7) & 1),
+      Some(fps) if fps > 30 => frames = frames * 2 + ((tc >> 23) & 1),
+      _ => {}
+    }
+    Timecode {
+      hours: bcd(tc & 0x3f),
+      minutes: bcd((tc >> 8) & 0x7f),
+      seconds: bcd((tc >> 16) & 0x7f),
+      frames,
+      drop_frame: tc & (1 << 30) != 0,
+    }
+  }
+
+  // Parse HH:MM:SS:FF, or HH:MM:SS;FF for drop-frame, as FFmpeg writes the timecode tag.
+  pub fn parse(text: &str) -> Option<Self> {
+    let drop_frame = text.contains(';');
+    let fields: Vec<u32> = text.split([':', ';', '.']).map(|field| field.parse().ok()).collect::<Option<_>>()?;
+    let [hours, minutes, seconds, frames] = fields[..] else {
+      return None;
+    };
+    Some(Timecode { hours, minutes, seconds, frames, drop_frame })
+  }
+
+  // This timecode advanced by `frames` frames, wrapping at 24 hours.
+  pub fn advance(&self, frames: i64, nominal_fps: u32) -> Self {
+    let frames_per_day = Timecode { hours: 24, minutes: 0, seconds: 0, frames: 0, drop_frame: self.drop_frame }
+      .frame_number(nominal_fps);
+    from_frame_number((self.frame_number(nominal_fps) + frames).rem_euclid(frames_per_day), nominal_fps, self.drop_frame)
+  }
+
+  // Frames since 00:00:00:00. Drop-frame timecodes skip the first 2 frame numbers (4 at 60 fps)
+  // of every minute except each tenth.
+  fn frame_number(&self, nominal_fps: u32) -> i64 {
+    let fps = i64::from(nominal_fps);
+    let total_minutes = i64::from(self.hours) * 60 + i64::from(self.minutes);
+    let number = (total_minutes * 60 + i64::from(self.seconds)) * fps + i64::from(self.frames);
+    if self.drop_frame {
+      number - fps / 15 * (total_minutes - total_minutes / 10)
+    } else {
+      number
+    }
+  }
+
+  pub fn describe(&self) -> String {
+    format!("{:02}:{:02}:{:02}{}{:02}", self.hours, self.minutes, self.seconds, if self.drop_frame { ';' } else { ':' },
+      self.frames)
+  }
+}
+
+// Frames counted by the timecode per second: 30 for 29.97 fps, 60 for 59.94.
+pub fn nominal_fps(rate: Option<Rational>) -> Option<u32> {
+  let rate = rate.filter(|rate| rate.numerator() > 0 && rate.denominator() > 0)?;
+  Some(f64::from(rate).round() as u32).filter(|&fps| fps > 0)
+}
+
+// A frame whose timecode isn't the previous timecode advanced by the frames in between.
+pub struct Discontinuity {
+  pub frame: i64,
+  pub timestamp: Option<i64>,
+  pub expected: Timecode,
+  pub found: Timecode,
+}
+
+pub struct Tracker {
+  nominal_fps: Option<u32>,
+  // Decoded frames carrying a timecode.
+  pub frames: u64,
+  pub first: Option<Timecode>,
+  pub last: Option<Timecode>,
+  // Index among the decoded frames of the last one with a timecode.
+  last_frame: i64,
+  pub discontinuities: u64,
+  pub examples: Vec<Discontinuity>,
+}
+
+impl Tracker {
+  pub fn new(rate: Option<Rational>) -> Self {
+    Tracker {
+      nominal_fps: nominal_fps(rate),
+      frames: 0,
+      first: None,
+      last: None,
+      last_frame: 0,
+      discontinuities: 0,
+      examples: Vec::new(),
+    }
+  }
+
+  // Read the timecode of a decoded frame, the index-th one.
+  pub fn push(&mut self, frame: &ffmpeg::util::frame::video::Video, index: i64) {
+    let Some(data) = frame.side_data(side_data::Type::S12M_TIMECODE) else {
+      return;
+    };
+    // An array of 32-bit values: the number of timecodes, then up to 3 of them, the first one
+    // being the frame's.
+    let words: Vec<u32> = data.data().chunks_exact(4).map(|w| u32::from_ne_bytes([w[0], w[1], w[2], w[3]])).collect();
+    let (Some(&count), Some(&tc)) = (words.first(), words.get(1)) else {
+      return;
+    };
+    if count == 0 {
+      return;
+    }
+    let timecode = Timecode::from_smpte(tc, self.nominal_fps);
+    if let (Some(last), Some(fps)) = (self.last, self.nominal_fps) {
+      // Frames without a timecode in between (MPEG-2 only has one per GOP) still advance it.
+      let expected = last.advance(index - self.last_frame, fps);
+      if timecode != expected {
+        self.discontinuities += 1;
+        if self.examples.len() < MAX_EXAMPLES {
+          self.examples.push(Discontinuity { frame: index, timestamp: frame.timestamp(), expected, found: timecode });
+        }
+      }
+    }
+    self.first.get_or_insert(timecode);
+    self.last = Some(timecode);
+    self.last_frame = index;
+    self.frames += 1;
+  }
+
+  // Whether the timecodes run on without a break; None without a nominal rate to check them at.
+  pub fn continuous(&self) -> Option<bool> {
+    self.nominal_fps.map(|_| self.discontinuities == 0)
+  }
+}
+
+// The timecode of a frame number, the inverse of Timecode::frame_number.
+fn from_frame_number(number: i64, nominal_fps: u32, drop_frame: bool) -> Timecode {
+  let fps = i64::from(nominal_fps);
+  let mut number = number;
+  if drop_frame {
+    // Add back the frame numbers skipped before this one: per 10 minutes, 9 minutes skip some.
+    let drop = fps / 15;
+    let per_ten_minutes = fps * 600 - drop * 9;
+    let per_minute = fps * 60 - drop;
+    let (tens, rest) = (number / per_ten_minutes, number % per_ten_minutes);
+    number += drop * 9 * tens + if rest > drop { drop * ((rest - drop) / per_minute) } else { 0 };
+  }
+  Timecode {
+    hours: (number / (fps * 3600)) as u32,
+    minutes: (number / (fps * 60) % 60) as u32,
+    seconds: (number / fps % 60) as u32,
+    frames: (number % fps) as u32,
+    drop_frame,
+  }
+}
+
+#[derive(Serialize)]
+pub struct DiscontinuityReport {
+  pub frame: i64,
+  pub timestamp_ms: Option<i64>,
+  pub timestamp_raw: Option<Timestamp>,
+  pub expected: String,
+  pub found: String,
+}
+
+#[derive(Serialize)]
+pub struct TimecodeReport {
+  // Whether any decoded frame carries a timecode: "absent" and "present but broken" differ.
+  pub present: bool,
+  pub frames_with_timecode: u64,
+  pub first: Option<String>,
+  pub last: Option<String>,
+  pub drop_frame: Option<bool>,
+  pub continuous: Option<bool>,
+  pub discontinuities: u64,
+  pub discontinuity_examples: Vec<DiscontinuityReport>,
+}
+
+pub fn to_report(tracker: &Tracker, time_base: Rational) -> TimecodeReport {
+  TimecodeReport {
+    present: tracker.frames > 0,
+    frames_with_timecode: tracker.frames,
+    first: tracker.first.map(|tc| tc.describe()),
+    last: tracker.last.map(|tc| tc.describe()),
+    drop_frame: tracker.first.map(|tc| tc.drop_frame),
+    continuous: tracker.continuous().filter(|_| tracker.frames > 0),
+    discontinuities: tracker.discontinuities,
+    discontinuity_examples: tracker.examples.iter().map(|d| DiscontinuityReport {
+      frame: d.frame,
+      timestamp_ms: d.timestamp.and_then(|ts| timestamp_to_signed_millis(ts, time_base)),
+      timestamp_raw: d.timestamp.map(|ts| Timestamp::new(ts, time_base)),
+      expected: d.expected.describe(),
+      found: d.found.describe(),
+    }).collect(),
+  }
+}
+
+// Print the timecode section and return a finding for discontinuities, located at the first one.
+pub fn report(tracker: &Tracker, frame_count: i64, time_base: Rational, verbose: bool) -> Option<Finding> {
+  out!("{}{}Timecodes - {}", RESET, BLUE_BOLD, RESET);
+  let (Some(first), Some(last)) = (tracker.first, tracker.last) else {
+    out!("Timecodes: none embedded");
+    return None;
+  };
+  out!("Timecodes: present on {} of {} frames", format_with_commas(tracker.frames as i64), format_with_commas(frame_count));
+  out!("First timecode: {}", first.describe());
+  out!("Last timecode: {}", last.describe());
+  out!("Drop frame: {}", if first.drop_frame { "yes" } else { "no" });
+  let Some(fps) = tracker.nominal_fps else {
+    out!("Continuous: not checked (unknown frame rate)");
+    return None;
+  };
+  if tracker.discontinuities == 0 {
+    out!("Continuous: yes (at {} frames per second)", fps);
+    return None;
+  }
+  out!("Continuous: no, {} discontinuities (at {} frames per second)", format_with_commas(tracker.discontinuities as i64), fps);
+  for d in &tracker.examples {
+    out!("  Frame {} ({}): expected {}, found {}", format_with_commas(d.frame),
+      timestamp::describe_optional(d.timestamp.map(|ts| Timestamp::new(ts, time_base)), verbose),
+      d.expected.describe(), d.found.describe());
+  }
+  let first_break = &tracker.examples[0];
+  let finding = Finding::new(&findings::TIMECODE_DISCONTINUITY, format!("{} timecode discontinuities, the first from {} to {}",
+    format_with_commas(tracker.discontinuities as i64), first_break.expected.describe(), first_break.found.describe()));
+  Some(match first_break.timestamp {
+    Some(ts) => finding.at(Timestamp::new(ts, time_base)),
+    None => finding,
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn timecode(text: &str) -> Timecode {
+    Timecode::parse(text).unwrap()
+  }
+
+  // The SMPTE 12M-1 binary form of a timecode, with `field` set at bit `field_bit`.
+  fn smpte(hours: u32, minutes: u32, seconds: u32, frames: u32, drop_frame: bool, field_bit: Option<u32>) -> u32 {
+    let bcd = |value: u32| ((value / 10) << 4) | (value % 10);
+    (bcd(frames) << 24) | (bcd(seconds) << 16) | (bcd(minutes) << 8) | bcd(hours) | (u32::from(drop_frame) << 30)
+      | field_bit.map_or(0, |bit| 1 << bit)
+  }
+
+  #[test]
+  fn parse_and_describe() {
+    assert_eq!(timecode("01:02:03:04"), Timecode { hours: 1, minutes: 2, seconds: 3, frames: 4, drop_frame: false });
+    assert!(timecode("01:02:03;04").drop_frame);
+    assert!(!timecode("01:02:03.04").drop_frame);
+    assert_eq!(timecode("00:09:59;29").describe(), "00:09:59;29");
+    assert!(Timecode::parse("01:02:03").is_none());
+    assert!(Timecode::parse("01:02:03:xx").is_none());
+  }
+
+  #[test]
+  fn drop_frame_numbers() {
+    // The first minute keeps every frame number; 00:01:00;00 and ;01 don't exist.
+    assert_eq!(timecode("00:00:59;29").frame_number(30), 1799);
+    assert_eq!(timecode("00:01:00;02").frame_number(30), 1800);
+    // Nine minutes of 1798 frames after the first of 1800.
+    assert_eq!(timecode("00:10:00;00").frame_number(30), 17982);
+    assert_eq!(timecode("01:00:00;00").frame_number(30), 107892);
+    assert_eq!(timecode("00:01:00;04").frame_number(60), 3600);
+    assert_eq!(timecode("00:01:00:00").frame_number(30), 1800);
+  }
+
+  #[test]
+  fn drop_frame_advance() {
+    assert_eq!(timecode("00:00:59;29").advance(1, 30), timecode("00:01:00;02"));
+    assert_eq!(timecode("00:01:00;02").advance(-1, 30), timecode("00:00:59;29"));
+    // Every tenth minute keeps its first frame numbers.
+    assert_eq!(timecode("00:09:59;29").advance(1, 30), timecode("00:10:00;00"));
+    assert_eq!(timecode("00:10:59;29").advance(1, 30), timecode("00:11:00;02"));
+    assert_eq!(timecode("00:00:59;59").advance(1, 60), timecode("00:01:00;04"));
+    assert_eq!(timecode("00:00:59:29").advance(1, 30), timecode("00:01:00:00"));
+    // An hour of 29.97 fps drop-frame timecode is 107,892 frames.
+    assert_eq!(timecode("00:00:00;00").advance(107_892, 30), timecode("01:00:00;00"));
+  }
+
+  #[test]
+  fn advance_wraps_at_24_hours() {
+    assert_eq!(timecode("23:59:59:24").advance(1, 25), timecode("00:00:00:00"));
+    assert_eq!(timecode("23:59:59;29").advance(3, 30), timecode("00:00:00;02"));
+    assert_eq!(timecode("00:00:00:00").advance(-1, 25), timecode("23:59:59:24"));
+  }
+
+  #[test]
+  fn smpte_unpacking() {
+    let tc = Timecode::from_smpte(smpte(10, 59, 58, 23, false, None), Some(25));
+    assert_eq!(tc, timecode("10:59:58:23"));
+    assert!(Timecode::from_smpte(smpte(0, 1, 0, 2, true, None), Some(30)).drop_frame);
+    // Without a known rate the frame pair number is taken as is.
+    assert_eq!(Timecode::from_smpte(smpte(0, 0, 1, 12, false, Some(23)), None).frames, 12);
+  }
+
+  #[test]
+  fn smpte_field_bit() {
+    // At 50 fps the field bit is bit 7, above 30 fps otherwise bit 23; each doubles the pair number.
+    assert_eq!(Timecode::from_smpte(smpte(0, 0, 1, 12, false, None), Some(50)).frames, 24);
+    assert_eq!(Timecode::from_smpte(smpte(0, 0, 1, 12, false, Some(7)), Some(50)).frames, 25);
+    assert_eq!(Timecode::from_smpte(smpte(0, 0, 1, 12, false, Some(23)), Some(50)).frames, 24);
+    assert_eq!(Timecode::from_smpte(smpte(0, 0, 1, 29, true, None), Some(60)).frames, 58);
+    assert_eq!(Timecode::from_smpte(smpte(0, 0, 1, 29, true, Some(23)), Some(60)).frames, 59);
+    // At or below 30 fps there is no field bit.
+    assert_eq!(Timecode::from_smpte(smpte(0, 0, 1, 12, false, Some(23)), Some(30)).frames, 12);
+  }
+
+  #[test]
+  fn nominal_rate() {
+    assert_eq!(nominal_fps(Some(Rational::new(30000, 1001))), Some(30));
+    assert_eq!(nominal_fps(Some(Rational::new(60000, 1001))), Some(60));
+    assert_eq!(nominal_fps(Some(Rational::new(25, 1))), Some(25));
+    assert_eq!(nominal_fps(Some(Rational::new(0, 1))), None);
+    assert_eq!(nominal_fps(None), None);
+  }
+}
@@ -0,0 +1,103 @@
+// Timestamps kept in their native time base. Reports convert them to friendly times, but the raw
+// integer and time base stay available so figures can be cross-checked against ffprobe or MP4Box
+// without rounding differences getting in the way.
+
+use ffmpeg::Rational;
+use serde::ser::{Serialize, SerializeStruct, Serializer};
+
+use crate::{format_signed_millis, timestamp_to_millis, timestamp_to_signed_millis};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Timestamp {
+  pub value: i64,
+  pub time_base: Rational,
+}
+
+impl Timestamp {
+  pub fn new(value: i64, time_base: Rational) -> Self {
+    Timestamp { value, time_base }
+  }
+
+  // A container-level value, which libavformat expresses in AV_TIME_BASE units.
+  pub fn from_av_time_base(value: i64) -> Self {
+    Timestamp::new(value, Rational::new(1, ffmpeg::ffi::AV_TIME_BASE))
+  }
+
+  // Milliseconds, or None for a degenerate time base or a negative timestamp.
+  pub fn millis(&self) -> Option<u64> {
+    if self.value < 0 {
+      return None;
+    }
+    timestamp_to_millis(self.value, self.time_base)
+  }
+
+  // Signed milliseconds, or None for a degenerate time base.
+  pub fn signed_millis(&self) -> Option<i64> {
+    timestamp_to_signed_millis(self.value, self.time_base)
+  }
+
+  // Friendly form, with the raw value appended in verbose mode, e.g.
+  // "00m 10s .000ms (153600 @ 1/15360)".
+  pub fn describe(&self, verbose: bool) -> String {
+    let friendly = self.signed_millis().map_or_else(|| "unknown".to_string(), format_signed_millis);
+    if verbose {
+      format!("{} ({} @ {}/{})", friendly, self.value, self.time_base.numerator(), self.time_base.denominator())
+    } else {
+      friendly
+    }
+  }
+}
+
+// In the JSON report, next to the milliseconds of a time: {"value": 153600, "time_base": "1/15360"}.
+impl Serialize for Timestamp {
+  fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+    let mut raw = serializer.serialize_struct("Timestamp", 2)?;
+    raw.serialize_field("value", &self.value)?;
+    raw.serialize_field("time_base", &format!("{}/{}", self.time_base.numerator(), self.time_base.denominator()))?;
+    raw.end()
+  }
+}
+
+// Describe an optional timestamp, marking missing values as unknown.
+pub fn describe_optional(ts: Option<Timestamp>, verbose: bool) -> String {
+  ts.map_or_else(|| "unknown".to_string(), |ts| ts.describe(verbose))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn negative_timestamps_keep_their_sign() {
+    let ts = Timestamp::new(-1024, Rational::new(1, 12800));
+    assert_eq!(ts.millis(), None);
+    assert_eq!(ts.signed_millis(), Some(-80));
+    assert_eq!(ts.describe(false), "-00m 00s .080ms");
+    assert_eq!(ts.describe(true), "-00m 00s .080ms (-1024 @ 1/12800)");
+  }
+
+  #[test]
+  fn positive_timestamps() {
+    let ts = Timestamp::new(153600, Rational::new(1, 15360));
+    assert_eq!(ts.millis(), Some(10_000));
+    assert_eq!(ts.signed_millis(), Some(10_000));
+    assert_eq!(ts.describe(true), "00m 10s .000ms (153600 @ 1/15360)");
+    assert_eq!(Timestamp::from_av_time_base(3_723_450_000).describe(false), "01h 02m 03s .450ms");
+  }
+
+  #[test]
+  fn degenerate_time_base() {
+    let ts = Timestamp::new(1000, Rational::new(0, 0));
+    assert_eq!((ts.millis(), ts.signed_millis()), (None, None));
+    assert_eq!(ts.describe(false), "unknown");
+    assert_eq!(describe_optional(None, true), "unknown");
+  }
+
+  #[test]
+  fn serializes_the_raw_value_and_time_base() {
+    let ts = Timestamp::new(153600, Rational::new(1, 15360));
+    assert_eq!(serde_json::to_string(&ts).unwrap(), r#"{"value":153600,"time_base":"1/15360"}"#);
+    assert_eq!(serde_json::to_string(&Timestamp::new(-1024, Rational::new(1, 12800))).unwrap(),
+      r#"{"value":-1024,"time_base":"1/12800"}"#);
+  }
+}
@@ -0,0 +1,117 @@
+// QuickTime timecode tracks: the 'tmcd' data streams of MOV masters from editorial. The mov
+// demuxer reads the track's first sample into the stream's "timecode" tag and keeps the sample
+// description as extradata: reserved, flags, time scale, frame duration and frames per second.
+// The timecode of the last frame is the start timecode advanced by the video's frames.
+
+use ffmpeg::media::Type;
+use ffmpeg::Rational;
+use serde::Serialize;
+
+use crate::timecode::{self, Timecode};
+use crate::{BLUE_BOLD, RESET};
+
+// 'tmcd' as the little-endian codec tag FFmpeg stores.
+const TMCD_TAG: u32 = u32::from_le_bytes(*b"tmcd");
+
+// Drop-frame flag of the sample description.
+const DROP_FRAME_FLAG: u32 = 0x0001;
+
+pub struct TimecodeTrack {
+  pub index: usize,
+  // Time scale over frame duration.
+  pub frame_rate: Option<Rational>,
+  // Frames counted per second, 30 for 29.97 fps.
+  pub nominal_fps: Option<u32>,
+  pub drop_frame: bool,
+  pub start: Option<Timecode>,
+}
+
+impl TimecodeTrack {
+  // The timecode of the last of `frame_count` frames.
+  pub fn last_frame(&self, frame_count: i64) -> Option<Timecode> {
+    let (start, fps) = (self.start?, self.nominal_fps?);
+    (frame_count > 0).then(|| start.advance(frame_count - 1, fps))
+  }
+}
+
+fn read_u32(data: &[u8], at: usize) -> Option<u32> {
+  data.get(at..at + 4).map(|bytes| u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+// Every timecode track of the input.
+pub fn tracks(ictx: &ffmpeg::format::context::Input) -> Vec<TimecodeTrack> {
+  ictx
+    .streams()
+    .filter(|stream| stream.parameters().medium() == Type::Data)
+    .filter_map(|stream| {
+      let parameters = stream.parameters();
+      let (tag, extradata) = unsafe {
+        let par = parameters.as_ptr();
+        let extradata = if (*par).extradata.is_null() || (*par).extradata_size <= 0 {
+          &[][..]
+        } else {
+          std::slice::from_raw_parts((*par).extradata, (*par).extradata_size as usize)
+        };
+        ((*par).codec_tag, extradata)
+      };
+      if tag != TMCD_TAG {
+        return None;
+      }
+      let flags = read_u32(extradata, 4);
+      let frame_rate = match (read_u32(extradata, 8), read_u32(extradata, 12)) {
+        (Some(scale), Some(duration)) if scale > 0 && duration > 0 => Some(Rational::new(scale as i32, duration as i32)),
+        _ => None,
+      };
+      // The frames per second byte is what the timecode counts by; the rate rounds to it otherwise.
+      let nominal_fps = extradata.get(16).map(|&fps| u32::from(fps)).filter(|&fps| fps > 0)
+        .or_else(|| timecode::nominal_fps(frame_rate));
+      let start = stream.metadata().get("timecode").and_then(Timecode::parse);
+      let drop_frame = flags.map_or_else(|| start.is_some_and(|tc| tc.drop_frame), |flags| flags & DROP_FRAME_FLAG != 0);
+      Some(TimecodeTrack {
+        index: stream.index(),
+        frame_rate,
+        nominal_fps,
+        drop_frame,
+        start: start.map(|tc| Timecode { drop_frame, ..tc }),
+      })
+    })
+    .collect()
+}
+
+#[derive(Serialize)]
+pub struct TimecodeTrackReport {
+  pub stream_index: usize,
+  pub frame_rate: Option<f64>,
+  pub drop_frame: bool,
+  pub start: Option<String>,
+  pub last_frame: Option<String>,
+}
+
+pub fn to_report(track: &TimecodeTrack, frame_count: i64) -> TimecodeTrackReport {
+  TimecodeTrackReport {
+    stream_index: track.index,
+    frame_rate: track.frame_rate.map(f64::from),
+    drop_frame: track.drop_frame,
+    start: track.start.map(|tc| tc.describe()),
+    last_frame: track.last_frame(frame_count).map(|tc| tc.describe()),
+  }
+}
+
+// Print one block per timecode track, or that there is none.
+pub fn report(tracks: &[TimecodeTrack], frame_count: i64) {
+  out!("{}{}Timecode tracks - {}", RESET, BLUE_BOLD, RESET);
+  if tracks.is_empty() {
+    out!("Timecode tracks: none");
+    return;
+  }
+  let describe = |tc: Option<Timecode>| tc.map_or_else(|| "unknown".to_string(), |tc| tc.describe());
+  for track in tracks {
+    out!("Timecode track (stream #{}):", track.index);
+    out!("  Start timecode: {}", describe(track.start));
+    out!("  Frame rate: {}", track.frame_rate.map_or_else(|| "unknown".to_string(), |rate| {
+      format!("{:.3} fps ({}/{})", f64::from(rate), rate.numerator(), rate.denominator())
+    }));
+    out!("  Drop frame: {}", if track.drop_frame { "yes" } else { "no" });
+    out!("  Last frame timecode: {}", describe(track.last_frame(frame_count)));
+  }
+}
@@ -0,0 +1,633 @@
+// MPEG-TS specifics: the programs of the transport stream with the PID of every elementary
+// stream, as the demuxer read them from the PMTs, and a scan of the 188-byte packet headers of a
+// local file, which libavformat doesn't expose: packets flagged with discontinuity_indicator,
+// continuity counter errors (lost or reordered packets) and PCR values that go backwards or jump,
+// with their byte offsets. Captures spliced from several sources restart their timestamps, so the
+// video packets are also split into continuous segments at every DTS jump, which the measured
+// duration sums instead of spanning the jumps.
+
+use std::fs::File;
+use std::io::{self, BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+
+use ffmpeg::format::context::Input;
+use ffmpeg::Rational;
+use serde::Serialize;
+
+use crate::findings::{self, Finding};
+use crate::monotonic::DISCONTINUITY_SECONDS;
+use crate::stream_table::type_name;
+use crate::timestamp::Timestamp;
+use crate::{format_signed_millis, format_with_commas, timestamp_to_signed_millis, BLUE_BOLD, RESET};
+
+const SYNC_BYTE: u8 = 0x47;
+// Plain TS, M2TS with its 4-byte timestamp prefix, and TS with 16 bytes of Reed-Solomon parity.
+const PACKET_SIZES: [usize; 3] = [188, 192, 204];
+// Packets in a row that must start with the sync byte for a packet size to be taken.
+const SYNC_CHECKS: usize = 4;
+const NULL_PID: usize = 0x1FFF;
+
+// The PCR base counts a 90 kHz clock and wraps at 33 bits. A PCR more than PCR_JUMP ahead of the
+// previous one of its PID, or behind it, is a discontinuity; the standard repeats it at least
+// every 100 ms.
+const PCR_CLOCK: i64 = 90_000;
+const PCR_WRAP: i64 = 1 << 33;
+const PCR_JUMP: i64 = PCR_CLOCK;
+
+// Positions kept of the continuity errors, and PCR jumps listed.
+const MAX_EXAMPLES: usize = 5;
+const MAX_PCR_JUMPS: usize = 20;
+
+pub struct ProgramStream {
+  pub index: usize,
+  pub pid: i32,
+  pub kind: &'static str,
+  pub codec: String,
+}
+
+pub struct Program {
+  pub number: i32,
+  pub pmt_pid: i32,
+  pub pcr_pid: i32,
+  pub streams: Vec<ProgramStream>,
+}
+
+pub struct PcrJump {
+  pub pid: u16,
+  // Byte offset of the packet carrying the PCR.
+  pub offset: u64,
+  // The previous PCR of the PID and this one, as read from the 90 kHz base and in milliseconds.
+  pub from: i64,
+  pub to: i64,
+  pub from_ms: i64,
+  pub to_ms: i64,
+  // The packet sets discontinuity_indicator, so the jump is announced.
+  pub flagged: bool,
+}
+
+impl PcrJump {
+  pub fn backwards(&self) -> bool {
+    self.to_ms < self.from_ms
+  }
+}
+
+#[derive(Default)]
+pub struct HeaderScan {
+  pub packet_size: u64,
+  pub packets: u64,
+  // Places where the sync byte wasn't where the packet size put it.
+  pub sync_losses: u64,
+  pub discontinuity_flagged: u64,
+  pub continuity_errors: u64,
+  // Byte offset and PID of the first continuity counter errors.
+  pub continuity_examples: Vec<(u64, u16)>,
+  pub pcr_jump_count: u64,
+  // Those without discontinuity_indicator.
+  pub unannounced_pcr_jumps: u64,
+  // The first MAX_PCR_JUMPS PCR discontinuities.
+  pub pcr_jumps: Vec<PcrJump>,
+}
+
+pub struct Transport {
+  pub programs: Vec<Program>,
+  // None for URLs, stdin and files still being written.
+  pub scan: Option<io::Result<HeaderScan>>,
+}
+
+pub fn is_transport_stream(ictx: &Input) -> bool {
+  ictx.format().name().split(',').any(|name| name == "mpegts")
+}
+
+fn programs(ictx: &Input) -> Vec<Program> {
+  let listed: Vec<(i32, i32, i32, Vec<usize>)> = unsafe {
+    let ctx = ictx.as_ptr();
+    (0..(*ctx).nb_programs as usize)
+      .map(|p| {
+        let program = *(*ctx).programs.add(p);
+        let streams = std::slice::from_raw_parts((*program).stream_index, (*program).nb_stream_indexes as usize)
+          .iter().map(|&index| index as usize).collect();
+        ((*program).program_num, (*program).pmt_pid, (*program).pcr_pid, streams)
+      })
+      .collect()
+  };
+  listed.into_iter().map(|(number, pmt_pid, pcr_pid, indices)| Program {
+    number,
+    pmt_pid,
+    pcr_pid,
+    // The demuxer sets the stream ID to the PID.
+    streams: indices.into_iter().filter_map(|index| ictx.stream(index)).map(|stream| ProgramStream {
+      index: stream.index(),
+      pid: stream.id(),
+      kind: type_name(stream.parameters().medium()),
+      codec: stream.parameters().id().name().to_string(),
+    }).collect(),
+  }).collect()
+}
+
+// Packet size and offset of the first packet: the first size at which SYNC_CHECKS packets in a
+// row start with the sync byte.
+fn detect_packets(head: &[u8]) -> Option<(usize, usize)> {
+  PACKET_SIZES.into_iter().find_map(|size| {
+    (0..size).find(|&start| (0..SYNC_CHECKS).all(|k| head.get(start + k * size) == Some(&SYNC_BYTE)))
+      .map(|start| (size, start))
+  })
+}
+
+#[derive(Clone, Copy, Default)]
+struct PidState {
+  counter: Option<u8>,
+  pcr: Option<i64>,
+}
+
+// The PCR base (90 kHz) of a packet's adaptation field, and whether it sets discontinuity_indicator.
+fn adaptation_field(packet: &[u8]) -> (Option<i64>, bool) {
+  let length = packet[4] as usize;
+  if length == 0 {
+    return (None, false);
+  }
+  let flags = packet[5];
+  let pcr = (flags & 0x10 != 0 && length >= 7).then(|| {
+    ((packet[6] as i64) << 25) | ((packet[7] as i64) << 17) | ((packet[8] as i64) << 9) | ((packet[9] as i64) << 1)
+      | ((packet[10] as i64) >> 7)
+  });
+  (pcr, flags & 0x80 != 0)
+}
+
+// Read a whole packet, or what is left of the file: the last packet of an M2TS file isn't followed
+// by the timestamp prefix of another one.
+fn read_packet(reader: &mut impl Read, packet: &mut [u8]) -> io::Result<usize> {
+  let mut filled = 0;
+  while filled < packet.len() {
+    match reader.read(&mut packet[filled..]) {
+      Ok(0) => break,
+      Ok(read) => filled += read,
+      Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+      Err(e) => return Err(e),
+    }
+  }
+  Ok(filled)
+}
+
+fn scan(path: &Path) -> io::Result<HeaderScan> {
+  let mut reader = BufReader::with_capacity(1 << 20, File::open(path)?);
+  let mut head = vec![0u8; PACKET_SIZES[2] * (SYNC_CHECKS + 1)];
+  let read = reader.read(&mut head)?;
+  let Some((size, start)) = detect_packets(&head[..read]) else {
+    return Err(io::Error::new(io::ErrorKind::InvalidData, "no run of TS sync bytes at the start of the file"));
+  };
+  reader.seek(SeekFrom::Start(start as u64))?;
+
+  let mut result = HeaderScan { packet_size: size as u64, ..HeaderScan::default() };
+  let mut pids = vec![PidState::default(); NULL_PID + 1];
+  let mut buffer = vec![0u8; size];
+  let mut offset = start as u64;
+  loop {
+    let read = read_packet(&mut reader, &mut buffer)?;
+    if read < PACKET_SIZES[0] {
+      break;
+    }
+    let packet = &buffer[..read];
+    if packet[0] != SYNC_BYTE {
+      // Resume at the next sync byte of what was read.
+      result.sync_losses += 1;
+      if let Some(skip) = packet.iter().position(|&b| b == SYNC_BYTE) {
+        reader.seek_relative(skip as i64 - read as i64)?;
+        offset += skip as u64;
+      } else {
+        offset += read as u64;
+      }
+      continue;
+    }
+    result.packets += 1;
+    let pid = (((packet[1] & 0x1F) as usize) << 8) | packet[2] as usize;
+    if pid == NULL_PID {
+      offset += size as u64;
+      continue;
+    }
+    let control = (packet[3] >> 4) & 0x03;
+    let counter = packet[3] & 0x0F;
+    let (pcr, discontinuity) = if control & 0x02 != 0 { adaptation_field(packet) } else { (None, false) };
+    result.discontinuity_flagged += discontinuity as u64;
+
+    let state = &mut pids[pid];
+    // The counter only advances on packets with a payload; a packet may be sent twice, and a
+    // discontinuity allows any value.
+    if control & 0x01 != 0 {
+      let expected = state.counter.map(|previous| (previous, (previous + 1) & 0x0F));
+      if let Some((previous, next)) = expected.filter(|_| !discontinuity) {
+        if counter != previous && counter != next {
+          result.continuity_errors += 1;
+          if result.continuity_examples.len() < MAX_EXAMPLES {
+            result.continuity_examples.push((offset, pid as u16));
+          }
+        }
+      }
+      state.counter = Some(counter);
+    }
+    if let Some(pcr) = pcr {
+      if let Some(previous) = state.pcr {
+        let mut delta = pcr - previous;
+        if delta < -PCR_WRAP / 2 {
+          delta += PCR_WRAP;
+        }
+        if !(0..=PCR_JUMP).contains(&delta) {
+          result.pcr_jump_count += 1;
+          result.unannounced_pcr_jumps += !discontinuity as u64;
+          if result.pcr_jumps.len() < MAX_PCR_JUMPS {
+            result.pcr_jumps.push(PcrJump {
+              pid: pid as u16,
+              offset,
+              from: previous,
+              to: pcr,
+              from_ms: previous * 1000 / PCR_CLOCK,
+              to_ms: pcr * 1000 / PCR_CLOCK,
+              flagged: discontinuity,
+            });
+          }
+        }
+      }
+      state.pcr = Some(pcr);
+    }
+    offset += size as u64;
+  }
+  Ok(result)
+}
+
+// The programs of a transport stream, and the header scan of a local, complete file; None for
+// other containers.
+pub fn read(ictx: &Input, path: &str, following: bool) -> Option<Transport> {
+  if !is_transport_stream(ictx) {
+    return None;
+  }
+  let path = Path::new(path);
+  Some(Transport {
+    programs: programs(ictx),
+    scan: (path.is_file() && !following).then(|| scan(path)),
+  })
+}
+
+pub struct Segment {
+  pub first_dts: i64,
+  pub last_dts: i64,
+  // Duration of the last packet, after which the next segment would have started.
+  pub last_duration: i64,
+  pub packets: u64,
+  // Byte position of the first packet, -1 when unknown.
+  pub position: i64,
+}
+
+// The video packets split into runs of continuous DTS at jumps of DISCONTINUITY_SECONDS or more,
+// either way.
+pub struct Segments {
+  threshold: Option<i64>,
+  previous_dts: Option<i64>,
+  pub segments: Vec<Segment>,
+}
+
+impl Segments {
+  pub fn new(time_base: Rational) -> Self {
+    let threshold = (time_base.numerator() > 0)
+      .then(|| DISCONTINUITY_SECONDS * time_base.denominator() as i64 / time_base.numerator() as i64);
+    Segments { threshold, previous_dts: None, segments: Vec::new() }
+  }
+
+  pub fn push(&mut self, packet: &ffmpeg::Packet) {
+    let Some(dts) = packet.dts() else {
+      return;
+    };
+    let jump = self.previous_dts.zip(self.threshold)
+      .is_some_and(|(previous, threshold)| dts.abs_diff(previous) >= threshold as u64);
+    self.previous_dts = Some(dts);
+    match self.segments.last_mut() {
+      Some(segment) if !jump => {
+        segment.packets += 1;
+        if dts >= segment.last_dts {
+          segment.last_dts = dts;
+          segment.last_duration = packet.duration();
+        }
+      }
+      _ => self.segments.push(Segment {
+        first_dts: dts,
+        last_dts: dts,
+        last_duration: packet.duration(),
+        packets: 1,
+        position: packet.position() as i64,
+      }),
+    }
+  }
+
+  pub fn discontinuities(&self) -> usize {
+    self.segments.len().saturating_sub(1)
+  }
+
+  // The spans of the segments added up as if each followed the previous one without a jump; None
+  // without a discontinuity.
+  pub fn continuous_span(&self) -> Option<i64> {
+    let last = self.segments.len().checked_sub(1).filter(|&last| last > 0)?;
+    Some(self.segments.iter().enumerate()
+      .map(|(i, segment)| segment.last_dts - segment.first_dts + if i < last { segment.last_duration.max(0) } else { 0 })
+      .sum())
+  }
+}
+
+fn describe_pid(pid: i64) -> String {
+  format!("0x{:04X}", pid)
+}
+
+// Print the programs, the header scan and the continuous segments of the video stream, and return
+// a finding for continuity errors and for PCR jumps the stream doesn't announce.
+pub fn report(transport: &Transport, segments: Option<&Segments>, time_base: Rational) -> Vec<Finding> {
+  let mut issues = Vec::new();
+  out!("{}{}MPEG-TS - {}", RESET, BLUE_BOLD, RESET);
+  if transport.programs.is_empty() {
+    out!("Programs: none declared");
+  }
+  for program in &transport.programs {
+    out!("Program {}: PMT PID {}, PCR PID {}", program.number, describe_pid(program.pmt_pid as i64),
+      describe_pid(program.pcr_pid as i64));
+    for stream in &program.streams {
+      out!("  #{} {} {}, PID {}", stream.index, stream.kind, stream.codec, describe_pid(stream.pid as i64));
+    }
+  }
+
+  match &transport.scan {
+    None => out!("Packet header scan: not available for URLs, stdin and --follow"),
+    Some(Err(err)) => out!("Packet header scan: failed, {}", err),
+    Some(Ok(scan)) => {
+      out!("Packet header scan: {} packets of {} bytes", format_with_commas(scan.packets as i64), scan.packet_size);
+      if scan.sync_losses > 0 {
+        out!("Sync losses: {}", format_with_commas(scan.sync_losses as i64));
+      }
+      out!("Discontinuity-flagged packets: {}", format_with_commas(scan.discontinuity_flagged as i64));
+      let examples: Vec<String> = scan.continuity_examples.iter()
+        .map(|&(offset, pid)| format!("byte {} PID {}", format_with_commas(offset as i64), describe_pid(pid as i64)))
+        .collect();
+      let more = if scan.continuity_errors as usize > examples.len() { ", ..." } else { "" };
+      out!("Continuity counter errors: {}{}", format_with_commas(scan.continuity_errors as i64),
+        if examples.is_empty() { String::new() } else { format!(" (at {}{})", examples.join(", "), more) });
+      out!("PCR discontinuities: {}", format_with_commas(scan.pcr_jump_count as i64));
+      for jump in &scan.pcr_jumps {
+        out!("  PID {} at byte {}: {} to {}, {}{}", describe_pid(jump.pid as i64), format_with_commas(jump.offset as i64),
+          format_signed_millis(jump.from_ms), format_signed_millis(jump.to_ms),
+          if jump.backwards() { "backwards" } else { "forward" }, if jump.flagged { ", flagged" } else { "" });
+      }
+      if scan.pcr_jump_count as usize > scan.pcr_jumps.len() {
+        out!("  ...");
+      }
+
+      if let Some(&(offset, pid)) = scan.continuity_examples.first() {
+        issues.push(Finding::new(&findings::TS_CONTINUITY_ERRORS, format!(
+          "{} continuity counter errors, the first at byte {} in PID {}", format_with_commas(scan.continuity_errors as i64),
+          format_with_commas(offset as i64), describe_pid(pid as i64))));
+      }
+      if let Some(first) = scan.pcr_jumps.iter().find(|jump| !jump.flagged) {
+        issues.push(Finding::new(&findings::TS_PCR_DISCONTINUITY, format!(
+          "{} PCR discontinuities without discontinuity_indicator, the first at byte {} in PID {} ({} to {})",
+          format_with_commas(scan.unannounced_pcr_jumps as i64), format_with_commas(first.offset as i64),
+          describe_pid(first.pid as i64), format_signed_millis(first.from_ms), format_signed_millis(first.to_ms))));
+      }
+    }
+  }
+
+  let Some(segments) = segments.filter(|segments| segments.discontinuities() > 0) else {
+    return issues;
+  };
+  let millis = |ts: i64| timestamp_to_signed_millis(ts, time_base).map_or_else(|| "unknown".to_string(), format_signed_millis);
+  out!("Continuous segments of the video stream: {}", segments.segments.len());
+  for (number, segment) in segments.segments.iter().enumerate() {
+    out!("  {}: DTS {} to {}, {} packets{}", number + 1, millis(segment.first_dts), millis(segment.last_dts),
+      format_with_commas(segment.packets as i64),
+      if segment.position >= 0 { format!(", from byte {}", format_with_commas(segment.position)) } else { String::new() });
+  }
+  issues
+}
+
+#[derive(Serialize)]
+pub struct ProgramStreamReport {
+  pub index: usize,
+  pub pid: i32,
+  pub kind: &'static str,
+  pub codec: String,
+}
+
+#[derive(Serialize)]
+pub struct ProgramReport {
+  pub number: i32,
+  pub pmt_pid: i32,
+  pub pcr_pid: i32,
+  pub streams: Vec<ProgramStreamReport>,
+}
+
+#[derive(Serialize)]
+pub struct PcrJumpReport {
+  pub pid: u16,
+  pub byte_offset: u64,
+  pub from_ms: i64,
+  pub to_ms: i64,
+  pub from_raw: Timestamp,
+  pub to_raw: Timestamp,
+  // Announced with discontinuity_indicator.
+  pub flagged: bool,
+}
+
+#[derive(Serialize)]
+pub struct HeaderScanReport {
+  pub packet_size: u64,
+  pub packets: u64,
+  pub sync_losses: u64,
+  pub discontinuity_flagged_packets: u64,
+  pub continuity_errors: u64,
+  pub pcr_discontinuities: u64,
+  // The first ones.
+  pub pcr_jumps: Vec<PcrJumpReport>,
+}
+
+#[derive(Serialize)]
+pub struct SegmentReport {
+  pub first_dts_ms: Option<i64>,
+  pub last_dts_ms: Option<i64>,
+  pub first_dts_raw: Timestamp,
+  pub last_dts_raw: Timestamp,
+  pub packets: u64,
+  pub byte_offset: Option<i64>,
+}
+
+#[derive(Serialize)]
+pub struct TransportReport {
+  pub programs: Vec<ProgramReport>,
+  // Null for URLs, stdin and --follow, or when the scan failed.
+  pub header_scan: Option<HeaderScanReport>,
+  // The continuous segments of the video stream; a single one without discontinuities.
+  pub segments: Vec<SegmentReport>,
+}
+
+pub fn to_report(transport: &Transport, segments: Option<&Segments>, time_base: Rational) -> TransportReport {
+  let millis = |ts: i64| timestamp_to_signed_millis(ts, time_base);
+  let pcr_time_base = Rational::new(1, PCR_CLOCK as i32);
+  TransportReport {
+    programs: transport.programs.iter().map(|program| ProgramReport {
+      number: program.number,
+      pmt_pid: program.pmt_pid,
+      pcr_pid: program.pcr_pid,
+      streams: program.streams.iter().map(|stream| ProgramStreamReport {
+        index: stream.index,
+        pid: stream.pid,
+        kind: stream.kind,
+        codec: stream.codec.clone(),
+      }).collect(),
+    }).collect(),
+    header_scan: transport.scan.as_ref().and_then(|scan| scan.as_ref().ok()).map(|scan| HeaderScanReport {
+      packet_size: scan.packet_size,
+      packets: scan.packets,
+      sync_losses: scan.sync_losses,
+      discontinuity_flagged_packets: scan.discontinuity_flagged,
+      continuity_errors: scan.continuity_errors,
+      pcr_discontinuities: scan.pcr_jump_count,
+      pcr_jumps: scan.pcr_jumps.iter().map(|jump| PcrJumpReport {
+        pid: jump.pid,
+        byte_offset: jump.offset,
+        from_ms: jump.from_ms,
+        to_ms: jump.to_ms,
+        from_raw: Timestamp::new(jump.from, pcr_time_base),
+        to_raw: Timestamp::new(jump.to, pcr_time_base),
+        flagged: jump.flagged,
+      }).collect(),
+    }),
+    segments: segments.map_or_else(Vec::new, |segments| segments.segments.iter().map(|segment| SegmentReport {
+      first_dts_ms: millis(segment.first_dts),
+      last_dts_ms: millis(segment.last_dts),
+      first_dts_raw: Timestamp::new(segment.first_dts, time_base),
+      last_dts_raw: Timestamp::new(segment.last_dts, time_base),
+      packets: segment.packets,
+      byte_offset: (segment.position >= 0).then_some(segment.position),
+    }).collect()),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  // A 188-byte packet of `pid`, with an adaptation field when there is a PCR or a discontinuity,
+  // and a payload of 0xFF bytes unless `payload` is false.
+  fn packet(pid: u16, counter: u8, pcr: Option<i64>, discontinuity: bool, payload: bool) -> Vec<u8> {
+    let adaptation = pcr.is_some() || discontinuity;
+    let control = u8::from(adaptation) << 1 | u8::from(payload);
+    let mut packet = vec![SYNC_BYTE, (pid >> 8) as u8 & 0x1F, pid as u8, control << 4 | counter, 0, 0];
+    if adaptation {
+      packet[5] = u8::from(discontinuity) << 7 | u8::from(pcr.is_some()) << 4;
+      if let Some(pcr) = pcr {
+        packet.extend([(pcr >> 25) as u8, (pcr >> 17) as u8, (pcr >> 9) as u8, (pcr >> 1) as u8, (pcr as u8 & 1) << 7 | 0x7E, 0]);
+      }
+      packet[4] = packet.len() as u8 - 5;
+    } else {
+      packet.truncate(4);
+    }
+    packet.resize(188, 0xFF);
+    packet
+  }
+
+  // The packets written to a file unique to the test, and scanned.
+  fn scan_file(name: &str, data: &[u8]) -> HeaderScan {
+    let path = std::env::temp_dir().join(format!("video_analyzer_transport_{}_{}.ts", std::process::id(), name));
+    std::fs::write(&path, data).unwrap();
+    let result = scan(&path);
+    let _ = std::fs::remove_file(&path);
+    result.unwrap()
+  }
+
+  #[test]
+  fn packet_size_and_start() {
+    let ts: Vec<u8> = (0..5).flat_map(|counter| packet(0x100, counter, None, false, true)).collect();
+    assert_eq!(detect_packets(&ts), Some((188, 0)));
+    assert_eq!(detect_packets(&[&[0x00; 5][..], &ts].concat()), Some((188, 5)));
+    let m2ts: Vec<u8> = (0..5).flat_map(|counter| [vec![0; 4], packet(0x100, counter, None, false, true)].concat()).collect();
+    assert_eq!(detect_packets(&m2ts), Some((192, 4)));
+    let parity: Vec<u8> = (0..5).flat_map(|counter| [packet(0x100, counter, None, false, true), vec![0; 16]].concat()).collect();
+    assert_eq!(detect_packets(&parity), Some((204, 0)));
+    // Fewer packets than SYNC_CHECKS.
+    assert_eq!(detect_packets(&ts[..188 * 3]), None);
+    assert_eq!(detect_packets(&[0xFF; 1024]), None);
+  }
+
+  #[test]
+  fn adaptation_fields() {
+    assert_eq!(adaptation_field(&packet(0x100, 0, None, true, true)), (None, true));
+    assert_eq!(adaptation_field(&packet(0x100, 0, Some(900_000), false, true)), (Some(900_000), false));
+    assert_eq!(adaptation_field(&packet(0x100, 0, Some(PCR_WRAP - 1), true, false)), (Some(PCR_WRAP - 1), true));
+    // An empty adaptation field, and a PCR flag in a field too short to hold the PCR.
+    let mut empty = packet(0x100, 0, None, false, true);
+    empty[4] = 0;
+    assert_eq!(adaptation_field(&empty), (None, false));
+    let mut short = packet(0x100, 0, Some(900_000), false, true);
+    short[4] = 6;
+    assert_eq!(adaptation_field(&short), (None, false));
+  }
+
+  #[test]
+  fn header_scan_of_188_byte_packets() {
+    let packets = [
+      packet(0x100, 0, None, false, true),
+      packet(0x100, 1, None, false, true),
+      // Sent twice, which the counter allows.
+      packet(0x100, 1, None, false, true),
+      // 2 is missing.
+      packet(0x100, 3, None, false, true),
+      packet(0x101, 0, Some(90_000), false, true),
+      // A second later, as far as a PCR may move.
+      packet(0x101, 1, Some(180_000), false, true),
+      // Backwards, without discontinuity_indicator.
+      packet(0x101, 2, Some(45_000), false, true),
+      packet(NULL_PID as u16, 7, None, false, true),
+      // Announced: any counter and any PCR.
+      packet(0x101, 9, Some(900_000), true, true),
+      // The PCR base wraps at 33 bits.
+      packet(0x102, 0, Some(PCR_WRAP - 9_000), false, true),
+      packet(0x102, 1, Some(9_000), false, true),
+      // No payload, so the counter doesn't advance.
+      packet(0x102, 1, None, true, false),
+    ];
+    let scan = scan_file("188", &packets.concat());
+    assert_eq!((scan.packet_size, scan.packets, scan.sync_losses), (188, 12, 0));
+    assert_eq!(scan.discontinuity_flagged, 2);
+    assert_eq!(scan.continuity_errors, 1);
+    assert_eq!(scan.continuity_examples, [(3 * 188, 0x100)]);
+    assert_eq!((scan.pcr_jump_count, scan.unannounced_pcr_jumps), (2, 1));
+    let backwards = &scan.pcr_jumps[0];
+    assert_eq!((backwards.pid, backwards.offset, backwards.from, backwards.to), (0x101, 6 * 188, 180_000, 45_000));
+    assert_eq!((backwards.from_ms, backwards.to_ms, backwards.flagged), (2_000, 500, false));
+    assert!(backwards.backwards());
+    let announced = &scan.pcr_jumps[1];
+    assert_eq!((announced.offset, announced.to_ms, announced.flagged), (8 * 188, 10_000, true));
+    assert!(!announced.backwards());
+  }
+
+  #[test]
+  fn header_scan_of_192_byte_packets_with_a_sync_loss() {
+    // M2TS: a 4-byte timestamp ahead of every packet, and 7 stray bytes after the fifth one. The
+    // last packet ends the file without a timestamp after it and still counts.
+    let mut data = Vec::new();
+    for counter in 0..8 {
+      if counter == 5 {
+        data.extend([0; 7]);
+      }
+      data.extend([0; 4]);
+      // 6 is missing.
+      data.extend(packet(0x100, if counter >= 6 { counter + 1 } else { counter }, None, false, true));
+    }
+    let scan = scan_file("192", &data);
+    assert_eq!((scan.packet_size, scan.packets, scan.sync_losses), (192, 8, 1));
+    assert_eq!(scan.continuity_errors, 1);
+    assert_eq!(scan.continuity_examples, [(4 + 6 * 192 + 7, 0x100)]);
+    assert_eq!(scan.pcr_jump_count, 0);
+  }
+
+  #[test]
+  fn scan_needs_sync_bytes() {
+    let path = std::env::temp_dir().join(format!("video_analyzer_transport_{}_nosync.ts", std::process::id()));
+    std::fs::write(&path, [0u8; 2048]).unwrap();
+    let result = scan(&path);
+    let _ = std::fs::remove_file(&path);
+    assert!(matches!(result, Err(err) if err.kind() == io::ErrorKind::InvalidData));
+  }
+}
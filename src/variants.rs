@@ -0,0 +1,167 @@
+// HLS and DASH inputs: the variant streams (renditions) of a master playlist or manifest. FFmpeg
+// exposes the video of every variant as a stream of its own. By default all of them are decoded in
+// the same pass over the packets and compared in a table; --variant keeps one and discards the
+// others, so the demuxer doesn't download their segments.
+
+use ffmpeg::media::Type;
+use ffmpeg::Discard;
+
+use crate::report::VariantReport;
+use crate::timestamp::Timestamp;
+use crate::{format_optional_millis, format_with_commas, BLUE_BOLD, RESET};
+
+pub struct Variant {
+  // Position among the variants, as --variant takes it.
+  pub number: usize,
+  pub stream_index: usize,
+  pub codec: String,
+  pub width: u32,
+  pub height: u32,
+  // Declared bandwidth in bits per second: BANDWIDTH of the HLS playlist, @bandwidth in DASH.
+  pub bandwidth: Option<u64>,
+}
+
+// What the analysis measured for one variant. Frame figures are None when it wasn't decoded.
+pub struct Figures {
+  pub frames: Option<i64>,
+  pub duration: Option<Timestamp>,
+  pub keyframe_intervals_ms: Vec<u64>,
+  pub bytes: u64,
+}
+
+impl Figures {
+  pub fn duration_ms(&self) -> Option<u64> {
+    self.duration?.millis()
+  }
+
+  pub fn longest_keyframe_interval_ms(&self) -> Option<u64> {
+    self.keyframe_intervals_ms.iter().copied().max()
+  }
+
+  pub fn average_keyframe_interval_ms(&self) -> Option<u64> {
+    let count = self.keyframe_intervals_ms.len() as u64;
+    (count > 0).then(|| self.keyframe_intervals_ms.iter().sum::<u64>() / count)
+  }
+
+  // Bits per second over the measured duration.
+  pub fn measured_bitrate(&self) -> Option<u64> {
+    self.duration_ms().filter(|&ms| ms > 0).map(|ms| self.bytes * 8 * 1000 / ms)
+  }
+}
+
+pub fn is_adaptive(ictx: &ffmpeg::format::context::Input) -> bool {
+  ictx.format().name().split(',').any(|name| name == "hls" || name == "dash")
+}
+
+// The variants of an adaptive input, in stream order. Empty for any other input.
+pub fn list(ictx: &ffmpeg::format::context::Input) -> Vec<Variant> {
+  if !is_adaptive(ictx) {
+    return Vec::new();
+  }
+  ictx.streams()
+    .filter(|stream| stream.parameters().medium() == Type::Video)
+    .enumerate()
+    .map(|(number, stream)| {
+      let parameters = stream.parameters();
+      let (width, height, bit_rate) = unsafe {
+        let p = parameters.as_ptr();
+        ((*p).width.max(0) as u32, (*p).height.max(0) as u32, (*p).bit_rate)
+      };
+      // The HLS demuxer puts the BANDWIDTH attribute in the stream metadata; DASH sets the bit rate.
+      let bandwidth = stream.metadata().get("variant_bitrate").and_then(|value| value.parse().ok())
+        .or((bit_rate > 0).then_some(bit_rate as u64));
+      Variant { number, stream_index: stream.index(), codec: parameters.id().name().to_string(), width, height, bandwidth }
+    })
+    .collect()
+}
+
+// The error for a --variant the input doesn't have, listing the ones it does.
+pub fn invalid_selection(variants: &[Variant], number: usize) -> String {
+  if variants.is_empty() {
+    return format!("--variant {} needs an HLS or DASH input with variant streams", number);
+  }
+  let listed: Vec<String> = variants.iter().map(|v| format!("{} ({}x{})", v.number, v.width, v.height)).collect();
+  format!("no variant {}; the input has variants {}", number, listed.join(", "))
+}
+
+// Keep only `variant`: its video stream and the other streams of its programs (an HLS variant's
+// audio renditions), or every non-video stream when the demuxer doesn't group them in programs.
+pub fn select(ictx: &mut ffmpeg::format::context::Input, variant: &Variant) {
+  let programs: Vec<Vec<usize>> = unsafe {
+    let ctx = ictx.as_ptr();
+    (0..(*ctx).nb_programs as usize)
+      .map(|p| {
+        let program = *(*ctx).programs.add(p);
+        std::slice::from_raw_parts((*program).stream_index, (*program).nb_stream_indexes as usize)
+          .iter().map(|&index| index as usize).collect()
+      })
+      .collect()
+  };
+  let kept: Vec<usize> = programs.into_iter().filter(|streams| streams.contains(&variant.stream_index)).flatten().collect();
+  let video: Vec<usize> = ictx.streams().filter(|s| s.parameters().medium() == Type::Video).map(|s| s.index()).collect();
+  for index in 0..ictx.nb_streams() as usize {
+    let discard = index != variant.stream_index && (video.contains(&index) || (!kept.is_empty() && !kept.contains(&index)));
+    if let (true, Some(mut stream)) = (discard, ictx.stream_mut(index)) {
+      unsafe { (*stream.as_mut_ptr()).discard = Discard::All.into() };
+    }
+  }
+}
+
+fn describe_bitrate(bps: Option<u64>) -> String {
+  bps.map_or_else(|| "unknown".to_string(), |bps| format!("{} kb/s", format_with_commas((bps / 1000) as i64)))
+}
+
+fn describe_interval(ms: Option<u64>) -> String {
+  ms.map_or_else(|| "unknown".to_string(), |ms| format!("{:.1}s", ms as f64 / 1000.0))
+}
+
+pub fn to_report(variant: &Variant, figures: &Figures) -> VariantReport {
+  VariantReport {
+    number: variant.number,
+    stream_index: variant.stream_index,
+    codec: variant.codec.clone(),
+    width: variant.width,
+    height: variant.height,
+    bandwidth: variant.bandwidth,
+    frame_count: figures.frames,
+    duration_ms: figures.duration_ms(),
+    duration_raw: figures.duration,
+    average_keyframe_interval_ms: figures.average_keyframe_interval_ms(),
+    longest_keyframe_interval_ms: figures.longest_keyframe_interval_ms(),
+    measured_bitrate: figures.measured_bitrate(),
+  }
+}
+
+// Print the variants section: one row per variant, then the roll-up over all of them.
+pub fn report(variants: &[Variant], figures: &[Figures], analyzed: usize) {
+  out!("{}{}Variants - {}", RESET, BLUE_BOLD, RESET);
+  out!("{:<3} {:>6} {:>11} {:<6} {:>14} {:>10} {:>12} {:>14} {:>14}", "#", "stream", "resolution", "codec",
+    "bandwidth", "frames", "duration", "key frames", "measured");
+  for (variant, figures) in variants.iter().zip(figures) {
+    out!("{:<3} {:>6} {:>11} {:<6} {:>14} {:>10} {:>12} {:>14} {:>14}{}", variant.number, variant.stream_index,
+      format!("{}x{}", variant.width, variant.height), variant.codec, describe_bitrate(variant.bandwidth),
+      figures.frames.map_or_else(|| "not decoded".to_string(), format_with_commas),
+      format_optional_millis(figures.duration_ms()),
+      format!("{} / {}", describe_interval(figures.average_keyframe_interval_ms()),
+        describe_interval(figures.longest_keyframe_interval_ms())),
+      describe_bitrate(figures.measured_bitrate()),
+      if variant.stream_index == analyzed { " (analyzed)" } else { "" });
+  }
+  out!("Key frames: average / longest interval");
+
+  let durations: Vec<u64> = figures.iter().filter_map(|f| f.duration_ms()).collect();
+  let bitrates: Vec<u64> = figures.iter().filter_map(Figures::measured_bitrate).collect();
+  let mut rollup = vec![format!("{} variants", variants.len())];
+  if let (Some(low), Some(high)) = (durations.iter().min(), durations.iter().max()) {
+    rollup.push(format!("durations {} to {} (spread {})", format_optional_millis(Some(*low)),
+      format_optional_millis(Some(*high)), format_optional_millis(Some(high - low))));
+  }
+  if let (Some(low), Some(high)) = (bitrates.iter().min(), bitrates.iter().max()) {
+    rollup.push(format!("measured {} to {}", describe_bitrate(Some(*low)), describe_bitrate(Some(*high))));
+  }
+  let longest = figures.iter().filter_map(Figures::longest_keyframe_interval_ms).max();
+  if longest.is_some() {
+    rollup.push(format!("longest key frame interval {}", describe_interval(longest)));
+  }
+  out!("Roll-up: {}", rollup.join(", "));
+}
@@ -0,0 +1,128 @@
+// One-paragraph summary of a report for people who won't read the tables, e.g.
+// "1h02m 1080p25 H.264 file, 4.9 Mbps average, stereo AAC. Key frames every 2.0s. 2 warnings: ...".
+
+use ffmpeg::media::Type;
+
+use crate::findings::{Finding, Severity};
+
+// Key metrics the first sentences are built from. Unknown values are left out of the text.
+#[derive(Default)]
+pub struct Summary {
+  pub duration_ms: Option<u64>,
+  pub height: u32,
+  pub fps: Option<f64>,
+  pub codec: String,
+  // Overall bit rate of the file in bits/s, 0 when unknown.
+  pub bit_rate: i64,
+  // e.g. "stereo AAC", None without an audio stream.
+  pub audio: Option<String>,
+  pub keyframe_interval_s: Option<f64>,
+}
+
+// Default cap on the verdict length in characters.
+pub const DEFAULT_MAX_LENGTH: usize = 400;
+
+// Friendly duration, e.g. "1h02m", "4m05s" or "12s".
+fn format_duration(ms: u64) -> String {
+  let secs = ms / 1000;
+  match (secs / 3600, (secs % 3600) / 60, secs % 60) {
+    (0, 0, s) => format!("{}s", s),
+    (0, m, s) => format!("{}m{:02}s", m, s),
+    (h, m, _) => format!("{}h{:02}m", h, m),
+  }
+}
+
+// Marketing-style codec names where they differ from FFmpeg's.
+fn codec_label(name: &str) -> String {
+  match name {
+    "h264" => "H.264".to_string(),
+    "hevc" => "HEVC".to_string(),
+    "mpeg2video" => "MPEG-2".to_string(),
+    "mpeg4" => "MPEG-4".to_string(),
+    "prores" => "ProRes".to_string(),
+    other => other.to_uppercase(),
+  }
+}
+
+// Describe the best audio stream, e.g. "stereo AAC" or "5.1 AC3".
+pub fn describe_audio(ictx: &ffmpeg::format::context::Input) -> Option<String> {
+  let stream = ictx.streams().best(Type::Audio)?;
+  let parameters = stream.parameters();
+  let channels = unsafe { (*parameters.as_ptr()).ch_layout.nb_channels };
+  let layout = match channels {
+    1 => "mono".to_string(),
+    2 => "stereo".to_string(),
+    6 => "5.1".to_string(),
+    8 => "7.1".to_string(),
+    n => format!("{}-channel", n),
+  };
+  Some(format!("{} {}", layout, codec_label(parameters.id().name())))
+}
+
+fn plural(count: usize, word: &str) -> String {
+  format!("{} {}{}", count, word, if count == 1 { "" } else { "s" })
+}
+
+// Assemble the verdict from the metrics and the findings, errors before warnings, keeping it
+// within `max_length` characters by dropping the findings that don't fit.
+pub fn render(summary: &Summary, findings: &[Finding], max_length: usize) -> String {
+  // "1h02m 1080p25 H.264 file, 4.9 Mbps average, stereo AAC."
+  let mut file = Vec::new();
+  if let Some(ms) = summary.duration_ms {
+    file.push(format_duration(ms));
+  }
+  if summary.height > 0 {
+    file.push(match summary.fps {
+      Some(fps) if (fps - fps.round()).abs() < 0.01 => format!("{}p{}", summary.height, fps.round()),
+      Some(fps) => format!("{}p{:.2}", summary.height, fps),
+      None => format!("{}p", summary.height),
+    });
+  }
+  file.push(codec_label(&summary.codec));
+  let mut first = format!("{} file", file.join(" "));
+  if summary.bit_rate > 0 {
+    first.push_str(&format!(", {:.1} Mbps average", summary.bit_rate as f64 / 1_000_000.0));
+  }
+  if let Some(audio) = &summary.audio {
+    first.push_str(&format!(", {}", audio));
+  }
+  let mut sentences = vec![format!("{}.", first)];
+  if let Some(interval) = summary.keyframe_interval_s {
+    sentences.push(format!("Key frames every {:.1}s.", interval));
+  }
+
+  // One sentence per severity, most severe first; informational findings are left out.
+  let mut remaining = max_length.saturating_sub(sentences.join(" ").len());
+  let mut dropped = 0;
+  for (severity, word) in [(Severity::Error, "error"), (Severity::Warning, "warning")] {
+    let messages: Vec<&str> = findings.iter().filter(|f| f.severity() == severity).map(|f| f.message.as_str()).collect();
+    if messages.is_empty() {
+      continue;
+    }
+    let mut sentence = plural(messages.len(), word);
+    for (i, message) in messages.iter().enumerate() {
+      let part = format!("{} {}", if i == 0 { ":" } else { ";" }, message);
+      if sentence.len() + part.len() + 2 > remaining {
+        dropped += messages.len() - i;
+        break;
+      }
+      sentence.push_str(&part);
+    }
+    sentence.push('.');
+    remaining = remaining.saturating_sub(sentence.len() + 1);
+    sentences.push(sentence);
+  }
+  if findings.iter().all(|f| f.severity() > Severity::Warning) {
+    sentences.push("No issues found.".to_string());
+  }
+  if dropped > 0 {
+    sentences.push(format!("({} more in the report.)", dropped));
+  }
+  let verdict = sentences.join(" ");
+  // The metrics alone can exceed a very small cap; cut on a character boundary then.
+  if verdict.chars().count() > max_length {
+    let cut: String = verdict.chars().take(max_length.saturating_sub(1)).collect();
+    return format!("{}…", cut);
+  }
+  verdict
+}
@@ -0,0 +1,159 @@
+// --all-video-streams: the video streams besides the analyzed one, decoded in the same pass over
+// the packets, for multi-angle files where every stream matters, and for the variants of HLS and
+// DASH inputs. Each gets the frame counts of the main analysis; the detailed checks stay with the
+// analyzed stream.
+
+use ffmpeg::codec::threading;
+use ffmpeg::media::Type;
+use ffmpeg::Rational;
+
+use crate::report::VideoStreamReport;
+use crate::timestamp::{self, Timestamp};
+use crate::{format_with_commas, BLUE_BOLD, RESET};
+
+pub struct VideoStream {
+  pub index: usize,
+  pub codec: String,
+  pub width: u32,
+  pub height: u32,
+  pub time_base: Rational,
+  pub frame_count: i64,
+  pub keyframe_count: i64,
+  // Timestamps (stream time base) of every key frame, for the key frame intervals.
+  pub keyframe_timestamps: Vec<i64>,
+  // Index of the last key frame among the decoded frames.
+  pub last_frame: i64,
+  // Lowest and highest best-effort frame timestamps, for the measured duration.
+  pub first_frame_timestamp: Option<i64>,
+  pub last_frame_timestamp: Option<i64>,
+  // Packets the decoder rejected; decoding goes on past them.
+  pub decode_errors: u64,
+  // False when FFmpeg has no decoder for the codec.
+  pub decodable: bool,
+  decoder: Option<ffmpeg::decoder::Video>,
+  // Reused for every frame of this stream.
+  frame: ffmpeg::util::frame::video::Video,
+}
+
+impl VideoStream {
+  // Duration spanned by the decoded frames' timestamps.
+  pub fn measured_duration(&self) -> Option<Timestamp> {
+    let (first, last) = (self.first_frame_timestamp?, self.last_frame_timestamp?);
+    Some(Timestamp::new(last - first, self.time_base))
+  }
+
+  // Count the frames the decoder has ready.
+  fn receive(&mut self) {
+    let Some(decoder) = self.decoder.as_mut() else {
+      return;
+    };
+    while decoder.receive_frame(&mut self.frame).is_ok() {
+      if self.frame.is_key() {
+        self.last_frame = self.frame_count;
+        self.keyframe_count += 1;
+        self.keyframe_timestamps.extend(self.frame.timestamp());
+      }
+      if let Some(ts) = self.frame.timestamp() {
+        self.first_frame_timestamp = Some(self.first_frame_timestamp.map_or(ts, |t| t.min(ts)));
+        self.last_frame_timestamp = Some(self.last_frame_timestamp.map_or(ts, |t| t.max(ts)));
+      }
+      self.frame_count += 1;
+    }
+  }
+
+  // Decode a packet of this stream.
+  pub fn decode(&mut self, packet: &ffmpeg::Packet) {
+    let Some(decoder) = self.decoder.as_mut() else {
+      return;
+    };
+    if decoder.send_packet(packet).is_err() {
+      self.decode_errors += 1;
+      return;
+    }
+    self.receive();
+  }
+
+  // Drain the frames the decoder still holds at the end of the input and release it.
+  pub fn finish(&mut self) {
+    if self.decoder.as_mut().is_some_and(|decoder| decoder.send_eof().is_ok()) {
+      self.receive();
+    }
+    self.decoder = None;
+  }
+}
+
+// Open a decoder for every video stream except `skip`, all with the same threading configuration.
+pub fn open(ictx: &ffmpeg::format::context::Input, skip: usize, threading_config: threading::Config) -> Vec<VideoStream> {
+  ictx
+    .streams()
+    .filter(|stream| stream.index() != skip && stream.parameters().medium() == Type::Video)
+    .map(|stream| {
+      let parameters = stream.parameters();
+      let (width, height) = unsafe {
+        let p = parameters.as_ptr();
+        ((*p).width.max(0) as u32, (*p).height.max(0) as u32)
+      };
+      // The threading has to be set before the decoder is opened to take effect.
+      let decoder = ffmpeg::codec::context::Context::from_parameters(parameters.clone())
+        .and_then(|mut context| {
+          context.set_threading(threading_config);
+          context.decoder().video()
+        })
+        .ok();
+      VideoStream {
+        index: stream.index(),
+        codec: parameters.id().name().to_string(),
+        width,
+        height,
+        time_base: stream.time_base(),
+        frame_count: 0,
+        keyframe_count: 0,
+        keyframe_timestamps: Vec::new(),
+        last_frame: 0,
+        first_frame_timestamp: None,
+        last_frame_timestamp: None,
+        decode_errors: 0,
+        decodable: decoder.is_some(),
+        decoder,
+        frame: ffmpeg::util::frame::video::Video::empty(),
+      }
+    })
+    .collect()
+}
+
+// The figures of one stream, with the DTS of its last packet.
+pub fn to_report(stream: &VideoStream, highest_dts: Option<i64>) -> VideoStreamReport {
+  VideoStreamReport {
+    index: stream.index,
+    codec: stream.codec.clone(),
+    width: stream.width,
+    height: stream.height,
+    frame_count: stream.decodable.then_some(stream.frame_count),
+    keyframe_count: stream.decodable.then_some(stream.keyframe_count),
+    last_keyframe_index: stream.decodable.then_some(stream.last_frame),
+    decode_errors: stream.decodable.then_some(stream.decode_errors),
+    decoded_duration_ms: stream.measured_duration().and_then(|d| d.millis()),
+    last_packet_ms: highest_dts.and_then(|dts| Timestamp::new(dts, stream.time_base).signed_millis()),
+    decoded_duration_raw: stream.measured_duration(),
+    last_packet_raw: highest_dts.map(|dts| Timestamp::new(dts, stream.time_base)),
+  }
+}
+
+// Print one block per stream with the figures calculated from its frames.
+pub fn report(streams: &[VideoStream], stream_highest_dts: &[Option<i64>], verbose: bool) {
+  for stream in streams {
+    out!("{}{}Calculated from the frames of stream #{} ({}, {}x{}) - {}", RESET, BLUE_BOLD, stream.index,
+      stream.codec, stream.width, stream.height, RESET);
+    if !stream.decodable {
+      out!("Not decoded: no decoder for {}", stream.codec);
+      continue;
+    }
+    let last_packet = stream_highest_dts.get(stream.index).copied().flatten().map(|dts| Timestamp::new(dts, stream.time_base));
+    out!("Last key frame id: {}", format_with_commas(stream.last_frame));
+    out!("Frames count: {}", format_with_commas(stream.frame_count));
+    out!("Key frames: {}", format_with_commas(stream.keyframe_count));
+    out!("Decode errors: {}", format_with_commas(stream.decode_errors as i64));
+    out!("Last Frame Time: {}", timestamp::describe_optional(last_packet, verbose));
+    out!("Measured Duration: {}", timestamp::describe_optional(stream.measured_duration(), verbose));
+  }
+}
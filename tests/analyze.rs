@@ -0,0 +1,53 @@
+// The public API of the library, as other programs call it.
+
+extern crate ffmpeg_next as ffmpeg;
+
+use std::path::Path;
+
+use video_analyzer::selftest::{synthesize, FRAMES};
+use video_analyzer::{analyze, analyze_captured, AnalyzeError, AnalyzeOptions};
+
+// The self-test's pattern runs at 25 fps.
+const FRAME_MS: u64 = 40;
+
+#[test]
+fn synthesized_clip_is_analyzed() {
+  ffmpeg::init().unwrap();
+  let path = std::env::temp_dir().join(format!("video_analyzer_analyze_{}.mp4", std::process::id()));
+  synthesize(&path).unwrap();
+  let result = analyze(&path, &AnalyzeOptions::default());
+  let _ = std::fs::remove_file(&path);
+  let report = result.unwrap();
+
+  assert_eq!(report.video_stream_index, Some(0));
+  assert_eq!(report.frame_count, Some(FRAMES));
+  // The last frame starts one frame before the end of the file.
+  let span_ms = report.decoded_duration_ms.unwrap();
+  assert!(span_ms.abs_diff((FRAMES as u64 - 1) * FRAME_MS) <= 1, "{span_ms}");
+  let duration_ms = report.container_duration_ms.unwrap();
+  assert!(duration_ms.abs_diff(FRAMES as u64 * FRAME_MS) <= 1, "{duration_ms}");
+}
+
+#[test]
+fn missing_file_is_an_open_error() {
+  let result = analyze(Path::new("/nonexistent/video_analyzer/missing.mp4"), &AnalyzeOptions::default());
+  assert!(matches!(result, Err(AnalyzeError::OpenError(_))));
+}
+
+#[test]
+fn captured_analysis_returns_the_error_and_no_report() {
+  let options = AnalyzeOptions { print_report: true, ..AnalyzeOptions::default() };
+  let (result, text) = analyze_captured(Path::new("/nonexistent/video_analyzer/missing.mkv"), &options);
+  assert!(matches!(result, Err(AnalyzeError::OpenError(_))));
+  assert!(text.is_empty(), "{text}");
+}
+
+#[cfg(unix)]
+#[test]
+fn paths_must_be_utf8() {
+  use std::ffi::OsStr;
+  use std::os::unix::ffi::OsStrExt;
+
+  let path = Path::new(OsStr::from_bytes(b"/tmp/\xff.mp4"));
+  assert!(matches!(analyze(path, &AnalyzeOptions::default()), Err(AnalyzeError::InvalidArgument(_))));
+}
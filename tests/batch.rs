@@ -0,0 +1,44 @@
+// A batch killed part way: with --json-lines, every file finished before the kill is on stdout as
+// a complete line of JSON.
+
+extern crate ffmpeg_next as ffmpeg;
+
+use std::io::{BufRead, BufReader, Read};
+use std::process::{Command, Stdio};
+
+#[test]
+fn killed_batch_leaves_parseable_lines() {
+  ffmpeg::init().unwrap();
+  let path = std::env::temp_dir().join(format!("video_analyzer_batch_{}.mp4", std::process::id()));
+  video_analyzer::selftest::synthesize(&path).unwrap();
+  let paths = vec![path.to_str().unwrap().to_string(); 200];
+
+  let mut child = Command::new(env!("CARGO_BIN_EXE_video_analyzer"))
+    .args(["--quiet", "--no-decode", "--json-lines"])
+    .args(&paths)
+    .stdout(Stdio::piped())
+    .stderr(Stdio::null())
+    .spawn()
+    .unwrap();
+  let mut stdout = BufReader::new(child.stdout.take().unwrap());
+  let mut lines = Vec::new();
+  for _ in 0..2 {
+    let mut line = String::new();
+    stdout.read_line(&mut line).unwrap();
+    lines.push(line);
+  }
+  child.kill().unwrap();
+  child.wait().unwrap();
+  let mut rest = String::new();
+  stdout.read_to_string(&mut rest).unwrap();
+  let _ = std::fs::remove_file(&path);
+
+  // A line the kill cut short has no newline yet.
+  lines.extend(rest.split_inclusive('\n').filter(|line| line.ends_with('\n')).map(str::to_string));
+  assert!(lines.len() >= 2 && lines.len() < paths.len(), "{} lines", lines.len());
+  for line in &lines {
+    let report: serde_json::Value = serde_json::from_str(line).unwrap();
+    assert_eq!(report["path"], paths[0].as_str());
+    assert!(report["frame_count"].as_i64().is_some_and(|frames| frames > 0), "{line}");
+  }
+}
@@ -0,0 +1,67 @@
+// Inputs without a video stream, written at test time so no binary fixture is checked in. Each
+// test file uses a part of them.
+#![allow(dead_code)]
+
+use std::path::{Path, PathBuf};
+
+use ffmpeg::format::Sample;
+use ffmpeg::{ChannelLayout, Rational};
+
+// Length of the audio-only fixture.
+pub const AUDIO_SECONDS: i64 = 2;
+const RATE: i32 = 8000;
+const SAMPLES_PER_FRAME: usize = 800;
+
+// A path in the temp directory unique to this test process.
+pub fn temp_path(name: &str) -> PathBuf {
+  std::env::temp_dir().join(format!("video_analyzer_{}_{}", std::process::id(), name))
+}
+
+// Move every packet the encoder has ready into the muxer.
+fn write_encoded(encoder: &mut ffmpeg::encoder::Audio, octx: &mut ffmpeg::format::context::Output,
+  stream_time_base: Rational) -> Result<(), ffmpeg::Error> {
+  let mut packet = ffmpeg::Packet::empty();
+  while encoder.receive_packet(&mut packet).is_ok() {
+    packet.set_stream(0);
+    packet.rescale_ts(Rational::new(1, RATE), stream_time_base);
+    packet.write_interleaved(octx)?;
+  }
+  Ok(())
+}
+
+// Write AUDIO_SECONDS of silence as 16-bit mono PCM, in whatever container the extension names.
+pub fn audio_only(path: &Path) -> Result<(), ffmpeg::Error> {
+  let codec = ffmpeg::encoder::find(ffmpeg::codec::Id::PCM_S16LE).ok_or(ffmpeg::Error::EncoderNotFound)?;
+  let mut octx = ffmpeg::format::output(&path)?;
+  let mut encoder = ffmpeg::codec::context::Context::new().encoder().audio()?;
+  encoder.set_rate(RATE);
+  encoder.set_format(Sample::I16(ffmpeg::format::sample::Type::Packed));
+  encoder.set_channel_layout(ChannelLayout::MONO);
+  encoder.set_channels(1);
+  encoder.set_time_base(Rational::new(1, RATE));
+  let mut encoder = encoder.open_as(codec)?;
+
+  let mut stream = octx.add_stream(codec)?;
+  stream.set_parameters(&encoder);
+  stream.set_time_base(Rational::new(1, RATE));
+  octx.write_header()?;
+  let stream_time_base = octx.stream(0).map_or(Rational::new(1, RATE), |s| s.time_base());
+
+  let frames = AUDIO_SECONDS * RATE as i64 / SAMPLES_PER_FRAME as i64;
+  for index in 0..frames {
+    let mut frame = ffmpeg::frame::Audio::new(encoder.format(), SAMPLES_PER_FRAME, ChannelLayout::MONO);
+    frame.set_rate(RATE as u32);
+    frame.data_mut(0).fill(0);
+    frame.set_pts(Some(index * SAMPLES_PER_FRAME as i64));
+    encoder.send_frame(&frame)?;
+    write_encoded(&mut encoder, &mut octx, stream_time_base)?;
+  }
+  encoder.send_eof()?;
+  write_encoded(&mut encoder, &mut octx, stream_time_base)?;
+  octx.write_trailer()
+}
+
+// A container that opens but declares no stream at all: an FFmetadata file with only a title.
+pub fn empty_container(path: &Path) -> std::io::Result<()> {
+  std::fs::write(path, ";FFMETADATA1\ntitle=no streams\n")
+}
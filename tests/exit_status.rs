@@ -0,0 +1,35 @@
+// The exit status of the binary, which scripts use to tell the outcomes apart without parsing the
+// output; see exit.rs.
+
+extern crate ffmpeg_next as ffmpeg;
+
+mod common;
+
+use std::process::{Command, Stdio};
+
+fn exit_code(args: &[&str]) -> Option<i32> {
+  Command::new(env!("CARGO_BIN_EXE_video_analyzer"))
+    .arg("--quiet")
+    .args(args)
+    .stdout(Stdio::null())
+    .stderr(Stdio::null())
+    .status()
+    .unwrap()
+    .code()
+}
+
+#[test]
+fn missing_file_is_an_open_failure() {
+  assert_eq!(exit_code(&["/nonexistent/video_analyzer/missing.mp4"]), Some(2));
+}
+
+#[test]
+fn audio_only_is_a_success_unless_video_is_required() {
+  ffmpeg::init().unwrap();
+  let path = common::temp_path("exit_status.wav");
+  common::audio_only(&path).unwrap();
+  let path_arg = path.to_str().unwrap();
+  let codes = (exit_code(&[path_arg]), exit_code(&["--require-video", path_arg]));
+  let _ = std::fs::remove_file(&path);
+  assert_eq!(codes, (Some(0), Some(3)));
+}
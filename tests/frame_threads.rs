@@ -0,0 +1,23 @@
+// Frame threads hold back as many frames as there are threads until the decoder is drained at the
+// end of the input; every one of them must still be counted.
+
+extern crate ffmpeg_next as ffmpeg;
+
+use video_analyzer::selftest::{synthesize, FRAMES};
+use video_analyzer::{analyze, AnalyzeOptions};
+
+const THREADS: i32 = 8;
+
+#[test]
+fn frame_threads_count_every_frame() {
+  ffmpeg::init().unwrap();
+  let path = std::env::temp_dir().join(format!("video_analyzer_frame_threads_{}.mp4", std::process::id()));
+  synthesize(&path).unwrap();
+  let options = AnalyzeOptions { threads_number_input: THREADS, ..AnalyzeOptions::default() };
+  let result = analyze(&path, &options);
+  let _ = std::fs::remove_file(&path);
+  let report = result.unwrap();
+
+  assert_eq!(report.decoder_threads, Some(THREADS as usize));
+  assert_eq!(report.frame_count, Some(FRAMES));
+}
@@ -0,0 +1,39 @@
+// Files without a video stream: an audio-only file is reported on without the frame figures, and
+// a container without any stream is the only hard failure.
+
+extern crate ffmpeg_next as ffmpeg;
+
+mod common;
+
+use video_analyzer::{analyze, AnalyzeError, AnalyzeOptions};
+
+#[test]
+fn audio_only_is_reported_without_video() {
+  ffmpeg::init().unwrap();
+  let path = common::temp_path("no_video.wav");
+  common::audio_only(&path).unwrap();
+  let result = analyze(&path, &AnalyzeOptions::default());
+  let required = analyze(&path, &AnalyzeOptions { require_video: true, ..AnalyzeOptions::default() });
+  let _ = std::fs::remove_file(&path);
+  let report = result.unwrap();
+
+  assert_eq!(report.video_stream_index, None);
+  assert_eq!(report.frame_count, None);
+  assert_eq!(report.streams.len(), 1);
+  assert_eq!(report.streams[0].kind, "audio");
+  let codes: Vec<&str> = report.findings.iter().map(|finding| finding.code).collect();
+  assert!(codes.contains(&"NO_VIDEO_STREAM"), "{codes:?}");
+  let duration_ms = report.container_duration_ms.unwrap();
+  assert!(duration_ms.abs_diff(common::AUDIO_SECONDS as u64 * 1000) <= 1, "{duration_ms}");
+  assert!(matches!(required, Err(AnalyzeError::NoVideoStreamError)));
+}
+
+#[test]
+fn empty_container_is_an_error() {
+  ffmpeg::init().unwrap();
+  let path = common::temp_path("no_streams.ffmeta");
+  common::empty_container(&path).unwrap();
+  let result = analyze(&path, &AnalyzeOptions::default());
+  let _ = std::fs::remove_file(&path);
+  assert!(matches!(result, Err(AnalyzeError::NoStreamsError)));
+}
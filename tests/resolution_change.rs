@@ -0,0 +1,23 @@
+// A resolution switch mid-stream: every frame decodes across it, and the switch is reported once,
+// at the first frame of the second size.
+
+extern crate ffmpeg_next as ffmpeg;
+
+use video_analyzer::selftest::{synthesize_resolution_change, SEGMENT_FRAMES, SIZES};
+use video_analyzer::{analyze, AnalyzeOptions};
+
+#[test]
+fn resolution_change_is_reported_at_its_frame() {
+  ffmpeg::init().unwrap();
+  let path = std::env::temp_dir().join(format!("video_analyzer_resolution_change_{}.ts", std::process::id()));
+  synthesize_resolution_change(&path).unwrap();
+  let result = analyze(&path, &AnalyzeOptions::default());
+  let _ = std::fs::remove_file(&path);
+  let report = result.unwrap();
+
+  assert_eq!(report.frame_count, Some(SEGMENT_FRAMES * SIZES.len() as i64));
+  let changes: Vec<_> = report.resolution.unwrap().changes.iter()
+    .map(|change| ((change.from.width, change.from.height), (change.to.width, change.to.height), change.frame))
+    .collect();
+  assert_eq!(changes, [(SIZES[0], SIZES[1], SEGMENT_FRAMES)]);
+}
@@ -0,0 +1,11 @@
+// The self-test as part of `cargo test`: it encodes its own asset with FFmpeg's native MPEG-4
+// encoder, so the figures of its report are checked in CI and not only when someone runs
+// `video_analyzer self-test`.
+
+extern crate ffmpeg_next as ffmpeg;
+
+#[test]
+fn self_test_passes() {
+  ffmpeg::init().unwrap();
+  assert!(video_analyzer::selftest::run().unwrap());
+}